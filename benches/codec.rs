@@ -0,0 +1,156 @@
+//! Benchmarks for the codec hot paths.
+//!
+//! Run with `cargo bench`. Inputs are deterministic so numbers are comparable
+//! across runs: the real `languages/english/payload.yaml` wordlist, payloads
+//! filled from a seeded RNG, and cover text built by wrapping each payload word
+//! in a fixed cover sentence. Grammar-check benchmarks need the nlprule model
+//! files (see `GrammarChecker::from_language`) and are skipped without them.
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/grammar.rs"]
+mod grammar;
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use glossia::index::WordIndex;
+use glossia::types::Pos;
+use glossia::{codec, decode};
+use grammar::Grammar;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::collections::{HashMap, HashSet};
+
+/// Round-trip payload sizes: 1 KB, 100 KB, 1 MB
+const SIZES: [usize; 3] = [1 << 10, 100 << 10, 1 << 20];
+
+/// Longest derivation accepted when sampling sentences
+const MAX_SLOTS: usize = 40;
+
+fn wordlist() -> Vec<String> {
+    let yaml = std::fs::read_to_string("languages/english/payload.yaml").expect("read payload.yaml");
+    let data: HashMap<String, HashMap<String, f64>> = serde_yaml::from_str(&yaml).expect("parse payload.yaml");
+    let mut words: Vec<String> = data.into_keys().collect();
+    words.sort();
+    words
+}
+
+fn payload(len: usize) -> Vec<u8> {
+    let mut rng = StdRng::seed_from_u64(len as u64);
+    (0..len).map(|_| rng.gen()).collect()
+}
+
+fn cover_text(words: &[String]) -> String {
+    words
+        .iter()
+        .map(|w| format!("The quiet {} waited by the door.", w))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn bench_bit_packing(c: &mut Criterion) {
+    let list = wordlist();
+    let bytes = payload(SIZES[0]);
+    let words = codec::encode_bytes(&bytes, &list).unwrap();
+
+    let mut group = c.benchmark_group("bit_packing");
+    group.throughput(Throughput::Bytes(bytes.len() as u64));
+    group.bench_function("encode_bytes", |b| b.iter(|| codec::encode_bytes(black_box(&bytes), &list)));
+    group.bench_function("decode_bytes", |b| b.iter(|| codec::decode_bytes(black_box(&words), &list)));
+    group.finish();
+}
+
+fn bench_word_lookup(c: &mut Criterion) {
+    let list = wordlist();
+    let set: HashSet<String> = list.iter().cloned().collect();
+    let index = WordIndex::shared(&list).unwrap();
+    let probes: Vec<String> = list.iter().step_by(7).cloned().collect();
+
+    let mut group = c.benchmark_group("word_lookup");
+    group.throughput(Throughput::Elements(probes.len() as u64));
+    group.bench_function("index_get", |b| {
+        b.iter(|| probes.iter().filter_map(|w| index.get(black_box(w))).count())
+    });
+    group.bench_function("hash_set", |b| {
+        b.iter(|| probes.iter().filter(|w| set.contains(black_box(w.as_str()))).count())
+    });
+    group.bench_function("normalize_token", |b| {
+        let mut buf = String::new();
+        b.iter(|| {
+            for w in &probes {
+                decode::normalize_token_into(black_box(w), &mut buf);
+            }
+        })
+    });
+    group.finish();
+}
+
+fn bench_sentence_generation(c: &mut Criterion) {
+    let grammar = Grammar::default().expect("load body grammar");
+    let compiled = grammar.compile();
+
+    let mut group = c.benchmark_group("sentence_generation");
+    group.bench_function("sample_derivation", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| grammar.sample_derivation(&mut rng, "S", MAX_SLOTS))
+    });
+    group.bench_function("compiled_sample", |b| {
+        let mut rng = StdRng::seed_from_u64(1);
+        b.iter(|| compiled.sample(&mut rng, "S", MAX_SLOTS))
+    });
+    group.bench_function("sequence_table_k12", |b| {
+        b.iter(|| compiled.enumerate_sequences_with_probability("S", black_box(12)))
+    });
+
+    // Retry loop shape of the generator: draw candidates until one passes the checker
+    #[cfg(feature = "grammar")]
+    if let Ok(checker) = glossia::GrammarChecker::global(glossia::Language::English) {
+        let sentences = [
+            "The quiet abandon waited by the door.",
+            "A quiet ability wait by the door.",
+            "The loud absorb were under a table.",
+        ];
+        group.bench_function("grammar_check_retries", |b| {
+            b.iter(|| sentences.iter().position(|s| checker.is_correct(black_box(s))))
+        });
+    }
+    group.finish();
+}
+
+fn bench_round_trip(c: &mut Criterion) {
+    let list = wordlist();
+    let mut group = c.benchmark_group("round_trip");
+    group.sample_size(10);
+
+    for size in SIZES {
+        let bytes = payload(size);
+        let text = cover_text(&codec::encode_bytes(&bytes, &list).unwrap());
+        group.throughput(Throughput::Bytes(size as u64));
+
+        group.bench_with_input(BenchmarkId::new("encode", size), &bytes, |b, bytes| {
+            b.iter(|| cover_text(&codec::encode_bytes(bytes, &list).unwrap()))
+        });
+        group.bench_with_input(BenchmarkId::new("decode_text_into", size), &text, |b, text| {
+            let mut out = Vec::with_capacity(size);
+            b.iter(|| {
+                out.clear();
+                codec::decode_text_into(text, &list, &mut out).unwrap()
+            })
+        });
+        group.bench_with_input(BenchmarkId::new("extract_and_decode", size), &text, |b, text| {
+            let set: HashSet<String> = list.iter().cloned().collect();
+            b.iter(|| {
+                let words = decode::extract_payload_words(text, &set);
+                codec::decode_bytes(&words, &list).unwrap()
+            })
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_bit_packing,
+    bench_word_lookup,
+    bench_sentence_generation,
+    bench_round_trip
+);
+criterion_main!(benches);
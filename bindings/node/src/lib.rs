@@ -0,0 +1,158 @@
+//! Native Node.js addon for the glossia codec (napi-rs).
+//!
+//! Exposes a `Glossia` class configured once with the framing options and an
+//! optional `payload.yaml` path (the embedded English wordlist otherwise).
+//! `encode` and `decode` run on the libuv thread pool and return promises;
+//! `encodeSync` and `decodeSync` are there for scripts. Cover sentences are
+//! produced by the `glossia` CLI; this addon packs and unpacks carrier words.
+
+use glossia::payload::{self, PayloadOptions};
+use napi::bindgen_prelude::*;
+use napi::{Env, Task};
+use napi_derive::napi;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::Arc;
+
+/// Options accepted by the `Glossia` constructor.
+#[napi(object)]
+#[derive(Default)]
+pub struct GlossiaOptions {
+    /// Append a checksum word when encoding; verify and strip it when decoding
+    pub checksum: Option<bool>,
+    /// Whiten the payload bytes (keyed by `passphrase` if given)
+    pub whiten: Option<bool>,
+    pub passphrase: Option<String>,
+    /// With `whiten`: add a key-committing tag, so only `passphrase` decodes
+    pub commit: Option<bool>,
+    /// Path to a `payload.yaml` wordlist instead of the embedded English one
+    pub wordlist_path: Option<String>,
+    /// Seed for reproducible output (whitening nonces)
+    pub seed: Option<i64>,
+}
+
+#[napi]
+pub struct Glossia {
+    wordlist: Arc<Vec<String>>,
+    options: PayloadOptions,
+    seed: Option<u64>,
+}
+
+fn to_napi(e: anyhow::Error) -> Error {
+    Error::from_reason(format!("{:#}", e))
+}
+
+#[napi]
+impl Glossia {
+    #[napi(constructor)]
+    pub fn new(options: Option<GlossiaOptions>) -> Result<Self> {
+        let options = options.unwrap_or_default();
+        let wordlist = match &options.wordlist_path {
+            Some(path) => {
+                let yaml = std::fs::read_to_string(path)
+                    .map_err(|e| Error::from_reason(format!("Failed to read wordlist '{}': {}", path, e)))?;
+                payload::wordlist_from_yaml(&yaml).map_err(to_napi)?
+            }
+            None => glossia::embedded::wordlist().to_vec(),
+        };
+        Ok(Self {
+            wordlist: Arc::new(wordlist),
+            options: PayloadOptions {
+                checksum: options.checksum.unwrap_or(false),
+                whiten: options.whiten.unwrap_or(false),
+                passphrase: options.passphrase,
+                commit: options.commit.unwrap_or(false),
+            },
+            seed: options.seed.map(|s| s as u64),
+        })
+    }
+
+    /// Carrier words for `payload`, resolved off the main thread.
+    #[napi(ts_return_type = "Promise<string[]>")]
+    pub fn encode(&self, payload: Buffer) -> AsyncTask<EncodeTask> {
+        AsyncTask::new(EncodeTask { job: self.job(), bytes: payload.to_vec() })
+    }
+
+    /// Payload bytes recovered from cover `text`, resolved off the main thread.
+    #[napi(ts_return_type = "Promise<Buffer>")]
+    pub fn decode(&self, text: String) -> AsyncTask<DecodeTask> {
+        AsyncTask::new(DecodeTask { job: self.job(), text })
+    }
+
+    #[napi]
+    pub fn encode_sync(&self, payload: Buffer) -> Result<Vec<String>> {
+        self.job().encode(&payload)
+    }
+
+    #[napi]
+    pub fn decode_sync(&self, text: String) -> Result<Buffer> {
+        self.job().decode(&text).map(Buffer::from)
+    }
+
+    /// Number of carrier words a payload of `bytes` bytes needs.
+    #[napi]
+    pub fn capacity(&self, bytes: u32) -> u32 {
+        payload::carrier_words(bytes as usize, self.wordlist.len(), &self.options) as u32
+    }
+
+    fn job(&self) -> Job {
+        Job { wordlist: Arc::clone(&self.wordlist), options: self.options.clone(), seed: self.seed }
+    }
+}
+
+/// Everything a worker thread needs, detached from the JS object.
+struct Job {
+    wordlist: Arc<Vec<String>>,
+    options: PayloadOptions,
+    seed: Option<u64>,
+}
+
+impl Job {
+    fn encode(&self, bytes: &[u8]) -> Result<Vec<String>> {
+        let mut rng = match self.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        payload::encode_payload(&mut rng, bytes, &self.wordlist, &self.options).map_err(to_napi)
+    }
+
+    fn decode(&self, text: &str) -> Result<Vec<u8>> {
+        payload::decode_payload(text, &self.wordlist, &self.options).map_err(to_napi)
+    }
+}
+
+pub struct EncodeTask {
+    job: Job,
+    bytes: Vec<u8>,
+}
+
+impl Task for EncodeTask {
+    type Output = Vec<String>;
+    type JsValue = Vec<String>;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.job.encode(&self.bytes)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(output)
+    }
+}
+
+pub struct DecodeTask {
+    job: Job,
+    text: String,
+}
+
+impl Task for DecodeTask {
+    type Output = Vec<u8>;
+    type JsValue = Buffer;
+
+    fn compute(&mut self) -> Result<Self::Output> {
+        self.job.decode(&self.text)
+    }
+
+    fn resolve(&mut self, _env: Env, output: Self::Output) -> Result<Self::JsValue> {
+        Ok(Buffer::from(output))
+    }
+}
@@ -0,0 +1,88 @@
+//! UniFFI bindings for iOS and Android.
+//!
+//! `src/glossia.udl` declares the interface; `uniffi-bindgen` generates the
+//! Swift and Kotlin wrappers from it:
+//!
+//! ```bash
+//! cargo build --release
+//! cargo run --bin uniffi-bindgen generate --library target/release/libglossia_ffi.so \
+//!     --language swift --out-dir out/swift
+//! cargo run --bin uniffi-bindgen generate --library target/release/libglossia_ffi.so \
+//!     --language kotlin --out-dir out/kotlin
+//! ```
+//!
+//! Every call uses the embedded English wordlist and [`glossia::payload`], so
+//! mobile clients frame payloads exactly like the CLI and the other bindings.
+
+use glossia::{decode::wrap_lines, embedded};
+use glossia::payload::{self, PayloadOptions};
+
+uniffi::include_scaffolding!("glossia");
+
+pub struct EncodeOptions {
+    pub checksum: bool,
+    pub whiten: bool,
+    pub passphrase: Option<String>,
+    pub commit: bool,
+    /// Column [`encode_text`] wraps the cover text at
+    pub wrap: Option<u32>,
+}
+
+impl From<EncodeOptions> for PayloadOptions {
+    fn from(options: EncodeOptions) -> Self {
+        PayloadOptions {
+            checksum: options.checksum,
+            whiten: options.whiten,
+            passphrase: options.passphrase,
+            commit: options.commit,
+        }
+    }
+}
+
+pub struct Detection {
+    pub carrier_words: u32,
+    pub total_words: u32,
+    pub checksum_valid: bool,
+    pub likely: bool,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum GlossiaError {
+    #[error("Encoding failed: {message}")]
+    Encode { message: String },
+    #[error("Decoding failed: {message}")]
+    Decode { message: String },
+}
+
+pub fn encode(payload: Vec<u8>, options: EncodeOptions) -> Result<Vec<String>, GlossiaError> {
+    payload::encode_payload(&mut rand::thread_rng(), &payload, embedded::wordlist(), &options.into())
+        .map_err(|e| GlossiaError::Encode { message: format!("{:#}", e) })
+}
+
+pub fn encode_text(payload: Vec<u8>, options: EncodeOptions) -> Result<String, GlossiaError> {
+    let wrap = options.wrap;
+    let text = encode(payload, options)?.join(" ");
+    Ok(match wrap {
+        Some(width) => wrap_lines(&text, width as usize),
+        None => text,
+    })
+}
+
+pub fn decode(text: String, options: EncodeOptions) -> Result<Vec<u8>, GlossiaError> {
+    payload::decode_payload(&text, embedded::wordlist(), &options.into())
+        .map_err(|e| GlossiaError::Decode { message: format!("{:#}", e) })
+}
+
+pub fn detect(text: String) -> Detection {
+    let found = payload::detect(&text, embedded::wordlist());
+    Detection {
+        carrier_words: found.carrier_words as u32,
+        total_words: found.total_words as u32,
+        checksum_valid: found.checksum_valid,
+        likely: found.is_likely(),
+    }
+}
+
+pub fn capacity(payload_len: u32, options: EncodeOptions) -> u32 {
+    payload::carrier_words(payload_len as usize, embedded::wordlist().len(), &options.into()) as u32
+}
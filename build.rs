@@ -0,0 +1,109 @@
+//! Build-time packing of the embedded wordlist (feature `embedded-wordlist`).
+//!
+//! The English payload wordlist and its POS weights are front-coded (each word
+//! stores only the length of the prefix it shares with the previous word plus
+//! its new suffix), deflated, and written to `$OUT_DIR/payload.bin` for
+//! `glossia::embedded` to inflate on first use.
+//!
+//! With the `grpc` feature it also compiles `proto/glossia.proto`, and with
+//! `embedded-models` it finds the nlprule model files to compile in (see
+//! [`embed_models`]).
+
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+const PAYLOAD_YAML: &str = "languages/english/payload.yaml";
+
+/// The model files `embedded-models` compiles in
+const MODEL_FILES: [(&str, &str); 2] = [("en_tokenizer.bin", "GLOSSIA_EMBEDDED_TOKENIZER"), ("en_rules.bin", "GLOSSIA_EMBEDDED_RULES")];
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+    #[cfg(feature = "grpc")]
+    tonic_build::compile_protos("proto/glossia.proto").expect("compile gRPC protos");
+
+    if std::env::var_os("CARGO_FEATURE_EMBEDDED_MODELS").is_some() {
+        embed_models();
+    }
+    if std::env::var_os("CARGO_FEATURE_EMBEDDED_WORDLIST").is_none() {
+        return;
+    }
+    println!("cargo:rerun-if-changed={}", PAYLOAD_YAML);
+
+    let yaml = std::fs::read_to_string(PAYLOAD_YAML).expect("read payload wordlist");
+    let entries: BTreeMap<String, BTreeMap<String, f64>> =
+        serde_yaml::from_str(&yaml).expect("parse payload wordlist");
+
+    // One line per word: <shared prefix length><suffix>\t<POS>=<weight>,...
+    let mut packed = String::new();
+    let mut previous = "";
+    for (word, weights) in &entries {
+        let shared = word
+            .bytes()
+            .zip(previous.bytes())
+            .take_while(|(a, b)| a == b)
+            .count();
+        let weights: Vec<String> = weights.iter().map(|(pos, w)| format!("{}={}", pos, w)).collect();
+        packed.push_str(&format!("{}{}\t{}\n", shared, &word[shared..], weights.join(",")));
+        previous = word;
+    }
+
+    let mut encoder = DeflateEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(packed.as_bytes()).expect("compress wordlist");
+    let compressed = encoder.finish().expect("compress wordlist");
+
+    let out_dir = std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo");
+    std::fs::write(Path::new(&out_dir).join("payload.bin"), compressed).expect("write packed wordlist");
+}
+
+/// Point `GLOSSIA_EMBEDDED_TOKENIZER` and `GLOSSIA_EMBEDDED_RULES` at the model
+/// files for `include_bytes!`: the ones in `$GLOSSIA_MODEL_DIR`, else the first
+/// of `data/`, `/opt/nlprule-data` and `/app/data` that has both. With
+/// `embedded-models-zstd` they are zstd-compressed into `$OUT_DIR` first.
+fn embed_models() {
+    println!("cargo:rerun-if-env-changed=GLOSSIA_MODEL_DIR");
+    let candidates: Vec<PathBuf> = match std::env::var_os("GLOSSIA_MODEL_DIR") {
+        Some(dir) => vec![PathBuf::from(dir)],
+        None => ["data", "/opt/nlprule-data", "/app/data"].iter().map(PathBuf::from).collect(),
+    };
+    let dir = candidates
+        .iter()
+        .find(|dir| MODEL_FILES.iter().all(|(file, _)| dir.join(file).is_file()))
+        .unwrap_or_else(|| {
+            panic!(
+                "embedded-models needs en_tokenizer.bin and en_rules.bin in GLOSSIA_MODEL_DIR or one of: {}",
+                candidates.iter().map(|dir| dir.display().to_string()).collect::<Vec<_>>().join(", ")
+            )
+        });
+    let dir = std::fs::canonicalize(dir).expect("resolve model directory");
+
+    let compress = std::env::var_os("CARGO_FEATURE_EMBEDDED_MODELS_ZSTD").is_some();
+    let out_dir = PathBuf::from(std::env::var("OUT_DIR").expect("OUT_DIR is set by cargo"));
+    for (file, var) in MODEL_FILES {
+        let path = dir.join(file);
+        println!("cargo:rerun-if-changed={}", path.display());
+        let embedded = if compress {
+            let bytes = std::fs::read(&path).expect("read model file");
+            let compressed = zstd_compress(&bytes);
+            let target = out_dir.join(format!("{}.zst", file));
+            std::fs::write(&target, compressed).expect("write compressed model");
+            target
+        } else {
+            path
+        };
+        println!("cargo:rustc-env={}={}", var, embedded.display());
+    }
+}
+
+#[cfg(feature = "embedded-models-zstd")]
+fn zstd_compress(bytes: &[u8]) -> Vec<u8> {
+    zstd::encode_all(bytes, 19).expect("compress model file")
+}
+
+#[cfg(not(feature = "embedded-models-zstd"))]
+fn zstd_compress(_bytes: &[u8]) -> Vec<u8> {
+    unreachable!("only called with embedded-models-zstd")
+}
@@ -0,0 +1,127 @@
+//! age's ASCII armor, stripped down to the binary file and put back exactly.
+//!
+//! Users often run `age --armor` before hiding a message, which wraps the
+//! encrypted file in PEM-style base64 lines, a third larger than the file.
+//! [`dearmor`] recovers the binary file and [`armor`] writes the armor back
+//! the way age does (64 columns, LF line ends), so
+//! [`DataMode::Age`](crate::data_mode::DataMode::Age) carries only the file
+//! and the decoder still gets the text the user started from. With the `age`
+//! feature, [`encode_encrypted_age`] does the encryption as well.
+
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+
+pub const BEGIN_MARKER: &str = "-----BEGIN AGE ENCRYPTED FILE-----";
+pub const END_MARKER: &str = "-----END AGE ENCRYPTED FILE-----";
+
+/// Base64 characters per armor line
+const COLUMNS: usize = 64;
+
+/// First line of every age file
+const MAGIC: &[u8] = b"age-encryption.org/v1\n";
+
+/// Whether `text` starts like an armored age file.
+pub fn is_armored(text: &str) -> bool {
+    text.trim_start().starts_with(BEGIN_MARKER)
+}
+
+/// The binary age file inside armored `text`.
+pub fn dearmor(text: &str) -> Result<Vec<u8>> {
+    let mut lines = text.trim().lines().map(str::trim_end);
+    if lines.next() != Some(BEGIN_MARKER) {
+        bail!("Not an armored age file (no {})", BEGIN_MARKER);
+    }
+    let mut encoded = String::with_capacity(text.len());
+    let mut ended = false;
+    for line in lines.by_ref() {
+        if line == END_MARKER {
+            ended = true;
+            break;
+        }
+        encoded.push_str(line);
+    }
+    if !ended || lines.next().is_some() {
+        bail!("Armored age file does not end with {}", END_MARKER);
+    }
+    let file = BASE64.decode(&encoded).context("Invalid base64 in age armor")?;
+    if !file.starts_with(MAGIC) {
+        bail!("Armor does not hold an age file");
+    }
+    Ok(file)
+}
+
+/// `file` armored as `age --armor` writes it, without the final newline.
+pub fn armor(file: &[u8]) -> String {
+    let encoded = BASE64.encode(file);
+    let mut out = String::with_capacity(encoded.len() + encoded.len() / COLUMNS + BEGIN_MARKER.len() + END_MARKER.len() + 2);
+    out.push_str(BEGIN_MARKER);
+    out.push('\n');
+    for line in encoded.as_bytes().chunks(COLUMNS) {
+        out.push_str(std::str::from_utf8(line).expect("base64 is ASCII"));
+        out.push('\n');
+    }
+    out.push_str(END_MARKER);
+    out
+}
+
+/// Encrypt `data` to the age `recipients` (`age1...` X25519 public keys) and
+/// pack the file as [`DataMode::Age`](crate::data_mode::DataMode::Age), ready
+/// to encode; [`data_mode::unpack`](crate::data_mode::unpack) gives back the
+/// armored file, for `age --decrypt`.
+#[cfg(feature = "age")]
+pub fn encode_encrypted_age(data: &[u8], recipients: &[String]) -> Result<Vec<u8>> {
+    use anyhow::anyhow;
+    use std::io::Write;
+
+    let recipients = recipients
+        .iter()
+        .map(|recipient| recipient.parse::<age::x25519::Recipient>().map_err(|e| anyhow!("Invalid age recipient '{}': {}", recipient, e)))
+        .collect::<Result<Vec<_>>>()?;
+    let encryptor = age::Encryptor::with_recipients(recipients.iter().map(|recipient| recipient as &dyn age::Recipient))
+        .map_err(|e| anyhow!("{}", e))?;
+    let mut file = Vec::with_capacity(data.len() + 256);
+    let mut writer = encryptor.wrap_output(&mut file)?;
+    writer.write_all(data)?;
+    writer.finish()?;
+    crate::data_mode::pack(crate::data_mode::DataMode::Age, (armor(&file) + "\n").as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_armor_round_trip() {
+        let file: Vec<u8> = MAGIC.iter().copied().chain((0..200u32).map(|i| (i * 13 % 256) as u8)).collect();
+        let armored = armor(&file);
+        assert!(is_armored(&armored));
+        assert!(armored.lines().skip(1).all(|line| line.len() <= COLUMNS));
+        assert_eq!(dearmor(&armored).unwrap(), file);
+        assert_eq!(dearmor(&armored.replace('\n', "\r\n")).unwrap(), file);
+
+        assert!(dearmor(&armored.replace(END_MARKER, "")).is_err());
+        assert!(dearmor(&armor(b"not an age file")).is_err());
+        assert!(!is_armored("-----BEGIN PGP MESSAGE-----"));
+    }
+
+    #[cfg(feature = "age")]
+    #[test]
+    fn test_encode_encrypted_age() {
+        use std::io::Read;
+
+        let identity = age::x25519::Identity::generate();
+        let recipient = identity.to_public().to_string();
+        let packed = encode_encrypted_age(b"Meet at noon", &[recipient]).unwrap();
+        let armored = String::from_utf8(crate::data_mode::unpack(&packed).unwrap()).unwrap();
+        assert!(armored.starts_with(BEGIN_MARKER) && armored.ends_with("-----\n"));
+        assert!(packed.len() * 4 < armored.len() * 3);
+
+        let file = dearmor(&armored).unwrap();
+        let decryptor = age::Decryptor::new(&file[..]).unwrap();
+        let mut plaintext = Vec::new();
+        decryptor.decrypt(std::iter::once(&identity as &dyn age::Identity)).unwrap().read_to_end(&mut plaintext).unwrap();
+        assert_eq!(plaintext, b"Meet at noon");
+        assert!(encode_encrypted_age(b"x", &["age1nope".to_string()]).is_err());
+    }
+}
@@ -0,0 +1,50 @@
+//! Loading nlprule models off the async runtime (feature `async`).
+//!
+//! Parsing the models takes seconds of CPU, which would stall every other
+//! task on a runtime thread. [`GrammarChecker::from_language_async`] runs
+//! [`GrammarChecker::from_language`] on tokio's blocking pool instead, and
+//! [`GrammarChecker::warm_up`] starts filling the shared checker
+//! ([`GrammarChecker::global`]) in the background, so a server can accept
+//! requests while the models load.
+
+use crate::{GrammarChecker, Language};
+use anyhow::{Context, Result};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+
+impl GrammarChecker {
+    /// [`GrammarChecker::from_language`] on the blocking thread pool.
+    pub async fn from_language_async(language: Language) -> Result<Self> {
+        tokio::task::spawn_blocking(move || Self::from_language(language))
+            .await
+            .context("Model loading task failed")?
+    }
+
+    /// Start loading the shared checker for `language` in the background.
+    /// Awaiting the handle gives the loaded checker; calls to
+    /// [`GrammarChecker::global`] made meanwhile wait for the same load.
+    pub fn warm_up(language: Language) -> JoinHandle<Result<Arc<Self>>> {
+        tokio::task::spawn_blocking(move || Self::global(language))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_async_load_matches_sync() {
+        let sync = GrammarChecker::from_language(Language::English).map(|_| ()).map_err(|e| e.to_string());
+        let loaded = GrammarChecker::from_language_async(Language::English).await;
+        assert_eq!(loaded.map(|_| ()).map_err(|e| e.to_string()), sync);
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_fills_global() {
+        // Skip if the model files are not available
+        let Ok(warmed) = GrammarChecker::warm_up(Language::English).await.unwrap() else {
+            return;
+        };
+        assert!(Arc::ptr_eq(&warmed, &GrammarChecker::global(Language::English).unwrap()));
+    }
+}
@@ -3,23 +3,32 @@
 //! This tool reads two YAML files containing words with POS tag weights,
 //! compares them word by word, and outputs a third YAML file with the
 //! differences (file1_weight - file2_weight) for each POS tag.
+//!
+//! With `--max-divergence` or `--fail-on-missing` it also acts as a gate:
+//! the exit status is non-zero when any word's weight distribution has moved
+//! too far, or when a word is present in only one file.
+//!
+//! `--report html` writes a sortable page with per-word bar charts of both
+//! distributions and their deltas; `--report terminal` prints a colorized
+//! summary of the most divergent words.
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use anyhow::Context;
-use serde_yaml;
+use glossia::weights;
 use std::collections::BTreeMap;
 
-/// Load YAML file and parse word -> POS weights mapping
+/// Load and validate a YAML file as a word -> POS weights mapping
 fn load_yaml_weights(path: &PathBuf) -> anyhow::Result<HashMap<String, HashMap<String, f64>>> {
-    let content = std::fs::read_to_string(path)
-        .with_context(|| format!("Failed to read YAML file: {:?}", path))?;
+    let pos_weights = weights::load(path)
+        .with_context(|| format!("Invalid weight file: {:?}", path))?;
     
-    let yaml_data: HashMap<String, HashMap<String, f64>> = serde_yaml::from_str(&content)
-        .with_context(|| format!("Failed to parse YAML file: {:?}", path))?;
-    
-    Ok(yaml_data)
+    Ok(pos_weights
+        .into_inner()
+        .into_iter()
+        .map(|(word, tags)| (word, tags.into_iter().collect()))
+        .collect())
 }
 
 /// Compare two weight maps and calculate differences
@@ -52,6 +61,180 @@ fn calculate_differences(
     differences
 }
 
+/// Jensen-Shannon divergence (base 2, so between 0 and 1) of two words'
+/// POS weights, each normalized to a distribution. A word with no weight in
+/// one file counts as fully divergent unless it has none in either.
+fn js_divergence(weights1: &HashMap<String, f64>, weights2: &HashMap<String, f64>) -> f64 {
+    let total1: f64 = weights1.values().sum();
+    let total2: f64 = weights2.values().sum();
+    if total1 <= 0.0 || total2 <= 0.0 {
+        return if total1 <= 0.0 && total2 <= 0.0 { 0.0 } else { 1.0 };
+    }
+
+    let mut tags: Vec<&String> = weights1.keys().chain(weights2.keys()).collect();
+    tags.sort();
+    tags.dedup();
+
+    let kl_term = |p: f64, m: f64| if p > 0.0 { p * (p / m).log2() } else { 0.0 };
+    tags.into_iter()
+        .map(|tag| {
+            let p = weights1.get(tag).copied().unwrap_or(0.0) / total1;
+            let q = weights2.get(tag).copied().unwrap_or(0.0) / total2;
+            let m = (p + q) / 2.0;
+            (kl_term(p, m) + kl_term(q, m)) / 2.0
+        })
+        .sum()
+}
+
+/// One word's weights in each file, for the reports.
+struct WordRow {
+    word: String,
+    weights1: Option<HashMap<String, f64>>,
+    weights2: Option<HashMap<String, f64>>,
+    divergence: f64,
+}
+
+impl WordRow {
+    /// Every tag either file gives this word, sorted
+    fn tags(&self) -> Vec<&String> {
+        let mut tags: Vec<&String> = self.weights1.iter().chain(self.weights2.iter()).flat_map(|w| w.keys()).collect();
+        tags.sort();
+        tags.dedup();
+        tags
+    }
+
+    fn weight(weights: &Option<HashMap<String, f64>>, tag: &str) -> f64 {
+        weights.as_ref().and_then(|w| w.get(tag)).copied().unwrap_or(0.0)
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum ReportFormat {
+    /// Self-contained HTML page with a sortable table and bar charts
+    Html,
+    /// Colorized summary of the most divergent words
+    Terminal,
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// Horizontal bars for one distribution (`delta` bars are centered on zero).
+fn html_bars(row: &WordRow, value: impl Fn(&str) -> f64, delta: bool) -> String {
+    let mut out = String::new();
+    for tag in row.tags() {
+        let v = value(tag);
+        let width = (v.abs() * 100.0).min(100.0);
+        let (class, offset) = match (delta, v < 0.0) {
+            (false, _) => ("bar", 0.0),
+            (true, false) => ("bar up", 50.0),
+            (true, true) => ("bar down", 50.0 - width / 2.0),
+        };
+        let width = if delta { width / 2.0 } else { width };
+        let label = if delta { format!("{:+.3}", v) } else { format!("{:.3}", v) };
+        out.push_str(&format!(
+            "<div class=\"tag\"><span>{}</span><div class=\"track\"><div class=\"{}\" style=\"margin-left:{:.1}%;width:{:.1}%\"></div></div><span>{}</span></div>",
+            escape_html(tag), class, offset, width, label
+        ));
+    }
+    out
+}
+
+fn html_report(rows: &[WordRow], file1: &Path, file2: &Path) -> String {
+    let mut body = String::new();
+    for row in rows {
+        let bars1 = html_bars(row, |tag| WordRow::weight(&row.weights1, tag), false);
+        let bars2 = html_bars(row, |tag| WordRow::weight(&row.weights2, tag), false);
+        let delta = html_bars(row, |tag| WordRow::weight(&row.weights1, tag) - WordRow::weight(&row.weights2, tag), true);
+        let status = match (&row.weights1, &row.weights2) {
+            (Some(_), None) => "only file1",
+            (None, Some(_)) => "only file2",
+            _ => "",
+        };
+        body.push_str(&format!(
+            "<tr><td data-sort=\"{word}\">{word}<small>{status}</small></td><td data-sort=\"{div:.6}\">{div:.4}</td><td>{bars1}</td><td>{bars2}</td><td>{delta}</td></tr>\n",
+            word = escape_html(&row.word),
+            status = status,
+            div = row.divergence,
+            bars1 = bars1,
+            bars2 = bars2,
+            delta = delta,
+        ));
+    }
+    format!(
+        r##"<!DOCTYPE html>
+<html><head><meta charset="utf-8"><title>POS weight diff</title>
+<style>
+body {{ font-family: sans-serif; margin: 1em; }}
+table {{ border-collapse: collapse; width: 100%; }}
+th, td {{ border-bottom: 1px solid #ddd; padding: 4px; vertical-align: top; text-align: left; }}
+th {{ cursor: pointer; background: #f4f4f4; position: sticky; top: 0; }}
+small {{ color: #a00; margin-left: 0.5em; }}
+.tag {{ display: flex; align-items: center; gap: 4px; font-size: 12px; }}
+.tag span:first-child {{ width: 4em; }}
+.track {{ flex: 1; background: #f0f0f0; height: 10px; min-width: 80px; }}
+.bar {{ background: #4a7ebb; height: 10px; }}
+.up {{ background: #3a9a4a; }}
+.down {{ background: #c0392b; }}
+</style></head><body>
+<h1>POS weight diff</h1>
+<p>file1: {file1}<br>file2: {file2}<br>{count} words. Divergence is the Jensen-Shannon divergence of the normalized weights (0 identical, 1 disjoint). Click a column header to sort.</p>
+<table id="diff"><thead><tr><th data-type="text">Word</th><th data-type="number">Divergence</th><th>file1</th><th>file2</th><th>Delta (file1 - file2)</th></tr></thead>
+<tbody>
+{body}</tbody></table>
+<script>
+document.querySelectorAll("#diff th[data-type]").forEach((th, col) => {{
+  th.addEventListener("click", () => {{
+    const tbody = th.closest("table").tBodies[0];
+    const numeric = th.dataset.type === "number";
+    const dir = th.dataset.dir === "asc" ? -1 : 1;
+    th.dataset.dir = dir === 1 ? "asc" : "desc";
+    const key = tr => tr.cells[col].dataset.sort;
+    const rows = Array.from(tbody.rows).sort((a, b) =>
+      dir * (numeric ? parseFloat(key(a)) - parseFloat(key(b)) : key(a).localeCompare(key(b))));
+    rows.forEach(tr => tbody.appendChild(tr));
+  }});
+}});
+</script>
+</body></html>
+"##,
+        file1 = escape_html(&file1.display().to_string()),
+        file2 = escape_html(&file2.display().to_string()),
+        count = rows.len(),
+        body = body,
+    )
+}
+
+/// Print the `limit` most divergent words with per-tag bars and colored deltas.
+fn terminal_report(rows: &[WordRow], limit: usize) {
+    const GREEN: &str = "\x1b[32m";
+    const RED: &str = "\x1b[31m";
+    const DIM: &str = "\x1b[2m";
+    const RESET: &str = "\x1b[0m";
+    let bar = |v: f64| "█".repeat((v * 20.0).round().clamp(0.0, 20.0) as usize);
+
+    eprintln!("\nMost divergent words (of {}):", rows.len());
+    for row in rows.iter().take(limit) {
+        let note = match (&row.weights1, &row.weights2) {
+            (Some(_), None) => format!(" {}only in file1{}", RED, RESET),
+            (None, Some(_)) => format!(" {}only in file2{}", RED, RESET),
+            _ => String::new(),
+        };
+        eprintln!("{} {}(divergence {:.3}){}{}", row.word, DIM, row.divergence, RESET, note);
+        for tag in row.tags() {
+            let w1 = WordRow::weight(&row.weights1, tag);
+            let w2 = WordRow::weight(&row.weights2, tag);
+            let delta = w1 - w2;
+            let color = if delta > 1e-10 { GREEN } else if delta < -1e-10 { RED } else { DIM };
+            eprintln!(
+                "  {:<6} {:>5.3} {:<20} {:>5.3} {:<20} {}{:+.3}{}",
+                tag, w1, bar(w1), w2, bar(w2), color, delta, RESET
+            );
+        }
+    }
+}
+
 #[derive(Parser)]
 #[command(
     name = "compare_pos_weights",
@@ -81,6 +264,27 @@ struct Args {
     /// Only include words that exist in both files (default: false, includes all words)
     #[arg(short = 'b', long = "both-only")]
     both_only: bool,
+
+    /// Fail if any word's POS distributions diverge by more than this
+    /// (Jensen-Shannon divergence, 0 = identical, 1 = disjoint)
+    #[arg(long = "max-divergence")]
+    max_divergence: Option<f64>,
+
+    /// Fail if any word appears in only one of the files
+    #[arg(long = "fail-on-missing")]
+    fail_on_missing: bool,
+
+    /// Also produce a visual report of the diff
+    #[arg(long = "report", value_enum)]
+    report: Option<ReportFormat>,
+
+    /// Where to write the HTML report (default: the output path with an .html extension)
+    #[arg(long = "report-file")]
+    report_file: Option<PathBuf>,
+
+    /// Number of words shown in the terminal report
+    #[arg(long = "report-limit", default_value = "20")]
+    report_limit: usize,
 }
 
 /// Round a float to specified decimal places
@@ -124,12 +328,20 @@ fn main() -> anyhow::Result<()> {
     let mut file1_total_tags = 0;
     let mut file2_total_tags = 0;
     let mut words_compared = 0;
+
+    // Gate failures
+    let mut diverged: Vec<(String, f64)> = Vec::new();
+    let mut missing: Vec<String> = Vec::new();
+    let mut rows: Vec<WordRow> = Vec::new();
     
     for word in all_words {
         let w1 = weights1.get(&word);
         let w2 = weights2.get(&word);
         
         let (has_w1, has_w2) = (w1.is_some(), w2.is_some());
+        if !(has_w1 && has_w2) {
+            missing.push(word.clone());
+        }
         
         // Skip if both-only flag is set and word is not in both files
         if args.both_only && (!has_w1 || !has_w2) {
@@ -147,6 +359,13 @@ fn main() -> anyhow::Result<()> {
             file2_total_tags += tags2;
             words_compared += 1;
             
+            if let Some(max) = args.max_divergence {
+                let divergence = js_divergence(w1.unwrap(), w2.unwrap());
+                if divergence > max {
+                    diverged.push((word.clone(), divergence));
+                }
+            }
+            
             if tags1 > tags2 {
                 file1_more_nuanced += 1;
             } else if tags2 > tags1 {
@@ -162,6 +381,15 @@ fn main() -> anyhow::Result<()> {
         
         let weights1_map = w1.cloned().unwrap_or_default();
         let weights2_map = w2.cloned().unwrap_or_default();
+
+        if args.report.is_some() {
+            rows.push(WordRow {
+                word: word.clone(),
+                weights1: w1.cloned(),
+                weights2: w2.cloned(),
+                divergence: js_divergence(&weights1_map, &weights2_map),
+            });
+        }
         
         let word_differences = calculate_differences(&weights1_map, &weights2_map);
         
@@ -221,6 +449,42 @@ fn main() -> anyhow::Result<()> {
     
     eprintln!("\nDifferences saved to {:?}", args.output);
     eprintln!("Output contains {} words with non-zero differences", differences.len());
+
+    rows.sort_by(|a, b| b.divergence.total_cmp(&a.divergence).then_with(|| a.word.cmp(&b.word)));
+    match args.report {
+        Some(ReportFormat::Html) => {
+            let path = args.report_file.clone().unwrap_or_else(|| args.output.with_extension("html"));
+            std::fs::write(&path, html_report(&rows, &args.file1, &args.file2))
+                .with_context(|| format!("Failed to write report to {:?}", path))?;
+            eprintln!("HTML report saved to {:?}", path);
+        }
+        Some(ReportFormat::Terminal) => terminal_report(&rows, args.report_limit),
+        None => {}
+    }
+
+    let mut failures = Vec::new();
+    if let Some(max) = args.max_divergence {
+        if !diverged.is_empty() {
+            diverged.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            eprintln!("\nWords diverging by more than {}:", max);
+            for (word, divergence) in &diverged {
+                eprintln!("  {}: {:.4}", word, divergence);
+            }
+            failures.push(format!("{} word(s) diverge by more than {}", diverged.len(), max));
+        }
+    }
+    if args.fail_on_missing && !missing.is_empty() {
+        missing.sort();
+        eprintln!("\nWords missing from one file:");
+        for word in &missing {
+            let side = if weights1.contains_key(word) { "file2" } else { "file1" };
+            eprintln!("  {} (not in {})", word, side);
+        }
+        failures.push(format!("{} word(s) are missing from one file", missing.len()));
+    }
+    if !failures.is_empty() {
+        anyhow::bail!("POS weight check failed: {}", failures.join("; "));
+    }
     
     Ok(())
 }
@@ -0,0 +1,264 @@
+//! Tool to write (and check) the test-vector corpus.
+//!
+//! Every vector is one fixed-seed `glossia --from-ascii` run: the options,
+//! the plaintext, and the cover text it produced. The corpus covers each
+//! grammar, output style, engine and wordlist (with and without a checksum
+//! word) at a few seeds, and every vector is decoded again before it is
+//! written, so a vector never records an encoding that does not round-trip.
+//!
+//! `tests/vectors.json` is the checked-in corpus: `--check` regenerates it and
+//! reports any vector whose output changed, which is what the golden test
+//! runs. Ports to other languages read the same file to verify they decode
+//! (and, given the same random stream, encode) compatibly.
+
+use anyhow::{bail, Context};
+use clap::Parser;
+use serde::{Deserialize, Serialize};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Corpus format version, bumped when the vector fields change
+const FORMAT_VERSION: u32 = 1;
+
+/// Seeds every combination is encoded at
+const SEEDS: [u64; 2] = [7, 42];
+
+/// Plaintexts every combination encodes
+const INPUTS: [&str; 2] = ["Meet at noon", "The quick brown fox jumps over the lazy dog, 0123456789!"];
+
+/// Options the vectors are encoded with besides their own: fixed so the
+/// output doesn't depend on the terminal
+const FIXED_ARGS: [&str; 4] = ["--highlight", "none", "--mode", "text"];
+
+#[derive(Parser)]
+#[command(
+    name = "gen_vectors",
+    about = "Write the fixed-seed test-vector corpus, or check it is still current",
+    long_about = "Runs glossia --from-ascii for every grammar, output style, engine and wordlist\n\
+                  at fixed seeds, decodes each result again, and writes the\n\
+                  (options, input, output) triples as JSON."
+)]
+struct Args {
+    /// Write the corpus here instead of stdout
+    #[arg(short = 'o', long = "output")]
+    output: Option<PathBuf>,
+
+    /// Compare against this corpus instead of writing one; exits 1 on any difference
+    #[arg(long = "check")]
+    check: Option<PathBuf>,
+
+    /// The glossia binary to run (default: the one beside this tool)
+    #[arg(long = "glossia")]
+    glossia: Option<PathBuf>,
+}
+
+/// The settings of one vector, by their command-line names
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct VectorOptions {
+    /// `subject` or `body`; `None` for layouts that don't use the grammar
+    grammar: Option<String>,
+    engine: String,
+    style: String,
+    wordlist: String,
+    checksum: bool,
+    seed: u64,
+}
+
+impl VectorOptions {
+    /// The options both directions take
+    fn shared_args(&self) -> Vec<String> {
+        let mut args = vec!["--engine".to_string(), self.engine.clone(), "--wordlist".to_string(), self.wordlist.clone()];
+        if self.engine == "prose" {
+            args.extend(["--output-style".to_string(), self.style.clone()]);
+        }
+        if self.checksum {
+            args.push("--checksum".to_string());
+        }
+        args
+    }
+
+    /// The full `glossia` arguments that encode `input`
+    fn encode_args(&self, input: &str) -> Vec<String> {
+        let mut args = vec!["--from-ascii".to_string(), input.to_string(), "--seed".to_string(), self.seed.to_string()];
+        if let Some(grammar) = &self.grammar {
+            args.extend(["--grammar".to_string(), grammar.clone()]);
+        }
+        args.extend(self.shared_args());
+        args.extend(FIXED_ARGS.map(String::from));
+        args
+    }
+
+    /// The full `glossia` arguments that decode a cover text from stdin
+    fn decode_args(&self) -> Vec<String> {
+        let mut args = vec!["--decode-ascii".to_string(), "-".to_string()];
+        args.extend(self.shared_args());
+        args.extend(["--mode".to_string(), "text".to_string()]);
+        args
+    }
+
+    fn name(&self, input_index: usize) -> String {
+        let mut parts = vec![self.engine.clone()];
+        if self.engine == "prose" {
+            parts.push(self.style.clone());
+        }
+        parts.extend(self.grammar.clone());
+        parts.push(self.wordlist.clone());
+        if self.checksum {
+            parts.push("checksum".to_string());
+        }
+        parts.push(format!("input{}", input_index));
+        parts.push(format!("seed{}", self.seed));
+        parts.join("-")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct Vector {
+    name: String,
+    options: VectorOptions,
+    /// The `glossia` command line that produced `output`
+    args: Vec<String>,
+    input: String,
+    output: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Corpus {
+    version: u32,
+    vectors: Vec<Vector>,
+}
+
+/// Every combination the corpus covers, before seeds and inputs
+fn combinations() -> Vec<VectorOptions> {
+    let mut out = Vec::new();
+    let option = |grammar: Option<&str>, engine: &str, style: &str, wordlist: &str, checksum: bool| VectorOptions {
+        grammar: grammar.map(String::from),
+        engine: engine.to_string(),
+        style: style.to_string(),
+        wordlist: wordlist.to_string(),
+        checksum,
+        seed: 0,
+    };
+    for checksum in [false, true] {
+        // The phonetic list frames its own bytes and takes no checksum word
+        for wordlist in glossia::capabilities::WORDLISTS.iter().filter(|&&wordlist| !checksum || wordlist != "phonetic") {
+            for grammar in ["subject", "body"] {
+                out.push(option(Some(grammar), "prose", "prose", wordlist, checksum));
+            }
+            for style in ["list", "recipe"] {
+                out.push(option(None, "prose", style, wordlist, checksum));
+            }
+        }
+        // Emoji packs wordlist indices, which the phonetic list doesn't have
+        out.push(option(None, "emoji", "prose", "bip39", checksum));
+    }
+    out
+}
+
+/// Run `glossia` with `args`, feeding it `stdin`, and return its stdout.
+fn run(glossia: &Path, config: &Path, args: &[String], stdin: &str) -> anyhow::Result<String> {
+    let mut child = Command::new(glossia)
+        .args(args)
+        .arg("--config")
+        .arg(config)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", glossia.display()))?;
+    child.stdin.take().expect("stdin is piped").write_all(stdin.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("glossia {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).lines().next().unwrap_or_default());
+    }
+    String::from_utf8(output.stdout).context("glossia wrote non-UTF-8 output")
+}
+
+/// Encode every combination at every seed and input, checking each decodes.
+fn generate(glossia: &Path) -> anyhow::Result<Corpus> {
+    // An empty config, so the user's own defaults can't leak into the vectors
+    let config = std::env::temp_dir().join(format!("gen_vectors_{}.toml", std::process::id()));
+    std::fs::write(&config, "")?;
+    let result = (|| {
+        let mut vectors = Vec::new();
+        for combination in combinations() {
+            for seed in SEEDS {
+                let options = VectorOptions { seed, ..combination.clone() };
+                for (i, input) in INPUTS.iter().enumerate() {
+                    let args = options.encode_args(input);
+                    let output = run(glossia, &config, &args, "")?;
+                    let decoded = run(glossia, &config, &options.decode_args(), &output)?;
+                    if decoded.trim() != *input {
+                        bail!("{} does not round-trip: decoded {:?}", options.name(i), decoded);
+                    }
+                    vectors.push(Vector { name: options.name(i), options: options.clone(), args, input: input.to_string(), output });
+                }
+            }
+        }
+        Ok(Corpus { version: FORMAT_VERSION, vectors })
+    })();
+    let _ = std::fs::remove_file(&config);
+    result
+}
+
+/// Names of the vectors that differ between `expected` and `actual`, and of
+/// those only one of them has.
+fn differences(expected: &Corpus, actual: &Corpus) -> Vec<String> {
+    let mut out = Vec::new();
+    for vector in &actual.vectors {
+        match expected.vectors.iter().find(|v| v.name == vector.name) {
+            Some(old) if old == vector => {}
+            Some(_) => out.push(format!("{}: output changed", vector.name)),
+            None => out.push(format!("{}: not in the corpus", vector.name)),
+        }
+    }
+    for vector in &expected.vectors {
+        if !actual.vectors.iter().any(|v| v.name == vector.name) {
+            out.push(format!("{}: no longer generated", vector.name));
+        }
+    }
+    out
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let glossia = match args.glossia {
+        Some(path) => path,
+        None => std::env::current_exe()?.with_file_name(format!("glossia{}", std::env::consts::EXE_SUFFIX)),
+    };
+    if !glossia.exists() {
+        bail!("{} not found; build it first (cargo build --bin glossia) or pass --glossia", glossia.display());
+    }
+
+    let corpus = generate(&glossia)?;
+
+    if let Some(path) = args.check {
+        let text = std::fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+        let expected: Corpus = serde_json::from_str(&text).with_context(|| format!("Invalid corpus {}", path.display()))?;
+        if expected.version != FORMAT_VERSION {
+            bail!("{} is format version {}, this tool writes {}", path.display(), expected.version, FORMAT_VERSION);
+        }
+        let changed = differences(&expected, &corpus);
+        if !changed.is_empty() {
+            for line in &changed {
+                eprintln!("{}", line);
+            }
+            eprintln!("{} of {} vectors differ; regenerate with: cargo run --bin gen_vectors -- -o {}", changed.len(), corpus.vectors.len(), path.display());
+            std::process::exit(1);
+        }
+        eprintln!("All {} vectors match {}", corpus.vectors.len(), path.display());
+        return Ok(());
+    }
+
+    let json = serde_json::to_string_pretty(&corpus)? + "\n";
+    match args.output {
+        Some(path) => {
+            std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!("Wrote {} vectors to {}", corpus.vectors.len(), path.display());
+        }
+        None => print!("{}", json),
+    }
+    Ok(())
+}
@@ -10,16 +10,50 @@ mod grammar;
 
 use rand::{seq::SliceRandom, Rng, SeedableRng};
 use rand::rngs::StdRng;
+use indexmap::IndexMap;
 use std::collections::{HashMap, HashSet};
 use std::env;
-use std::sync::OnceLock;
-use std::path::Path;
-use std::time::Instant;
+use std::io::{IsTerminal, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::path::{Path, PathBuf};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use glossia::config::{FecLevel, GlossiaConfig};
+use glossia::data_mode::{self, DataMode};
+use glossia::{capabilities, chaff, codec, container, decode, deniable, document, emoji, footer, frequency, html, interleave, lemma, noisy, normalize, numbers, parity, patch, payload, phonetic, readability, segment, signing, spelling, synonyms, validity, weights, whiten};
+use k256::schnorr::{SigningKey, VerifyingKey};
+use glossia::secret::{SecretPayload, Wipe};
+use glossia::session::EncodeSession;
+use glossia::error::GlossiaError;
+use glossia::index::WordIndex;
+use glossia::noisy::NoiseModel;
+use glossia::normalize::NormalizeOptions;
+use glossia::payload::PayloadOptions;
+#[cfg(feature = "neural")]
+use glossia::neural::{self, NeuralOptions};
+use glossia::cover_model::CoverModel;
+use glossia::frequency::ZipfTable;
+use glossia::homophones::HomophoneTable;
+use glossia::prefix::PrefixRules;
+use glossia::resync::{self, Fragment};
+use glossia::synonyms::SynonymTable;
+use glossia::template::TemplateSet;
+use glossia::steganalysis;
+use glossia::style::{Style, StyleProfile};
+use glossia::lemma::Lemmatizer;
+use glossia::topic::Topic;
 use glossia::types::Pos;
+use glossia::weights::{PosWeights, SlotMap};
 use grammar::{Grammar, SequenceWithProbability};
 
 static PRINTED_SENTENCE_KINDS: OnceLock<()> = OnceLock::new();
 
+/// Payload words packed per `--session` chunk
+const SESSION_CHUNK_WORDS: usize = 64;
+
+/// Column prose output is wrapped at unless `--wrap` says otherwise
+const DEFAULT_WRAP: usize = 80;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum HighlightMode {
     None,
@@ -47,16 +81,124 @@ fn parse_color(color_str: &str) -> Result<u8, String> {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 enum GenerationMode {
     Subject,
     Body,
 }
 
+impl GenerationMode {
+    /// Parse `subject` or `body`
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "subject" => Some(GenerationMode::Subject),
+            "body" => Some(GenerationMode::Body),
+            _ => None,
+        }
+    }
+}
+
+/// What the cover text is made of.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Engine {
+    /// Sentences (or templates and lists) around the payload words
+    #[default]
+    Prose,
+    /// Bursts of emoji from a 1024-emoji alphabet, 10 bits each
+    Emoji,
+    /// Text written by a language model, the payload choosing its tokens
+    #[cfg(feature = "neural")]
+    Neural,
+}
+
+impl Engine {
+    /// Parse one of [`capabilities::ENGINES`]
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "prose" => Some(Engine::Prose),
+            "emoji" => Some(Engine::Emoji),
+            #[cfg(feature = "neural")]
+            "neural" => Some(Engine::Neural),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Engine::Prose => "prose",
+            Engine::Emoji => "emoji",
+            #[cfg(feature = "neural")]
+            Engine::Neural => "neural",
+        }
+    }
+}
+
+/// The model behind `--engine neural`, which writes and reads the cover text.
+#[cfg(feature = "neural")]
+#[derive(Clone, Debug)]
+struct NeuralSettings {
+    /// GGUF model file (`--model`)
+    model: String,
+    /// The model's `tokenizer.json` (`--tokenizer`)
+    tokenizer: String,
+    /// `--prompt` and `--top-k`
+    options: NeuralOptions,
+}
+
+/// Which payload wordlist words come from.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Wordlist {
+    /// The language's BIP39 list (`languages/<lang>/payload.yaml`)
+    #[default]
+    Bip39,
+    /// Alternating two- and three-syllable words, one byte each, for reading
+    /// aloud (`languages/<lang>/phonetic.yaml`)
+    Phonetic,
+}
+
+impl Wordlist {
+    /// Parse one of [`capabilities::WORDLISTS`]
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "bip39" => Some(Wordlist::Bip39),
+            "phonetic" => Some(Wordlist::Phonetic),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 enum SentenceLengthMode {
     Compact,
     Natural,
+    /// Natural, reweighted toward the `--style` sample's sentence lengths
+    Styled,
+}
+
+/// How often a slot with topical candidates takes one of them
+const TOPIC_BIAS: f64 = 0.6;
+
+/// How `--from-ascii` input and `--decode-ascii` output are treated.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PayloadMode {
+    /// Text on a terminal, raw bytes when stdout is piped or the payload isn't UTF-8
+    Auto,
+    /// UTF-8 text, printed with a trailing newline
+    Text,
+    /// Raw bytes, written exactly as decoded
+    Binary,
+}
+
+impl PayloadMode {
+    /// Parse `auto`, `text` or `binary`
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(PayloadMode::Auto),
+            "text" => Some(PayloadMode::Text),
+            "binary" => Some(PayloadMode::Binary),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -68,15 +210,20 @@ enum Number {
 #[derive(Clone, Debug)]
 struct PayloadTok {
     word: String,
-    allowed: HashSet<Pos>,
+    /// Tags the word may fill, in weight-file order (kept ordered so a
+    /// seeded encode always tries them the same way)
+    allowed: Vec<Pos>,
 }
 
 impl PayloadTok {
     fn new(word: impl Into<String>, allowed: &[Pos]) -> Self {
-        Self {
-            word: word.into(),
-            allowed: allowed.iter().copied().collect(),
+        let mut tags = Vec::new();
+        for &pos in allowed {
+            if !tags.contains(&pos) {
+                tags.push(pos);
+            }
         }
+        Self { word: word.into(), allowed: tags }
     }
 }
 
@@ -90,6 +237,24 @@ struct Lexicon {
     payload_set: HashSet<String>,
     /// Lowercased full wordlist set (for collision checks when inflecting cover words).
     wordlist_set: HashSet<String>,
+    /// Frequency ranks for filler slots; without one, cover words are picked
+    /// uniformly among the shortest candidates.
+    filler: Option<&'static ZipfTable>,
+    /// Word trigrams of the user's corpus (`--cover-model`), asked first for
+    /// filler words and sentence openers.
+    cover_model: Option<CoverModel>,
+    /// Vocabulary of the thread a reply continues (`--reply-to`); topical
+    /// candidates are preferred in every slot.
+    topic: Option<Topic>,
+    /// The `--style` sample, whose sentence lengths `SentenceLengthMode::Styled`
+    /// follows.
+    style: Option<StyleProfile>,
+}
+
+/// Slots whose cover words are function words, picked by natural frequency
+/// rather than length when the lexicon has a filler table.
+fn is_filler_slot(pos: Pos) -> bool {
+    matches!(pos, Pos::Det | Pos::Conj | Pos::Prep | Pos::Modal | Pos::Aux | Pos::Cop | Pos::Adv)
 }
 
 impl Lexicon {
@@ -98,18 +263,92 @@ impl Lexicon {
             by_pos: HashMap::new(),
             payload_set,
             wordlist_set,
+            filler: None,
+            cover_model: None,
+            topic: None,
+            style: None,
+        }
+    }
+
+    fn with_filler(mut self, table: &'static ZipfTable) -> Self {
+        self.filler = Some(table);
+        self
+    }
+
+    fn with_cover_model(mut self, model: Option<CoverModel>) -> Self {
+        self.cover_model = model;
+        self
+    }
+
+    /// Prefer `topic`'s words, and take its nouns as cover nouns too, unless
+    /// they are (or could be read back as inflections of) wordlist words.
+    fn with_topic(mut self, topic: Option<Topic>) -> Self {
+        if let Some(topic) = &topic {
+            let carrier_like = |word: &str| {
+                self.wordlist_set.contains(word) || lemma::RuleLemmatizer.lemmas(word).iter().any(|l| self.wordlist_set.contains(l))
+            };
+            let nouns = self.by_pos.get(&Pos::N).cloned().unwrap_or_default();
+            let thread_nouns: Vec<&str> = topic.nouns().filter(|w| !carrier_like(w) && !nouns.iter().any(|n| n == w)).collect();
+            self = self.with_words(Pos::N, &thread_nouns);
+        }
+        self.topic = topic;
+        self
+    }
+
+    fn with_style(mut self, style: Option<StyleProfile>) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Choose among `candidates` for `pos` after `sentence` (the words so far):
+    /// a topical candidate `TOPIC_BIAS` of the time when there is one, else
+    /// from the cover model for filler slots and sentence openers when it
+    /// knows a candidate, then Zipf-weighted for filler slots, otherwise
+    /// uniformly among the shortest (keeps output compact).
+    fn choose_candidate<R: Rng>(
+        &self,
+        rng: &mut R,
+        pos: Pos,
+        sentence: &[String],
+        candidates: &[&String],
+    ) -> Option<String> {
+        if let Some(topic) = &self.topic {
+            let words: Vec<&str> = candidates.iter().map(|w| w.as_str()).collect();
+            if rng.gen_bool(TOPIC_BIAS) {
+                if let Some(word) = topic.choose(rng, &words) {
+                    return Some(word.to_string());
+                }
+            }
         }
+        if let Some(model) = self.cover_model.as_ref().filter(|_| is_filler_slot(pos) || sentence.is_empty()) {
+            let previous: Vec<&str> = sentence.iter().map(String::as_str).collect();
+            let words: Vec<&str> = candidates.iter().map(|w| w.as_str()).collect();
+            if let Some(word) = model.choose(rng, &previous, &words) {
+                return Some(word.to_string());
+            }
+        }
+        if let Some(table) = self.filler.filter(|_| is_filler_slot(pos)) {
+            let words: Vec<&str> = candidates.iter().map(|w| w.as_str()).collect();
+            return table.choose(rng, &words).map(str::to_string);
+        }
+        let min_len = candidates.iter().map(|w| w.len()).min()?;
+        let shortest: Vec<&String> = candidates
+            .iter()
+            .filter(|w| w.len() == min_len)
+            .copied()
+            .collect();
+        shortest.choose(rng).map(|w| w.to_string())
     }
 
     fn with_words(mut self, pos: Pos, words: &[&str]) -> Self {
         self.by_pos
             .entry(pos)
-            .or_insert_with(Vec::new)
+            .or_default()
             .extend(words.iter().map(|w| w.to_string()));
         self
     }
 
-    fn pick_cover<R: Rng>(&self, rng: &mut R, pos: Pos, recent_words: &[&str]) -> String {
+    fn pick_cover<R: Rng>(&self, rng: &mut R, pos: Pos, sentence: &[String], recent_words: &[&str]) -> String {
         let list = self.by_pos.get(&pos).unwrap_or_else(|| {
             panic!(
                 "missing lexicon list for {:?}. Add cover words for this POS.",
@@ -122,7 +361,7 @@ impl Lexicon {
             .iter()
             .filter(|w| {
                 !self.payload_set.contains(&w.to_lowercase()) && 
-                !recent_words.iter().any(|&rw| rw == w.as_str())
+                !recent_words.contains(&w.as_str())
             })
             .collect();
 
@@ -132,30 +371,13 @@ impl Lexicon {
                 .iter()
                 .filter(|w| !self.payload_set.contains(&w.to_lowercase()))
                 .collect();
-            if fallback.is_empty() {
-                panic!("No available cover words for {:?}", pos);
-            }
-            // Prioritize shorter words in fallback too
-            let min_len = fallback.iter().map(|w| w.len()).min().unwrap_or(0);
-            let shortest_fallback: Vec<&String> = fallback
-                .iter()
-                .filter(|w| w.len() == min_len)
-                .copied()
-                .collect();
-            return shortest_fallback.choose(rng).unwrap().to_string();
+            // Prioritize shorter (or, for filler, more frequent) words in fallback too
+            return self
+                .choose_candidate(rng, pos, sentence, &fallback)
+                .unwrap_or_else(|| panic!("No available cover words for {:?}", pos));
         }
 
-        // Find the shortest length among available words
-        let min_len = available.iter().map(|w| w.len()).min().unwrap_or(0);
-        
-        // Filter to only words of the shortest length
-        let shortest_words: Vec<&String> = available
-            .iter()
-            .filter(|w| w.len() == min_len)
-            .copied()
-            .collect();
-
-        shortest_words.choose(rng).unwrap().to_string()
+        self.choose_candidate(rng, pos, sentence, &available).unwrap()
     }
 
     /// Like `pick_cover`, but allows an additional predicate to enforce lightweight grammar constraints
@@ -166,6 +388,7 @@ impl Lexicon {
         &self,
         rng: &mut R,
         pos: Pos,
+        sentence: &[String],
         recent_words: &[&str],
         predicate: F,
     ) -> Option<String> {
@@ -176,23 +399,12 @@ impl Lexicon {
             .iter()
             .filter(|w| {
                 !self.payload_set.contains(&w.to_lowercase())
-                    && !recent_words.iter().any(|&rw| rw == w.as_str())
+                    && !recent_words.contains(&w.as_str())
                     && predicate(w.as_str())
             })
             .collect();
 
-        if available.is_empty() {
-            return None;
-        }
-
-        let min_len = available.iter().map(|w| w.len()).min().unwrap_or(0);
-        let shortest: Vec<&String> = available
-            .iter()
-            .filter(|w| w.len() == min_len)
-            .copied()
-            .collect();
-
-        Some(shortest.choose(rng).unwrap().to_string())
+        self.choose_candidate(rng, pos, sentence, &available)
     }
 
 }
@@ -232,6 +444,7 @@ fn start_nonterminal_for_pos(pos: Pos) -> &'static str {
 }
 
 /// Precomputed sequences organized by start symbol and k
+#[derive(Clone)]
 struct SequenceCache {
     by_start_symbol: HashMap<String, Vec<Vec<SequenceWithProbability>>>,
 }
@@ -255,7 +468,7 @@ impl SequenceCache {
         
         let mut by_start_symbol = HashMap::new();
         let grammar_str = std::fs::read_to_string(grammar_path)?;
-        let grammar = Grammar::from_str(&grammar_str)?;
+        let grammar = Grammar::from_str(&grammar_str)?.compile();
         
         // Load sequences for all possible start symbols
         // Body grammar only uses "S" (simplified), subject grammar may have S_* variants
@@ -277,6 +490,31 @@ impl SequenceCache {
         Ok(SequenceCache { by_start_symbol })
     }
     
+    /// Like `load`, but memoized per (mode, k_max) for the life of the process,
+    /// so later variations and REPL encodes skip the precompute.
+    fn shared(mode: GenerationMode, k_max: usize, verbose: bool) -> Result<Self, Box<dyn std::error::Error>> {
+        static LOADED: OnceLock<Mutex<HashMap<(GenerationMode, usize), SequenceCache>>> = OnceLock::new();
+        let loaded = LOADED.get_or_init(Default::default);
+        if let Some(cache) = loaded.lock().unwrap().get(&(mode, k_max)) {
+            return Ok(cache.clone());
+        }
+        let cache = Self::load(mode, k_max, verbose)?;
+        loaded.lock().unwrap().insert((mode, k_max), cache.clone());
+        Ok(cache)
+    }
+
+    /// Drop every sequence that uses one of `banned` slots.
+    fn exclude_slots(&mut self, banned: &HashSet<Pos>) {
+        if banned.is_empty() {
+            return;
+        }
+        for sequences_by_k in self.by_start_symbol.values_mut() {
+            for sequences in sequences_by_k.iter_mut() {
+                sequences.retain(|s| !s.sequence.iter().any(|pos| banned.contains(pos)));
+            }
+        }
+    }
+
     /// Get sequences for a given start symbol and k
     fn get(&self, start_symbol: &str, k: usize) -> Option<&[SequenceWithProbability]> {
         self.by_start_symbol
@@ -657,11 +895,113 @@ fn pluralize_cover_noun(s: &str) -> String {
     format!("{s}s")
 }
 
+/// Constraints a filled sentence must satisfy before the encoder accepts it.
+/// Rejected sentences are refilled with fresh cover words; payload placements
+/// never change.
+#[derive(Default)]
+struct SentenceFilter {
+    /// Maximum Flesch-Kincaid grade level per sentence
+    max_grade: Option<f64>,
+    /// Sentences matching this (case-insensitive) pattern are rejected
+    avoid: Option<regex::Regex>,
+    /// POS slots that must not appear at all (no safe cover word exists)
+    banned_slots: HashSet<Pos>,
+}
+
+impl SentenceFilter {
+    /// How many times to refill a rejected sentence before keeping the best attempt
+    const MAX_ATTEMPTS: usize = 8;
+
+    fn is_active(&self) -> bool {
+        self.max_grade.is_some() || self.avoid.is_some()
+    }
+
+    /// How far a sentence is from passing (0.0 means accepted)
+    fn violation(&self, words: &[String]) -> f64 {
+        let mut violation = 0.0;
+        if let Some(max_grade) = self.max_grade {
+            let grade = readability::readability(&words.join(" ")).flesch_kincaid;
+            violation += (grade - max_grade).max(0.0);
+        }
+        if let Some(ref avoid) = self.avoid {
+            // Any avoided match outweighs a readability miss
+            violation += 100.0 * avoid.find_iter(&words.join(" ")).count() as f64;
+        }
+        violation
+    }
+}
+
+/// Combine avoided words and patterns into one case-insensitive regex.
+/// Words match whole words only; patterns are used as given.
+fn build_avoid_regex(words: &[String], patterns: &[String]) -> Result<Option<regex::Regex>, String> {
+    let alternatives: Vec<String> = words
+        .iter()
+        .map(|w| format!(r"\b{}\b", regex::escape(w.trim())))
+        .chain(patterns.iter().map(|p| format!("(?:{})", p)))
+        .collect();
+    if alternatives.is_empty() {
+        return Ok(None);
+    }
+    regex::Regex::new(&format!("(?i){}", alternatives.join("|")))
+        .map(Some)
+        .map_err(|e| format!("Invalid avoid pattern: {}", e))
+}
+
+/// Load the bundled avoid list for a language: one regex per line, '#' comments.
+fn load_avoid_patterns(language: &str) -> Result<Vec<String>, String> {
+    let path = format!("languages/{}/avoid.txt", language);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read avoid list '{}': {}", path, e))?;
+    Ok(content
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| line.to_string())
+        .collect())
+}
+
+/// `fill_slots`, refilling cover words until the sentence passes `filter`.
+/// If no attempt passes, the attempt closest to passing is kept.
+#[allow(clippy::too_many_arguments)]
+fn fill_slots_filtered<R: Rng>(
+    rng: &mut R,
+    lex: &Lexicon,
+    slots: &[Pos],
+    payload: &[PayloadTok],
+    payload_i: &mut usize,
+    prev_words: &[&str],
+    forced_placements: Option<&HashMap<usize, usize>>,
+    filter: &SentenceFilter,
+) -> Vec<String> {
+    if !filter.is_active() {
+        return fill_slots(rng, lex, slots, payload, payload_i, prev_words, None, forced_placements);
+    }
+
+    let start = *payload_i;
+    let mut best: Option<(f64, Vec<String>, usize)> = None;
+    for _ in 0..SentenceFilter::MAX_ATTEMPTS {
+        let mut attempt_i = start;
+        let words = fill_slots(rng, lex, slots, payload, &mut attempt_i, prev_words, None, forced_placements);
+        let violation = filter.violation(&words);
+        if best.as_ref().is_none_or(|(v, _, _)| violation < *v) {
+            best = Some((violation, words, attempt_i));
+        }
+        if violation == 0.0 {
+            break;
+        }
+    }
+
+    let (_, words, used_i) = best.expect("at least one fill attempt");
+    *payload_i = used_i;
+    words
+}
+
 /// Fill a slot stream with cover words + payload words (in-order).
 /// Returns (words, payload_embedded_count).
 /// `prev_words` are the last few words from the previous sentence (if any), to prevent repetition across sentences.
 /// `expected_first_pos` is the POS that should appear first (if set), used to ensure payload word placement.
 /// `forced_placements` maps slot_index -> payload_index for slots that must contain specific payload words.
+#[allow(clippy::too_many_arguments)]
 fn fill_slots<R: Rng>(
     rng: &mut R,
     lex: &Lexicon,
@@ -781,7 +1121,7 @@ fn fill_slots<R: Rng>(
                             Some(payload[*payload_i].word.as_str())
                         } else {
                             // Next word will be a cover word - actually pick it now and cache it
-                            let picked_word = lex.pick_cover(rng, *next_slot, &recent_words);
+                            let picked_word = lex.pick_cover(rng, *next_slot, &out, &recent_words);
                             word_cache.insert(i + 1, picked_word.clone());
                             // Get reference from cache (it was just inserted, so unwrap is safe)
                             word_cache.get(&(i + 1)).map(|s| s.as_str())
@@ -792,7 +1132,7 @@ fn fill_slots<R: Rng>(
                 } else if i + 1 < slots.len() {
                     // No more payload words, next will be a cover word - pick it now and cache it
                     if let Some(next_slot) = slots.get(i + 1) {
-                        let picked_word = lex.pick_cover(rng, *next_slot, &recent_words);
+                        let picked_word = lex.pick_cover(rng, *next_slot, &out, &recent_words);
                         word_cache.insert(i + 1, picked_word);
                         // Get reference from cache (it was just inserted, so unwrap is safe)
                         word_cache.get(&(i + 1)).map(|s| s.as_str())
@@ -935,49 +1275,49 @@ fn fill_slots<R: Rng>(
                             let want_transitive = next_starts_np;
 
                             let constrained = if after_modal && want_transitive {
-                                lex.pick_cover_filtered(rng, slot, &recent_words, |w| {
+                                lex.pick_cover_filtered(rng, slot, &out, &recent_words, |w| {
                                     is_bare_verb_form(w) && is_likely_transitive_verb(w)
                                 })
                             } else if after_modal {
-                                lex.pick_cover_filtered(rng, slot, &recent_words, |w| is_bare_verb_form(w))
+                                lex.pick_cover_filtered(rng, slot, &out, &recent_words, is_bare_verb_form)
                             } else if want_transitive {
-                                lex.pick_cover_filtered(rng, slot, &recent_words, |w| {
+                                lex.pick_cover_filtered(rng, slot, &out, &recent_words, |w| {
                                     is_likely_transitive_verb(w)
                                 })
                             } else {
                                 None
                             };
 
-                            constrained.unwrap_or_else(|| lex.pick_cover(rng, slot, &recent_words))
+                            constrained.unwrap_or_else(|| lex.pick_cover(rng, slot, &out, &recent_words))
                         } else if slot == Pos::To {
                             "to".to_string()
                         } else if slot == Pos::Prefix {
                             // Prefix words are always cover words (not payload)
-                            lex.pick_cover(rng, slot, &recent_words)
+                            lex.pick_cover(rng, slot, &out, &recent_words)
                         } else if slot == Pos::N {
                             let num = noun_number.get(&i).copied().unwrap_or(Number::Singular);
                             match num {
-                                Number::Singular => lex.pick_cover(rng, slot, &recent_words),
+                                Number::Singular => lex.pick_cover(rng, slot, &out, &recent_words),
                                 Number::Plural => {
                                     // Attempt a few times to find a plural that won't collide with BIP39.
                                     const MAX_TRIES: usize = 8;
                                     let mut chosen = None;
                                     for _ in 0..MAX_TRIES {
-                                        let base = lex.pick_cover(rng, slot, &recent_words);
+                                        let base = lex.pick_cover(rng, slot, &out, &recent_words);
                                         let plural = pluralize_cover_noun(&base);
                                         let plural_lc = plural.to_lowercase();
                                         if !lex.wordlist_set.contains(&plural_lc)
-                                            && !recent_words.iter().any(|&rw| rw == plural_lc.as_str())
+                                            && !recent_words.contains(&plural_lc.as_str())
                                         {
                                             chosen = Some(plural);
                                             break;
                                         }
                                     }
-                                    chosen.unwrap_or_else(|| lex.pick_cover(rng, slot, &recent_words))
+                                    chosen.unwrap_or_else(|| lex.pick_cover(rng, slot, &out, &recent_words))
                                 }
                             }
                         } else {
-                            lex.pick_cover(rng, slot, &recent_words)
+                            lex.pick_cover(rng, slot, &out, &recent_words)
                         };
                         out.push(cover_word);
                     }
@@ -997,6 +1337,7 @@ fn fill_slots<R: Rng>(
 
 /// Compute k candidates based on the length mode.
 /// Returns a vector of k values to try in order.
+#[allow(clippy::too_many_arguments)]
 fn compute_k_candidates<R: Rng>(
     rng: &mut R,
     cache: &SequenceCache,
@@ -1004,6 +1345,7 @@ fn compute_k_candidates<R: Rng>(
     k_min: usize,
     k_max: usize,
     length_mode: SentenceLengthMode,
+    style: Option<&StyleProfile>,
     require_prefix: bool,
 ) -> Vec<usize> {
     match length_mode {
@@ -1012,11 +1354,14 @@ fn compute_k_candidates<R: Rng>(
             let k_start = if require_prefix { k_min + 1 } else { k_min };
             (k_start..=k_max).collect()
         }
-        SentenceLengthMode::Natural => {
+        SentenceLengthMode::Natural | SentenceLengthMode::Styled => {
             // Natural mode: sample k from grammar's length distribution
             // Ignore k_min and sample from all available k values up to k_max
             // Start from k=1 (k=0 would be empty sequence, not useful)
             let natural_k_start = if require_prefix { 2 } else { 1 }; // k=1 can't have Prefix, need at least k=2
+            // Styled: weight each length by how often the sample uses it
+            // (k counts the Dot slot, which adds no word)
+            let style = style.filter(|_| length_mode == SentenceLengthMode::Styled);
             
             // Compute weights for each k (ignoring k_min)
             let mut k_weights: Vec<(usize, f64)> = Vec::new();
@@ -1032,6 +1377,7 @@ fn compute_k_candidates<R: Rng>(
                             .map(|seq_prob| seq_prob.probability)
                             .sum()
                     };
+                    let weight = weight * style.map_or(1.0, |profile| profile.length_weight(k - 1));
                     if weight > 0.0 {
                         k_weights.push((k, weight));
                     }
@@ -1095,6 +1441,7 @@ fn compute_k_candidates<R: Rng>(
 
 /// Generate sentences until all payload tokens are embedded.
 /// Returns (formatted_text, payload_set) where formatted_text has BIP39 words highlighted according to highlight_mode.
+#[allow(clippy::too_many_arguments)]
 fn generate_text<R: Rng>(
     rng: &mut R,
     lex: &Lexicon,
@@ -1105,6 +1452,27 @@ fn generate_text<R: Rng>(
     k_min: usize,
     k_max: usize,
     length_mode: SentenceLengthMode,
+    filter: &SentenceFilter,
+) -> (String, HashSet<String>) {
+    let mut sentences = Vec::new();
+    generate_text_traced(rng, lex, payload, highlight_mode, verbose, mode, k_min, k_max, length_mode, filter, &mut sentences)
+}
+
+/// [`generate_text`], also pushing the POS slots and the words of each
+/// sentence it keeps onto `sentences`, in order.
+#[allow(clippy::too_many_arguments)]
+fn generate_text_traced<R: Rng>(
+    rng: &mut R,
+    lex: &Lexicon,
+    payload: &[PayloadTok],
+    highlight_mode: HighlightMode,
+    verbose: bool,
+    mode: GenerationMode,
+    k_min: usize,
+    k_max: usize,
+    length_mode: SentenceLengthMode,
+    filter: &SentenceFilter,
+    sentences: &mut Vec<(Vec<Pos>, Vec<String>)>,
 ) -> (String, HashSet<String>) {
     let mut words: Vec<String> = Vec::new();
     let mut payload_i: usize = 0;
@@ -1113,7 +1481,7 @@ fn generate_text<R: Rng>(
     let payload_set: HashSet<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
 
     // Load precomputed sequences
-    let cache = match SequenceCache::load(mode, k_max, verbose) {
+    let mut cache = match SequenceCache::shared(mode, k_max, verbose) {
         Ok(c) => c,
         Err(e) => {
             eprintln!("Error loading sequence cache: {}", e);
@@ -1123,6 +1491,7 @@ fn generate_text<R: Rng>(
             panic!("Sequence cache required for new algorithm");
         }
     };
+    cache.exclude_slots(&filter.banned_slots);
 
     // For subject mode, generate a single sentence with all payload words
     // For body mode, generate multiple sentences as before
@@ -1134,8 +1503,11 @@ fn generate_text<R: Rng>(
         let mut prev_words_strings: Vec<String> = Vec::new(); // Store owned strings
         let mut sentence_count = 0;
         const MAX_SENTENCES: usize = 100; // Safety limit to prevent infinite loops
+        // Every sentence places at least one payload word (or the loop stops), so
+        // long payloads get one sentence per word at most
+        let max_sentences = MAX_SENTENCES.max(payload.len());
         
-        while current_payload_i < payload.len() && sentence_count < MAX_SENTENCES {
+        while current_payload_i < payload.len() && sentence_count < max_sentences {
             sentence_count += 1;
             
             // Get the next payload word's POS for start_symbol selection
@@ -1145,17 +1517,6 @@ fn generate_text<R: Rng>(
                 ("S", want_prefix)  // First sentence: use "S" which includes (0.3: Prefix SContent) option
             } else if current_payload_i < payload.len() {
                 let next_word = &payload[current_payload_i];
-                if next_word.allowed.is_empty() {
-                    panic!(
-                        "BUG: Payload word '{}' has no allowed POS tags!\n\
-                         This indicates a POS tagging failure. Check:\n\
-                         1. Is '{}' in bip39_POS.txt?\n\
-                         2. Does it have POS tags assigned?\n\
-                         3. Is the POS tag parsing working correctly?",
-                        next_word.word,
-                        next_word.word
-                    );
-                }
                 
                 let pos = if next_word.allowed.contains(&Pos::N) {
                     Pos::N
@@ -1168,7 +1529,9 @@ fn generate_text<R: Rng>(
                 } else if next_word.allowed.contains(&Pos::Prep) {
                     Pos::Prep
                 } else {
-                    next_word.allowed.iter().next().copied().expect("Payload word should have at least one POS tag")
+                    // Words only tagged Pron or Intj have no slot; they head for a noun
+                    // start and end up force-placed by generate_fallback_sentence
+                    next_word.allowed.first().copied().unwrap_or(Pos::N)
                 };
                 
                 let nt = start_nonterminal_for_pos(pos);
@@ -1216,6 +1579,7 @@ fn generate_text<R: Rng>(
                 k_min,
                 k_max,
                 length_mode,
+                lex.style.as_ref(),
                 want_prefix,
             );
             let mut planned = None;
@@ -1243,6 +1607,7 @@ fn generate_text<R: Rng>(
                     k_min,
                     k_max,
                     length_mode,
+                    lex.style.as_ref(),
                     false,  // Don't require prefix in fallback
                 );
                 for k in k_candidates_fallback {
@@ -1295,15 +1660,15 @@ fn generate_text<R: Rng>(
             
             // Convert prev_words_strings to slice for fill_slots
             let prev_words_refs: Vec<&str> = prev_words_strings.iter().map(|s| s.as_str()).collect();
-            let mut sentence_words = fill_slots(
-                rng, 
-                lex, 
-                &slots, 
-                payload, 
+            let mut sentence_words = fill_slots_filtered(
+                rng,
+                lex,
+                &slots,
+                payload,
                 &mut temp_payload_i,
                 &prev_words_refs,
-                None, // start_pos not needed with forced placements
-                Some(&forced_placements)
+                Some(&forced_placements),
+                filter,
             );
             
             // Update current_payload_i to reflect what was actually used
@@ -1319,9 +1684,10 @@ fn generate_text<R: Rng>(
             // Update prev_words_strings with last few words from this sentence for next iteration
             // Extract strings before appending to avoid lifetime issues
             let start_idx = sentence_words.len().saturating_sub(3);
-            prev_words_strings = sentence_words[start_idx..].iter().cloned().collect();
+            prev_words_strings = sentence_words[start_idx..].to_vec();
             
             // Append this sentence to all sentences
+            sentences.push((slots, sentence_words.clone()));
             all_sentence_words.append(&mut sentence_words);
             
             // If no progress was made, break to avoid infinite loop
@@ -1334,10 +1700,8 @@ fn generate_text<R: Rng>(
         }
         
         // Verify all payload words were embedded
-        if current_payload_i < payload.len() {
-            if verbose {
-                eprintln!("Warning: Not all payload words embedded in subject mode. Embedded {}/{} after {} sentences", current_payload_i, payload.len(), sentence_count);
-            }
+        if current_payload_i < payload.len() && verbose {
+            eprintln!("Warning: Not all payload words embedded in subject mode. Embedded {}/{} after {} sentences", current_payload_i, payload.len(), sentence_count);
         }
         
         let mut sentence_words = all_sentence_words;
@@ -1364,7 +1728,7 @@ fn generate_text<R: Rng>(
                                     // Find the payload token to get its POS tag
                                     if let Some(payload_tok) = payload.iter().find(|t| t.word.to_lowercase() == word_clean) {
                                         // Use the first allowed POS tag for madlib
-                                        if let Some(&first_pos) = payload_tok.allowed.iter().next() {
+                                        if let Some(&first_pos) = payload_tok.allowed.first() {
                                             let pos_str = match first_pos {
                                                 Pos::Det => "Det",
                                                 Pos::Adj => "Adj",
@@ -1425,19 +1789,6 @@ fn generate_text<R: Rng>(
         };
         
         let start_symbol = if let Some(next_word) = next_word {
-            // Panic if the payload word has no allowed POS tags - this indicates a POS tagging failure
-            if next_word.allowed.is_empty() {
-                panic!(
-                    "BUG: Payload word '{}' has no allowed POS tags!\n\
-                     This indicates a POS tagging failure. Check:\n\
-                     1. Is '{}' in bip39_POS.txt?\n\
-                     2. Does it have POS tags assigned?\n\
-                     3. Is the POS tag parsing working correctly?",
-                    next_word.word,
-                    next_word.word
-                );
-            }
-            
             let pos = if next_word.allowed.contains(&Pos::N) {
                 Pos::N
             } else if next_word.allowed.contains(&Pos::V) {
@@ -1449,7 +1800,8 @@ fn generate_text<R: Rng>(
             } else if next_word.allowed.contains(&Pos::Prep) {
                 Pos::Prep
             } else {
-                next_word.allowed.iter().next().copied().expect("Payload word should have at least one POS tag")
+                // Unslotted words (Pron, Intj) are force-placed by generate_fallback_sentence
+                next_word.allowed.first().copied().unwrap_or(Pos::N)
             };
             
             let nt = start_nonterminal_for_pos(pos);
@@ -1485,6 +1837,7 @@ fn generate_text<R: Rng>(
             k_min,
             k_max,
             length_mode,
+            lex.style.as_ref(),
             false,  // Body mode never requires prefix
         );
         let mut planned = None;
@@ -1512,6 +1865,7 @@ fn generate_text<R: Rng>(
                 k_min,
                 k_max,
                 length_mode,
+                lex.style.as_ref(),
                 false,  // Body mode never requires prefix
             );
             for k in k_candidates_fallback {
@@ -1550,6 +1904,7 @@ fn generate_text<R: Rng>(
                         k_min,
                         k_max,
                         length_mode,
+                        lex.style.as_ref(),
                         false,  // Body mode never requires prefix
                     );
                     for k in k_candidates_alt {
@@ -1605,15 +1960,15 @@ fn generate_text<R: Rng>(
         let max_forced_idx = forced_placements.values().max().copied().unwrap_or(payload_i_before.saturating_sub(1));
         let mut temp_payload_i = (max_forced_idx + 1).max(payload_i_before);
         
-        let mut sentence_words = fill_slots(
-            rng, 
-            lex, 
-            &slots, 
-            payload, 
+        let mut sentence_words = fill_slots_filtered(
+            rng,
+            lex,
+            &slots,
+            payload,
             &mut temp_payload_i,
             &prev_words_refs,
-            None, // start_pos not needed with forced placements
-            Some(&forced_placements)
+            Some(&forced_placements),
+            filter,
         );
         
         // Update payload_i to reflect what was actually used
@@ -1762,7 +2117,7 @@ fn generate_text<R: Rng>(
                                     // Find the payload token to get its POS tag
                                     if let Some(payload_tok) = payload.iter().find(|t| t.word.to_lowercase() == word_clean) {
                                         // Use the first allowed POS tag for madlib
-                                        if let Some(&first_pos) = payload_tok.allowed.iter().next() {
+                                        if let Some(&first_pos) = payload_tok.allowed.first() {
                                             let pos_str = match first_pos {
                                                 Pos::Det => "Det",
                                                 Pos::Adj => "Adj",
@@ -1801,6 +2156,7 @@ fn generate_text<R: Rng>(
             if !words.is_empty() {
                 // ensure previous ended with punctuation. (We put '.' on last token)
             }
+            sentences.push((slots, sentence_words.clone()));
             words.append(&mut sentence_words);
         } else {
             // Sentence contained no payload words - skip it
@@ -1832,7 +2188,7 @@ fn generate_text<R: Rng>(
                 // Find the payload token to get its POS tag
                 if let Some(payload_tok) = payload.iter().find(|t| t.word.to_lowercase() == word_clean) {
                     // Use the first allowed POS tag for madlib
-                    if let Some(&first_pos) = payload_tok.allowed.iter().next() {
+                    if let Some(&first_pos) = payload_tok.allowed.first() {
                         let pos_str = match first_pos {
                             Pos::Det => "Det",
                             Pos::Adj => "Adj",
@@ -1880,60 +2236,289 @@ fn generate_text<R: Rng>(
     (rendered_words.join(" "), payload_set)
 }
 
-/// Word wrap text to a specified line width, preserving sentence boundaries
-fn word_wrap(text: &str, width: usize) -> String {
-    let mut result = Vec::new();
-    let mut current_line = String::new();
-    
-    // Split by sentences first to preserve sentence boundaries
-    let sentences: Vec<&str> = text.split_inclusive('.').collect();
-    
-    for sentence in sentences {
-        let trimmed_sentence = sentence.trim();
-        if trimmed_sentence.is_empty() {
-            continue;
-        }
-        
-        // If adding this sentence would exceed width, start a new line
-        if !current_line.is_empty() {
-            let test_line = format!("{} {}", current_line, trimmed_sentence);
-            if test_line.len() > width {
-                result.push(current_line.clone());
-                current_line = trimmed_sentence.to_string();
-            } else {
-                current_line = test_line;
+/// The payload bits one carrier word holds (`--explain`).
+#[derive(Clone, Debug, PartialEq)]
+struct CarrierTrace {
+    /// Position among the carriers, from 0
+    index: usize,
+    /// The word's index in the wordlist, which is the bits it holds
+    value: usize,
+    /// The bits of the carrier stream (the payload after framing) it holds
+    bits: std::ops::Range<usize>,
+}
+
+/// One word of an explained sentence: the slot it fills, and its bits if it
+/// is a carrier rather than filler.
+#[derive(Clone, Debug, PartialEq)]
+struct WordTrace {
+    word: String,
+    slot: Option<Pos>,
+    carrier: Option<CarrierTrace>,
+}
+
+/// One sentence of an explained encode.
+#[derive(Clone, Debug, PartialEq)]
+struct SentenceTrace {
+    slots: Vec<Pos>,
+    /// The likeliest productions the grammar makes `slots` with, in
+    /// depth-first order, and that derivation's probability; `None` for a
+    /// fallback sentence outside the grammar
+    derivation: Option<(Vec<(String, usize)>, f64)>,
+    words: Vec<WordTrace>,
+}
+
+/// Generate the text the first variation would, without writing it anywhere,
+/// and explain it sentence by sentence: which word carries which bits of
+/// the payload, which words are filler, and which productions made the
+/// sentence.
+#[allow(clippy::too_many_arguments)]
+fn encode_explain<R: Rng>(
+    rng: &mut R,
+    lex: &Lexicon,
+    payload: &[PayloadTok],
+    mode: GenerationMode,
+    k_min: usize,
+    k_max: usize,
+    length_mode: SentenceLengthMode,
+    filter: &SentenceFilter,
+    wordlist: &[String],
+) -> (String, Vec<SentenceTrace>) {
+    let mut planned = Vec::new();
+    let (text, _) = generate_text_traced(rng, lex, payload, HighlightMode::None, false, mode, k_min, k_max, length_mode, filter, &mut planned);
+    let grammar = get_grammar(mode);
+    let bits = codec::bits_per_word(wordlist.len()).unwrap_or(0);
+    let index: HashMap<&str, usize> = wordlist.iter().enumerate().map(|(i, w)| (w.as_str(), i)).collect();
+    let mut next = 0;
+    let sentences = planned
+        .into_iter()
+        .map(|(slots, sentence_words)| {
+            // One word per slot; a Dot is punctuation on the word before it
+            let word_slots: Vec<Pos> = slots.iter().copied().filter(|&slot| slot != Pos::Dot).collect();
+            let aligned = word_slots.len() == sentence_words.len();
+            let words = sentence_words
+                .into_iter()
+                .enumerate()
+                .map(|(i, word)| {
+                    let normalized = normalize_token_for_bip39(&word);
+                    let carries = payload.get(next).is_some_and(|tok| tok.word.to_lowercase() == normalized);
+                    let carrier = carries.then(|| {
+                        let carrier = CarrierTrace {
+                            index: next,
+                            value: index.get(normalized.as_str()).copied().unwrap_or_default(),
+                            bits: next * bits..(next + 1) * bits,
+                        };
+                        next += 1;
+                        carrier
+                    });
+                    WordTrace { word, slot: aligned.then(|| word_slots[i]), carrier }
+                })
+                .collect();
+            let derivation = grammar
+                .parse_sequence("S", &slots)
+                .map(|(derivation, probability)| (derivation.productions, probability));
+            SentenceTrace { slots, derivation, words }
+        })
+        .collect();
+    (text, sentences)
+}
+
+/// Print what [`encode_explain`] found: a JSON report, or a sentence by
+/// sentence listing.
+fn print_explanation(text: &str, sentences: &[SentenceTrace], mode: GenerationMode, bits: usize, json: bool) {
+    let grammar = get_grammar(mode);
+    let productions = |sentence: &SentenceTrace| -> Option<Vec<String>> {
+        let (productions, _) = sentence.derivation.as_ref()?;
+        productions.iter().map(|(nt, idx)| grammar.production_text(nt, *idx)).collect()
+    };
+    if json {
+        let sentences: Vec<_> = sentences
+            .iter()
+            .map(|sentence| {
+                let words: Vec<_> = sentence
+                    .words
+                    .iter()
+                    .map(|word| {
+                        serde_json::json!({
+                            "word": word.word,
+                            "slot": word.slot.map(|slot| slot.name()),
+                            "carrier": word.carrier.as_ref().map(|c| serde_json::json!({
+                                "index": c.index,
+                                "value": c.value,
+                                "bits": [c.bits.start, c.bits.end],
+                            })),
+                        })
+                    })
+                    .collect();
+                serde_json::json!({
+                    "slots": sentence.slots.iter().map(|slot| slot.name()).collect::<Vec<_>>(),
+                    "productions": productions(sentence),
+                    "probability": sentence.derivation.as_ref().map(|(_, p)| p),
+                    "words": words,
+                })
+            })
+            .collect();
+        let report = serde_json::json!({ "text": text, "bits_per_word": bits, "sentences": sentences });
+        println!("{}", report);
+        return;
+    }
+
+    println!("{}", text.trim());
+    let (mut carriers, mut filler) = (0, 0);
+    for (n, sentence) in sentences.iter().enumerate() {
+        let slots: Vec<&str> = sentence.slots.iter().map(|slot| slot.name()).collect();
+        println!();
+        match (&sentence.derivation, productions(sentence)) {
+            (Some((_, probability)), Some(productions)) => {
+                println!("Sentence {}: {} (p = {:.5})", n + 1, slots.join(" "), probability);
+                for production in productions {
+                    println!("  {}", production);
+                }
             }
-        } else {
-            current_line = trimmed_sentence.to_string();
+            _ => println!("Sentence {}: {} (fallback, not derived by the grammar)", n + 1, slots.join(" ")),
         }
-        
-        // If current line exceeds width, wrap it word by word
-        if current_line.len() > width {
-            let words: Vec<String> = current_line.split_whitespace().map(|s| s.to_string()).collect();
-            current_line.clear();
-            
-            for word in words {
-                if current_line.is_empty() {
-                    current_line = word;
-                } else {
-                    let test_line = format!("{} {}", current_line, word);
-                    if test_line.len() > width {
-                        result.push(current_line.clone());
-                        current_line = word;
-                    } else {
-                        current_line = test_line;
-                    }
+        for word in &sentence.words {
+            let slot = word.slot.map_or("?", |slot| slot.name());
+            match &word.carrier {
+                Some(c) if bits > 0 => {
+                    carriers += 1;
+                    println!(
+                        "  {:<14} {:<6} carrier {}: bits {}..{} = {:0width$b}",
+                        word.word, slot, c.index, c.bits.start, c.bits.end, c.value, width = bits
+                    );
+                }
+                Some(c) => {
+                    carriers += 1;
+                    println!("  {:<14} {:<6} carrier {}: word {}", word.word, slot, c.index, c.value);
+                }
+                None => {
+                    filler += 1;
+                    println!("  {:<14} {:<6} filler", word.word, slot);
                 }
             }
         }
     }
-    
-    // Add any remaining line
-    if !current_line.is_empty() {
-        result.push(current_line);
+    let total = (carriers + filler).max(1);
+    println!();
+    println!(
+        "{} carriers of {} bits and {} filler words: {:.0}% of the words carry the payload",
+        carriers, bits, filler, 100.0 * carriers as f64 / total as f64
+    );
+}
+
+/// Interleave noise sentences with the carrier sentences of a body text (see
+/// `glossia::chaff`). With a key, the text opens with sentences holding a
+/// random nonce, chaff positions follow the keyed stream for that nonce, and
+/// each noise sentence carries about as many random wordlist words as an
+/// average carrier sentence; without one, noise sentences are cover words only.
+#[allow(clippy::too_many_arguments)]
+fn inject_chaff<R: Rng>(
+    rng: &mut R,
+    lex: &Lexicon,
+    text: &str,
+    fraction: f64,
+    passphrase: Option<&str>,
+    language: &str,
+    wordlist: Wordlist,
+    slot_map: &SlotMap,
+    highlight_mode: HighlightMode,
+    k_min: usize,
+    k_max: usize,
+    filter: &SentenceFilter,
+) -> Result<String, String> {
+    let carriers = chaff::split_sentences(text);
+    let mut out = Vec::new();
+    let layout = match passphrase {
+        Some(passphrase) => {
+            let all_words = load_payload_words(language, wordlist)?;
+            let nonce = chaff::draw_nonce(rng, all_words.len());
+            let nonce_words: Vec<PayloadTok> = nonce
+                .iter()
+                .map(|&i| PayloadTok::new(all_words[i].clone(), slot_map.slots(&all_words[i])))
+                .collect();
+            let (opening, _) = generate_text(rng, lex, &nonce_words, highlight_mode, false, GenerationMode::Body, k_min, k_max, SentenceLengthMode::Compact, filter);
+            out.push(opening);
+            chaff::chaff_layout(&mut chaff::keyed_coins(passphrase, &nonce), carriers.len(), fraction)
+        }
+        None => chaff::chaff_layout(rng, carriers.len(), fraction),
+    };
+
+    let chaff_per_sentence = if passphrase.is_some() {
+        let carrier_words = decode::extract_payload_words(text, &lex.wordlist_set).len();
+        (carrier_words as f64 / carriers.len().max(1) as f64).round().max(1.0) as usize
+    } else {
+        0
+    };
+
+    let mut cache = SequenceCache::load(GenerationMode::Body, k_max, false).map_err(|e| e.to_string())?;
+    cache.exclude_slots(&filter.banned_slots);
+
+    let mut carriers = carriers.into_iter();
+    for is_chaff in layout {
+        if !is_chaff {
+            out.extend(carriers.next());
+            continue;
+        }
+        let chaff_words: Vec<PayloadTok> = select_random_words(rng, chaff_per_sentence, language, wordlist)?
+            .into_iter()
+            .map(|word| {
+                let tags = slot_map.slots(&word);
+                PayloadTok::new(word, tags)
+            })
+            .collect();
+        out.push(noise_sentence(rng, lex, &cache, &chaff_words, highlight_mode, k_min, k_max, filter));
     }
-    
-    result.join("\n")
+    Ok(out.join(" "))
+}
+
+/// One body sentence of cover words, embedding whichever of `chaff` fit in order.
+#[allow(clippy::too_many_arguments)]
+fn noise_sentence<R: Rng>(
+    rng: &mut R,
+    lex: &Lexicon,
+    cache: &SequenceCache,
+    chaff: &[PayloadTok],
+    highlight_mode: HighlightMode,
+    k_min: usize,
+    k_max: usize,
+    filter: &SentenceFilter,
+) -> String {
+    // Noise sentences stay short-to-medium so they don't stand out
+    let k_hi = k_max.clamp(k_min, 10);
+    let slots = loop {
+        let k = rng.gen_range(k_min..=k_hi);
+        if let Some(sequences) = cache.get("S", k).filter(|s| !s.is_empty()) {
+            if let Ok(chosen) = sequences.choose_weighted(rng, |s| s.probability) {
+                break chosen.sequence.clone();
+            }
+        }
+    };
+
+    let mut chaff_i = 0;
+    let mut words = fill_slots_filtered(rng, lex, &slots, chaff, &mut chaff_i, &[], None, filter);
+    if let Some(first) = words.first_mut() {
+        *first = capitalize(first);
+    }
+    if let Some(last) = words.last_mut() {
+        if !last.ends_with('.') {
+            last.push('.');
+        }
+    }
+
+    let chaff_set: HashSet<String> = chaff.iter().map(|t| t.word.to_lowercase()).collect();
+    words
+        .iter()
+        .map(|word| {
+            if !chaff_set.contains(&normalize_token_for_bip39(word)) {
+                return word.clone();
+            }
+            match highlight_mode {
+                HighlightMode::Bars => wrap_payload_with_bars(word),
+                HighlightMode::Color(color) => wrap_payload_with_color(word, color),
+                HighlightMode::None | HighlightMode::Madlib => word.clone(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 fn capitalize(s: &str) -> String {
@@ -1945,26 +2530,9 @@ fn capitalize(s: &str) -> String {
 }
 
 fn normalize_token_for_bip39(s: &str) -> String {
-    // Decoder is case-insensitive; BIP39 words are lowercase ASCII a-z.
-    // Strip ANSI escape codes (e.g., \x1b[1m, \x1b[0m), highlighting bars (|), and punctuation.
-    let mut result = s.to_string();
-    
-    // Remove ANSI escape codes: ESC[ followed by digits and 'm'
-    // Pattern: \x1b[ or ESC[ followed by optional digits and 'm'
-    result = regex::Regex::new(r"\x1b\[[0-9;]*m")
-        .unwrap()
-        .replace_all(&result, "")
-        .to_string();
-    
-    // Remove highlighting bars
-    result = result.replace('|', "");
-    
-    // Strip leading/trailing non-letters to tolerate punctuation/quotes
-    result = result.trim()
-        .trim_matches(|c: char| !c.is_ascii_alphabetic())
-        .to_lowercase();
-    
-    result
+    // Decoder is case-insensitive; BIP39 words are lowercase ASCII a-z (or Han and kana).
+    // Strips ANSI escape codes, highlighting bars (|), and punctuation.
+    decode::normalize_token(s)
 }
 
 fn wrap_payload_with_bars(word_with_punct: &str) -> String {
@@ -1974,7 +2542,7 @@ fn wrap_payload_with_bars(word_with_punct: &str) -> String {
     let mut core = word_with_punct.to_string();
     let mut suffix = String::new();
     while let Some(last) = core.chars().last() {
-        if last.is_ascii_alphabetic() {
+        if last.is_ascii_alphabetic() || segment::is_unspaced_char(last) {
             break;
         }
         core.pop();
@@ -1991,7 +2559,7 @@ fn wrap_payload_with_color(word_with_punct: &str, color_code: u8) -> String {
     let mut core = word_with_punct.to_string();
     let mut suffix = String::new();
     while let Some(last) = core.chars().last() {
-        if last.is_ascii_alphabetic() {
+        if last.is_ascii_alphabetic() || segment::is_unspaced_char(last) {
             break;
         }
         core.pop();
@@ -2000,107 +2568,42 @@ fn wrap_payload_with_color(word_with_punct: &str, color_code: u8) -> String {
     format!("\x1b[{}m{core}\x1b[0m{suffix}", color_code)
 }
 
-/// Build comprehensive POS mapping for all BIP39 words.
-/// Returns a HashMap mapping each word to its allowed POS tags.
-/// Uses YAML format (word -> POS weights) from payload.yaml.
-fn build_pos_mapping(language: &str) -> Result<HashMap<String, Vec<Pos>>, String> {
-    let wordlist_path = get_wordlist_path(language)?;
-    build_pos_mapping_from_yaml(&wordlist_path)
+/// Share of listed carriers that `--prefixes` writes with a prefix
+const PREFIX_RATE: f64 = 0.3;
+
+/// Slots narrower than this (after `--slot-threshold`) reject the threshold:
+/// the few words left would repeat conspicuously across outputs.
+const MIN_SLOT_WORDS: usize = 16;
+
+/// Load and validate the POS weights of a language's payload wordlist (payload.yaml).
+fn load_payload_weights(language: &str, wordlist: Wordlist) -> Result<PosWeights, String> {
+    let wordlist_path = get_wordlist_path(language, wordlist)?;
+    weights::load(&wordlist_path).map_err(|e| format!("Invalid weight file '{}': {}", wordlist_path, e))
 }
 
-/// Build POS mapping from YAML format.
-/// YAML structure: { word: { POS: weight, ... }, ... }
-/// Weights are normalized and POS tags are extracted.
-fn build_pos_mapping_from_yaml(path: &str) -> Result<HashMap<String, Vec<Pos>>, String> {
-    let yaml_content = std::fs::read_to_string(path)
-        .map_err(|e| format!("Failed to read YAML file '{}': {}", path, e))?;
-    
-    // Parse YAML as HashMap<String, HashMap<String, f64>>
-    // Outer key = word, inner key = POS tag, value = weight
-    let yaml_data: HashMap<String, HashMap<String, f64>> = serde_yaml::from_str(&yaml_content)
-        .map_err(|e| format!("Failed to parse YAML file '{}': {}", path, e))?;
-    
-    let mut mapping = HashMap::new();
-    
-    for (word, pos_weights) in yaml_data {
-        let word_lower = word.to_lowercase();
-        let mut pos_tags = Vec::new();
-        
-        // Extract POS tags (weights are normalized later if needed)
-        // For now, we just collect all POS tags with non-zero weights
-        for (pos_str, weight) in pos_weights {
-            if weight > 0.0 {
-                if let Some(pos) = parse_pos_tag(&pos_str) {
-                    pos_tags.push(pos);
-                }
-            }
-        }
-        
-        // Only add words with valid POS tags
-        if !word_lower.is_empty() && !pos_tags.is_empty() {
-            mapping.insert(word_lower, pos_tags);
-        }
-    }
-    
-    Ok(mapping)
-}
-
-/// Parse a single POS tag string to Pos enum.
-fn parse_pos_tag(pos_str: &str) -> Option<Pos> {
-    match pos_str.trim() {
-        "Det" => Some(Pos::Det),
-        "Adj" => Some(Pos::Adj),
-        "N" => Some(Pos::N),
-        "V" => Some(Pos::V),
-        "Modal" => Some(Pos::Modal),
-        "Aux" => Some(Pos::Aux),
-        "Cop" => Some(Pos::Cop),
-        "To" => Some(Pos::To),
-        "Prep" => Some(Pos::Prep),
-        "Adv" => Some(Pos::Adv),
-        "Conj" => Some(Pos::Conj),
-        "Dot" => Some(Pos::Dot),
-        "Prefix" => Some(Pos::Prefix),
-        _ => None,
-    }
-}
-
-/// Get POS tags for a word from the comprehensive mapping.
-/// Returns a vector of allowed POS tags.
+/// The POS slots each payload word may fill, given `--slot-threshold` (the
+/// minimum POS weight for a word to fill a slot).
 /// Used for tagging payload (BIP39) words. Cover words use explicit POS tags from cover.yaml.
-fn tag_word(word: &str) -> Vec<Pos> {
+fn payload_slots(wordlist: Wordlist, threshold: f64) -> SlotMap {
     // For now, use English POS mapping for tagging payload words
     // TODO: Make this language-aware if needed
-    static POS_MAP: OnceLock<HashMap<String, Vec<Pos>>> = OnceLock::new();
-    
-    let mapping = POS_MAP.get_or_init(|| {
-        // Default to English for POS tagging
-        build_pos_mapping("english").unwrap_or_else(|_| HashMap::new())
-    });
-    
-    let word_lower = word.to_lowercase();
-    // Require explicit POS tags - return empty if word not found
-    mapping.get(&word_lower).cloned().unwrap_or_default()
+    load_payload_weights("english", wordlist)
+        .map(|pos_weights| SlotMap::new(&pos_weights, threshold))
+        .unwrap_or_default()
 }
 
 /// Load all payload words from the wordlist file.
 /// Uses YAML format (extracts keys) from payload.yaml.
-fn load_payload_words(language: &str) -> Result<Vec<String>, String> {
-    let wordlist_path = get_wordlist_path(language)?;
+fn load_payload_words(language: &str, wordlist: Wordlist) -> Result<Vec<String>, String> {
+    let wordlist_path = get_wordlist_path(language, wordlist)?;
     load_payload_words_from_yaml(&wordlist_path)
 }
 
 /// Load payload words from YAML file (extracts keys).
 fn load_payload_words_from_yaml(path: &str) -> Result<Vec<String>, String> {
-    let yaml_content = std::fs::read_to_string(path)
-        .map_err(|e| format!("Failed to read YAML file '{}': {}", path, e))?;
-    
-    // Parse YAML as HashMap<String, HashMap<String, f64>>
-    // Extract just the keys (words)
-    let yaml_data: HashMap<String, HashMap<String, f64>> = serde_yaml::from_str(&yaml_content)
-        .map_err(|e| format!("Failed to parse YAML file '{}': {}", path, e))?;
+    let pos_weights = weights::load(path).map_err(|e| format!("Invalid weight file '{}': {}", path, e))?;
     
-    let mut words: Vec<String> = yaml_data.keys().cloned().collect();
+    let mut words: Vec<String> = pos_weights.words().map(str::to_string).collect();
     words.sort(); // Keep consistent ordering
     Ok(words)
 }
@@ -2110,21 +2613,15 @@ fn load_payload_words_from_yaml(path: &str) -> Result<Vec<String>, String> {
 /// Returns a HashMap mapping POS to Vec of words
 fn load_cover_words_by_pos(wordlist_set: &HashSet<String>, language: &str) -> HashMap<Pos, Vec<String>> {
     let cover_yaml_path = format!("languages/{}/cover.yaml", language);
-    let yaml_content = std::fs::read_to_string(&cover_yaml_path)
-        .unwrap_or_else(|e| {
-            eprintln!("Error: Failed to read cover.yaml from '{}': {}", cover_yaml_path, e);
-            std::process::exit(1);
-        });
-    
-    let yaml_data: HashMap<String, HashMap<String, f64>> = serde_yaml::from_str(&yaml_content)
+    let pos_weights = weights::load(&cover_yaml_path)
         .unwrap_or_else(|e| {
-            eprintln!("Error: Failed to parse cover.yaml as YAML: {}", e);
+            eprintln!("Error: Invalid cover.yaml '{}': {}", cover_yaml_path, e);
             std::process::exit(1);
         });
     
     let mut by_pos: HashMap<Pos, Vec<String>> = HashMap::new();
     
-    for (word, pos_weights) in yaml_data {
+    for word in pos_weights.words() {
         let word_lower = word.to_lowercase();
         
         // Skip if word is in wordlist set
@@ -2133,12 +2630,8 @@ fn load_cover_words_by_pos(wordlist_set: &HashSet<String>, language: &str) -> Ha
         }
         
         // Extract POS tags with non-zero weights
-        for (pos_str, weight) in pos_weights {
-            if weight > 0.0 {
-                if let Some(pos) = parse_pos_tag(&pos_str) {
-                    by_pos.entry(pos).or_insert_with(Vec::new).push(word.clone());
-                }
-            }
+        for pos in pos_weights.pos_tags(word) {
+            by_pos.entry(pos).or_default().push(word.to_string());
         }
     }
     
@@ -2151,77 +2644,499 @@ fn load_cover_words_by_pos(wordlist_set: &HashSet<String>, language: &str) -> Ha
     by_pos
 }
 
+/// Build the cover lexicon from cover.yaml words (with runtime defaults for
+/// any POS it lacks), checking that no cover word is a wordlist word.
+fn build_lexicon(
+    cover_by_pos: &HashMap<Pos, Vec<String>>,
+    payload_set: HashSet<String>,
+    wordlist_set: HashSet<String>,
+    verbose: bool,
+) -> Lexicon {
+    // Extract function words from cover.yaml (with runtime defaults if missing)
+    let det_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Det)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["the", "a", "an", "each", "some"]);
+    
+    let modal_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Modal)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["should", "could", "would", "might", "may"]);
+    
+    let aux_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Aux)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["do", "does"]);
+    
+    let cop_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Cop)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["is", "are"]);
+    
+    let to_words: Vec<&str> = cover_by_pos
+        .get(&Pos::To)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["to"]);
+
+    let conj_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Conj)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["and", "but", "or"]);
+    
+    // Subject grammar can emit Prefix (e.g., "re", "fwd") so ensure we always have
+    // some safe defaults even if cover.yaml doesn't provide them.
+    let prefix_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Prefix)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["re", "fwd", "fw", "update"]);
+    
+    // Extract content words from cover.yaml (with runtime defaults if missing)
+    let adj_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Adj)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["clear", "simple", "bright", "quiet", "steady"]);
+    
+    let n_words: Vec<&str> = cover_by_pos
+        .get(&Pos::N)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["user", "note", "server", "system"]);
+    
+    let v_words: Vec<&str> = cover_by_pos
+        .get(&Pos::V)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["check", "send", "hold", "verify", "process"]);
+    
+    let prep_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Prep)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["about", "above", "along", "beneath", "throughout"]);
+    
+    let adv_words: Vec<&str> = cover_by_pos
+        .get(&Pos::Adv)
+        .map(|v| v.iter().map(|s| s.as_str()).collect())
+        .unwrap_or_else(|| vec!["soon", "well", "quite", "very"]);
+    
+    // Validate all cover words against BIP39 wordlist
+    let all_cover_words: Vec<&str> = det_words.iter()
+        .chain(modal_words.iter())
+        .chain(aux_words.iter())
+        .chain(cop_words.iter())
+        .chain(to_words.iter())
+        .chain(conj_words.iter())
+        .chain(prefix_words.iter())
+        .chain(adj_words.iter())
+        .chain(n_words.iter())
+        .chain(v_words.iter())
+        .chain(prep_words.iter())
+        .chain(adv_words.iter())
+        .copied()
+        .collect();
+    
+    validate_cover_words(&all_cover_words, &wordlist_set);
+    
+    if verbose {
+        eprintln!("Loaded cover words from cover.yaml:");
+        eprintln!("  Adjectives: {}", adj_words.len());
+        eprintln!("  Nouns: {}", n_words.len());
+        eprintln!("  Verbs: {}", v_words.len());
+        eprintln!("  Prepositions: {}", prep_words.len());
+        eprintln!("  Adverbs: {}", adv_words.len());
+        if !prefix_words.is_empty() {
+            eprintln!("  Prefixes: {}", prefix_words.len());
+        }
+    }
+    
+    Lexicon::new(payload_set, wordlist_set)
+        .with_words(Pos::Det, &det_words)
+        .with_words(Pos::Modal, &modal_words)
+        .with_words(Pos::Aux, &aux_words)
+        .with_words(Pos::Cop, &cop_words)
+        .with_words(Pos::To, &to_words)
+        .with_words(Pos::Conj, &conj_words)
+        .with_words(Pos::Prefix, &prefix_words)
+        .with_words(Pos::Adj, &adj_words)
+        .with_words(Pos::N, &n_words)
+        .with_words(Pos::V, &v_words)
+        .with_words(Pos::Prep, &prep_words)
+        .with_words(Pos::Adv, &adv_words)
+        .with_filler(frequency::english())
+}
+
 /// Encode ASCII text to wordlist words using bit-packing.
 /// If wordlist has 2048 words (11 bits), we can pack bytes efficiently:
 /// - 1 word = 11 bits
 /// - 1 ASCII byte = 8 bits
 /// - So we can pack 1 byte per word (with 3 bits unused) or pack multiple bytes across words
+///
 /// For simplicity and to maximize efficiency, we pack bytes across words:
 /// - 11 bits per word means we can pack 1 byte + 3 bits from next byte
 /// - This gives us ~1.375 bytes per word on average
-fn encode_ascii_to_words(ascii_text: &str, language: &str) -> Result<Vec<String>, String> {
-    let all_words = load_payload_words(language)?;
-    if all_words.is_empty() {
-        return Err("Wordlist is empty".to_string());
+fn encode_ascii_to_words(ascii_text: &SecretPayload, language: &str, wordlist: Wordlist) -> Result<Vec<String>, String> {
+    if wordlist == Wordlist::Phonetic {
+        return Ok(phonetic::encode_bytes(ascii_text.expose()));
     }
-    
-    let word_count = all_words.len();
-    let bits_per_word = (word_count as f64).log2().floor() as usize;
-    
-    // If wordlist is not a power of 2, fall back to simple byte-per-word encoding
-    if word_count != (1 << bits_per_word) {
-        // Simple encoding: 1 byte per word
-        let bytes = ascii_text.as_bytes();
-        let mut words = Vec::with_capacity(bytes.len());
-        for &byte in bytes {
-            let index = byte as usize % word_count;
-            words.push(all_words[index].clone());
+    let all_words = load_payload_words(language, wordlist)?;
+    codec::encode_bytes(ascii_text.expose(), &all_words).map_err(|e| e.to_string())
+}
+
+/// How `--decode` should read a cover text. The default reads plain prose
+/// carriers as space-separated words.
+#[derive(Default)]
+struct DecodeOptions {
+    /// Map transcribed homophones back to wordlist words
+    homophone_safe: bool,
+    /// Strip `--prefixes` prefixes from carriers ("unsafe" -> "safe")
+    prefixes: bool,
+    /// Check and drop the `--parity` marker ending each sentence
+    parity: bool,
+    /// Verify and strip a trailing checksum word
+    checksum: bool,
+    /// Search for garbled carriers (implies a checksum)
+    noise: Option<NoiseModel>,
+    /// Spelling dictionary telling filler words from misread carriers (`--noise ocr`)
+    dictionary: Option<PathBuf>,
+    /// Read inflected tokens ("running", "ran") as their wordlist lemma
+    lemmatize: bool,
+    /// Zero-fill unreadable words and report the bytes lost instead of failing
+    lossy: bool,
+    /// Look carriers up with `WordIndex::get_constant_time`
+    constant_time: bool,
+    /// Read carriers only from the blanks of these `--template`s
+    template: Option<TemplateSet>,
+    /// Read carriers from the lines of a list style, past its scaffolding
+    output_style: Style,
+    /// Read the words back from emoji instead of prose
+    engine: Engine,
+    /// Read the payload from the model's token choices (`--engine neural`)
+    #[cfg(feature = "neural")]
+    neural: Option<NeuralSettings>,
+    /// Unpack the words back to the `--from-ascii` text
+    ascii: bool,
+    /// Restore a `--data-mode` packed payload to the exact input
+    data_mode: bool,
+    /// The `--wordlist` the carriers come from
+    wordlist: Wordlist,
+    /// Key for `deniable` and keyed chaff
+    passphrase: Option<String>,
+    /// Unlock one payload of a `--decoy` carrier
+    deniable: bool,
+    /// Drop keyed chaff sentences first (ignored without a passphrase, where
+    /// chaff carries no wordlist words)
+    chaff_fraction: Option<f64>,
+    /// Undo `--whiten` (keyed by `passphrase` if set)
+    whiten: bool,
+    /// Check and strip the `--commit` tag of a whitened payload
+    commit: bool,
+    /// Undo `--interleave`, at the depth its header word gives
+    interleave: bool,
+    /// Require a valid `--sign` signature from this key
+    verify: Option<VerifyingKey>,
+    /// Apply the decoded `--patch-base` patch to these bytes
+    patch_base: Option<Vec<u8>>,
+    /// Read the `--timestamp`/`--expires` validity stamp off the payload
+    timestamp: bool,
+    /// Fail on a validity stamp that has expired (implies `timestamp`)
+    reject_expired: bool,
+    /// Read carriers up to the last intact `--resync` marker, this many apart
+    resync: Option<usize>,
+}
+
+/// Recover the payload from a cover text: space-separated words, or the ASCII text.
+fn decode_cover_text(
+    cover_text: &str,
+    language: &str,
+    options: &DecodeOptions,
+    verbose: bool,
+) -> Result<String, String> {
+    let mut bytes = decode_cover_bytes(cover_text, language, options, verbose).map_err(|e| e.to_string())?;
+    let text = String::from_utf8_lossy(&bytes).into_owned();
+    bytes.wipe();
+    Ok(text)
+}
+
+/// Like `decode_cover_text`, but returns the payload bytes unchanged.
+fn decode_cover_bytes(
+    cover_text: &str,
+    language: &str,
+    options: &DecodeOptions,
+    verbose: bool,
+) -> anyhow::Result<Vec<u8>> {
+    decode_cover_report(cover_text, language, options, verbose).map(|report| report.bytes)
+}
+
+/// What `decode_cover_report` read from a cover text.
+struct DecodeReport {
+    /// The payload bytes
+    bytes: Vec<u8>,
+    /// The payload's validity stamp, when `DecodeOptions::timestamp` read one
+    validity: Option<validity::Validity>,
+}
+
+/// Like `decode_cover_bytes`, with what the payload said about itself.
+fn decode_cover_report(
+    cover_text: &str,
+    language: &str,
+    options: &DecodeOptions,
+    verbose: bool,
+) -> anyhow::Result<DecodeReport> {
+    let all_words = load_payload_words(language, options.wordlist).map_err(anyhow::Error::msg)?;
+    let unfooted = footer::strip(cover_text);
+    let cover_text = unfooted.as_ref();
+
+    // Prose may have been hard-wrapped or retyped on the way (PDF, email); layouts are line-based
+    let unwrapped;
+    let cover_text = if options.engine == Engine::Prose && options.template.is_none() && options.output_style == Style::Prose {
+        unwrapped = normalize::clean(cover_text, NormalizeOptions::default());
+        unwrapped.as_str()
+    } else {
+        cover_text
+    };
+
+    let stripped;
+    let cover_text = match (options.chaff_fraction, &options.passphrase) {
+        (Some(fraction), Some(passphrase)) => {
+            stripped = chaff::strip_chaff(cover_text, fraction, passphrase, &all_words)?;
+            stripped.as_str()
+        }
+        _ => cover_text,
+    };
+
+    if let (true, Some(passphrase)) = (options.deniable, &options.passphrase) {
+        let wordlist_set: HashSet<String> = all_words.iter().cloned().collect();
+        let corrections = decode_corrections(language, options, &wordlist_set)?;
+        let words = decode::extract_payload_words_with_corrections(cover_text, &wordlist_set, &corrections);
+        let bytes = deniable::decode_deniable(&words, passphrase, &all_words)?;
+        if verbose {
+            eprintln!("Unlocked {} bytes from {} carrier words", bytes.len(), words.len());
         }
-        return Ok(words);
+        return Ok(DecodeReport { bytes, validity: None });
     }
-    
-    // Efficient bit-packing: pack bytes into words
-    // bits_per_word bits per word (e.g., 11 bits for 2048 words)
-    let bytes = ascii_text.as_bytes();
-    let mut words = Vec::new();
-    let mut bit_buffer: u32 = 0;
-    let mut bits_in_buffer = 0;
-    
-    for &byte in bytes {
-        // Add 8 bits from this byte to the buffer
-        bit_buffer = (bit_buffer << 8) | (byte as u32);
-        bits_in_buffer += 8;
-        
-        // While we have enough bits for a word, extract words
-        while bits_in_buffer >= bits_per_word {
-            // Extract the top bits_per_word bits
-            let bits_to_extract = bits_per_word;
-            let shift = bits_in_buffer - bits_to_extract;
-            let word_index = ((bit_buffer >> shift) & ((1u32 << bits_to_extract) - 1)) as usize;
-            
-            words.push(all_words[word_index].clone());
-            
-            // Remove the extracted bits
-            bit_buffer &= (1u32 << shift) - 1;
-            bits_in_buffer -= bits_to_extract;
+
+    #[cfg(feature = "neural")]
+    let neural = options.neural.as_ref().map(|settings| decode_neural(settings, cover_text, verbose)).transpose()?;
+    #[cfg(not(feature = "neural"))]
+    let neural: Option<Vec<u8>> = None;
+
+    // Plain ASCII payloads stream from the text straight into bytes
+    let streamed = options.ascii && options.noise.is_none() && !options.homophone_safe && !options.prefixes && !options.parity && !options.checksum && !options.whiten && !options.interleave && !options.lemmatize && !options.lossy
+        && options.template.is_none() && options.output_style == Style::Prose && options.engine == Engine::Prose
+        && options.wordlist == Wordlist::Bip39;
+    let mut bytes = if let Some(bytes) = neural {
+        bytes
+    } else if let Some(every) = options.resync {
+        let recovery = resync::recover(cover_text, &all_words, every)?;
+        if verbose || !recovery.is_complete() {
+            eprintln!("Markers vouch for {} of {} payload bytes", recovery.resume_at(), recovery.total);
+        }
+        if !recovery.is_complete() {
+            eprintln!("Resend the rest with --resync {} --resume-from {}", every, recovery.resume_at());
+        }
+        recovery.into_bytes()?
+    } else if options.constant_time {
+        let index = WordIndex::shared(&all_words)?;
+        let mut indices = decode::payload_indices_constant_time(cover_text, &index)?;
+        let body = if options.checksum { codec::strip_checksum_indices(&indices, &all_words) } else { Ok(indices.as_slice()) };
+        let bytes = body.and_then(|body| codec::decode_indices(body, all_words.len()));
+        if verbose {
+            eprintln!("Decoded {} payload words in constant time", indices.len());
+        }
+        indices.wipe();
+        bytes?
+    } else if streamed {
+        let mut bytes = Vec::new();
+        let count = codec::decode_text_into(cover_text, &all_words, &mut bytes)?;
+        if verbose {
+            eprintln!("Decoded {} payload words", count);
+        }
+        bytes
+    } else {
+        let decoded = if let Some(model) = options.noise {
+            let dictionary = options.dictionary.as_deref().map(spelling::load).transpose()?;
+            let report = noisy::decode_noisy_report(cover_text, &all_words, model, dictionary.as_deref())?;
+            if verbose {
+                for word in report.uncertain() {
+                    eprintln!(
+                        "Read '{}' as '{}' at byte {} ({} match, p={:.2}); check it against the source",
+                        word.token, word.word, word.span.start, word.kind.name(), word.probability
+                    );
+                }
+            }
+            report.words.into_iter().map(|word| word.word).collect()
+        } else {
+            let wordlist_set: HashSet<String> = all_words.iter().cloned().collect();
+            let corrections = decode_corrections(language, options, &wordlist_set)?;
+            if options.lemmatize {
+                let lemmatizer = lemma::default_lemmatizer();
+                lemma::decode_lemmatized(cover_text, &all_words, lemmatizer.as_ref(), &corrections, options.checksum)?
+            } else {
+                let words = match (options.engine, &options.template, options.output_style) {
+                    (Engine::Emoji, _, _) => emoji::decode(cover_text, &all_words)?,
+                    (Engine::Prose, Some(templates), _) => templates.extract(cover_text, &wordlist_set, &corrections)?,
+                    (Engine::Prose, None, Style::Prose) if options.parity => {
+                        parity::check(cover_text, &all_words, &corrections)?
+                    }
+                    (Engine::Prose, None, Style::Prose) => {
+                        decode::extract_payload_words_with_corrections(cover_text, &wordlist_set, &corrections)
+                    }
+                    (Engine::Prose, None, list_style) => list_style.extract(cover_text)?,
+                    #[cfg(feature = "neural")]
+                    (Engine::Neural, _, _) => unreachable!("the model reads neural cover texts above"),
+                };
+                if options.checksum && !options.lossy {
+                    codec::strip_checksum(&words, &all_words)?
+                } else {
+                    words
+                }
+            }
+        };
+        if verbose {
+            eprintln!("Decoded {} payload words", decoded.len());
+        }
+
+        // Phonetic words carry a byte each; check their alternation even when printing words
+        let phonetic_bytes = match options.wordlist {
+            Wordlist::Phonetic => Some(phonetic::decode_words(&decoded)?),
+            Wordlist::Bip39 => None,
+        };
+
+        if !options.ascii {
+            return Ok(DecodeReport { bytes: decoded.join(" ").into_bytes(), validity: None });
+        }
+
+        if let Some(bytes) = phonetic_bytes {
+            bytes
+        } else if options.whiten {
+            if options.commit {
+                whiten::decode_committed(&decoded, &all_words, options.passphrase.as_deref())?
+            } else {
+                whiten::decode_whitened(&decoded, &all_words, options.passphrase.as_deref())?
+            }
+        } else if options.interleave {
+            interleave::decode_interleaved(&decoded, &all_words)?
+        } else if options.lossy {
+            let lossy = codec::decode_lossy(&decoded, &all_words, options.checksum)?;
+            for &position in &lossy.restored {
+                eprintln!("Filled in unreadable word {} ('{}') from the checksum", position + 1, decoded[position]);
+            }
+            for span in &lossy.errors {
+                eprintln!("Warning: bytes {}..{} could not be recovered (unreadable bits read as zeros)", span.start, span.end);
+            }
+            lossy.bytes
+        } else {
+            codec::decode_bytes(&decoded, &all_words)?
+        }
+    };
+
+    if let Some(pubkey) = &options.verify {
+        let verified = signing::verify_bytes(&bytes, pubkey);
+        bytes.wipe();
+        bytes = verified?;
+        if verbose {
+            eprintln!("Signature verified for {}", signing::npub(pubkey));
         }
     }
-    
-    // If there are remaining bits, pad and encode the last word
-    if bits_in_buffer > 0 {
-        // Pad with zeros to make a complete word
-        let padding = bits_per_word - bits_in_buffer;
-        bit_buffer <<= padding;
-        let word_index = bit_buffer as usize % word_count;
-        words.push(all_words[word_index].clone());
+
+    let mut validity = None;
+    if options.timestamp || options.reject_expired {
+        let stamped = validity::read_stamp(&bytes).map(|(stamp, payload)| (stamp, payload.to_vec()));
+        bytes.wipe();
+        let (stamp, payload) = stamped?;
+        bytes = payload;
+        if options.reject_expired {
+            stamp.check(unix_now())?;
+        }
+        validity = Some(stamp);
     }
-    
-    Ok(words)
+
+    if let Some(base) = &options.patch_base {
+        let applied = patch::apply(base, &bytes);
+        bytes.wipe();
+        bytes = applied?;
+        if verbose {
+            eprintln!("Applied the patch to the {}-byte base", base.len());
+        }
+    }
+
+    if options.data_mode {
+        if let (true, Ok(framed)) = (verbose, data_mode::unpack_framed(&bytes)) {
+            eprintln!("Framed {} payload, schema id {}", framed.framing.name(), framed.schema_id);
+        }
+        let unpacked = data_mode::unpack(&bytes);
+        bytes.wipe();
+        bytes = unpacked?;
+    }
+
+    Ok(DecodeReport { bytes, validity })
+}
+
+/// Write each container entry to `dir/name`, returning the paths and sizes.
+/// Names that are not a plain file name are refused before anything is written.
+fn write_container(dir: &Path, entries: &IndexMap<String, Vec<u8>>) -> Result<Vec<(PathBuf, usize)>, String> {
+    if let Some(name) = entries.keys().find(|name| name.contains(['/', '\\']) || *name == "." || *name == "..") {
+        return Err(format!("Container entry '{}' is not a plain file name", name));
+    }
+    std::fs::create_dir_all(dir).map_err(|e| format!("Cannot create {}: {}", dir.display(), e))?;
+    let mut written = Vec::new();
+    for (name, bytes) in entries {
+        let path = dir.join(name);
+        std::fs::write(&path, bytes).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+        written.push((path, bytes.len()));
+    }
+    Ok(written)
+}
+
+/// Load the homophone table for a language (languages/{language}/homophones.txt).
+fn load_homophones(language: &str) -> Result<HomophoneTable, String> {
+    let path = format!("languages/{}/homophones.txt", language);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read homophone table '{}': {}", path, e))?;
+    Ok(HomophoneTable::parse(&content))
+}
+
+/// Token -> wordlist word fixes applied before lookup: transcribed homophones
+/// (`--homophone-safe`) and prefixed carriers (`--prefixes`).
+fn decode_corrections(
+    language: &str,
+    options: &DecodeOptions,
+    wordlist_set: &HashSet<String>,
+) -> anyhow::Result<HashMap<String, String>> {
+    let mut corrections = HashMap::new();
+    if options.homophone_safe {
+        corrections.extend(load_homophones(language).map_err(anyhow::Error::msg)?.corrections(wordlist_set));
+    }
+    if options.prefixes {
+        corrections.extend(load_prefixes(language).map_err(anyhow::Error::msg)?.corrections(wordlist_set));
+    }
+    Ok(corrections)
+}
+
+/// Load the prefix rules for a language (languages/{language}/prefixes.txt).
+fn load_prefixes(language: &str) -> Result<PrefixRules, String> {
+    let path = format!("languages/{}/prefixes.txt", language);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read prefix rules '{}': {}", path, e))?;
+    PrefixRules::parse(&content).map_err(|e| format!("Invalid prefix rules '{}': {}", path, e))
+}
+
+/// Load the synonym table for a language (languages/{language}/synonyms.txt),
+/// without the sets that contain wordlist words.
+fn load_synonyms(language: &str, wordlist: Wordlist) -> Result<SynonymTable, String> {
+    let path = format!("languages/{}/synonyms.txt", language);
+    let content = std::fs::read_to_string(&path)
+        .map_err(|e| format!("Failed to read synonym table '{}': {}", path, e))?;
+    let table = SynonymTable::parse(&content).map_err(|e| format!("Invalid synonym table '{}': {}", path, e))?;
+    let words: HashSet<String> = load_payload_words(language, wordlist)?.into_iter().collect();
+    Ok(table.without(&words))
 }
 
 /// Randomly select N words from the BIP39 wordlist.
-fn select_random_words<R: Rng>(rng: &mut R, count: usize, language: &str) -> Result<Vec<String>, String> {
-    let all_words = load_payload_words(language)?;
+fn select_random_words<R: Rng>(rng: &mut R, count: usize, language: &str, wordlist: Wordlist) -> Result<Vec<String>, String> {
+    if wordlist == Wordlist::Phonetic {
+        // Random bytes keep the even/odd alternation
+        let bytes: Vec<u8> = (0..count).map(|_| rng.gen()).collect();
+        return Ok(phonetic::encode_bytes(&bytes));
+    }
+    let all_words = load_payload_words(language, wordlist)?;
     if all_words.is_empty() || count == 0 {
         return Ok(Vec::new());
     }
@@ -2257,286 +3172,2107 @@ fn validate_cover_words(cover_words: &[&str], wordlist_set: &HashSet<String>) {
     }
 }
 
-fn print_usage(program_name: &str) {
-    eprintln!("Usage: {} [OPTIONS] [<word1> <word2> ... <wordN>]", program_name);
-    eprintln!();
-    eprintln!("Generate natural sentences embedding BIP39 words in-order.");
-    eprintln!();
-    eprintln!("Arguments:");
-    eprintln!("  <word1> <word2> ...    BIP39 words to embed (positional, optional if --random or --from-ascii used)");
-    eprintln!();
-    eprintln!("Options:");
-    eprintln!("  --random <N>            Generate sentences from N random BIP39 words");
-    eprintln!("  --from-ascii <text>     Encode ASCII plaintext to wordlist words");
-    eprintln!("                          Use '-' to read from stdin");
-    eprintln!("  --grammar <grammar>      Grammar to use: 'subject' (default) or 'body'");
-    eprintln!("                          subject: Short sentences, may include prefixes (Re:, Fwd:, etc.)");
-    eprintln!("                          body: Longer sentences, no prefixes");
-    eprintln!("  --highlight <color>      Highlight BIP39 words with color (default: bars | |)");
-    eprintln!("                          Colors: black, red, green, yellow, blue, magenta, cyan, white");
-    eprintln!("                          Or ANSI codes: 30-37");
-    eprintln!("                          Use 'none' to disable highlighting");
-    eprintln!("  --madlib                Replace BIP39 words with [POS] placeholders");
-    eprintln!("  --seed <N>              Seed for deterministic random generation");
-    eprintln!("  --variations <N>         Generate N variations and select the most compact (default: 1)");
-    eprintln!("  --language, -l <lang>    Language for wordlist: 'english' (default), 'french', 'german'");
-    eprintln!("  --k-min <N>              Minimum sentence length in POS slots including Dot (default: 3)");
-    eprintln!("  --k-max <N>              Maximum sentence length in POS slots including Dot (default: 20)");
-    eprintln!("  --length-mode <mode>      Sentence length selection: 'compact' or 'natural'");
-    eprintln!("                          default: subject -> compact, body -> natural");
-    eprintln!("                          compact: Try k from k_min to k_max, shortest first");
-    eprintln!("                          natural: Sample k from grammar's length distribution");
-    eprintln!("  --show-grammar           Display the grammar rules (then continue execution)");
-    eprintln!("  --verbose, -v           Show detailed debugging information");
-    eprintln!("  --help                  Show this help message");
-    eprintln!();
-    eprintln!("Examples:");
-    eprintln!("  {} abandon ability able about above absent", program_name);
-    eprintln!("  {} --random 10", program_name);
-    eprintln!("  {} --from-ascii \"Hello World\"", program_name);
-    eprintln!("  {} --from-ascii - < input.txt", program_name);
-    eprintln!("  {} --random 5 --grammar subject --highlight none", program_name);
-    eprintln!("  {} --random 5 --grammar body --highlight highlight", program_name);
-}
-
-fn parse_args() -> Result<(Vec<String>, Option<usize>, Option<String>, bool, Option<u64>, usize, HighlightMode, GenerationMode, String, bool, usize, usize, SentenceLengthMode), String> {
-    let args: Vec<String> = env::args().collect();
-    let program_name = args[0].clone();
-    
-    if args.len() < 2 {
-        return Err("No words provided. Use --random <N> or provide words as arguments.".to_string());
+/// Options a `repl` session carries between lines, changed with `set`.
+struct ReplSettings {
+    language: String,
+    generation_mode: GenerationMode,
+    highlight_mode: HighlightMode,
+    length_mode: SentenceLengthMode,
+    k_min: usize,
+    k_max: usize,
+    checksum: bool,
+    audit: bool,
+    verbose: bool,
+}
+
+impl Default for ReplSettings {
+    fn default() -> Self {
+        ReplSettings {
+            language: "english".to_string(),
+            generation_mode: GenerationMode::Subject,
+            highlight_mode: HighlightMode::Bars,
+            length_mode: SentenceLengthMode::Compact,
+            k_min: 3,
+            k_max: 20,
+            checksum: false,
+            audit: false,
+            verbose: false,
+        }
     }
-    
-    let mut words = Vec::new();
-    let mut random_count: Option<usize> = None;
-    let mut ascii_input: Option<String> = None;
-    let mut verbose = false;
-    let mut seed: Option<u64> = None;
-    let mut variations = 1;
-    let mut highlight_mode = HighlightMode::Bars;
-    let mut generation_mode = GenerationMode::Subject;
-    let mut language = "english".to_string();
-    let mut show_grammar = false;
-    let mut k_min = 3;
-    let mut k_max = 20;
-    let mut length_mode = SentenceLengthMode::Compact;
-    let mut length_mode_explicit = false;
-    let mut i = 1;
-    
-    while i < args.len() {
-        match args[i].as_str() {
-            "--help" | "-h" => {
-                print_usage(&program_name);
-                std::process::exit(0);
+}
+
+impl ReplSettings {
+    /// The defaults, with the language, grammar and checksum of `config`
+    fn from_config(config: &GlossiaConfig) -> Self {
+        let defaults = ReplSettings::default();
+        ReplSettings {
+            language: config.language.clone().unwrap_or(defaults.language),
+            generation_mode: config.grammar.as_deref().and_then(GenerationMode::from_name).unwrap_or(defaults.generation_mode),
+            checksum: config.fec != FecLevel::None,
+            ..defaults
+        }
+    }
+}
+
+/// Wordlist and cover words loaded once for a `repl` or `watch` session.
+struct Repl {
+    settings: ReplSettings,
+    rng: StdRng,
+    all_words: Vec<String>,
+    wordlist_set: HashSet<String>,
+    cover_by_pos: HashMap<Pos, Vec<String>>,
+    slot_map: SlotMap,
+    /// Most recent cover text, for `audit` and `decode` with no argument
+    last_text: Option<String>,
+}
+
+impl Repl {
+    fn new(settings: ReplSettings) -> Result<Self, String> {
+        let mut repl = Repl {
+            settings,
+            rng: StdRng::from_entropy(),
+            all_words: Vec::new(),
+            wordlist_set: HashSet::new(),
+            cover_by_pos: HashMap::new(),
+            slot_map: SlotMap::default(),
+            last_text: None,
+        };
+        repl.load_language()?;
+        Ok(repl)
+    }
+
+    fn load_language(&mut self) -> Result<(), String> {
+        self.all_words = load_payload_words(&self.settings.language, Wordlist::Bip39)?;
+        self.wordlist_set = self.all_words.iter().map(|w| w.to_lowercase()).collect();
+        self.cover_by_pos = load_cover_words_by_pos(&self.wordlist_set, &self.settings.language);
+        self.slot_map = payload_slots(Wordlist::Bip39, 0.0);
+        Ok(())
+    }
+
+    /// Run one input line; returns false when the session should end.
+    fn run_line(&mut self, line: &str) -> bool {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let rest = rest.trim();
+        let result = match command {
+            "" => Ok(()),
+            "quit" | "exit" => return false,
+            "help" => {
+                print_repl_help();
+                Ok(())
             }
-            "--verbose" | "-v" => {
-                verbose = true;
-                i += 1;
+            "show" => {
+                self.show_settings();
+                Ok(())
             }
-            "--random" => {
-                if i + 1 >= args.len() {
-                    return Err("--random requires a value".to_string());
-                }
-                random_count = Some(args[i + 1].parse()
-                    .map_err(|_| format!("Invalid number for --random: {}", args[i + 1]))?);
-                i += 2;
+            "set" => self.set(rest),
+            "encode" => {
+                let words: Vec<String> = rest.split_whitespace().map(|w| w.to_string()).collect();
+                self.encode(words)
             }
-            "--grammar" => {
-                if i + 1 >= args.len() {
-                    return Err("--grammar requires a value".to_string());
-                }
-                generation_mode = match args[i + 1].as_str() {
-                    "subject" => GenerationMode::Subject,
-                    "body" => GenerationMode::Body,
-                    _ => return Err(format!("Invalid grammar: {}. Use 'subject' or 'body'", args[i + 1])),
-                };
-                i += 2;
+            "random" => rest
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid number: {}", rest))
+                .map(|count| (0..count).map(|_| self.all_words.choose(&mut self.rng).unwrap().clone()).collect())
+                .and_then(|words| self.encode(words)),
+            "ascii" => {
+                let text = SecretPayload::from(rest.to_string());
+                encode_ascii_to_words(&text, &self.settings.language, Wordlist::Bip39).and_then(|words| self.encode(words))
             }
-            "--mode" => {
-                // Deprecated: support old --mode flag for backward compatibility
-                if i + 1 >= args.len() {
-                    return Err("--mode requires a value (use --grammar instead)".to_string());
-                }
-                eprintln!("Warning: --mode is deprecated, use --grammar instead");
-                generation_mode = match args[i + 1].as_str() {
+            "decode" => self.decode(rest, false),
+            "decode-ascii" => self.decode(rest, true),
+            "detect" => self.last_or(rest).map(|text| {
+                let found = payload::detect(&text, &self.all_words);
+                println!(
+                    "{} of {} tokens are carrier words; checksum {}; likely payload: {}",
+                    found.carrier_words,
+                    found.total_words,
+                    if found.checksum_valid { "valid" } else { "none" },
+                    if found.is_likely() { "yes" } else { "no" }
+                );
+            }),
+            "audit" => self.last_or(rest).map(|text| println!("{}", steganalysis::audit(&strip_highlighting(&text), &self.wordlist_set))),
+            other => Err(format!("Unknown command '{}' (try 'help')", other)),
+        };
+        if let Err(e) = result {
+            eprintln!("Error: {}", e);
+        }
+        true
+    }
+
+    fn last_or(&self, text: &str) -> Result<String, String> {
+        if !text.is_empty() {
+            return Ok(text.to_string());
+        }
+        self.last_text.clone().ok_or_else(|| "No text given and nothing encoded yet".to_string())
+    }
+
+    fn encode(&mut self, words: Vec<String>) -> Result<(), String> {
+        let text = self.cover_text(words)?;
+        println!("{}", decode::wrap_lines(&text, DEFAULT_WRAP));
+        if self.settings.audit {
+            println!("{}", steganalysis::audit(&strip_highlighting(&text), &self.wordlist_set));
+        }
+        self.last_text = Some(text);
+        Ok(())
+    }
+
+    /// Generate a cover text for wordlist `words` with the current settings.
+    fn cover_text(&mut self, mut words: Vec<String>) -> Result<String, String> {
+        if words.is_empty() {
+            return Err("No words to encode".to_string());
+        }
+        if let Some(word) = words.iter().find(|w| !self.wordlist_set.contains(&w.to_lowercase())) {
+            return Err(format!("'{}' is not in the {} wordlist", word, self.settings.language));
+        }
+        if self.settings.checksum {
+            words = codec::append_checksum(&words, &self.all_words).map_err(|e| e.to_string())?;
+        }
+        let payload: Vec<PayloadTok> = words.iter().map(|w| PayloadTok::new(w.clone(), self.slot_map.slots(w))).collect();
+        let payload_set: HashSet<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
+        let lex = build_lexicon(&self.cover_by_pos, payload_set, self.wordlist_set.clone(), self.settings.verbose);
+        let filter = SentenceFilter { max_grade: None, avoid: None, banned_slots: HashSet::new() };
+        let settings = &self.settings;
+        let (text, _) = generate_text(
+            &mut self.rng,
+            &lex,
+            &payload,
+            settings.highlight_mode,
+            settings.verbose,
+            settings.generation_mode,
+            settings.k_min,
+            settings.k_max,
+            settings.length_mode,
+            &filter,
+        );
+        Ok(text)
+    }
+
+    fn decode(&self, text: &str, ascii: bool) -> Result<(), String> {
+        let text = strip_highlighting(&self.last_or(text)?);
+        let options = DecodeOptions {
+            checksum: self.settings.checksum,
+            ascii,
+            ..Default::default()
+        };
+        let mut decoded = decode_cover_text(&text, &self.settings.language, &options, self.settings.verbose)?;
+        println!("{}", decoded);
+        decoded.wipe();
+        Ok(())
+    }
+
+    fn set(&mut self, args: &str) -> Result<(), String> {
+        let (name, value) = args.split_once(char::is_whitespace).ok_or("Usage: set <option> <value>")?;
+        let value = value.trim();
+        let flag = || match value {
+            "on" | "true" => Ok(true),
+            "off" | "false" => Ok(false),
+            _ => Err(format!("Expected on or off, got '{}'", value)),
+        };
+        let number = || value.parse::<usize>().map_err(|_| format!("Invalid number: {}", value));
+        let settings = &mut self.settings;
+        match name {
+            "mode" => {
+                settings.generation_mode = match value {
                     "subject" => GenerationMode::Subject,
                     "body" => GenerationMode::Body,
-                    _ => return Err(format!("Invalid grammar: {}. Use 'subject' or 'body'", args[i + 1])),
+                    _ => return Err(format!("Invalid mode: {}. Use 'subject' or 'body'", value)),
+                };
+                settings.length_mode = match settings.generation_mode {
+                    GenerationMode::Subject => SentenceLengthMode::Compact,
+                    GenerationMode::Body => SentenceLengthMode::Natural,
                 };
-                i += 2;
             }
-            "--highlight" => {
-                if i + 1 >= args.len() {
-                    return Err("--highlight requires a value".to_string());
-                }
-                highlight_mode = match args[i + 1].as_str() {
+            "length-mode" => {
+                settings.length_mode = match value {
+                    "compact" => SentenceLengthMode::Compact,
+                    "natural" => SentenceLengthMode::Natural,
+                    _ => return Err(format!("Invalid length mode: {}. Use 'compact' or 'natural'", value)),
+                };
+            }
+            "highlight" => {
+                settings.highlight_mode = match value {
                     "none" => HighlightMode::None,
                     "bars" => HighlightMode::Bars,
-                    color_str => {
-                        match parse_color(color_str) {
-                            Ok(color_code) => HighlightMode::Color(color_code),
-                            Err(e) => return Err(e),
-                        }
-                    }
+                    color => HighlightMode::Color(parse_color(color)?),
                 };
-                i += 2;
             }
-            "--madlib" => {
-                highlight_mode = HighlightMode::Madlib;
-                i += 1;
-            }
-            "--seed" => {
-                if i + 1 >= args.len() {
-                    return Err("--seed requires a value".to_string());
-                }
-                seed = Some(args[i + 1].parse()
-                    .map_err(|_| format!("Invalid number for --seed: {}", args[i + 1]))?);
-                i += 2;
+            "k-min" => settings.k_min = number()?,
+            "k-max" => settings.k_max = number()?,
+            "seed" => {
+                let seed = value.parse::<u64>().map_err(|_| format!("Invalid seed: {}", value))?;
+                self.rng = StdRng::seed_from_u64(seed);
             }
-            "--variations" => {
-                if i + 1 >= args.len() {
-                    return Err("--variations requires a value".to_string());
-                }
-                variations = args[i + 1].parse()
-                    .map_err(|_| format!("Invalid number for --variations: {}", args[i + 1]))?;
-                if variations == 0 {
-                    return Err("--variations must be at least 1".to_string());
+            "checksum" => settings.checksum = flag()?,
+            "audit" => settings.audit = flag()?,
+            "verbose" => settings.verbose = flag()?,
+            "language" => {
+                let previous = std::mem::replace(&mut settings.language, value.to_string());
+                if let Err(e) = self.load_language() {
+                    self.settings.language = previous;
+                    return Err(e);
                 }
-                i += 2;
             }
-            "--language" | "-l" => {
-                if i + 1 >= args.len() {
-                    return Err("--language requires a value".to_string());
-                }
-                language = args[i + 1].clone();
+            _ => return Err(format!("Unknown option '{}' (try 'show')", name)),
+        }
+        if self.settings.k_min > self.settings.k_max {
+            eprintln!("Warning: k-min ({}) is greater than k-max ({})", self.settings.k_min, self.settings.k_max);
+        }
+        Ok(())
+    }
+
+    fn show_settings(&self) {
+        let s = &self.settings;
+        let on_off = |b: bool| if b { "on" } else { "off" };
+        println!("language     {}", s.language);
+        println!("mode         {}", match s.generation_mode { GenerationMode::Subject => "subject", GenerationMode::Body => "body" });
+        println!("length-mode  {}", match s.length_mode { SentenceLengthMode::Compact => "compact", SentenceLengthMode::Natural => "natural", SentenceLengthMode::Styled => "style" });
+        println!("highlight    {}", match s.highlight_mode {
+            HighlightMode::None => "none".to_string(),
+            HighlightMode::Bars => "bars".to_string(),
+            HighlightMode::Color(code) => code.to_string(),
+            HighlightMode::Madlib => "madlib".to_string(),
+        });
+        println!("k-min        {}", s.k_min);
+        println!("k-max        {}", s.k_max);
+        println!("checksum     {}", on_off(s.checksum));
+        println!("audit        {}", on_off(s.audit));
+        println!("verbose      {}", on_off(s.verbose));
+    }
+}
+
+/// Remove `--highlight` bars and colour codes so a text reads as plain prose.
+fn strip_highlighting(text: &str) -> String {
+    regex::Regex::new(r"\x1b\[[0-9;]*m")
+        .unwrap()
+        .replace_all(text, "")
+        .replace('|', "")
+}
+
+fn print_repl_help() {
+    println!("Commands:");
+    println!("  encode <w1> <w2> ...   Encode wordlist words");
+    println!("  random <N>             Encode N random wordlist words");
+    println!("  ascii <text>           Encode ASCII text");
+    println!("  decode [text]          Print the payload words in a text (default: last output)");
+    println!("  decode-ascii [text]    Decode an ascii-encoded text (default: last output)");
+    println!("  detect [text]          Report whether a text looks like it carries a payload");
+    println!("  audit [text]           Print a detectability score card");
+    println!("  set <option> <value>   Change an option: mode, length-mode, highlight, k-min,");
+    println!("                         k-max, seed, checksum, audit, verbose, language");
+    println!("  show                   Print the current options");
+    println!("  help                   Print this help");
+    println!("  quit                   Leave the REPL");
+}
+
+/// `glossia repl`: read commands from stdin until EOF or `quit`, keeping the
+/// wordlist, cover words and grammar sequences loaded between them.
+fn run_repl() -> Result<(), CommandError> {
+    use std::io::{self, BufRead};
+
+    let config = GlossiaConfig::load_default().map_err(|e| CommandError::usage(format!("{:#}", e)))?;
+    let mut repl = Repl::new(ReplSettings::from_config(&config)).map_err(CommandError::wordlist)?;
+    let interactive = io::stdin().is_terminal();
+    if interactive {
+        println!("glossia REPL. Type 'help' for commands, 'quit' to leave.");
+    }
+    let stdin = io::stdin();
+    let mut lines = stdin.lock().lines();
+    loop {
+        if interactive {
+            print!("glossia> ");
+            io::stdout().flush().map_err(|e| e.to_string())?;
+        }
+        let Some(line) = lines.next() else {
+            break;
+        };
+        let mut line = line.map_err(|e| format!("Failed to read from stdin: {}", e))?;
+        let more = repl.run_line(&line);
+        line.wipe();
+        if !more {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Suffix of the cover-text file `glossia watch` keeps next to each file.
+#[cfg(feature = "watch")]
+const WATCH_SUFFIX: &str = ".glossia.txt";
+
+/// `glossia watch <dir> [--passphrase <p>]`
+fn parse_watch_args(args: &[String]) -> Result<(PathBuf, PayloadOptions), CommandError> {
+    let mut dir: Option<PathBuf> = None;
+    // Every cover text carries a checksum, so a half-written or edited file
+    // is never decoded over the original
+    let mut options = PayloadOptions { checksum: true, ..PayloadOptions::default() };
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--passphrase" => {
+                let passphrase = args.get(i + 1).ok_or_else(|| CommandError::usage("--passphrase requires a value"))?;
+                options.whiten = true;
+                options.passphrase = Some(passphrase.clone());
                 i += 2;
             }
-            "--show-grammar" => {
-                show_grammar = true;
+            arg if arg.starts_with("--") => return Err(CommandError::usage(format!("Unknown option for watch: {}", arg))),
+            path if dir.is_none() => {
+                dir = Some(PathBuf::from(path));
                 i += 1;
             }
-            "--k-min" => {
-                if i + 1 >= args.len() {
-                    return Err("--k-min requires a value".to_string());
-                }
-                k_min = args[i + 1].parse()
-                    .map_err(|_| format!("Invalid number for --k-min: {}", args[i + 1]))?;
-                if k_min < 3 {
-                    return Err("--k-min must be at least 3".to_string());
+            extra => return Err(CommandError::usage(format!("Unexpected argument for watch: {}", extra))),
+        }
+    }
+    let dir = dir.ok_or_else(|| CommandError::usage("Usage: glossia watch <dir> [--passphrase <p>]"))?;
+    if !dir.is_dir() {
+        return Err(format!("Not a directory: {}", dir.display()).into());
+    }
+    Ok((dir, options))
+}
+
+/// Bring the counterpart of `path` up to date: encode a plain file to
+/// `<name>.glossia.txt`, or decode a `.glossia.txt` file back to `<name>`.
+/// Returns the file written, or `None` when the counterpart already matches
+/// (which is also what stops the two directions from re-triggering each other).
+#[cfg(feature = "watch")]
+fn watch_sync(repl: &mut Repl, path: &Path, options: &PayloadOptions) -> Result<Option<PathBuf>, String> {
+    let name = match path.file_name().and_then(|n| n.to_str()) {
+        Some(name) if !name.starts_with('.') && !name.ends_with('~') && path.is_file() => name,
+        _ => return Ok(None),
+    };
+    let read = |path: &Path| std::fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e));
+
+    if let Some(original) = name.strip_suffix(WATCH_SUFFIX) {
+        let text = String::from_utf8_lossy(&read(path)?).into_owned();
+        let mut bytes = payload::decode_payload(&text, &repl.all_words, options).map_err(|e| e.to_string())?;
+        let target = path.with_file_name(original);
+        let unchanged = std::fs::read(&target).map(|mut current| {
+            let same = current == bytes;
+            current.wipe();
+            same
+        });
+        let result = if unchanged.unwrap_or(false) {
+            Ok(None)
+        } else {
+            std::fs::write(&target, &bytes)
+                .map(|_| Some(target.clone()))
+                .map_err(|e| format!("Failed to write {}: {}", target.display(), e))
+        };
+        bytes.wipe();
+        return result;
+    }
+
+    let mut bytes = read(path)?;
+    let target = path.with_file_name(format!("{}{}", name, WATCH_SUFFIX));
+    let unchanged = std::fs::read(&target)
+        .ok()
+        .and_then(|text| payload::decode_payload(&String::from_utf8_lossy(&text), &repl.all_words, options).ok())
+        .map(|mut current| {
+            let same = current == bytes;
+            current.wipe();
+            same
+        });
+    if unchanged.unwrap_or(false) {
+        bytes.wipe();
+        return Ok(None);
+    }
+    let words = payload::encode_payload(&mut repl.rng, &bytes, &repl.all_words, options);
+    bytes.wipe();
+    let text = repl.cover_text(words.map_err(|e| e.to_string())?)?;
+    std::fs::write(&target, decode::wrap_lines(&text, DEFAULT_WRAP) + "\n")
+        .map_err(|e| format!("Failed to write {}: {}", target.display(), e))?;
+    Ok(Some(target))
+}
+
+/// `glossia watch`: keep a `.glossia.txt` cover text beside every file in a
+/// directory, and decode edited or new `.glossia.txt` files back.
+#[cfg(feature = "watch")]
+fn run_watch(args: &[String]) -> Result<(), CommandError> {
+    use notify::{EventKind, RecursiveMode, Watcher};
+    use std::sync::mpsc;
+
+    let (dir, options) = parse_watch_args(args)?;
+    let mut repl = Repl::new(ReplSettings {
+        generation_mode: GenerationMode::Body,
+        highlight_mode: HighlightMode::None,
+        length_mode: SentenceLengthMode::Natural,
+        ..ReplSettings::default()
+    })
+    .map_err(CommandError::wordlist)?;
+    let mut sync = |path: &Path| match watch_sync(&mut repl, path, &options) {
+        Ok(Some(written)) => eprintln!("{} -> {}", path.display(), written.display()),
+        Ok(None) => {}
+        Err(e) => eprintln!("Skipping {}: {}", path.display(), e),
+    };
+
+    let entries = std::fs::read_dir(&dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        sync(&entry.path());
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).map_err(|e| format!("Failed to start watcher: {}", e))?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(|e| format!("Failed to watch {}: {}", dir.display(), e))?;
+    eprintln!("Watching {} (Ctrl-C to stop)", dir.display());
+    for event in rx {
+        match event {
+            Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                for path in &event.paths {
+                    sync(path);
                 }
-                i += 2;
             }
-            "--k-max" => {
-                if i + 1 >= args.len() {
-                    return Err("--k-max requires a value".to_string());
-                }
-                k_max = args[i + 1].parse()
-                    .map_err(|_| format!("Invalid number for --k-max: {}", args[i + 1]))?;
-                if k_max < k_min {
-                    return Err(format!("--k-max ({}) must be >= --k-min ({})", k_max, k_min));
-                }
-                if k_max > 20 {
-                    return Err("--k-max cannot exceed 20 (sequences only precomputed up to k=20)".to_string());
-                }
-                i += 2;
+            Ok(_) => {}
+            Err(e) => eprintln!("Watch error: {}", e),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "watch"))]
+fn run_watch(args: &[String]) -> Result<(), CommandError> {
+    parse_watch_args(args)?;
+    Err(CommandError::usage("watch needs glossia built with the 'watch' feature"))
+}
+
+/// Read a document for --file: the text layer of a PDF, the paragraphs of a
+/// DOCX, or a text file as is.
+fn read_document(path: &str) -> Result<String, String> {
+    document::read_text(Path::new(path)).map_err(|e| format!("{:#}", e))
+}
+
+/// Read the system clipboard as text.
+#[cfg(feature = "clipboard")]
+fn read_clipboard() -> Result<String, String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.get_text())
+        .map_err(|e| format!("Failed to read clipboard: {}", e))
+}
+
+/// Replace the system clipboard contents with `text`.
+#[cfg(feature = "clipboard")]
+fn write_clipboard(text: &str) -> Result<(), String> {
+    arboard::Clipboard::new()
+        .and_then(|mut clipboard| clipboard.set_text(text))
+        .map_err(|e| format!("Failed to write clipboard: {}", e))
+}
+
+/// Encrypt a --from-ascii payload for --age-recipient, packed as the `age`
+/// data mode.
+#[cfg(feature = "age")]
+fn encrypt_age(input: &[u8], recipients: &[String]) -> Result<Vec<u8>, String> {
+    glossia::age_armor::encode_encrypted_age(input, recipients).map_err(|e| format!("--age-recipient: {:#}", e))
+}
+
+#[cfg(not(feature = "age"))]
+fn encrypt_age(_input: &[u8], _recipients: &[String]) -> Result<Vec<u8>, String> {
+    Err("age encryption is not enabled (build with --features age)".to_string())
+}
+
+/// `--engine neural`: have the model write a cover text for `payload` and
+/// print it, with its capacity and speed on stderr.
+#[cfg(feature = "neural")]
+fn encode_neural(settings: &NeuralSettings, payload: &[u8], json: bool, verbose: bool, to_clipboard: bool) {
+    let loaded = Instant::now();
+    let mut model = neural::Gguf::load(&settings.model, &settings.tokenizer)
+        .unwrap_or_else(|e| fail(json, "io", format!("Error: {:#}", e)));
+    if verbose {
+        eprintln!("Loaded {} in {:.1}s", settings.model, loaded.elapsed().as_secs_f64());
+    }
+    let report = neural::encode_with(&mut model, payload, &settings.options)
+        .unwrap_or_else(|e| fail(json, "generation", format!("Error writing with the model: {:#}", e)));
+    if json {
+        println!(
+            "{}",
+            serde_json::json!({
+                "text": report.text,
+                "tokens": report.tokens,
+                "payload_bits": report.payload_bits,
+                "bits_per_token": report.bits_per_token(),
+                "elapsed_ms": report.elapsed.as_millis() as u64,
+            })
+        );
+    } else {
+        // Not wrapped: the model reads the text back exactly as written
+        println!("{}", report.text);
+        eprintln!(
+            "{} tokens carry {} bits ({:.2} bits per token) in {:.1}s, {:.1} tokens/s",
+            report.tokens,
+            report.payload_bits,
+            report.bits_per_token(),
+            report.elapsed.as_secs_f64(),
+            report.tokens_per_second()
+        );
+    }
+    if to_clipboard {
+        if let Err(e) = write_clipboard(&report.text) {
+            fail(json, "io", format!("Error: {}", e));
+        }
+    }
+}
+
+/// The payload bytes the model's token choices in `cover_text` carry.
+#[cfg(feature = "neural")]
+fn decode_neural(settings: &NeuralSettings, cover_text: &str, verbose: bool) -> anyhow::Result<Vec<u8>> {
+    let mut model = neural::Gguf::load(&settings.model, &settings.tokenizer)?;
+    let started = Instant::now();
+    let bytes = neural::decode_with(&mut model, cover_text, &settings.options)?;
+    if verbose {
+        eprintln!("Read {} bytes back through the model in {:.1}s", bytes.len(), started.elapsed().as_secs_f64());
+    }
+    Ok(bytes)
+}
+
+/// The clock, in unix seconds, for validity stamps.
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// A decoded validity stamp, for stderr.
+fn describe_validity(stamp: &validity::Validity, now: u64) -> String {
+    let made = format!("Stamped at {} (unix time)", stamp.created);
+    match stamp.expires {
+        None => format!("{}, with no expiry", made),
+        Some(expires) if stamp.is_expired(now) => format!("{}, expired {} ago", made, duration(now - expires)),
+        Some(expires) => format!("{}, expires in {}", made, duration(expires - now)),
+    }
+}
+
+/// `seconds` in its largest whole unit, like `3 days`.
+fn duration(seconds: u64) -> String {
+    let (count, unit) = [(86_400, "day"), (3600, "hour"), (60, "minute")]
+        .into_iter()
+        .find(|&(size, _)| seconds >= size)
+        .map_or((seconds, "second"), |(size, unit)| (seconds / size, unit));
+    format!("{} {}{}", count, unit, if count == 1 { "" } else { "s" })
+}
+
+/// An `--expires` time: unix seconds, or `+N` seconds, minutes, hours or days
+/// (`+90m`, `+7d`) after `now`.
+fn parse_expiry(when: &str, now: u64) -> Result<u64, String> {
+    let invalid = || format!("--expires: '{}' is not unix seconds or +N followed by s, m, h or d", when);
+    let Some(relative) = when.strip_prefix('+') else {
+        return when.parse().map_err(|_| invalid());
+    };
+    let unit = match relative.chars().last() {
+        Some('s') => 1,
+        Some('m') => 60,
+        Some('h') => 3600,
+        Some('d') => 86_400,
+        _ => return Err(invalid()),
+    };
+    let count: u64 = relative[..relative.len() - 1].parse().map_err(|_| invalid())?;
+    count.checked_mul(unit).and_then(|seconds| now.checked_add(seconds)).ok_or_else(invalid)
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn read_clipboard() -> Result<String, String> {
+    Err("Clipboard support is not enabled (build with --features clipboard)".to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+fn write_clipboard(_text: &str) -> Result<(), String> {
+    Err("Clipboard support is not enabled (build with --features clipboard)".to_string())
+}
+
+/// Set from `--json-errors`: report failures as JSON on stderr.
+static JSON_ERRORS: AtomicBool = AtomicBool::new(false);
+
+/// Exit status for each error code, so scripts can tell failures apart
+/// (documented in the README). Codes not listed exit with 1.
+const EXIT_CODES: &[(&str, i32)] = &[
+    ("usage", 2),
+    ("invalid_arguments", 2),
+    ("io", 3),
+    ("wordlist", 4),
+    ("unknown_word", 5),
+    ("checksum", 6),
+    ("signature", 7),
+    ("passphrase", 8),
+    ("decode", 9),
+    ("encode", 10),
+    ("generation", 11),
+    ("grammar", 12),
+    ("session", 13),
+    ("expired", 14),
+    ("truncated", 15),
+];
+
+fn exit_code(code: &str) -> i32 {
+    EXIT_CODES.iter().find(|(c, _)| *c == code).map_or(1, |&(_, status)| status)
+}
+
+/// Error code for a library failure: its `GlossiaError` kind, `io` for an
+/// I/O error, or `fallback`.
+fn error_code(err: &anyhow::Error, fallback: &'static str) -> &'static str {
+    if let Some(e) = err.downcast_ref::<GlossiaError>() {
+        e.code()
+    } else if err.downcast_ref::<std::io::Error>().is_some() {
+        "io"
+    } else {
+        fallback
+    }
+}
+
+/// A `repl`/`watch`/`completions`/`manpages`/`capabilities`/`train-cover-model` failure and its error code.
+struct CommandError {
+    code: &'static str,
+    message: String,
+}
+
+impl CommandError {
+    fn usage(message: impl Into<String>) -> Self {
+        CommandError { code: "usage", message: message.into() }
+    }
+
+    fn wordlist(message: String) -> Self {
+        CommandError { code: "wordlist", message }
+    }
+}
+
+/// Anything else a subcommand hits is reading or writing files.
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError { code: "io", message }
+    }
+}
+
+/// Report an error and exit with the status for `code`: as
+/// `{"error": {"code", "message", "exit_code"}}` on stdout with `--json` or on
+/// stderr with `--json-errors`, and as the bare message on stderr otherwise.
+fn fail(json: bool, code: &str, message: impl std::fmt::Display) -> ! {
+    let status = exit_code(code);
+    let report = || serde_json::json!({ "error": { "code": code, "message": message.to_string(), "exit_code": status } });
+    if json {
+        println!("{}", report());
+    } else if JSON_ERRORS.load(Ordering::Relaxed) {
+        eprintln!("{}", report());
+    } else {
+        eprintln!("{}", message);
+    }
+    std::process::exit(status);
+}
+
+/// Examples printed after `--help`.
+const EXAMPLES: &str = "\
+Examples:
+  glossia abandon ability able about above absent
+  glossia --random 10
+  glossia --from-ascii \"Hello World\"
+  glossia --from-ascii - < input.txt
+  glossia --random 5 --grammar subject --highlight none
+  glossia --random 5 --grammar body --highlight cyan
+  glossia --decode \"The |able| user can |abandon| it.\"
+  glossia --from-ascii - --mode binary < key.bin | glossia --decode-ascii - --mode binary > key.out";
+
+/// The CLI as a clap `Command`: `parse_args` parses with it, and
+/// `completions` and `manpages` are generated from it.
+fn cli_command() -> clap::Command {
+    use clap::builder::PossibleValue;
+    use clap::{Arg, ArgAction, ArgGroup, Command, ValueHint};
+
+    let flag = |name: &'static str, help: &'static str| Arg::new(name).long(name).action(ArgAction::SetTrue).help(help);
+    // Values are taken as given, like a passphrase or text starting with '-'
+    let value = |name: &'static str, value_name: &'static str, help: &'static str| {
+        Arg::new(name).long(name).value_name(value_name).allow_hyphen_values(true).help(help)
+    };
+    // The text to read, which --file and the clipboard supply instead
+    let input = |name: &'static str, help: &'static str| {
+        Arg::new(name).long(name).value_name("TEXT").num_args(0..=1).help(help)
+    };
+
+    Command::new("glossia")
+        .about("Generate natural sentences embedding BIP39 words in-order")
+        .args_override_self(true)
+        .disable_help_subcommand(true)
+        .after_help(EXAMPLES)
+        .arg(Arg::new("words").value_name("WORD").num_args(0..).action(ArgAction::Append).help("BIP39 words to embed (optional with --random or --from-ascii)"))
+        .arg(value("random", "N", "Generate sentences from N random BIP39 words"))
+        .arg(value("from-ascii", "TEXT", "Encode ASCII plaintext to wordlist words ('-' for stdin)"))
+        .arg(value("grammar", "GRAMMAR", "Grammar to use (default: subject)").value_parser(["subject", "body"]).long_help(
+            "Grammar to use: 'subject' (default) or 'body'\n\
+             subject: Short sentences, may include prefixes (Re:, Fwd:, etc.)\n\
+             body: Longer sentences, no prefixes",
+        ))
+        .arg(value("mode", "MODE", "Payload as text or raw bytes (default: auto)").value_parser([
+            PossibleValue::new("auto"),
+            PossibleValue::new("text"),
+            PossibleValue::new("binary"),
+            PossibleValue::new("subject").hide(true),
+            PossibleValue::new("body").hide(true),
+        ]).long_help(
+            "Payload handling: 'auto' (default), 'text' or 'binary'\n\
+             binary: --decode-ascii writes the exact bytes, no newline\n\
+             auto: raw bytes when stdout is not a terminal or the payload isn't UTF-8\n\
+             text: input must be UTF-8",
+        ))
+        .arg(value("highlight", "COLOR", "Highlight BIP39 words: none, bars, a color name or an ANSI code (30-37)").long_help(
+            "Highlight BIP39 words with color (default: bars | |)\n\
+             Colors: black, red, green, yellow, blue, magenta, cyan, white\n\
+             Or ANSI codes: 30-37\n\
+             Use 'none' to disable highlighting",
+        ))
+        .arg(flag("madlib", "Replace BIP39 words with [POS] placeholders"))
+        .arg(value("seed", "N", "Seed for deterministic random generation"))
+        .arg(value("variations", "N", "Generate N variations and select the most compact (default: 1)"))
+        .arg(value("language", "LANG", "Language for the wordlist (default: english)").short('l'))
+        .arg(value("k-min", "N", "Minimum sentence length in POS slots including Dot (default: 3)"))
+        .arg(value("k-max", "N", "Maximum sentence length in POS slots including Dot (default: 20)"))
+        .arg(value("length-mode", "MODE", "Sentence length selection").value_parser(["compact", "natural"]).long_help(
+            "Sentence length selection: 'compact' or 'natural'\n\
+             default: subject -> compact, body -> natural\n\
+             compact: Try k from k_min to k_max, shortest first\n\
+             natural: Sample k from grammar's length distribution",
+        ))
+        // Every byte is airtime: no check or header words
+        .arg(flag("low-bandwidth", "Short sentences and no check or header words, for LoRa, mesh and serial links").conflicts_with_all(["checksum", "parity", "whiten", "interleave", "interleave-depth", "session", "decoy", "chaff"]).long_help(
+            "For LoRa, mesh and serial links: body grammar in compact (short) sentences,\n\
+             no highlighting, no checksum or header words",
+        ))
+        .arg(flag("show-grammar", "Display the grammar rules and bits per sentence (then continue execution)"))
+        .arg(value("export-dot", "FILE", "Write the grammar as a Graphviz DOT graph").value_hint(ValueHint::FilePath).long_help(
+            "Write the grammar as a Graphviz DOT graph, with POS slots annotated by\n\
+             wordlist entropy (then continue execution)",
+        ))
+        .arg(flag("explain", "Dry run: print the text with the productions and payload bits behind each word").conflicts_with("template").long_help(
+            "Dry run: print the generated text and, per sentence, the productions that\n\
+             made it and the payload bits each word carries (or that it is filler), then stop",
+        ))
+        .arg(value("max-grade", "G", "Refill cover words in sentences above this Flesch-Kincaid grade").long_help(
+            "Refill cover words in sentences whose Flesch-Kincaid grade exceeds G\n\
+             (payload words are never changed)",
+        ))
+        .arg(flag("avoid", "Re-sample cover words in sentences matching the bundled avoid list").long_help(
+            "Re-sample cover words in sentences matching the bundled avoid list\n\
+             (languages/<lang>/avoid.txt)",
+        ))
+        .arg(value("avoid-words", "WORDS", "Also avoid these words (comma-separated)").action(ArgAction::Append))
+        .arg(value("avoid-pattern", "REGEX", "Also avoid sentences matching this regex (repeatable)").action(ArgAction::Append))
+        .arg(flag("lemmatize", "With --decode: read inflected carriers as their wordlist word").conflicts_with_all(["noise", "deniable"]).long_help(
+            "With --decode: read inflected carriers (\"running\", \"ran\") as their wordlist\n\
+             word; with --checksum, ambiguous readings are settled by the checksum",
+        ))
+        // Whitened carriers are ciphertext, so their lookup time gives nothing away
+        .arg(flag("constant-time", "With --decode-ascii: look carriers up in time that does not depend on the payload").conflicts_with_all(["noise", "lemmatize", "prefixes", "homophone-safe", "lossy", "deniable", "whiten", "interleave", "interleave-depth", "template"]).long_help(
+            "With --decode-ascii: look carriers up by scanning the whole wordlist, so\n\
+             decode time does not depend on the payload",
+        ))
+        .arg(flag("lossy", "With --decode-ascii: zero-fill unreadable carriers and report the bytes lost").conflicts_with_all(["noise", "lemmatize", "deniable", "whiten", "interleave", "interleave-depth", "verify", "data-mode"]).long_help(
+            "With --decode-ascii: read unreadable carriers as zeros and report the byte\n\
+             ranges lost instead of failing; with --checksum, one unreadable word may be\n\
+             filled in",
+        ))
+        .arg(flag("homophone-safe", "Avoid cover words that sound like wordlist words; map homophones back when decoding").long_help(
+            "For text that will be read aloud: avoid cover words that sound like wordlist\n\
+             words; with --decode, map transcribed homophones back to wordlist words",
+        ))
+        .arg(flag("prefixes", "Write some carriers with a prefix (unsafe, reload); strip prefixes when decoding").conflicts_with("noise").long_help(
+            "Write some carriers with a prefix (unsafe, reload) from\n\
+             languages/<lang>/prefixes.txt; with --decode, strip them",
+        ))
+        .arg(flag("parity", "End each sentence with a parity marker word; report failing sentences when decoding").long_help(
+            "End each sentence with a marker word holding parity bits over its carriers;\n\
+             with --decode, name the sentences that fail instead of failing the whole message",
+        ))
+        .arg(input("decode", "Print the payload words embedded in text ('-' for stdin)").conflicts_with("payload").overrides_with("decode-ascii"))
+        .arg(input("decode-ascii", "Decode text produced with --from-ascii back to ASCII ('-' for stdin)").conflicts_with("payload").overrides_with("decode"))
+        .arg(flag("checksum", "Append a checksum word when encoding; verify and strip it when decoding"))
+        .arg(flag("fingerprint", "End the text with a four-word phrase naming the payload; with --decode, check it").conflicts_with_all(["session", "decoy", "deniable", "age-recipient"]).long_help(
+            "End the text with a four-word phrase naming the payload, to read back; with\n\
+             --decode, check it against the payload",
+        ))
+        .arg(flag("footer", "End the text with a note naming the command that decodes it").long_help(
+            "End the text with a note, below a '-- ' line, naming the command that decodes\n\
+             it; decoding strips it",
+        ))
+        .arg(value("footer-template", "TEXT", "Footer text instead of the default; {command} is the decode command").long_help(
+            "The note instead of the default; {command} is the decode command. It cannot\n\
+             use wordlist words",
+        ))
+        .arg(value("noise", "MODEL", "With --decode: recover carriers garbled by OCR or speech recognition").conflicts_with("deniable").value_parser(["ocr", "asr"]).long_help(
+            "With --decode: recover carriers garbled by 'ocr' (scans) or 'asr' (speech\n\
+             recognition); needs a --checksum payload",
+        ))
+        .arg(value("dictionary", "FILE", "With --noise ocr: spelling dictionary (Hunspell .dic or one word per line)").value_hint(ValueHint::FilePath).long_help(
+            "With --noise ocr: words that are spelled right are less likely misread\n\
+             carriers (Hunspell .dic or one word per line)",
+        ))
+        .arg(value("data-mode", "MODE", "Pack structured --from-ascii input compactly; restore it with --decode-ascii --data-mode").conflicts_with_all(["decoy", "deniable"]).value_parser(DataMode::ALL.iter().map(DataMode::name).collect::<Vec<_>>()).long_help(
+            "Pack structured --from-ascii input compactly: 'auto' (the first mode that\n\
+             fits), 'ascii', 'nostr-gift-wrap' (NIP-59 event JSON), 'nostr-event' (any\n\
+             signed event, restored as canonical JSON), 'age' (an armored age file, stored\n\
+             without its armor), 'jwt' or 'paseto' (tokens, stored as their segments'\n\
+             bytes), 'ssh-key' (public key or authorized_keys lines), 'git-object' (a signed\n\
+             commit or tag, restored so it hashes the same), 'fingerprint' (hex in groups,\n\
+             like 3A:9F:0C or 4AEE 18F8), 'msgpack' or 'protobuf' (serialized bytes as\n\
+             given, checked and framed with --schema-id); decode with --decode-ascii\n\
+             --data-mode",
+        ))
+        .arg(value("decoy", "TEXT", "Deniable mode: also embed this innocuous text").long_help(
+            "Deniable mode: also embed this innocuous text; needs --from-ascii,\n\
+             --passphrase and --decoy-passphrase",
+        ))
+        .arg(value("passphrase", "P", "Key for --decoy (the real payload), --chaff and --whiten"))
+        .arg(value("decoy-passphrase", "P", "Passphrase that unlocks the --decoy text"))
+        // Deniable carriers are already masked
+        .arg(flag("whiten", "XOR the --from-ascii bytes with a keystream so carrier words are uniform").conflicts_with_all(["decoy", "deniable"]).long_help(
+            "XOR the --from-ascii bytes with a keystream (keyed by --passphrase if given)\n\
+             so carrier words are uniform; decode with --decode-ascii --whiten",
+        ))
+        .arg(flag("commit", "With --whiten and --passphrase: add a key-committing tag so no other passphrase decodes it"))
+        // Both --interleave and --whiten put a header word ahead of the packed bytes
+        .arg(flag("interleave", "Spread the bits of each --from-ascii byte across the text against burst loss").conflicts_with_all(["whiten", "decoy", "deniable"]).long_help(
+            "Spread the bits of each --from-ascii byte across the text, so a lost sentence\n\
+             damages scattered bits instead of a run of bytes; decode with --decode-ascii\n\
+             --interleave",
+        ))
+        .arg(value("interleave-depth", "D", "Interleave D columns wide (default 8; implies --interleave)").conflicts_with_all(["whiten", "decoy", "deniable"]))
+        .arg(value("schema-id", "N", "With --data-mode msgpack or protobuf: a schema number stored in the payload header").long_help(
+            "With --data-mode msgpack or protobuf: a schema number in the payload header\n\
+             for the receiver to route on (default 0)",
+        ))
+        .arg(value("age-recipient", "AGE1", "Encrypt the --from-ascii payload to this age public key (repeatable)").conflicts_with_all(["data-mode", "decoy", "deniable"]).action(ArgAction::Append).long_help(
+            "Encrypt the --from-ascii payload to this age public key (repeatable) and pack\n\
+             it as --data-mode age; decode with --decode-ascii --data-mode age, then\n\
+             age --decrypt",
+        ))
+        .arg(value("expires", "WHEN", "Stamp the --from-ascii payload with an expiry: unix seconds, or +N with s, m, h or d").conflicts_with_all(["decoy", "deniable", "session"]).long_help(
+            "Stamp the --from-ascii payload with the time it was made and when it expires:\n\
+             unix seconds, or +N followed by s, m, h or d",
+        ))
+        .arg(flag("timestamp", "Stamp the --from-ascii payload with its creation time; with --decode-ascii, read the stamp").conflicts_with_all(["decoy", "session"]))
+        .arg(flag("reject-expired", "With --decode-ascii: read the stamp and fail if it expired"))
+        .arg(value("resync", "N", "Put a #k/T marker after every N carriers; with --decode-ascii, report truncation").conflicts_with_all(["prefixes", "homophone-safe", "noise", "lemmatize", "lossy", "constant-time", "whiten", "interleave", "interleave-depth", "sign", "verify", "session", "decoy", "deniable", "chaff", "template"]).long_help(
+            "Put a #k/T marker after every N carriers of the --from-ascii payload; with\n\
+             --decode-ascii, decode up to the last intact marker and report where a\n\
+             cut-short text stopped",
+        ))
+        // The bytes sent again must be the bytes sent the first time, and a stamp or
+        // age encryption changes on every run
+        .arg(value("resume-from", "BYTE", "With --resync: encode the payload from this byte").conflicts_with_all(["timestamp", "expires", "age-recipient"]).long_help(
+            "With --resync: encode the payload from this byte, as a retransmission to paste\n\
+             after the cut-short text",
+        ))
+        .arg(value("patch-base", "FILE", "Encode the --from-ascii payload as a patch against FILE; apply it to FILE when decoding").conflicts_with_all(["data-mode", "container-entry", "age-recipient", "decoy", "deniable", "lossy"]).value_hint(ValueHint::FilePath))
+        .arg(value("container-entry", "NAME=FILE", "Carry this file as a named entry of a container payload (repeatable)").conflicts_with_all(["data-mode", "age-recipient", "decoy", "deniable"]).action(ArgAction::Append).value_hint(ValueHint::FilePath).long_help(
+            "Carry this file as a named entry of a container payload (repeatable), instead\n\
+             of --from-ascii",
+        ))
+        .arg(value("extract-container", "DIR", "With --decode-ascii: write each entry of a container payload to DIR/NAME").value_hint(ValueHint::DirPath))
+        .arg(value("sign", "KEY", "Append a BIP340 signature to the --from-ascii payload (hex, nsec or @file)").conflicts_with("decoy"))
+        .arg(value("verify", "PUBKEY", "With --decode-ascii: require a valid signature from this key (hex or npub)").conflicts_with("deniable").long_help(
+            "With --decode-ascii: require a valid --sign signature from this key (hex or\n\
+             npub) and strip it",
+        ))
+        .arg(flag("deniable", "With --decode: unlock the --decoy carrier payload that --passphrase opens"))
+        .arg(value("chaff", "F", "Body grammar: interleave noise sentences, about a fraction F of the output").conflicts_with("decoy").long_help(
+            "Body grammar: interleave noise sentences, about a fraction F of the output;\n\
+             with --passphrase their positions are keyed and they carry random wordlist\n\
+             words (decode with the same --chaff and --passphrase)",
+        ))
+        .arg(value("style", "FILE", "Write like a sample of your own text (sentence lengths, punctuation, paragraphs)").value_hint(ValueHint::FilePath).long_help(
+            "Write like a sample of your own text: its sentence lengths, ?/! endings,\n\
+             commas, contractions and (body) paragraph size",
+        ))
+        .arg(value("cover-model", "FILE", "Pick filler words and sentence openers like a corpus (see train-cover-model)").value_hint(ValueHint::FilePath))
+        .arg(value("reply-to", "FILE", "Keep to the subject of the earlier messages of a thread (blank-line separated)").value_hint(ValueHint::FilePath).long_help(
+            "Keep to the subject of the earlier messages of a thread (oldest first,\n\
+             separated by blank lines)",
+        ))
+        .arg(value("synonym-payload", "TEXT", "Also hide up to 255 bytes of text in the choice between cover-word synonyms").long_help(
+            "Also hide up to 255 bytes of text in the choice between cover-word synonyms\n\
+             (languages/<lang>/synonyms.txt)",
+        ))
+        .arg(flag("synonyms", "With --decode: also print the --synonym-payload text"))
+        .arg(value("number-payload", "TEXT", "Also hide up to 255 bytes of text in the values of quantities, times and years").long_help(
+            "Also hide up to 255 bytes of text in the values of quantities, times and years\n\
+             (added to prose as needed)",
+        ))
+        .arg(flag("numbers", "With --decode: also print the --number-payload text"))
+        .arg(value("template", "FILE", "Fill your own sentence templates with typed blanks like {N}").conflicts_with_all(["number-payload", "numbers", "chaff", "style", "synonym-payload", "noise", "lemmatize", "decoy", "deniable"]).value_hint(ValueHint::FilePath).long_help(
+            "Fill your own sentence templates (one per line, blanks like {N} or {Adj})\n\
+             instead of generating sentences; with --decode, read the words from the same\n\
+             blanks",
+        ))
+        .arg(value("output-style", "STYLE", "Lay the words out as prose, a shopping list or recipe steps").value_parser(["prose", "list", "recipe"]).long_help(
+            "prose (default), list (a shopping list) or recipe (numbered steps); pass it to\n\
+             --decode too",
+        ))
+        .arg(value("wordlist", "WORDLIST", "Payload words from the BIP39 list, or phonetically distinct words for voice").value_parser(["bip39", "phonetic"]).long_help(
+            "bip39 (default) or phonetic: alternating two- and three-syllable words, one\n\
+             byte each, for voice channels",
+        ))
+        .arg(value("engine", "ENGINE", "Write prose, or bursts of emoji for platforms where prose is unusual").value_parser(capabilities::ENGINES.to_vec()).long_help(
+            "prose (default) or emoji (bursts of emoji, 10 bits each, for short messages);\n\
+             pass it to --decode too\n\
+             neural (feature 'neural'): a language model writes the text and the\n\
+             --from-ascii payload picks among its likeliest tokens",
+        ))
+        .arg(value("model", "FILE", "With --engine neural: the GGUF model that writes and reads the text").value_hint(ValueHint::FilePath))
+        .arg(value("tokenizer", "FILE", "With --engine neural: the model's tokenizer.json").value_hint(ValueHint::FilePath))
+        .arg(value("prompt", "TEXT", "With --engine neural: text the cover text continues"))
+        .arg(value("top-k", "N", "With --engine neural: tokens weighed per step, a power of two").long_help(
+            "With --engine neural: tokens weighed per step, a power of two (default 16, up\n\
+             to 4 bits a token)",
+        ))
+        .arg(value("slot-threshold", "W", "Payload words fill only the POS slots they are weighted above W for").long_help(format!(
+            "Payload words fill only the POS slots they are weighted above W for in\n\
+             payload.yaml (default 0: any positive weight); rejected if a word would fit no\n\
+             slot or a slot falls below {} words",
+            MIN_SLOT_WORDS
+        )))
+        .arg(value("session", "FILE", "Encode a large --from-ascii payload in resumable chunks").conflicts_with_all(["whiten", "interleave", "interleave-depth", "sign", "decoy", "chaff"]).value_hint(ValueHint::FilePath).long_help(
+            "Encode a large --from-ascii payload in resumable chunks, checkpointing\n\
+             progress to FILE (resumed if it exists)",
+        ))
+        .arg(value("chunks", "N", "With --session: stop after N chunks").long_help(format!("With --session: stop after N chunks of {} words", SESSION_CHUNK_WORDS)))
+        .arg(flag("audit", "Print a detectability score card for the output"))
+        .arg(input("detect", "Report whether text looks like it carries a payload ('-' for stdin)").conflicts_with_all(["decode", "decode-ascii", "payload"]))
+        .arg(value("file", "PATH", "Read the text for --decode/--detect from a PDF, DOCX or text file").conflicts_with_all(["from-clipboard", "clipboard"]).value_hint(ValueHint::FilePath).long_help(
+            "Read the text for --decode/--decode-ascii/--detect (which then take no value)\n\
+             from a file: PDF (text layer, needs the 'pdf' feature), DOCX ('docx' feature)\n\
+             or plain text",
+        ))
+        .arg(flag("html", "Read --decode/--detect input as an HTML page or RSS/Atom feed").long_help(
+            "Read --decode/--detect input as an HTML page or RSS/Atom feed: only the\n\
+             visible text is decoded",
+        ))
+        .arg(flag("from-clipboard", "Read the input from the clipboard").long_help(
+            "Read the input from the clipboard: the text to encode, or the text for\n\
+             --decode/--decode-ascii/--detect (which then take no value). An encode secret\n\
+             is cleared from the clipboard",
+        ))
+        .arg(flag("to-clipboard", "Copy the cover text (or decoded payload) to the clipboard"))
+        .arg(value("config", "FILE", "Defaults from a TOML config (default: ~/.config/glossia/config.toml)").value_hint(ValueHint::FilePath).long_help(
+            "Defaults from a TOML config (default: ~/.config/glossia/config.toml); flags\n\
+             override it",
+        ))
+        .arg(value("wrap", "N", "Wrap prose output at word boundaries to N columns (0: one line per paragraph)").long_help(format!(
+            "Wrap prose output at word boundaries to N columns (default {}; 0 for one line\n\
+             per paragraph); --decode undoes it",
+            DEFAULT_WRAP
+        )))
+        .arg(flag("clipboard", "Both --from-clipboard and --to-clipboard: replace the clipboard contents with their encoding").short('c'))
+        .arg(flag("json", "Print results (and errors, with a code) as JSON on stdout"))
+        .arg(flag("json-errors", "Report errors as JSON on stderr").long_help(
+            "Report errors as {\"error\": {\"code\", \"message\", \"exit_code\"}} on stderr",
+        ))
+        .arg(flag("verbose", "Show detailed debugging information").short('v'))
+        // One payload to encode, and no payload when decoding or detecting
+        .group(ArgGroup::new("payload").args(["words", "random", "from-ascii", "container-entry"]))
+        .subcommand(Command::new("repl").about("Encode and decode interactively with the wordlist and grammar kept loaded"))
+        .subcommand(
+            Command::new("watch")
+                .about("Keep .glossia.txt cover texts beside every file in a directory")
+                .arg(Arg::new("dir").required(true).value_hint(ValueHint::DirPath))
+                .arg(value("passphrase", "P", "Key the whitening of every file")),
+        )
+        .subcommand(
+            Command::new("completions")
+                .about("Print a shell completion script")
+                .arg(Arg::new("shell").required(true).value_parser(["bash", "elvish", "fish", "powershell", "zsh"])),
+        )
+        .subcommand(
+            Command::new("manpages")
+                .about("Write man pages for glossia and its subcommands")
+                .arg(Arg::new("dir").required(true).value_hint(ValueHint::DirPath)),
+        )
+        .subcommand(Command::new("capabilities").about("Print the engines, styles, formats and features of this build as JSON"))
+        .subcommand(
+            Command::new("train-cover-model")
+                .about("Train a --cover-model from a corpus of text like the cover should read")
+                .arg(Arg::new("corpus").required(true).value_hint(ValueHint::FilePath))
+                .arg(Arg::new("model").required(true).value_hint(ValueHint::FilePath)),
+        )
+}
+
+/// A clap error as a usage message: its first paragraph, without clap's
+/// `error:` prefix, tip or usage.
+fn usage_message(err: &clap::Error) -> String {
+    let text = err.render().to_string();
+    let text = text.strip_prefix("error: ").unwrap_or(&text);
+    text.split("\n\n").next().unwrap_or_default().trim_end().to_string()
+}
+
+/// `glossia completions <shell>`
+fn run_completions(args: &[String]) -> Result<(), CommandError> {
+    let [shell] = args else {
+        return Err(CommandError::usage("Usage: glossia completions <bash|elvish|fish|powershell|zsh>"));
+    };
+    let shell: clap_complete::Shell = shell.parse().map_err(CommandError::usage)?;
+    clap_complete::generate(shell, &mut cli_command(), "glossia", &mut std::io::stdout());
+    Ok(())
+}
+
+/// `glossia manpages <dir>`: glossia.1 plus one page per subcommand.
+fn run_manpages(args: &[String]) -> Result<(), CommandError> {
+    let [dir] = args else {
+        return Err(CommandError::usage("Usage: glossia manpages <dir>"));
+    };
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create {}: {}", dir, e))?;
+    clap_mangen::generate_to(cli_command(), dir).map_err(|e| format!("Failed to write man pages to {}: {}", dir, e).into())
+}
+
+/// `glossia capabilities`: the engines, styles, formats and features of this
+/// build as JSON.
+fn run_capabilities(args: &[String]) -> Result<(), CommandError> {
+    if !args.is_empty() {
+        return Err(CommandError::usage("Usage: glossia capabilities"));
+    }
+    println!("{}", capabilities::capabilities().to_json());
+    Ok(())
+}
+
+/// The topic of a thread file: its messages, oldest first, separated by blank
+/// lines. `None` if they have no content words.
+fn topic_of_thread(thread: &str) -> Option<Topic> {
+    let mut messages = vec![String::new()];
+    for line in thread.lines() {
+        match messages.last_mut() {
+            Some(message) if !line.trim().is_empty() => message.extend([line, "\n"]),
+            _ => messages.push(String::new()),
+        }
+    }
+    let messages: Vec<&str> = messages.iter().map(String::as_str).filter(|m| !m.is_empty()).collect();
+    Some(Topic::from_messages(&messages)).filter(|topic| !topic.is_empty())
+}
+
+/// `glossia train-cover-model <corpus> <model>`: count the word trigrams of
+/// a corpus and store them for `--cover-model`.
+fn run_train_cover_model(args: &[String]) -> Result<(), CommandError> {
+    let [corpus, model] = args else {
+        return Err(CommandError::usage("Usage: glossia train-cover-model <corpus> <model>"));
+    };
+    let text = std::fs::read_to_string(corpus).map_err(|e| format!("Failed to read corpus '{}': {}", corpus, e))?;
+    let trained = CoverModel::train(&text).map_err(|e| CommandError::usage(format!("{}: {}", corpus, e)))?;
+    let bytes = trained.to_bytes();
+    std::fs::write(model, &bytes).map_err(|e| format!("Failed to write cover model '{}': {}", model, e))?;
+    eprintln!("Wrote {} ({} words, {} bytes)", model, trained.vocabulary(), bytes.len());
+    Ok(())
+}
+
+/// Parsed command-line options.
+struct CliArgs {
+    words: Vec<String>,
+    random_count: Option<usize>,
+    ascii_input: Option<SecretPayload>,
+    verbose: bool,
+    seed: Option<u64>,
+    variations: usize,
+    highlight_mode: HighlightMode,
+    generation_mode: GenerationMode,
+    language: String,
+    show_grammar: bool,
+    export_dot: Option<String>,
+    explain: bool,
+    audit: bool,
+    max_grade: Option<f64>,
+    avoid_default: bool,
+    avoid_words: Vec<String>,
+    avoid_patterns: Vec<String>,
+    homophone_safe: bool,
+    prefixes: bool,
+    parity: bool,
+    lemmatize: bool,
+    lossy: bool,
+    constant_time: bool,
+    extract_container: Option<PathBuf>,
+    decode_input: Option<String>,
+    decode_ascii: bool,
+    detect_input: Option<String>,
+    json: bool,
+    to_clipboard: bool,
+    payload_mode: PayloadMode,
+    checksum: bool,
+    fingerprint: bool,
+    read_back: Option<String>,
+    footer: Option<String>,
+    noise: Option<NoiseModel>,
+    dictionary: Option<PathBuf>,
+    data_mode: Option<DataMode>,
+    passphrase: Option<String>,
+    decoy: Option<String>,
+    decoy_passphrase: Option<String>,
+    deniable: bool,
+    chaff_fraction: Option<f64>,
+    slot_threshold: f64,
+    style: Option<StyleProfile>,
+    cover_model: Option<CoverModel>,
+    reply_to: Option<Topic>,
+    synonym_payload: Option<String>,
+    synonyms: bool,
+    number_payload: Option<String>,
+    numbers: bool,
+    template: Option<TemplateSet>,
+    output_style: Style,
+    engine: Engine,
+    #[cfg(feature = "neural")]
+    neural: Option<NeuralSettings>,
+    wordlist: Wordlist,
+    whiten: bool,
+    commit: bool,
+    interleave: Option<usize>,
+    sign: Option<SigningKey>,
+    verify: Option<VerifyingKey>,
+    patch_base: Option<Vec<u8>>,
+    timestamp: bool,
+    reject_expired: bool,
+    resync: Option<usize>,
+    resync_fragment: Option<Fragment>,
+    session: Option<String>,
+    chunks: Option<usize>,
+    k_min: usize,
+    k_max: usize,
+    length_mode: SentenceLengthMode,
+    wrap: usize,
+}
+
+/// The number `--name` was given, if it was.
+fn number_arg<T: std::str::FromStr>(matches: &clap::ArgMatches, name: &str) -> Result<Option<T>, String> {
+    matches
+        .get_one::<String>(name)
+        .map(|value| value.parse().map_err(|_| format!("Invalid number for --{}: {}", name, value)))
+        .transpose()
+}
+
+/// Text given on the command line, or read from stdin for `-`.
+fn text_arg(value: &str) -> Result<String, String> {
+    if value != "-" {
+        return Ok(value.to_string());
+    }
+    use std::io::{self, Read};
+    let mut buffer = String::new();
+    io::stdin().read_to_string(&mut buffer).map_err(|e| format!("Failed to read from stdin: {}", e))?;
+    Ok(buffer)
+}
+
+fn parse_args() -> Result<CliArgs, String> {
+    if env::args_os().len() < 2 {
+        return Err("No words provided. Use --random <N> or provide words as arguments.".to_string());
+    }
+    let matches = match cli_command().try_get_matches_from(env::args_os()) {
+        Ok(matches) => matches,
+        Err(e) if e.kind() == clap::error::ErrorKind::DisplayHelp => e.exit(),
+        Err(e) => return Err(usage_message(&e)),
+    };
+    if let Some((name, _)) = matches.subcommand() {
+        // main runs subcommands given first; later on they are words
+        return Err(format!("'{}' is a command: use glossia {} ...", name, name));
+    }
+    let value = |name: &str| matches.get_one::<String>(name).cloned();
+    let values = |name: &str| matches.get_many::<String>(name).into_iter().flatten().cloned().collect::<Vec<_>>();
+    let flag = |name: &str| matches.get_flag(name);
+    let given = |name: &str| matches.value_source(name) == Some(clap::parser::ValueSource::CommandLine);
+
+    // The config file's defaults, which the flags override
+    let config = match value("config") {
+        Some(path) => GlossiaConfig::from_file(&path),
+        None => GlossiaConfig::load_default(),
+    }
+    .map_err(|e| format!("{:#}", e))?;
+
+    let words = values("words");
+    let random_count: Option<usize> = number_arg(&matches, "random")?;
+    let verbose = flag("verbose");
+    let seed = number_arg(&matches, "seed")?.or(config.seed);
+    let variations = number_arg(&matches, "variations")?.unwrap_or(1);
+    if variations == 0 {
+        return Err("--variations must be at least 1".to_string());
+    }
+
+    let mut payload_mode = config.mode.as_deref().and_then(PayloadMode::from_name).unwrap_or(PayloadMode::Auto);
+    let mut generation_mode = config.grammar.as_deref().and_then(GenerationMode::from_name).unwrap_or(GenerationMode::Subject);
+    if let Some(mode) = value("mode") {
+        match PayloadMode::from_name(&mode) {
+            Some(mode) => payload_mode = mode,
+            None => {
+                // Deprecated: support old --mode flag for backward compatibility
+                eprintln!("Warning: --mode subject|body is deprecated, use --grammar instead");
+                generation_mode = GenerationMode::from_name(&mode)
+                    .ok_or_else(|| format!("Invalid grammar: {}. Use 'subject' or 'body'", mode))?;
+            }
+        }
+    }
+    if let Some(grammar) = value("grammar") {
+        generation_mode = GenerationMode::from_name(&grammar)
+            .ok_or_else(|| format!("Invalid grammar: {}. Use 'subject' or 'body'", grammar))?;
+    }
+
+    let mut highlight_mode = match value("highlight").as_deref() {
+        None | Some("bars") => HighlightMode::Bars,
+        Some("none") => HighlightMode::None,
+        Some(color) => HighlightMode::Color(parse_color(color)?),
+    };
+    // Whichever of --madlib and --highlight comes last
+    if flag("madlib") && matches.index_of("madlib") > matches.index_of("highlight") {
+        highlight_mode = HighlightMode::Madlib;
+    }
+
+    let language = value("language").or_else(|| config.language.clone()).unwrap_or_else(|| "english".to_string());
+    let show_grammar = flag("show-grammar");
+    let export_dot = value("export-dot");
+    let explain = flag("explain");
+    let audit = flag("audit");
+    let max_grade = number_arg(&matches, "max-grade")?;
+    let avoid_default = flag("avoid");
+    let avoid_words: Vec<String> = values("avoid-words")
+        .iter()
+        .flat_map(|list| list.split(',').map(|w| w.trim().to_string()).filter(|w| !w.is_empty()))
+        .collect();
+    let avoid_patterns = values("avoid-pattern");
+    let homophone_safe = flag("homophone-safe");
+    let prefixes = flag("prefixes");
+    let mut parity = flag("parity") || config.fec == FecLevel::Parity;
+    let lemmatize = flag("lemmatize");
+    let lossy = flag("lossy");
+    let constant_time = flag("constant-time");
+    let fingerprint = flag("fingerprint");
+    let footer = match value("footer-template") {
+        Some(template) => Some(template),
+        None => config.footer.clone().or_else(|| flag("footer").then(|| footer::DEFAULT_TEMPLATE.to_string())),
+    };
+
+    // The clipboard or --file supplies the text for --decode/--decode-ascii/--detect,
+    // which then take no value
+    let from_clipboard = flag("from-clipboard") || flag("clipboard");
+    let from_file = value("file");
+    let text_input = |name: &str| -> Result<Option<String>, String> {
+        if !given(name) {
+            return Ok(None);
+        }
+        match (matches.get_one::<String>(name), &from_file) {
+            (Some(_), _) if from_clipboard || from_file.is_some() => {
+                Err(format!("--{} takes no value with --from-clipboard or --file", name))
+            }
+            (Some(text), _) => text_arg(text).map(Some),
+            (None, _) if from_clipboard => read_clipboard().map(Some),
+            (None, Some(path)) => read_document(path).map(Some),
+            (None, None) => Err(format!("--{} requires a value (text string or '-' for stdin)", name)),
+        }
+    };
+    let decode_ascii = given("decode-ascii");
+    let mut decode_input = text_input(if decode_ascii { "decode-ascii" } else { "decode" })?;
+    let mut detect_input = text_input("detect")?;
+    let html_input = flag("html");
+    let json = flag("json");
+    let to_clipboard = flag("to-clipboard") || flag("clipboard");
+    let mut checksum = flag("checksum") || config.fec != FecLevel::None;
+
+    let noise = match value("noise").as_deref() {
+        None => None,
+        Some("ocr") => Some(NoiseModel::Ocr),
+        Some("asr") => Some(NoiseModel::Asr),
+        Some(other) => return Err(format!("Invalid noise model: {}. Use 'ocr' or 'asr'", other)),
+    };
+    let dictionary = value("dictionary").map(PathBuf::from);
+    let data_mode = match value("data-mode") {
+        Some(name) => {
+            let names: Vec<&str> = DataMode::ALL.iter().map(DataMode::name).collect();
+            Some(DataMode::from_name(&name).ok_or_else(|| format!("Invalid data mode: {}. Use one of: {}", name, names.join(", ")))?)
+        }
+        None => None,
+    };
+    let schema_id: Option<u64> = match value("schema-id") {
+        Some(id) => Some(id.parse().map_err(|_| format!("Invalid schema id: {}", id))?),
+        None => None,
+    };
+    let age_recipients = values("age-recipient");
+    let container_entries = values("container-entry")
+        .iter()
+        .map(|entry| match entry.split_once('=') {
+            Some((name, path)) => Ok((name.to_string(), PathBuf::from(path))),
+            None => Err("--container-entry requires a name=file value".to_string()),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    let extract_container = value("extract-container").map(PathBuf::from);
+    let expires = value("expires");
+    let timestamp = flag("timestamp");
+    let reject_expired = flag("reject-expired");
+    let resync: Option<usize> = number_arg(&matches, "resync")?;
+    if resync == Some(0) {
+        return Err("--resync must be at least 1".to_string());
+    }
+    let resume_from: Option<usize> = match value("resume-from") {
+        Some(offset) => Some(offset.parse().map_err(|_| format!("Invalid byte offset for --resume-from: {}", offset))?),
+        None => None,
+    };
+    let patch_base = value("patch-base").map(PathBuf::from);
+
+    let passphrase = value("passphrase");
+    let decoy = value("decoy");
+    let decoy_passphrase = value("decoy-passphrase");
+    let sign = match value("sign") {
+        Some(key) => {
+            let key = match key.strip_prefix('@') {
+                Some(path) => std::fs::read_to_string(path).map_err(|e| format!("Failed to read key file '{}': {}", path, e))?,
+                None => key,
+            };
+            Some(signing::parse_secret_key(&key).map_err(|e| format!("Invalid --sign key: {}", e))?)
+        }
+        None => None,
+    };
+    let verify = match value("verify") {
+        Some(key) => Some(signing::parse_public_key(&key).map_err(|e| format!("Invalid --verify key: {}", e))?),
+        None => None,
+    };
+    let session = value("session");
+    let chunks: Option<usize> = number_arg(&matches, "chunks")?;
+    if chunks == Some(0) {
+        return Err("--chunks must be at least 1".to_string());
+    }
+    let whiten = flag("whiten");
+    let commit = flag("commit");
+    let interleave = match number_arg(&matches, "interleave-depth")? {
+        Some(0) => return Err("--interleave-depth must be at least 1".to_string()),
+        Some(depth) => Some(depth),
+        None => flag("interleave").then_some(interleave::DEFAULT_DEPTH),
+    };
+    let deniable = flag("deniable");
+    let chaff_fraction: Option<f64> = number_arg(&matches, "chaff")?;
+    if chaff_fraction.is_some_and(|fraction| !(0.0..0.9).contains(&fraction)) {
+        return Err("--chaff must be at least 0 and below 0.9".to_string());
+    }
+
+    let style = match value("style") {
+        Some(path) => {
+            let sample = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read style sample '{}': {}", path, e))?;
+            let profile = StyleProfile::from_text(&sample);
+            if profile.is_empty() {
+                return Err(format!("Style sample '{}' has no sentences", path));
+            }
+            Some(profile)
+        }
+        None => None,
+    };
+    let cover_model = match value("cover-model") {
+        Some(path) => {
+            let bytes = std::fs::read(&path).map_err(|e| format!("Failed to read cover model '{}': {}", path, e))?;
+            Some(CoverModel::from_bytes(&bytes).map_err(|e| format!("{}: {}", path, e))?)
+        }
+        None => None,
+    };
+    let reply_to = match value("reply-to") {
+        Some(path) => {
+            let thread = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read thread '{}': {}", path, e))?;
+            Some(topic_of_thread(&thread).ok_or_else(|| format!("Thread '{}' has no content words", path))?)
+        }
+        None => None,
+    };
+    let synonym_payload = value("synonym-payload");
+    if synonym_payload.as_ref().is_some_and(|text| text.len() > synonyms::MAX_PAYLOAD_BYTES) {
+        return Err(format!("--synonym-payload holds at most {} bytes", synonyms::MAX_PAYLOAD_BYTES));
+    }
+    let synonyms = flag("synonyms");
+    let number_payload = value("number-payload");
+    if number_payload.as_ref().is_some_and(|text| text.len() > synonyms::MAX_PAYLOAD_BYTES) {
+        return Err(format!("--number-payload holds at most {} bytes", synonyms::MAX_PAYLOAD_BYTES));
+    }
+    let numbers = flag("numbers");
+    let template = match value("template") {
+        Some(path) => {
+            let content = std::fs::read_to_string(&path).map_err(|e| format!("Failed to read template file '{}': {}", path, e))?;
+            Some(TemplateSet::parse(&content).map_err(|e| format!("Invalid template file '{}': {}", path, e))?)
+        }
+        None => None,
+    };
+    let output_style = match value("output-style") {
+        Some(name) => Style::from_name(&name).ok_or_else(|| format!("Invalid output style: {}. Use 'prose', 'list' or 'recipe'", name))?,
+        None => config.style.as_deref().and_then(Style::from_name).unwrap_or(Style::Prose),
+    };
+    let engine = match value("engine") {
+        Some(name) => {
+            let (last, others) = capabilities::ENGINES.split_last().expect("there are engines");
+            Engine::from_name(&name).ok_or_else(|| format!("Invalid engine: {}. Use '{}' or '{}'", name, others.join("', '"), last))?
+        }
+        None => config.engine.as_deref().and_then(Engine::from_name).unwrap_or(Engine::Prose),
+    };
+    let model = value("model");
+    let tokenizer = value("tokenizer");
+    let prompt = value("prompt");
+    let top_k = match value("top-k") {
+        Some(k) => match k.parse::<usize>() {
+            Ok(k) if k >= 2 && k.is_power_of_two() => Some(k),
+            _ => return Err(format!("Invalid --top-k: {}. Use a power of two from 2", k)),
+        },
+        None => None,
+    };
+    let wordlist = match value("wordlist") {
+        Some(name) => Wordlist::from_name(&name).ok_or_else(|| format!("Invalid wordlist: {}. Use 'bip39' or 'phonetic'", name))?,
+        None => config.wordlist.as_deref().and_then(Wordlist::from_name).unwrap_or(Wordlist::Bip39),
+    };
+    let slot_threshold = number_arg(&matches, "slot-threshold")?.unwrap_or(0.0);
+    if !(0.0..1.0).contains(&slot_threshold) {
+        return Err("--slot-threshold must be at least 0 and below 1".to_string());
+    }
+    let k_min = number_arg(&matches, "k-min")?.unwrap_or(3);
+    if k_min < 3 {
+        return Err("--k-min must be at least 3".to_string());
+    }
+    let k_max = number_arg(&matches, "k-max")?.unwrap_or(20);
+    if k_max < k_min {
+        return Err(format!("--k-max ({}) must be >= --k-min ({})", k_max, k_min));
+    }
+    if k_max > 20 {
+        return Err("--k-max cannot exceed 20 (sequences only precomputed up to k=20)".to_string());
+    }
+    let mut length_mode_explicit = given("length-mode");
+    let mut length_mode = match value("length-mode").as_deref() {
+        None | Some("compact") => SentenceLengthMode::Compact,
+        Some("natural") => SentenceLengthMode::Natural,
+        Some(other) => return Err(format!("Invalid length mode: {}. Use 'compact' or 'natural'", other)),
+    };
+    let low_bandwidth = flag("low-bandwidth");
+    let wrap = number_arg(&matches, "wrap")?.unwrap_or(DEFAULT_WRAP);
+    let mut ascii_input = match value("from-ascii") {
+        // Read stdin raw, so binary payloads pass through unchanged
+        Some(input) if input == "-" => {
+            use std::io::{self, Read};
+            let mut buffer = Vec::new();
+            io::stdin().read_to_end(&mut buffer).map_err(|e| format!("Failed to read from stdin: {}", e))?;
+            Some(SecretPayload::new(buffer))
+        }
+        Some(input) => Some(SecretPayload::from(input)),
+        None => None,
+    };
+
+    if !container_entries.is_empty() {
+        let mut entries = IndexMap::new();
+        for (name, path) in &container_entries {
+            let bytes = std::fs::read(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+            if entries.insert(name.clone(), bytes).is_some() {
+                return Err(format!("--container-entry '{}' is given twice", name));
+            }
+        }
+        let packed = container::pack_container(&entries).map_err(|e| format!("--container-entry: {}", e));
+        entries.values_mut().for_each(Wipe::wipe);
+        let packed = packed?;
+        if verbose {
+            eprintln!("Packed {} entries into a {}-byte container", entries.len(), packed.len());
+        }
+        ascii_input = Some(SecretPayload::new(packed));
+    }
+
+    if noise.is_some() && decode_input.is_none() {
+        return Err("--noise only applies to --decode".to_string());
+    }
+    if dictionary.is_some() && noise != Some(NoiseModel::Ocr) {
+        return Err("--dictionary only applies to --noise ocr".to_string());
+    }
+
+    if decoy.is_some() {
+        if ascii_input.is_none() {
+            return Err("--decoy requires the real payload via --from-ascii".to_string());
+        }
+        if passphrase.is_none() || decoy_passphrase.is_none() {
+            return Err("--decoy requires both --passphrase and --decoy-passphrase".to_string());
+        }
+        if checksum {
+            return Err("--checksum cannot be combined with --decoy (the carrier has its own checks)".to_string());
+        }
+    } else if decoy_passphrase.is_some() {
+        return Err("--decoy-passphrase only applies with --decoy".to_string());
+    }
+
+    if sign.is_some() && ascii_input.is_none() {
+        return Err("--sign applies to --from-ascii payloads".to_string());
+    }
+    if verify.is_some() && !decode_ascii {
+        return Err("--verify needs --decode-ascii".to_string());
+    }
+
+    if deniable && (decode_input.is_none() || passphrase.is_none()) {
+        return Err("--deniable applies to --decode and needs --passphrase".to_string());
+    }
+    if passphrase.is_some() && decoy.is_none() && !deniable && chaff_fraction.is_none() && !whiten {
+        return Err("--passphrase needs --decoy, --deniable, --chaff or --whiten".to_string());
+    }
+    if whiten {
+        if decode_input.is_some() && !decode_ascii {
+            return Err("--whiten decodes to bytes; use --decode-ascii".to_string());
+        }
+        if decode_input.is_none() && ascii_input.is_none() {
+            return Err("--whiten applies to --from-ascii payloads".to_string());
+        }
+    }
+    if commit && (!whiten || passphrase.is_none()) {
+        return Err("--commit needs --whiten and --passphrase".to_string());
+    }
+    if interleave.is_some() {
+        if decode_input.is_some() && !decode_ascii {
+            return Err("--interleave decodes to bytes; use --decode-ascii".to_string());
+        }
+        if decode_input.is_none() && ascii_input.is_none() {
+            return Err("--interleave applies to --from-ascii payloads".to_string());
+        }
+    }
+    if chaff_fraction.is_some() && decode_input.is_none() {
+        if generation_mode != GenerationMode::Body {
+            return Err("--chaff needs --grammar body (sentences are the unit of chaff)".to_string());
+        }
+        if highlight_mode == HighlightMode::Madlib {
+            return Err("--chaff cannot be combined with --madlib".to_string());
+        }
+    }
+
+    if session.is_some() {
+        if ascii_input.is_none() {
+            return Err("--session applies to --from-ascii payloads".to_string());
+        }
+        if checksum {
+            return Err("--session cannot be combined with --checksum".to_string());
+        }
+        if variations > 1 {
+            return Err("--session generates one text per chunk; drop --variations".to_string());
+        }
+    } else if chunks.is_some() {
+        return Err("--chunks only applies with --session".to_string());
+    }
+
+    if synonym_payload.is_some() && (decode_input.is_some() || detect_input.is_some()) {
+        return Err("--synonym-payload applies when encoding (read it back with --decode --synonyms)".to_string());
+    }
+    if synonyms && decode_input.is_none() {
+        return Err("--synonyms only applies to --decode".to_string());
+    }
+    if number_payload.is_some() && (decode_input.is_some() || detect_input.is_some()) {
+        return Err("--number-payload applies when encoding (read it back with --decode --numbers)".to_string());
+    }
+    if numbers && decode_input.is_none() {
+        return Err("--numbers only applies to --decode".to_string());
+    }
+    if template.is_some() && output_style != Style::Prose {
+        return Err("--template cannot be combined with --output-style".to_string());
+    }
+    // Both lay out the words themselves and decode by position
+    let fixed_layout = match (&template, output_style) {
+        (Some(_), _) => Some("--template"),
+        (None, Style::Prose) => None,
+        (None, _) => Some("--output-style"),
+    };
+    if let Some(option) = fixed_layout {
+        if variations > 1 {
+            return Err(format!("{} cannot be combined with --variations", option));
+        }
+        if output_style != Style::Prose {
+            if chaff_fraction.is_some() || style.is_some() || synonym_payload.is_some() {
+                return Err("--output-style cannot be combined with --chaff, --style or --synonym-payload".to_string());
+            }
+            if noise.is_some() || lemmatize || deniable || decoy.is_some() {
+                return Err("--output-style cannot be combined with --noise, --lemmatize, --decoy or --deniable".to_string());
+            }
+        }
+    }
+
+    if engine == Engine::Emoji {
+        if fixed_layout.is_some() || chaff_fraction.is_some() || style.is_some() || cover_model.is_some() || reply_to.is_some() || variations > 1 {
+            return Err("--engine emoji cannot be combined with --template, --output-style, --chaff, --style, --cover-model, --reply-to or --variations".to_string());
+        }
+        if synonym_payload.is_some() || synonyms || number_payload.is_some() || numbers {
+            return Err("--engine emoji has no synonyms or numbers to carry a side payload".to_string());
+        }
+        if noise.is_some() || lemmatize || homophone_safe || deniable || decoy.is_some() {
+            return Err("--engine emoji cannot be combined with --noise, --lemmatize, --homophone-safe, --decoy or --deniable".to_string());
+        }
+    }
+
+    // --engine neural: the model, its tokenizer and how it writes
+    #[cfg(feature = "neural")]
+    let neural = match (engine, model, tokenizer) {
+        (Engine::Neural, Some(model), Some(tokenizer)) => {
+            let mut options = NeuralOptions { prompt: prompt.unwrap_or_default(), ..Default::default() };
+            options.top_k = top_k.unwrap_or(options.top_k);
+            Some(NeuralSettings { model, tokenizer, options })
+        }
+        (Engine::Neural, _, _) => return Err("--engine neural needs --model and --tokenizer".to_string()),
+        (_, None, None) if prompt.is_none() && top_k.is_none() => None,
+        _ => return Err("--model, --tokenizer, --prompt and --top-k only apply to --engine neural".to_string()),
+    };
+    #[cfg(not(feature = "neural"))]
+    if model.is_some() || tokenizer.is_some() || prompt.is_some() || top_k.is_some() {
+        return Err("--model, --tokenizer, --prompt and --top-k need glossia built with the 'neural' feature".to_string());
+    }
+    #[cfg(feature = "neural")]
+    if neural.is_some() {
+        // The model carries bytes, not wordlist words
+        if ascii_input.is_none() && !(decode_input.is_some() && decode_ascii) {
+            return Err("--engine neural carries --from-ascii payloads, read back with --decode-ascii".to_string());
+        }
+        if fixed_layout.is_some() || chaff_fraction.is_some() || style.is_some() || cover_model.is_some() || reply_to.is_some() || variations > 1 || session.is_some() {
+            return Err("--engine neural cannot be combined with --template, --output-style, --chaff, --style, --cover-model, --reply-to, --variations or --session".to_string());
+        }
+        if synonym_payload.is_some() || synonyms || number_payload.is_some() || numbers || fingerprint {
+            return Err("--engine neural cannot be combined with --synonym-payload, --number-payload or --fingerprint".to_string());
+        }
+        if noise.is_some() || lemmatize || homophone_safe || prefixes || parity || lossy || constant_time || wordlist == Wordlist::Phonetic {
+            return Err("--engine neural reads tokens, not words: it cannot be combined with --noise, --lemmatize, --homophone-safe, --prefixes, --parity, --lossy, --constant-time or --wordlist phonetic".to_string());
+        }
+        if checksum || whiten || interleave.is_some() || sign.is_some() || verify.is_some() || deniable || decoy.is_some() {
+            return Err("--engine neural cannot be combined with --checksum, --whiten, --interleave, --sign, --verify, --deniable or --decoy".to_string());
+        }
+    }
+
+    if wordlist == Wordlist::Phonetic {
+        // One byte per word, framed by the alternation rather than by the codec
+        if whiten || interleave.is_some() || sign.is_some() || verify.is_some() || session.is_some() || checksum {
+            return Err("--wordlist phonetic cannot be combined with --whiten, --interleave, --sign, --verify, --session or --checksum".to_string());
+        }
+        if noise.is_some() || lemmatize || deniable || decoy.is_some() || engine == Engine::Emoji {
+            return Err("--wordlist phonetic cannot be combined with --noise, --lemmatize, --decoy, --deniable or --engine emoji".to_string());
+        }
+    }
+
+    if prefixes && (engine == Engine::Emoji || output_style != Style::Prose) {
+        return Err("--prefixes cannot be combined with --engine emoji or --output-style".to_string());
+    }
+
+    if parity {
+        // Markers end prose sentences and are themselves wordlist words
+        if engine == Engine::Emoji || fixed_layout.is_some() || variations > 1 {
+            return Err("--parity cannot be combined with --engine emoji, --template, --output-style or --variations".to_string());
+        }
+        if noise.is_some() || lemmatize || deniable || decoy.is_some() || wordlist == Wordlist::Phonetic {
+            return Err("--parity cannot be combined with --noise, --lemmatize, --decoy, --deniable or --wordlist phonetic".to_string());
+        }
+    }
+
+    if lemmatize && decode_input.is_none() {
+        return Err("--lemmatize only applies to --decode".to_string());
+    }
+
+    if lossy {
+        if decode_input.is_none() || !decode_ascii {
+            return Err("--lossy only applies to --decode-ascii".to_string());
+        }
+        if parity || wordlist == Wordlist::Phonetic {
+            return Err("--lossy cannot be combined with --parity or --wordlist phonetic".to_string());
+        }
+    }
+
+    if resync.is_some() {
+        // Markers count plain codec carriers between them
+        if checksum || parity || variations > 1 {
+            return Err("--resync cannot be combined with --checksum, --parity or --variations".to_string());
+        }
+        if engine != Engine::Prose || output_style != Style::Prose || wordlist == Wordlist::Phonetic {
+            return Err("--resync cannot be combined with --engine, --output-style or --wordlist phonetic".to_string());
+        }
+        if decode_input.is_some() && !decode_ascii {
+            return Err("--resync decodes to bytes; use --decode-ascii".to_string());
+        }
+    }
+    if resume_from.is_some() && (resync.is_none() || decode_input.is_some()) {
+        return Err("--resume-from needs --resync and a --from-ascii payload".to_string());
+    }
+
+    // --explain traces grammar sentences over codec carriers, and prints instead of writing
+    if explain {
+        if decode_input.is_some() || detect_input.is_some() {
+            return Err("--explain only applies to encoding".to_string());
+        }
+        if engine != Engine::Prose || output_style != Style::Prose || wordlist == Wordlist::Phonetic || variations > 1 {
+            return Err("--explain cannot be combined with --engine, --output-style, --wordlist phonetic or --variations".to_string());
+        }
+    }
+
+    // The footer goes on the one text printed
+    if footer.is_some() && decode_input.is_none() && variations > 1 {
+        return Err("--footer cannot be combined with --variations".to_string());
+    }
+
+    if constant_time {
+        if decode_input.is_none() || !decode_ascii {
+            return Err("--constant-time only applies to --decode-ascii".to_string());
+        }
+        if parity || output_style != Style::Prose || engine == Engine::Emoji || wordlist == Wordlist::Phonetic {
+            return Err("--constant-time cannot be combined with --parity, --output-style, --engine emoji or --wordlist phonetic".to_string());
+        }
+    }
+
+    if (from_clipboard || to_clipboard) && !cfg!(feature = "clipboard") {
+        return Err("Clipboard options need glossia built with the 'clipboard' feature".to_string());
+    }
+    if from_clipboard && decode_input.is_none() && detect_input.is_none() {
+        if ascii_input.is_some() || random_count.is_some() || !words.is_empty() {
+            return Err("--from-clipboard cannot be combined with other input".to_string());
+        }
+        // Take the secret and clear it, so it leaves the clipboard before encoding starts
+        ascii_input = Some(SecretPayload::from(read_clipboard()?));
+        write_clipboard("")?;
+    }
+    if payload_mode == PayloadMode::Text {
+        if let Some(ref input) = ascii_input {
+            if std::str::from_utf8(input.expose()).is_err() {
+                return Err("Input is not valid UTF-8 text; use --mode binary".to_string());
             }
-            "--length-mode" => {
-                if i + 1 >= args.len() {
-                    return Err("--length-mode requires a value".to_string());
+        }
+    }
+    if extract_container.is_some() && (decode_input.is_none() || !decode_ascii || data_mode.is_some()) {
+        return Err("--extract-container only applies to --decode-ascii, without --data-mode".to_string());
+    }
+    // Name the payload as given, before a patch, age or --data-mode repacks it
+    let read_back = match (fingerprint, &ascii_input) {
+        (false, _) => None,
+        _ if variations > 1 => return Err("--fingerprint cannot be combined with --variations".to_string()),
+        (true, Some(input)) => Some(payload::fingerprint_sentence(input.expose())),
+        (true, None) => None,
+    };
+    // A patch replaces the payload before anything else packs it
+    let patch_base = match patch_base {
+        Some(path) => {
+            let base = std::fs::read(&path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+            match ascii_input.take() {
+                Some(input) => {
+                    let packed = patch::diff(&base, input.expose());
+                    if verbose {
+                        eprintln!("Patch of {} bytes turns the {}-byte base into {} bytes", packed.len(), base.len(), input.len());
+                    }
+                    ascii_input = Some(SecretPayload::new(packed));
                 }
-                length_mode = match args[i + 1].as_str() {
-                    "compact" => SentenceLengthMode::Compact,
-                    "natural" => SentenceLengthMode::Natural,
-                    _ => return Err(format!("Invalid length mode: {}. Use 'compact' or 'natural'", args[i + 1])),
-                };
-                length_mode_explicit = true;
-                i += 2;
+                None if decode_input.is_some() && decode_ascii => {}
+                None => return Err("--patch-base applies to --from-ascii payloads and --decode-ascii".to_string()),
             }
-            "--from-ascii" => {
-                if i + 1 >= args.len() {
-                    return Err("--from-ascii requires a value (text string or '-' for stdin)".to_string());
-                }
-                let input = args[i + 1].clone();
-                if input == "-" {
-                    // Read from stdin
-                    use std::io::{self, Read};
-                    let mut buffer = String::new();
-                    io::stdin().read_to_string(&mut buffer)
-                        .map_err(|e| format!("Failed to read from stdin: {}", e))?;
-                    ascii_input = Some(buffer);
-                } else {
-                    ascii_input = Some(input);
+            Some(base)
+        }
+        None => None,
+    };
+    if !age_recipients.is_empty() {
+        let input = ascii_input.take().ok_or("--age-recipient applies to --from-ascii payloads")?;
+        let packed = encrypt_age(input.expose(), &age_recipients)?;
+        if verbose {
+            eprintln!("Encrypted {} bytes of input to {} age recipients into {} bytes", input.len(), age_recipients.len(), packed.len());
+        }
+        ascii_input = Some(SecretPayload::new(packed));
+    }
+    if schema_id.is_some() && !matches!(data_mode, Some(DataMode::Framed(_))) {
+        return Err("--schema-id needs --data-mode msgpack or protobuf".to_string());
+    }
+    // Pack the payload now, so every encoder below carries the packed bytes
+    if let Some(mode) = data_mode {
+        if decode_input.is_some() && !decode_ascii {
+            return Err("--data-mode decodes to bytes; use --decode-ascii".to_string());
+        }
+        match ascii_input.take() {
+            Some(input) => {
+                let mode = match mode {
+                    DataMode::Auto => data_mode::detect(input.expose()),
+                    mode => mode,
+                };
+                let packed = match (mode, schema_id) {
+                    (DataMode::Framed(framing), Some(id)) => data_mode::pack_framed(framing, id, input.expose()),
+                    _ => data_mode::pack(mode, input.expose()),
                 }
-                i += 2;
-            }
-            arg if arg.starts_with("--") => {
-                return Err(format!("Unknown option: {}", arg));
-            }
-            word => {
-                words.push(word.to_string());
-                i += 1;
+                .map_err(|e| format!("--data-mode {}: {:#}", mode.name(), e))?;
+                if verbose {
+                    eprintln!("Packed {} bytes of input as {} into {} bytes", input.len(), mode.name(), packed.len());
+                }
+                ascii_input = Some(SecretPayload::new(packed));
             }
+            None if decode_input.is_none() => return Err("--data-mode applies to --from-ascii payloads".to_string()),
+            None => {}
         }
     }
-    
-    if random_count.is_some() && !words.is_empty() {
-        return Err("Cannot use --random with explicit words. Use one or the other.".to_string());
+    // The validity stamp goes outside every packing, so it is read first
+    if decode_input.is_some() {
+        if expires.is_some() {
+            return Err("--expires applies to encoding; check it with --decode-ascii --reject-expired".to_string());
+        }
+        if (timestamp || reject_expired) && !decode_ascii {
+            return Err("--timestamp and --reject-expired apply to --decode-ascii".to_string());
+        }
+    } else if reject_expired {
+        return Err("--reject-expired only applies to --decode-ascii".to_string());
+    } else if timestamp || expires.is_some() {
+        let input = ascii_input.take().ok_or("--expires and --timestamp apply to --from-ascii payloads")?;
+        let created = unix_now();
+        let expires = expires.as_deref().map(|when| parse_expiry(when, created)).transpose()?;
+        let stamped = validity::stamp(&validity::Validity { created, expires }, input.expose()).map_err(|e| format!("--expires: {}", e))?;
+        ascii_input = Some(SecretPayload::new(stamped));
     }
-    
-    if random_count.is_some() && ascii_input.is_some() {
-        return Err("Cannot use --random with --from-ascii. Use one or the other.".to_string());
+    // --resync: the markers give the whole payload's length; --resume-from
+    // then encodes only its tail
+    let resync_fragment = match (resync, decode_input.is_none()) {
+        (Some(every), true) => {
+            let input = ascii_input.take().ok_or("--resync applies to --from-ascii payloads")?;
+            let offset = resume_from.unwrap_or(0);
+            let fragment = Fragment::new(input.len(), offset, every).map_err(|e| format!("--resume-from: {}", e))?;
+            ascii_input = Some(if offset > 0 { SecretPayload::new(input.expose()[offset..].to_vec()) } else { input });
+            Some(fragment)
+        }
+        _ => None,
+    };
+    if let Some(ref path) = from_file {
+        if decode_input.is_none() && detect_input.is_none() {
+            return Err(format!("--file {} needs --decode, --decode-ascii or --detect", path));
+        }
     }
-    
-    if ascii_input.is_some() && !words.is_empty() {
-        return Err("Cannot use --from-ascii with explicit words. Use one or the other.".to_string());
+    if html_input {
+        if decode_input.is_none() && detect_input.is_none() {
+            return Err("--html applies to --decode and --detect input".to_string());
+        }
+        // Pages and feeds are read as the text a browser would show
+        decode_input = decode_input.map(|input| html::visible_text(&input));
+        detect_input = detect_input.map(|input| html::visible_text(&input));
     }
-    
-    if random_count.is_none() && words.is_empty() && ascii_input.is_none() && !show_grammar {
+
+    if random_count.is_none() && words.is_empty() && ascii_input.is_none() && !show_grammar && export_dot.is_none() && decode_input.is_none() && detect_input.is_none() {
         return Err("No words provided. Use --random <N>, --from-ascii <text>, or provide words as arguments.".to_string());
     }
 
+    // Every byte is airtime: short sentences, and no words but the payload's
+    if low_bandwidth {
+        checksum = false;
+        parity = false;
+        if !given("grammar") {
+            generation_mode = GenerationMode::Body;
+        }
+        if !length_mode_explicit {
+            length_mode = SentenceLengthMode::Compact;
+            length_mode_explicit = true;
+        }
+        if !given("highlight") {
+            highlight_mode = HighlightMode::None;
+        }
+    }
+
     // Default length mode depends on grammar mode unless explicitly overridden:
     // - subject: compact (shortest-first)
     // - body: natural (sample from grammar length distribution)
+    // - with --style: the sample's sentence lengths
     if !length_mode_explicit {
         length_mode = match generation_mode {
+            _ if style.is_some() => SentenceLengthMode::Styled,
             GenerationMode::Subject => SentenceLengthMode::Compact,
             GenerationMode::Body => SentenceLengthMode::Natural,
         };
     }
     
-    Ok((words, random_count, ascii_input, verbose, seed, variations, highlight_mode, generation_mode, language, show_grammar, k_min, k_max, length_mode))
+    Ok(CliArgs {
+        words,
+        random_count,
+        ascii_input,
+        verbose,
+        seed,
+        variations,
+        highlight_mode,
+        generation_mode,
+        language,
+        show_grammar,
+        export_dot,
+        explain,
+        audit,
+        max_grade,
+        avoid_default,
+        avoid_words,
+        avoid_patterns,
+        homophone_safe,
+        prefixes,
+        parity,
+        lemmatize,
+        lossy,
+        constant_time,
+        extract_container,
+        decode_input,
+        decode_ascii,
+        detect_input,
+        json,
+        to_clipboard,
+        payload_mode,
+        checksum,
+        fingerprint,
+        read_back,
+        footer,
+        noise,
+        dictionary,
+        data_mode,
+        passphrase,
+        decoy,
+        decoy_passphrase,
+        deniable,
+        chaff_fraction,
+        slot_threshold,
+        style,
+        cover_model,
+        reply_to,
+        synonym_payload,
+        synonyms,
+        number_payload,
+        numbers,
+        template,
+        output_style,
+        engine,
+        #[cfg(feature = "neural")]
+        neural,
+        wordlist,
+        whiten,
+        commit,
+        interleave,
+        sign,
+        verify,
+        patch_base,
+        timestamp,
+        reject_expired,
+        resync,
+        resync_fragment,
+        session,
+        chunks,
+        k_min,
+        k_max,
+        length_mode,
+        wrap,
+    })
 }
 
 /// Get the wordlist file path for a given language.
-/// Returns the path to the payload.yaml file.
-/// Exits with error if payload.yaml doesn't exist.
-fn get_wordlist_path(language: &str) -> Result<String, String> {
+/// Returns the path to the payload.yaml file (phonetic.yaml with `--wordlist phonetic`).
+/// Exits with error if the file doesn't exist.
+fn get_wordlist_path(language: &str, wordlist: Wordlist) -> Result<String, String> {
     // Standardized path: languages/{language}/payload.yaml
-    let payload_yaml = format!("languages/{}/payload.yaml", language);
+    let file = match wordlist {
+        Wordlist::Bip39 => "payload.yaml",
+        Wordlist::Phonetic => "phonetic.yaml",
+    };
+    let payload_yaml = format!("languages/{}/{}", language, file);
     if std::path::Path::new(&payload_yaml).exists() {
         return Ok(payload_yaml);
     }
     
-    Err(format!("Wordlist file not found for language '{}'. Expected: {}\nOnly languages with payload.yaml are supported.", 
-                language, payload_yaml))
+    Err(format!("Wordlist file not found for language '{}'. Expected: {}\nOnly languages with {} are supported.", 
+                language, payload_yaml, file))
 }
 
 // --- CLI usage ---
 fn main() {
-    // Add blank lines at program start
-    println!();
-    println!();
-    
-    let (mut words, random_count, ascii_input, verbose, seed, variations, highlight_mode, generation_mode, language, show_grammar, k_min, k_max, length_mode) = match parse_args() {
+    JSON_ERRORS.store(env::args().any(|arg| arg == "--json-errors"), Ordering::Relaxed);
+
+    let command_args: Vec<String> = env::args().skip(2).collect();
+    let command = match env::args().nth(1).as_deref() {
+        Some("repl") => Some(run_repl()),
+        Some("watch") => Some(run_watch(&command_args)),
+        Some("completions") => Some(run_completions(&command_args)),
+        Some("manpages") => Some(run_manpages(&command_args)),
+        Some("capabilities") => Some(run_capabilities(&command_args)),
+        Some("train-cover-model") => Some(run_train_cover_model(&command_args)),
+        _ => None,
+    };
+    if let Some(result) = command {
+        if let Err(e) = result {
+            fail(false, e.code, format!("Error: {}", e.message));
+        }
+        return;
+    }
+
+    let CliArgs {
+        mut words,
+        random_count,
+        ascii_input,
+        verbose,
+        seed,
+        variations,
+        highlight_mode,
+        generation_mode,
+        language,
+        show_grammar,
+        export_dot,
+        explain,
+        audit,
+        max_grade,
+        avoid_default,
+        avoid_words,
+        avoid_patterns,
+        homophone_safe,
+        prefixes,
+        parity,
+        lemmatize,
+        lossy,
+        constant_time,
+        extract_container,
+        decode_input,
+        decode_ascii,
+        detect_input,
+        json,
+        to_clipboard,
+        payload_mode,
+        checksum,
+        fingerprint,
+        read_back,
+        footer,
+        noise,
+        dictionary,
+        data_mode,
+        passphrase,
+        decoy,
+        decoy_passphrase,
+        deniable,
+        chaff_fraction,
+        slot_threshold,
+        style,
+        cover_model,
+        reply_to,
+        synonym_payload,
+        synonyms,
+        number_payload,
+        numbers,
+        template,
+        output_style,
+        engine,
+        #[cfg(feature = "neural")]
+        neural,
+        wordlist,
+        whiten,
+        commit,
+        interleave,
+        sign,
+        verify,
+        patch_base,
+        timestamp,
+        reject_expired,
+        resync,
+        resync_fragment,
+        session,
+        chunks,
+        k_min,
+        k_max,
+        length_mode,
+        wrap,
+    } = match parse_args() {
         Ok(args) => args,
+        Err(e) if env::args().any(|arg| arg == "--json") => fail(true, "usage", format!("Error: {}", e)),
+        Err(e) if JSON_ERRORS.load(Ordering::Relaxed) => fail(false, "usage", format!("Error: {}", e)),
         Err(e) => {
             eprintln!("Error: {}", e);
             eprintln!();
-            print_usage(&env::args().next().unwrap_or_else(|| "glossia".to_string()));
-            std::process::exit(1);
+            eprintln!("{}", cli_command().render_usage());
+            eprintln!();
+            eprintln!("For more information, try '--help'.");
+            std::process::exit(exit_code("usage"));
         }
     };
+
+    // Add blank lines at program start, for a terminal only: piped output
+    // (and --mode binary bytes) must be exactly the payload
+    if !json && payload_mode != PayloadMode::Binary && std::io::stdout().is_terminal() {
+        println!();
+        println!();
+    }
+
+    if let (Some(topic), true) = (&reply_to, verbose) {
+        eprintln!("Thread topic: {}", topic.top(8).join(", "));
+    }
+
+    // Payload words only fill slots they are weighted above the threshold for;
+    // refuse a threshold that strands words or starves a slot
+    if slot_threshold > 0.0 && decode_input.is_none() && detect_input.is_none() {
+        let slot_check = load_payload_weights(&language, wordlist).and_then(|pos_weights| {
+            SlotMap::new(&pos_weights, slot_threshold)
+                .validate(&pos_weights, MIN_SLOT_WORDS)
+                .map_err(|e| e.to_string())
+        });
+        if let Err(e) = slot_check {
+            fail(json, "wordlist", format!("Error: {}", e));
+        }
+    }
+    let slot_map = payload_slots(wordlist, slot_threshold);
     
     // If --show-grammar flag is set, display grammar and continue
     if show_grammar {
@@ -2559,31 +5295,269 @@ fn main() {
                 } else {
                     vec!["S", "S_N", "S_V", "S_Adj", "S_Adv", "S_Prep", "S_Det"]
                 };
+                let compiled = g.compiled();
                 let mut by_start_symbol = HashMap::new();
                 for start_symbol in start_symbols {
-                    let sequences_by_k = g.precompute_sequences_with_probability(start_symbol, k_max);
+                    let sequences_by_k = compiled.precompute_sequences_with_probability(start_symbol, k_max);
                     if !sequences_by_k.is_empty() {
                         by_start_symbol.insert(start_symbol.to_string(), sequences_by_k);
                     }
                 }
                 print_sentence_kinds_once(mode_str, k_max, &by_start_symbol, true);
+                // Payload capacity at the current --slot-threshold
+                if let Ok(pos_weights) = load_payload_weights(&language, wordlist) {
+                    let wordlist: Vec<String> = pos_weights.words().map(str::to_string).collect();
+                    print!("{}", g.entropy_report(&wordlist, &SlotMap::new(&pos_weights, slot_threshold)));
+                }
                 println!(); // Add blank line after grammar
             }
             Err(e) => {
-                eprintln!("Error loading grammar: {}", e);
-                std::process::exit(1);
+                fail(json, "grammar", format!("Error loading grammar: {}", e));
+            }
+        }
+    }
+
+    // If --export-dot is set, write the grammar graph (with per-slot entropy) and continue
+    if let Some(ref dot_path) = export_dot {
+        let grammar = match generation_mode {
+            GenerationMode::Subject => Grammar::subject(),
+            GenerationMode::Body => Grammar::default(),
+        };
+        let grammar = grammar.unwrap_or_else(|e| {
+            fail(json, "grammar", format!("Error loading grammar: {}", e));
+        });
+
+        // Slot entropy: log2 of how many wordlist words can fill each POS slot.
+        let mut pos_counts: HashMap<Pos, usize> = HashMap::new();
+        if let Ok(pos_weights) = load_payload_weights(&language, wordlist) {
+            for (pos, words) in SlotMap::new(&pos_weights, slot_threshold).by_slot() {
+                pos_counts.insert(*pos, words.len());
+            }
+        }
+        let slot_entropy: HashMap<Pos, f64> = pos_counts
+            .into_iter()
+            .map(|(pos, count)| (pos, (count as f64).log2()))
+            .collect();
+
+        if let Err(e) = std::fs::write(dot_path, grammar.to_dot_with_entropy(&slot_entropy)) {
+            fail(json, "io", format!("Error writing DOT file '{}': {}", dot_path, e));
+        }
+        eprintln!("Grammar graph written to {}", dot_path);
+    }
+
+    if let Some(ref cover_text) = decode_input {
+        let options = DecodeOptions {
+            homophone_safe,
+            prefixes,
+            parity,
+            checksum,
+            noise,
+            dictionary,
+            lemmatize,
+            lossy,
+            constant_time,
+            template,
+            output_style,
+            engine,
+            #[cfg(feature = "neural")]
+            neural: neural.clone(),
+            ascii: decode_ascii,
+            data_mode: data_mode.is_some(),
+            wordlist,
+            passphrase,
+            deniable,
+            chaff_fraction,
+            whiten,
+            commit,
+            interleave: interleave.is_some(),
+            verify,
+            patch_base,
+            timestamp,
+            reject_expired,
+            resync,
+        };
+        let side = synonyms.then(|| {
+            load_synonyms(&language, wordlist)
+                .and_then(|table| table.extract(cover_text).map_err(|e| e.to_string()))
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_else(|e| fail(json, "decode", format!("Error reading synonym payload: {}", e)))
+        });
+        let number_side = numbers.then(|| {
+            load_payload_words(&language, wordlist)
+                .map(|words| numbers::NumberChannel::new(&words.into_iter().collect()))
+                .and_then(|channel| channel.extract(cover_text).map_err(|e| e.to_string()))
+                .map(|bytes| String::from_utf8_lossy(&bytes).into_owned())
+                .unwrap_or_else(|e| fail(json, "decode", format!("Error reading number payload: {}", e)))
+        });
+        // A --footer follows the read-back phrase
+        let unfooted = footer::strip(cover_text);
+        // The read-back phrase holds phonetic words, some of them carriers
+        let (cover_text, read_back) = match payload::split_fingerprint(&unfooted) {
+            Some((text, phrase)) if fingerprint => (text, Some(phrase)),
+            None if fingerprint => fail(json, "usage", "Error: --fingerprint: the text does not end with a read-back phrase"),
+            _ => (unfooted.as_ref(), None),
+        };
+        let decoded = decode_cover_report(cover_text, &language, &options, verbose).and_then(|report| match read_back {
+            Some(phrase) if payload::fingerprint(&report.bytes).to_lowercase() != phrase => {
+                Err(GlossiaError::ChecksumMismatch { expected: phrase, found: payload::fingerprint(&report.bytes).to_lowercase() }.into())
+            }
+            Some(phrase) => {
+                eprintln!("{} {} (matches the decoded payload)", payload::FINGERPRINT_LEAD, phrase);
+                Ok(report)
+            }
+            None => Ok(report),
+        });
+        if let (false, Ok(DecodeReport { validity: Some(stamp), .. })) = (json, &decoded) {
+            eprintln!("{}", describe_validity(stamp, unix_now()));
+        }
+        let stamp_json = |validity: Option<validity::Validity>| {
+            validity.map(|stamp| serde_json::json!({ "created": stamp.created, "expires": stamp.expires }))
+        };
+        match decoded {
+            Ok(DecodeReport { bytes: mut decoded, validity }) if extract_container.is_some() => {
+                let entries = container::decode_container(&decoded);
+                decoded.wipe();
+                let mut entries = entries.unwrap_or_else(|e| fail(json, "decode", format!("Error reading container: {}", e)));
+                let written = write_container(extract_container.as_deref().expect("checked above"), &entries);
+                entries.values_mut().for_each(Wipe::wipe);
+                let written = written.unwrap_or_else(|e| fail(json, "io", format!("Error: {}", e)));
+                if json {
+                    let files: Vec<_> = written.iter().map(|(path, len)| serde_json::json!({ "path": path.display().to_string(), "bytes": len })).collect();
+                    let mut report = serde_json::json!({ "entries": files });
+                    if let Some(stamp) = stamp_json(validity) {
+                        report["validity"] = stamp;
+                    }
+                    println!("{}", report);
+                } else {
+                    for (path, len) in written {
+                        println!("Wrote {} ({} bytes)", path.display(), len);
+                    }
+                }
+            }
+            Ok(DecodeReport { bytes: mut decoded, validity }) => {
+                let text = std::str::from_utf8(&decoded).ok();
+                let binary = match payload_mode {
+                    PayloadMode::Text => false,
+                    PayloadMode::Binary => true,
+                    PayloadMode::Auto => text.is_none(),
+                };
+                if json {
+                    let mut report = if binary {
+                        let hex: String = decoded.iter().map(|b| format!("{:02x}", b)).collect();
+                        serde_json::json!({ "payload_hex": hex })
+                    } else {
+                        serde_json::json!({ "payload": String::from_utf8_lossy(&decoded) })
+                    };
+                    if let Some(ref side) = side {
+                        report["synonym_payload"] = serde_json::json!(side);
+                    }
+                    if let Some(ref side) = number_side {
+                        report["number_payload"] = serde_json::json!(side);
+                    }
+                    if let Some(stamp) = stamp_json(validity) {
+                        report["validity"] = stamp;
+                    }
+                    println!("{}", report);
+                } else if binary || (payload_mode == PayloadMode::Auto && !std::io::stdout().is_terminal()) {
+                    // A filter: pass the bytes through exactly, with no trailing newline
+                    let mut stdout = std::io::stdout().lock();
+                    if let Err(e) = stdout.write_all(&decoded).and_then(|_| stdout.flush()) {
+                        fail(json, "io", format!("Error writing to stdout: {}", e));
+                    }
+                } else {
+                    println!("{}", String::from_utf8_lossy(&decoded));
+                }
+                if let (false, Some(side)) = (json, &side) {
+                    eprintln!("Synonym payload: {}", side);
+                }
+                if let (false, Some(side)) = (json, &number_side) {
+                    eprintln!("Number payload: {}", side);
+                }
+                if to_clipboard {
+                    if let Err(e) = write_clipboard(&String::from_utf8_lossy(&decoded)) {
+                        fail(json, "io", format!("Error: {}", e));
+                    }
+                }
+                decoded.wipe();
+            }
+            Err(e) => {
+                fail(json, error_code(&e, "decode"), format!("Error decoding: {}", e));
             }
         }
+        return;
+    }
+
+    if let Some(ref text) = detect_input {
+        let all_words = load_payload_words(&language, wordlist).unwrap_or_else(|e| fail(json, "wordlist", format!("Error loading wordlist: {}", e)));
+        let found = payload::detect(&footer::strip(text), &all_words);
+        if json {
+            println!(
+                "{}",
+                serde_json::json!({
+                    "carrier_words": found.carrier_words,
+                    "total_words": found.total_words,
+                    "checksum_valid": found.checksum_valid,
+                    "likely": found.is_likely(),
+                })
+            );
+        } else {
+            println!("Carrier words: {} of {} tokens", found.carrier_words, found.total_words);
+            println!("Trailing checksum: {}", if found.checksum_valid { "valid" } else { "none" });
+            println!("Likely carries a payload: {}", if found.is_likely() { "yes" } else { "no" });
+        }
+        return;
     }
 
+    // Grammar inspection flags alone don't need a payload.
+    if words.is_empty() && random_count.is_none() && ascii_input.is_none() {
+        return;
+    }
+
+    // --footer: the command that decodes the text, from the options it is made with
+    let footer = footer.map(|footer_template| {
+        let mut command = vec!["glossia".to_string(), if ascii_input.is_some() { "--decode-ascii -" } else { "--decode -" }.to_string()];
+        let keyed = passphrase.is_some() && (whiten || chaff_fraction.is_some());
+        let flags = [
+            (language != "english", format!("--language {}", language)),
+            (wordlist == Wordlist::Phonetic, "--wordlist phonetic".to_string()),
+            (engine != Engine::Prose, format!("--engine {}", engine.name())),
+            (output_style != Style::Prose, format!("--output-style {}", output_style.name())),
+            (template.is_some(), "--template <templates>".to_string()),
+            (checksum, "--checksum".to_string()),
+            (parity, "--parity".to_string()),
+            (prefixes, "--prefixes".to_string()),
+            (homophone_safe, "--homophone-safe".to_string()),
+            (whiten, "--whiten".to_string()),
+            (commit, "--commit".to_string()),
+            (interleave.is_some(), "--interleave".to_string()),
+            (decoy.is_some(), "--deniable".to_string()),
+            (chaff_fraction.is_some(), format!("--chaff {}", chaff_fraction.unwrap_or_default())),
+            (keyed || decoy.is_some(), "--passphrase <passphrase>".to_string()),
+            (resync.is_some(), format!("--resync {}", resync.unwrap_or_default())),
+            (data_mode.is_some(), "--data-mode auto".to_string()),
+            (timestamp, "--timestamp".to_string()),
+            (patch_base.is_some(), "--patch-base <previous version>".to_string()),
+            (fingerprint, "--fingerprint".to_string()),
+            (synonym_payload.is_some(), "--synonyms".to_string()),
+            (number_payload.is_some(), "--numbers".to_string()),
+        ];
+        command.extend(flags.into_iter().filter(|(on, _)| *on).map(|(_, flag)| flag));
+        if let Some(key) = &sign {
+            command.push(format!("--verify {}", signing::npub(key.verifying_key())));
+        }
+        (footer_template, command.join(" "))
+    });
+
     // Use seeded RNG if seed provided, otherwise generate random seed from thread_rng
-    let seed_value = if let Some(s) = seed {
+    let mut seed_value = if let Some(s) = seed {
         s
     } else {
         // Generate a random seed from thread_rng for non-deterministic behavior
         rand::thread_rng().gen::<u64>()
     };
     let mut rng = StdRng::seed_from_u64(seed_value);
+    // Checkpointed encode state, saved once this invocation's text is printed
+    let mut encode_session: Option<(EncodeSession, String)> = None;
     
     if verbose {
         if seed.is_some() {
@@ -2598,182 +5572,273 @@ fn main() {
     }
 
     // Validate language and get wordlist path early
-    match get_wordlist_path(&language) {
+    match get_wordlist_path(&language, wordlist) {
         Ok(path) => {
             if verbose {
                 eprintln!("Using wordlist: {}", path);
             }
         }
         Err(e) => {
-            eprintln!("Error: {}", e);
-            std::process::exit(1);
+            fail(json, "wordlist", format!("Error: {}", e));
         }
     };
 
+    // The model writes the whole text; none of the word pipeline below applies
+    #[cfg(feature = "neural")]
+    if let (Some(settings), Some(ascii_text)) = (&neural, &ascii_input) {
+        encode_neural(settings, ascii_text.expose(), json, verbose, to_clipboard);
+        return;
+    }
+
     // If ASCII input provided, encode it to words
-    if let Some(ascii_text) = ascii_input {
-        words = match encode_ascii_to_words(&ascii_text, &language) {
+    if let (Some(ascii_text), Some(decoy_text)) = (&ascii_input, &decoy) {
+        let real_pass = passphrase.as_deref().unwrap_or_default();
+        let decoy_pass = decoy_passphrase.as_deref().unwrap_or_default();
+        words = match load_payload_words(&language, wordlist).and_then(|all_words| {
+            deniable::encode_deniable(&mut rng, ascii_text.expose(), decoy_text.as_bytes(), real_pass, decoy_pass, &all_words)
+                .map_err(|e| e.to_string())
+        }) {
+            Ok(carrier) => {
+                if verbose {
+                    eprintln!("Deniable carrier: {} words for {} real and {} decoy bytes", carrier.len(), ascii_text.len(), decoy_text.len());
+                }
+                carrier
+            }
+            Err(e) => {
+                fail(json, "encode", format!("Error encoding deniable payloads: {}", e));
+            }
+        };
+    } else if let (Some(ascii_text), true) = (&ascii_input, whiten) {
+        let payload = match &sign {
+            Some(secret_key) => signing::sign_bytes(&mut rng, ascii_text.expose(), secret_key).map(SecretPayload::from),
+            None => Ok(ascii_text.clone()),
+        };
+        words = match payload.map_err(|e| e.to_string()).and_then(|payload| {
+            let all_words = load_payload_words(&language, wordlist)?;
+            let encoded = if commit {
+                whiten::encode_committed(&mut rng, payload.expose(), &all_words, passphrase.as_deref())
+            } else {
+                whiten::encode_whitened(&mut rng, payload.expose(), &all_words, passphrase.as_deref())
+            };
+            encoded.map_err(|e| e.to_string())
+        }) {
             Ok(encoded_words) => {
                 if verbose {
-                    eprintln!("Encoded {} bytes to {} words", ascii_text.len(), encoded_words.len());
+                    eprintln!("Whitened {} bytes to {} words (including the nonce words)", ascii_text.len(), encoded_words.len());
                 }
                 encoded_words
             }
             Err(e) => {
-                eprintln!("Error encoding ASCII to words: {}", e);
-                std::process::exit(1);
+                fail(json, "encode", format!("Error whitening payload: {}", e));
             }
         };
-    }
-    
-    // If random words requested, select them now
-    if let Some(count) = random_count {
-        words = match select_random_words(&mut rng, count, &language) {
-            Ok(selected_words) => {
+    } else if let (Some(ascii_text), Some(depth)) = (&ascii_input, interleave) {
+        let payload = match &sign {
+            Some(secret_key) => signing::sign_bytes(&mut rng, ascii_text.expose(), secret_key).map(SecretPayload::from),
+            None => Ok(ascii_text.clone()),
+        };
+        words = match payload.map_err(|e| e.to_string()).and_then(|payload| {
+            let all_words = load_payload_words(&language, wordlist)?;
+            interleave::encode_interleaved(payload.expose(), &all_words, depth).map_err(|e| e.to_string())
+        }) {
+            Ok(encoded_words) => {
                 if verbose {
-                    eprintln!("Selected {} random BIP39 words: {}", count, selected_words.join(" "));
+                    eprintln!("Interleaved {} bytes {} columns wide into {} words (including the header word)", ascii_text.len(), depth, encoded_words.len());
                 }
-                selected_words
+                encoded_words
             }
             Err(e) => {
-                eprintln!("Error loading wordlist: {}", e);
-                std::process::exit(1);
+                fail(json, "encode", format!("Error interleaving payload: {}", e));
             }
         };
-    }
-
-    // Tag each word with POS tags
-    let payload: Vec<PayloadTok> = words
-        .iter()
-        .map(|word| {
-            let tags = tag_word(word);
-            PayloadTok::new(word.clone(), &tags)
-        })
-        .collect();
-
-    let payload_set: HashSet<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
-    let payload_set_clone = payload_set.clone(); // Keep a copy for later statistics
-
-    // Load wordlist words for validation
-    let wordlist_words = match load_payload_words(&language) {
-        Ok(words) => words,
-        Err(e) => {
-            eprintln!("Error loading wordlist: {}", e);
-            std::process::exit(1);
+    } else if let (Some(ascii_text), Some(path)) = (&ascii_input, &session) {
+        let started = match std::fs::read_to_string(path) {
+            Ok(saved) => EncodeSession::resume(&saved, ascii_text.expose()).map_err(|e| e.to_string()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(EncodeSession::new(ascii_text.expose(), seed_value)),
+            Err(e) => Err(format!("Failed to read session file '{}': {}", path, e)),
+        };
+        let mut state = match started {
+            Ok(state) if state.is_finished() => {
+                fail(json, "session", format!("Session '{}' has already encoded the whole payload", path));
+            }
+            Ok(state) => state,
+            Err(e) => {
+                fail(json, "session", format!("Error: {}", e));
+            }
+        };
+        // Each invocation draws its generation seed from its first chunk
+        seed_value = state.chunk_rng().gen();
+        let all_words = match load_payload_words(&language, wordlist) {
+            Ok(all_words) => all_words,
+            Err(e) => {
+                fail(json, "wordlist", format!("Error loading wordlist: {}", e));
+            }
+        };
+        for _ in 0..chunks.unwrap_or(usize::MAX) {
+            if state.is_finished() {
+                break;
+            }
+            match state.next_words(ascii_text.expose(), SESSION_CHUNK_WORDS, &all_words) {
+                Ok(chunk_words) => words.extend(chunk_words),
+                Err(e) => {
+                    fail(json, "encode", format!("Error encoding session chunk: {}", e));
+                }
+            }
         }
-    };
-    let wordlist_set: HashSet<String> = wordlist_words.iter().map(|w| w.to_lowercase()).collect();
-    
-    // Load cover words with explicit POS tags from cover.yaml
-    let cover_by_pos = load_cover_words_by_pos(&wordlist_set, &language);
-    
-    // Extract function words from cover.yaml (with runtime defaults if missing)
-    let det_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Det)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["the", "a", "an", "each", "some"]);
-    
-    let modal_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Modal)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["should", "could", "would", "might", "may"]);
-    
-    let aux_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Aux)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["do", "does"]);
-    
-    let cop_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Cop)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["is", "are"]);
-    
-    let to_words: Vec<&str> = cover_by_pos
-        .get(&Pos::To)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["to"]);
-
-    let conj_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Conj)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["and", "but", "or"]);
-    
-    // Subject grammar can emit Prefix (e.g., "re", "fwd") so ensure we always have
-    // some safe defaults even if cover.yaml doesn't provide them.
-    let prefix_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Prefix)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["re", "fwd", "fw", "update"]);
-    
-    // Extract content words from cover.yaml (with runtime defaults if missing)
-    let adj_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Adj)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["clear", "simple", "bright", "quiet", "steady"]);
-    
-    let n_words: Vec<&str> = cover_by_pos
-        .get(&Pos::N)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["user", "note", "server", "system"]);
-    
-    let v_words: Vec<&str> = cover_by_pos
-        .get(&Pos::V)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["check", "send", "hold", "verify", "process"]);
-    
-    let prep_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Prep)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["about", "above", "along", "beneath", "throughout"]);
-    
-    let adv_words: Vec<&str> = cover_by_pos
-        .get(&Pos::Adv)
-        .map(|v| v.iter().map(|s| s.as_str()).collect())
-        .unwrap_or_else(|| vec!["soon", "well", "quite", "very"]);
-    
-    // Validate all cover words against BIP39 wordlist
-    let all_cover_words: Vec<&str> = det_words.iter()
-        .chain(modal_words.iter())
-        .chain(aux_words.iter())
-        .chain(cop_words.iter())
-        .chain(to_words.iter())
-        .chain(conj_words.iter())
-        .chain(prefix_words.iter())
-        .chain(adj_words.iter())
-        .chain(n_words.iter())
-        .chain(v_words.iter())
-        .chain(prep_words.iter())
-        .chain(adv_words.iter())
-        .copied()
-        .collect();
+        if verbose {
+            eprintln!(
+                "Session: {} of {} bytes encoded after {} chunk(s)",
+                state.byte_pos, state.payload_len, state.chunk
+            );
+        }
+        encode_session = Some((state, path.clone()));
+    } else if let Some(ascii_text) = ascii_input {
+        let encoded = match &sign {
+            Some(secret_key) => load_payload_words(&language, wordlist).and_then(|all_words| {
+                signing::encode_signed(&mut rng, ascii_text.expose(), secret_key, &all_words).map_err(|e| e.to_string())
+            }),
+            None => encode_ascii_to_words(&ascii_text, &language, wordlist),
+        };
+        if let (Some(secret_key), true) = (&sign, verbose) {
+            eprintln!("Signing as {}", signing::npub(secret_key.verifying_key()));
+        }
+        words = match encoded {
+            Ok(encoded_words) => {
+                if verbose {
+                    eprintln!("Encoded {} bytes to {} words", ascii_text.len(), encoded_words.len());
+                }
+                encoded_words
+            }
+            Err(e) => {
+                fail(json, "encode", format!("Error encoding ASCII to words: {}", e));
+            }
+        };
+    }
     
-    validate_cover_words(&all_cover_words, &wordlist_set);
+    // If random words requested, select them now
+    if let Some(count) = random_count {
+        words = match select_random_words(&mut rng, count, &language, wordlist) {
+            Ok(selected_words) => {
+                if verbose {
+                    eprintln!("Selected {} random BIP39 words: {}", count, selected_words.join(" "));
+                }
+                selected_words
+            }
+            Err(e) => {
+                fail(json, "wordlist", format!("Error loading wordlist: {}", e));
+            }
+        };
+    }
+
+    // Name the first word that can't be carried, and where it is
+    if !words.is_empty() {
+        let all_words = load_payload_words(&language, wordlist)
+            .unwrap_or_else(|e| fail(json, "wordlist", format!("Error loading wordlist: {}", e)));
+        let known: HashSet<&str> = all_words.iter().map(String::as_str).collect();
+        if let Some(position) = words.iter().position(|w| !known.contains(w.to_lowercase().as_str())) {
+            let err = GlossiaError::UnknownWord { word: words[position].clone(), position: position + 1 };
+            fail(json, err.code(), format!("Error: {}", err));
+        }
+        if wordlist == Wordlist::Phonetic {
+            if let Err(e) = phonetic::check_alternation(&words) {
+                fail(json, error_code(&e, "encode"), format!("Error: {}", e));
+            }
+        }
+    }
+
+    // Words decode as they are joined here, lowercase and space-separated
+    let read_back = read_back.or_else(|| {
+        fingerprint.then(|| payload::fingerprint_sentence(words.iter().map(|w| w.to_lowercase()).collect::<Vec<_>>().join(" ").as_bytes()))
+    });
+
+    if checksum {
+        words = match load_payload_words(&language, wordlist)
+            .and_then(|all_words| codec::append_checksum(&words, &all_words).map_err(|e| e.to_string()))
+        {
+            Ok(framed) => framed,
+            Err(e) => {
+                fail(json, "encode", format!("Error adding checksum: {}", e));
+            }
+        };
+    }
+
+    // Tag each word with POS tags
+    let payload: Vec<PayloadTok> = words
+        .iter()
+        .map(|word| PayloadTok::new(word.clone(), slot_map.slots(word)))
+        .collect();
+
+    let payload_set: HashSet<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
+    let payload_set_clone = payload_set.clone(); // Keep a copy for later statistics
+
+    // Load wordlist words for validation
+    let wordlist_words = match load_payload_words(&language, wordlist) {
+        Ok(words) => words,
+        Err(e) => {
+            fail(json, "wordlist", format!("Error loading wordlist: {}", e));
+        }
+    };
+    let wordlist_set: HashSet<String> = wordlist_words.iter().map(|w| w.to_lowercase()).collect();
     
-    if verbose {
-        eprintln!("Loaded cover words from cover.yaml:");
-        eprintln!("  Adjectives: {}", adj_words.len());
-        eprintln!("  Nouns: {}", n_words.len());
-        eprintln!("  Verbs: {}", v_words.len());
-        eprintln!("  Prepositions: {}", prep_words.len());
-        eprintln!("  Adverbs: {}", adv_words.len());
-        if !prefix_words.is_empty() {
-            eprintln!("  Prefixes: {}", prefix_words.len());
+    // Load cover words with explicit POS tags from cover.yaml
+    let mut cover_by_pos = load_cover_words_by_pos(&wordlist_set, &language);
+
+    // Homophone-safe: drop cover words a listener could hear as wordlist words.
+    // Slots left with no cover word at all are removed from the sentence shapes.
+    let mut banned_slots: HashSet<Pos> = HashSet::new();
+    if homophone_safe {
+        let table = match load_homophones(&language) {
+            Ok(table) => table,
+            Err(e) => {
+                fail(json, "io", format!("Error: {}", e));
+            }
+        };
+        let collisions = table.collisions(&wordlist_set);
+        for (pos, cover_words) in cover_by_pos.iter_mut() {
+            let before = cover_words.len();
+            cover_words.retain(|w| !collisions.contains(w));
+            if verbose && cover_words.len() < before {
+                eprintln!("Homophone-safe: removed {} {:?} cover word(s)", before - cover_words.len(), pos);
+            }
+            if before > 0 && cover_words.is_empty() {
+                banned_slots.insert(*pos);
+            }
+        }
+        if verbose && !banned_slots.is_empty() {
+            eprintln!("Homophone-safe: avoiding sentence shapes with {:?}", banned_slots);
+        }
+        let ambiguous = table.ambiguous(&wordlist_set);
+        for tok in &payload {
+            let word = tok.word.to_lowercase();
+            if ambiguous.contains(&word) {
+                eprintln!("Warning: payload word '{}' sounds like another wordlist word and may not survive being read aloud", word);
+            }
         }
     }
+
+    // --prefixes: a cover word that looks like a prefixed carrier ("unsafe")
+    // would decode as its base, so drop it
+    let prefix_rules = if prefixes {
+        let rules = load_prefixes(&language).unwrap_or_else(|e| fail(json, "io", format!("Error: {}", e)));
+        let prefixed = rules.corrections(&wordlist_set);
+        for (pos, cover_words) in cover_by_pos.iter_mut() {
+            let before = cover_words.len();
+            cover_words.retain(|w| !prefixed.contains_key(w));
+            if verbose && cover_words.len() < before {
+                eprintln!("Prefixes: removed {} {:?} cover word(s)", before - cover_words.len(), pos);
+            }
+            if before > 0 && cover_words.is_empty() {
+                banned_slots.insert(*pos);
+            }
+        }
+        Some(rules)
+    } else {
+        None
+    };
     
-    let lex = Lexicon::new(payload_set, wordlist_set.clone())
-        .with_words(Pos::Det, &det_words)
-        .with_words(Pos::Modal, &modal_words)
-        .with_words(Pos::Aux, &aux_words)
-        .with_words(Pos::Cop, &cop_words)
-        .with_words(Pos::To, &to_words)
-        .with_words(Pos::Conj, &conj_words)
-        .with_words(Pos::Prefix, &prefix_words)
-        .with_words(Pos::Adj, &adj_words)
-        .with_words(Pos::N, &n_words)
-        .with_words(Pos::V, &v_words)
-        .with_words(Pos::Prep, &prep_words)
-        .with_words(Pos::Adv, &adv_words);
+    let lex = build_lexicon(&cover_by_pos, payload_set, wordlist_set.clone(), verbose).with_style(style.clone())
+        .with_cover_model(cover_model)
+        .with_topic(reply_to);
 
     let input_word_count = payload.len();
     let expected_words: Vec<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
@@ -2797,14 +5862,101 @@ fn main() {
         eprintln!("Generating {} variations to maximize compactness...", variations);
     }
 
+    let mut avoid_patterns = avoid_patterns;
+    if avoid_default {
+        match load_avoid_patterns(&language) {
+            Ok(patterns) => avoid_patterns.extend(patterns),
+            Err(e) => {
+                fail(json, "invalid_arguments", format!("Error: {}", e));
+            }
+        }
+    }
+    let avoid = match build_avoid_regex(&avoid_words, &avoid_patterns) {
+        Ok(avoid) => avoid,
+        Err(e) => {
+            fail(json, "invalid_arguments", format!("Error: {}", e));
+        }
+    };
+    if let Some(ref avoid) = avoid {
+        // A payload word may itself be avoided; re-sampling can't help there.
+        for word in &expected_words {
+            if avoid.is_match(word) {
+                eprintln!("Warning: payload word '{}' matches the avoid list and cannot be replaced", word);
+            }
+        }
+    }
+    let sentence_filter = SentenceFilter { max_grade, avoid, banned_slots };
+
+    // --template and list styles: lay the words out instead of generating sentences
+    let tagged: Vec<(String, Vec<Pos>)> = payload
+        .iter()
+        .map(|tok| (tok.word.to_lowercase(), tok.allowed.clone()))
+        .collect();
+    let grammar_variations = match template {
+        _ if engine == Engine::Emoji => {
+            match emoji::encode(&mut rng, &expected_words, &wordlist_words) {
+                Ok(bursts) => {
+                    best_output_count = bursts.split_whitespace().count();
+                    best_text = Some(bursts);
+                }
+                Err(e) => {
+                    fail(json, "generation", format!("Error writing emoji: {}", e));
+                }
+            }
+            0
+        }
+        Some(ref templates) => {
+            match templates.fill(&mut rng, &tagged, &cover_by_pos) {
+                Ok(filled) => {
+                    if verbose {
+                        eprintln!(
+                            "Template fill: {} line(s); {} blanks per cycle carry up to {:.0} bits",
+                            templates.templates_needed(&tagged).unwrap_or_default(),
+                            templates.blanks_per_cycle(),
+                            templates.capacity_bits(wordlist_words.len())
+                        );
+                    }
+                    best_output_count = filled.split_whitespace().count();
+                    best_text = Some(filled);
+                }
+                Err(e) => {
+                    fail(json, "generation", format!("Error filling templates: {}", e));
+                }
+            }
+            0
+        }
+        None if output_style != Style::Prose => {
+            match output_style.render(&mut rng, &tagged) {
+                Ok(list) => {
+                    best_output_count = list.split_whitespace().count();
+                    best_text = Some(list);
+                }
+                Err(e) => {
+                    fail(json, "generation", format!("Error rendering {:?} output: {}", output_style, e));
+                }
+            }
+            0
+        }
+        None => variations,
+    };
+
+    // --explain: the text the first variation makes, and what each of its
+    // words does; nothing is written or post-processed
+    if explain {
+        let mut explain_rng = StdRng::seed_from_u64(seed_value);
+        let (text, sentences) = encode_explain(&mut explain_rng, &lex, &payload, generation_mode, k_min, k_max, length_mode, &sentence_filter, &wordlist_words);
+        print_explanation(&text, &sentences, generation_mode, codec::bits_per_word(wordlist_words.len()).unwrap_or(0), json);
+        return;
+    }
+
     let generation_start = Instant::now();
-    for variation in 0..variations {
+    for variation in 0..grammar_variations {
         // Use different seeds for each variation (increment base seed)
         let variation_seed = seed_value.wrapping_add(variation as u64);
         let mut variation_rng = StdRng::seed_from_u64(variation_seed);
         
         let variation_start = Instant::now();
-        let (text, payload_set_from_gen) = generate_text(&mut variation_rng, &lex, &payload, highlight_mode, variations == 1 && verbose, generation_mode, k_min, k_max, length_mode);
+        let (text, payload_set_from_gen) = generate_text(&mut variation_rng, &lex, &payload, highlight_mode, variations == 1 && verbose, generation_mode, k_min, k_max, length_mode, &sentence_filter);
         let variation_elapsed = variation_start.elapsed();
         
         // Validate that the generated text contains exactly the input BIP39 words in order
@@ -2912,7 +6064,11 @@ fn main() {
     let text = match best_text {
         Some(t) => t,
         None => {
-            eprintln!("Error: Failed to generate any valid variations after {} attempts.", variations);
+            let message = format!("Error: Failed to generate any valid variations after {} attempts.", variations);
+            if json {
+                fail(json, "generation", message);
+            }
+            eprintln!("{}", message);
             eprintln!("This may happen if:");
             eprintln!("  - The grammar cannot accommodate all input words");
             eprintln!("  - There are POS tagging issues with some words");
@@ -2925,22 +6081,169 @@ fn main() {
         }
     };
     
-    // Word wrap the output to 80 characters
-    if variations > 1 {
+    let text = match chaff_fraction {
+        Some(fraction) => {
+            let mut chaff_rng = StdRng::seed_from_u64(seed_value.wrapping_add(variations as u64));
+            match inject_chaff(&mut chaff_rng, &lex, &text, fraction, passphrase.as_deref(), &language, wordlist, &slot_map, highlight_mode, k_min, k_max, &sentence_filter) {
+                Ok(with_chaff) => with_chaff,
+                Err(e) => {
+                    fail(json, "encode", format!("Error adding chaff sentences: {}", e));
+                }
+            }
+        }
+        None => text,
+    };
+
+    // --synonym-payload: side-channel bits in the choice between synonyms
+    let text = match synonym_payload {
+        Some(ref side) => {
+            let mut synonym_rng = StdRng::seed_from_u64(seed_value.wrapping_add(variations as u64).wrapping_add(2));
+            let embedded = load_synonyms(&language, wordlist)
+                .and_then(|table| table.embed(&mut synonym_rng, &text, side.as_bytes()).map_err(|e| e.to_string()));
+            match embedded {
+                Ok(with_synonyms) => with_synonyms,
+                Err(e) => {
+                    fail(json, "encode", format!("Error embedding synonym payload: {}", e));
+                }
+            }
+        }
+        None => text,
+    };
+
+    // --number-payload: side-channel bits in quantities, clock times and years
+    let text = match number_payload {
+        Some(ref side) => {
+            let channel = numbers::NumberChannel::new(&wordlist_set);
+            let mut number_rng = StdRng::seed_from_u64(seed_value.wrapping_add(variations as u64).wrapping_add(3));
+            match channel.embed(&mut number_rng, &text, side.as_bytes(), output_style == Style::Prose) {
+                Ok(with_numbers) => with_numbers,
+                Err(e) => {
+                    fail(json, "encode", format!("Error embedding number payload: {}", e));
+                }
+            }
+        }
+        None => text,
+    };
+
+    // --prefixes: "unsafe", "reload" for some listed carriers
+    let text = match prefix_rules {
+        Some(ref rules) => {
+            let mut prefix_rng = StdRng::seed_from_u64(seed_value.wrapping_add(variations as u64).wrapping_add(4));
+            rules.attach(&mut prefix_rng, &text, &wordlist_set, PREFIX_RATE)
+        }
+        None => text,
+    };
+
+    // --style: punctuation, contractions and (body) paragraphs of the sample
+    let text = match style {
+        Some(ref profile) => {
+            let mut style_rng = StdRng::seed_from_u64(seed_value.wrapping_add(variations as u64).wrapping_add(1));
+            profile.apply(&mut style_rng, &text, &wordlist_set, generation_mode == GenerationMode::Body)
+        }
+        None => text,
+    };
+
+    // --parity: a marker noun after each sentence's carriers, written last so
+    // that it checks the carriers as printed
+    let text = if parity {
+        if segment::is_unspaced_wordlist(&wordlist_words) {
+            fail(json, "usage", "Error: --parity needs a wordlist written with spaces between words");
+        }
+        let markers: Vec<String> = wordlist_words.iter().filter(|w| slot_map.slots(w).contains(&Pos::N)).cloned().collect();
+        let corrections = match prefix_rules {
+            Some(ref rules) => rules.corrections(&wordlist_set),
+            None => HashMap::new(),
+        };
+        let mut parity_rng = StdRng::seed_from_u64(seed_value.wrapping_add(variations as u64).wrapping_add(5));
+        match parity::mark(&mut parity_rng, &text, &wordlist_words, &markers, &corrections) {
+            Ok(marked) => marked,
+            Err(e) => {
+                fail(json, "encode", format!("Error adding parity markers: {}", e));
+            }
+        }
+    } else {
+        text
+    };
+
+    // Chinese and Japanese carriers are written without spaces between words
+    let text = if segment::is_unspaced_wordlist(&wordlist_words) {
+        segment::join_unspaced(&text)
+    } else {
+        text
+    };
+
+    // --resync: markers around the carriers as printed, before the read-back
+    // phrase (whose words are not counted)
+    let text = match resync_fragment {
+        Some(fragment) => match fragment.mark(&text, &wordlist_words) {
+            Ok(marked) => marked,
+            Err(e) => {
+                fail(json, "encode", format!("Error adding resync markers: {}", e));
+            }
+        },
+        None => text,
+    };
+
+    // --fingerprint: the read-back phrase as a final sentence, or line of its own
+    let text = match read_back {
+        Some(sentence) if output_style != Style::Prose || engine == Engine::Emoji => format!("{}\n{}", text, sentence),
+        Some(sentence) if text.trim_end().ends_with(['.', '!', '?']) => format!("{} {}", text.trim_end(), sentence),
+        Some(sentence) => format!("{}. {}", text.trim_end(), sentence),
+        None => text,
+    };
+
+    // --footer: how to decode the text, below a line decoding stops at
+    let text = match footer {
+        Some((footer_template, command)) => match footer::render(&footer_template, &command, &wordlist_set) {
+            Ok(note) => footer::append(&text, &note),
+            Err(e) => {
+                fail(json, "usage", format!("Error: --footer-template: {}", e));
+            }
+        },
+        None => text,
+    };
+
+    // Word wrap the output to --wrap columns
+    if json {
+        // Printed with the report below
+    } else if variations > 1 {
         for (i, vtext) in valid_variation_texts.iter().enumerate() {
             if i > 0 {
                 println!();
                 println!();
             }
-            println!("{}", word_wrap(vtext, 80));
+            println!("{}", decode::wrap_lines(vtext, wrap));
         }
+    } else if output_style != Style::Prose || engine == Engine::Emoji {
+        // One item or step per line, as laid out, or a single run of emoji
+        println!("{}", text);
     } else {
-        println!("{}", word_wrap(&text, 80));
+        println!("{}", decode::wrap_lines(&text, wrap));
+    }
+    if to_clipboard {
+        if let Err(e) = write_clipboard(&strip_highlighting(&text)) {
+            fail(json, "io", format!("Error: {}", e));
+        }
     }
 
     // Calculate detailed statistics from the best text
     let sentences: Vec<&str> = text.split('.').filter(|s| !s.trim().is_empty()).collect();
     let sentence_count = sentences.len();
+
+    if let Some((mut state, path)) = encode_session {
+        state.record_sentences(sentence_count);
+        if let Err(e) = std::fs::write(&path, state.checkpoint()) {
+            fail(json, "io", format!("Error saving session checkpoint '{}': {}", path, e));
+        }
+        if state.is_finished() {
+            eprintln!("Session complete: {} bytes in {} chunk(s)", state.payload_len, state.chunk);
+        } else {
+            eprintln!(
+                "Session checkpoint saved to '{}' ({} of {} bytes); run again to continue",
+                path, state.byte_pos, state.payload_len
+            );
+        }
+    }
     let avg_words_per_sentence = if sentence_count > 0 {
         best_output_count as f64 / sentence_count as f64
     } else {
@@ -3035,17 +6338,48 @@ fn main() {
         }
     }
 
+    // Score the text as a reader would see it, without highlighting
+    let audit_card = (audit && highlight_mode != HighlightMode::Madlib)
+        .then(|| steganalysis::audit(&strip_highlighting(&text), &wordlist_set));
+
+    if json {
+        let mut report = serde_json::json!({
+            "text": text,
+            "payload_words": expected_words,
+            "sentences": sentence_count,
+            "seed": seed_value,
+        });
+        if variations > 1 {
+            report["variations"] = serde_json::json!(valid_variation_texts);
+        }
+        if let Some(card) = &audit_card {
+            report["audit"] = serde_json::json!(card);
+        }
+        println!("{}", report);
+    } else if let Some(card) = audit_card {
+        eprintln!();
+        eprintln!("{}", card);
+    }
+
     // Decoding: split on whitespace/punct, keep only tokens that are in the BIP39 set.
     // The payload_set contains all the BIP39 words, so filtering is straightforward.
 }
 
 #[cfg(test)]
+#[allow(clippy::useless_vec, clippy::default_constructed_unit_structs, clippy::manual_range_contains)]
 mod tests {
     use super::*;
     use rand::RngCore;
     use rand::SeedableRng;
+    #[cfg(feature = "grammar")]
     use glossia::GrammarChecker;
 
+    /// English payload slots at the default `--slot-threshold`
+    fn english_slots() -> &'static SlotMap {
+        static SLOTS: OnceLock<SlotMap> = OnceLock::new();
+        SLOTS.get_or_init(|| payload_slots(Wordlist::Bip39, 0.0))
+    }
+
     #[test]
     fn test_max_subsequence_embedding() {
         // Test basic embedding: slots [Det, N, V, Dot], payload [N, V]
@@ -3125,18 +6459,15 @@ mod tests {
         
         let payload: Vec<PayloadTok> = words
             .iter()
-            .map(|word| {
-                let tags = tag_word(word);
-                PayloadTok::new(word.clone(), &tags)
-            })
+            .map(|word| PayloadTok::new(word.clone(), english_slots().slots(word)))
             .collect();
 
         let payload_set: HashSet<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
-        let wordlist_words = load_payload_words("english").unwrap();
+        let wordlist_words = load_payload_words("english", Wordlist::Bip39).unwrap();
         let wordlist_set: HashSet<String> = wordlist_words.iter().map(|w| w.to_lowercase()).collect();
         
         let lex = setup_test_lexicon(payload_set.clone(), wordlist_set);
-        let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Body, 3, 20, SentenceLengthMode::Compact);
+        let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Body, 3, 20, SentenceLengthMode::Compact, &SentenceFilter::default());
 
         // Extract BIP39 words in order
         let extracted: Vec<String> = text
@@ -3249,6 +6580,172 @@ mod tests {
         assert_eq!(out[2], "send", "Expected transitive verb before NP object");
     }
 
+    #[test]
+    fn test_filler_slots_follow_word_frequency() {
+        let lex = Lexicon::new(HashSet::new(), HashSet::new())
+            .with_words(Pos::Det, &["each", "the"])
+            .with_words(Pos::N, &["administration", "dog"])
+            .with_filler(frequency::english());
+        let mut rng = StdRng::seed_from_u64(TEST_SEED);
+
+        let mut the = 0;
+        for _ in 0..500 {
+            if lex.pick_cover(&mut rng, Pos::Det, &[], &[]) == "the" {
+                the += 1;
+            }
+            // Content slots still take the shortest word
+            assert_eq!(lex.pick_cover(&mut rng, Pos::N, &[], &[]), "dog");
+        }
+        assert!(the > 400, "'the' picked {} of 500 times", the);
+    }
+
+    #[test]
+    fn test_topic_of_thread_splits_messages() {
+        let older = topic_of_thread("The walleye are biting.\n\nThe pike took my lure.\n").unwrap();
+        let newer = topic_of_thread("The pike took my lure.\n \nThe walleye are biting.").unwrap();
+        assert!(older.weight("pike") > older.weight("walleye"));
+        assert!(newer.weight("walleye") > newer.weight("pike"));
+        assert!(topic_of_thread("\n\nIt is what it is.\n").is_none());
+    }
+
+    #[test]
+    fn test_footer_decodes_with_the_text() {
+        let wordlist: HashSet<String> = load_payload_words("english", Wordlist::Bip39).unwrap().into_iter().collect();
+        let note = footer::render(footer::DEFAULT_TEMPLATE, "glossia --decode-ascii -", &wordlist).unwrap();
+        let bytes = b"Meet at noon".to_vec();
+        let words = encode_ascii_to_words(&SecretPayload::new(bytes.clone()), "english", Wordlist::Bip39).unwrap();
+        let text = footer::append(&words.join(" "), &note);
+        let options = DecodeOptions {
+            ascii: true,
+            ..Default::default()
+        };
+        assert_eq!(decode_cover_bytes(&text, "english", &options, false).unwrap(), bytes);
+    }
+
+    #[test]
+    fn test_explain_traces_carriers_and_productions() {
+        let wordlist = load_payload_words("english", Wordlist::Bip39).unwrap();
+        let words = encode_ascii_to_words(&SecretPayload::new(b"hi there".to_vec()), "english", Wordlist::Bip39).unwrap();
+        let payload: Vec<PayloadTok> = words.iter().map(|word| PayloadTok::new(word.clone(), english_slots().slots(word))).collect();
+        let payload_set: HashSet<String> = words.iter().cloned().collect();
+        let lex = setup_test_lexicon(payload_set, wordlist.iter().cloned().collect());
+        let mut rng = StdRng::seed_from_u64(TEST_SEED);
+        let (text, sentences) = encode_explain(&mut rng, &lex, &payload, GenerationMode::Body, 3, 20, SentenceLengthMode::Natural, &SentenceFilter::default(), &wordlist);
+
+        // The same text a plain encode makes
+        let mut rng = StdRng::seed_from_u64(TEST_SEED);
+        let (plain, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Body, 3, 20, SentenceLengthMode::Natural, &SentenceFilter::default());
+        assert_eq!(text, plain);
+
+        let carriers: Vec<&CarrierTrace> = sentences.iter().flat_map(|s| &s.words).filter_map(|w| w.carrier.as_ref()).collect();
+        assert_eq!(carriers.len(), words.len());
+        for (k, carrier) in carriers.iter().enumerate() {
+            assert_eq!((carrier.index, carrier.bits.clone()), (k, k * 11..(k + 1) * 11));
+            assert_eq!(wordlist[carrier.value], words[k]);
+        }
+        assert!(sentences.iter().flat_map(|s| &s.words).any(|w| w.carrier.is_none()), "no filler in {}", text);
+        for sentence in &sentences {
+            let (productions, probability) = sentence.derivation.as_ref().expect("a grammar sentence");
+            assert_eq!(productions[0].0, "S");
+            assert!(*probability > 0.0 && *probability <= 1.0);
+        }
+    }
+
+    #[test]
+    fn test_cover_model_picks_fillers_and_openers() {
+        let model = CoverModel::train("Caught each pike off a dock. Caught each bass from a boat.").unwrap();
+        let lex = Lexicon::new(HashSet::new(), HashSet::new())
+            .with_words(Pos::Det, &["each", "the"])
+            .with_words(Pos::N, &["administration", "dog"])
+            .with_words(Pos::V, &["caught", "saw"])
+            .with_filler(frequency::english())
+            .with_cover_model(Some(model));
+        let mut rng = StdRng::seed_from_u64(TEST_SEED);
+        let sentence = ["caught".to_string()];
+
+        for _ in 0..100 {
+            // The corpus has "each" and never "the"
+            assert_eq!(lex.pick_cover(&mut rng, Pos::Det, &sentence, &[]), "each");
+            // Sentence openers follow the corpus; other content words don't
+            assert_eq!(lex.pick_cover(&mut rng, Pos::V, &[], &[]), "caught");
+            assert_eq!(lex.pick_cover(&mut rng, Pos::V, &sentence, &[]), "saw");
+            assert_eq!(lex.pick_cover(&mut rng, Pos::N, &sentence, &[]), "dog");
+        }
+    }
+
+    #[test]
+    fn test_max_grade_filter_prefers_plain_cover_words() {
+        // With a strict grade limit, refilling should settle on the short cover noun
+        // while the payload word stays in place.
+        let payload = vec![PayloadTok::new("walk", &[Pos::V])];
+        let payload_set: HashSet<String> = ["walk".to_string()].into_iter().collect();
+        let lex = Lexicon::new(payload_set.clone(), payload_set)
+            .with_words(Pos::N, &["dog", "administration"]);
+
+        let slots = vec![Pos::Det, Pos::N, Pos::V, Pos::Dot];
+        let forced: HashMap<usize, usize> = [(2, 0)].into_iter().collect();
+        let filter = SentenceFilter { max_grade: Some(2.0), ..Default::default() };
+        let mut rng = StdRng::seed_from_u64(TEST_SEED);
+
+        for _ in 0..20 {
+            let mut payload_i = 0usize;
+            let out = fill_slots_filtered(&mut rng, &lex, &slots, &payload, &mut payload_i, &[], Some(&forced), &filter);
+            assert_eq!(out[1], "dog");
+            assert_eq!(out[2], "walk.");
+            assert_eq!(payload_i, 1);
+        }
+    }
+
+    #[test]
+    fn test_avoid_filter_resamples_cover_words() {
+        let avoid = build_avoid_regex(&["murder".to_string()], &[]).unwrap();
+        assert!(avoid.as_ref().unwrap().is_match("The Murder walk."));
+        assert!(!avoid.as_ref().unwrap().is_match("The murderous walk."));
+
+        let payload = vec![PayloadTok::new("walk", &[Pos::V])];
+        let payload_set: HashSet<String> = ["walk".to_string()].into_iter().collect();
+        let lex = Lexicon::new(payload_set.clone(), payload_set)
+            .with_words(Pos::N, &["dog", "murder"]);
+
+        let slots = vec![Pos::Det, Pos::N, Pos::V, Pos::Dot];
+        let forced: HashMap<usize, usize> = [(2, 0)].into_iter().collect();
+        let filter = SentenceFilter { avoid, ..Default::default() };
+        let mut rng = StdRng::seed_from_u64(TEST_SEED);
+
+        for _ in 0..20 {
+            let mut payload_i = 0usize;
+            let out = fill_slots_filtered(&mut rng, &lex, &slots, &payload, &mut payload_i, &[], Some(&forced), &filter);
+            assert_eq!(out[1], "dog");
+            assert_eq!(out[2], "walk.");
+        }
+    }
+
+    #[test]
+    fn test_bundled_avoid_list_compiles() {
+        let patterns = load_avoid_patterns("english").expect("english avoid list");
+        assert!(!patterns.is_empty());
+        let avoid = build_avoid_regex(&[], &patterns).unwrap().unwrap();
+        assert!(avoid.is_match("The gun is near the child."));
+        assert!(!avoid.is_match("The quiet dog can walk."));
+    }
+
+    #[test]
+    fn test_keyed_chaff_strips_back_to_carrier_text() {
+        let mut rng = StdRng::seed_from_u64(TEST_SEED);
+        let words = ["dolphin", "blanket", "hotel", "oxygen", "crater", "umbrella"];
+        let payload: Vec<PayloadTok> = words.iter().map(|w| PayloadTok::new(*w, english_slots().slots(w))).collect();
+        let payload_set: HashSet<String> = words.iter().map(|w| w.to_string()).collect();
+        let wordlist_set: HashSet<String> = load_payload_words("english", Wordlist::Bip39).unwrap().into_iter().collect();
+        let lex = setup_test_lexicon(payload_set, wordlist_set);
+        let filter = SentenceFilter::default();
+        let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::Bars, false, GenerationMode::Body, 3, 20, SentenceLengthMode::Compact, &filter);
+
+        let with_chaff = inject_chaff(&mut rng, &lex, &text, 0.5, Some("chaff key"), "english", Wordlist::Bip39, english_slots(), HighlightMode::Bars, 3, 20, &filter).unwrap();
+        assert!(chaff::split_sentences(&with_chaff).len() > chaff::split_sentences(&text).len());
+        let wordlist = load_payload_words("english", Wordlist::Bip39).unwrap();
+        assert_eq!(chaff::strip_chaff(&with_chaff, 0.5, "chaff key", &wordlist).unwrap(), chaff::split_sentences(&text).join(" "));
+    }
+
     /// Fixed seed for reproducible tests
     const TEST_SEED: u64 = 42;
 
@@ -3295,9 +6792,10 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "grammar")]
     fn test_generated_sentences_grammar() {
         // Skip if grammar checker files are not available
-        let grammar_checker = match GrammarChecker::from_language(glossia::Language::English) {
+        let grammar_checker = match GrammarChecker::global(glossia::Language::English) {
             Ok(checker) => checker,
             Err(_) => {
                 eprintln!("Skipping grammar test: nlprule binary files not found");
@@ -3307,22 +6805,19 @@ mod tests {
 
         // Generate sentences with random BIP39 words (using fixed seed for reproducibility)
         let mut rng = StdRng::seed_from_u64(TEST_SEED);
-        let words = select_random_words(&mut rng, 10, "english").unwrap();
+        let words = select_random_words(&mut rng, 10, "english", Wordlist::Bip39).unwrap();
         
         let payload: Vec<PayloadTok> = words
             .iter()
-            .map(|word| {
-                let tags = tag_word(word);
-                PayloadTok::new(word.clone(), &tags)
-            })
+            .map(|word| PayloadTok::new(word.clone(), english_slots().slots(word)))
             .collect();
 
         let payload_set: HashSet<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
-        let wordlist_words = load_payload_words("english").unwrap();
+        let wordlist_words = load_payload_words("english", Wordlist::Bip39).unwrap();
         let wordlist_set: HashSet<String> = wordlist_words.iter().map(|w| w.to_lowercase()).collect();
         
         let lex = setup_test_lexicon(payload_set.clone(), wordlist_set);
-        let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Subject, 3, 20, SentenceLengthMode::Compact);
+        let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Subject, 3, 20, SentenceLengthMode::Compact, &SentenceFilter::default());
 
         // Extract individual sentences
         let sentences = extract_sentences(&text);
@@ -3365,9 +6860,10 @@ mod tests {
     }
 
     #[test]
+    #[cfg(feature = "grammar")]
     fn test_grammar_with_different_payload_sizes() {
         // Skip if grammar checker files are not available
-        let grammar_checker = match GrammarChecker::from_language(glossia::Language::English) {
+        let grammar_checker = match GrammarChecker::global(glossia::Language::English) {
             Ok(checker) => checker,
             Err(_) => {
                 eprintln!("Skipping grammar test: nlprule binary files not found");
@@ -3378,22 +6874,19 @@ mod tests {
         // Test with different payload sizes (using same seed for reproducibility)
         for word_count in [5, 8, 12] {
             let mut rng = StdRng::seed_from_u64(TEST_SEED);
-            let words = select_random_words(&mut rng, word_count, "english").unwrap();
+            let words = select_random_words(&mut rng, word_count, "english", Wordlist::Bip39).unwrap();
             
             let payload: Vec<PayloadTok> = words
                 .iter()
-                .map(|word| {
-                    let tags = tag_word(word);
-                    PayloadTok::new(word.clone(), &tags)
-                })
+                .map(|word| PayloadTok::new(word.clone(), english_slots().slots(word)))
                 .collect();
 
             let payload_set: HashSet<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
-            let wordlist_words = load_payload_words("english").unwrap();
+            let wordlist_words = load_payload_words("english", Wordlist::Bip39).unwrap();
             let wordlist_set: HashSet<String> = wordlist_words.iter().map(|w| w.to_lowercase()).collect();
 
             let lex = setup_test_lexicon(payload_set, wordlist_set);
-            let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Subject, 3, 20, SentenceLengthMode::Compact);
+            let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Subject, 3, 20, SentenceLengthMode::Compact, &SentenceFilter::default());
 
             let sentences = extract_sentences(&text);
             
@@ -3422,22 +6915,19 @@ mod tests {
     fn test_sentence_structure() {
         // Test that generated sentences have reasonable structure (using fixed seed for reproducibility)
         let mut rng = StdRng::seed_from_u64(TEST_SEED);
-        let words = select_random_words(&mut rng, 5, "english").unwrap();
+        let words = select_random_words(&mut rng, 5, "english", Wordlist::Bip39).unwrap();
         
         let payload: Vec<PayloadTok> = words
             .iter()
-            .map(|word| {
-                let tags = tag_word(word);
-                PayloadTok::new(word.clone(), &tags)
-            })
+            .map(|word| PayloadTok::new(word.clone(), english_slots().slots(word)))
             .collect();
 
         let payload_set: HashSet<String> = payload.iter().map(|t| t.word.to_lowercase()).collect();
-        let wordlist_words = load_payload_words("english").unwrap();
+        let wordlist_words = load_payload_words("english", Wordlist::Bip39).unwrap();
         let wordlist_set: HashSet<String> = wordlist_words.iter().map(|w| w.to_lowercase()).collect();
 
         let lex = setup_test_lexicon(payload_set, wordlist_set);
-        let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Subject, 3, 20, SentenceLengthMode::Compact);
+        let (text, _) = generate_text(&mut rng, &lex, &payload, HighlightMode::None, false, GenerationMode::Subject, 3, 20, SentenceLengthMode::Compact, &SentenceFilter::default());
 
         let sentences = extract_sentences(&text);
         
@@ -3469,6 +6959,7 @@ mod tests {
             3,
             10,
             SentenceLengthMode::Compact,
+            None,
             false,
         );
         
@@ -3482,6 +6973,7 @@ mod tests {
             3,
             10,
             SentenceLengthMode::Compact,
+            None,
             true,
         );
         
@@ -3501,6 +6993,7 @@ mod tests {
             3,  // k_min is ignored in natural mode
             10,
             SentenceLengthMode::Natural,
+            None,
             false,
         );
         
@@ -3535,6 +7028,7 @@ mod tests {
             3,  // k_min is ignored in natural mode
             10,
             SentenceLengthMode::Natural,
+            None,
             true,
         );
         
@@ -3569,6 +7063,7 @@ mod tests {
             3,
             8,
             SentenceLengthMode::Natural,
+            None,
             false,
         );
         
@@ -3606,4 +7101,41 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_capability_names_parse() {
+        let caps = capabilities::capabilities();
+        assert!(caps.engines.iter().all(|name| Engine::from_name(name).is_some()));
+        assert!(caps.wordlists.iter().all(|name| Wordlist::from_name(name).is_some()));
+        assert!(caps.styles.iter().all(|name| Style::from_name(name).is_some()));
+    }
+
+    #[test]
+    fn test_cli_command_is_consistent() {
+        cli_command().debug_assert();
+    }
+
+    #[test]
+    fn test_parse_expiry() {
+        assert_eq!(parse_expiry("1760000000", 5), Ok(1_760_000_000));
+        assert_eq!(parse_expiry("+90m", 1000), Ok(1000 + 5400));
+        assert_eq!(parse_expiry("+7d", 0), Ok(604_800));
+        for bad in ["+7w", "+d", "7d", "+-1s", "soon", &format!("+{}d", u64::MAX)] {
+            assert!(parse_expiry(bad, 1000).is_err(), "{}", bad);
+        }
+        assert_eq!(duration(604_799), "6 days");
+        assert_eq!(duration(1), "1 second");
+    }
+
+    #[test]
+    fn test_decode_cover_bytes_round_trips_binary() {
+        let bytes: Vec<u8> = vec![0x00, 0xff, b'\r', b'\n', 0x80, 0x0a, 0xfe];
+        let words = encode_ascii_to_words(&SecretPayload::new(bytes.clone()), "english", Wordlist::Bip39).unwrap();
+        let options = DecodeOptions {
+            ascii: true,
+            ..Default::default()
+        };
+        let decoded = decode_cover_bytes(&words.join(" "), "english", &options, false).unwrap();
+        assert_eq!(decoded, bytes);
+    }
 }
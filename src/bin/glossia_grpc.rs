@@ -0,0 +1,235 @@
+//! gRPC front end for the glossia codec (feature `grpc`).
+//!
+//! Serves `glossia.v1.Glossia` (see `proto/glossia.proto`) over a
+//! [`glossia::service::Service`]. Configuration comes from the optional YAML
+//! file given as the only argument, then `GLOSSIA_*` environment variables.
+//! With `audit_log` set, the service's audit events go to stdout as JSON lines.
+//! SIGHUP or the `Reload` RPC reloads the wordlist without a restart, and
+//! `Config` reports the version in use. `Reload` wants the configured
+//! `reload_token` as `authorization: Bearer <token>` metadata, or a loopback
+//! client when no token is set.
+//!
+//! ```bash
+//! cargo run --features grpc --bin glossia_grpc -- service.yaml
+//! ```
+
+// tonic handlers answer with `Result<_, Status>`, and `Status` is large
+#![allow(clippy::result_large_err)]
+
+use glossia::payload::PayloadOptions;
+use glossia::service::{ActiveConfig, Service, ServiceConfig, ServiceError, AUDIT_TARGET};
+use std::net::{IpAddr, Ipv4Addr};
+use std::pin::Pin;
+use std::sync::Arc;
+use tokio_stream::{Stream, StreamExt};
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::prelude::*;
+
+mod proto {
+    tonic::include_proto!("glossia.v1");
+}
+
+use proto::glossia_server::{Glossia, GlossiaServer};
+use proto::{
+    ConfigRequest, ConfigResponse, DecodeRequest, DecodeResponse, DetectRequest, DetectResponse, EncodeRequest,
+    EncodeResponse, ReloadRequest,
+};
+
+type ResponseStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send>>;
+
+impl From<proto::PayloadOptions> for PayloadOptions {
+    fn from(options: proto::PayloadOptions) -> Self {
+        PayloadOptions {
+            checksum: options.checksum,
+            whiten: options.whiten,
+            passphrase: options.passphrase,
+            commit: options.commit,
+        }
+    }
+}
+
+impl From<ActiveConfig> for ConfigResponse {
+    fn from(active: ActiveConfig) -> Self {
+        ConfigResponse {
+            version: active.version,
+            wordlist: active.wordlist.display().to_string(),
+            wordlist_sha256: active.wordlist_sha256,
+            words: active.words as u32,
+            loaded_at: active.loaded_at,
+        }
+    }
+}
+
+struct GrpcService {
+    service: Arc<Service>,
+}
+
+/// The status for a failed request. Limit refusals are `RESOURCE_EXHAUSTED`
+/// and a refused reload `PERMISSION_DENIED`, with the HTTP equivalent in
+/// `glossia-status` (413, 429 or 403), the stable code in `glossia-error`,
+/// and `retry-after` seconds when rate limited.
+fn status(err: anyhow::Error) -> Status {
+    let Some(refusal) = err.downcast_ref::<ServiceError>() else {
+        return Status::invalid_argument(format!("{:#}", err));
+    };
+    let mut status = match refusal {
+        ServiceError::Forbidden => Status::permission_denied(refusal.to_string()),
+        _ => Status::resource_exhausted(refusal.to_string()),
+    };
+    let metadata = status.metadata_mut();
+    metadata.insert("glossia-status", refusal.http_status().into());
+    metadata.insert("glossia-error", refusal.code().parse().expect("codes are ASCII"));
+    if let ServiceError::RateLimited { retry_after } = refusal {
+        metadata.insert("retry-after", (retry_after.as_secs_f64().ceil() as u64).into());
+    }
+    status
+}
+
+/// The address a request's rate limit is kept under; connections without
+/// one (Unix sockets) share a single limit.
+fn client<T>(request: &Request<T>) -> IpAddr {
+    request.remote_addr().map_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED), |addr| addr.ip())
+}
+
+/// The token in a request's `authorization: Bearer <token>` metadata.
+fn bearer_token<T>(request: &Request<T>) -> Option<String> {
+    let value = request.metadata().get("authorization")?.to_str().ok()?;
+    value.strip_prefix("Bearer ").map(str::to_string)
+}
+
+fn encode(service: &Service, client: IpAddr, request: EncodeRequest) -> Result<EncodeResponse, Status> {
+    service.admit(client).map_err(status)?;
+    let options = request.options.map(PayloadOptions::from);
+    service
+        .encode(&request.payload, options.as_ref())
+        .map(|words| EncodeResponse { words })
+        .map_err(status)
+}
+
+fn decode(service: &Service, client: IpAddr, request: DecodeRequest) -> Result<DecodeResponse, Status> {
+    service.admit(client).map_err(status)?;
+    let options = request.options.map(PayloadOptions::from);
+    service
+        .decode(&request.text, options.as_ref())
+        .map(|payload| DecodeResponse { payload })
+        .map_err(status)
+}
+
+#[tonic::async_trait]
+impl Glossia for GrpcService {
+    async fn encode(&self, request: Request<EncodeRequest>) -> Result<Response<EncodeResponse>, Status> {
+        let client = client(&request);
+        encode(&self.service, client, request.into_inner()).map(Response::new)
+    }
+
+    async fn decode(&self, request: Request<DecodeRequest>) -> Result<Response<DecodeResponse>, Status> {
+        let client = client(&request);
+        decode(&self.service, client, request.into_inner()).map(Response::new)
+    }
+
+    async fn detect(&self, request: Request<DetectRequest>) -> Result<Response<DetectResponse>, Status> {
+        self.service.admit(client(&request)).map_err(status)?;
+        let found = self.service.detect(&request.into_inner().text);
+        Ok(Response::new(DetectResponse {
+            carrier_words: found.carrier_words as u32,
+            total_words: found.total_words as u32,
+            checksum_valid: found.checksum_valid,
+            likely: found.is_likely(),
+        }))
+    }
+
+    type EncodeStreamStream = ResponseStream<EncodeResponse>;
+
+    async fn encode_stream(
+        &self,
+        request: Request<Streaming<EncodeRequest>>,
+    ) -> Result<Response<Self::EncodeStreamStream>, Status> {
+        // Every chunk counts against the client's rate limit
+        let service = Arc::clone(&self.service);
+        let client = client(&request);
+        let chunks = request
+            .into_inner()
+            .map(move |chunk| chunk.and_then(|chunk| encode(&service, client, chunk)));
+        Ok(Response::new(Box::pin(chunks)))
+    }
+
+    type DecodeStreamStream = ResponseStream<DecodeResponse>;
+
+    async fn decode_stream(
+        &self,
+        request: Request<Streaming<DecodeRequest>>,
+    ) -> Result<Response<Self::DecodeStreamStream>, Status> {
+        // Every chunk counts against the client's rate limit
+        let service = Arc::clone(&self.service);
+        let client = client(&request);
+        let chunks = request
+            .into_inner()
+            .map(move |chunk| chunk.and_then(|chunk| decode(&service, client, chunk)));
+        Ok(Response::new(Box::pin(chunks)))
+    }
+
+    async fn config(&self, request: Request<ConfigRequest>) -> Result<Response<ConfigResponse>, Status> {
+        self.service.admit(client(&request)).map_err(status)?;
+        Ok(Response::new(self.service.active().into()))
+    }
+
+    async fn reload(&self, request: Request<ReloadRequest>) -> Result<Response<ConfigResponse>, Status> {
+        let client = client(&request);
+        self.service.admit(client).map_err(status)?;
+        let token = bearer_token(&request);
+        let service = Arc::clone(&self.service);
+        let reloaded = tokio::task::spawn_blocking(move || service.reload_for(client, token.as_deref()))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+        reloaded.map(|active| Response::new(active.into())).map_err(|err| match err.downcast_ref::<ServiceError>() {
+            Some(_) => status(err),
+            None => Status::failed_precondition(format!("{:#}", err)),
+        })
+    }
+}
+
+/// Reload the wordlist on every SIGHUP, reporting the outcome on stderr.
+#[cfg(unix)]
+fn reload_on_hangup(service: Arc<Service>) -> std::io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut hangups = signal(SignalKind::hangup())?;
+    tokio::spawn(async move {
+        while hangups.recv().await.is_some() {
+            let service = Arc::clone(&service);
+            let reloaded = tokio::task::spawn_blocking(move || (service.reload(), service.active().version)).await;
+            match reloaded {
+                Ok((Ok(active), _)) => eprintln!("Wordlist {} is version {}", active.wordlist.display(), active.version),
+                Ok((Err(err), version)) => eprintln!("Reload failed, keeping version {}: {:#}", version, err),
+                Err(err) => eprintln!("Reload failed: {}", err),
+            }
+        }
+    });
+    Ok(())
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config = match std::env::args().nth(1) {
+        Some(path) => ServiceConfig::from_file(path)?,
+        None => ServiceConfig::default(),
+    }
+    .with_env()?;
+    if config.audit_log {
+        let audit = Targets::new().with_target(AUDIT_TARGET, LevelFilter::INFO);
+        tracing_subscriber::registry()
+            .with(tracing_subscriber::fmt::layer().json().with_filter(audit))
+            .init();
+    }
+    let listen = config.listen;
+    let service = Arc::new(Service::new(config)?);
+    #[cfg(unix)]
+    reload_on_hangup(Arc::clone(&service))?;
+
+    eprintln!("glossia gRPC listening on {}", listen);
+    Server::builder()
+        .add_service(GlossiaServer::new(GrpcService { service }))
+        .serve(listen)
+        .await?;
+    Ok(())
+}
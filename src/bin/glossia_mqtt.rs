@@ -0,0 +1,201 @@
+//! Example bridge for LoRa and mesh users: payloads in, text-shaped MQTT
+//! messages out (feature `mqtt`).
+//!
+//! `publish` reads a payload from stdin, encodes it with `glossia --from-ascii
+//! --low-bandwidth`, splits the cover text into [`Platform::Mesh`] messages
+//! and publishes each to the topic. `subscribe` collects messages from the
+//! topic until a tagged set is complete, puts them back in order
+//! ([`transport::reassemble`]) and prints the payload they decode to, one
+//! line per payload.
+//!
+//! ```bash
+//! glossia_mqtt subscribe --broker localhost --topic mesh/notes
+//! echo "Meet at noon" | glossia_mqtt publish --broker localhost --topic mesh/notes
+//! ```
+
+use anyhow::{bail, Context};
+use clap::{Parser, Subcommand};
+use glossia::transport::{self, Platform, Profile};
+use rumqttc::{Client, Event, MqttOptions, Packet, QoS};
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+#[derive(Parser)]
+#[command(
+    name = "glossia_mqtt",
+    about = "Publish payloads to an MQTT topic as short cover-text messages, or decode them on subscribe"
+)]
+struct Args {
+    /// MQTT broker host
+    #[arg(long = "broker", default_value = "localhost", global = true)]
+    broker: String,
+
+    /// MQTT broker port
+    #[arg(long = "port", default_value_t = 1883, global = true)]
+    port: u16,
+
+    /// Topic to publish to or subscribe to
+    #[arg(long = "topic", default_value = "glossia", global = true)]
+    topic: String,
+
+    /// The glossia binary to run (default: the one beside this tool)
+    #[arg(long = "glossia", global = true)]
+    glossia: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Mode,
+}
+
+#[derive(Subcommand)]
+enum Mode {
+    /// Encode stdin and publish it as one or more messages
+    Publish {
+        /// Largest message in bytes, its (i/n) tag included
+        #[arg(long = "max-bytes-per-message", default_value_t = Platform::Mesh.profile().max_bytes_per_message)]
+        max_bytes_per_message: usize,
+
+        /// Seed for repeatable cover text
+        #[arg(long = "seed")]
+        seed: Option<u64>,
+    },
+    /// Print the payload of each complete set of messages on the topic
+    Subscribe,
+}
+
+/// Run `glossia` with `args` and `--json`, feeding it `stdin`, and return the
+/// JSON it prints.
+fn run(glossia: &Path, args: &[&str], stdin: &[u8]) -> anyhow::Result<serde_json::Value> {
+    let mut child = Command::new(glossia)
+        .args(args)
+        .arg("--json")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", glossia.display()))?;
+    child.stdin.take().expect("stdin is piped").write_all(stdin)?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("glossia {} failed: {}", args.join(" "), String::from_utf8_lossy(&output.stderr).lines().next().unwrap_or_default());
+    }
+    serde_json::from_slice(&output.stdout).context("glossia wrote invalid JSON")
+}
+
+/// The payload bytes a decode result holds, text or hex.
+fn payload_bytes(result: &serde_json::Value) -> anyhow::Result<Vec<u8>> {
+    if let Some(text) = result["payload"].as_str() {
+        return Ok(text.as_bytes().to_vec());
+    }
+    let hex = result["payload_hex"].as_str().context("glossia printed no payload")?;
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2).unwrap_or_default(), 16).context("Invalid payload hex"))
+        .collect()
+}
+
+fn publish(args: &Args, glossia: &Path, max_bytes_per_message: usize, seed: Option<u64>) -> anyhow::Result<()> {
+    let mut payload = Vec::new();
+    std::io::stdin().read_to_end(&mut payload)?;
+    let mut encode_args = vec!["--from-ascii", "-", "--low-bandwidth", "--mode", "binary"];
+    let seed = seed.map(|seed| seed.to_string());
+    if let Some(seed) = &seed {
+        encode_args.extend(["--seed", seed.as_str()]);
+    }
+    let encoded = run(glossia, &encode_args, &payload)?;
+    let cover = encoded["text"].as_str().context("glossia printed no text")?;
+    let profile = Profile { max_bytes_per_message, ..Platform::Mesh.profile() };
+    let messages = transport::adapt(cover, &profile);
+
+    let options = MqttOptions::new(format!("glossia-pub-{}", std::process::id()), args.broker.as_str(), args.port);
+    let (client, mut connection) = Client::new(options, messages.len() + 1);
+    for message in &messages {
+        client.publish(args.topic.as_str(), QoS::AtLeastOnce, false, message.as_bytes())?;
+    }
+    // Drive the connection until the broker has acknowledged every message
+    let mut acked = 0;
+    for event in connection.iter() {
+        if let Event::Incoming(Packet::PubAck(_)) = event? {
+            acked += 1;
+            if acked == messages.len() {
+                client.disconnect()?;
+                break;
+            }
+        }
+    }
+    eprintln!("Published {} bytes as {} messages to {}", payload.len(), messages.len(), args.topic);
+    Ok(())
+}
+
+fn subscribe(args: &Args, glossia: &Path) -> anyhow::Result<()> {
+    let mut options = MqttOptions::new(format!("glossia-sub-{}", std::process::id()), args.broker.as_str(), args.port);
+    options.set_keep_alive(Duration::from_secs(30));
+    let (client, mut connection) = Client::new(options, 10);
+    client.subscribe(args.topic.as_str(), QoS::AtLeastOnce)?;
+
+    // Messages of the set being collected, by their (i/n) tag
+    let mut pending: BTreeMap<usize, String> = BTreeMap::new();
+    let mut total = 0;
+    for event in connection.iter() {
+        let Event::Incoming(Packet::Publish(publish)) = event? else {
+            continue;
+        };
+        let Ok(message) = String::from_utf8(publish.payload.to_vec()) else {
+            eprintln!("Skipping a message that is not UTF-8");
+            continue;
+        };
+        let (index, of) = tag(&message).unwrap_or((1, 1));
+        // A message of another set means the last one will not complete
+        if of != total || pending.contains_key(&index) {
+            if !pending.is_empty() {
+                eprintln!("Dropping an incomplete set of {} of {} messages", pending.len(), total);
+            }
+            pending.clear();
+            total = of;
+        }
+        pending.insert(index, message);
+        if pending.len() < total {
+            continue;
+        }
+        let messages: Vec<String> = std::mem::take(&mut pending).into_values().collect();
+        let decoded = transport::reassemble(&messages)
+            .and_then(|cover| run(glossia, &["--decode-ascii", "-", "--low-bandwidth"], cover.as_bytes()))
+            .and_then(|result| payload_bytes(&result));
+        match decoded {
+            Ok(payload) => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(&payload)?;
+                stdout.write_all(b"\n")?;
+                stdout.flush()?;
+            }
+            Err(e) => eprintln!("Could not decode a set of {} messages: {:#}", messages.len(), e),
+        }
+    }
+    Ok(())
+}
+
+/// The `(i/n)` tag a message starts with.
+fn tag(message: &str) -> Option<(usize, usize)> {
+    let (tag, _) = message.strip_prefix('(')?.split_once(") ")?;
+    let (index, of) = tag.split_once('/')?;
+    Some((index.parse().ok()?, of.parse().ok()?))
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let glossia = match &args.glossia {
+        Some(path) => path.clone(),
+        None => std::env::current_exe()?.with_file_name(format!("glossia{}", std::env::consts::EXE_SUFFIX)),
+    };
+    if !glossia.exists() {
+        bail!("{} not found; build it first (cargo build --bin glossia) or pass --glossia", glossia.display());
+    }
+
+    match &args.command {
+        Mode::Publish { max_bytes_per_message, seed } => publish(&args, &glossia, *max_bytes_per_message, *seed),
+        Mode::Subscribe => subscribe(&args, &glossia),
+    }
+}
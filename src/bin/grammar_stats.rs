@@ -0,0 +1,373 @@
+//! Tool to report how a grammar behaves when it carries random payloads.
+//!
+//! This tool samples random BIP39 payloads, simulates encoding them by drawing
+//! derivations from the PCFG and embedding payload words (in order) into slots
+//! whose POS they allow, and reports how often each production and sentence
+//! template fires, the per-position slot entropy, and the average words per
+//! sentence. It is meant for tuning the grammar weights toward natural
+//! distributions; it does not run the full planner, so absolute numbers are an
+//! approximation of what `glossia` produces.
+//!
+//! With `--entropy` it skips sampling and prints the grammar's exact payload
+//! capacity instead (`Grammar::entropy_report`), at the POS weight threshold
+//! `--slot-threshold` restricts slots to, next to the unrestricted capacity.
+
+#[allow(dead_code)]
+#[path = "../grammar.rs"]
+mod grammar;
+
+use anyhow::Context;
+use clap::{Parser, ValueEnum};
+use glossia::types::Pos;
+use glossia::weights::SlotMap;
+use grammar::{CompiledGrammar, Grammar};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Longest derivation we accept before resampling (bounds recursive rules)
+const MAX_SLOTS: usize = 40;
+
+/// Sentences sampled without placing a payload word before that word is skipped
+const MAX_STALLED_SENTENCES: usize = 50;
+
+#[derive(Clone, Copy, ValueEnum)]
+enum GrammarKind {
+    Subject,
+    Body,
+}
+
+#[derive(Parser)]
+#[command(
+    name = "grammar_stats",
+    about = "Report production, template and slot statistics for a grammar",
+    long_about = "Encodes a corpus of random payloads by sampling derivations from the grammar\n\
+                  and reports how often each production and sentence template fires,\n\
+                  the POS entropy of each sentence position, and the average words per sentence."
+)]
+struct Args {
+    /// Which built-in grammar to analyze
+    #[arg(short = 'g', long = "grammar", value_enum, default_value = "body")]
+    grammar: GrammarKind,
+
+    /// Analyze a grammar file instead of the built-in one
+    #[arg(short = 'f', long = "file")]
+    file: Option<PathBuf>,
+
+    /// Language directory under languages/ to read payload.yaml from
+    #[arg(short = 'l', long = "language", default_value = "english")]
+    language: String,
+
+    /// Number of random payloads to encode
+    #[arg(short = 'n', long = "payloads", default_value = "200")]
+    payloads: usize,
+
+    /// Payload words per payload
+    #[arg(short = 'w', long = "words", default_value = "12")]
+    words: usize,
+
+    /// Random seed (for reproducible reports)
+    #[arg(short = 's', long = "seed", default_value = "0")]
+    seed: u64,
+
+    /// Number of templates to list (most frequent first)
+    #[arg(short = 't', long = "top", default_value = "15")]
+    top: usize,
+
+    /// Report bits per sentence from slot sizes and production weights instead of sampling
+    #[arg(long = "entropy")]
+    entropy: bool,
+
+    /// With --entropy, the POS weight a word needs to fill a slot
+    #[arg(long = "slot-threshold", default_value = "0")]
+    slot_threshold: f64,
+}
+
+/// POS tags that carry payload words in the generator (everything else is cover-only)
+fn is_payload_slot(pos: Pos) -> bool {
+    !matches!(
+        pos,
+        Pos::Dot | Pos::Prefix | Pos::Aux | Pos::Cop | Pos::To | Pos::Modal | Pos::Conj
+    )
+}
+
+fn parse_pos_tag(pos_str: &str) -> Option<Pos> {
+    match pos_str.trim() {
+        "Det" => Some(Pos::Det),
+        "Adj" => Some(Pos::Adj),
+        "N" => Some(Pos::N),
+        "V" => Some(Pos::V),
+        "Modal" => Some(Pos::Modal),
+        "Aux" => Some(Pos::Aux),
+        "Cop" => Some(Pos::Cop),
+        "To" => Some(Pos::To),
+        "Prep" => Some(Pos::Prep),
+        "Adv" => Some(Pos::Adv),
+        "Conj" => Some(Pos::Conj),
+        "Dot" => Some(Pos::Dot),
+        "Prefix" => Some(Pos::Prefix),
+        _ => None,
+    }
+}
+
+/// Load payload words with the POS tags they may fill (sorted for reproducibility)
+fn load_payload_words(path: &PathBuf) -> anyhow::Result<Vec<(String, Vec<Pos>)>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read YAML file: {:?}", path))?;
+
+    let yaml_data: HashMap<String, HashMap<String, f64>> = serde_yaml::from_str(&content)
+        .with_context(|| format!("Failed to parse YAML file: {:?}", path))?;
+
+    let mut words: Vec<(String, Vec<Pos>)> = yaml_data
+        .into_iter()
+        .filter_map(|(word, weights)| {
+            let tags: Vec<Pos> = weights
+                .iter()
+                .filter(|(_, w)| **w > 0.0)
+                .filter_map(|(pos, _)| parse_pos_tag(pos))
+                .filter(|pos| is_payload_slot(*pos))
+                .collect();
+            if tags.is_empty() {
+                None
+            } else {
+                Some((word.to_lowercase(), tags))
+            }
+        })
+        .collect();
+    words.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(words)
+}
+
+/// Shannon entropy (bits) of a count distribution
+fn entropy_bits<K>(counts: &HashMap<K, usize>) -> f64 {
+    let total: usize = counts.values().sum();
+    if total == 0 {
+        return 0.0;
+    }
+    counts
+        .values()
+        .filter(|c| **c > 0)
+        .map(|c| {
+            let p = *c as f64 / total as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+fn format_template(slots: &[Pos]) -> String {
+    slots
+        .iter()
+        .map(|p| format!("{:?}", p))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[derive(Default)]
+struct Stats {
+    sentences: usize,
+    words: usize,
+    carriers: usize,
+    skipped_words: usize,
+    rejected_derivations: usize,
+    productions: HashMap<(String, usize), usize>,
+    templates: HashMap<Vec<Pos>, usize>,
+    positions: Vec<HashMap<Pos, usize>>,
+}
+
+impl Stats {
+    fn record(&mut self, slots: &[Pos], productions: &[(String, usize)], carriers: usize) {
+        self.sentences += 1;
+        self.words += slots.iter().filter(|p| **p != Pos::Dot).count();
+        self.carriers += carriers;
+        for prod in productions {
+            *self.productions.entry(prod.clone()).or_default() += 1;
+        }
+        *self.templates.entry(slots.to_vec()).or_default() += 1;
+        if self.positions.len() < slots.len() {
+            self.positions.resize_with(slots.len(), HashMap::new);
+        }
+        for (i, pos) in slots.iter().enumerate() {
+            *self.positions[i].entry(*pos).or_default() += 1;
+        }
+    }
+}
+
+/// Encode one payload: keep sampling sentences until every payload word is placed.
+fn encode_payload(
+    grammar: &CompiledGrammar,
+    rng: &mut StdRng,
+    payload: &[&(String, Vec<Pos>)],
+    stats: &mut Stats,
+) {
+    let mut next = 0;
+    let mut stalled = 0;
+
+    while next < payload.len() {
+        let derivation = grammar.sample(rng, "S", MAX_SLOTS);
+        if derivation.is_none() {
+            stats.rejected_derivations += 1;
+        }
+
+        // Greedy in-order embedding: a slot carries the next payload word if it fits
+        let mut carriers = 0;
+        for slot in derivation.iter().flat_map(|d| &d.slots) {
+            if next < payload.len() && payload[next].1.contains(slot) {
+                next += 1;
+                carriers += 1;
+            }
+        }
+
+        // The generator never emits a sentence that carries nothing
+        let Some(derivation) = derivation.filter(|_| carriers > 0) else {
+            stalled += 1;
+            if stalled >= MAX_STALLED_SENTENCES {
+                stats.skipped_words += 1;
+                next += 1;
+                stalled = 0;
+            }
+            continue;
+        };
+        stalled = 0;
+        stats.record(&derivation.slots, &derivation.productions, carriers);
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+
+    let grammar = match (&args.file, args.grammar) {
+        (Some(path), _) => Grammar::from_file(path),
+        (None, GrammarKind::Subject) => Grammar::subject(),
+        (None, GrammarKind::Body) => Grammar::default(),
+    }
+    .map_err(|e| anyhow::anyhow!("Failed to load grammar: {}", e))?;
+
+    let payload_path = PathBuf::from(format!("languages/{}/payload.yaml", args.language));
+    if args.entropy {
+        let weights = glossia::weights::load(&payload_path)?;
+        let mut wordlist: Vec<String> = weights.words().map(str::to_string).collect();
+        wordlist.sort();
+        let report = grammar.entropy_report(&wordlist, &SlotMap::new(&weights, args.slot_threshold));
+        print!("{}", report);
+        if args.slot_threshold > 0.0 {
+            let baseline = grammar.entropy_report(&wordlist, &SlotMap::new(&weights, 0.0));
+            println!(
+                "Without the threshold: {:.2} bits ({:+.2})",
+                baseline.bits_per_sentence,
+                report.bits_per_sentence - baseline.bits_per_sentence
+            );
+        }
+        return Ok(());
+    }
+    eprintln!("Loading payload words from {:?}...", payload_path);
+    let payload_words = load_payload_words(&payload_path)?;
+    if payload_words.is_empty() {
+        anyhow::bail!("No payload words with content POS tags in {:?}", payload_path);
+    }
+    eprintln!("Loaded {} words", payload_words.len());
+
+    eprintln!(
+        "Encoding {} payloads of {} words (seed {})...",
+        args.payloads, args.words, args.seed
+    );
+    let compiled = grammar.compile();
+    let mut rng = StdRng::seed_from_u64(args.seed);
+    let mut stats = Stats::default();
+    for _ in 0..args.payloads {
+        let payload: Vec<&(String, Vec<Pos>)> = (0..args.words)
+            .filter_map(|_| payload_words.choose(&mut rng))
+            .collect();
+        encode_payload(&compiled, &mut rng, &payload, &mut stats);
+    }
+
+    if stats.sentences == 0 {
+        anyhow::bail!("Grammar produced no sentences that could carry payload words");
+    }
+
+    let sentences = stats.sentences as f64;
+    println!("Sentences: {}", stats.sentences);
+    println!("Average words per sentence: {:.2}", stats.words as f64 / sentences);
+    println!(
+        "Average payload words per sentence: {:.2}",
+        stats.carriers as f64 / sentences
+    );
+    if stats.skipped_words > 0 {
+        println!("Payload words no sentence could carry: {}", stats.skipped_words);
+    }
+    if stats.rejected_derivations > 0 {
+        println!(
+            "Derivations rejected (over {} slots): {}",
+            MAX_SLOTS, stats.rejected_derivations
+        );
+    }
+
+    // Production usage, compared against the configured weights
+    println!("\nProduction usage:");
+    for nt in grammar.non_terminals() {
+        let fired: Vec<usize> = (0..grammar.production_count(nt))
+            .map(|i| {
+                stats
+                    .productions
+                    .get(&(nt.to_string(), i))
+                    .copied()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let total: usize = fired.iter().sum();
+        if total == 0 {
+            println!("  {} (never expanded)", nt);
+            continue;
+        }
+        println!("  {} ({} expansions)", nt, total);
+        for (i, count) in fired.iter().enumerate() {
+            println!(
+                "    #{}: {:>7} ({:5.1}%)",
+                i,
+                count,
+                100.0 * *count as f64 / total as f64
+            );
+        }
+    }
+
+    let mut templates: Vec<(&Vec<Pos>, &usize)> = stats.templates.iter().collect();
+    templates.sort_by(|a, b| {
+        b.1.cmp(a.1)
+            .then_with(|| format_template(a.0).cmp(&format_template(b.0)))
+    });
+    println!(
+        "\nTemplates: {} distinct, {:.2} bits",
+        templates.len(),
+        entropy_bits(&stats.templates)
+    );
+    for (slots, count) in templates.into_iter().take(args.top) {
+        println!(
+            "  {:>7} ({:5.1}%)  {}",
+            count,
+            100.0 * *count as f64 / sentences,
+            format_template(slots)
+        );
+    }
+
+    println!("\nSlot entropy by position:");
+    for (i, counts) in stats.positions.iter().enumerate() {
+        let reached: usize = counts.values().sum();
+        let mut tags: Vec<(&Pos, &usize)> = counts.iter().collect();
+        tags.sort_by(|a, b| b.1.cmp(a.1));
+        let summary = tags
+            .iter()
+            .map(|(pos, c)| format!("{:?} {:.0}%", pos, 100.0 * **c as f64 / reached as f64))
+            .collect::<Vec<_>>()
+            .join(", ");
+        println!(
+            "  {:>2}: {:.2} bits ({:5.1}% of sentences)  {}",
+            i,
+            entropy_bits(counts),
+            100.0 * reached as f64 / sentences,
+            summary
+        );
+    }
+
+    Ok(())
+}
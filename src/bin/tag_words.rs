@@ -70,18 +70,14 @@ fn tag_word_with_nlprule(checker: &GrammarChecker, word: &str) -> HashSet<String
     
     for sentence in test_sentences {
         for sent in checker.tokenize(&sentence) {
-            for token in sent.tokens() {
-                // Access text via token.word().text() according to nlprule API
-                let token_text = token.word().text().as_str().to_lowercase();
+            for token in &sent.tokens {
+                let token_text = token.text.to_lowercase();
                 let word_lower = word.to_lowercase();
                 
                 // Check if this token matches our word
                 if token_text == word_lower {
-                    // Extract POS tags from token using nlprule's API
-                    // According to docs: token.word().tags()[0].pos()
-                    for tag in token.word().tags() {
-                        let pos_tag = tag.pos().as_str();
-                        if let Some(normalized_pos) = normalize_nlprule_pos(pos_tag) {
+                    for tag in &token.tags {
+                        if let Some(normalized_pos) = normalize_nlprule_pos(&tag.tag) {
                             pos_tags.insert(normalized_pos.to_string());
                         }
                     }
@@ -103,17 +99,15 @@ fn tag_word_alternative(checker: &GrammarChecker, word: &str) -> HashSet<String>
     let comprehensive_sentence = format!("The {} {} {} {} it {} well.", word, word, word, word, word);
     
     for sent in checker.tokenize(&comprehensive_sentence) {
-        for token in sent.tokens() {
-            // Access text via token.word().text() according to nlprule API
-            let token_text = token.word().text().as_str().to_lowercase();
+        for token in &sent.tokens {
+            let token_text = token.text.to_lowercase();
             let word_lower = word.to_lowercase();
             
             // Check if this token matches our word
             if token_text == word_lower {
                 // Extract all POS tags for this word occurrence
-                for tag in token.word().tags() {
-                    let pos_tag = tag.pos().as_str();
-                    if let Some(normalized_pos) = normalize_nlprule_pos(pos_tag) {
+                for tag in &token.tags {
+                    if let Some(normalized_pos) = normalize_nlprule_pos(&tag.tag) {
                         pos_tags.insert(normalized_pos.to_string());
                     }
                 }
@@ -160,7 +154,7 @@ fn main() -> anyhow::Result<()> {
         Err(e) => {
             eprintln!("Error: Could not load nlprule data files.");
             eprintln!("Please ensure en_tokenizer.bin and en_rules.bin are available.");
-            eprintln!("They should be in: current directory, data/, /app/data/, or /opt/nlprule-data/");
+            eprintln!("Put them in $GLOSSIA_MODEL_DIR or one of the directories listed below.");
             eprintln!("\nError details: {}", e);
             eprintln!("\nNote: nlprule binary files can be downloaded from:");
             eprintln!("https://github.com/bminixhofer/nlprule");
@@ -186,9 +180,8 @@ fn main() -> anyhow::Result<()> {
     
     // Tag each word
     let mut tagged_words: Vec<(String, HashSet<String>)> = Vec::new();
-    let mut processed = 0;
     
-    for word in words {
+    for (processed, word) in words.into_iter().enumerate() {
         if processed % 100 == 0 && processed > 0 {
             eprintln!("Processed {} words...", processed);
         }
@@ -200,7 +193,6 @@ fn main() -> anyhow::Result<()> {
         };
         
         tagged_words.push((word, pos_tags));
-        processed += 1;
     }
     
     eprintln!("Tagging complete!");
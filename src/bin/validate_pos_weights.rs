@@ -3,12 +3,22 @@
 //! This tool reads YAML files containing words with POS tag weights,
 //! uses nlprule to tag each word in various contexts, calculates observed
 //! POS tag frequencies, and outputs a new YAML file with nlprule's weights.
+//!
+//! `--verbose-yaml` additionally records which test frames produced which
+//! tags, so a blended distribution can be traced back to its contexts.
+//!
+//! Words keep their input order by default (`--sort input`), and the output
+//! is spliced into the input text so comments and section headers survive;
+//! `--sort alpha` writes a plain alphabetical YAML file instead.
 
-use clap::Parser;
-use std::collections::HashMap;
+use clap::{Parser, ValueEnum};
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
 use anyhow::Context;
 use glossia::GrammarChecker;
+use glossia::weights;
 
 /// Convert nlprule POS tags to our simplified format
 fn normalize_nlprule_pos(nlprule_tag: &str) -> Option<&'static str> {
@@ -39,98 +49,210 @@ fn normalize_nlprule_pos(nlprule_tag: &str) -> Option<&'static str> {
     }
 }
 
-/// Test contexts for different POS categories
-fn get_test_contexts(word: &str) -> Vec<String> {
+/// Test contexts for different POS categories, labeled with the frame
+/// (the POS each sentence is built to elicit)
+fn get_test_contexts(word: &str) -> Vec<(&'static str, String)> {
     vec![
         // Noun contexts
-        format!("The {} works.", word),
-        format!("A {} helps.", word),
-        format!("This {} is good.", word),
-        format!("Many {} help.", word),
-        format!("Some {} work.", word),
-        format!("Each {} helps.", word),
+        ("noun", format!("The {} works.", word)),
+        ("noun", format!("A {} helps.", word)),
+        ("noun", format!("This {} is good.", word)),
+        ("noun", format!("Many {} help.", word)),
+        ("noun", format!("Some {} work.", word)),
+        ("noun", format!("Each {} helps.", word)),
         // Verb contexts
-        format!("They {} it.", word),
-        format!("I {} now.", word),
-        format!("We {} here.", word),
-        format!("He {} well.", word),
-        format!("She {} quickly.", word),
-        format!("It {} fast.", word),
+        ("verb", format!("They {} it.", word)),
+        ("verb", format!("I {} now.", word)),
+        ("verb", format!("We {} here.", word)),
+        ("verb", format!("He {} well.", word)),
+        ("verb", format!("She {} quickly.", word)),
+        ("verb", format!("It {} fast.", word)),
         // Adjective contexts
-        format!("The {} thing works.", word),
-        format!("It is {}.", word),
-        format!("A {} item helps.", word),
-        format!("Very {} stuff.", word),
-        format!("That seems {}.", word),
-        format!("It looks {}.", word),
-        format!("They are {}.", word),
+        ("adjective", format!("The {} thing works.", word)),
+        ("adjective", format!("It is {}.", word)),
+        ("adjective", format!("A {} item helps.", word)),
+        ("adjective", format!("Very {} stuff.", word)),
+        ("adjective", format!("That seems {}.", word)),
+        ("adjective", format!("It looks {}.", word)),
+        ("adjective", format!("They are {}.", word)),
         // Adverb contexts
-        format!("They work {}.", word),
-        format!("It runs {}.", word),
-        format!("Very {} done.", word),
-        format!("It moves {}.", word),
+        ("adverb", format!("They work {}.", word)),
+        ("adverb", format!("It runs {}.", word)),
+        ("adverb", format!("Very {} done.", word)),
+        ("adverb", format!("It moves {}.", word)),
         // Preposition contexts
-        format!("They go {} it.", word),
-        format!("We work {} it.", word),
-        format!("It sits {} there.", word),
+        ("preposition", format!("They go {} it.", word)),
+        ("preposition", format!("We work {} it.", word)),
+        ("preposition", format!("It sits {} there.", word)),
         // Determiner contexts
-        format!("{} thing works.", word),
-        format!("{} items help.", word),
+        ("determiner", format!("{} thing works.", word)),
+        ("determiner", format!("{} items help.", word)),
         // Conjunction contexts
-        format!("This {} that.", word),
-        format!("Here {} there.", word),
+        ("conjunction", format!("This {} that.", word)),
+        ("conjunction", format!("Here {} there.", word)),
     ]
 }
 
-/// Calculate observed POS tag frequencies for a word using nlprule
+/// The tags nlprule gave a word in one test sentence.
+#[derive(Serialize)]
+struct ContextResult {
+    frame: &'static str,
+    sentence: String,
+    tags: Vec<String>,
+}
+
+/// A word's entry in the `--verbose-yaml` file.
+#[derive(Serialize)]
+struct ContextReport {
+    /// The weights written to the main output
+    weights: BTreeMap<String, f64>,
+    /// Tag counts per frame
+    by_frame: BTreeMap<&'static str, BTreeMap<String, usize>>,
+    contexts: Vec<ContextResult>,
+}
+
+impl ContextReport {
+    fn new(weights: &HashMap<String, f64>, contexts: Vec<ContextResult>) -> Self {
+        let mut by_frame: BTreeMap<&'static str, BTreeMap<String, usize>> = BTreeMap::new();
+        for context in &contexts {
+            let counts = by_frame.entry(context.frame).or_default();
+            for tag in &context.tags {
+                *counts.entry(tag.clone()).or_insert(0) += 1;
+            }
+        }
+        ContextReport {
+            weights: weights.iter().map(|(pos, w)| (pos.clone(), *w)).collect(),
+            by_frame,
+            contexts,
+        }
+    }
+}
+
+/// Calculate observed POS tag frequencies for a word using nlprule, along
+/// with the tags seen in each test context
 fn calculate_observed_weights(
     checker: &GrammarChecker,
     word: &str,
-) -> HashMap<String, f64> {
+) -> (HashMap<String, f64>, Vec<ContextResult>) {
     let mut pos_counts: HashMap<String, usize> = HashMap::new();
     let mut total_count = 0usize;
+    let mut results = Vec::new();
     
     let contexts = get_test_contexts(word);
     
-    for sentence in contexts {
+    for (frame, sentence) in contexts {
+        let mut tags_here = Vec::new();
         for sent in checker.tokenize(&sentence) {
-            for token in sent.tokens() {
-                let token_text = token.word().text().as_str().to_lowercase();
+            for token in &sent.tokens {
+                let token_text = token.text.to_lowercase();
                 let word_lower = word.to_lowercase();
                 
                 if token_text == word_lower {
-                    for tag in token.word().tags() {
-                        let pos_tag = tag.pos().as_str();
-                        if let Some(normalized_pos) = normalize_nlprule_pos(pos_tag) {
+                    for tag in &token.tags {
+                        if let Some(normalized_pos) = normalize_nlprule_pos(&tag.tag) {
                             *pos_counts.entry(normalized_pos.to_string()).or_insert(0) += 1;
                             total_count += 1;
+                            tags_here.push(normalized_pos.to_string());
                         }
                     }
                 }
             }
         }
+        results.push(ContextResult { frame, sentence, tags: tags_here });
     }
     
     // Convert counts to frequencies
     if total_count == 0 {
-        return HashMap::new();
+        return (HashMap::new(), results);
     }
     
-    pos_counts
+    let weights = pos_counts
         .into_iter()
         .map(|(pos, count)| (pos, count as f64 / total_count as f64))
-        .collect()
+        .collect();
+    (weights, results)
 }
 
-/// Load YAML file and parse word -> POS weights mapping
-fn load_yaml_weights(path: &PathBuf) -> anyhow::Result<HashMap<String, HashMap<String, f64>>> {
+/// Word -> POS weights, in file order
+type WeightMap = IndexMap<String, IndexMap<String, f64>>;
+
+/// Load and validate a YAML file as a word -> POS weights mapping, keeping
+/// the file's word and tag order. Also returns the raw text, for `render_in_place`.
+fn load_yaml_weights(path: &PathBuf) -> anyhow::Result<(WeightMap, String)> {
     let content = std::fs::read_to_string(path)
         .with_context(|| format!("Failed to read YAML file: {:?}", path))?;
     
-    let yaml_data: HashMap<String, HashMap<String, f64>> = serde_yaml::from_str(&content)
-        .with_context(|| format!("Failed to parse YAML file: {:?}", path))?;
+    let pos_weights = weights::parse(&content)
+        .with_context(|| format!("Invalid weight file: {:?}", path))?;
     
-    Ok(yaml_data)
+    Ok((pos_weights.into_inner(), content))
+}
+
+/// `weights` ordered like the word's tags in the input file, with tags the
+/// input didn't have after them by name.
+fn order_like(weights: &HashMap<String, f64>, input: &IndexMap<String, f64>) -> IndexMap<String, f64> {
+    let mut ordered: IndexMap<String, f64> = input
+        .keys()
+        .filter_map(|tag| weights.get(tag).map(|w| (tag.clone(), *w)))
+        .collect();
+    let mut new_tags: Vec<(&String, &f64)> = weights.iter().filter(|(tag, _)| !input.contains_key(*tag)).collect();
+    new_tags.sort_by(|a, b| a.0.cmp(b.0));
+    ordered.extend(new_tags.into_iter().map(|(tag, w)| (tag.clone(), *w)));
+    ordered
+}
+
+/// Format a weight the way the curated files write them (`1.0`, `0.35`).
+fn format_weight(weight: f64) -> String {
+    if weight.fract() == 0.0 {
+        format!("{:.1}", weight)
+    } else {
+        format!("{}", weight)
+    }
+}
+
+/// Rewrite `source` with each word's weights replaced by `weights`, keeping
+/// comments, blank lines and word order as they are. Words missing from
+/// `weights` (beyond `--max-words`) are left out.
+fn render_in_place(source: &str, weights: &WeightMap) -> String {
+    let mut out = String::new();
+    // Inside the weight block of a word already written
+    let mut in_block = false;
+    for line in source.lines() {
+        let trimmed = line.trim_start();
+        let indented = trimmed.len() < line.len();
+        if in_block && indented {
+            // Keep annotations inside a block; the old weights are replaced
+            if trimmed.starts_with('#') {
+                out.push_str(line);
+                out.push('\n');
+            }
+            continue;
+        }
+        in_block = false;
+
+        let key = match line.split_once(':') {
+            Some((key, _)) if !indented && !trimmed.starts_with('#') => Some(key),
+            _ => None,
+        };
+        let Some(key) = key else {
+            out.push_str(line);
+            out.push('\n');
+            continue;
+        };
+        in_block = true;
+        let word = key.trim().trim_matches(|c| c == '"' || c == '\'');
+        match weights.get(word) {
+            Some(tags) if tags.is_empty() => out.push_str(&format!("{}: {{}}\n", key)),
+            Some(tags) => {
+                out.push_str(&format!("{}:\n", key));
+                for (tag, weight) in tags {
+                    out.push_str(&format!("  {}: {}\n", tag, format_weight(*weight)));
+                }
+            }
+            None => {}
+        }
+    }
+    out
 }
 
 /// Round a float to specified decimal places
@@ -139,6 +261,14 @@ fn round_to_decimal_places(value: f64, places: usize) -> f64 {
     (value * multiplier).round() / multiplier
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum SortOrder {
+    /// Alphabetical by word (plain YAML, comments dropped)
+    Alpha,
+    /// Input file order, with comments and blank lines kept
+    Input,
+}
+
 #[derive(Parser)]
 #[command(
     name = "validate_pos_weights",
@@ -168,6 +298,14 @@ struct Args {
     /// Round weights to this many decimal places (default: 3)
     #[arg(short = 'r', long = "round", default_value = "3")]
     decimal_places: usize,
+
+    /// Also write per-context results (which test frames produced which tags) to this YAML file
+    #[arg(long = "verbose-yaml")]
+    verbose_yaml: Option<PathBuf>,
+
+    /// Word order of the output
+    #[arg(long = "sort", value_enum, default_value = "input")]
+    sort: SortOrder,
 }
 
 fn main() -> anyhow::Result<()> {
@@ -175,12 +313,12 @@ fn main() -> anyhow::Result<()> {
     
     // Initialize grammar checker
     eprintln!("Loading nlprule tokenizer and rules...");
-    let checker = match GrammarChecker::from_language(glossia::Language::English) {
+    let checker = match GrammarChecker::global(glossia::Language::English) {
         Ok(checker) => checker,
         Err(e) => {
             eprintln!("Error: Could not load nlprule data files.");
             eprintln!("Please ensure en_tokenizer.bin and en_rules.bin are available.");
-            eprintln!("They should be in: current directory, data/, /app/data/, or /opt/nlprule-data/");
+            eprintln!("Put them in $GLOSSIA_MODEL_DIR or one of the directories listed below.");
             eprintln!("\nError details: {}", e);
             return Err(e);
         }
@@ -188,11 +326,11 @@ fn main() -> anyhow::Result<()> {
     
     // Load YAML file to get word list (preserve order)
     eprintln!("Loading words from {:?}...", args.file);
-    let all_words = load_yaml_weights(&args.file)?;
+    let (all_words, source) = load_yaml_weights(&args.file)?;
     eprintln!("Loaded {} words", all_words.len());
     
     // Limit words if requested
-    let words_to_process: Vec<(String, HashMap<String, f64>)> = if let Some(max) = args.max_words {
+    let words_to_process: Vec<(String, IndexMap<String, f64>)> = if let Some(max) = args.max_words {
         all_words.into_iter().take(max).collect()
     } else {
         all_words.into_iter().collect()
@@ -201,16 +339,16 @@ fn main() -> anyhow::Result<()> {
     eprintln!("Processing {} words...", words_to_process.len());
     
     // Calculate observed weights for each word
-    let mut output_weights: HashMap<String, HashMap<String, f64>> = HashMap::new();
-    let mut processed = 0;
+    let mut output_weights: WeightMap = IndexMap::new();
     let mut words_without_tags = 0;
+    let mut context_reports: BTreeMap<String, ContextReport> = BTreeMap::new();
     
-    for (word, _expected_weights) in words_to_process {
+    for (processed, (word, input_weights)) in words_to_process.into_iter().enumerate() {
         if processed % 50 == 0 && processed > 0 {
             eprintln!("Processed {} words...", processed);
         }
         
-        let observed_weights = calculate_observed_weights(&checker, &word);
+        let (observed_weights, contexts) = calculate_observed_weights(&checker, &word);
         
         // Filter out weights below threshold and round
         let mut filtered_weights: HashMap<String, f64> = HashMap::new();
@@ -228,15 +366,19 @@ fn main() -> anyhow::Result<()> {
                 .into_iter()
                 .map(|(pos, weight)| (pos, round_to_decimal_places(weight / total, args.decimal_places)))
                 .collect();
-            output_weights.insert(word, normalized);
+            if args.verbose_yaml.is_some() {
+                context_reports.insert(word.clone(), ContextReport::new(&normalized, contexts));
+            }
+            output_weights.insert(word, order_like(&normalized, &input_weights));
         } else {
             // If no weights found, preserve the word with empty weights
             // This ensures all words from input are included in output
-            output_weights.insert(word, HashMap::new());
+            if args.verbose_yaml.is_some() {
+                context_reports.insert(word.clone(), ContextReport::new(&HashMap::new(), contexts));
+            }
+            output_weights.insert(word, IndexMap::new());
             words_without_tags += 1;
         }
-        
-        processed += 1;
     }
     
     eprintln!("Processing complete!");
@@ -245,8 +387,14 @@ fn main() -> anyhow::Result<()> {
     }
     
     // Output YAML
-    let yaml_output = serde_yaml::to_string(&output_weights)
-        .context("Failed to serialize weights to YAML")?;
+    let yaml_output = match args.sort {
+        SortOrder::Input => render_in_place(&source, &output_weights),
+        SortOrder::Alpha => {
+            output_weights.sort_keys();
+            serde_yaml::to_string(&output_weights)
+                .context("Failed to serialize weights to YAML")?
+        }
+    };
     
     if let Some(ref path) = args.output {
         std::fs::write(path, yaml_output)
@@ -256,6 +404,14 @@ fn main() -> anyhow::Result<()> {
     } else {
         print!("{}", yaml_output);
     }
+
+    if let Some(ref path) = args.verbose_yaml {
+        let report = serde_yaml::to_string(&context_reports)
+            .context("Failed to serialize context results to YAML")?;
+        std::fs::write(path, report)
+            .with_context(|| format!("Failed to write context results to {:?}", path))?;
+        eprintln!("Per-context results saved to {:?}", path);
+    }
     
     Ok(())
 }
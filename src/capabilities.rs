@@ -0,0 +1,121 @@
+//! What this build supports, for front ends that build their menus from it.
+//!
+//! A GUI that hard-codes the engine or style names falls out of step as the
+//! crate gains modes; [`capabilities`] lists them instead, along with the
+//! input formats and Cargo features compiled into this build. The same data
+//! is available as JSON ([`Capabilities::to_json`]), which the WASM exports
+//! and `glossia capabilities` return.
+
+use crate::data_mode::DataMode;
+use crate::style::Style;
+use crate::Language;
+use serde::Serialize;
+
+/// Cover text engines, by their `--engine` names
+pub const ENGINES: &[&str] = &[
+    "prose",
+    "emoji",
+    #[cfg(feature = "neural")]
+    "neural",
+];
+
+/// Payload wordlists, by their `--wordlist` names
+pub const WORDLISTS: &[&str] = &["bip39", "phonetic"];
+
+/// Cargo features and whether each is compiled in
+const FEATURES: &[(&str, bool)] = &[
+    ("zeroize", cfg!(feature = "zeroize")),
+    ("grammar", cfg!(feature = "grammar")),
+    ("mmap", cfg!(feature = "mmap")),
+    ("fetch", cfg!(feature = "fetch")),
+    ("clipboard", cfg!(feature = "clipboard")),
+    ("watch", cfg!(feature = "watch")),
+    ("pdf", cfg!(feature = "pdf")),
+    ("docx", cfg!(feature = "docx")),
+    ("age", cfg!(feature = "age")),
+    ("mqtt", cfg!(feature = "mqtt")),
+    ("neural", cfg!(feature = "neural")),
+    ("service", cfg!(feature = "service")),
+    ("grpc", cfg!(feature = "grpc")),
+    ("async", cfg!(feature = "async")),
+    ("embedded-models", cfg!(feature = "embedded-models")),
+    ("embedded-models-zstd", cfg!(feature = "embedded-models-zstd")),
+    ("embedded-wordlist", cfg!(feature = "embedded-wordlist")),
+    ("spellcheck", cfg!(feature = "spellcheck")),
+    ("wasm", cfg!(feature = "wasm")),
+    ("wasm-slim", cfg!(feature = "wasm-slim")),
+];
+
+/// The options this build accepts, each list in the order a menu would show it.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Capabilities {
+    /// Input the decoder reads: `text` and `html` always, `pdf` and `docx`
+    /// with their features
+    pub formats: Vec<&'static str>,
+    /// Cover text engines ([`ENGINES`])
+    pub engines: Vec<&'static str>,
+    /// Output styles, by their `--output-style` names
+    pub styles: Vec<&'static str>,
+    /// Payload wordlists ([`WORDLISTS`])
+    pub wordlists: Vec<&'static str>,
+    /// How `--from-ascii` input may be packed, by their `--data-mode` names
+    pub data_modes: Vec<&'static str>,
+    /// Languages with a bundled grammar and wordlist
+    pub languages: Vec<&'static str>,
+    /// Cargo features compiled in
+    pub features: Vec<&'static str>,
+}
+
+impl Capabilities {
+    /// The capabilities as a JSON object with one array per field.
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("capabilities serialize")
+    }
+}
+
+/// What this build of the crate supports.
+pub fn capabilities() -> Capabilities {
+    let features: Vec<&'static str> = FEATURES.iter().filter(|(_, on)| *on).map(|(name, _)| *name).collect();
+    let mut formats = vec!["text", "html"];
+    formats.extend(["pdf", "docx"].into_iter().filter(|format| features.contains(format)));
+    Capabilities {
+        formats,
+        engines: ENGINES.to_vec(),
+        styles: Style::ALL.iter().map(Style::name).collect(),
+        wordlists: WORDLISTS.to_vec(),
+        data_modes: DataMode::ALL.iter().map(DataMode::name).collect(),
+        languages: Language::ALL.iter().map(Language::name).collect(),
+        features,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capabilities() {
+        let caps = capabilities();
+        assert_eq!(caps.styles, vec!["prose", "list", "recipe"]);
+        for name in &caps.styles {
+            assert_eq!(Style::from_name(name).map(|style| style.name()), Some(*name));
+        }
+        for name in &caps.data_modes {
+            assert_eq!(DataMode::from_name(name).map(|mode| mode.name()), Some(*name));
+        }
+        assert!(caps.languages.contains(&"english"));
+        assert_eq!(caps.features.contains(&"grammar"), cfg!(feature = "grammar"));
+        assert_eq!(caps.formats.contains(&"pdf"), cfg!(feature = "pdf"));
+    }
+
+    #[test]
+    fn test_to_json() {
+        let json: serde_json::Value = serde_json::from_str(&capabilities().to_json()).unwrap();
+        for field in ["formats", "engines", "styles", "wordlists", "data_modes", "languages", "features"] {
+            assert!(json[field].is_array(), "{}", field);
+        }
+        assert_eq!(json["engines"], serde_json::json!(ENGINES));
+        assert_eq!(ENGINES[..2], ["prose", "emoji"]);
+        assert_eq!(ENGINES.contains(&"neural"), cfg!(feature = "neural"));
+    }
+}
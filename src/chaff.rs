@@ -0,0 +1,169 @@
+//! Noise sentences interleaved with carrier sentences.
+//!
+//! Chaff sentences are inserted between the sentences that carry the payload.
+//! Where they go is decided by a coin stream: with probability `fraction` the
+//! next output sentence is chaff, otherwise it is the next carrier sentence.
+//!
+//! Without a passphrase the coin stream is just random and chaff sentences
+//! contain only cover words, so ordinary decoding (and `--checksum`) ignores
+//! them. With a passphrase the stream is keyed and chaff sentences also carry
+//! random wordlist words: counting wordlist words then overstates the
+//! payload, and only a key holder knows which sentences to drop.
+//!
+//! A keyed text opens with sentences holding a per-message nonce of
+//! [`whiten::nonce_words`] random wordlist words. The nonce salts the
+//! passphrase, so two messages under one passphrase get unrelated layouts.
+
+use crate::decode;
+use crate::index::WordIndex;
+use crate::keys::{self, PassphraseKey};
+use crate::whiten;
+use anyhow::{bail, Result};
+use rand::Rng;
+use std::collections::HashSet;
+
+const POSITIONS_LABEL: &str = "chaff/positions";
+
+/// The coin stream for chaff positions under `passphrase`, salted by the
+/// message's `nonce` (wordlist indices).
+pub fn keyed_coins(passphrase: &str, nonce: &[usize]) -> impl Rng {
+    PassphraseKey::derive(passphrase, &keys::index_salt(nonce)).rng(POSITIONS_LABEL, b"")
+}
+
+/// A random nonce for a keyed text, as indices into a `wordlist_len`-word list.
+pub fn draw_nonce<R: Rng>(rng: &mut R, wordlist_len: usize) -> Vec<usize> {
+    (0..whiten::nonce_words(wordlist_len)).map(|_| rng.gen_range(0..wordlist_len)).collect()
+}
+
+/// The nonce a keyed text opens with, and the sentences after the ones that
+/// hold it. `wordlist` must be in canonical sorted order.
+pub fn split_nonce(text: &str, wordlist: &[String]) -> Result<(Vec<usize>, Vec<String>)> {
+    let index = WordIndex::shared(wordlist)?;
+    let set: HashSet<String> = wordlist.iter().cloned().collect();
+    let count = whiten::nonce_words(wordlist.len());
+    let mut sentences = split_sentences(text).into_iter();
+    let mut nonce = Vec::with_capacity(count);
+    while nonce.len() < count {
+        let Some(sentence) = sentences.next() else {
+            bail!("Too few words to hold a chaff nonce ({} needed)", count);
+        };
+        nonce.extend(decode::extract_payload_words(&sentence, &set).iter().filter_map(|w| index.get(&w.to_lowercase())));
+    }
+    if nonce.len() > count {
+        bail!("The chaff nonce sentences hold {} wordlist words, expected {}", nonce.len(), count);
+    }
+    Ok((nonce, sentences.collect()))
+}
+
+/// Interleave `carriers` carrier sentences with chaff. Returns one entry per
+/// output sentence, `true` for chaff. Chaff may also follow the last carrier.
+pub fn chaff_layout<R: Rng>(coins: &mut R, carriers: usize, fraction: f64) -> Vec<bool> {
+    let fraction = fraction.clamp(0.0, 0.95);
+    let mut layout = Vec::new();
+    let mut placed = 0;
+    loop {
+        let chaff = coins.gen_bool(fraction);
+        if !chaff {
+            if placed == carriers {
+                break;
+            }
+            placed += 1;
+        }
+        layout.push(chaff);
+    }
+    layout
+}
+
+/// Which of `total` sentences are chaff, replaying the coin stream used by
+/// [`chaff_layout`].
+pub fn chaff_mask<R: Rng>(coins: &mut R, total: usize, fraction: f64) -> Vec<bool> {
+    let fraction = fraction.clamp(0.0, 0.95);
+    (0..total).map(|_| coins.gen_bool(fraction)).collect()
+}
+
+/// Split text into sentences at tokens ending in `.`, `!` or `?`. Trailing
+/// words without terminal punctuation form a final sentence.
+pub fn split_sentences(text: &str) -> Vec<String> {
+    let mut sentences = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    for token in text.split_whitespace() {
+        current.push(token);
+        let end = crate::decode::strip_markup(token);
+        if end.ends_with(['.', '!', '?']) {
+            sentences.push(current.join(" "));
+            current.clear();
+        }
+    }
+    if !current.is_empty() {
+        sentences.push(current.join(" "));
+    }
+    sentences
+}
+
+/// Drop the nonce and chaff sentences of a keyed text, leaving the carrier
+/// sentences. `wordlist` must be in canonical sorted order.
+pub fn strip_chaff(text: &str, fraction: f64, passphrase: &str, wordlist: &[String]) -> Result<String> {
+    let (nonce, sentences) = split_nonce(text, wordlist)?;
+    let mask = chaff_mask(&mut keyed_coins(passphrase, &nonce), sentences.len(), fraction);
+    Ok(sentences
+        .into_iter()
+        .zip(mask)
+        .filter(|(_, chaff)| !chaff)
+        .map(|(s, _)| s)
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_layout_places_every_carrier_and_replays() {
+        let layout = chaff_layout(&mut keyed_coins("k", &[1, 2]), 10, 0.4);
+        assert_eq!(layout.iter().filter(|c| !**c).count(), 10);
+        assert!(layout.iter().any(|c| *c));
+        assert_eq!(chaff_mask(&mut keyed_coins("k", &[1, 2]), layout.len(), 0.4), layout);
+        assert_ne!(chaff_mask(&mut keyed_coins("k", &[1, 3]), 40, 0.4), chaff_mask(&mut keyed_coins("k", &[1, 2]), 40, 0.4));
+
+        let none = chaff_layout(&mut StdRng::seed_from_u64(0), 5, 0.0);
+        assert_eq!(none, vec![false; 5]);
+    }
+
+    #[test]
+    fn test_strip_chaff_keeps_carrier_sentences_in_order() {
+        let list = alpha_wordlist();
+        let nonce = draw_nonce(&mut StdRng::seed_from_u64(1), list.len());
+        let layout = chaff_layout(&mut keyed_coins("k", &nonce), 4, 0.5);
+        let mut carrier = 0;
+        let opening = format!("Words {}.", nonce.iter().map(|&i| list[i].as_str()).collect::<Vec<_>>().join(" "));
+        let text: Vec<String> = std::iter::once(opening)
+            .chain(layout.iter().map(|&chaff| {
+                if chaff {
+                    "Some noise here.".to_string()
+                } else {
+                    carrier += 1;
+                    format!("Carrier number |c{}|.", carrier)
+                }
+            }))
+            .collect();
+        let stripped = strip_chaff(&text.join(" "), 0.5, "k", &list).unwrap();
+        assert_eq!(
+            stripped,
+            "Carrier number |c1|. Carrier number |c2|. Carrier number |c3|. Carrier number |c4|."
+        );
+        assert_ne!(strip_chaff(&text.join(" "), 0.5, "x", &list).unwrap(), stripped);
+        assert!(strip_chaff("Carrier number one.", 0.5, "k", &list).is_err());
+    }
+
+    #[test]
+    fn test_split_sentences() {
+        assert_eq!(
+            split_sentences("One two. Three \x1b[1mfour\x1b[0m! Five"),
+            vec!["One two.", "Three \x1b[1mfour\x1b[0m!", "Five"]
+        );
+    }
+}
@@ -0,0 +1,522 @@
+//! Packing raw bytes into wordlist words and back.
+//!
+//! With a power-of-two wordlist (2048 words for BIP39) bytes are bit-packed,
+//! `log2(len)` bits per word, and the last word ends with a 1 bit followed by
+//! zero padding (as in ISO/IEC 7816-4), so the byte length is exact and a
+//! payload ending in `0x00` decodes whole. Other wordlist sizes fall back to
+//! one word per byte.
+//!
+//! An optional trailing checksum word ([`append_checksum`]) lets a decoder tell
+//! a correct recovery from a plausible-looking wrong one. [`decode_lossy`]
+//! keeps going past words it cannot read and uses the checksum to fill one in.
+
+use crate::error::GlossiaError;
+use crate::index::WordIndex;
+use crate::secret::Wipe;
+use anyhow::{bail, Result};
+use std::ops::Range;
+
+/// Bits carried per word, or `None` if the wordlist size isn't a power of two.
+pub fn bits_per_word(wordlist_len: usize) -> Option<usize> {
+    if wordlist_len >= 2 && wordlist_len.is_power_of_two() {
+        Some(wordlist_len.trailing_zeros() as usize)
+    } else {
+        None
+    }
+}
+
+/// Encode bytes as words from `wordlist` (which must be in canonical sorted order).
+pub fn encode_bytes(bytes: &[u8], wordlist: &[String]) -> Result<Vec<String>> {
+    if wordlist.is_empty() {
+        bail!("Wordlist is empty");
+    }
+    let word_count = wordlist.len();
+
+    let Some(bits_per_word) = bits_per_word(word_count) else {
+        // Simple encoding: 1 byte per word
+        return Ok(bytes
+            .iter()
+            .map(|&byte| wordlist[byte as usize % word_count].clone())
+            .collect());
+    };
+
+    let mut words = Vec::new();
+    let mut bit_buffer: u32 = 0;
+    let mut bits_in_buffer = 0;
+
+    for &byte in bytes {
+        bit_buffer = (bit_buffer << 8) | (byte as u32);
+        bits_in_buffer += 8;
+
+        while bits_in_buffer >= bits_per_word {
+            let shift = bits_in_buffer - bits_per_word;
+            let word_index = ((bit_buffer >> shift) & ((1u32 << bits_per_word) - 1)) as usize;
+            words.push(wordlist[word_index].clone());
+            bit_buffer &= (1u32 << shift) - 1;
+            bits_in_buffer -= bits_per_word;
+        }
+    }
+
+    // End marker: a 1 bit, then zeros to the end of the word
+    bit_buffer = (bit_buffer << 1) | 1;
+    bits_in_buffer += 1;
+    bit_buffer <<= bits_per_word - bits_in_buffer;
+    words.push(wordlist[bit_buffer as usize].clone());
+
+    Ok(words)
+}
+
+/// Number of words [`encode_bytes`] packs `byte_len` bytes into.
+pub fn packed_words(byte_len: usize, wordlist_len: usize) -> usize {
+    match bits_per_word(wordlist_len) {
+        // The end marker takes at least one bit
+        Some(bits) => 8 * byte_len / bits + 1,
+        None => byte_len,
+    }
+}
+
+/// Decode words produced by [`encode_bytes`] back into bytes. Fails if the
+/// last word holds no end marker, as when the text was cut short.
+pub fn decode_bytes(words: &[String], wordlist: &[String]) -> Result<Vec<u8>> {
+    let index = WordIndex::shared(wordlist)?;
+    let mut bytes = Vec::new();
+    let mut unpacker = Unpacker::new(&mut bytes, wordlist.len());
+    for (position, w) in words.iter().enumerate() {
+        let found = index.get(&w.to_lowercase());
+        let pushed = match found {
+            Some(i) => unpacker.push(i),
+            None => Err(unknown_word(w, position).into()),
+        };
+        if let Err(e) = pushed {
+            bytes.wipe();
+            return Err(e);
+        }
+    }
+    if let Err(e) = unpacker.finish() {
+        bytes.wipe();
+        return Err(e);
+    }
+    Ok(bytes)
+}
+
+/// Decode word indices (positions in `wordlist`) like [`decode_bytes`]
+/// decodes the words at them. Unpacking takes the same steps whatever the
+/// indices are, so with indices from [`WordIndex::get_constant_time`] the
+/// whole decode does.
+pub fn decode_indices(indices: &[usize], wordlist_len: usize) -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut unpacker = Unpacker::new(&mut bytes, wordlist_len);
+    for &i in indices {
+        if i >= wordlist_len {
+            bytes.wipe();
+            bail!("Word index {} is past the end of the wordlist", i);
+        }
+        if let Err(e) = unpacker.push(i) {
+            bytes.wipe();
+            return Err(e);
+        }
+    }
+    if let Err(e) = unpacker.finish() {
+        bytes.wipe();
+        return Err(e);
+    }
+    Ok(bytes)
+}
+
+/// What [`decode_lossy`] could recover
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LossyDecode {
+    /// The payload, with the bits of unreadable words as zeros
+    pub bytes: Vec<u8>,
+    /// Ranges of `bytes` that could not be recovered, in order
+    pub errors: Vec<Range<usize>>,
+    /// Unreadable words (counting from 0) the checksum word filled in
+    pub restored: Vec<usize>,
+}
+
+/// Decode words like [`decode_bytes`], but take a word missing from the
+/// wordlist as an erasure rather than failing: its bits are zeros and the
+/// bytes they fall in are reported as errors. An erased last word takes the
+/// end marker with it, so the payload is taken to fill every whole byte the
+/// words hold.
+///
+/// With `checksum`, the last word is an [`append_checksum`] word. A single
+/// erasure is filled in when exactly one wordlist word makes the checksum
+/// match; with nothing erased, a checksum that does not match marks the whole
+/// payload as an error.
+pub fn decode_lossy(words: &[String], wordlist: &[String], checksum: bool) -> Result<LossyDecode> {
+    let index = WordIndex::shared(wordlist)?;
+    // `None` marks an erasure
+    let mut indices: Vec<Option<usize>> = words.iter().map(|w| index.get(&w.to_lowercase())).collect();
+    let mut restored = Vec::new();
+    let mut unverified = false;
+    if checksum {
+        let Some(check) = indices.pop() else {
+            bail!("No words to verify");
+        };
+        let erased: Vec<usize> = (0..indices.len()).filter(|&i| indices[i].is_none()).collect();
+        let mut trial: Vec<usize> = indices.iter().map(|i| i.unwrap_or(0)).collect();
+        match (check, erased.as_slice()) {
+            (Some(check), []) => unverified = checksum_index(&trial, wordlist.len()) != check,
+            (Some(check), &[position]) => {
+                let fits: Vec<usize> = (0..wordlist.len())
+                    .filter(|&i| {
+                        trial[position] = i;
+                        checksum_index(&trial, wordlist.len()) == check
+                    })
+                    .collect();
+                if let [i] = fits[..] {
+                    indices[position] = Some(i);
+                    restored.push(position);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut bytes = Vec::new();
+    let mut unpacker = Unpacker::new(&mut bytes, wordlist.len());
+    for i in &indices {
+        if let Err(e) = unpacker.push(i.unwrap_or(0)) {
+            bytes.wipe();
+            return Err(e);
+        }
+    }
+    let finished = match indices.last() {
+        Some(None) => unpacker.finish_unmarked(),
+        _ => unpacker.finish(),
+    };
+    if let Err(e) = finished {
+        bytes.wipe();
+        return Err(e);
+    }
+
+    let mut errors: Vec<Range<usize>> = Vec::new();
+    let erasures = indices.iter().enumerate().filter(|(_, i)| i.is_none()).map(|(position, _)| position);
+    for position in erasures {
+        let span = match bits_per_word(wordlist.len()) {
+            Some(bits) => position * bits / 8..((position + 1) * bits).div_ceil(8),
+            None => position..position + 1,
+        };
+        let span = span.start.min(bytes.len())..span.end.min(bytes.len());
+        match errors.last_mut() {
+            Some(last) if last.end >= span.start => last.end = last.end.max(span.end),
+            _ if !span.is_empty() => errors.push(span),
+            _ => {}
+        }
+    }
+    if unverified && !bytes.is_empty() {
+        errors.clear();
+        errors.push(0..bytes.len());
+    }
+    Ok(LossyDecode { bytes, errors, restored })
+}
+
+/// Decode the payload words of cover `text` straight into `out`, without
+/// collecting the words first. Returns the number of payload words read.
+///
+/// Equivalent to [`crate::decode::extract_payload_words`] followed by
+/// [`decode_bytes`], appending to `out` rather than returning a new buffer.
+pub fn decode_text_into(text: &str, wordlist: &[String], out: &mut Vec<u8>) -> Result<usize> {
+    let index = WordIndex::shared(wordlist)?;
+    let start = out.len();
+    let mut unpacker = Unpacker::new(out, wordlist.len());
+    for i in crate::decode::payload_indices(text, &index) {
+        if let Err(e) = unpacker.push(i) {
+            out[start..].wipe();
+            out.truncate(start);
+            return Err(e);
+        }
+    }
+    let finished = unpacker.finish();
+    if finished.is_err() {
+        out[start..].wipe();
+        out.truncate(start);
+    }
+    finished
+}
+
+/// Unpacks word indices into bytes as they arrive, mirroring [`encode_bytes`].
+struct Unpacker<'a> {
+    out: &'a mut Vec<u8>,
+    start: usize,
+    bits_per_word: Option<usize>,
+    bit_buffer: u32,
+    bits_in_buffer: usize,
+    words: usize,
+    /// The last index pushed, which holds the end marker
+    last: usize,
+}
+
+impl<'a> Unpacker<'a> {
+    fn new(out: &'a mut Vec<u8>, wordlist_len: usize) -> Self {
+        let start = out.len();
+        Self { out, start, bits_per_word: bits_per_word(wordlist_len), bit_buffer: 0, bits_in_buffer: 0, words: 0, last: 0 }
+    }
+
+    fn push(&mut self, index: usize) -> Result<()> {
+        self.words += 1;
+        self.last = index;
+        let Some(bits_per_word) = self.bits_per_word else {
+            let byte = u8::try_from(index).map_err(|_| anyhow::anyhow!("Word index {} is not a byte", index))?;
+            self.out.push(byte);
+            return Ok(());
+        };
+        self.bit_buffer = (self.bit_buffer << bits_per_word) | index as u32;
+        self.bits_in_buffer += bits_per_word;
+        while self.bits_in_buffer >= 8 {
+            let shift = self.bits_in_buffer - 8;
+            self.out.push((self.bit_buffer >> shift) as u8);
+            self.bit_buffer &= (1u32 << shift) - 1;
+            self.bits_in_buffer -= 8;
+        }
+        Ok(())
+    }
+
+    /// Drop the end marker and padding (see [`encode_bytes`]) and return the
+    /// word count.
+    fn finish(self) -> Result<usize> {
+        let Some(bits_per_word) = self.bits_per_word else {
+            return Ok(self.words);
+        };
+        if self.words == 0 {
+            return Ok(0);
+        }
+        // The marker is the lowest 1 bit of the last word
+        if self.last == 0 {
+            bail!("The last payload word holds no end marker: the text is cut short or not a payload");
+        }
+        let data_bits = self.words * bits_per_word - self.last.trailing_zeros() as usize - 1;
+        if !data_bits.is_multiple_of(8) {
+            bail!("The payload words end partway through a byte: the text is cut short or not a payload");
+        }
+        Ok(self.truncate(data_bits / 8))
+    }
+
+    /// Like [`Unpacker::finish`] when the last word is unknown: keep every
+    /// whole byte in front of the smallest possible marker.
+    fn finish_unmarked(self) -> Result<usize> {
+        match self.bits_per_word {
+            Some(bits_per_word) if self.words > 0 => {
+                let len = (self.words * bits_per_word - 1) / 8;
+                Ok(self.truncate(len))
+            }
+            _ => Ok(self.words),
+        }
+    }
+
+    fn truncate(self, len: usize) -> usize {
+        let end = self.start + len;
+        self.out[end..].wipe();
+        self.out.truncate(end);
+        self.words
+    }
+}
+
+/// CRC-32 (IEEE) of `data`.
+pub fn crc32(data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFFu32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+/// Checksum word index for a sequence of word indices: CRC-32 of the indices
+/// (as little-endian u16s), reduced to the wordlist size.
+pub fn checksum_index(indices: &[usize], wordlist_len: usize) -> usize {
+    let data: Vec<u8> = indices
+        .iter()
+        .flat_map(|&i| (i as u16).to_le_bytes())
+        .collect();
+    crc32(&data) as usize % wordlist_len
+}
+
+fn word_indices(words: &[String], wordlist: &[String]) -> Result<Vec<usize>> {
+    let index = WordIndex::shared(wordlist)?;
+    words
+        .iter()
+        .enumerate()
+        .map(|(position, w)| index.get(&w.to_lowercase()).ok_or_else(|| unknown_word(w, position).into()))
+        .collect()
+}
+
+/// The error for `word` at (0-based) `position` missing from the wordlist.
+pub(crate) fn unknown_word(word: &str, position: usize) -> GlossiaError {
+    GlossiaError::UnknownWord { word: word.to_string(), position: position + 1 }
+}
+
+/// Append a checksum word covering `words`.
+pub fn append_checksum(words: &[String], wordlist: &[String]) -> Result<Vec<String>> {
+    let indices = word_indices(words, wordlist)?;
+    let mut out = words.to_vec();
+    out.push(wordlist[checksum_index(&indices, wordlist.len())].clone());
+    Ok(out)
+}
+
+/// Verify and remove the trailing checksum word added by [`append_checksum`].
+pub fn strip_checksum(words: &[String], wordlist: &[String]) -> Result<Vec<String>> {
+    let Some((last, body)) = words.split_last() else {
+        bail!("No words to verify");
+    };
+    let indices = word_indices(words, wordlist)?;
+    let expected = checksum_index(&indices[..body.len()], wordlist.len());
+    if indices[body.len()] != expected {
+        return Err(GlossiaError::ChecksumMismatch {
+            expected: wordlist[expected].clone(),
+            found: last.clone(),
+        }
+        .into());
+    }
+    Ok(body.to_vec())
+}
+
+/// Like [`strip_checksum`], on word indices: the indices before a trailing
+/// checksum index that matches them.
+pub fn strip_checksum_indices<'a>(indices: &'a [usize], wordlist: &[String]) -> Result<&'a [usize]> {
+    let Some((&last, body)) = indices.split_last() else {
+        bail!("No words to verify");
+    };
+    let expected = checksum_index(body, wordlist.len());
+    if last != expected {
+        return Err(GlossiaError::ChecksumMismatch {
+            expected: wordlist[expected].clone(),
+            found: wordlist.get(last).cloned().unwrap_or_default(),
+        }
+        .into());
+    }
+    Ok(body)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+
+    /// The first `len` words of [`alpha_wordlist`]
+    fn wordlist(len: usize) -> Vec<String> {
+        alpha_wordlist()[..len].to_vec()
+    }
+
+    #[test]
+    fn test_roundtrip_power_of_two() {
+        let list = wordlist(2048);
+        for text in ["", "H", "Hi", "Hey", "Hello World", "The quick brown fox jumps."] {
+            let words = encode_bytes(text.as_bytes(), &list).unwrap();
+            assert_eq!(words.len(), packed_words(text.len(), list.len()));
+            let bytes = decode_bytes(&words, &list).unwrap();
+            assert_eq!(bytes, text.as_bytes(), "roundtrip of {:?}", text);
+        }
+    }
+
+    #[test]
+    fn test_trailing_zero_bytes_roundtrip() {
+        let list = wordlist(2048);
+        let mut payloads: Vec<Vec<u8>> = vec![vec![0x41, 0x42, 0x43, 0x00], vec![0; 4]];
+        // Every length, so the marker lands at every offset within a word
+        payloads.extend((0..=22).map(|len| vec![0; len]));
+        payloads.extend((1..=22).map(|len| (1..=len as u8).chain([0, 0, 0]).collect()));
+        for bytes in payloads {
+            let words = encode_bytes(&bytes, &list).unwrap();
+            assert_eq!(decode_bytes(&words, &list).unwrap(), bytes);
+        }
+        // Without its marker word, a cut-short text is refused
+        let words = encode_bytes(b"Hello World", &list).unwrap();
+        assert!(decode_bytes(&words[..words.len() - 1], &list).is_err());
+    }
+
+    #[test]
+    fn test_roundtrip_byte_per_word() {
+        let list = wordlist(300);
+        let words = encode_bytes(b"abc", &list).unwrap();
+        assert_eq!(words.len(), 3);
+        assert_eq!(decode_bytes(&words, &list).unwrap(), b"abc");
+    }
+
+    #[test]
+    fn test_decode_text_into_matches_word_decoding() {
+        let list = alpha_wordlist();
+        let words = encode_bytes(b"Hello World", &list).unwrap();
+        let text = words
+            .iter()
+            .map(|w| format!("The |{}| said,", w.to_uppercase()))
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        let mut out = b"prefix:".to_vec();
+        assert_eq!(decode_text_into(&text, &list, &mut out).unwrap(), words.len());
+        assert_eq!(out, b"prefix:Hello World");
+
+        let short: Vec<String> = list[..300].to_vec();
+        let mut out = Vec::new();
+        let text = format!("{} {}", list[1], list[299]);
+        assert!(decode_text_into(&text, &short, &mut out).is_err());
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_checksum_roundtrip_and_detection() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+
+        let list = wordlist(2048);
+        let words = encode_bytes(b"Hello", &list).unwrap();
+        let framed = append_checksum(&words, &list).unwrap();
+        assert_eq!(framed.len(), words.len() + 1);
+        assert_eq!(strip_checksum(&framed, &list).unwrap(), words);
+
+        let mut corrupted = framed.clone();
+        corrupted[0] = if corrupted[0] == list[0] { list[1].clone() } else { list[0].clone() };
+        assert!(strip_checksum(&corrupted, &list).is_err());
+        assert!(strip_checksum(&[], &list).is_err());
+
+        let index = WordIndex::new(&list).unwrap();
+        let indices: Vec<usize> = framed.iter().filter_map(|w| index.get_constant_time(w)).collect();
+        let body = strip_checksum_indices(&indices, &list).unwrap();
+        assert_eq!(decode_indices(body, list.len()).unwrap(), b"Hello");
+        assert!(strip_checksum_indices(&indices[1..], &list).is_err());
+        assert!(decode_indices(&[2048], list.len()).is_err());
+    }
+
+    #[test]
+    fn test_decode_lossy_continues_past_erasures() {
+        let list = wordlist(2048);
+        let text = b"The quick brown fox jumps.";
+        let words = encode_bytes(text, &list).unwrap();
+        let mut smudged = words.clone();
+        smudged[3] = "smudge".to_string();
+        smudged[10] = "?".to_string();
+
+        // Word 3 holds bits 33..44, word 10 bits 110..121
+        let lossy = decode_lossy(&smudged, &list, false).unwrap();
+        assert_eq!(lossy.errors, [4..6, 13..16]);
+        assert_eq!(lossy.bytes.len(), text.len());
+        assert_eq!((&lossy.bytes[..4], &lossy.bytes[6..13], &lossy.bytes[16..]), (&text[..4], &text[6..13], &text[16..]));
+
+        // The checksum word fills in one erasure where a single word fits it
+        // (at word 3, two words do), and never two
+        let mut framed = append_checksum(&words, &list).unwrap();
+        framed[6] = "smudge".to_string();
+        let lossy = decode_lossy(&framed, &list, true).unwrap();
+        assert_eq!((lossy.bytes.as_slice(), lossy.errors.as_slice(), lossy.restored.as_slice()), (&text[..], &[][..], &[6][..]));
+        framed[3] = "?".to_string();
+        assert_eq!(decode_lossy(&framed, &list, true).unwrap().errors, [4..6, 8..10]);
+        let mut ambiguous = append_checksum(&words, &list).unwrap();
+        ambiguous[3] = "?".to_string();
+        assert!(decode_lossy(&ambiguous, &list, true).unwrap().restored.is_empty());
+
+        // A wrong word with nothing erased fails the whole payload
+        let mut wrong = append_checksum(&words, &list).unwrap();
+        wrong[0] = if wrong[0] == list[0] { list[1].clone() } else { list[0].clone() };
+        assert_eq!(decode_lossy(&wrong, &list, true).unwrap().errors, vec![0..text.len(); 1]);
+    }
+
+    #[test]
+    fn test_decode_rejects_unknown_word() {
+        let list = wordlist(2048);
+        assert!(decode_bytes(&["nope".to_string()], &list).is_err());
+    }
+}
@@ -0,0 +1,189 @@
+//! User defaults shared by every front end.
+//!
+//! A [`GlossiaConfig`] is read from TOML, by default
+//! `~/.config/glossia/config.toml` ([`GlossiaConfig::default_path`]). The CLI
+//! applies it before its own flags, which override it; the bindings and the
+//! WASM exports build one from the same TOML, so a setting means the same
+//! thing everywhere. Every key is optional:
+//!
+//! ```toml
+//! grammar = "body"        # subject | body
+//! mode = "auto"           # auto | text | binary
+//! engine = "prose"        # see capabilities::ENGINES
+//! style = "prose"         # prose | list | recipe
+//! wordlist = "bip39"      # see capabilities::WORDLISTS
+//! language = "english"
+//! seed = 42               # fixed seed for repeatable output; random if unset
+//! fec = "checksum"        # none | checksum | parity
+//! model_dir = "/opt/nlprule-data"
+//! rule_files = ["house_style_rules.bin"]  # extra nlprule rules, compiled (not XML)
+//! footer = "Written with glossia; decode it with: {command}"  # how-to-decode note, off if unset
+//!
+//! [rules]                 # which grammar rules to apply (see GrammarCheckerOptions)
+//! enabled_categories = ["grammar", "typos"]
+//! disabled_ids = ["typography/oxford_comma"]
+//! ```
+
+use crate::capabilities::{ENGINES, WORDLISTS};
+use crate::payload::PayloadOptions;
+use crate::style::Style;
+use crate::GrammarCheckerOptions;
+#[cfg(feature = "grammar")]
+use crate::{GrammarChecker, Language};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// How much error protection encoding adds. Glossia has no correcting code:
+/// the levels detect damage, and `parity` also names the damaged sentences.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FecLevel {
+    /// No check words
+    #[default]
+    None,
+    /// A trailing checksum word (`--checksum`)
+    Checksum,
+    /// A checksum word and per-sentence parity markers (`--checksum --parity`)
+    Parity,
+}
+
+/// Defaults for the encode and decode options. Unset keys leave each front
+/// end's own default in place.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GlossiaConfig {
+    /// Sentence grammar, `subject` or `body` (`--grammar`)
+    pub grammar: Option<String>,
+    /// Payload handling, `auto`, `text` or `binary` (`--mode`)
+    pub mode: Option<String>,
+    /// Cover text engine (`--engine`)
+    pub engine: Option<String>,
+    /// Output style (`--output-style`)
+    pub style: Option<String>,
+    /// Payload wordlist (`--wordlist`)
+    pub wordlist: Option<String>,
+    /// Language directory under `languages/` (`--language`)
+    pub language: Option<String>,
+    /// Seed for repeatable output (`--seed`); a fresh random seed if unset
+    pub seed: Option<u64>,
+    /// Error protection added when encoding
+    pub fec: FecLevel,
+    /// Directory holding the nlprule tokenizer and rules binaries
+    pub model_dir: Option<PathBuf>,
+    /// Compiled nlprule rules binaries loaded on top of the model's rules.
+    /// LanguageTool rule XML must be compiled with `nlprule-build` first; an
+    /// XML file here is an error ([`GrammarChecker::add_rules`]).
+    pub rule_files: Vec<PathBuf>,
+    /// Grammar rules the checker applies
+    pub rules: GrammarCheckerOptions,
+    /// Template of a how-to-decode footer added to every text
+    /// (`--footer-template`; see [`footer`](crate::footer))
+    pub footer: Option<String>,
+}
+
+impl GlossiaConfig {
+    /// Parse and check a TOML config.
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let config: Self = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Read a TOML config file.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::from_toml(&text).with_context(|| format!("Invalid config {}", path.display()))
+    }
+
+    /// `$XDG_CONFIG_HOME/glossia/config.toml`, falling back to
+    /// `~/.config/glossia/config.toml`; `None` without either variable.
+    pub fn default_path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME").filter(|dir| !dir.is_empty()) {
+            Some(dir) => PathBuf::from(dir),
+            None => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("glossia").join("config.toml"))
+    }
+
+    /// The config at [`GlossiaConfig::default_path`], or the defaults if
+    /// there is no file there.
+    pub fn load_default() -> Result<Self> {
+        match Self::default_path().filter(|path| path.exists()) {
+            Some(path) => Self::from_file(path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    /// Reject values no front end accepts, naming the key.
+    pub fn validate(&self) -> Result<()> {
+        let styles: Vec<&str> = Style::ALL.iter().map(Style::name).collect();
+        let checks: [(&str, &Option<String>, &[&str]); 5] = [
+            ("grammar", &self.grammar, &["subject", "body"]),
+            ("mode", &self.mode, &["auto", "text", "binary"]),
+            ("engine", &self.engine, ENGINES),
+            ("style", &self.style, &styles),
+            ("wordlist", &self.wordlist, WORDLISTS),
+        ];
+        for (key, value, allowed) in checks {
+            if let Some(value) = value {
+                if !allowed.contains(&value.as_str()) {
+                    bail!("{} must be one of {}, not '{}'", key, allowed.join(", "), value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The payload framing the config asks for, for the bindings and services.
+    pub fn payload_options(&self) -> PayloadOptions {
+        PayloadOptions { checksum: self.fec != FecLevel::None, ..Default::default() }
+    }
+
+    /// The grammar checker for `language`, from `model_dir` if set, otherwise
+    /// from the default search locations ([`GrammarChecker::from_language`]),
+    /// with the `rule_files` added and only the `rules` the config selects applied.
+    /// [`GrammarChecker::global_with`] shares one such checker per process.
+    #[cfg(feature = "grammar")]
+    pub fn grammar_checker(&self, language: Language) -> Result<GrammarChecker> {
+        let mut checker = match &self.model_dir {
+            Some(dir) => GrammarChecker::from_dir(dir, language),
+            None => GrammarChecker::from_language(language),
+        }?;
+        for path in &self.rule_files {
+            checker.add_rules_file(path)?;
+        }
+        checker.with_options(&self.rules).context("Invalid [rules] in config")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_toml() {
+        let config = GlossiaConfig::from_toml("grammar = \"body\"\nseed = 7\nfec = \"parity\"\nmodel_dir = \"models\"\nrule_files = [\"house.bin\"]\n").unwrap();
+        assert_eq!(config.grammar.as_deref(), Some("body"));
+        assert_eq!(config.seed, Some(7));
+        assert_eq!(config.fec, FecLevel::Parity);
+        assert_eq!(config.model_dir, Some(PathBuf::from("models")));
+        assert_eq!(config.rule_files, vec![PathBuf::from("house.bin")]);
+        assert!(config.payload_options().checksum);
+        assert_eq!(GlossiaConfig::from_toml("").unwrap(), GlossiaConfig::default());
+
+        let config = GlossiaConfig::from_toml("[rules]\nenabled_categories = [\"grammar\"]\ndisabled_ids = [\"grammar/a_an\"]\n").unwrap();
+        assert_eq!(config.rules.enabled_categories, Some(vec!["grammar".to_string()]));
+        assert_eq!(config.rules.disabled_ids, vec!["grammar/a_an"]);
+    }
+
+    #[test]
+    fn test_rejects_unknown_values() {
+        let err = GlossiaConfig::from_toml("style = \"poem\"").unwrap_err();
+        assert!(err.to_string().contains("style must be one of prose, list, recipe"), "{}", err);
+        assert!(GlossiaConfig::from_toml("colour = \"red\"").is_err());
+        assert!(GlossiaConfig::from_toml("fec = \"reed-solomon\"").is_err());
+        assert!(GlossiaConfig::from_toml("[rules]\nstyle = false").is_err());
+    }
+}
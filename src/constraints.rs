@@ -0,0 +1,222 @@
+//! Checking a message against what a platform does to it, before it is posted.
+//!
+//! [`transport`](crate::transport) adapts cover text to a platform; this
+//! module says what is wrong with a text that wasn't adapted (a reply typed
+//! around the cover text, a profile with other limits) or was adapted for
+//! another platform. [`validate_for`] lists every [`Violation`]: a message
+//! over the platform's length, characters its clients drop or garble (Nostr
+//! clients that escape quotes twice), Markdown that would be rendered, and
+//! text that would be turned into a link and previewed. Each one says where
+//! it is and what to do about it.
+//!
+//! ```
+//! use glossia::constraints::validate_for;
+//! use glossia::transport::{encode_for_platform, Platform};
+//!
+//! let text = "The *plan* is on example.com.";
+//! assert_eq!(validate_for(text, Platform::Matrix).len(), 3);
+//! for message in encode_for_platform(text, Platform::Matrix) {
+//!     assert!(validate_for(&message, Platform::Matrix).is_empty());
+//! }
+//! ```
+
+use crate::transport::{self, Platform, Profile, INLINE_MARKDOWN, LINK_BREAK};
+use std::fmt;
+
+/// Characters some Nostr clients escape twice in the event JSON, or fold
+const NOSTR_QUOTES: &[char] = &['"', '\\', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}'];
+
+/// One way a message would not arrive as written. Offsets are in bytes, into
+/// the text as given (the unquoted text, for a TXT record).
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The text is longer than one message; [`transport::adapt`] would split
+    /// it into `messages`
+    TooLong { chars: usize, bytes: usize, limit: String, messages: usize },
+    /// A character the platform's clients drop or garble
+    Forbidden { at: usize, character: char, reason: &'static str },
+    /// Text a Markdown-rendering client would format
+    Markdown { at: usize, text: String },
+    /// Text a client would link and fetch a preview of
+    Link { at: usize, text: String },
+}
+
+impl Violation {
+    /// Short stable identifier, as [`GlossiaError::code`](crate::error::GlossiaError::code) has
+    pub fn code(&self) -> &'static str {
+        match self {
+            Violation::TooLong { .. } => "too_long",
+            Violation::Forbidden { .. } => "forbidden_character",
+            Violation::Markdown { .. } => "markdown",
+            Violation::Link { .. } => "link",
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::TooLong { chars, bytes, limit, messages } => write!(
+                f,
+                "{} characters ({} bytes) is over the {} a message holds: send it as the {} messages encode_for_platform splits it into",
+                chars, bytes, limit, messages
+            ),
+            Violation::Forbidden { at, character, reason } => {
+                write!(f, "{:?} at byte {}: {}; remove or replace it", character, at, reason)
+            }
+            Violation::Markdown { at, text } => {
+                write!(f, "'{}' at byte {} would be rendered as Markdown: escape it with a backslash", text, at)
+            }
+            Violation::Link { at, text } => write!(
+                f,
+                "'{}' at byte {} would be linked and previewed: reword it, or put a zero-width space after its dot",
+                text, at
+            ),
+        }
+    }
+}
+
+/// What is wrong with posting `text` as one message to `platform`, in order
+/// of position (length first). Empty if it would arrive as written.
+pub fn validate_for(text: &str, platform: Platform) -> Vec<Violation> {
+    let forbidden: &[char] = match platform {
+        Platform::Nostr => NOSTR_QUOTES,
+        _ => &[],
+    };
+    validate(text, &platform.profile(), |c| {
+        forbidden.contains(&c).then_some("some Nostr clients escape quotes and backslashes twice or fold curly quotes")
+    })
+}
+
+/// [`validate_for`] against any `profile`, with `forbidden` giving the reason a
+/// character is garbled (control characters other than line breaks and tabs
+/// always are).
+pub fn validate(text: &str, profile: &Profile, forbidden: impl Fn(char) -> Option<&'static str>) -> Vec<Violation> {
+    let unquoted;
+    let text = if profile.txt_record && transport::is_txt_record(text) {
+        match transport::from_txt_record(text) {
+            Ok(inner) => {
+                unquoted = inner;
+                &unquoted
+            }
+            Err(_) => text,
+        }
+    } else {
+        text
+    };
+
+    let mut out = Vec::new();
+    let (chars, bytes) = (text.chars().count(), text.len());
+    if chars > profile.max_chars || bytes > profile.max_bytes_per_message {
+        let limit = if chars > profile.max_chars {
+            format!("{} characters", profile.max_chars)
+        } else {
+            format!("{} bytes", profile.max_bytes_per_message)
+        };
+        out.push(Violation::TooLong { chars, bytes, limit, messages: transport::adapt(text, profile).len() });
+    }
+
+    let mut found: Vec<Violation> = text
+        .char_indices()
+        .filter_map(|(at, character)| {
+            let reason = if character.is_control() && !matches!(character, '\n' | '\r' | '\t') {
+                Some("clients drop or refuse control characters")
+            } else {
+                forbidden(character)
+            };
+            reason.map(|reason| Violation::Forbidden { at, character, reason })
+        })
+        .collect();
+    if profile.markdown {
+        found.extend(markdown(text));
+    }
+    if profile.unfurls_links {
+        found.extend(
+            transport::link_re()
+                .find_iter(text)
+                .filter(|link| !text[link.end()..].starts_with(LINK_BREAK))
+                .map(|link| Violation::Link { at: link.start(), text: link.as_str().to_string() }),
+        );
+    }
+    found.sort_by_key(|violation| match violation {
+        Violation::Forbidden { at, .. } | Violation::Markdown { at, .. } | Violation::Link { at, .. } => *at,
+        Violation::TooLong { .. } => 0,
+    });
+    out.extend(found);
+    out
+}
+
+/// Unescaped inline Markdown, and line starts that make a heading, quote or
+/// list, as `transport` escapes them.
+fn markdown(text: &str) -> Vec<Violation> {
+    let mut out = Vec::new();
+    let mut line_start = 0;
+    for line in text.split('\n') {
+        let indent = line.len() - line.trim_start().len();
+        let body = &line[indent..];
+        let digits = body.bytes().take_while(u8::is_ascii_digit).count();
+        let marker = if body.starts_with('#') {
+            Some(1)
+        } else if body.starts_with("- ") || body.starts_with("+ ") {
+            Some(2)
+        } else if digits > 0 && (body[digits..].starts_with(". ") || body[digits..].starts_with(") ")) {
+            Some(digits + 2)
+        } else {
+            None
+        };
+        let mut inline_from = 0;
+        if let Some(len) = marker {
+            out.push(Violation::Markdown { at: line_start + indent, text: body[..len].to_string() });
+            inline_from = if body.starts_with('#') { 1 } else { len };
+        }
+        let mut chars = body.char_indices().skip_while(|&(i, _)| i < inline_from).peekable();
+        while let Some((i, c)) = chars.next() {
+            // Escapes, including those of line starts (`\#`, `1\.`)
+            if c == '\\' && chars.peek().is_some_and(|&(_, next)| INLINE_MARKDOWN.contains(&next) || "#-+.)".contains(next)) {
+                chars.next();
+            } else if INLINE_MARKDOWN.contains(&c) {
+                out.push(Violation::Markdown { at: line_start + indent + i, text: c.to_string() });
+            }
+        }
+        line_start += line.len() + 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COVER: &str = "The plan is set and the lake is calm. A boat will wait near the old bridge.";
+
+    #[test]
+    fn test_adapted_messages_pass() {
+        let text = format!("# Re: lunch\n1. the *cheap* one_two\n- see example.com or https://x.org/a\n{}", COVER.repeat(100));
+        for &platform in Platform::ALL {
+            for message in transport::encode_for_platform(&text, platform) {
+                assert_eq!(validate_for(&message, platform), [], "{:?}: {}", platform, message);
+            }
+        }
+    }
+
+    #[test]
+    fn test_reports_each_violation() {
+        let text = "# Hi\nSee *this* at example.com\u{7} now";
+        let codes = |platform| validate_for(text, platform).iter().map(Violation::code).collect::<Vec<_>>();
+        assert_eq!(codes(Platform::Matrix), ["markdown", "markdown", "markdown", "link", "forbidden_character"]);
+        assert_eq!(codes(Platform::Mesh), ["forbidden_character"]);
+        let matrix = validate_for(text, Platform::Matrix);
+        assert_eq!(matrix[3], Violation::Link { at: 19, text: "example.com".to_string() });
+        assert!(matrix[0].to_string().contains("'#' at byte 0"), "{}", matrix[0]);
+
+        let nostr = validate_for("She said \u{201C}hi\u{201D} and \"bye\"", Platform::Nostr);
+        assert_eq!(nostr.iter().map(|v| v.code()).collect::<Vec<_>>(), ["forbidden_character"; 4]);
+        assert!(validate_for("She said \"bye\"", Platform::Signal).is_empty());
+
+        let long = format!("{} ", COVER).repeat(40);
+        let signal = validate_for(&long, Platform::Signal);
+        assert!(matches!(&signal[..], [Violation::TooLong { chars: 3040, messages: 2, .. }]), "{:?}", signal);
+        assert!(signal[0].to_string().contains("over the 2000 characters"));
+        assert!(validate_for(COVER, Platform::Mesh).is_empty());
+    }
+}
@@ -0,0 +1,106 @@
+//! Several named payloads carried as one.
+//!
+//! A recovery kit is rarely a single secret: an `nsec`, a page of backup
+//! codes, a note saying where the rest is kept. [`pack_container`] puts them
+//! in one payload behind a small table of contents (a version byte, the
+//! entry count, then each entry's name and length), so the whole bundle is
+//! hidden in one text; [`decode_container`] gives the entries back by name,
+//! in the order they were packed.
+
+use crate::framing::{read_text, read_varint, write_text, write_varint};
+use anyhow::{anyhow, bail, Result};
+use indexmap::IndexMap;
+
+/// Format version, the first byte of a container
+const VERSION: u8 = 1;
+
+/// Pack named payloads into one, keeping their order.
+pub fn pack_container(entries: &IndexMap<String, Vec<u8>>) -> Result<Vec<u8>> {
+    if entries.is_empty() {
+        bail!("A container needs at least one entry");
+    }
+    let mut out = vec![VERSION];
+    write_varint(&mut out, entries.len() as u64);
+    for (name, bytes) in entries {
+        check_name(name)?;
+        write_text(&mut out, name);
+        write_varint(&mut out, bytes.len() as u64);
+    }
+    for bytes in entries.values() {
+        out.extend_from_slice(bytes);
+    }
+    Ok(out)
+}
+
+/// The entries of a container [`pack_container`] packed, by name.
+pub fn decode_container(packed: &[u8]) -> Result<IndexMap<String, Vec<u8>>> {
+    let (&version, rest) = packed.split_first().ok_or_else(|| anyhow!("Container is empty"))?;
+    if version != VERSION {
+        bail!("Unknown container version {}", version);
+    }
+    let (count, mut rest) = read_varint(rest)?;
+    let mut toc = Vec::new();
+    for _ in 0..count {
+        let (name, tail) = read_text(rest)?;
+        let (len, tail) = read_varint(tail)?;
+        toc.push((name, len));
+        rest = tail;
+    }
+    let mut entries = IndexMap::new();
+    for (name, len) in toc {
+        let bytes = rest.get(..len as usize).ok_or_else(|| anyhow!("Container entry '{}' is truncated", name))?;
+        rest = &rest[bytes.len()..];
+        if entries.insert(name.to_string(), bytes.to_vec()).is_some() {
+            bail!("Container lists '{}' twice", name);
+        }
+    }
+    if !rest.is_empty() {
+        bail!("Container has {} bytes left over", rest.len());
+    }
+    Ok(entries)
+}
+
+fn check_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.chars().any(char::is_control) {
+        bail!("Container entry name {:?} is empty or has control characters", name);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bundle() -> IndexMap<String, Vec<u8>> {
+        IndexMap::from([
+            ("nsec".to_string(), b"nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5".to_vec()),
+            ("backup codes".to_string(), b"3141-5926\n2718-2818\n".to_vec()),
+            ("empty".to_string(), Vec::new()),
+            ("key".to_string(), vec![0, 255, 7]),
+        ])
+    }
+
+    #[test]
+    fn test_container_round_trip() {
+        let packed = pack_container(&bundle()).unwrap();
+        let entries = decode_container(&packed).unwrap();
+        assert_eq!(entries, bundle());
+        assert_eq!(entries.keys().collect::<Vec<_>>(), ["nsec", "backup codes", "empty", "key"]);
+        // Version, count, then a name, its length byte and a length per entry
+        let toc = 2 + bundle().keys().map(|name| 2 + name.len()).sum::<usize>();
+        assert_eq!(packed.len(), toc + bundle().values().map(Vec::len).sum::<usize>());
+    }
+
+    #[test]
+    fn test_refuses_malformed_containers() {
+        assert!(pack_container(&IndexMap::new()).is_err());
+        assert!(pack_container(&IndexMap::from([("a\nb".to_string(), vec![1])])).is_err());
+
+        let packed = pack_container(&bundle()).unwrap();
+        assert!(decode_container(&packed[..packed.len() - 1]).is_err());
+        assert!(decode_container(&[packed.as_slice(), &[0]].concat()).is_err());
+        assert!(decode_container(&[&[2], &packed[1..]].concat()).is_err());
+        // Two entries both named "a"
+        assert!(decode_container(&[1, 2, 1, b'a', 0, 1, b'a', 0]).is_err());
+    }
+}
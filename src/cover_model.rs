@@ -0,0 +1,245 @@
+//! A word Markov model of the user's own writing, for choosing cover words.
+//!
+//! The Zipf table makes filler words as common as they are in English at
+//! large, but a fishing forum does not read like English at large. A
+//! [`CoverModel`] is an order-2 model trained on a corpus of the kind of text
+//! the cover should pass for: it counts which word follows each pair of
+//! words, with the start of a sentence as its own context, and weighs a
+//! choice by those counts (backing off to pairs, then single words, when a
+//! context was never seen). The generator asks it for filler words and for
+//! the first words of sentences. [`CoverModel::to_bytes`] stores it compactly
+//! so a corpus is trained once.
+
+use crate::decode::normalize_token;
+use crate::framing::{read_text, read_varint, write_text, write_varint};
+use anyhow::{anyhow, bail, Result};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use std::collections::HashMap;
+
+/// First bytes of a stored model
+const MAGIC: &[u8; 4] = b"GLCM";
+
+/// Format version, the byte after the magic
+const VERSION: u8 = 1;
+
+/// Id of the start-of-sentence context; words are numbered from 1
+const START: u32 = 0;
+
+/// Weight kept at each back-off step ("stupid backoff")
+const BACKOFF: f64 = 0.4;
+
+/// Word trigram counts, and the bigram and unigram counts they imply.
+#[derive(Clone, Debug, Default)]
+pub struct CoverModel {
+    /// Words by id; id 0 is the start of a sentence and has no word
+    words: Vec<String>,
+    ids: HashMap<String, u32>,
+    trigrams: HashMap<[u32; 3], u32>,
+    bigrams: HashMap<[u32; 2], u32>,
+    unigrams: HashMap<u32, u32>,
+    /// Times each pair and each single word was followed by anything
+    pair_totals: HashMap<[u32; 2], u32>,
+    word_totals: HashMap<u32, u32>,
+    total: u32,
+}
+
+impl CoverModel {
+    /// Count the word trigrams of `corpus`, sentence by sentence. Words are
+    /// lowercased and stripped of punctuation, as decoding sees them.
+    pub fn train(corpus: &str) -> Result<Self> {
+        let mut model = Self { words: vec![String::new()], ..Default::default() };
+        let mut counts: HashMap<[u32; 3], u32> = HashMap::new();
+        for sentence in crate::chaff::split_sentences(corpus) {
+            let mut context = [START, START];
+            for token in sentence.split_whitespace() {
+                let word = normalize_token(token);
+                if word.is_empty() {
+                    continue;
+                }
+                let id = model.intern(word);
+                *counts.entry([context[0], context[1], id]).or_insert(0) += 1;
+                context = [context[1], id];
+            }
+        }
+        if counts.is_empty() {
+            bail!("Cover model corpus has no words");
+        }
+        for (trigram, count) in counts {
+            model.add(trigram, count);
+        }
+        Ok(model)
+    }
+
+    fn intern(&mut self, word: String) -> u32 {
+        if let Some(&id) = self.ids.get(&word) {
+            return id;
+        }
+        let id = self.words.len() as u32;
+        self.ids.insert(word.clone(), id);
+        self.words.push(word);
+        id
+    }
+
+    fn add(&mut self, [a, b, c]: [u32; 3], count: u32) {
+        *self.trigrams.entry([a, b, c]).or_insert(0) += count;
+        *self.bigrams.entry([b, c]).or_insert(0) += count;
+        *self.unigrams.entry(c).or_insert(0) += count;
+        *self.pair_totals.entry([a, b]).or_insert(0) += count;
+        *self.word_totals.entry(b).or_insert(0) += count;
+        self.total += count;
+    }
+
+    /// Distinct words the model has seen
+    pub fn vocabulary(&self) -> usize {
+        self.words.len() - 1
+    }
+
+    /// How likely `word` is to follow `previous` (the sentence so far; only
+    /// the last two words count), 0 for a word the corpus never has.
+    pub fn weight(&self, previous: &[&str], word: &str) -> f64 {
+        let Some(&id) = self.ids.get(&normalize_token(word)) else {
+            return 0.0;
+        };
+        let context = self.context(previous);
+        let ratio = |count: Option<&u32>, total: Option<&u32>| match (count, total) {
+            (Some(&count), Some(&total)) => Some(count as f64 / total as f64),
+            _ => None,
+        };
+        ratio(self.trigrams.get(&[context[0], context[1], id]), self.pair_totals.get(&context))
+            .or_else(|| {
+                ratio(self.bigrams.get(&[context[1], id]), self.word_totals.get(&context[1])).map(|p| BACKOFF * p)
+            })
+            .unwrap_or_else(|| BACKOFF * BACKOFF * self.unigrams.get(&id).copied().unwrap_or(0) as f64 / self.total as f64)
+    }
+
+    /// Ids of the last two words of `previous`, `START` before the first
+    /// (a word the model doesn't know is a context it never saw).
+    fn context(&self, previous: &[&str]) -> [u32; 2] {
+        let id = |word: &str| self.ids.get(&normalize_token(word)).copied().unwrap_or(u32::MAX);
+        match previous {
+            [] => [START, START],
+            [only] => [START, id(only)],
+            [.., second, last] => [id(second), id(last)],
+        }
+    }
+
+    /// Pick one of `candidates` to follow `previous`, weighted by
+    /// [`weight`](Self::weight); `None` if the corpus has none of them.
+    pub fn choose<'a, R: Rng + ?Sized>(&self, rng: &mut R, previous: &[&str], candidates: &[&'a str]) -> Option<&'a str> {
+        let weights = WeightedIndex::new(candidates.iter().map(|word| self.weight(previous, word))).ok()?;
+        Some(candidates[weights.sample(rng)])
+    }
+
+    /// The model as bytes: a magic and version, the vocabulary, then each
+    /// trigram's word ids and count as varints.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = MAGIC.to_vec();
+        out.push(VERSION);
+        write_varint(&mut out, self.vocabulary() as u64);
+        for word in &self.words[1..] {
+            write_text(&mut out, word);
+        }
+        let mut trigrams: Vec<_> = self.trigrams.iter().collect();
+        trigrams.sort();
+        write_varint(&mut out, trigrams.len() as u64);
+        for (ids, &count) in trigrams {
+            for &id in ids {
+                write_varint(&mut out, id.into());
+            }
+            write_varint(&mut out, count.into());
+        }
+        out
+    }
+
+    /// A model [`to_bytes`](Self::to_bytes) stored.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let rest = bytes.strip_prefix(MAGIC.as_slice()).ok_or_else(|| anyhow!("Not a glossia cover model"))?;
+        let (&version, rest) = rest.split_first().ok_or_else(|| anyhow!("Cover model is truncated"))?;
+        if version != VERSION {
+            bail!("Unknown cover model version {}", version);
+        }
+        let mut model = Self { words: vec![String::new()], ..Default::default() };
+        let (count, mut rest) = read_varint(rest)?;
+        for _ in 0..count {
+            let (word, tail) = read_text(rest)?;
+            if model.ids.contains_key(word) {
+                bail!("Cover model lists '{}' twice", word);
+            }
+            model.intern(word.to_string());
+            rest = tail;
+        }
+        let (count, mut rest) = read_varint(rest)?;
+        for _ in 0..count {
+            let mut fields = [0u32; 4];
+            for field in &mut fields {
+                let (value, tail) = read_varint(rest)?;
+                *field = u32::try_from(value)?;
+                rest = tail;
+            }
+            let [a, b, c, count] = fields;
+            if [a, b, c].iter().any(|&id| id as usize >= model.words.len()) || c == START || count == 0 {
+                bail!("Cover model has a malformed trigram");
+            }
+            model.add([a, b, c], count);
+        }
+        if !rest.is_empty() {
+            bail!("Cover model has {} bytes left over", rest.len());
+        }
+        if model.total == 0 {
+            bail!("Cover model has no words");
+        }
+        Ok(model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const FORUM: &str = "Caught a pike off the dock this morning. The pike took a spinner. \
+        Anyone fishing the lake this weekend? The lake was calm and the bass were biting. \
+        Caught three bass on a worm.";
+
+    #[test]
+    fn test_weights_follow_the_corpus() {
+        let model = CoverModel::train(FORUM).unwrap();
+        assert_eq!(model.vocabulary(), 23);
+        // Sentence openers
+        assert!(model.weight(&[], "caught") > model.weight(&[], "anyone"));
+        assert_eq!(model.weight(&[], "pike"), BACKOFF * BACKOFF * 2.0 / model.total as f64);
+        // "the" follows "off" once in "off the dock"
+        assert_eq!(model.weight(&["caught", "a", "pike", "off"], "The"), 1.0);
+        assert!(model.weight(&["off", "the"], "dock") > model.weight(&["off", "the"], "lake"));
+        assert_eq!(model.weight(&["the"], "marlin"), 0.0);
+
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for _ in 0..1000 {
+            *counts.entry(model.choose(&mut rng, &["on"], &["a", "the", "an"]).unwrap()).or_insert(0) += 1;
+        }
+        assert!(counts["a"] > counts["the"] * 2, "{:?}", counts);
+        assert!(!counts.contains_key("an"));
+        assert!(model.choose(&mut rng, &[], &["marlin"]).is_none());
+        assert!(CoverModel::train(" ... ").is_err());
+    }
+
+    #[test]
+    fn test_bytes_round_trip() {
+        let model = CoverModel::train(FORUM).unwrap();
+        let bytes = model.to_bytes();
+        let loaded = CoverModel::from_bytes(&bytes).unwrap();
+        assert_eq!(loaded.to_bytes(), bytes);
+        for previous in [&[][..], &["the"], &["caught", "three"], &["off", "the"]] {
+            for word in ["the", "bass", "pike", "lake", "marlin"] {
+                assert_eq!(loaded.weight(previous, word), model.weight(previous, word));
+            }
+        }
+        assert!(CoverModel::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+        assert!(CoverModel::from_bytes(&[bytes.as_slice(), &[0]].concat()).is_err());
+        assert!(CoverModel::from_bytes(b"GLCM\x02").is_err());
+        assert!(CoverModel::from_bytes(b"not a model").is_err());
+    }
+}
@@ -0,0 +1,349 @@
+//! Recovering payload words from cover text.
+//!
+//! Cover words never overlap the payload wordlist, so decoding is just: split
+//! the text into tokens, normalize each one, and keep the tokens that are
+//! wordlist words, in order.
+//!
+//! Tokens are normalized into one reused buffer ([`normalize_token_into`]),
+//! so scanning a long document allocates only for the words it keeps.
+//!
+//! Wordlists written without spaces (Chinese, Japanese) are found by
+//! [`segment`](crate::segment) instead of by splitting on whitespace.
+//!
+//! Text copied out of PDFs and email is often hard-wrapped, with words
+//! hyphenated across lines; [`unwrap_lines`] undoes that before decoding so
+//! a split carrier is read as one token.
+
+use crate::index::WordIndex;
+use crate::segment::{self, is_unspaced_char};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// Normalize a token for wordlist lookup.
+///
+/// Decoding is case-insensitive; strips ANSI escape codes (e.g. `\x1b[1m`),
+/// highlighting bars (`|`), and leading/trailing punctuation or quotes.
+pub fn normalize_token(s: &str) -> String {
+    strip_markup(s)
+        .trim()
+        .trim_matches(|c: char| !is_word_char(c))
+        .to_lowercase()
+}
+
+/// Letters a token keeps at its ends: ASCII letters, and the Han and kana
+/// of unspaced wordlists
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphabetic() || is_unspaced_char(c)
+}
+
+/// Normalize a token like [`normalize_token`], writing into `buf` (cleared
+/// first) instead of allocating, and return the normalized slice.
+pub fn normalize_token_into<'b>(s: &str, buf: &'b mut String) -> &'b str {
+    buf.clear();
+    let mut chars = s.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '|' => {}
+            '\x1b' if chars.peek() == Some(&'[') => {
+                // Skip `\x1b[<digits;>m`; anything else is kept verbatim
+                let rest = chars.clone().skip(1);
+                let code_len = rest.clone().take_while(|c| c.is_ascii_digit() || *c == ';').count();
+                if rest.clone().nth(code_len) == Some('m') {
+                    chars.nth(code_len + 1);
+                } else {
+                    buf.push(c);
+                }
+            }
+            c => buf.push(c),
+        }
+    }
+    let start = buf.len() - buf.trim_start_matches(|c: char| !is_word_char(c)).len();
+    let end = buf.trim_end_matches(|c: char| !is_word_char(c)).len().max(start);
+    buf.truncate(end);
+    buf.drain(..start);
+    if buf.is_ascii() {
+        buf.make_ascii_lowercase();
+    } else {
+        *buf = buf.to_lowercase();
+    }
+    buf.as_str()
+}
+
+/// Remove ANSI escape codes and highlighting bars, leaving everything else.
+pub fn strip_markup(s: &str) -> String {
+    strip_ansi(s).replace('|', "")
+}
+
+fn strip_ansi(s: &str) -> std::borrow::Cow<'_, str> {
+    static ANSI: OnceLock<Regex> = OnceLock::new();
+    let ansi = ANSI.get_or_init(|| Regex::new(r"\x1b\[[0-9;]*m").unwrap());
+    ansi.replace_all(s, "")
+}
+
+/// Join words hyphenated across a line break (`aban-\ndon` becomes
+/// `abandon`) and drop soft hyphens. A hyphen is only taken for a line-end
+/// break when a lowercase letter continues the word on the next line.
+pub fn rejoin_hyphenated(text: &str) -> String {
+    static WRAP: OnceLock<Regex> = OnceLock::new();
+    let wrap = WRAP.get_or_init(|| Regex::new(r"(\p{Alphabetic})[-\u{2010}][ \t]*\r?\n[ \t]*(\p{Lowercase})").unwrap());
+    wrap.replace_all(&text.replace('\u{00AD}', ""), "$1$2").into_owned()
+}
+
+/// Undo hard wrapping: rejoin hyphenated words ([`rejoin_hyphenated`]) and
+/// turn single line breaks into spaces. Blank lines between paragraphs are
+/// kept, as one empty line.
+pub fn unwrap_lines(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for line in rejoin_hyphenated(text).lines().map(str::trim) {
+        if line.is_empty() {
+            if !out.is_empty() && !out.ends_with("\n\n") {
+                out.push_str("\n\n");
+            }
+            continue;
+        }
+        if !out.is_empty() && !out.ends_with('\n') {
+            out.push(' ');
+        }
+        out.push_str(line);
+    }
+    out.truncate(out.trim_end().len());
+    out
+}
+
+/// Hard-wrap each line of `text` at word boundaries to at most `width`
+/// columns, counting characters and not highlighting escapes. Words are
+/// never split and a word ending in a hyphen keeps the next word on its line,
+/// so [`unwrap_lines`] gives back the text with its spacing collapsed. A word
+/// longer than `width` gets a line of its own; a `width` of 0 leaves `text`
+/// as it is.
+pub fn wrap_lines(text: &str, width: usize) -> String {
+    if width == 0 {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len() + text.len() / width);
+    for (n, line) in text.lines().enumerate() {
+        if n > 0 {
+            out.push('\n');
+        }
+        let mut column = 0;
+        let mut joined = false;
+        for word in line.split_whitespace() {
+            let len = strip_ansi(word).chars().count();
+            if column > 0 && column + 1 + len > width && !joined {
+                out.push('\n');
+                column = 0;
+            } else if column > 0 {
+                out.push(' ');
+                column += 1;
+            }
+            out.push_str(word);
+            column += len;
+            // "x-" then a line break would be rejoined with the next word
+            joined = word.ends_with(['-', '\u{2010}']);
+        }
+    }
+    out
+}
+
+/// Extract the payload words embedded in `text`, in order.
+pub fn extract_payload_words(text: &str, wordlist: &HashSet<String>) -> Vec<String> {
+    extract_payload_words_with_corrections(text, wordlist, &HashMap::new())
+}
+
+/// Like [`extract_payload_words`], but first maps tokens through `corrections`
+/// (e.g. `"wood" -> "would"` style transcription fixes) before the wordlist lookup.
+/// Unspaced wordlists are segmented, without corrections.
+pub fn extract_payload_words_with_corrections(
+    text: &str,
+    wordlist: &HashSet<String>,
+    corrections: &HashMap<String, String>,
+) -> Vec<String> {
+    if segment::is_unspaced_wordlist(wordlist) {
+        let max_chars = wordlist.iter().map(|w| w.chars().count()).max().unwrap_or_default();
+        return segment::segment(text, max_chars, |w| wordlist.contains(w)).into_iter().map(str::to_string).collect();
+    }
+    let mut buf = String::new();
+    text.split_whitespace()
+        .filter_map(|token| {
+            let w = normalize_token_into(token, &mut buf);
+            let w = corrections.get(w).map(String::as_str).unwrap_or(w);
+            wordlist.get(w).cloned()
+        })
+        .collect()
+}
+
+/// Wordlist indices of the payload words in `text`, in order, without
+/// allocating per token.
+pub fn payload_indices<'a>(text: &'a str, index: &'a WordIndex) -> impl Iterator<Item = usize> + 'a {
+    // Exactly one of the two is non-empty
+    let segmented = index
+        .is_unspaced()
+        .then(|| segment::segment(text, index.max_chars(), |w| index.get(w).is_some()))
+        .into_iter()
+        .flatten()
+        .filter_map(move |word| index.get(word));
+    let mut buf = String::new();
+    let spaced = text
+        .split_whitespace()
+        .take_while(move |_| !index.is_unspaced())
+        .filter_map(move |token| index.get(normalize_token_into(token, &mut buf)));
+    segmented.chain(spaced)
+}
+
+/// Like [`payload_indices`], but looked up with
+/// [`WordIndex::get_constant_time`] so that reading them takes the same time
+/// whichever words they are. Only for spaced wordlists: segmenting an
+/// unspaced text depends on the words it finds.
+pub fn payload_indices_constant_time(text: &str, index: &WordIndex) -> anyhow::Result<Vec<usize>> {
+    if index.is_unspaced() {
+        anyhow::bail!("Constant-time lookup needs a wordlist written with spaces between words");
+    }
+    let mut buf = String::new();
+    Ok(text.split_whitespace().filter_map(|token| index.get_constant_time(normalize_token_into(token, &mut buf))).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_normalize_token() {
+        assert_eq!(normalize_token("|Abandon|."), "abandon");
+        assert_eq!(normalize_token("\x1b[31mability\x1b[0m,"), "ability");
+        assert_eq!(normalize_token("\"able\""), "able");
+        assert_eq!(normalize_token("..."), "");
+    }
+
+    #[test]
+    fn test_normalize_token_into_matches_normalize_token() {
+        let mut buf = String::new();
+        for token in [
+            "|Abandon|.",
+            "\x1b[31mability\x1b[0m,",
+            "\x1b[1;32m|Zoo|\x1b[0m!",
+            "\x1b[bad",
+            "\"able\"",
+            "...",
+            "",
+            "ÉCOLE",
+            "«Façade»",
+            "don't",
+        ] {
+            assert_eq!(normalize_token_into(token, &mut buf), normalize_token(token), "{:?}", token);
+        }
+    }
+
+    #[test]
+    fn test_extract_payload_words_in_order() {
+        let wordlist = set(&["abandon", "ability", "able"]);
+        let text = "The |able| user can |abandon| their plan. |Ability| is clear.";
+        assert_eq!(
+            extract_payload_words(text, &wordlist),
+            vec!["able", "abandon", "ability"]
+        );
+
+        let index = WordIndex::new(&["abandon", "ability", "able"].map(String::from)).unwrap();
+        assert_eq!(payload_indices_constant_time(text, &index).unwrap(), payload_indices(text, &index).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn test_extract_unspaced_wordlist() {
+        let words = ["的", "一", "是", "在"];
+        let wordlist = set(&words);
+        let text = "我们|在|公园里，|一|起看|的|是花。";
+        assert_eq!(extract_payload_words(text, &wordlist), vec!["在", "一", "的", "是"]);
+
+        let index = WordIndex::new(&words.iter().map(|w| w.to_string()).collect::<Vec<_>>()).unwrap();
+        assert_eq!(payload_indices(text, &index).collect::<Vec<_>>(), vec![3, 1, 0, 2]);
+        assert!(payload_indices_constant_time(text, &index).is_err());
+        assert_eq!(normalize_token("「的」。"), "的");
+    }
+
+    /// Wrap like a mail client or typesetter: break at `width` columns,
+    /// hyphenating a word when at least three letters fit on the line
+    fn hard_wrap(text: &str, width: usize) -> String {
+        let mut out = String::new();
+        let mut line = 0;
+        for word in text.split_whitespace() {
+            let gap = usize::from(line > 0);
+            if line + gap + word.len() <= width {
+                out.push_str(&" "[..gap]);
+                out.push_str(word);
+                line += gap + word.len();
+                continue;
+            }
+            let room = width.saturating_sub(line + gap + 1);
+            if room >= 3 && word.len() >= room + 2 && word.bytes().take(room + 1).all(|b| b.is_ascii_alphabetic()) {
+                out.push_str(&" "[..gap]);
+                out.push_str(&word[..room]);
+                out.push_str("-\n");
+                out.push_str(&word[room..]);
+                line = word.len() - room;
+            } else {
+                out.push('\n');
+                out.push_str(word);
+                line = word.len();
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn test_unwrap_lines_round_trips_hard_wrapping() {
+        let carriers = ["abandon", "ability", "absorb", "abstract", "accident", "account", "achieve", "acquire"];
+        let wordlist = set(&carriers);
+        let text: Vec<String> = carriers.iter().cycle().take(24).map(|w| format!("We saw the {} today.", w)).collect();
+        let text = text.join(" ");
+        let expected = extract_payload_words(&text, &wordlist);
+
+        let mut split_carriers = false;
+        for width in [72, 40, 50, 60, 66, 80] {
+            let wrapped = hard_wrap(&text, width);
+            split_carriers |= extract_payload_words(&wrapped, &wordlist) != expected;
+            assert_eq!(extract_payload_words(&unwrap_lines(&wrapped), &wordlist), expected, "width {}", width);
+            assert_eq!(unwrap_lines(&wrapped), text, "width {}", width);
+        }
+        // At 72 columns "account" is split as "acc-" / "ount"
+        assert!(split_carriers);
+    }
+
+    #[test]
+    fn test_unwrap_lines_keeps_paragraphs() {
+        assert_eq!(unwrap_lines("One line\nwraps here.\r\n  \n\nNew para-\ngraph, not a Line-\nBreak.\n"), "One line wraps here.\n\nNew paragraph, not a Line- Break.");
+        assert_eq!(rejoin_hyphenated("co\u{00AD}conut, x -\ny"), "coconut, x -\ny");
+    }
+
+    #[test]
+    fn test_wrap_lines_is_undone_by_unwrap_lines() {
+        let text = "The |abandon|  ability was \x1b[32mabsorbed\x1b[0m by a well- known account, as ever.\n\nA second paragraph, with a deliberately-overlong-compound-word-here and more.";
+        let spaced = "The |abandon| ability was \x1b[32mabsorbed\x1b[0m by a well- known account, as ever.\n\nA second paragraph, with a deliberately-overlong-compound-word-here and more.";
+        for width in [1, 10, 17, 24, 40, 80, 200] {
+            let wrapped = wrap_lines(text, width);
+            assert_eq!(unwrap_lines(&wrapped), spaced, "width {}", width);
+            for line in wrapped.lines().filter(|line| line.contains(' ')) {
+                let joined = line.contains("well- known");
+                assert!(strip_ansi(line).chars().count() <= width || joined, "width {}: {:?}", width, line);
+            }
+        }
+        assert_eq!(wrap_lines(text, 0), text);
+    }
+
+    #[test]
+    fn test_extract_with_corrections() {
+        let wordlist = set(&["eight", "sea"]);
+        let corrections: HashMap<String, String> = [("ate", "eight"), ("see", "sea")]
+            .iter()
+            .map(|(a, b)| (a.to_string(), b.to_string()))
+            .collect();
+        let text = "They ate by the see.";
+        assert_eq!(
+            extract_payload_words_with_corrections(text, &wordlist, &corrections),
+            vec!["eight", "sea"]
+        );
+    }
+}
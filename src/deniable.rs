@@ -0,0 +1,310 @@
+//! Deniable dual-payload encoding.
+//!
+//! One carrier sequence holds two payloads. Handing over the decoy passphrase
+//! reveals an innocuous message; the real passphrase reveals the secret, and
+//! nothing in the carrier shows that a second payload exists.
+//!
+//! Layout: the first carrier word is a public layout salt, then
+//! [`whiten::nonce_words`] random words salting the passphrases, then `N`
+//! slots. Each passphrase (with the layout salt) derives a keyed permutation
+//! of the slots, and a payload occupies the first `len + 1` slots in its
+//! permutation order: a length word, then `len` data words packing the
+//! payload's byte length, the payload and a keyed tag over both. Every word is
+//! masked with a passphrase-derived stream, and slots neither payload uses
+//! are filled with random words, so all slots look alike. A wrong passphrase
+//! unmasks noise, which the tag rejects with probability `1 - 2^-128`.
+//!
+//! The encoder retries salts until the two payloads' slots are disjoint. For
+//! random `r`- and `d`-subsets of `N` slots the chance of that is
+//! `prod_{i<d} (N - r - i) / (N - i)`, roughly `exp(-r*d/N)`, so the number of
+//! slots needed grows with the product of the two lengths. [`deniable_slots`]
+//! picks the smallest `N` for which searching every salt misses with
+//! probability below 2^-20.
+//!
+//! Whoever holds the decoy passphrase can work out `N` for the decoy alone,
+//! so `N` must not follow the real payload's length. It is sized for the
+//! real payload's [`real_capacity`] instead: a size bucket (32, 64, 128, ...
+//! words) that every carrier reserves, so a carrier with a short real
+//! payload, or an empty one, is as long as any other for the same decoy.
+
+use crate::codec;
+use crate::error::GlossiaError;
+use crate::index::WordIndex;
+use crate::keys::{self, PassphraseKey, TAG_LEN};
+use crate::whiten;
+use crate::secret::Wipe;
+use anyhow::{bail, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashSet;
+
+const LAYOUT_LABEL: &str = "deniable/layout";
+const MASK_LABEL: &str = "deniable/mask";
+const TAG_LABEL: &str = "deniable/tag";
+
+/// Acceptable probability that no salt gives a disjoint layout
+const SEARCH_FAILURE_LN: f64 = -20.0 * std::f64::consts::LN_2;
+
+/// Framing words per payload: the length word
+const FRAME_WORDS: usize = 1;
+
+/// Framing bytes packed with each payload: its byte length and the tag
+const FRAME_BYTES: usize = 2 + TAG_LEN;
+
+/// Smallest real payload capacity, in data words. The framing bytes take
+/// about 13 of them.
+pub const MIN_REAL_CAPACITY: usize = 32;
+
+/// Data words reserved for a real payload of `real_words`: the smallest of
+/// [`MIN_REAL_CAPACITY`] and its doublings that holds it, and at most what a
+/// length word can count (`wordlist_len - 1`).
+pub fn real_capacity(real_words: usize, wordlist_len: usize) -> usize {
+    let bucket = real_words.max(MIN_REAL_CAPACITY).next_power_of_two();
+    bucket.min(wordlist_len.saturating_sub(1))
+}
+
+/// Number of slots (excluding the salt word) needed to hold a real payload
+/// of up to `real_capacity` and a decoy of `decoy_words` data words with a
+/// `wordlist_len`-word list.
+pub fn deniable_slots(real_capacity: usize, decoy_words: usize, wordlist_len: usize) -> usize {
+    let r = real_capacity + FRAME_WORDS;
+    let d = decoy_words + FRAME_WORDS;
+    let mut n = r + d;
+    while search_failure_ln(r, d, n, wordlist_len) > SEARCH_FAILURE_LN {
+        n += 1;
+    }
+    n
+}
+
+/// ln of the probability that none of `salts` layouts is disjoint.
+fn search_failure_ln(r: usize, d: usize, n: usize, salts: usize) -> f64 {
+    let disjoint: f64 = (0..d).map(|i| (n - r - i) as f64 / (n - i) as f64).product();
+    if disjoint >= 1.0 {
+        return f64::NEG_INFINITY;
+    }
+    salts as f64 * (1.0 - disjoint).ln()
+}
+
+/// Encode `real` and `decoy` into one carrier word sequence. `wordlist` must
+/// be in canonical sorted order; `rng` supplies the salt and filler words.
+pub fn encode_deniable<R: Rng>(
+    rng: &mut R,
+    real: &[u8],
+    decoy: &[u8],
+    real_pass: &str,
+    decoy_pass: &str,
+    wordlist: &[String],
+) -> Result<Vec<String>> {
+    if real_pass == decoy_pass {
+        bail!("The real and decoy passphrases must differ");
+    }
+    let n_words = wordlist.len();
+    let index = WordIndex::shared(wordlist)?;
+    // The frame lengths don't depend on the salt the tags cover
+    let real_words = codec::packed_words(real.len() + FRAME_BYTES, n_words);
+    let decoy_words = codec::packed_words(decoy.len() + FRAME_BYTES, n_words);
+    if real_words.max(decoy_words) >= n_words {
+        bail!("Payload of {} words is too long to frame", real_words.max(decoy_words));
+    }
+    // Sized for the real payload's bucket, not its length, which the decoy
+    // passphrase could otherwise read off the carrier
+    let slots = deniable_slots(real_capacity(real_words, n_words), decoy_words, n_words);
+
+    let kdf_salt: Vec<usize> = (0..whiten::nonce_words(n_words)).map(|_| rng.gen_range(0..n_words)).collect();
+    let real_key = PassphraseKey::derive(real_pass, &keys::index_salt(&kdf_salt));
+    let decoy_key = PassphraseKey::derive(decoy_pass, &keys::index_salt(&kdf_salt));
+    let start = rng.gen_range(0..n_words);
+    for t in 0..n_words {
+        let salt = (start + t) % n_words;
+        let real_order = layout(&real_key, salt, slots);
+        let decoy_order = layout(&decoy_key, salt, slots);
+        let real_slots = &real_order[..real_words + FRAME_WORDS];
+        let decoy_slots = &decoy_order[..decoy_words + FRAME_WORDS];
+        let taken: HashSet<usize> = decoy_slots.iter().copied().collect();
+        if real_slots.iter().any(|s| taken.contains(s)) {
+            continue;
+        }
+
+        let mut carrier: Vec<usize> = (0..slots).map(|_| rng.gen_range(0..n_words)).collect();
+        let mut real_frame = frame(real, &real_key, salt, wordlist, &index)?;
+        let mut decoy_frame = frame(decoy, &decoy_key, salt, wordlist, &index)?;
+        place(&mut carrier, &real_frame, real_slots, &real_key, salt, n_words);
+        place(&mut carrier, &decoy_frame, decoy_slots, &decoy_key, salt, n_words);
+        real_frame.wipe();
+        decoy_frame.wipe();
+
+        let mut words = vec![wordlist[salt].clone()];
+        words.extend(kdf_salt.iter().map(|&i| wordlist[i].clone()));
+        words.extend(carrier.into_iter().map(|i| wordlist[i].clone()));
+        return Ok(words);
+    }
+    bail!("No salt gives a disjoint layout for these payloads")
+}
+
+/// Decode the payload that `passphrase` unlocks from a carrier produced by
+/// [`encode_deniable`]. A wrong passphrase is rejected by the frame's tag.
+pub fn decode_deniable(words: &[String], passphrase: &str, wordlist: &[String]) -> Result<Vec<u8>> {
+    let n_words = wordlist.len();
+    let index = WordIndex::shared(wordlist)?;
+    let indices: Vec<usize> = words
+        .iter()
+        .enumerate()
+        .map(|(position, w)| index.get(&w.to_lowercase()).ok_or_else(|| codec::unknown_word(w, position).into()))
+        .collect::<Result<_>>()?;
+    let Some((&salt, rest)) = indices.split_first() else {
+        bail!("No carrier words to decode");
+    };
+    let Some((kdf_salt, carrier)) = rest.split_at_checked(whiten::nonce_words(n_words)) else {
+        bail!("Carrier is too short to hold its salt");
+    };
+    if carrier.len() < FRAME_WORDS {
+        bail!("Carrier is too short to hold a payload");
+    }
+
+    let key = PassphraseKey::derive(passphrase, &keys::index_salt(kdf_salt));
+    let order = layout(&key, salt, carrier.len());
+    let mut mask = key.rng(MASK_LABEL, &(salt as u32).to_le_bytes());
+    let mut next = order.iter().map(|&slot| {
+        let m = mask.gen_range(0..n_words);
+        (carrier[slot] + n_words - m) % n_words
+    });
+
+    let len = next.next().unwrap_or_default();
+    if len + FRAME_WORDS > carrier.len() {
+        return Err(GlossiaError::WrongPassphrase.into());
+    }
+    let mut data: Vec<usize> = next.take(len).collect();
+    let unpacked = codec::decode_indices(&data, n_words);
+    data.wipe();
+    let Ok(mut framed) = unpacked else {
+        return Err(GlossiaError::WrongPassphrase.into());
+    };
+    let payload = unframe(&framed, &key, salt);
+    framed.wipe();
+    payload.ok_or_else(|| GlossiaError::WrongPassphrase.into())
+}
+
+/// Length word and data words (as word indices) for `bytes`: the data words
+/// pack the byte length, the bytes and a tag over both under `key` and `salt`.
+fn frame(bytes: &[u8], key: &PassphraseKey, salt: usize, wordlist: &[String], index: &WordIndex) -> Result<Vec<usize>> {
+    let Ok(len) = u16::try_from(bytes.len()) else {
+        bail!("Payload of {} bytes is too long to frame", bytes.len());
+    };
+    let mut packed = len.to_le_bytes().to_vec();
+    packed.extend_from_slice(bytes);
+    let tag = key.commit_tag(TAG_LABEL, &tagged(salt, &packed));
+    packed.extend_from_slice(&tag);
+    let data = codec::encode_bytes(&packed, wordlist);
+    packed.wipe();
+    let mut data = data?;
+    let mut framed = vec![data.len()];
+    framed.extend(data.iter().filter_map(|w| index.get(w)));
+    data.wipe();
+    Ok(framed)
+}
+
+/// The payload in unpacked frame bytes, if the byte length fits and the tag
+/// verifies under `key` and `salt`.
+fn unframe(framed: &[u8], key: &PassphraseKey, salt: usize) -> Option<Vec<u8>> {
+    let (body, tag) = framed.split_at_checked(framed.len().checked_sub(TAG_LEN)?)?;
+    let len = u16::from_le_bytes(body.get(..2)?.try_into().ok()?) as usize;
+    let mut signed = tagged(salt, body);
+    let valid = body.len() == 2 + len && key.verify_tag(TAG_LABEL, &signed, tag);
+    signed.wipe();
+    valid.then(|| body[2..].to_vec())
+}
+
+/// The bytes a frame's tag covers: the salt, then the length-prefixed payload.
+fn tagged(salt: usize, body: &[u8]) -> Vec<u8> {
+    let mut out = (salt as u32).to_le_bytes().to_vec();
+    out.extend_from_slice(body);
+    out
+}
+
+/// Write a masked frame into its slots.
+fn place(carrier: &mut [usize], frame: &[usize], slots: &[usize], key: &PassphraseKey, salt: usize, n_words: usize) {
+    let mut mask = key.rng(MASK_LABEL, &(salt as u32).to_le_bytes());
+    for (&value, &slot) in frame.iter().zip(slots) {
+        carrier[slot] = (value + mask.gen_range(0..n_words)) % n_words;
+    }
+}
+
+/// The keyed slot order for one passphrase and salt.
+fn layout(key: &PassphraseKey, salt: usize, slots: usize) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..slots).collect();
+    order.shuffle(&mut key.rng(LAYOUT_LABEL, &(salt as u32).to_le_bytes()));
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_each_passphrase_recovers_its_payload() {
+        let list = alpha_wordlist();
+        let mut rng = StdRng::seed_from_u64(7);
+        let words = encode_deniable(&mut rng, b"Meet at the north gate", b"Pick up milk", "real pass", "decoy pass", &list)
+            .unwrap();
+        assert_eq!(decode_deniable(&words, "real pass", &list).unwrap(), b"Meet at the north gate");
+        assert_eq!(decode_deniable(&words, "decoy pass", &list).unwrap(), b"Pick up milk");
+        for guess in 0..20 {
+            assert!(decode_deniable(&words, &format!("guess {}", guess), &list).is_err());
+        }
+        assert!(encode_deniable(&mut rng, b"a", b"b", "same", "same", &list).is_err());
+    }
+
+    #[test]
+    fn test_decoy_view_does_not_depend_on_real_payload() {
+        let list = alpha_wordlist();
+        let a = encode_deniable(&mut StdRng::seed_from_u64(1), b"secret one", b"hello", "r", "d", &list).unwrap();
+        let b = encode_deniable(&mut StdRng::seed_from_u64(1), b"secret two", b"hello", "r", "d", &list).unwrap();
+        assert_eq!(decode_deniable(&a, "d", &list).unwrap(), b"hello");
+        assert_eq!(decode_deniable(&b, "d", &list).unwrap(), b"hello");
+
+        // The plain encoding of the real payload doesn't show through the masking
+        let plain = codec::encode_bytes(b"secret one", &list).unwrap();
+        let carrier: HashSet<&String> = a.iter().collect();
+        assert!(plain.iter().filter(|w| carrier.contains(w)).count() < plain.len() / 2);
+    }
+
+    #[test]
+    fn test_structured_payload_looks_uniform() {
+        let list = alpha_wordlist();
+        let zeros = [0u8; 64];
+        let words = encode_deniable(&mut StdRng::seed_from_u64(3), &zeros, b"ok", "r", "d", &list).unwrap();
+        let mut counts: HashMap<&String, usize> = HashMap::new();
+        for w in &words {
+            *counts.entry(w).or_default() += 1;
+        }
+        assert!(counts.values().all(|&c| c <= 3), "repeated carrier words: {:?}", counts);
+        assert_eq!(decode_deniable(&words, "r", &list).unwrap(), zeros);
+    }
+
+    #[test]
+    fn test_carrier_length_does_not_show_the_real_payload() {
+        let list = alpha_wordlist();
+        let length = |real: &[u8]| encode_deniable(&mut StdRng::seed_from_u64(5), real, b"Pick up milk", "r", "d", &list).unwrap().len();
+        // No real payload, a short one and one filling the smallest bucket
+        let empty = length(b"");
+        assert_eq!(length(b"yes"), empty);
+        assert_eq!(length(&[7u8; (MIN_REAL_CAPACITY * 11 - 1) / 8 - FRAME_BYTES]), empty);
+        // A larger payload moves to the next bucket, whatever the decoy
+        assert!(length(&[7u8; 60]) > empty);
+        assert_eq!(real_capacity(17, 2048), 32);
+        assert_eq!(real_capacity(200, 256), 255);
+    }
+
+    #[test]
+    fn test_slot_count_grows_with_both_payloads() {
+        let small = deniable_slots(5, 5, 2048);
+        assert!(small >= 2 * (5 + FRAME_WORDS));
+        assert!(deniable_slots(20, 5, 2048) > small);
+        assert!(deniable_slots(5, 20, 2048) > small);
+        assert!(search_failure_ln(5 + FRAME_WORDS, 5 + FRAME_WORDS, small, 2048) <= SEARCH_FAILURE_LN);
+    }
+}
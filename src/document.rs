@@ -0,0 +1,108 @@
+//! Text from documents a cover text was pasted into.
+//!
+//! [`read_text`] picks an extractor by file extension: the text layer of a
+//! PDF (feature `pdf`, via `pdf-extract`), the body paragraphs of a DOCX
+//! (feature `docx`, via `docx-rs`), or the file itself as UTF-8 text.
+//!
+//! PDF text comes back as the lines of the page, and typesetting hyphenates
+//! words at line ends ("aban-\ndon"), which splits a carrier into two
+//! non-words, so [`read_text`] puts such words back together
+//! ([`rejoin_hyphenated`]).
+
+use crate::decode::rejoin_hyphenated;
+use anyhow::{Context, Result};
+use std::path::Path;
+
+/// Document formats with their own extractor
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Pdf,
+    Docx,
+    Text,
+}
+
+impl Format {
+    /// The format a file name's extension implies (plain text if unknown)
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("pdf") => Format::Pdf,
+            Some("docx") => Format::Docx,
+            _ => Format::Text,
+        }
+    }
+}
+
+/// The text of the document at `path`, ready for decoding.
+pub fn read_text(path: &Path) -> Result<String> {
+    match Format::from_path(path) {
+        Format::Pdf => {
+            let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok(rejoin_hyphenated(&pdf_text(&bytes).with_context(|| format!("Failed to extract text from {}", path.display()))?))
+        }
+        Format::Docx => {
+            let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            docx_text(&bytes).with_context(|| format!("Failed to extract text from {}", path.display()))
+        }
+        Format::Text => std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display())),
+    }
+}
+
+/// The text layer of a PDF, page by page.
+#[cfg(feature = "pdf")]
+pub fn pdf_text(bytes: &[u8]) -> Result<String> {
+    pdf_extract::extract_text_from_mem(bytes).map_err(|e| anyhow::anyhow!("{}", e))
+}
+
+/// The text layer of a PDF (needs the `pdf` feature).
+#[cfg(not(feature = "pdf"))]
+pub fn pdf_text(_bytes: &[u8]) -> Result<String> {
+    anyhow::bail!("Reading PDF files needs glossia built with the 'pdf' feature")
+}
+
+/// The body paragraphs of a DOCX, one per line.
+#[cfg(feature = "docx")]
+pub fn docx_text(bytes: &[u8]) -> Result<String> {
+    use docx_rs::{DocumentChild, ParagraphChild, RunChild};
+    let docx = docx_rs::read_docx(bytes).map_err(|e| anyhow::anyhow!("{}", e))?;
+    let mut paragraphs = Vec::new();
+    for child in &docx.document.children {
+        let DocumentChild::Paragraph(paragraph) = child else {
+            continue;
+        };
+        let mut text = String::new();
+        for part in &paragraph.children {
+            let ParagraphChild::Run(run) = part else {
+                continue;
+            };
+            for piece in &run.children {
+                match piece {
+                    RunChild::Text(t) => text.push_str(&t.text),
+                    RunChild::Tab(_) => text.push('\t'),
+                    RunChild::Break(_) => text.push('\n'),
+                    _ => {}
+                }
+            }
+        }
+        paragraphs.push(text);
+    }
+    Ok(paragraphs.join("\n"))
+}
+
+/// The body paragraphs of a DOCX (needs the `docx` feature).
+#[cfg(not(feature = "docx"))]
+pub fn docx_text(_bytes: &[u8]) -> Result<String> {
+    anyhow::bail!("Reading DOCX files needs glossia built with the 'docx' feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_from_path() {
+        assert_eq!(Format::from_path(Path::new("report.PDF")), Format::Pdf);
+        assert_eq!(Format::from_path(Path::new("notes/draft.docx")), Format::Docx);
+        assert_eq!(Format::from_path(Path::new("cover.txt")), Format::Text);
+        assert_eq!(Format::from_path(Path::new("README")), Format::Text);
+    }
+}
@@ -0,0 +1,103 @@
+//! Payload wordlist compiled into the binary (feature `embedded-wordlist`).
+//!
+//! For targets without a filesystem (WASM) the English wordlist and its POS
+//! weights are packed at build time by `build.rs`: front-coded, then deflated.
+//! The blob is inflated once, on first use, and the result is shared.
+
+use crate::types::Pos;
+use flate2::read::DeflateDecoder;
+use std::collections::HashMap;
+use std::io::Read;
+use std::sync::OnceLock;
+
+static PACKED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/payload.bin"));
+
+struct Unpacked {
+    words: Vec<String>,
+    weights: HashMap<String, Vec<(Pos, f64)>>,
+}
+
+fn unpacked() -> &'static Unpacked {
+    static UNPACKED: OnceLock<Unpacked> = OnceLock::new();
+    UNPACKED.get_or_init(|| {
+        let mut text = String::new();
+        DeflateDecoder::new(PACKED)
+            .read_to_string(&mut text)
+            .expect("embedded wordlist is valid deflate");
+
+        let mut words: Vec<String> = Vec::new();
+        let mut weights = HashMap::new();
+        for line in text.lines() {
+            let (coded, tags) = line.split_once('\t').unwrap_or((line, ""));
+            let digits = coded.bytes().take_while(u8::is_ascii_digit).count();
+            let shared: usize = coded[..digits].parse().unwrap_or(0);
+            let prefix = words.last().map_or("", |w| &w[..shared.min(w.len())]);
+            let word = format!("{}{}", prefix, &coded[digits..]);
+
+            let tags = tags
+                .split(',')
+                .filter_map(|tag| {
+                    let (pos, weight) = tag.split_once('=')?;
+                    Some((parse_pos(pos)?, weight.parse().ok()?))
+                })
+                .collect();
+            weights.insert(word.clone(), tags);
+            words.push(word);
+        }
+        Unpacked { words, weights }
+    })
+}
+
+fn parse_pos(tag: &str) -> Option<Pos> {
+    Some(match tag {
+        "Det" => Pos::Det,
+        "Adj" => Pos::Adj,
+        "N" => Pos::N,
+        "V" => Pos::V,
+        "Modal" => Pos::Modal,
+        "Aux" => Pos::Aux,
+        "Cop" => Pos::Cop,
+        "To" => Pos::To,
+        "Prep" => Pos::Prep,
+        "Adv" => Pos::Adv,
+        "Conj" => Pos::Conj,
+        "Dot" => Pos::Dot,
+        "Prefix" => Pos::Prefix,
+        _ => return None,
+    })
+}
+
+/// The English payload wordlist, sorted (the order `codec` expects).
+pub fn wordlist() -> &'static [String] {
+    &unpacked().words
+}
+
+/// POS weights of an embedded payload word, as listed in `payload.yaml`.
+pub fn pos_weights(word: &str) -> Option<&'static [(Pos, f64)]> {
+    unpacked().weights.get(word).map(Vec::as_slice)
+}
+
+/// Size of the packed blob compiled into the binary, in bytes.
+pub fn packed_len() -> usize {
+    PACKED.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_wordlist_matches_yaml() {
+        let yaml = std::fs::read_to_string("languages/english/payload.yaml").unwrap();
+        let data: HashMap<String, HashMap<String, f64>> = serde_yaml::from_str(&yaml).unwrap();
+        let mut expected: Vec<String> = data.keys().cloned().collect();
+        expected.sort();
+
+        assert_eq!(wordlist(), expected.as_slice());
+        assert!(packed_len() < yaml.len() / 2);
+
+        let abandon = pos_weights("abandon").expect("abandon is embedded");
+        assert!(abandon.contains(&(Pos::V, 0.6)));
+        assert!(pos_weights("notaword").is_none());
+    }
+}
@@ -0,0 +1,48 @@
+//! nlprule models compiled into the binary (feature `embedded-models`).
+//!
+//! `build.rs` finds `en_tokenizer.bin` and `en_rules.bin` at build time (in
+//! `GLOSSIA_MODEL_DIR` or `data/`) and `include_bytes!` takes them from there,
+//! so a single binary or a Docker image needs no model volume. With
+//! `embedded-models-zstd` the copies are zstd-compressed and are decompressed
+//! while they are parsed. [`GrammarChecker::from_language`] falls back to them
+//! when no model files are found on disk.
+
+use crate::{GrammarChecker, Language};
+use anyhow::{Context, Result};
+use nlprule::{Rules, Tokenizer};
+
+static TOKENIZER: &[u8] = include_bytes!(env!("GLOSSIA_EMBEDDED_TOKENIZER"));
+static RULES: &[u8] = include_bytes!(env!("GLOSSIA_EMBEDDED_RULES"));
+
+#[cfg(feature = "embedded-models-zstd")]
+fn reader(bytes: &'static [u8]) -> Result<impl std::io::Read> {
+    Ok(zstd::stream::read::Decoder::with_buffer(bytes)?)
+}
+
+#[cfg(not(feature = "embedded-models-zstd"))]
+fn reader(bytes: &'static [u8]) -> Result<impl std::io::Read> {
+    Ok(bytes)
+}
+
+impl GrammarChecker {
+    /// Create a new GrammarChecker from the models compiled into the binary
+    pub fn from_embedded(language: Language) -> Result<Self> {
+        let (tokenizer, rules) = match language {
+            Language::English => (TOKENIZER, RULES),
+        };
+        let tokenizer = Tokenizer::from_reader(reader(tokenizer)?).context("Failed to load the embedded tokenizer")?;
+        let rules = Rules::from_reader(reader(rules)?).context("Failed to load the embedded rules")?;
+        Ok(Self { tokenizer, rules, options: Default::default() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_embedded_models_load() {
+        let checker = GrammarChecker::from_embedded(Language::English).unwrap();
+        assert!(checker.is_correct("The quick brown fox jumps over the lazy dog."));
+    }
+}
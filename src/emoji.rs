@@ -0,0 +1,193 @@
+//! Emoji carrier text.
+//!
+//! Some platforms have little room for prose but see plenty of reaction
+//! spam ("🔥🙌 😂😂🎉 👀"). This engine writes the payload as emoji drawn from
+//! a fixed alphabet of [`ALPHABET_SIZE`] emoji, so each emoji carries
+//! [`SYMBOL_BITS`] bits. Payload words are packed as their wordlist indices,
+//! most significant bit first, and the emoji are grouped into short bursts.
+//!
+//! Platforms and people decorate emoji, so the decoder ignores anything
+//! outside the alphabet: skin-tone modifiers and variation selectors on an
+//! alphabet emoji are dropped, and whole ZWJ sequences ("👨‍👩‍👧") are skipped,
+//! since the encoder never writes one.
+
+use anyhow::{bail, Result};
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Bits carried by one emoji
+pub const SYMBOL_BITS: usize = 10;
+
+/// Emoji in the alphabet
+pub const ALPHABET_SIZE: usize = 1 << SYMBOL_BITS;
+
+/// Most emoji in one burst
+const MAX_BURST: usize = 4;
+
+const ZWJ: char = '\u{200D}';
+const VARIATION_SELECTOR: char = '\u{FE0F}';
+
+/// The alphabet, one emoji per line; a line's position is its value
+const ALPHABET_FILE: &str = include_str!("emoji_alphabet.txt");
+
+struct Alphabet {
+    symbols: Vec<char>,
+    values: HashMap<char, usize>,
+}
+
+fn alphabet() -> &'static Alphabet {
+    static ALPHABET: OnceLock<Alphabet> = OnceLock::new();
+    ALPHABET.get_or_init(|| {
+        let symbols: Vec<char> = ALPHABET_FILE
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .filter_map(|line| line.chars().next())
+            .collect();
+        let values = symbols.iter().enumerate().map(|(value, &c)| (c, value)).collect();
+        Alphabet { symbols, values }
+    })
+}
+
+fn is_skin_tone(c: char) -> bool {
+    ('\u{1F3FB}'..='\u{1F3FF}').contains(&c)
+}
+
+/// Bits per payload word for `wordlist`, which must be larger than the
+/// alphabet so that padding never reads as an extra word
+fn word_bits(wordlist: &[String]) -> Result<usize> {
+    let bits = wordlist.len().next_power_of_two().trailing_zeros() as usize;
+    if bits <= SYMBOL_BITS {
+        bail!("The emoji engine needs a wordlist of more than {} words, got {}", ALPHABET_SIZE, wordlist.len());
+    }
+    Ok(bits)
+}
+
+/// Write `words` (all `wordlist` words) as bursts of emoji.
+pub fn encode<R: Rng + ?Sized>(rng: &mut R, words: &[String], wordlist: &[String]) -> Result<String> {
+    let bits_per_word = word_bits(wordlist)?;
+    let positions: HashMap<&str, usize> = wordlist.iter().enumerate().map(|(i, w)| (w.as_str(), i)).collect();
+    let mut bits = Vec::with_capacity(words.len() * bits_per_word);
+    for word in words {
+        let Some(&index) = positions.get(word.to_lowercase().as_str()) else {
+            bail!("'{}' is not in the wordlist", word);
+        };
+        bits.extend((0..bits_per_word).rev().map(|i| (index >> i) & 1 == 1));
+    }
+    // Fewer than SYMBOL_BITS random bits finish the last emoji
+    while bits.len() % SYMBOL_BITS != 0 {
+        bits.push(rng.gen());
+    }
+
+    let symbols = &alphabet().symbols;
+    let mut emoji = bits
+        .chunks(SYMBOL_BITS)
+        .map(|chunk| symbols[chunk.iter().fold(0, |acc, &bit| (acc << 1) | bit as usize)]);
+    let mut bursts = Vec::new();
+    loop {
+        let burst: String = emoji.by_ref().take(rng.gen_range(1..=MAX_BURST)).collect();
+        if burst.is_empty() {
+            break;
+        }
+        bursts.push(burst);
+    }
+    Ok(bursts.join(" "))
+}
+
+/// Values of the alphabet emoji in `text`, in order, skipping decorations
+fn symbol_values(text: &str) -> Vec<usize> {
+    let values = &alphabet().values;
+    let mut found = Vec::new();
+    // The emoji read last, unless it has been joined into a ZWJ sequence
+    let mut current: Option<usize> = None;
+    let mut joining = false;
+    for c in text.chars() {
+        if c == VARIATION_SELECTOR || is_skin_tone(c) {
+            continue;
+        }
+        if c == ZWJ {
+            current = None;
+            joining = true;
+            continue;
+        }
+        if std::mem::take(&mut joining) {
+            // Part of the sequence the ZWJ started
+            continue;
+        }
+        found.extend(current.take());
+        current = values.get(&c).copied();
+    }
+    found.extend(current);
+    found
+}
+
+/// Read back the words of an [`encode`]d text.
+pub fn decode(text: &str, wordlist: &[String]) -> Result<Vec<String>> {
+    let bits_per_word = word_bits(wordlist)?;
+    let bits: Vec<bool> = symbol_values(text)
+        .into_iter()
+        .flat_map(|value| (0..SYMBOL_BITS).rev().map(move |i| (value >> i) & 1 == 1))
+        .collect();
+    bits.chunks_exact(bits_per_word)
+        .map(|chunk| {
+            let index = chunk.iter().fold(0, |acc, &bit| (acc << 1) | bit as usize);
+            match wordlist.get(index) {
+                Some(word) => Ok(word.clone()),
+                None => bail!("Emoji decode to word index {}, past the end of the wordlist", index),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    fn wordlist() -> Vec<String> {
+        (0..2048).map(|i| format!("w{}", i)).collect()
+    }
+
+    #[test]
+    fn test_alphabet_is_complete() {
+        let alphabet = alphabet();
+        assert_eq!(alphabet.symbols.len(), ALPHABET_SIZE);
+        assert_eq!(alphabet.values.len(), ALPHABET_SIZE);
+        let decorations: HashSet<char> = [ZWJ, VARIATION_SELECTOR].into_iter().collect();
+        assert!(alphabet.symbols.iter().all(|&c| !is_skin_tone(c) && !decorations.contains(&c)));
+        // Every line holds exactly one emoji
+        assert!(ALPHABET_FILE
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .all(|line| line.trim().chars().count() == 1));
+    }
+
+    #[test]
+    fn test_round_trip_through_decorations() {
+        let wordlist = wordlist();
+        let words: Vec<String> = ["w0", "w2047", "w1024", "w7", "w1500"].iter().map(|w| w.to_string()).collect();
+        let mut rng = StdRng::seed_from_u64(4);
+        let text = encode(&mut rng, &words, &wordlist).unwrap();
+        // 55 bits fill 6 emoji
+        assert_eq!(text.chars().filter(|c| !c.is_whitespace()).count(), 6);
+        assert_eq!(decode(&text, &wordlist).unwrap(), words);
+
+        // Skin tones, variation selectors, ZWJ sequences and words in between
+        let mut decorated = String::from("lol 👨\u{200D}👩\u{200D}👧 ");
+        for c in text.chars() {
+            decorated.push(c);
+            if !c.is_whitespace() {
+                decorated.push('\u{1F3FD}');
+                decorated.push(VARIATION_SELECTOR);
+            }
+        }
+        decorated.push_str(" ❤\u{FE0F}\u{200D}🔥!!");
+        assert_eq!(decode(&decorated, &wordlist).unwrap(), words);
+
+        assert!(encode(&mut rng, &["nope".to_string()], &wordlist).is_err());
+        assert!(decode(&text, &wordlist[..1024]).is_err());
+    }
+}
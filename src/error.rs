@@ -0,0 +1,114 @@
+//! Failures callers may need to tell apart.
+//!
+//! Library functions return `anyhow::Result`. Where the cause matters to a
+//! caller (a script deciding whether to retry, re-transcribe or give up), the
+//! error is raised as a [`GlossiaError`], which front ends recover with
+//! `err.downcast_ref::<GlossiaError>()`. Everything else stays a plain
+//! message.
+
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GlossiaError {
+    /// A carrier word is not in the wordlist (`position` counts from 1)
+    UnknownWord { word: String, position: usize },
+    /// A trailing checksum word does not match the words before it
+    ChecksumMismatch { expected: String, found: String },
+    /// No carrier sequence within reach of a noisy transcription has a valid checksum
+    NoValidCandidate,
+    /// A phonetic-list word sits at a position of the wrong parity, so a word
+    /// before it was dropped, doubled or swapped (`position` counts from 1)
+    BrokenAlternation { word: String, position: usize },
+    /// The parity markers of these sentences (counting from 1) do not match
+    /// their carriers
+    ParityMismatch { sentences: Vec<usize> },
+    /// A signature is missing, malformed, or does not verify
+    BadSignature(String),
+    /// The passphrase unlocks no payload in the carrier
+    WrongPassphrase,
+    /// The payload's validity stamp expired at `expires`, before `now` (unix seconds)
+    Expired { expires: u64, now: u64 },
+    /// The text was cut short: its resync markers vouch for the first
+    /// `recovered` of `total` payload bytes
+    Truncated { recovered: usize, total: usize },
+}
+
+impl GlossiaError {
+    /// Short stable identifier, used as the CLI's `--json` error code.
+    pub fn code(&self) -> &'static str {
+        match self {
+            GlossiaError::UnknownWord { .. } => "unknown_word",
+            GlossiaError::ChecksumMismatch { .. }
+            | GlossiaError::NoValidCandidate
+            | GlossiaError::BrokenAlternation { .. }
+            | GlossiaError::ParityMismatch { .. } => "checksum",
+            GlossiaError::BadSignature(_) => "signature",
+            GlossiaError::WrongPassphrase => "passphrase",
+            GlossiaError::Expired { .. } => "expired",
+            GlossiaError::Truncated { .. } => "truncated",
+        }
+    }
+}
+
+impl fmt::Display for GlossiaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GlossiaError::UnknownWord { word, position } => {
+                write!(f, "'{}' is not in the wordlist (word {})", word, position)
+            }
+            GlossiaError::ChecksumMismatch { expected, found } => {
+                write!(f, "Checksum mismatch: expected '{}', found '{}'", expected, found)
+            }
+            GlossiaError::NoValidCandidate => {
+                write!(f, "No carrier sequence in the search beam matches its checksum")
+            }
+            GlossiaError::BrokenAlternation { word, position } => {
+                write!(f, "'{}' (word {}) breaks the even/odd alternation: a word before it was lost or repeated", word, position)
+            }
+            GlossiaError::ParityMismatch { sentences } => {
+                let list: Vec<String> = sentences.iter().map(usize::to_string).collect();
+                let noun = if sentences.len() == 1 { "sentence" } else { "sentences" };
+                write!(f, "{} {} failed parity", noun, list.join(", "))
+            }
+            GlossiaError::BadSignature(reason) => write!(f, "{}", reason),
+            GlossiaError::WrongPassphrase => write!(f, "No payload for this passphrase"),
+            GlossiaError::Expired { expires, now } => {
+                write!(f, "Payload expired at {} ({} seconds ago)", expires, now - expires)
+            }
+            GlossiaError::Truncated { recovered, total } => write!(
+                f,
+                "Text was cut short: recovered {} of {} payload bytes; resend from byte {}",
+                recovered, total, recovered
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GlossiaError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::codec;
+
+    fn wordlist() -> Vec<String> {
+        ["apple", "banana", "cherry", "date"].iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_codec_errors_downcast() {
+        let words: Vec<String> = ["apple", "kiwi"].iter().map(|w| w.to_string()).collect();
+        let err = codec::decode_bytes(&words, &wordlist()).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<GlossiaError>(),
+            Some(&GlossiaError::UnknownWord { word: "kiwi".to_string(), position: 2 })
+        );
+
+        let body: Vec<String> = vec!["banana".to_string(), "cherry".to_string()];
+        let mut framed = codec::append_checksum(&body, &wordlist()).unwrap();
+        let last = framed.len() - 1;
+        framed[last] = wordlist().into_iter().find(|w| *w != framed[last]).unwrap();
+        let err = codec::strip_checksum(&framed, &wordlist()).unwrap_err();
+        assert_eq!(err.downcast_ref::<GlossiaError>().map(GlossiaError::code), Some("checksum"));
+    }
+}
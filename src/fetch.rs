@@ -0,0 +1,147 @@
+//! Loading nlprule models in the browser (feature `fetch`, wasm32 only).
+//!
+//! [`GrammarChecker::from_fetch`] downloads the tokenizer and rules binaries and
+//! caches them in IndexedDB: one store maps each URL to the SHA-256 of the bytes
+//! it served, another maps that checksum to the bytes. Later page loads read the
+//! cache, re-checking the checksum, and only hit the network on a miss. Caching
+//! is best-effort; without IndexedDB (private browsing, quota) models are simply
+//! downloaded every time.
+
+use crate::GrammarChecker;
+use anyhow::{anyhow, bail, Context, Result};
+use js_sys::{Promise, Uint8Array};
+use nlprule::{Rules, Tokenizer};
+use sha2::{Digest, Sha256};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{IdbDatabase, IdbRequest, IdbTransactionMode, Response};
+
+const DB_NAME: &str = "glossia-models";
+/// SHA-256 (hex) -> model bytes
+const BLOBS: &str = "blobs";
+/// URL -> SHA-256 (hex) of the bytes it served
+const URLS: &str = "urls";
+
+impl GrammarChecker {
+    /// Create a new GrammarChecker from model binaries served at the given URLs,
+    /// using the IndexedDB cache when it holds them.
+    pub async fn from_fetch(tokenizer_url: &str, rules_url: &str) -> Result<Self> {
+        let db = open_db().await.ok();
+        let tokenizer_bytes = cached_fetch(db.as_ref(), tokenizer_url).await?;
+        let tokenizer = Tokenizer::from_reader(&tokenizer_bytes[..])
+            .with_context(|| format!("Failed to load tokenizer from {}", tokenizer_url))?;
+        let rules_bytes = cached_fetch(db.as_ref(), rules_url).await?;
+        let rules = Rules::from_reader(&rules_bytes[..])
+            .with_context(|| format!("Failed to load rules from {}", rules_url))?;
+
+        Ok(Self { tokenizer, rules, options: Default::default() })
+    }
+}
+
+fn js_err(e: JsValue) -> anyhow::Error {
+    anyhow!("{:?}", e)
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+async fn cached_fetch(db: Option<&IdbDatabase>, url: &str) -> Result<Vec<u8>> {
+    if let Some(bytes) = match db {
+        Some(db) => cached(db, url).await,
+        None => None,
+    } {
+        return Ok(bytes);
+    }
+
+    let bytes = fetch(url).await?;
+    if let Some(db) = db {
+        let checksum = sha256_hex(&bytes);
+        let _ = put(db, BLOBS, &checksum, &Uint8Array::from(&bytes[..]).into()).await;
+        let _ = put(db, URLS, url, &JsValue::from_str(&checksum)).await;
+    }
+    Ok(bytes)
+}
+
+async fn cached(db: &IdbDatabase, url: &str) -> Option<Vec<u8>> {
+    let checksum = get(db, URLS, url).await.ok()?.as_string()?;
+    let bytes = get(db, BLOBS, &checksum).await.ok()?.dyn_into::<Uint8Array>().ok()?.to_vec();
+    (sha256_hex(&bytes) == checksum).then_some(bytes)
+}
+
+async fn fetch(url: &str) -> Result<Vec<u8>> {
+    let window = web_sys::window().context("No window to fetch from")?;
+    let response: Response = JsFuture::from(window.fetch_with_str(url))
+        .await
+        .map_err(js_err)
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .unchecked_into();
+    if !response.ok() {
+        bail!("Failed to fetch {}: HTTP {}", url, response.status());
+    }
+    let buffer = JsFuture::from(response.array_buffer().map_err(js_err)?)
+        .await
+        .map_err(js_err)?;
+    Ok(Uint8Array::new(&buffer).to_vec())
+}
+
+async fn open_db() -> Result<IdbDatabase> {
+    let factory = web_sys::window()
+        .context("No window")?
+        .indexed_db()
+        .map_err(js_err)?
+        .context("IndexedDB is unavailable")?;
+    let open = factory.open_with_u32(DB_NAME, 1).map_err(js_err)?;
+    let upgrading = open.clone();
+    let on_upgrade = Closure::once_into_js(move || {
+        if let Ok(db) = upgrading.result() {
+            let db: IdbDatabase = db.unchecked_into();
+            let _ = db.create_object_store(BLOBS);
+            let _ = db.create_object_store(URLS);
+        }
+    });
+    open.set_onupgradeneeded(Some(on_upgrade.unchecked_ref()));
+    Ok(await_request(&open).await?.unchecked_into())
+}
+
+async fn get(db: &IdbDatabase, store: &str, key: &str) -> Result<JsValue> {
+    let tx = db.transaction_with_str(store).map_err(js_err)?;
+    let request = tx
+        .object_store(store)
+        .map_err(js_err)?
+        .get(&JsValue::from_str(key))
+        .map_err(js_err)?;
+    await_request(&request).await
+}
+
+async fn put(db: &IdbDatabase, store: &str, key: &str, value: &JsValue) -> Result<()> {
+    let tx = db
+        .transaction_with_str_and_mode(store, IdbTransactionMode::Readwrite)
+        .map_err(js_err)?;
+    let request = tx
+        .object_store(store)
+        .map_err(js_err)?
+        .put_with_key(value, &JsValue::from_str(key))
+        .map_err(js_err)?;
+    await_request(&request).await.map(|_| ())
+}
+
+/// Resolve once an IndexedDB request succeeds (with its result) or fails.
+async fn await_request(request: &IdbRequest) -> Result<JsValue> {
+    let promise = Promise::new(&mut |resolve, reject| {
+        let succeeded = request.clone();
+        let on_success = Closure::once_into_js(move || {
+            let result = succeeded.result().unwrap_or(JsValue::UNDEFINED);
+            let _ = resolve.call1(&JsValue::NULL, &result);
+        });
+        let failed = request.clone();
+        let on_error = Closure::once_into_js(move || {
+            let error = failed.error().ok().flatten().map_or(JsValue::UNDEFINED, JsValue::from);
+            let _ = reject.call1(&JsValue::NULL, &error);
+        });
+        request.set_onsuccess(Some(on_success.unchecked_ref()));
+        request.set_onerror(Some(on_error.unchecked_ref()));
+    });
+    JsFuture::from(promise).await.map_err(js_err)
+}
@@ -0,0 +1,99 @@
+//! Grouped hex fingerprints packed as their bytes, and written back exactly.
+//!
+//! Key and certificate fingerprints and MAC addresses are hex in groups:
+//! `3A:9F:0C:...` (TLS, MAC), `3a-9f-0c-...`, `3a9f.0c12.ab34` (Cisco),
+//! `4AEE 18F8 3AFD ...` (PGP). [`compact_fingerprint`] stores the separator,
+//! the casing and the group width in two bytes, then the bytes the hex
+//! spells; [`expand_fingerprint`] writes the same string back. Grouping that
+//! is not one width and one separator throughout, or mixed case, is refused.
+
+use anyhow::{anyhow, bail, Result};
+
+/// Separators between groups; a separator's index is what is stored
+const SEPARATORS: [char; 4] = [':', ' ', '-', '.'];
+
+/// Flag bit of an uppercase fingerprint, above the separator index
+const UPPERCASE: u8 = 4;
+
+/// Pack a fingerprint of two or more hex groups.
+pub fn compact_fingerprint(text: &str) -> Result<Vec<u8>> {
+    let separator = text
+        .chars()
+        .find(|c| !c.is_ascii_hexdigit())
+        .ok_or_else(|| anyhow!("Not grouped hex (no separator)"))?;
+    let separator_index = SEPARATORS
+        .iter()
+        .position(|&s| s == separator)
+        .ok_or_else(|| anyhow!("{:?} does not separate fingerprint groups", separator))?;
+    let groups: Vec<&str> = text.split(separator).collect();
+    let width = groups[0].len();
+    let (last, full) = groups.split_last().expect("split yields a group");
+    if width == 0 || width > 255 || full.iter().any(|group| group.len() != width) || last.is_empty() || last.len() > width {
+        bail!("Fingerprint groups are not of one width");
+    }
+    let digits: String = groups.concat();
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) || !digits.len().is_multiple_of(2) {
+        bail!("Fingerprint is not whole bytes of hex");
+    }
+    let upper = digits.bytes().any(|b| b.is_ascii_uppercase());
+    if upper && digits.bytes().any(|b| b.is_ascii_lowercase()) {
+        bail!("Fingerprint mixes upper and lower case hex");
+    }
+    let mut out = vec![separator_index as u8 | if upper { UPPERCASE } else { 0 }, width as u8];
+    for pair in digits.as_bytes().chunks(2) {
+        out.push(u8::from_str_radix(std::str::from_utf8(pair).expect("hex is ASCII"), 16).expect("checked hex"));
+    }
+    Ok(out)
+}
+
+/// The fingerprint [`compact_fingerprint`] packed.
+pub fn expand_fingerprint(packed: &[u8]) -> Result<String> {
+    let [style, width, bytes @ ..] = packed else {
+        bail!("Packed fingerprint is truncated");
+    };
+    let separator = *SEPARATORS.get((style & !UPPERCASE) as usize).ok_or_else(|| anyhow!("Unknown fingerprint separator {}", style))?;
+    if *width == 0 || bytes.is_empty() {
+        bail!("Packed fingerprint is empty");
+    }
+    let digits: String = bytes
+        .iter()
+        .map(|b| if style & UPPERCASE != 0 { format!("{:02X}", b) } else { format!("{:02x}", b) })
+        .collect();
+    let groups: Vec<&str> = digits
+        .as_bytes()
+        .chunks(*width as usize)
+        .map(|group| std::str::from_utf8(group).expect("hex is ASCII"))
+        .collect();
+    Ok(groups.join(&separator.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fingerprint_round_trip() {
+        for text in [
+            "3A:9F:0C:12:AB:34:56:78:9A:BC:DE:F0:11:22:33:44:55:66:77:88",
+            "3a-9f-0c-12-ab-34",
+            "3a9f.0c12.ab34",
+            "4AEE 18F8 3AFD EB23 B562 D5C6 9F2A 9E5B 0A36 9C1D",
+            "30:31:32",
+        ] {
+            let packed = compact_fingerprint(text).unwrap();
+            assert_eq!(expand_fingerprint(&packed).unwrap(), text);
+            assert!(packed.len() * 2 < text.len() + 4, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_refuses_other_text() {
+        assert!(compact_fingerprint("3a9f0c12").is_err());
+        assert!(compact_fingerprint("3A:9f:0C").is_err());
+        assert!(compact_fingerprint("3A:9F::0C").is_err());
+        assert!(compact_fingerprint("3A:9F-0C").is_err());
+        assert!(compact_fingerprint("3A9F:0C:12").is_err());
+        assert!(compact_fingerprint("Meet at noon").is_err());
+        assert!(expand_fingerprint(&[9, 2, 1]).is_err());
+    }
+}
@@ -0,0 +1,102 @@
+//! A footer telling a human recipient how to read the text.
+//!
+//! Someone who gets a cover text without knowing what it is has no way to
+//! find out. A footer is a short note below an email signature delimiter
+//! (a `-- ` line) naming the tool and the command that decodes the text.
+//! It is written from a template whose `{command}` is filled in, and may not
+//! use wordlist words, so it carries nothing; decoders [`strip`] it anyway,
+//! since layouts read lines and the command's option values (`list`, `age`)
+//! can be wordlist words.
+//!
+//! ```
+//! use glossia::footer;
+//!
+//! let wordlist = ["abandon".to_string(), "ability".to_string()].into_iter().collect();
+//! let note = footer::render(footer::DEFAULT_TEMPLATE, "glossia --decode -", &wordlist)?;
+//! let text = footer::append("The abandon ran.", &note);
+//! assert!(text.ends_with("\n-- \nWritten with glossia; decode it with: glossia --decode -"));
+//! assert_eq!(footer::strip(&text), "The abandon ran.");
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use crate::decode::normalize_token;
+use anyhow::{bail, Result};
+use std::borrow::Cow;
+use std::collections::HashSet;
+
+/// The note when no template is configured
+pub const DEFAULT_TEMPLATE: &str = "Written with glossia; decode it with: {command}";
+
+/// Placeholder for the decode command in a template
+pub const COMMAND: &str = "{command}";
+
+/// The line a footer starts below, as in email signatures
+pub const DELIMITER: &str = "-- ";
+
+/// `template` with its `{command}` filled in. Fails if the template has a
+/// blank line (which would end the footer early) or a word of `wordlist`.
+pub fn render(template: &str, command: &str, wordlist: &HashSet<String>) -> Result<String> {
+    if template.lines().any(|line| line.trim().is_empty()) {
+        bail!("Footer template cannot have blank lines");
+    }
+    let words = template.replace(COMMAND, " ");
+    if let Some(word) = words.split_whitespace().map(normalize_token).find(|word| wordlist.contains(word)) {
+        bail!("Footer template uses the wordlist word '{}', which would read as payload", word);
+    }
+    Ok(template.trim().replace(COMMAND, command))
+}
+
+/// `text` with `footer` below a delimiter line
+pub fn append(text: &str, footer: &str) -> String {
+    format!("{}\n\n{}\n{}", text.trim_end(), DELIMITER, footer)
+}
+
+/// `text` without its footers: each delimiter line (`--`, with or without
+/// the trailing space) and the lines after it up to a blank line, so the
+/// footers of texts pasted one after another all go.
+pub fn strip(text: &str) -> Cow<'_, str> {
+    if !text.lines().any(|line| line.trim() == "--") {
+        return Cow::Borrowed(text);
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut in_footer = false;
+    for line in text.split_inclusive('\n') {
+        if line.trim() == "--" {
+            in_footer = true;
+        } else if in_footer && line.trim().is_empty() {
+            in_footer = false;
+        } else if !in_footer {
+            out.push_str(line);
+        }
+    }
+    out.truncate(out.trim_end().len());
+    Cow::Owned(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wordlist() -> HashSet<String> {
+        ["abandon", "ability", "list", "note"].iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_render_refuses_carriers() {
+        let command = "glossia --decode - --output-style list";
+        let note = render("Decode with {command} (see the docs).", command, &wordlist()).unwrap();
+        assert_eq!(note, "Decode with glossia --decode - --output-style list (see the docs).");
+        assert!(render("A note for you: {command}", command, &wordlist()).is_err());
+        assert!(render("Decode with\n\n{command}", command, &wordlist()).is_err());
+    }
+
+    #[test]
+    fn test_strip_every_footer() {
+        let one = append("First abandon.", "Decode with glossia");
+        let two = append("Second ability.", "Decode with glossia");
+        assert_eq!(strip(&format!("{}\n\n{}", one, two)), "First abandon.\n\nSecond ability.");
+        // Retyped without the trailing space
+        assert_eq!(strip("Text here.\n--\nDecode with glossia"), "Text here.");
+        assert!(matches!(strip("No footer -- at all."), Cow::Borrowed(_)));
+    }
+}
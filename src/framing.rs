@@ -0,0 +1,189 @@
+//! Serialized structured payloads carried as they are, with a schema id.
+//!
+//! An application that already serializes its messages as MessagePack or
+//! Protobuf gains nothing from another packing step; what its receiver lacks
+//! is which deserializer to hand the bytes to. A [`Framing`] names the
+//! encoding and [`DataMode::Framed`](crate::data_mode::DataMode::Framed)
+//! stores it with a numeric schema id in the payload header, so
+//! [`data_mode::unpack_framed`](crate::data_mode::unpack_framed) can route the
+//! bytes without agreeing on them out of band. [`Framing::check`] only reads
+//! the wire format, enough to refuse bytes that are not the encoding named;
+//! the schema is the application's business.
+
+use anyhow::{anyhow, bail, Context, Result};
+
+/// Wire format of a framed payload
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Framing {
+    /// One MessagePack value
+    MsgPack,
+    /// A Protocol Buffers message
+    Protobuf,
+}
+
+impl Framing {
+    pub const ALL: &'static [Framing] = &[Framing::MsgPack, Framing::Protobuf];
+
+    /// The `--data-mode` name, which [`Framing::from_name`] parses
+    pub fn name(&self) -> &'static str {
+        match self {
+            Framing::MsgPack => "msgpack",
+            Framing::Protobuf => "protobuf",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|framing| framing.name() == name)
+    }
+
+    /// Check that `bytes` are well formed in this wire format.
+    pub fn check(&self, bytes: &[u8]) -> Result<()> {
+        match self {
+            Framing::MsgPack => check_msgpack(bytes),
+            Framing::Protobuf => check_protobuf(bytes),
+        }
+    }
+}
+
+/// Append `value` as a LEB128 varint, as schema ids are stored.
+pub(crate) fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// A LEB128 varint from the front of `bytes`, and the bytes after it.
+pub(crate) fn read_varint(bytes: &[u8]) -> Result<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(byte & 0x7f) << (7 * i);
+        if byte < 0x80 {
+            return Ok((value, &bytes[i + 1..]));
+        }
+    }
+    bail!("Truncated or overlong varint")
+}
+
+/// Append `text` with its length in front, as packed modes store free text.
+pub(crate) fn write_text(out: &mut Vec<u8>, text: &str) {
+    write_varint(out, text.len() as u64);
+    out.extend_from_slice(text.as_bytes());
+}
+
+/// Text [`write_text`] wrote at the front of `bytes`, and the bytes after it.
+pub(crate) fn read_text(bytes: &[u8]) -> Result<(&str, &[u8])> {
+    let (len, rest) = read_varint(bytes)?;
+    let text = rest.get(..len as usize).ok_or_else(|| anyhow!("Packed text is truncated"))?;
+    Ok((std::str::from_utf8(text).context("Packed text is not UTF-8")?, &rest[text.len()..]))
+}
+
+/// Exactly one MessagePack value, read without recursion.
+fn check_msgpack(bytes: &[u8]) -> Result<()> {
+    let be = |b: &[u8]| b.iter().fold(0usize, |n, &byte| n << 8 | byte as usize);
+    let mut rest = bytes;
+    // Values still to read; a map or array adds its elements
+    let mut pending = 1usize;
+    while pending > 0 {
+        pending -= 1;
+        let marker = *rest.first().ok_or_else(|| anyhow!("MessagePack value is truncated"))?;
+        rest = &rest[1..];
+        match marker {
+            0x00..=0x7f | 0xe0..=0xff | 0xc0 | 0xc2 | 0xc3 => {}
+            0x80..=0x8f => pending += 2 * (marker & 0x0f) as usize,
+            0x90..=0x9f => pending += (marker & 0x0f) as usize,
+            0xa0..=0xbf => rest = skip(rest, (marker & 0x1f) as usize)?,
+            0xc4..=0xc6 | 0xd9..=0xdb => {
+                let width = 1 << (if marker <= 0xc6 { marker - 0xc4 } else { marker - 0xd9 });
+                let len = be(rest.get(..width).unwrap_or_default());
+                rest = skip(skip(rest, width)?, len)?;
+            }
+            0xc7..=0xc9 => {
+                let width = 1 << (marker - 0xc7);
+                let len = be(rest.get(..width).unwrap_or_default());
+                rest = skip(skip(rest, width)?, 1 + len)?;
+            }
+            0xca => rest = skip(rest, 4)?,
+            0xcb => rest = skip(rest, 8)?,
+            0xcc..=0xcf => rest = skip(rest, 1 << (marker - 0xcc))?,
+            0xd0..=0xd3 => rest = skip(rest, 1 << (marker - 0xd0))?,
+            0xd4..=0xd8 => rest = skip(rest, 1 + (1 << (marker - 0xd4)))?,
+            0xdc..=0xdf => {
+                let width = if marker & 1 == 0 { 2 } else { 4 };
+                let len = be(rest.get(..width).unwrap_or_default());
+                rest = skip(rest, width)?;
+                pending += if marker >= 0xde { 2 * len } else { len };
+            }
+            0xc1 => bail!("0xc1 is not a MessagePack marker"),
+        }
+        // Every pending value takes at least a byte
+        if pending > rest.len() {
+            bail!("MessagePack value is truncated");
+        }
+    }
+    if !rest.is_empty() {
+        bail!("{} bytes after the MessagePack value", rest.len());
+    }
+    Ok(())
+}
+
+/// A sequence of Protobuf fields: varint, 64-bit, length-delimited or 32-bit.
+fn check_protobuf(bytes: &[u8]) -> Result<()> {
+    let mut rest = bytes;
+    while !rest.is_empty() {
+        let (key, tail) = read_varint(rest)?;
+        if key >> 3 == 0 {
+            bail!("Protobuf field number 0");
+        }
+        rest = match key & 7 {
+            0 => read_varint(tail)?.1,
+            1 => skip(tail, 8)?,
+            2 => {
+                let (len, body) = read_varint(tail)?;
+                skip(body, usize::try_from(len).unwrap_or(usize::MAX))?
+            }
+            5 => skip(tail, 4)?,
+            wire_type => bail!("Unsupported Protobuf wire type {}", wire_type),
+        };
+    }
+    Ok(())
+}
+
+/// `bytes` after the first `n`.
+fn skip(bytes: &[u8], n: usize) -> Result<&[u8]> {
+    bytes.get(n..).ok_or_else(|| anyhow!("Framed payload is truncated"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_msgpack() {
+        // {"id": 7, "tags": ["a", nil], "ok": true}
+        let value = b"\x83\xa2id\x07\xa4tags\x92\xa1a\xc0\xa2ok\xc3";
+        Framing::MsgPack.check(value).unwrap();
+        Framing::MsgPack.check(b"\xc4\x03abc").unwrap();
+        assert!(Framing::MsgPack.check(&value[..value.len() - 1]).is_err());
+        assert!(Framing::MsgPack.check(b"\x07\x07").is_err());
+        assert!(Framing::MsgPack.check(b"").is_err());
+        assert!(Framing::MsgPack.check(b"\xdd\xff\xff\xff\xff").is_err());
+    }
+
+    #[test]
+    fn test_check_protobuf() {
+        // field 1 = 150, field 2 = "testing", field 3 = fixed32
+        let message = b"\x08\x96\x01\x12\x07testing\x1d\x01\x02\x03\x04";
+        Framing::Protobuf.check(message).unwrap();
+        Framing::Protobuf.check(b"").unwrap();
+        assert!(Framing::Protobuf.check(&message[..6]).is_err());
+        assert!(Framing::Protobuf.check(b"\x0b\x0c").is_err());
+        assert!(Framing::Protobuf.check(b"\x00\x01").is_err());
+
+        let mut out = Vec::new();
+        write_varint(&mut out, 300);
+        assert_eq!(out, [0xac, 0x02]);
+        assert_eq!(read_varint(&out).unwrap(), (300, &[][..]));
+    }
+}
@@ -0,0 +1,85 @@
+//! Zipf-weighted choice of filler words.
+//!
+//! Cover words that carry no payload (determiners, conjunctions, modals, ...)
+//! were picked uniformly, so "each" turned up as often as "the" and every
+//! sentence had the same cadence. A [`ZipfTable`] ranks words by how common
+//! they are in ordinary English and weights a choice by `1 / rank^s`, so
+//! filler word distributions follow natural text.
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// Zipf exponent: close to 1 for English word frequencies
+pub const ZIPF_EXPONENT: f64 = 1.0;
+
+/// Word ranks from a frequency list.
+#[derive(Clone, Debug, Default)]
+pub struct ZipfTable {
+    ranks: HashMap<String, usize>,
+}
+
+impl ZipfTable {
+    /// Parse a list with one word per line, most frequent first. Blank lines
+    /// and lines starting with `#` are ignored; repeats keep their first rank.
+    pub fn parse(content: &str) -> Self {
+        let mut ranks = HashMap::new();
+        let words = content
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for word in words {
+            let rank = ranks.len() + 1;
+            ranks.entry(word.to_lowercase()).or_insert(rank);
+        }
+        Self { ranks }
+    }
+
+    /// 1-based rank of `word`; unlisted words share the rank after the last
+    pub fn rank(&self, word: &str) -> usize {
+        self.ranks.get(&word.to_lowercase()).copied().unwrap_or(self.ranks.len() + 1)
+    }
+
+    /// Relative weight of `word`, `1 / rank^ZIPF_EXPONENT`
+    pub fn weight(&self, word: &str) -> f64 {
+        (self.rank(word) as f64).powf(-ZIPF_EXPONENT)
+    }
+
+    /// Pick one of `candidates` with Zipf weights (`None` if there are none).
+    pub fn choose<'a, R: Rng + ?Sized>(&self, rng: &mut R, candidates: &[&'a str]) -> Option<&'a str> {
+        let weights = WeightedIndex::new(candidates.iter().map(|word| self.weight(word))).ok()?;
+        Some(candidates[weights.sample(rng)])
+    }
+}
+
+/// The English frequency list shipped with the crate (`languages/english/frequency.txt`).
+pub fn english() -> &'static ZipfTable {
+    static TABLE: OnceLock<ZipfTable> = OnceLock::new();
+    TABLE.get_or_init(|| ZipfTable::parse(include_str!("../languages/english/frequency.txt")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_choose_follows_rank() {
+        let table = english();
+        assert_eq!(table.rank("the"), 1);
+        assert!(table.rank("each") > table.rank("some"));
+        assert_eq!(table.rank("notaword"), table.rank("alsonotaword"));
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let candidates = ["the", "each", "those"];
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for _ in 0..2000 {
+            *counts.entry(table.choose(&mut rng, &candidates).unwrap()).or_insert(0) += 1;
+        }
+        assert!(counts["the"] > counts["each"] * 10, "{:?}", counts);
+        assert!(counts["each"] > 0 && counts["those"] > 0);
+        assert!(table.choose(&mut rng, &[]).is_none());
+    }
+}
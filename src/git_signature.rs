@@ -0,0 +1,250 @@
+//! Signed git commit and tag objects, with their signature blocks packed as
+//! bytes and written back exactly.
+//!
+//! A signed commit carries an ASCII-armored PGP or SSH signature in its
+//! `gpgsig` (or `gpgsig-sha256`) header, every line after the first indented
+//! by a space; a signed tag appends the block to its message unindented. An
+//! object's id is the hash of its exact bytes, so [`compact_signed_object`]
+//! keeps everything around the blocks as text and stores each block as the
+//! signature bytes plus what it takes to write the armor back: its kind,
+//! indent, line width, PGP armor headers and whether it had a CRC line.
+//! [`expand_signed_object`] writes the same bytes back, so the object hashes
+//! to the same id and its signature still verifies.
+
+use crate::framing::{read_text, read_varint, write_text, write_varint};
+use anyhow::{anyhow, bail, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+
+/// Armor markers of a PGP and an SSH signature
+const MARKERS: [(&str, &str); 2] = [
+    ("-----BEGIN PGP SIGNATURE-----", "-----END PGP SIGNATURE-----"),
+    ("-----BEGIN SSH SIGNATURE-----", "-----END SSH SIGNATURE-----"),
+];
+
+/// Commit headers holding a signature; a block with no header is a tag's
+const HEADERS: [&str; 3] = ["gpgsig ", "gpgsig-sha256 ", ""];
+
+/// Flag bits of a packed block
+const SSH: u8 = 1;
+const HAS_CRC: u8 = 2;
+const INDENTED: u8 = 4;
+
+/// An armored signature, apart from its markers
+struct Block {
+    flags: u8,
+    /// Base64 characters per line
+    width: usize,
+    /// PGP armor header lines (`Comment: ...`), joined by `\n`
+    headers: String,
+    signature: Vec<u8>,
+}
+
+/// Pack a git object holding one or more signature blocks.
+pub fn compact_signed_object(text: &str) -> Result<Vec<u8>> {
+    let mut blocks = Vec::new();
+    let mut rest = text;
+    while let Some((start, end, block)) = find_block(rest)? {
+        blocks.push((&rest[..start], block));
+        rest = &rest[end..];
+    }
+    if blocks.is_empty() {
+        bail!("No gpgsig or armored signature block");
+    }
+    let mut out = Vec::with_capacity(text.len());
+    write_varint(&mut out, blocks.len() as u64);
+    for (before, block) in &blocks {
+        write_text(&mut out, before);
+        out.push(block.flags);
+        out.push(u8::try_from(block.width).map_err(|_| anyhow!("Signature lines are too long"))?);
+        write_text(&mut out, &block.headers);
+        write_varint(&mut out, block.signature.len() as u64);
+        out.extend_from_slice(&block.signature);
+    }
+    write_text(&mut out, rest);
+    Ok(out)
+}
+
+/// The object [`compact_signed_object`] packed.
+pub fn expand_signed_object(packed: &[u8]) -> Result<String> {
+    let (count, mut rest) = read_varint(packed)?;
+    let mut out = String::new();
+    for _ in 0..count {
+        let (before, tail) = read_text(rest)?;
+        let [flags, width, tail @ ..] = tail else {
+            bail!("Packed signature is truncated");
+        };
+        let (headers, tail) = read_text(tail)?;
+        let (len, tail) = read_varint(tail)?;
+        let signature = tail.get(..len as usize).ok_or_else(|| anyhow!("Packed signature is truncated"))?;
+        rest = &tail[signature.len()..];
+        if *width == 0 {
+            bail!("Packed signature has no line width");
+        }
+        out.push_str(before);
+        let block = Block { flags: *flags, width: *width as usize, headers: headers.to_string(), signature: signature.to_vec() };
+        write_block(&block, &mut out);
+    }
+    let (after, rest) = read_text(rest)?;
+    if !rest.is_empty() {
+        bail!("Packed object has {} bytes left over", rest.len());
+    }
+    out.push_str(after);
+    Ok(out)
+}
+
+/// The first signature block in `text`: where its BEGIN marker starts, where
+/// its END marker ends, and the block.
+fn find_block(text: &str) -> Result<Option<(usize, usize, Block)>> {
+    let line_starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1));
+    for line_start in line_starts {
+        let line = text[line_start..].split('\n').next().unwrap_or_default();
+        for (kind, (begin, end)) in MARKERS.iter().enumerate() {
+            let Some(header) = HEADERS.iter().find(|header| line.strip_prefix(**header) == Some(*begin)) else {
+                continue;
+            };
+            let indent = if header.is_empty() { "" } else { " " };
+            let start = line_start + header.len();
+            let mut lines = Vec::new();
+            let mut offset = line_start + line.len();
+            for line in text[offset..].split('\n').skip(1) {
+                offset += 1 + line.len();
+                let Some(line) = line.strip_prefix(indent) else {
+                    break;
+                };
+                if line == *end {
+                    let mut block = parse_block(&lines, kind == 1)?;
+                    if !indent.is_empty() {
+                        block.flags |= INDENTED;
+                    }
+                    return Ok(Some((start, offset, block)));
+                }
+                lines.push(line);
+            }
+        }
+    }
+    Ok(None)
+}
+
+/// A block from the lines between its markers, indent removed.
+fn parse_block(lines: &[&str], ssh: bool) -> Result<Block> {
+    let mut flags = if ssh { SSH } else { 0 };
+    let mut body = lines;
+    let mut headers = String::new();
+    if !ssh {
+        let blank = lines.iter().position(|line| line.is_empty()).ok_or_else(|| anyhow!("PGP armor has no blank line"))?;
+        headers = lines[..blank].join("\n");
+        body = &lines[blank + 1..];
+    }
+    let mut crc = None;
+    if let (false, Some((last, rest))) = (ssh, body.split_last()) {
+        if let Some(checksum) = last.strip_prefix('=') {
+            crc = Some(checksum);
+            flags |= HAS_CRC;
+            body = rest;
+        }
+    }
+    let width = body.first().map_or(0, |line| line.len());
+    let (last, full) = body.split_last().ok_or_else(|| anyhow!("Signature block is empty"))?;
+    if width == 0 || full.iter().any(|line| line.len() != width) || last.is_empty() || last.len() > width {
+        bail!("Signature lines are not of one width");
+    }
+    let signature = BASE64.decode(body.concat())?;
+    if crc.is_some_and(|crc| crc != BASE64.encode(&crc24(&signature).to_be_bytes()[1..])) {
+        bail!("PGP armor checksum does not match");
+    }
+    Ok(Block { flags, width, headers, signature })
+}
+
+fn write_block(block: &Block, out: &mut String) {
+    let (begin, end) = MARKERS[(block.flags & SSH) as usize];
+    let indent = if block.flags & INDENTED != 0 { " " } else { "" };
+    let mut lines = Vec::new();
+    if block.flags & SSH == 0 {
+        lines.extend(block.headers.split('\n').filter(|_| !block.headers.is_empty()).map(str::to_string));
+        lines.push(String::new());
+    }
+    let encoded = BASE64.encode(&block.signature);
+    lines.extend(encoded.as_bytes().chunks(block.width).map(|line| String::from_utf8_lossy(line).into_owned()));
+    if block.flags & HAS_CRC != 0 {
+        lines.push(format!("={}", BASE64.encode(&crc24(&block.signature).to_be_bytes()[1..])));
+    }
+    lines.push(end.to_string());
+    out.push_str(begin);
+    for line in lines {
+        out.push('\n');
+        out.push_str(indent);
+        out.push_str(&line);
+    }
+}
+
+/// The OpenPGP armor checksum (RFC 4880, section 6.1).
+fn crc24(data: &[u8]) -> u32 {
+    let mut crc = 0xB704CEu32;
+    for &byte in data {
+        crc ^= (byte as u32) << 16;
+        for _ in 0..8 {
+            crc <<= 1;
+            if crc & 0x1000000 != 0 {
+                crc ^= 0x1864CFB;
+            }
+        }
+    }
+    crc & 0xFFFFFF
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEAD: &str = "tree 9bedf67800b2923982bdf60c89c57ce6d15e3ba6\n\
+        parent 1d8b6f2b1b3a5e0a1b2c3d4e5f60718293a4b5c6\n\
+        author A U Thor <author@example.com> 1700000000 +0000\n\
+        committer A U Thor <author@example.com> 1700000000 +0000\n";
+
+    /// `lines` as git indents a header's continuation lines
+    fn indented(lines: &[String]) -> String {
+        lines.iter().map(|line| format!(" {}", line)).collect::<Vec<_>>().join("\n")
+    }
+
+    fn base64_lines(data: &[u8], width: usize) -> Vec<String> {
+        BASE64.encode(data).as_bytes().chunks(width).map(|line| String::from_utf8_lossy(line).into_owned()).collect()
+    }
+
+    #[test]
+    fn test_signed_commit_round_trip() {
+        let signature: Vec<u8> = (0..310u32).map(|i| (i * 7 % 251) as u8).collect();
+        assert_eq!(crc24(b"123456789"), 0x21CF02);
+
+        // PGP: blank line as a lone space, 64 columns, CRC
+        let mut pgp = vec![String::new()];
+        pgp.extend(base64_lines(&signature, 64));
+        pgp.push(format!("={}", BASE64.encode(&crc24(&signature).to_be_bytes()[1..])));
+        pgp.push(MARKERS[0].1.to_string());
+        // SSH: 70 columns, no blank line
+        let mut ssh = base64_lines(&signature, 70);
+        ssh.push(MARKERS[1].1.to_string());
+
+        for (header, marker, lines) in [("gpgsig", MARKERS[0].0, &pgp), ("gpgsig-sha256", MARKERS[1].0, &ssh)] {
+            let commit = format!("{}{} {}\n{}\n\nAdd the thing\n\nLonger body.\n", HEAD, header, marker, indented(lines));
+            let packed = compact_signed_object(&commit).unwrap();
+            assert_eq!(expand_signed_object(&packed).unwrap(), commit);
+            assert!(packed.len() * 5 < commit.len() * 4, "{} of {} bytes", packed.len(), commit.len());
+        }
+
+        // A tag: the block follows the message, unindented
+        let tag = format!("object 9bedf67800b2923982bdf60c89c57ce6d15e3ba6\ntype commit\ntag v1.0\n\nRelease\n{}\n{}\n", MARKERS[0].0, pgp.join("\n"));
+        assert_eq!(expand_signed_object(&compact_signed_object(&tag).unwrap()).unwrap(), tag);
+    }
+
+    #[test]
+    fn test_refuses_other_objects() {
+        assert!(compact_signed_object(HEAD).is_err());
+        let lines = base64_lines(&[5; 100], 64);
+        // Ragged lines and a checksum that does not match
+        let ragged = format!("{}gpgsig {}\n \n{}\n {}\n", HEAD, MARKERS[0].0, indented(&[lines[2].clone(), lines[0].clone()]), MARKERS[0].1);
+        assert!(compact_signed_object(&ragged).is_err());
+        let bad_crc = format!("{}gpgsig {}\n \n{}\n =AAAA\n {}\n", HEAD, MARKERS[0].0, indented(&lines), MARKERS[0].1);
+        assert!(compact_signed_object(&bad_crc).is_err());
+    }
+}
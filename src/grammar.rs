@@ -1,9 +1,12 @@
 use pest::Parser;
 use pest_derive::Parser;
 use rand::Rng;
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
 use std::path::Path;
+use std::sync::OnceLock;
 use glossia::types::{Pos, Sym};
+use glossia::weights::SlotMap;
 
 #[derive(Parser)]
 #[grammar = "grammar_parser.pest"]
@@ -23,8 +26,24 @@ pub struct GrammarRule {
 #[derive(Debug)]
 pub struct Grammar {
     pub(crate) rules: HashMap<String, GrammarRule>,
+    /// [`Grammar::compile`], built on first use
+    compiled: OnceLock<CompiledGrammar>,
 }
 
+/// DP memo for sequence enumeration: (nonterminal id, remaining_length) -> (sequence, probability)
+type SequenceMemo = HashMap<(usize, usize), Vec<(Vec<crate::Pos>, f64)>>;
+
+/// A sampled derivation: the POS slots it produced and which productions fired.
+#[derive(Clone, Debug)]
+pub struct Derivation {
+    pub slots: Vec<crate::Pos>,
+    /// (nonterminal, production index) for every expansion, in depth-first order.
+    pub productions: Vec<(String, usize)>,
+}
+
+/// Best derivation of a span found so far: its probability and productions.
+type SpanParse = Option<(f64, Vec<(String, usize)>)>;
+
 /// A POS sequence with its probability according to the grammar
 #[derive(Clone, Debug)]
 pub struct SequenceWithProbability {
@@ -32,6 +51,64 @@ pub struct SequenceWithProbability {
     pub probability: f64,
 }
 
+/// Longest sentence [`Grammar::entropy_report`] enumerates, in slots (the
+/// CLI's default `--k-max`)
+pub const REPORT_MAX_SLOTS: usize = 20;
+
+/// What one POS slot can carry.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotCapacity {
+    pub pos: Pos,
+    /// Wordlist words the slot admits
+    pub words: usize,
+    /// log2 of `words`: the bits a carrier in this slot chooses among
+    pub bits: f64,
+    /// Share of the wordlist the slot admits, the chance a payload word fits
+    pub fit: f64,
+}
+
+/// Sentences of one length, as the grammar weighs them.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LengthCapacity {
+    pub slots: usize,
+    /// Probability the grammar derives a sentence this long
+    pub probability: f64,
+    /// Mean bits of its slots' [`SlotCapacity::bits`], over its sentences
+    pub bits: f64,
+}
+
+/// The payload density of a grammar under a wordlist's slot assignment
+/// ([`Grammar::entropy_report`]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntropyReport {
+    /// The [`SlotMap`] threshold the slots were filled at
+    pub threshold: f64,
+    /// Every POS terminal the grammar uses
+    pub slots: Vec<SlotCapacity>,
+    /// Lengths up to [`REPORT_MAX_SLOTS`] the grammar derives
+    pub lengths: Vec<LengthCapacity>,
+    /// Probability of all those lengths (below 1 when recursion reaches past them)
+    pub coverage: f64,
+    /// Expected slots per sentence, among sentences up to the limit
+    pub slots_per_sentence: f64,
+    /// Expected bits per sentence, among sentences up to the limit
+    pub bits_per_sentence: f64,
+}
+
+impl fmt::Display for EntropyReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Slot capacity (threshold {}):", self.threshold)?;
+        for slot in &self.slots {
+            writeln!(f, "  {:<7} {:>5} words  {:5.2} bits  ({:5.1}% of the wordlist)", slot.pos.name(), slot.words, slot.bits, 100.0 * slot.fit)?;
+        }
+        writeln!(f, "Sentence lengths ({:.1}% of the grammar's probability):", 100.0 * self.coverage)?;
+        for length in &self.lengths {
+            writeln!(f, "  {:>2} slots  {:5.1}%  {:6.2} bits", length.slots, 100.0 * length.probability, length.bits)?;
+        }
+        writeln!(f, "Per sentence: {:.2} slots, {:.2} bits", self.slots_per_sentence, self.bits_per_sentence)
+    }
+}
+
 impl Grammar {
     /// Parse grammar from a string definition
     pub fn from_str(grammar_str: &str) -> Result<Self, Box<dyn std::error::Error>> {
@@ -123,7 +200,7 @@ impl Grammar {
             }
         }
         
-        Ok(Grammar { rules })
+        Ok(Grammar { rules, compiled: OnceLock::new() })
     }
     
     /// Load grammar from the embedded body.cfg file
@@ -180,18 +257,116 @@ impl Grammar {
     #[allow(dead_code)]
     pub fn expand<R: Rng>(&self, rng: &mut R, non_terminal: &str) -> Option<Vec<Sym>> {
         let rule = self.rules.get(non_terminal)?;
+        let idx = choose_production(rng, rule)?;
+        Some(rule.productions[idx].symbols.clone())
+    }
 
-        // Select production based on weights
-        let mut rand_val = rng.gen::<f64>();
-        for prod in &rule.productions {
-            rand_val -= prod.weight;
-            if rand_val <= 0.0 {
-                return Some(prod.symbols.clone());
+    /// Sample a complete derivation from `start_symbol`.
+    ///
+    /// Productions are chosen by weight and optional symbols are included with
+    /// probability 0.5 (the same convention the sequence enumerator uses). Returns
+    /// `None` if the derivation references an undefined nonterminal or grows past
+    /// `max_slots` terminals (which bounds recursive rules like `VP_PP_TAIL`).
+    #[allow(dead_code)]
+    pub fn sample_derivation<R: Rng>(
+        &self,
+        rng: &mut R,
+        start_symbol: &str,
+        max_slots: usize,
+    ) -> Option<Derivation> {
+        fn sample_symbol<R: Rng>(
+            grammar: &Grammar,
+            rng: &mut R,
+            sym: &Sym,
+            max_slots: usize,
+            out: &mut Derivation,
+        ) -> Option<()> {
+            match sym {
+                Sym::T(pos) => {
+                    out.slots.push(*pos);
+                    if out.slots.len() > max_slots {
+                        return None;
+                    }
+                }
+                Sym::Opt(inner) => {
+                    if rng.gen_bool(0.5) {
+                        sample_symbol(grammar, rng, inner, max_slots, out)?;
+                    }
+                }
+                Sym::NT(nt) => {
+                    let rule = grammar.rules.get(nt)?;
+                    let idx = choose_production(rng, rule)?;
+                    out.productions.push((nt.clone(), idx));
+                    for child in &rule.productions[idx].symbols {
+                        sample_symbol(grammar, rng, child, max_slots, out)?;
+                    }
+                }
             }
+            Some(())
         }
 
-        // Fallback to first production
-        rule.productions.first().map(|p| p.symbols.clone())
+        let mut derivation = Derivation {
+            slots: Vec::new(),
+            productions: Vec::new(),
+        };
+        sample_symbol(self, rng, &Sym::NT(start_symbol.to_string()), max_slots, &mut derivation)?;
+        Some(derivation)
+    }
+
+    /// Number of productions defined for a nonterminal (0 if undefined).
+    #[allow(dead_code)]
+    pub fn production_count(&self, non_terminal: &str) -> usize {
+        self.rules.get(non_terminal).map_or(0, |r| r.productions.len())
+    }
+
+    /// Nonterminal names, sorted.
+    #[allow(dead_code)]
+    pub fn non_terminals(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.rules.keys().map(|k| k.as_str()).collect();
+        names.sort();
+        names
+    }
+
+    /// Flatten the grammar into a [`CompiledGrammar`] for repeated sampling
+    /// and sequence enumeration.
+    pub fn compile(&self) -> CompiledGrammar {
+        let names: Vec<String> = self.non_terminals().into_iter().map(str::to_string).collect();
+        let ids: HashMap<String, usize> = names.iter().enumerate().map(|(i, n)| (n.clone(), i)).collect();
+        let step = |sym: &Sym| match sym {
+            Sym::T(pos) => Step::Slot(*pos),
+            Sym::NT(nt) => ids.get(nt).map_or(Step::Undefined, |&id| Step::Call(id)),
+            // The parser never nests optionals
+            Sym::Opt(_) => Step::Undefined,
+        };
+
+        let mut rules = Vec::with_capacity(names.len());
+        let mut productions = Vec::new();
+        let mut steps = Vec::new();
+        for name in &names {
+            let first = productions.len();
+            let mut cumulative = 0.0;
+            for prod in &self.rules[name].productions {
+                cumulative += prod.weight;
+                let start = steps.len();
+                for sym in &prod.symbols {
+                    if let Sym::Opt(inner) = sym {
+                        steps.push(Step::Opt);
+                        steps.push(step(inner));
+                    } else {
+                        steps.push(step(sym));
+                    }
+                }
+                productions.push((cumulative, prod.weight, start, steps.len()));
+            }
+            rules.push((first, productions.len()));
+        }
+
+        CompiledGrammar { names, ids, rules, productions, steps }
+    }
+
+    /// The [`CompiledGrammar`], compiled on the first call and shared after.
+    pub fn compiled(&self) -> &CompiledGrammar {
+        self.compiled.get_or_init(|| self.compile())
     }
     
     /// Enumerate all valid POS sequences of exactly length k with their probabilities.
@@ -202,10 +377,7 @@ impl Grammar {
         start_symbol: &str,
         k: usize,
     ) -> Vec<SequenceWithProbability> {
-        // Memoization: (nonterminal, remaining_length) -> (sequence, probability)
-        let mut memo: HashMap<(String, usize), Vec<(Vec<crate::Pos>, f64)>> = HashMap::new();
-
-        self.enumerate_sequences_with_probability_internal(start_symbol, k, &mut memo)
+        self.compiled().enumerate_sequences_with_probability(start_symbol, k)
     }
 
     /// Precompute POS sequences (and their probabilities) for lengths 0..=max_k.
@@ -214,165 +386,155 @@ impl Grammar {
     /// - **k → [POS sequences of length k]**
     ///
     /// The mapping is fully determined by the grammar file, so you can cache it
-    /// per grammar (and per start symbol). Internally this shares the same DP memo
-    /// across k values, which makes `max_k` up to ~20 cheap.
+    /// per grammar (and per start symbol). The work runs over the
+    /// [`CompiledGrammar`] tables; see
+    /// [`CompiledGrammar::precompute_sequences_with_probability`].
     pub fn precompute_sequences_with_probability(
         &self,
         start_symbol: &str,
         max_k: usize,
     ) -> Vec<Vec<SequenceWithProbability>> {
-        // Memoization: (nonterminal, remaining_length) -> (sequence, probability)
-        let mut memo: HashMap<(String, usize), Vec<(Vec<crate::Pos>, f64)>> = HashMap::new();
-
-        let mut by_k: Vec<Vec<SequenceWithProbability>> = vec![Vec::new(); max_k + 1];
-        for k in 0..=max_k {
-            by_k[k] = self.enumerate_sequences_with_probability_internal(start_symbol, k, &mut memo);
-        }
-        by_k
+        self.compiled().precompute_sequences_with_probability(start_symbol, max_k)
     }
-
-    fn enumerate_sequences_with_probability_internal(
-        &self,
-        start_symbol: &str,
-        k: usize,
-        memo: &mut HashMap<(String, usize), Vec<(Vec<crate::Pos>, f64)>>,
-    ) -> Vec<SequenceWithProbability> {
-        fn enumerate_recursive(
-            grammar: &Grammar,
-            sym: &Sym,
-            remaining: usize,
-            memo: &mut HashMap<(String, usize), Vec<(Vec<crate::Pos>, f64)>>,
-        ) -> Vec<(Vec<crate::Pos>, f64)> {
+    
+    /// How many bits a sentence can choose among: each slot the grammar
+    /// derives holds log2 of the `wordlist` words `weights` admits to it,
+    /// summed over a sentence and weighed by the probability of its
+    /// derivation from `S`. Raising the [`SlotMap`] threshold shrinks slots,
+    /// so comparing reports at two thresholds shows what a restriction costs.
+    pub fn entropy_report(&self, wordlist: &[String], weights: &SlotMap) -> EntropyReport {
+        fn terminals(sym: &Sym, out: &mut BTreeSet<Pos>) {
             match sym {
                 Sym::T(pos) => {
-                    if remaining == 1 {
-                        vec![(vec![*pos], 1.0)] // Terminal has probability 1.0
-                    } else {
-                        Vec::new()
-                    }
-                }
-                Sym::Opt(inner) => {
-                    let mut results = Vec::new();
-                    
-                    // Include the optional symbol (probability 0.5)
-                    let include_results = enumerate_recursive(grammar, inner, remaining, memo)
-                        .into_iter()
-                        .map(|(seq, prob)| (seq, prob * 0.5))
-                        .collect::<Vec<_>>();
-                    results.extend(include_results);
-                    
-                    // Exclude the optional symbol (probability 0.5, produces empty)
-                    if remaining == 0 {
-                        results.push((Vec::new(), 0.5));
-                    }
-                    
-                    results
+                    out.insert(*pos);
                 }
-                Sym::NT(nt) => {
-                    let key = (nt.clone(), remaining);
-                    if let Some(cached) = memo.get(&key) {
-                        return cached.clone();
+                Sym::Opt(inner) => terminals(inner, out),
+                Sym::NT(_) => {}
+            }
+        }
+        let mut used = BTreeSet::new();
+        for sym in self.rules.values().flat_map(|rule| &rule.productions).flat_map(|prod| &prod.symbols) {
+            terminals(sym, &mut used);
+        }
+
+        let known: HashSet<&str> = wordlist.iter().map(String::as_str).collect();
+        let slots: Vec<SlotCapacity> = used
+            .into_iter()
+            .map(|pos| {
+                let words = weights.words(pos).iter().filter(|word| known.contains(word.as_str())).count();
+                let bits = if words > 0 { (words as f64).log2() } else { 0.0 };
+                SlotCapacity { pos, words, bits, fit: words as f64 / wordlist.len().max(1) as f64 }
+            })
+            .collect();
+        let bits_of: HashMap<Pos, f64> = slots.iter().map(|slot| (slot.pos, slot.bits)).collect();
+
+        let lengths: Vec<LengthCapacity> = self
+            .precompute_sequences_with_probability("S", REPORT_MAX_SLOTS)
+            .into_iter()
+            .enumerate()
+            .filter_map(|(k, sequences)| {
+                let probability: f64 = sequences.iter().map(|s| s.probability).sum();
+                let weighted: f64 = sequences
+                    .iter()
+                    .map(|s| s.probability * s.sequence.iter().map(|pos| bits_of[pos]).sum::<f64>())
+                    .sum();
+                (probability > 0.0).then(|| LengthCapacity { slots: k, probability, bits: weighted / probability })
+            })
+            .collect();
+        let coverage: f64 = lengths.iter().map(|l| l.probability).sum();
+        let mean = |value: &dyn Fn(&LengthCapacity) -> f64| {
+            if coverage > 0.0 {
+                lengths.iter().map(|l| l.probability * value(l)).sum::<f64>() / coverage
+            } else {
+                0.0
+            }
+        };
+        EntropyReport {
+            threshold: weights.threshold(),
+            slots_per_sentence: mean(&|l| l.slots as f64),
+            bits_per_sentence: mean(&|l| l.bits),
+            slots,
+            lengths,
+            coverage,
+        }
+    }
+
+    /// The most probable derivation of the POS sequence `slots` from
+    /// `start_symbol`, with its probability, or `None` if the grammar cannot
+    /// derive it. An optional symbol counts 0.5 whether taken or skipped, as
+    /// in the sequence enumerator, so the probability is the one
+    /// [`Grammar::precompute_sequences_with_probability`] sums over.
+    pub fn parse_sequence(&self, start_symbol: &str, slots: &[Pos]) -> Option<(Derivation, f64)> {
+        fn better(a: SpanParse, b: SpanParse) -> SpanParse {
+            match (a, b) {
+                (Some(a), Some(b)) => Some(if b.0 > a.0 { b } else { a }),
+                (a, b) => a.or(b),
+            }
+        }
+
+        struct Parser<'a> {
+            grammar: &'a Grammar,
+            slots: &'a [Pos],
+            /// (nonterminal, start, end) -> best parse of that span
+            memo: HashMap<(String, usize, usize), SpanParse>,
+        }
+
+        impl Parser<'_> {
+            fn symbol(&mut self, sym: &Sym, i: usize, j: usize) -> SpanParse {
+                match sym {
+                    Sym::T(pos) => (j == i + 1 && self.slots[i] == *pos).then(|| (1.0, Vec::new())),
+                    Sym::Opt(inner) => {
+                        let taken = self.symbol(inner, i, j).map(|(p, productions)| (0.5 * p, productions));
+                        better(taken, (i == j).then(|| (0.5, Vec::new())))
                     }
-                    
-                    let rule = match grammar.rules.get(nt) {
-                        Some(r) => r,
-                        None => return Vec::new(),
-                    };
-                    
-                    let mut all_results = Vec::new();
-                    
-                    // Try each production
-                    for prod in &rule.productions {
-                        let prod_weight = prod.weight; // Already normalized to probability
-                        
-                        // Recursively enumerate for each symbol in the production
-                        // We need to try all ways to distribute remaining slots across symbols
-                        let mut production_results = vec![(Vec::new(), 1.0)];
-                        
-                        for symbol in &prod.symbols {
-                            let mut new_results = Vec::new();
-                            
-                            for (partial_seq, partial_prob) in production_results {
-                                let used = partial_seq.len();
-                                let available = remaining.saturating_sub(used);
-                                
-                                // Try allocating 0 to available slots to this symbol
-                                for symbol_slots in 0..=available {
-                                    let symbol_results = enumerate_recursive(
-                                        grammar,
-                                        symbol,
-                                        symbol_slots,
-                                        memo,
-                                    );
-                                    
-                                    for (symbol_seq, symbol_prob) in symbol_results {
-                                        // Symbol must use exactly the allocated slots
-                                        if symbol_seq.len() != symbol_slots {
-                                            continue;
-                                        }
-                                        
-                                        let mut combined = partial_seq.clone();
-                                        combined.extend(symbol_seq);
-                                        
-                                        // Only keep if we haven't exceeded remaining
-                                        if combined.len() <= remaining {
-                                            let combined_prob = partial_prob * symbol_prob;
-                                            new_results.push((combined, combined_prob));
-                                        }
-                                    }
-                                }
-                            }
-                            
-                            production_results = new_results;
+                    Sym::NT(nt) => {
+                        let key = (nt.clone(), i, j);
+                        if let Some(parsed) = self.memo.get(&key) {
+                            return parsed.clone();
                         }
-                        
-                        // Multiply by production weight and filter to exact length
-                        for (seq, symbol_prob) in production_results {
-                            if seq.len() == remaining {
-                                // Final probability = production weight * product of symbol probabilities
-                                let final_prob = prod_weight * symbol_prob;
-                                all_results.push((seq, final_prob));
+                        // A rule reached again on the same span adds nothing
+                        self.memo.insert(key.clone(), None);
+                        let rule = self.grammar.rules.get(nt)?;
+                        let mut best = None;
+                        for (idx, prod) in rule.productions.iter().enumerate() {
+                            if let Some((p, children)) = self.sequence(&prod.symbols, i, j) {
+                                let productions = std::iter::once((nt.clone(), idx)).chain(children).collect();
+                                best = better(best, Some((prod.weight * p, productions)));
                             }
                         }
+                        self.memo.insert(key, best.clone());
+                        best
                     }
-                    
-                    // Deduplicate: sum probabilities for identical sequences
-                    let mut prob_map: HashMap<Vec<crate::Pos>, f64> = HashMap::new();
-                    for (seq, prob) in all_results {
-                        *prob_map.entry(seq).or_insert(0.0) += prob;
-                    }
-                    
-                    let final_results: Vec<(Vec<crate::Pos>, f64)> = prob_map.into_iter().collect();
-                    memo.insert(key, final_results.clone());
-                    
-                    final_results
                 }
             }
+
+            fn sequence(&mut self, symbols: &[Sym], i: usize, j: usize) -> SpanParse {
+                let Some((first, rest)) = symbols.split_first() else {
+                    return (i == j).then(|| (1.0, Vec::new()));
+                };
+                let mut best = None;
+                for k in i..=j {
+                    let Some((p, mut productions)) = self.symbol(first, i, k) else { continue };
+                    let Some((q, tail)) = self.sequence(rest, k, j) else { continue };
+                    productions.extend(tail);
+                    best = better(best, Some((p * q, productions)));
+                }
+                best
+            }
         }
-        
-        let start_sym = Sym::NT(start_symbol.to_string());
-        let results = enumerate_recursive(self, &start_sym, k, memo);
-        
-        // Deduplicate final results and convert to SequenceWithProbability
-        let mut prob_map: HashMap<Vec<crate::Pos>, f64> = HashMap::new();
-        for (seq, prob) in results {
-            *prob_map.entry(seq).or_insert(0.0) += prob;
-        }
-        
-        let mut sequences: Vec<SequenceWithProbability> = prob_map
-            .into_iter()
-            .map(|(sequence, probability)| SequenceWithProbability {
-                sequence,
-                probability,
-            })
-            .collect();
-        
-        // Sort by probability (highest first)
-        sequences.sort_by(|a, b| b.probability.partial_cmp(&a.probability).unwrap_or(std::cmp::Ordering::Equal));
-        
-        sequences
+
+        let mut parser = Parser { grammar: self, slots, memo: HashMap::new() };
+        let (probability, productions) = parser.symbol(&Sym::NT(start_symbol.to_string()), 0, slots.len())?;
+        Some((Derivation { slots: slots.to_vec(), productions }, probability))
     }
-    
+
+    /// Production `idx` of `non_terminal` as a rule line, `NP = Det Adj N`.
+    pub fn production_text(&self, non_terminal: &str, idx: usize) -> Option<String> {
+        let prod = self.rules.get(non_terminal)?.productions.get(idx)?;
+        let symbols: Vec<String> = prod.symbols.iter().map(symbol_text).collect();
+        Some(format!("{} = {}", non_terminal, symbols.join(" ")))
+    }
+
     /// Format the grammar rules in a concise text representation
     pub fn format_concise(&self) -> String {
         let mut output = String::new();
@@ -387,34 +549,7 @@ impl Grammar {
             output.push_str(&format!("{} = ", non_terminal));
             
             let productions_str: Vec<String> = rule.productions.iter().map(|prod| {
-                let symbols_str: Vec<String> = prod.symbols.iter().map(|sym| {
-                    match sym {
-                        Sym::T(pos) => {
-                            // Format POS tags concisely
-                            match pos {
-                                Pos::Det => "Det".to_string(),
-                                Pos::Adj => "Adj".to_string(),
-                                Pos::N => "N".to_string(),
-                                Pos::V => "V".to_string(),
-                                Pos::Modal => "Modal".to_string(),
-                                Pos::Aux => "Aux".to_string(),
-                                Pos::Cop => "Cop".to_string(),
-                                Pos::To => "To".to_string(),
-                                Pos::Prep => "Prep".to_string(),
-                                Pos::Adv => "Adv".to_string(),
-                                Pos::Dot => "Dot".to_string(),
-                                Pos::Prefix => "Prefix".to_string(),
-                                Pos::Conj => "Conj".to_string(),
-                            }
-                        }
-                        Sym::NT(nt) => nt.clone(),
-                        Sym::Opt(inner) => match &**inner {
-                            Sym::T(pos) => format!("{:?}?", pos),
-                            Sym::NT(nt) => format!("{}?", nt),
-                            Sym::Opt(_) => "Opt?".to_string(),
-                        },
-                    }
-                }).collect();
+                let symbols_str: Vec<String> = prod.symbols.iter().map(symbol_text).collect();
                 let prod_str = symbols_str.join(" ");
                 if (prod.weight - 1.0).abs() > 0.001 {
                     format!("({:.2}: {})", prod.weight, prod_str)
@@ -429,52 +564,443 @@ impl Grammar {
         
         output
     }
+
+    /// Render the grammar as a Graphviz DOT digraph.
+    ///
+    /// Nonterminals are boxes, POS terminals are ellipses, and each production is a
+    /// small point node labelled with its probability. Edges from a production to its
+    /// symbols are numbered by position; optional symbols use dashed edges.
+    /// Nonterminals referenced but never defined are drawn in red, and rules that
+    /// cannot be reached from `S` are greyed out, so coverage gaps are easy to spot.
+    #[allow(dead_code)]
+    pub fn to_dot(&self) -> String {
+        self.render_dot(None)
+    }
+
+    /// Like [`Grammar::to_dot`], but annotates each POS terminal with its slot
+    /// entropy in bits (e.g. log2 of the number of words that can fill that slot).
+    pub fn to_dot_with_entropy(&self, slot_entropy: &HashMap<Pos, f64>) -> String {
+        self.render_dot(Some(slot_entropy))
+    }
+
+    fn render_dot(&self, slot_entropy: Option<&HashMap<Pos, f64>>) -> String {
+        fn symbol_target(sym: &Sym) -> (String, bool) {
+            match sym {
+                Sym::NT(nt) => (format!("\"{}\"", nt), false),
+                Sym::T(pos) => (format!("\"pos:{:?}\"", pos), false),
+                Sym::Opt(inner) => (symbol_target(inner).0, true),
+            }
+        }
+
+        fn collect_refs<'a>(sym: &'a Sym, nts: &mut Vec<&'a str>, terminals: &mut Vec<Pos>) {
+            match sym {
+                Sym::NT(nt) => nts.push(nt),
+                Sym::T(pos) => terminals.push(*pos),
+                Sym::Opt(inner) => collect_refs(inner, nts, terminals),
+            }
+        }
+
+        let mut rules: Vec<_> = self.rules.iter().collect();
+        rules.sort_by_key(|(name, _)| *name);
+
+        // Reachability from the conventional start symbol.
+        let mut reachable: std::collections::HashSet<&str> = std::collections::HashSet::new();
+        let mut stack: Vec<&str> = if self.rules.contains_key("S") { vec!["S"] } else { Vec::new() };
+        while let Some(nt) = stack.pop() {
+            if !reachable.insert(nt) {
+                continue;
+            }
+            if let Some(rule) = self.rules.get(nt) {
+                for prod in &rule.productions {
+                    let mut nts = Vec::new();
+                    let mut terminals = Vec::new();
+                    for sym in &prod.symbols {
+                        collect_refs(sym, &mut nts, &mut terminals);
+                    }
+                    stack.extend(nts);
+                }
+            }
+        }
+
+        let mut out = String::new();
+        out.push_str("digraph grammar {\n");
+        out.push_str("  rankdir=LR;\n");
+        out.push_str("  node [fontname=\"Helvetica\"];\n");
+        out.push_str("  edge [fontname=\"Helvetica\", fontsize=10];\n");
+
+        let mut undefined: Vec<&str> = Vec::new();
+        let mut terminals: Vec<Pos> = Vec::new();
+
+        for (non_terminal, rule) in &rules {
+            if reachable.is_empty() || reachable.contains(non_terminal.as_str()) {
+                out.push_str(&format!("  \"{}\" [shape=box];\n", non_terminal));
+            } else {
+                out.push_str(&format!(
+                    "  \"{}\" [shape=box, style=filled, fillcolor=lightgrey, label=\"{} (unreachable)\"];\n",
+                    non_terminal, non_terminal
+                ));
+            }
+
+            for (prod_idx, prod) in rule.productions.iter().enumerate() {
+                let prod_id = format!("\"{}/{}\"", non_terminal, prod_idx);
+                out.push_str(&format!("  {} [shape=point];\n", prod_id));
+                out.push_str(&format!(
+                    "  \"{}\" -> {} [label=\"{:.2}\"];\n",
+                    non_terminal, prod_id, prod.weight
+                ));
+
+                for (sym_idx, sym) in prod.symbols.iter().enumerate() {
+                    let (target, optional) = symbol_target(sym);
+                    if optional {
+                        out.push_str(&format!(
+                            "  {} -> {} [label=\"{}?\", style=dashed];\n",
+                            prod_id, target, sym_idx + 1
+                        ));
+                    } else {
+                        out.push_str(&format!("  {} -> {} [label=\"{}\"];\n", prod_id, target, sym_idx + 1));
+                    }
+
+                    let mut nts = Vec::new();
+                    collect_refs(sym, &mut nts, &mut terminals);
+                    for nt in nts {
+                        if !self.rules.contains_key(nt) && !undefined.contains(&nt) {
+                            undefined.push(nt);
+                        }
+                    }
+                }
+            }
+        }
+
+        undefined.sort();
+        for nt in undefined {
+            out.push_str(&format!(
+                "  \"{}\" [shape=box, color=red, fontcolor=red, label=\"{} (undefined)\"];\n",
+                nt, nt
+            ));
+        }
+
+        terminals.sort_by_key(|pos| format!("{:?}", pos));
+        terminals.dedup();
+        for pos in terminals {
+            let label = match slot_entropy.and_then(|e| e.get(&pos)) {
+                Some(bits) => format!("{:?}\\n{:.2} bits", pos, bits),
+                None => format!("{:?}", pos),
+            };
+            out.push_str(&format!("  \"pos:{:?}\" [shape=ellipse, label=\"{}\"];\n", pos, label));
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+/// One symbol of a compiled production.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Step {
+    /// Emit a POS slot.
+    Slot(Pos),
+    /// Expand nonterminal `id`.
+    Call(usize),
+    /// A nonterminal that is referenced but never defined; sampling through it fails.
+    Undefined,
+    /// Include the next step with probability 0.5, otherwise skip it.
+    Opt,
+}
+
+/// A [`Grammar`] flattened into lookup tables for fast sampling and sequence
+/// enumeration.
+///
+/// Nonterminals are numbered, and every production becomes a row of
+/// `(cumulative weight, weight, step range)`; every step is a [`Step`] in one shared
+/// array. Sampling walks these tables with an explicit stack instead of
+/// cloning and recursing through [`Sym`] trees, but draws from the RNG in the
+/// same order as [`Grammar::sample_derivation`], so a given seed yields the
+/// same derivation either way.
+#[derive(Clone, Debug)]
+pub struct CompiledGrammar {
+    names: Vec<String>,
+    ids: HashMap<String, usize>,
+    /// Per nonterminal: range into `productions`.
+    rules: Vec<(usize, usize)>,
+    /// Per production: (cumulative weight, weight, range into `steps`).
+    productions: Vec<(f64, f64, usize, usize)>,
+    steps: Vec<Step>,
+}
+
+impl CompiledGrammar {
+    /// Id of a nonterminal, if it is defined.
+    pub fn id(&self, non_terminal: &str) -> Option<usize> {
+        self.ids.get(non_terminal).copied()
+    }
+
+    /// Number of productions defined for nonterminal `id`.
+    #[allow(dead_code)]
+    pub fn choice_count(&self, id: usize) -> usize {
+        let (start, end) = self.rules[id];
+        end - start
+    }
+
+    /// Sample a complete derivation from `start_symbol`.
+    ///
+    /// Same contract as [`Grammar::sample_derivation`].
+    #[allow(dead_code)]
+    pub fn sample<R: Rng>(&self, rng: &mut R, start_symbol: &str, max_slots: usize) -> Option<Derivation> {
+        let mut derivation = Derivation {
+            slots: Vec::new(),
+            productions: Vec::new(),
+        };
+        let mut stack = vec![Step::Call(self.id(start_symbol)?)];
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Slot(pos) => {
+                    derivation.slots.push(pos);
+                    if derivation.slots.len() > max_slots {
+                        return None;
+                    }
+                }
+                Step::Opt => {
+                    let inner = stack.pop()?;
+                    if rng.gen_bool(0.5) {
+                        stack.push(inner);
+                    }
+                }
+                Step::Undefined => return None,
+                Step::Call(id) => {
+                    let (start, end) = self.rules[id];
+                    if start == end {
+                        return None;
+                    }
+                    let prod = self.choose(rng, start, end);
+                    derivation.productions.push((self.names[id].clone(), prod - start));
+                    let (_, _, first, last) = self.productions[prod];
+                    stack.extend(self.steps[first..last].iter().rev());
+                }
+            }
+        }
+        Some(derivation)
+    }
+
+    /// Enumerate all valid POS sequences of exactly length k with their
+    /// probabilities, highest first.
+    pub fn enumerate_sequences_with_probability(&self, start_symbol: &str, k: usize) -> Vec<SequenceWithProbability> {
+        self.sequences_of_length(start_symbol, k, &mut HashMap::new())
+    }
+
+    /// [`CompiledGrammar::enumerate_sequences_with_probability`] for every
+    /// length 0..=max_k, sharing one DP memo across lengths, which makes
+    /// `max_k` up to ~20 cheap.
+    pub fn precompute_sequences_with_probability(&self, start_symbol: &str, max_k: usize) -> Vec<Vec<SequenceWithProbability>> {
+        let mut memo = HashMap::new();
+        (0..=max_k).map(|k| self.sequences_of_length(start_symbol, k, &mut memo)).collect()
+    }
+
+    fn sequences_of_length(&self, start_symbol: &str, k: usize, memo: &mut SequenceMemo) -> Vec<SequenceWithProbability> {
+        let results = match self.id(start_symbol) {
+            Some(id) => self.enumerate_call(id, k, memo),
+            None => Vec::new(),
+        };
+
+        // Deduplicate final results and convert to SequenceWithProbability
+        let mut prob_map: HashMap<Vec<crate::Pos>, f64> = HashMap::new();
+        for (seq, prob) in results {
+            *prob_map.entry(seq).or_insert(0.0) += prob;
+        }
+
+        let mut sequences: Vec<SequenceWithProbability> = prob_map
+            .into_iter()
+            .map(|(sequence, probability)| SequenceWithProbability {
+                sequence,
+                probability,
+            })
+            .collect();
+
+        // Sort by probability (highest first), equal ones by sequence so seeded
+        // generation picks the same sequences every run
+        sequences.sort_by(|a, b| {
+            b.probability.partial_cmp(&a.probability).unwrap_or(std::cmp::Ordering::Equal).then_with(|| a.sequence.cmp(&b.sequence))
+        });
+
+        sequences
+    }
+
+    /// Sequences of exactly `remaining` slots derived from the step at `at`.
+    /// An [`Step::Opt`] covers the step after it.
+    fn enumerate_step(&self, at: usize, remaining: usize, memo: &mut SequenceMemo) -> Vec<(Vec<crate::Pos>, f64)> {
+        match self.steps[at] {
+            Step::Slot(pos) => {
+                if remaining == 1 {
+                    vec![(vec![pos], 1.0)] // Terminal has probability 1.0
+                } else {
+                    Vec::new()
+                }
+            }
+            Step::Opt => {
+                // Include the optional symbol (probability 0.5)
+                let mut results: Vec<_> = self
+                    .enumerate_step(at + 1, remaining, memo)
+                    .into_iter()
+                    .map(|(seq, prob)| (seq, prob * 0.5))
+                    .collect();
+
+                // Exclude the optional symbol (probability 0.5, produces empty)
+                if remaining == 0 {
+                    results.push((Vec::new(), 0.5));
+                }
+
+                results
+            }
+            Step::Call(id) => self.enumerate_call(id, remaining, memo),
+            Step::Undefined => Vec::new(),
+        }
+    }
+
+    fn enumerate_call(&self, id: usize, remaining: usize, memo: &mut SequenceMemo) -> Vec<(Vec<crate::Pos>, f64)> {
+        if let Some(cached) = memo.get(&(id, remaining)) {
+            return cached.clone();
+        }
+
+        let mut all_results = Vec::new();
+        let (first_prod, last_prod) = self.rules[id];
+        for &(_, prod_weight, first, last) in &self.productions[first_prod..last_prod] {
+            // Try all ways to distribute the remaining slots across the symbols
+            let mut production_results: Vec<(Vec<crate::Pos>, f64)> = vec![(Vec::new(), 1.0)];
+            let mut at = first;
+            while at < last {
+                let mut new_results = Vec::new();
+
+                for (partial_seq, partial_prob) in production_results {
+                    let available = remaining.saturating_sub(partial_seq.len());
+
+                    // Try allocating 0 to available slots to this symbol
+                    for symbol_slots in 0..=available {
+                        for (symbol_seq, symbol_prob) in self.enumerate_step(at, symbol_slots, memo) {
+                            // Symbol must use exactly the allocated slots
+                            if symbol_seq.len() != symbol_slots {
+                                continue;
+                            }
+
+                            let mut combined = partial_seq.clone();
+                            combined.extend(symbol_seq);
+
+                            // Only keep if we haven't exceeded remaining
+                            if combined.len() <= remaining {
+                                new_results.push((combined, partial_prob * symbol_prob));
+                            }
+                        }
+                    }
+                }
+
+                production_results = new_results;
+                at += if self.steps[at] == Step::Opt { 2 } else { 1 };
+            }
+
+            // Final probability = production weight * product of symbol probabilities,
+            // at exactly the requested length
+            for (seq, symbol_prob) in production_results {
+                if seq.len() == remaining {
+                    all_results.push((seq, prod_weight * symbol_prob));
+                }
+            }
+        }
+
+        // Deduplicate: sum probabilities for identical sequences
+        let mut prob_map: HashMap<Vec<crate::Pos>, f64> = HashMap::new();
+        for (seq, prob) in all_results {
+            *prob_map.entry(seq).or_insert(0.0) += prob;
+        }
+
+        // In sequence order, so sums (and ties) don't depend on hash order
+        let mut final_results: Vec<(Vec<crate::Pos>, f64)> = prob_map.into_iter().collect();
+        final_results.sort_by(|a, b| a.0.cmp(&b.0));
+        memo.insert((id, remaining), final_results.clone());
+
+        final_results
+    }
+
+    /// Pick a production in `start..end`, matching [`choose_production`].
+    #[allow(dead_code)]
+    fn choose<R: Rng>(&self, rng: &mut R, start: usize, end: usize) -> usize {
+        let r = rng.gen::<f64>();
+        (start..end)
+            .find(|&p| r - self.productions[p].0 <= 0.0)
+            .unwrap_or(start)
+    }
+}
+
+/// Pick a production index by weight (weights are already normalized).
+fn choose_production<R: Rng>(rng: &mut R, rule: &GrammarRule) -> Option<usize> {
+    let mut rand_val = rng.gen::<f64>();
+    for (idx, prod) in rule.productions.iter().enumerate() {
+        rand_val -= prod.weight;
+        if rand_val <= 0.0 {
+            return Some(idx);
+        }
+    }
+
+    // Fallback to first production
+    if rule.productions.is_empty() {
+        None
+    } else {
+        Some(0)
+    }
+}
+
+/// A production symbol as the grammar file writes it (`N`, `NP`, `Adj?`)
+fn symbol_text(sym: &Sym) -> String {
+    match sym {
+        Sym::T(pos) => pos.name().to_string(),
+        Sym::NT(nt) => nt.clone(),
+        Sym::Opt(inner) => match &**inner {
+            Sym::T(pos) => format!("{:?}?", pos),
+            Sym::NT(nt) => format!("{}?", nt),
+            Sym::Opt(_) => "Opt?".to_string(),
+        },
+    }
 }
 
 fn parse_symbol_sequence(pair: pest::iterators::Pair<Rule>) -> Result<Vec<Sym>, Box<dyn std::error::Error>> {
     let mut symbols = Vec::new();
     
     for symbol_pair in pair.into_inner() {
-        match symbol_pair.as_rule() {
-            Rule::symbol => {
-                let mut inner = symbol_pair.into_inner();
-                let sym_type = inner.next().unwrap();
-                let optional = inner.next();
-                
-                let base_sym = match sym_type.as_rule() {
-                    Rule::terminal => {
-                        let pos = match sym_type.as_str() {
-                            "Det" => Pos::Det,
-                            "Adj" => Pos::Adj,
-                            "N" => Pos::N,
-                            "V" => Pos::V,
-                            "Modal" => Pos::Modal,
-                            "Aux" => Pos::Aux,
-                            "Cop" => Pos::Cop,
-                            "To" => Pos::To,
-                            "Prep" => Pos::Prep,
-                            "Adv" => Pos::Adv,
-                            "Dot" => Pos::Dot,
-                            "Prefix" => Pos::Prefix,
-                            "Conj" => Pos::Conj,
-                            _ => return Err(format!("Unknown terminal: {}", sym_type.as_str()).into()),
-                        };
-                        Sym::T(pos)
-                    }
-                    Rule::non_terminal => {
-                        Sym::NT(sym_type.as_str().to_string())
-                    }
-                    _ => return Err("Invalid symbol type".into()),
+        if symbol_pair.as_rule() != Rule::symbol {
+            continue;
+        }
+        let mut inner = symbol_pair.into_inner();
+        let sym_type = inner.next().unwrap();
+        let optional = inner.next();
+        
+        let base_sym = match sym_type.as_rule() {
+            Rule::terminal => {
+                let pos = match sym_type.as_str() {
+                    "Det" => Pos::Det,
+                    "Adj" => Pos::Adj,
+                    "N" => Pos::N,
+                    "V" => Pos::V,
+                    "Modal" => Pos::Modal,
+                    "Aux" => Pos::Aux,
+                    "Cop" => Pos::Cop,
+                    "To" => Pos::To,
+                    "Prep" => Pos::Prep,
+                    "Adv" => Pos::Adv,
+                    "Dot" => Pos::Dot,
+                    "Prefix" => Pos::Prefix,
+                    "Conj" => Pos::Conj,
+                    _ => return Err(format!("Unknown terminal: {}", sym_type.as_str()).into()),
                 };
-                
-                // Optional symbols are represented explicitly in the AST and handled in expansion.
-                if optional.is_some() {
-                    symbols.push(Sym::Opt(Box::new(base_sym)));
-                } else {
-                    symbols.push(base_sym);
-                }
+                Sym::T(pos)
             }
-            _ => {}
+            Rule::non_terminal => {
+                Sym::NT(sym_type.as_str().to_string())
+            }
+            _ => return Err("Invalid symbol type".into()),
+        };
+        
+        // Optional symbols are represented explicitly in the AST and handled in expansion.
+        if optional.is_some() {
+            symbols.push(Sym::Opt(Box::new(base_sym)));
+        } else {
+            symbols.push(base_sym);
         }
     }
     
@@ -484,9 +1010,11 @@ fn parse_symbol_sequence(pair: pest::iterators::Pair<Rule>) -> Result<Vec<Sym>,
 
 
 #[cfg(test)]
+#[allow(clippy::needless_range_loop)]
 mod tests {
     use super::*;
     use crate::Pos;
+    use rand::SeedableRng;
     use std::time::{SystemTime, UNIX_EPOCH};
 
     fn format_pos_sequence(seq: &[Pos]) -> String {
@@ -515,6 +1043,8 @@ mod tests {
 
         // Precompute once (shares DP memo across k values) and then read by length.
         let precomputed = grammar.precompute_sequences_with_probability("S", 20);
+        // The compiled tables are built once and reused by later calls
+        assert!(std::ptr::eq(grammar.compiled(), grammar.compiled()));
         
         let mut total_prob_all_k: f64 = 0.0;
         let mut k_values: Vec<usize> = Vec::new();
@@ -634,6 +1164,119 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_sample_derivation_tracks_productions() {
+        let grammar = Grammar::default().expect("Failed to load body grammar");
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+
+        for _ in 0..50 {
+            let derivation = grammar
+                .sample_derivation(&mut rng, "S", 40)
+                .expect("body grammar derivations should stay under 40 slots");
+            assert_eq!(derivation.slots.last(), Some(&Pos::Dot));
+            assert_eq!(derivation.productions[0].0, "S");
+            for (nt, idx) in &derivation.productions {
+                assert!(*idx < grammar.production_count(nt));
+            }
+        }
+
+        assert!(grammar.sample_derivation(&mut rng, "MISSING", 40).is_none());
+    }
+
+    #[test]
+    fn test_compiled_grammar_matches_sample_derivation() {
+        let grammar = Grammar::default().expect("Failed to load body grammar");
+        let compiled = grammar.compile();
+        let mut tree_rng = rand::rngs::StdRng::seed_from_u64(11);
+        let mut table_rng = rand::rngs::StdRng::seed_from_u64(11);
+
+        for _ in 0..200 {
+            let expected = grammar.sample_derivation(&mut tree_rng, "S", 40);
+            let actual = compiled.sample(&mut table_rng, "S", 40);
+            assert_eq!(expected.as_ref().map(|d| &d.slots), actual.as_ref().map(|d| &d.slots));
+            assert_eq!(expected.map(|d| d.productions), actual.map(|d| d.productions));
+        }
+
+        let id = compiled.id("S").expect("S is defined");
+        assert_eq!(compiled.choice_count(id), grammar.production_count("S"));
+        assert!(compiled.sample(&mut table_rng, "MISSING", 40).is_none());
+
+        let partial = Grammar::from_str("S = NP Dot\nNP = Det? MISSING\n").expect("parse grammar");
+        let mut rng = rand::rngs::StdRng::seed_from_u64(0);
+        assert!(partial.compile().sample(&mut rng, "S", 40).is_none());
+    }
+
+    #[test]
+    fn test_to_dot_marks_coverage_gaps() {
+        let grammar = Grammar::from_str(
+            "S = (0.75: NP VP Dot) | (0.25: NP Dot)\nNP = Det Adj? N\nVP = V OBJ\nORPHAN = Adv\n",
+        )
+        .expect("parse grammar");
+
+        let dot = grammar.to_dot();
+        assert!(dot.starts_with("digraph grammar {"));
+        assert!(dot.contains("\"S\" -> \"S/0\" [label=\"0.75\"];"));
+        assert!(dot.contains("\"NP/0\" -> \"pos:Adj\" [label=\"2?\", style=dashed];"));
+        assert!(dot.contains("\"OBJ\" [shape=box, color=red"), "undefined nonterminal should be flagged");
+        assert!(dot.contains("\"ORPHAN (unreachable)\""), "unreachable rule should be flagged");
+
+        let mut entropy = HashMap::new();
+        entropy.insert(Pos::N, 11.0);
+        let dot = grammar.to_dot_with_entropy(&entropy);
+        assert!(dot.contains("\"pos:N\" [shape=ellipse, label=\"N\\n11.00 bits\"];"));
+        assert!(dot.contains("\"pos:V\" [shape=ellipse, label=\"V\"];"));
+    }
+
+    #[test]
+    fn test_entropy_report_weighs_slots_by_derivation() {
+        let grammar = Grammar::from_str("S = (0.75: Det N V Dot) | (0.25: N Dot)\n").expect("parse grammar");
+        let weights = glossia::weights::parse(
+            "able:\n  Adj: 0.6\n  N: 0.4\nbag:\n  N: 1.0\ncat:\n  N: 1.0\ndog:\n  N: 1.0\nrun:\n  V: 0.7\n  N: 0.3\n",
+        )
+        .unwrap();
+        let wordlist: Vec<String> = weights.words().map(str::to_string).collect();
+
+        let report = grammar.entropy_report(&wordlist, &SlotMap::new(&weights, 0.0));
+        let n = &report.slots[report.slots.iter().position(|s| s.pos == Pos::N).unwrap()];
+        assert_eq!((n.words, n.bits, n.fit), (5, 5f64.log2(), 1.0));
+        // Det and Dot admit no word and carry nothing
+        assert!(report.slots.iter().filter(|s| s.pos != Pos::N && s.pos != Pos::V).all(|s| s.bits == 0.0));
+        assert_eq!(report.lengths.iter().map(|l| (l.slots, l.probability)).collect::<Vec<_>>(), [(2, 0.25), (4, 0.75)]);
+        assert!((report.coverage - 1.0).abs() < 1e-12);
+        assert!((report.slots_per_sentence - 3.5).abs() < 1e-12);
+        assert!((report.bits_per_sentence - 5f64.log2()).abs() < 1e-12);
+
+        // At 0.5, "able" and "run" leave N, and V had one word to begin with
+        let restricted = grammar.entropy_report(&wordlist, &SlotMap::new(&weights, 0.5));
+        assert!((restricted.bits_per_sentence - 3f64.log2()).abs() < 1e-12);
+        assert!(restricted.to_string().contains("Per sentence: 3.50 slots, 1.58 bits"), "{}", restricted);
+    }
+
+    #[test]
+    fn test_parse_sequence_finds_the_likeliest_derivation() {
+        let grammar = Grammar::from_str(
+            "S = (0.7: NP V Dot) | (0.3: N V Dot)\nNP = (0.25: N) | (0.75: Det? Adj? N)\n",
+        )
+        .expect("parse grammar");
+        // "N V Dot": through NP = N (0.7 * 0.25), NP = Det? Adj? N skipping both
+        // (0.7 * 0.75 * 0.25), or S's own N V Dot (0.3)
+        let (derivation, probability) = grammar.parse_sequence("S", &[Pos::N, Pos::V, Pos::Dot]).unwrap();
+        assert_eq!(derivation.productions, [("S".to_string(), 1)]);
+        assert!((probability - 0.3).abs() < 1e-12);
+        // The probabilities of all its derivations are what the enumerator sums
+        let total: f64 = grammar.precompute_sequences_with_probability("S", 3)[3]
+            .iter()
+            .filter(|s| s.sequence == [Pos::N, Pos::V, Pos::Dot])
+            .map(|s| s.probability)
+            .sum();
+        assert!((total - (0.175 + 0.13125 + 0.3)).abs() < 1e-12, "{}", total);
+
+        let (derivation, _) = grammar.parse_sequence("S", &[Pos::Det, Pos::N, Pos::V, Pos::Dot]).unwrap();
+        assert_eq!(derivation.productions, [("S".to_string(), 0), ("NP".to_string(), 1)]);
+        assert_eq!(grammar.production_text("NP", 1).unwrap(), "NP = Det? Adj? N");
+        assert!(grammar.parse_sequence("S", &[Pos::V, Pos::Dot]).is_none());
+    }
+
     #[test]
     fn test_sequence_cache_roundtrip_tempdir() {
         // Write a temporary grammar file, build the cache, then ensure a subsequent call reads it.
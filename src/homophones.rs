@@ -0,0 +1,98 @@
+//! Homophone groups for spoken transmission.
+//!
+//! When cover text is read aloud and transcribed (phone, radio, speech
+//! recognition), words that sound alike are interchangeable. That breaks
+//! decoding in two ways: a cover word can come back as a wordlist word
+//! ("to" -> "two") and insert a spurious payload word, and a payload word can
+//! come back as a non-wordlist spelling ("eight" -> "ate") and be dropped.
+//! [`HomophoneTable`] answers both: which cover words to avoid when encoding,
+//! and how to map transcriptions back to payload words when decoding.
+
+use std::collections::{HashMap, HashSet};
+
+/// Groups of words that sound the same.
+#[derive(Clone, Debug, Default)]
+pub struct HomophoneTable {
+    groups: Vec<Vec<String>>,
+}
+
+impl HomophoneTable {
+    /// Parse a table with one group of homophones per line, separated by
+    /// whitespace. Blank lines and lines starting with `#` are ignored.
+    pub fn parse(content: &str) -> Self {
+        let groups = content
+            .lines()
+            .map(|line| line.trim())
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|line| line.split_whitespace().map(|w| w.to_lowercase()).collect::<Vec<_>>())
+            .filter(|group| group.len() > 1)
+            .collect();
+        Self { groups }
+    }
+
+    /// Words that sound like a wordlist word but are not themselves wordlist
+    /// words. Using these as cover words is unsafe for spoken transmission.
+    pub fn collisions(&self, wordlist: &HashSet<String>) -> HashSet<String> {
+        let mut out = HashSet::new();
+        for group in &self.groups {
+            if group.iter().any(|w| wordlist.contains(w)) {
+                out.extend(group.iter().filter(|w| !wordlist.contains(*w)).cloned());
+            }
+        }
+        out
+    }
+
+    /// Decode corrections: each non-wordlist spelling mapped to the single
+    /// wordlist word it sounds like. Groups containing several wordlist words
+    /// are ambiguous and left out (see [`HomophoneTable::ambiguous`]).
+    pub fn corrections(&self, wordlist: &HashSet<String>) -> HashMap<String, String> {
+        let mut out = HashMap::new();
+        for group in &self.groups {
+            let carriers: Vec<&String> = group.iter().filter(|w| wordlist.contains(*w)).collect();
+            if let [carrier] = carriers.as_slice() {
+                for word in group.iter().filter(|w| !wordlist.contains(*w)) {
+                    out.insert(word.clone(), (*carrier).clone());
+                }
+            }
+        }
+        out
+    }
+
+    /// Wordlist words that sound like another wordlist word ("pair"/"pear").
+    /// No cover-side choice can make these safe to read aloud.
+    pub fn ambiguous(&self, wordlist: &HashSet<String>) -> HashSet<String> {
+        let mut out = HashSet::new();
+        for group in &self.groups {
+            let carriers: Vec<&String> = group.iter().filter(|w| wordlist.contains(*w)).collect();
+            if carriers.len() > 1 {
+                out.extend(carriers.into_iter().cloned());
+            }
+        }
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_collisions_and_corrections() {
+        let table = HomophoneTable::parse("# comment\nto too two\nate eight\npair pear pare\nknot not\n");
+        let wordlist = set(&["two", "eight", "pair", "pear"]);
+
+        assert_eq!(table.collisions(&wordlist), set(&["to", "too", "ate", "pare"]));
+
+        let corrections = table.corrections(&wordlist);
+        assert_eq!(corrections.get("too").map(String::as_str), Some("two"));
+        assert_eq!(corrections.get("ate").map(String::as_str), Some("eight"));
+        assert!(!corrections.contains_key("pare"));
+        assert!(!corrections.contains_key("not"));
+
+        assert_eq!(table.ambiguous(&wordlist), set(&["pair", "pear"]));
+    }
+}
@@ -0,0 +1,191 @@
+//! Decoding cover text published as web pages and feeds.
+//!
+//! Markup gets in the way of the decoders: a carrier split by a tag
+//! (`ab<b>and</b>on`) or written as an entity is not a token, and script
+//! and style blocks add words nobody sees. [`visible_text`] keeps what a
+//! reader would see, with block elements as paragraph breaks. RSS and Atom
+//! feeds carry each post as an `<item>` or `<entry>`, often with its HTML
+//! escaped inside, so [`posts`] splits a feed and markup is stripped again
+//! once entities are decoded.
+//!
+//! [`decode_from_html`] decodes every post that looks like it carries a
+//! payload; with the `fetch` feature, [`decode_from_url`] downloads the page
+//! or feed first, so a monitoring tool can scan a site end to end.
+
+use crate::payload::{self, PayloadOptions};
+use anyhow::Result;
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// Elements whose content is never shown, and comments
+const HIDDEN: &str = r"(?is)<!--.*?-->|<script\b.*?</script\s*>|<style\b.*?</style\s*>|<noscript\b.*?</noscript\s*>|<template\b.*?</template\s*>|<svg\b.*?</svg\s*>|<head\b.*?</head\s*>";
+
+/// Tags that start or end a block of text
+const BLOCK: &str = r"(?i)</?(?:p|div|br|hr|li|ul|ol|h[1-6]|tr|td|th|table|article|section|header|footer|aside|nav|blockquote|pre|title|item|entry|summary|content|description)\b[^>]*>";
+
+fn regex(cell: &'static OnceLock<Regex>, pattern: &str) -> &'static Regex {
+    cell.get_or_init(|| Regex::new(pattern).unwrap())
+}
+
+/// Replace character references (`&amp;`, `&#8217;`, `&#x2014;`) with their
+/// characters. Unknown names are left as they are.
+pub fn decode_entities(text: &str) -> String {
+    static ENTITY: OnceLock<Regex> = OnceLock::new();
+    regex(&ENTITY, r"&(#[0-9]{1,7}|#[xX][0-9a-fA-F]{1,6}|[a-zA-Z]+);")
+        .replace_all(text, |caps: &Captures| {
+            let name = &caps[1];
+            let c = match name.strip_prefix('#') {
+                Some(hex) if hex.starts_with(['x', 'X']) => u32::from_str_radix(&hex[1..], 16).ok().and_then(char::from_u32),
+                Some(decimal) => decimal.parse().ok().and_then(char::from_u32),
+                None => named_entity(name),
+            };
+            c.map_or_else(|| caps[0].to_string(), String::from)
+        })
+        .into_owned()
+}
+
+fn named_entity(name: &str) -> Option<char> {
+    Some(match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        "ndash" => '–',
+        "mdash" => '—',
+        "hellip" => '…',
+        "lsquo" => '‘',
+        "rsquo" => '’',
+        "ldquo" => '“',
+        "rdquo" => '”',
+        "laquo" => '«',
+        "raquo" => '»',
+        "copy" => '©',
+        "reg" => '®',
+        "trade" => '™',
+        "middot" => '·',
+        "bull" => '•',
+        _ => return None,
+    })
+}
+
+/// Drop hidden elements and tags, unwrapping CDATA sections and turning
+/// block tags into paragraph breaks.
+fn strip_tags(html: &str) -> String {
+    static CDATA: OnceLock<Regex> = OnceLock::new();
+    static HIDDEN_RE: OnceLock<Regex> = OnceLock::new();
+    static BLOCK_RE: OnceLock<Regex> = OnceLock::new();
+    static TAG: OnceLock<Regex> = OnceLock::new();
+    let text = regex(&CDATA, r"(?s)<!\[CDATA\[(.*?)\]\]>").replace_all(html, "$1");
+    let text = regex(&HIDDEN_RE, HIDDEN).replace_all(&text, " ");
+    let text = regex(&BLOCK_RE, BLOCK).replace_all(&text, "\n\n");
+    regex(&TAG, r"<[a-zA-Z/!?][^>]*>").replace_all(&text, "").into_owned()
+}
+
+/// Collapse runs of spaces and blank lines, keeping paragraph breaks.
+fn tidy(text: &str) -> String {
+    let mut paragraphs = Vec::new();
+    for block in text.split("\n\n") {
+        let words: Vec<&str> = block.split_whitespace().collect();
+        if !words.is_empty() {
+            paragraphs.push(words.join(" "));
+        }
+    }
+    paragraphs.join("\n\n")
+}
+
+/// The text a reader would see in `html`: no scripts, styles, comments or
+/// tags, entities decoded, one paragraph per block element. Markup escaped
+/// inside the text (as feeds carry HTML) is stripped as well.
+pub fn visible_text(html: &str) -> String {
+    let text = decode_entities(&strip_tags(html));
+    tidy(&decode_entities(&strip_tags(&text)))
+}
+
+/// The visible text of each post in `document`: one per `<item>` (RSS) or
+/// `<entry>` (Atom) of a feed, or the whole document for a page.
+pub fn posts(document: &str) -> Vec<String> {
+    static ITEM: OnceLock<Regex> = OnceLock::new();
+    let items: Vec<String> = regex(&ITEM, r"(?is)<item\b[^>]*>(.*?)</item\s*>|<entry\b[^>]*>(.*?)</entry\s*>")
+        .captures_iter(document)
+        .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+        .map(|inner| visible_text(inner.as_str()))
+        .collect();
+    if items.is_empty() {
+        vec![visible_text(document)]
+    } else {
+        items
+    }
+}
+
+/// The payloads of the posts in a page or feed that look like they carry one
+/// ([`payload::Detection::is_likely`]) and decode with `options`, in order.
+pub fn decode_from_html(document: &str, wordlist: &[String], options: &PayloadOptions) -> Vec<Vec<u8>> {
+    posts(document)
+        .iter()
+        .filter(|text| payload::detect(text, wordlist).is_likely())
+        .filter_map(|text| payload::decode_payload(text, wordlist, options).ok())
+        .collect()
+}
+
+/// Download a page or feed and [`decode_from_html`] it.
+#[cfg(all(feature = "fetch", not(target_arch = "wasm32")))]
+pub fn decode_from_url(url: &str, wordlist: &[String], options: &PayloadOptions) -> Result<Vec<Vec<u8>>> {
+    use anyhow::Context;
+    let document = reqwest::blocking::get(url)
+        .and_then(|response| response.error_for_status())
+        .and_then(|response| response.text())
+        .with_context(|| format!("Failed to fetch {}", url))?;
+    Ok(decode_from_html(&document, wordlist, options))
+}
+
+/// Decode the visible text of `html` as one cover text, like
+/// [`payload::decode_payload`].
+pub fn decode_visible(html: &str, wordlist: &[String], options: &PayloadOptions) -> Result<Vec<u8>> {
+    payload::decode_payload(&visible_text(html), wordlist, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+    use crate::payload::encode_payload;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_visible_text() {
+        let html = "<html><head><title>Blog</title><style>p { color: red }</style></head>\
+            <body><script>var abandon = 1;</script><p>Caf&eacute; &amp; <b>bar</b>&#8217;s &lt;menu&gt;</p>\
+            <!-- a comment --><div>Second&nbsp;block<br>line</div></body></html>";
+        assert_eq!(visible_text(html), "Caf&eacute; & bar’s\n\nSecond block\n\nline");
+        assert_eq!(decode_entities("&#x41;&#66;&bogus;&#xFFFFFF;"), "AB&bogus;&#xFFFFFF;");
+    }
+
+    #[test]
+    fn test_feed_posts_decode_separately() {
+        let list = alpha_wordlist();
+        let options = PayloadOptions { checksum: true, ..Default::default() };
+        let mut rng = StdRng::seed_from_u64(6);
+        let cover = |payload: &[u8], rng: &mut StdRng| {
+            let words = encode_payload(rng, payload, &list, &options).unwrap();
+            words.iter().map(|w| format!("The {} was here.", w)).collect::<Vec<_>>().join(" ")
+        };
+        // RSS escapes the post HTML; Atom wraps it in CDATA
+        let first = cover(b"one", &mut rng).replace("The ", "&lt;p&gt;The &lt;em&gt;").replace(" was", "&lt;/em&gt; was");
+        let second = cover(b"two", &mut rng);
+        let feed = format!(
+            "<rss><channel><title>Feed</title>\
+             <item><title>First</title><description>{}</description></item>\
+             <item><title>Nothing here</title><description>Just an ordinary post.</description></item>\
+             <entry><content type=\"html\"><![CDATA[<p>{}</p>]]></content></entry></channel></rss>",
+            first, second
+        );
+        assert_eq!(posts(&feed).len(), 3);
+        assert_eq!(decode_from_html(&feed, &list, &options), vec![b"one".to_vec(), b"two".to_vec()]);
+
+        let page = format!("<p>{}</p><script>{}</script>", cover(b"page", &mut rng), list[5]);
+        assert_eq!(decode_visible(&page, &list, &options).unwrap(), b"page");
+    }
+}
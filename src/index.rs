@@ -0,0 +1,200 @@
+//! Wordlist lookup structures.
+//!
+//! A [`WordIndex`] is an FST over a wordlist mapping each word to its
+//! canonical index. Besides exact lookup it answers the two fuzzy queries the
+//! decoders need: the unique word sharing a 4-letter prefix (BIP39 words are
+//! unique in their first four letters) and the words within one edit.
+//!
+//! Building one costs more than a lookup, so indexes are shared: the first
+//! [`WordIndex::shared`] call for a wordlist builds it and later calls reuse it.
+//!
+//! An FST lookup walks as far as the word's bytes lead, so its time says
+//! something about the word. [`WordIndex::get_constant_time`] scans a
+//! fixed-width copy of the whole list instead, for decoding secrets on
+//! machines shared with someone who can time it.
+
+use crate::segment;
+use anyhow::{Context, Result};
+use fst::automaton::{Automaton, Levenshtein, Str};
+use fst::{IntoStreamer, Map, MapBuilder, Streamer};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// Letters that identify a BIP39 word
+pub const PREFIX_LEN: usize = 4;
+
+/// Distinct wordlists kept by [`WordIndex::shared`]
+const SHARED_CAPACITY: usize = 16;
+
+/// Word → index FST over one wordlist.
+pub struct WordIndex {
+    map: Map<Vec<u8>>,
+    words: Vec<String>,
+    /// Whether the words are Chinese or Japanese, found by segmenting
+    unspaced: bool,
+    /// Longest word, in characters
+    max_chars: usize,
+    /// Every word zero-padded to the longest word's byte length, end to end
+    padded: Vec<u8>,
+}
+
+impl WordIndex {
+    /// Build an index over `wordlist`; indices are positions in `wordlist`.
+    pub fn new(wordlist: &[String]) -> Result<Self> {
+        let mut entries: Vec<(&str, usize)> = wordlist.iter().enumerate().map(|(i, w)| (w.as_str(), i)).collect();
+        entries.sort_unstable();
+        let mut builder = MapBuilder::memory();
+        for (word, i) in entries {
+            builder
+                .insert(word, i as u64)
+                .with_context(|| format!("Wordlist has duplicate word '{}'", word))?;
+        }
+        let width = wordlist.iter().map(String::len).max().unwrap_or_default();
+        let padded = wordlist.iter().flat_map(|w| w.bytes().chain(std::iter::repeat(0)).take(width)).collect();
+        Ok(Self {
+            map: builder.into_map(),
+            words: wordlist.to_vec(),
+            unspaced: segment::is_unspaced_wordlist(wordlist),
+            max_chars: wordlist.iter().map(|w| w.chars().count()).max().unwrap_or_default(),
+            padded,
+        })
+    }
+
+    /// The shared index for `wordlist`, built on first use.
+    pub fn shared(wordlist: &[String]) -> Result<Arc<Self>> {
+        static SHARED: OnceLock<Mutex<Vec<Arc<WordIndex>>>> = OnceLock::new();
+        let mut shared = SHARED.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(index) = shared.iter().find(|index| index.words == wordlist) {
+            return Ok(Arc::clone(index));
+        }
+        let index = Arc::new(Self::new(wordlist)?);
+        if shared.len() == SHARED_CAPACITY {
+            shared.remove(0);
+        }
+        shared.push(Arc::clone(&index));
+        Ok(index)
+    }
+
+    /// Number of words indexed.
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Whether the words are written without spaces (see [`crate::segment`]).
+    pub fn is_unspaced(&self) -> bool {
+        self.unspaced
+    }
+
+    /// Length of the longest word, in characters.
+    pub fn max_chars(&self) -> usize {
+        self.max_chars
+    }
+
+    /// The word at `index`.
+    pub fn word(&self, index: usize) -> &str {
+        &self.words[index]
+    }
+
+    /// Index of `word`, matched exactly.
+    pub fn get(&self, word: &str) -> Option<usize> {
+        self.map.get(word).map(|i| i as usize)
+    }
+
+    /// Index of `word`, matched exactly like [`get`](Self::get), but compared
+    /// with every word over the same number of bytes and without stopping at
+    /// a match. The time taken depends on the wordlist and the length of
+    /// `word`, not on which word it is.
+    pub fn get_constant_time(&self, word: &str) -> Option<usize> {
+        let width = self.padded.len() / self.words.len().max(1);
+        if width == 0 {
+            return None;
+        }
+        let mut token = vec![0u8; width];
+        token.iter_mut().zip(word.bytes()).for_each(|(slot, byte)| *slot = byte);
+        let mut found = 0usize;
+        for (i, candidate) in self.padded.chunks_exact(width).enumerate() {
+            let mut diff = (word.len() ^ self.words[i].len()) as u64;
+            for (a, b) in token.iter().zip(candidate) {
+                diff |= u64::from(a ^ b);
+            }
+            // All ones when diff is zero, else zero
+            let equal = ((std::hint::black_box(diff) | diff.wrapping_neg()) >> 63).wrapping_sub(1);
+            found |= (i + 1) & equal as usize;
+        }
+        found.checked_sub(1)
+    }
+
+    /// Index of the only word starting with the first [`PREFIX_LEN`] letters
+    /// of `token`, if `token` is that long and exactly one word matches.
+    pub fn unique_prefix(&self, token: &str) -> Option<usize> {
+        let prefix = token.get(..PREFIX_LEN)?;
+        let mut stream = self.map.search(Str::new(prefix).starts_with()).into_stream();
+        let (_, first) = stream.next()?;
+        match stream.next() {
+            None => Some(first as usize),
+            Some(_) => None,
+        }
+    }
+
+    /// Indices of the words within one insertion, deletion or substitution of
+    /// `token` (including `token` itself), in wordlist order.
+    pub fn within_one_edit(&self, token: &str) -> Vec<usize> {
+        let Ok(automaton) = Levenshtein::new(token, 1) else {
+            return Vec::new();
+        };
+        let mut found = Vec::new();
+        let mut stream = self.map.search(automaton).into_stream();
+        while let Some((_, i)) = stream.next() {
+            found.push(i as usize);
+        }
+        found.sort_unstable();
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_exact_prefix_and_edit_queries() {
+        // Deliberately not in byte order: indices follow the given order
+        let words = list(&["zoo", "abandon", "ability", "able", "abstract", "absurd", "act"]);
+        let index = WordIndex::new(&words).unwrap();
+        assert_eq!(index.get("able"), Some(3));
+        assert_eq!(index.get("abl"), None);
+        assert_eq!(index.word(0), "zoo");
+
+        assert_eq!(index.unique_prefix("abanoned"), Some(1));
+        assert_eq!(index.unique_prefix("abst"), Some(4));
+        assert_eq!(index.unique_prefix("abs"), None);
+        assert_eq!(index.unique_prefix("abxx"), None);
+
+        assert_eq!(index.within_one_edit("abel"), Vec::<usize>::new());
+        assert_eq!(index.within_one_edit("ale"), vec![3]);
+        assert_eq!(index.within_one_edit("ac"), vec![6]);
+        assert_eq!(index.within_one_edit("zoo"), vec![0]);
+
+        for token in ["zoo", "abandon", "able", "abl", "ablex", "", "abandoned", "able\0"] {
+            assert_eq!(index.get_constant_time(token), index.get(token), "{:?}", token);
+        }
+        assert_eq!(WordIndex::new(&[]).unwrap().get_constant_time("a"), None);
+    }
+
+    #[test]
+    fn test_shared_reuses_and_rejects_duplicates() {
+        let words = list(&["one", "two", "three"]);
+        let a = WordIndex::shared(&words).unwrap();
+        let b = WordIndex::shared(&words).unwrap();
+        assert!(Arc::ptr_eq(&a, &b));
+        assert!(!Arc::ptr_eq(&a, &WordIndex::shared(&list(&["one", "two"])).unwrap()));
+        assert!(WordIndex::new(&list(&["one", "one"])).is_err());
+    }
+}
@@ -0,0 +1,134 @@
+//! Bit interleaving against burst loss.
+//!
+//! Carrier words hold the payload bits in order, so a deleted paragraph takes
+//! out a run of whole bytes. Interleaving writes the payload bits row by row
+//! into a block `depth` bits wide and reads it out column by column: the bits
+//! of one byte land in up to `depth` regions spread across the text, and a
+//! lost sentence becomes scattered bit errors in many bytes, which a
+//! forward error-correcting code can repair, instead of a missing stretch.
+//!
+//! The depth is stored in a header word ahead of the packed words, so the
+//! decoder needs no parameters of its own.
+
+use crate::codec;
+use crate::index::WordIndex;
+use crate::secret::Wipe;
+use anyhow::{bail, Result};
+
+/// Block width used when none is given: one column per bit of a byte
+pub const DEFAULT_DEPTH: usize = 8;
+
+/// Largest depth a header word can hold for `wordlist`
+pub fn max_depth(wordlist_len: usize) -> usize {
+    wordlist_len.saturating_sub(1)
+}
+
+/// Where each of `bits` bits goes in a block `depth` wide read column by column
+fn permutation(bits: usize, depth: usize) -> Vec<usize> {
+    let rows = bits.div_ceil(depth);
+    // Columns past this one are a row short
+    let full_columns = bits - rows.saturating_sub(1) * depth;
+    (0..bits)
+        .map(|i| {
+            let (row, column) = (i / depth, i % depth);
+            column * rows.saturating_sub(1) + column.min(full_columns) + row
+        })
+        .collect()
+}
+
+fn get_bit(bytes: &[u8], i: usize) -> bool {
+    (bytes[i / 8] >> (7 - i % 8)) & 1 == 1
+}
+
+fn set_bit(bytes: &mut [u8], i: usize) {
+    bytes[i / 8] |= 1 << (7 - i % 8);
+}
+
+/// Spread the bits of `bytes` `depth` columns wide. The length is unchanged.
+pub fn interleave(bytes: &[u8], depth: usize) -> Vec<u8> {
+    let mut out = vec![0u8; bytes.len()];
+    for (i, to) in permutation(bytes.len() * 8, depth).into_iter().enumerate() {
+        if get_bit(bytes, i) {
+            set_bit(&mut out, to);
+        }
+    }
+    out
+}
+
+/// Invert [`interleave`].
+pub fn deinterleave(bytes: &[u8], depth: usize) -> Vec<u8> {
+    let mut out = vec![0u8; bytes.len()];
+    for (i, from) in permutation(bytes.len() * 8, depth).into_iter().enumerate() {
+        if get_bit(bytes, from) {
+            set_bit(&mut out, i);
+        }
+    }
+    out
+}
+
+/// Interleave `bytes` and encode them as a header word followed by the packed
+/// words. `wordlist` must be in canonical sorted order.
+pub fn encode_interleaved(bytes: &[u8], wordlist: &[String], depth: usize) -> Result<Vec<String>> {
+    if depth == 0 || depth > max_depth(wordlist.len()) {
+        bail!("Interleave depth must be between 1 and {}, got {}", max_depth(wordlist.len()), depth);
+    }
+    let mut interleaved = interleave(bytes, depth);
+    let words = codec::encode_bytes(&interleaved, wordlist);
+    interleaved.wipe();
+    let mut out = vec![wordlist[depth].clone()];
+    out.extend(words?);
+    Ok(out)
+}
+
+/// Invert [`encode_interleaved`].
+pub fn decode_interleaved(words: &[String], wordlist: &[String]) -> Result<Vec<u8>> {
+    let Some((header_word, body)) = words.split_first() else {
+        bail!("No words to decode");
+    };
+    let depth = WordIndex::shared(wordlist)?
+        .get(&header_word.to_lowercase())
+        .ok_or_else(|| codec::unknown_word(header_word, 0))?;
+    if depth == 0 {
+        bail!("'{}' is not an interleave header", header_word);
+    }
+    let mut interleaved = codec::decode_bytes(body, wordlist)?;
+    let bytes = deinterleave(&interleaved, depth);
+    interleaved.wipe();
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+
+    #[test]
+    fn test_round_trip() {
+        let list = alpha_wordlist();
+        for payload in [&b""[..], b"a", b"Meet at noon", &[0u8; 3], b"ends in zero\0"] {
+            for depth in [1, 3, DEFAULT_DEPTH, 40, max_depth(list.len())] {
+                assert_eq!(deinterleave(&interleave(payload, depth), depth), payload);
+                let words = encode_interleaved(payload, &list, depth).unwrap();
+                assert_eq!(decode_interleaved(&words, &list).unwrap(), payload, "depth {}", depth);
+            }
+        }
+        assert!(encode_interleaved(b"x", &list, 0).is_err());
+        assert!(encode_interleaved(b"x", &list, list.len()).is_err());
+        assert!(decode_interleaved(&[list[0].clone()], &list).is_err());
+    }
+
+    #[test]
+    fn test_burst_spreads_over_bytes() {
+        let payload: Vec<u8> = (0..64).collect();
+        let mut interleaved = interleave(&payload, DEFAULT_DEPTH);
+        // Garble four consecutive bytes in the middle
+        for byte in &mut interleaved[30..34] {
+            *byte ^= 0xff;
+        }
+        let damaged = deinterleave(&interleaved, DEFAULT_DEPTH);
+        let wrong: Vec<u32> = damaged.iter().zip(&payload).map(|(a, b)| (a ^ b).count_ones()).collect();
+        // One flipped bit in each of 32 bytes instead of four whole bytes
+        assert!(wrong.iter().all(|&bits| bits <= 1));
+        assert_eq!(wrong.iter().filter(|&&bits| bits == 1).count(), 32);
+    }
+}
@@ -0,0 +1,139 @@
+//! Passphrase-derived keys.
+//!
+//! Keyed features need a random stream that only a passphrase holder can
+//! reproduce. A passphrase is stretched with scrypt, salted by a random value
+//! each message carries in its header words ([`index_salt`]), into a
+//! [`PassphraseKey`]; each feature then draws streams from it under its own
+//! label (and an optional salt), so one passphrase never yields the same
+//! stream twice and guesses can't be precomputed across messages.
+//! Features that must tell a right passphrase from a wrong one add a
+//! [`PassphraseKey::commit_tag`].
+
+use crate::secret::Wipe;
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use sha2::{Digest, Sha256};
+
+/// Domain of the scrypt salt. Bump the version when the stretching changes.
+const KDF_DOMAIN: &[u8] = b"glossia/passphrase/v2\0";
+
+/// scrypt cost: `N = 2^15`, `r = 8`, `p = 1`, so each guess takes 32 MiB of
+/// memory. This slows offline guessing; it is not a substitute for a strong
+/// passphrase.
+const KDF_LOG_N: u8 = 15;
+const KDF_R: u32 = 8;
+const KDF_P: u32 = 1;
+
+/// Bytes of a [`PassphraseKey::commit_tag`]
+pub const TAG_LEN: usize = 16;
+
+/// A KDF salt from the word indices of a message header: each index as four
+/// little-endian bytes.
+pub fn index_salt(indices: &[usize]) -> Vec<u8> {
+    indices.iter().flat_map(|&i| (i as u32).to_le_bytes()).collect()
+}
+
+/// A stretched passphrase. Wiped when dropped.
+#[derive(Clone)]
+pub struct PassphraseKey([u8; 32]);
+
+impl PassphraseKey {
+    /// Stretch `passphrase` into a key with scrypt, salted by `salt`.
+    pub fn derive(passphrase: &str, salt: &[u8]) -> Self {
+        let params = scrypt::Params::new(KDF_LOG_N, KDF_R, KDF_P, 32).expect("valid scrypt parameters");
+        let domain_salt = [KDF_DOMAIN, salt].concat();
+        let mut state = [0u8; 32];
+        scrypt::scrypt(passphrase.as_bytes(), &domain_salt, &params, &mut state).expect("32 bytes is a valid output length");
+        let key = Self(state);
+        state.wipe();
+        key
+    }
+
+    /// The key used when no passphrase is given. Streams drawn from it are
+    /// reproducible by anyone; they remove patterns but hide nothing.
+    pub fn unkeyed() -> Self {
+        Self([0u8; 32])
+    }
+
+    /// A deterministic random stream for `label`, varied by `salt`.
+    pub fn rng(&self, label: &str, salt: &[u8]) -> ChaCha20Rng {
+        let seed: [u8; 32] = Sha256::new()
+            .chain_update(self.0)
+            .chain_update(label.as_bytes())
+            .chain_update([0u8])
+            .chain_update(salt)
+            .finalize()
+            .into();
+        ChaCha20Rng::from_seed(seed)
+    }
+
+    /// A tag over `data` that commits to this key: SHA-256 of the key, `label`
+    /// and the length-prefixed data, truncated to [`TAG_LEN`] bytes. A checksum
+    /// can be made to pass under two passphrases at once; a text whose tag
+    /// verifies under two keys needs a collision of the hash, about 2^64 work.
+    pub fn commit_tag(&self, label: &str, data: &[u8]) -> [u8; TAG_LEN] {
+        let digest = Sha256::new()
+            .chain_update(b"glossia/commit/v1\0")
+            .chain_update(self.0)
+            .chain_update(label.as_bytes())
+            .chain_update([0u8])
+            .chain_update((data.len() as u64).to_le_bytes())
+            .chain_update(data)
+            .finalize();
+        digest[..TAG_LEN].try_into().expect("digest is longer than the tag")
+    }
+
+    /// Whether `tag` is the [`commit_tag`](Self::commit_tag) of `data`,
+    /// compared in constant time.
+    pub fn verify_tag(&self, label: &str, data: &[u8], tag: &[u8]) -> bool {
+        let expected = self.commit_tag(label, data);
+        tag.len() == TAG_LEN && expected.iter().zip(tag).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+    }
+}
+
+impl Drop for PassphraseKey {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl PartialEq for PassphraseKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl std::fmt::Debug for PassphraseKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("PassphraseKey(..)")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_streams_are_separated_by_passphrase_label_and_salt() {
+        let key = PassphraseKey::derive("correct horse", b"salt");
+        let draw = |key: &PassphraseKey, label: &str, salt: &[u8]| -> u64 { key.rng(label, salt).gen() };
+
+        assert_eq!(draw(&key, "a", b"1"), draw(&PassphraseKey::derive("correct horse", b"salt"), "a", b"1"));
+        assert_ne!(draw(&key, "a", b"1"), draw(&PassphraseKey::derive("correct horsf", b"salt"), "a", b"1"));
+        assert_ne!(draw(&key, "a", b"1"), draw(&PassphraseKey::derive("correct horse", b"salu"), "a", b"1"));
+        assert_ne!(draw(&key, "a", b"1"), draw(&key, "b", b"1"));
+        assert_ne!(draw(&key, "a", b"1"), draw(&key, "a", b"2"));
+    }
+
+    #[test]
+    fn test_commit_tag_binds_key_label_and_data() {
+        let key = PassphraseKey::derive("correct horse", b"salt");
+        let tag = key.commit_tag("a", b"data");
+        assert!(key.verify_tag("a", b"data", &tag));
+        assert!(!PassphraseKey::derive("correct horsf", b"salt").verify_tag("a", b"data", &tag));
+        assert!(!key.verify_tag("b", b"data", &tag));
+        assert!(!key.verify_tag("a", b"date", &tag));
+        assert!(!key.verify_tag("a", b"data", &tag[1..]));
+    }
+}
@@ -0,0 +1,284 @@
+//! Decoding carrier words that appear in an inflected form.
+//!
+//! Once a carrier word can be conjugated or pluralized ("run" written as
+//! "running" or "ran"), plain decoding no longer finds it. A [`Lemmatizer`]
+//! proposes base forms for tokens that are not wordlist words: nlprule's
+//! lemmas when the `grammar` feature and its model are available, otherwise
+//! (and as a supplement) the suffix rules and irregular forms of
+//! [`RuleLemmatizer`].
+//!
+//! A lemma can be wrong: an inflected cover word may reduce to a wordlist
+//! word ("lying" to "lie"), or a token may have several wordlist lemmas.
+//! With a checksum, [`decode_lemmatized`] searches the readings and keeps the
+//! most likely one whose checksum verifies; without one, every inflected
+//! token with a wordlist lemma is taken as a carrier.
+
+use crate::codec;
+use crate::decode;
+use crate::error::GlossiaError;
+use crate::index::WordIndex;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+
+/// Irregular forms the suffix rules cannot reach: (inflected, lemma).
+const IRREGULAR: &[(&str, &str)] = &[
+    ("ran", "run"), ("went", "go"), ("gone", "go"), ("was", "be"), ("were", "be"),
+    ("been", "be"), ("had", "have"), ("did", "do"), ("done", "do"), ("made", "make"),
+    ("said", "say"), ("took", "take"), ("taken", "take"), ("came", "come"), ("saw", "see"),
+    ("seen", "see"), ("knew", "know"), ("known", "know"), ("got", "get"), ("gotten", "get"),
+    ("gave", "give"), ("given", "give"), ("found", "find"), ("thought", "think"), ("told", "tell"),
+    ("became", "become"), ("left", "leave"), ("felt", "feel"), ("brought", "bring"), ("began", "begin"),
+    ("begun", "begin"), ("kept", "keep"), ("held", "hold"), ("wrote", "write"), ("written", "write"),
+    ("stood", "stand"), ("heard", "hear"), ("meant", "mean"), ("met", "meet"), ("paid", "pay"),
+    ("sat", "sit"), ("spoke", "speak"), ("spoken", "speak"), ("led", "lead"), ("grew", "grow"),
+    ("grown", "grow"), ("lost", "lose"), ("fell", "fall"), ("fallen", "fall"), ("sent", "send"),
+    ("built", "build"), ("understood", "understand"), ("drew", "draw"), ("drawn", "draw"), ("broke", "break"),
+    ("broken", "break"), ("spent", "spend"), ("rose", "rise"), ("risen", "rise"), ("drove", "drive"),
+    ("driven", "drive"), ("bought", "buy"), ("wore", "wear"), ("worn", "wear"), ("chose", "choose"),
+    ("chosen", "choose"), ("caught", "catch"), ("taught", "teach"), ("sold", "sell"), ("fought", "fight"),
+    ("threw", "throw"), ("thrown", "throw"), ("flew", "fly"), ("flown", "fly"), ("ate", "eat"),
+    ("eaten", "eat"), ("won", "win"), ("forgot", "forget"), ("forgotten", "forget"), ("hid", "hide"),
+    ("hidden", "hide"), ("shook", "shake"), ("shaken", "shake"), ("sang", "sing"), ("sung", "sing"),
+    ("swam", "swim"), ("swum", "swim"), ("woke", "wake"), ("woken", "wake"), ("struck", "strike"),
+    ("children", "child"), ("men", "man"), ("women", "woman"), ("feet", "foot"), ("teeth", "tooth"),
+    ("mice", "mouse"), ("geese", "goose"), ("people", "person"), ("lives", "life"), ("wives", "wife"),
+    ("knives", "knife"), ("leaves", "leaf"), ("halves", "half"), ("wolves", "wolf"), ("shelves", "shelf"),
+    ("better", "good"), ("best", "good"), ("worse", "bad"), ("worst", "bad"),
+];
+
+/// Prior that an inflected token with a wordlist lemma is a carrier (rather
+/// than an inflected cover word)
+const CARRIER_PROBABILITY: f64 = 0.8;
+
+const BEAM_WIDTH: usize = 256;
+
+/// Proposes base forms for a word.
+pub trait Lemmatizer {
+    /// Candidate lemmas of a lowercase `word`, most likely first, excluding
+    /// `word` itself. Candidates need not be real words.
+    fn lemmas(&self, word: &str) -> Vec<String>;
+}
+
+/// Suffix-stripping stemmer with an irregular-form table; needs no model.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RuleLemmatizer;
+
+impl Lemmatizer for RuleLemmatizer {
+    fn lemmas(&self, word: &str) -> Vec<String> {
+        let mut out: Vec<String> = IRREGULAR
+            .iter()
+            .filter(|(form, _)| *form == word)
+            .map(|(_, lemma)| lemma.to_string())
+            .collect();
+        let mut push = |candidate: String| {
+            if candidate.len() >= 2 && candidate != word && !out.contains(&candidate) {
+                out.push(candidate);
+            }
+        };
+        let strip = |suffix: &str| word.strip_suffix(suffix).filter(|stem| stem.len() >= 2);
+
+        // lying -> lie
+        if let Some(stem) = word.strip_suffix("ying").filter(|stem| !stem.is_empty()) {
+            push(format!("{}ie", stem));
+        }
+        if let Some(stem) = strip("ies").or_else(|| strip("ied")) {
+            push(format!("{}y", stem));
+        }
+        if let Some(stem) = strip("es") {
+            push(stem.to_string());
+        }
+        if let Some(stem) = strip("s").filter(|stem| !stem.ends_with('s')) {
+            push(stem.to_string());
+        }
+        for suffix in ["ing", "ed", "er", "est"] {
+            if let Some(stem) = strip(suffix) {
+                // running -> run, stopped -> stop
+                let mut chars = stem.chars().rev();
+                if let (Some(last), Some(prev)) = (chars.next(), chars.next()) {
+                    if last == prev && !"aeiouls".contains(last) {
+                        push(stem[..stem.len() - 1].to_string());
+                    }
+                }
+                push(stem.to_string());
+                // making -> make, hoped -> hope
+                push(format!("{}e", stem));
+            }
+        }
+        out
+    }
+}
+
+#[cfg(feature = "grammar")]
+impl Lemmatizer for crate::GrammarChecker {
+    /// nlprule's lemmas, then the [`RuleLemmatizer`] candidates for forms its
+    /// lexicon misses.
+    fn lemmas(&self, word: &str) -> Vec<String> {
+        let mut out: Vec<String> = Vec::new();
+        for sentence in self.tokenize(word) {
+            for token in &sentence.tokens {
+                for tag in &token.tags {
+                    let lemma = tag.lemma.to_lowercase();
+                    if !lemma.is_empty() && lemma != word && !out.contains(&lemma) {
+                        out.push(lemma);
+                    }
+                }
+            }
+        }
+        for lemma in RuleLemmatizer.lemmas(word) {
+            if !out.contains(&lemma) {
+                out.push(lemma);
+            }
+        }
+        out
+    }
+}
+
+/// The best lemmatizer this build has: nlprule's (with the `grammar` feature,
+/// if its English model loads), else [`RuleLemmatizer`].
+pub fn default_lemmatizer() -> Box<dyn Lemmatizer> {
+    #[cfg(feature = "grammar")]
+    if let Ok(checker) = crate::GrammarChecker::global(crate::Language::English) {
+        return Box::new(ArcLemmatizer(checker));
+    }
+    Box::new(RuleLemmatizer)
+}
+
+#[cfg(feature = "grammar")]
+struct ArcLemmatizer(std::sync::Arc<crate::GrammarChecker>);
+
+#[cfg(feature = "grammar")]
+impl Lemmatizer for ArcLemmatizer {
+    fn lemmas(&self, word: &str) -> Vec<String> {
+        self.0.lemmas(word)
+    }
+}
+
+/// Decode `text`, reading inflected tokens as their wordlist lemmas.
+/// `wordlist` must be in canonical sorted order; `corrections` is applied to
+/// each token first (as in [`decode::extract_payload_words_with_corrections`]).
+///
+/// With `checksum`, the payload must end in a checksum word: the most likely
+/// reading whose checksum verifies is returned without it, or
+/// [`GlossiaError::NoValidCandidate`]. Without, each inflected token takes its
+/// first wordlist lemma.
+pub fn decode_lemmatized(
+    text: &str,
+    wordlist: &[String],
+    lemmatizer: &dyn Lemmatizer,
+    corrections: &HashMap<String, String>,
+    checksum: bool,
+) -> Result<Vec<String>> {
+    let word_set: HashSet<String> = wordlist.iter().cloned().collect();
+    let index = WordIndex::shared(wordlist)?;
+
+    // Per carrier-bearing token: candidate indices; a surface wordlist word
+    // has exactly one and no "not a carrier" reading
+    let mut tokens: Vec<(Vec<usize>, bool)> = Vec::new();
+    for token in text.split_whitespace() {
+        let word = decode::normalize_token(token);
+        let word = corrections.get(&word).cloned().unwrap_or(word);
+        if let Some(i) = index.get(&word) {
+            tokens.push((vec![i], false));
+            continue;
+        }
+        if word.is_empty() {
+            continue;
+        }
+        let lemmas: Vec<usize> = lemmatizer
+            .lemmas(&word)
+            .iter()
+            .filter(|lemma| word_set.contains(*lemma))
+            .filter_map(|lemma| index.get(lemma))
+            .collect();
+        if !lemmas.is_empty() {
+            tokens.push((lemmas, true));
+        }
+    }
+
+    if !checksum {
+        return Ok(tokens.iter().map(|(lemmas, _)| wordlist[lemmas[0]].clone()).collect());
+    }
+
+    let mut beam: Vec<(Vec<usize>, f64)> = vec![(Vec::new(), 0.0)];
+    for (lemmas, inflected) in &tokens {
+        let mut options: Vec<(Option<usize>, f64)> = Vec::new();
+        if *inflected {
+            // Earlier lemmas are likelier; skipping means an inflected cover word
+            let share = CARRIER_PROBABILITY / lemmas.len() as f64;
+            options.extend(lemmas.iter().enumerate().map(|(rank, i)| (Some(*i), (share / (rank + 1) as f64).ln())));
+            options.push((None, (1.0 - CARRIER_PROBABILITY).ln()));
+        } else {
+            options.push((Some(lemmas[0]), 0.0));
+        }
+
+        let mut next: HashMap<Vec<usize>, f64> = HashMap::new();
+        for (seq, score) in &beam {
+            for (word, logp) in &options {
+                let mut extended = seq.clone();
+                extended.extend(word);
+                let s = score + logp;
+                let entry = next.entry(extended).or_insert(f64::NEG_INFINITY);
+                if s > *entry {
+                    *entry = s;
+                }
+            }
+        }
+        beam = next.into_iter().collect();
+        beam.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        beam.truncate(BEAM_WIDTH);
+    }
+
+    for (seq, _) in &beam {
+        if let Some((&check, body)) = seq.split_last() {
+            if codec::checksum_index(body, wordlist.len()) == check {
+                return Ok(body.iter().map(|&i| wordlist[i].clone()).collect());
+            }
+        }
+    }
+    Err(GlossiaError::NoValidCandidate.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+
+    /// Full-size (so checksum collisions are rare), alphabetic, with a few real words
+    fn wordlist() -> Vec<String> {
+        let mut words: Vec<String> = ["run", "hope", "stop", "city", "lie", "make", "apple", "zebra"]
+            .iter()
+            .map(|w| w.to_string())
+            .chain(alpha_wordlist().into_iter().take(2040))
+            .collect();
+        words.sort();
+        words
+    }
+
+    #[test]
+    fn test_rule_lemmatizer_reaches_base_forms() {
+        for (form, lemma) in [("running", "run"), ("ran", "run"), ("hoped", "hope"), ("stopped", "stop"), ("cities", "city"), ("making", "make"), ("lying", "lie")] {
+            assert!(RuleLemmatizer.lemmas(form).contains(&lemma.to_string()), "{} -> {}", form, lemma);
+        }
+        assert!(RuleLemmatizer.lemmas("run").iter().all(|l| l != "run"));
+    }
+
+    #[test]
+    fn test_checksum_resolves_inflected_cover_words() {
+        let list = wordlist();
+        let payload: Vec<String> = ["run", "city", "hope"].iter().map(|w| w.to_string()).collect();
+        let framed = codec::append_checksum(&payload, &list).unwrap();
+        let check = framed.last().unwrap();
+
+        // "lying" is an inflected cover word whose lemma happens to be a wordlist word
+        let text = format!("The cat was running to lying cities. They hoped {}.", check);
+        let decoded = decode_lemmatized(&text, &list, &RuleLemmatizer, &HashMap::new(), true).unwrap();
+        assert_eq!(decoded, payload);
+
+        let plain = decode_lemmatized(&text, &list, &RuleLemmatizer, &HashMap::new(), false).unwrap();
+        assert_eq!(plain.len(), 5);
+        assert_eq!(plain[1], "lie");
+
+        let err = decode_lemmatized("The zebra ran.", &list, &RuleLemmatizer, &HashMap::new(), true).unwrap_err();
+        assert_eq!(err.downcast_ref::<GlossiaError>(), Some(&GlossiaError::NoValidCandidate));
+    }
+}
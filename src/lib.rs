@@ -1,14 +1,110 @@
+#[cfg(feature = "async")]
+mod async_models;
+pub mod age_armor;
+pub mod auth_token;
+pub mod capabilities;
+pub mod chaff;
+pub mod codec;
+pub mod config;
+pub mod constraints;
+pub mod container;
+pub mod cover_model;
+pub mod data_mode;
+pub mod decode;
+pub mod deniable;
+pub mod document;
+#[cfg(feature = "embedded-wordlist")]
+pub mod embedded;
+#[cfg(feature = "embedded-models")]
+mod embedded_models;
+pub mod emoji;
+pub mod error;
+pub mod fingerprint;
+pub mod footer;
+pub mod framing;
+pub mod frequency;
+pub mod git_signature;
+#[cfg(all(feature = "fetch", target_arch = "wasm32"))]
+mod fetch;
+pub mod homophones;
+pub mod html;
+pub mod index;
+pub mod interleave;
+pub mod keys;
+pub mod lemma;
+#[cfg(all(feature = "neural", not(target_arch = "wasm32")))]
+pub mod neural;
+pub mod noisy;
+pub mod normalize;
+pub mod nostr;
+pub mod numbers;
+pub mod parity;
+pub mod patch;
+pub mod payload;
+pub mod phonetic;
+pub mod prefix;
+pub mod readability;
+pub mod resync;
+pub mod rule_filter;
+pub mod secret;
+pub mod segment;
+#[cfg(feature = "service")]
+pub mod service;
+pub mod session;
+pub mod signing;
+pub mod sniff;
+pub mod spelling;
+pub mod ssh_key;
+pub mod steganalysis;
+pub mod style;
+pub mod synonyms;
+pub mod template;
+#[cfg(test)]
+mod test_util;
+pub mod tokens;
+pub mod topic;
+pub mod transport;
 pub mod types;
+pub mod validity;
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub mod wasm;
+pub mod weights;
+pub mod whiten;
+
+pub use rule_filter::{GrammarCheckerOptions, RuleSummary};
 
+#[cfg(feature = "grammar")]
 use nlprule::{Tokenizer, Rules};
+#[cfg(feature = "grammar")]
 use anyhow::{Result, Context};
+#[cfg(feature = "grammar")]
+use std::collections::HashMap;
+#[cfg(feature = "grammar")]
+use std::ffi::OsString;
+#[cfg(feature = "grammar")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "grammar")]
+use std::sync::{Arc, Mutex, OnceLock};
 
 /// Helper enum to represent supported languages
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Language {
     English,
 }
 
+impl Language {
+    /// Every language with a bundled grammar and wordlist
+    pub const ALL: &'static [Language] = &[Language::English];
+
+    /// The directory name under `languages/`, as `--language` takes it
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::English => "english",
+        }
+    }
+}
+
+#[cfg(feature = "grammar")]
 impl Language {
     /// Get the language code (ISO 639-1)
     fn code(&self) -> &'static str {
@@ -28,53 +124,204 @@ impl Language {
     }
 }
 
-/// Grammar checker that wraps nlprule functionality
+/// Directories [`GrammarChecker::from_language`] looks for model files in, in
+/// order: `$GLOSSIA_MODEL_DIR`, the Docker locations (checked before local
+/// files, which may be stale or corrupted), `data/`, the current directory,
+/// then the platform data directory (see [`platform_data_dir`]).
+#[cfg(feature = "grammar")]
+pub fn model_search_dirs() -> Vec<PathBuf> {
+    search_dirs(|name| std::env::var_os(name))
+}
+
+#[cfg(feature = "grammar")]
+fn search_dirs(env: impl Fn(&str) -> Option<OsString>) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = env("GLOSSIA_MODEL_DIR").filter(|dir| !dir.is_empty()).map(PathBuf::from).into_iter().collect();
+    dirs.extend(["/opt/nlprule-data", "/app/data", "data", "."].map(PathBuf::from));
+    dirs.extend(platform_data_dir(env));
+    dirs
+}
+
+/// `glossia` under the per-user data directory: `%APPDATA%` on Windows,
+/// `~/Library/Application Support` on macOS, `$XDG_DATA_HOME` (default
+/// `~/.local/share`) elsewhere.
+#[cfg(feature = "grammar")]
+fn platform_data_dir(env: impl Fn(&str) -> Option<OsString>) -> Option<PathBuf> {
+    let var = |name: &str| env(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let base = if cfg!(windows) {
+        var("APPDATA")?
+    } else if cfg!(target_os = "macos") {
+        var("HOME")?.join("Library/Application Support")
+    } else {
+        var("XDG_DATA_HOME").or_else(|| Some(var("HOME")?.join(".local/share")))?
+    };
+    Some(base.join("glossia"))
+}
+
+/// Grammar checker that wraps nlprule functionality (requires the `grammar` feature)
+#[cfg(feature = "grammar")]
 pub struct GrammarChecker {
     tokenizer: Tokenizer,
     rules: Rules,
+    /// The rule selection last applied ([`GrammarChecker::set_options`])
+    options: GrammarCheckerOptions,
 }
 
+#[cfg(feature = "grammar")]
 impl GrammarChecker {
     /// Create a new GrammarChecker from language, loading tokenizer and rules from paths
+    #[cfg(not(all(feature = "mmap", not(target_arch = "wasm32"))))]
     pub fn from_paths(tokenizer_path: &str, rules_path: &str) -> Result<Self> {
         let tokenizer = Tokenizer::new(tokenizer_path)
             .with_context(|| format!("Failed to load tokenizer from {}", tokenizer_path))?;
         let rules = Rules::new(rules_path)
             .with_context(|| format!("Failed to load rules from {}", rules_path))?;
         
-        Ok(Self { tokenizer, rules })
+        Ok(Self { tokenizer, rules, options: Default::default() })
+    }
+
+    /// Create a new GrammarChecker from language, memory-mapping tokenizer and rules
+    /// from paths (the `mmap` feature; see [`GrammarChecker::from_paths_mmap`])
+    #[cfg(all(feature = "mmap", not(target_arch = "wasm32")))]
+    pub fn from_paths(tokenizer_path: &str, rules_path: &str) -> Result<Self> {
+        Self::from_paths_mmap(tokenizer_path, rules_path)
+    }
+
+    /// Create a new GrammarChecker by memory-mapping the model files.
+    ///
+    /// The binaries are deserialized straight from the mapping, so the file pages are
+    /// demand-loaded from (and shared through) the page cache instead of first being
+    /// copied into a heap buffer. The parsed model itself still lives on the heap.
+    #[cfg(all(feature = "mmap", not(target_arch = "wasm32")))]
+    pub fn from_paths_mmap(tokenizer_path: &str, rules_path: &str) -> Result<Self> {
+        fn map(path: &str) -> Result<memmap2::Mmap> {
+            let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path))?;
+            // SAFETY: model files are read-only inputs; truncating them while mapped is
+            // outside what we support, same as replacing them mid-read.
+            unsafe { memmap2::Mmap::map(&file) }.with_context(|| format!("Failed to map {}", path))
+        }
+
+        let tokenizer_map = map(tokenizer_path)?;
+        let tokenizer = Tokenizer::from_reader(&tokenizer_map[..])
+            .with_context(|| format!("Failed to load tokenizer from {}", tokenizer_path))?;
+        let rules_map = map(rules_path)?;
+        let rules = Rules::from_reader(&rules_map[..])
+            .with_context(|| format!("Failed to load rules from {}", rules_path))?;
+
+        Ok(Self { tokenizer, rules, options: Default::default() })
+    }
+
+    /// Create a new GrammarChecker from the tokenizer and rules binaries for
+    /// `language` in `dir`
+    pub fn from_dir(dir: impl AsRef<Path>, language: Language) -> Result<Self> {
+        let dir = dir.as_ref();
+        let tokenizer_path = dir.join(language.tokenizer_filename());
+        let rules_path = dir.join(language.rules_filename());
+        Self::from_paths(&tokenizer_path.to_string_lossy(), &rules_path.to_string_lossy())
+    }
+
+    /// Create a new GrammarChecker from the models compiled into the binary
+    /// (needs the `embedded-models` feature)
+    #[cfg(not(feature = "embedded-models"))]
+    pub fn from_embedded(_language: Language) -> Result<Self> {
+        anyhow::bail!("Embedded models need glossia built with the 'embedded-models' feature")
     }
 
-    /// Create a new GrammarChecker from language, using default paths
-    /// Checks multiple locations: current directory, data/, and /app/data (for Docker)
+    /// Create a new GrammarChecker from language, using the first of
+    /// [`model_search_dirs`] that holds both model files, or the models
+    /// compiled in with the `embedded-models` feature if none does
     pub fn from_language(language: Language) -> Result<Self> {
         let tokenizer_filename = language.tokenizer_filename();
         let rules_filename = language.rules_filename();
-        
-        // Try multiple locations (check Docker location first to avoid corrupted local files)
-        let locations = vec![
-            "/opt/nlprule-data/", // Docker persistent location (check first)
-            "/app/data/",         // Docker location
-            "data/",              // data subdirectory
-            "",                   // current directory
-        ];
-        
-        for location in &locations {
-            let tokenizer_path = format!("{}{}", location, tokenizer_filename);
-            let rules_path = format!("{}{}", location, rules_filename);
-            
-            if std::path::Path::new(&tokenizer_path).exists() && 
-               std::path::Path::new(&rules_path).exists() {
-                return Self::from_paths(&tokenizer_path, &rules_path);
+        let dirs = model_search_dirs();
+
+        for dir in &dirs {
+            if dir.join(&tokenizer_filename).exists() && dir.join(&rules_filename).exists() {
+                return Self::from_dir(dir, language);
             }
         }
-        
-        // If none found, try the default (current directory) and let it error with a helpful message
-        Self::from_paths(&tokenizer_filename, &rules_filename)
-            .with_context(|| format!(
-                "Could not find {} and {} in any of: current directory, data/, /app/data/, or /opt/nlprule-data/",
-                tokenizer_filename, rules_filename
-            ))
+
+        if cfg!(feature = "embedded-models") {
+            return Self::from_embedded(language);
+        }
+
+        // If none found, try the current directory and let it error with a helpful message
+        let searched: Vec<String> = dirs.iter().map(|dir| dir.display().to_string()).collect();
+        Self::from_dir(".", language).with_context(|| {
+            format!(
+                "Could not find {} and {} in any of: {} (set GLOSSIA_MODEL_DIR to their directory)",
+                tokenizer_filename,
+                rules_filename,
+                searched.join(", ")
+            )
+        })
+    }
+
+    /// The process-wide checker for `language`, loaded on first use.
+    ///
+    /// Loading the models is expensive, so callers that check text repeatedly
+    /// (servers, the encode tests, the weight tools) share one instance instead of
+    /// each calling [`GrammarChecker::from_language`]. A failed load is not cached,
+    /// so a later call retries once the model files are in place.
+    pub fn global(language: Language) -> Result<Arc<Self>> {
+        Self::global_with(language, &GrammarCheckerOptions::default(), &[])
+    }
+
+    /// Like [`GrammarChecker::global`], for a checker with `rule_files` added
+    /// ([`GrammarChecker::add_rules_file`]) and `options` applied. Shared
+    /// checkers can't be changed, so each combination of language, options
+    /// and rule files is loaded once and shared on its own.
+    pub fn global_with(language: Language, options: &GrammarCheckerOptions, rule_files: &[PathBuf]) -> Result<Arc<Self>> {
+        type Key = (Language, GrammarCheckerOptions, Vec<PathBuf>);
+        static REGISTRY: OnceLock<Mutex<HashMap<Key, Arc<GrammarChecker>>>> = OnceLock::new();
+        let key = (language, options.clone(), rule_files.to_vec());
+        let mut registry = REGISTRY.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(checker) = registry.get(&key) {
+            return Ok(Arc::clone(checker));
+        }
+        let mut checker = Self::from_language(language)?;
+        for path in rule_files {
+            checker.add_rules_file(path)?;
+        }
+        checker.set_options(options)?;
+        let checker = Arc::new(checker);
+        registry.insert(key, Arc::clone(&checker));
+        Ok(checker)
+    }
+
+    /// Add the rules in a compiled nlprule rules binary to the ones loaded.
+    ///
+    /// Only compiled binaries are accepted: nlprule parses LanguageTool rule
+    /// XML only at build time, so house-style rules (say, a `house_style`
+    /// category forbidding second person) are compiled with `nlprule-build`
+    /// into a `*_rules.bin` of their own and loaded here. Rule XML is refused
+    /// with an error saying so. Added rules are switched on; a later
+    /// [`GrammarChecker::set_options`] selects among them like any other.
+    /// Returns how many rules were added, or an error without adding any if
+    /// one shares an id with a rule already loaded.
+    pub fn add_rules(&mut self, mut reader: impl std::io::Read) -> Result<usize> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).context("Failed to read additional rules")?;
+        let start = bytes.strip_prefix("\u{feff}".as_bytes()).unwrap_or(&bytes).trim_ascii_start();
+        if ["<?xml", "<rules", "<!--"].iter().any(|tag| start.starts_with(tag.as_bytes())) {
+            anyhow::bail!(
+                "Additional rules must be a compiled nlprule rules binary, not LanguageTool rule XML; compile the XML with nlprule-build first"
+            );
+        }
+        let extra = Rules::from_reader(&bytes[..]).context("Failed to load additional rules (expected a compiled nlprule rules binary)")?;
+        let ids: std::collections::HashSet<String> = self.rules.rules().iter().map(|rule| rule.id().to_string()).collect();
+        if let Some(rule) = extra.rules().iter().find(|rule| ids.contains(&rule.id().to_string())) {
+            anyhow::bail!("Rule '{}' is already loaded", rule.id());
+        }
+        let added = extra.rules().len();
+        self.rules = std::mem::take(&mut self.rules).into_iter().chain(extra).collect();
+        Ok(added)
+    }
+
+    /// [`GrammarChecker::add_rules`] from a file.
+    pub fn add_rules_file(&mut self, path: impl AsRef<Path>) -> Result<usize> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        self.add_rules(std::io::BufReader::new(file)).with_context(|| format!("Failed to add rules from {}", path.display()))
     }
 
     /// Check grammar of a sentence and return suggestions
@@ -87,9 +334,9 @@ impl GrammarChecker {
         self.rules.correct(text, &self.tokenizer)
     }
 
-    /// Tokenize text and return sentences
-    pub fn tokenize<'a>(&'a self, text: &'a str) -> impl Iterator<Item = nlprule::types::Sentence<'a>> {
-        self.tokenizer.pipe(text)
+    /// Split text into sentences of tagged tokens
+    pub fn tokenize<'a>(&'a self, text: &'a str) -> impl Iterator<Item = tokens::Sentence> + 'a {
+        self.tokenizer.pipe(text).map(|sentence| tokens::Sentence::from(&sentence))
     }
 
     /// Check if a sentence is grammatically correct
@@ -99,10 +346,71 @@ impl GrammarChecker {
     }
 }
 
-#[cfg(test)]
+#[cfg(all(test, feature = "grammar"))]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_model_search_dirs() {
+        let env = |name: &str| match name {
+            "GLOSSIA_MODEL_DIR" => Some(OsString::from("/srv/models")),
+            "HOME" => Some(OsString::from("/home/ada")),
+            "APPDATA" => Some(OsString::from("C:\\Users\\ada\\AppData\\Roaming")),
+            _ => None,
+        };
+        let dirs = search_dirs(env);
+        assert_eq!(dirs[0], PathBuf::from("/srv/models"));
+        assert_eq!(dirs[1], PathBuf::from("/opt/nlprule-data"));
+        let platform = dirs.last().unwrap();
+        assert!(platform.ends_with("glossia") && platform != &PathBuf::from("glossia"), "{:?}", dirs);
+        if cfg!(all(unix, not(target_os = "macos"))) {
+            assert_eq!(platform, &PathBuf::from("/home/ada/.local/share/glossia"));
+        }
+        // Nothing set: just the fixed locations
+        assert_eq!(search_dirs(|_| None).len(), 4);
+    }
+
+    #[test]
+    fn test_global_checker_is_shared() {
+        // Skip if the model files are not available
+        let Ok(first) = GrammarChecker::global(Language::English) else {
+            return;
+        };
+        let second = GrammarChecker::global(Language::English).unwrap();
+        assert!(Arc::ptr_eq(&first, &second));
+
+        // Other options get a checker of their own, also shared
+        let options = GrammarCheckerOptions { enabled_categories: Some(vec!["grammar".into()]), disabled_ids: vec![] };
+        let narrowed = GrammarChecker::global_with(Language::English, &options, &[]).unwrap();
+        assert!(!Arc::ptr_eq(&first, &narrowed));
+        assert_eq!(narrowed.options(), &options);
+        assert_eq!(first.options(), &GrammarCheckerOptions::default());
+        assert!(Arc::ptr_eq(&narrowed, &GrammarChecker::global_with(Language::English, &options, &[]).unwrap()));
+        assert!(GrammarChecker::global_with(Language::English, &options, &[PathBuf::from("no_such_rules.bin")]).is_err());
+    }
+
+    #[test]
+    fn test_add_rules() {
+        // Skip if the model files are not available
+        let Ok(mut checker) = GrammarChecker::from_language(Language::English) else {
+            return;
+        };
+        let total = checker.rule_summary().total;
+        let mut empty = Vec::new();
+        Rules::default().to_writer(&mut empty).unwrap();
+        assert_eq!(checker.add_rules(&empty[..]).unwrap(), 0);
+        assert!(checker.add_rules(&b"not a rules binary"[..]).is_err());
+        let err = checker.add_rules(&b"<?xml version=\"1.0\"?>\n<rules lang=\"en\"></rules>"[..]).unwrap_err();
+        assert!(err.to_string().contains("nlprule-build"), "{}", err);
+
+        // The model's own rules are all duplicates
+        let mut bundled = Vec::new();
+        checker.rules.to_writer(&mut bundled).unwrap();
+        let err = checker.add_rules(&bundled[..]).unwrap_err();
+        assert!(err.to_string().contains("already loaded"), "{}", err);
+        assert_eq!(checker.rule_summary().total, total);
+    }
+
     #[test]
     fn test_grammar_checking() -> Result<()> {
         // This test requires the binary files to be present
@@ -0,0 +1,344 @@
+//! Cover text written by a language model (feature `neural`).
+//!
+//! The prose engine builds sentences around payload words, so the carriers
+//! are the odd words out. Here a small quantized GPT-style model writes the
+//! whole text and the payload only chooses between its likeliest next tokens:
+//! at each step up to `top_k` candidates are ranked by the model, and the next
+//! bits of the payload pick one. The receiver runs the same model over the
+//! text, finds where each token ranked, and reads the bits back, so both sides
+//! need the same model file, tokenizer and prompt.
+//!
+//! A step's candidates are kept prefix-free (no candidate's text starts
+//! another's), so the receiver can tell which one was written without
+//! re-tokenizing the text. [`encode_with`] and [`decode_with`] run over any
+//! [`LanguageModel`]; [`Gguf`] is a llama-architecture GGUF model run on the
+//! CPU through candle.
+
+use crate::framing::{read_varint, write_varint};
+use anyhow::{anyhow, bail, Context, Result};
+use candle_core::quantized::gguf_file;
+use candle_core::{DType, Device, Tensor};
+use candle_transformers::models::quantized_llama::ModelWeights;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tokenizers::Tokenizer;
+
+/// Tokens ranked per step before the prefix-free candidates are picked
+const SCAN_FACTOR: usize = 8;
+
+/// How the model writes: what it continues and how many tokens it weighs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct NeuralOptions {
+    /// Text the cover text continues; not part of the output
+    pub prompt: String,
+    /// Candidates per step, a power of two: 16 carries up to 4 bits a token
+    pub top_k: usize,
+    /// Likeliest tokens written after the payload, at most, to end the sentence
+    pub tail_tokens: usize,
+}
+
+impl Default for NeuralOptions {
+    fn default() -> Self {
+        Self { prompt: String::new(), top_k: 16, tail_tokens: 24 }
+    }
+}
+
+/// A next-token model, driven one token at a time.
+pub trait LanguageModel {
+    /// Start over after `prompt`; the logits of the first token.
+    fn start(&mut self, prompt: &str) -> Result<Vec<f32>>;
+    /// Append `token`; the logits of the one after it.
+    fn push(&mut self, token: u32) -> Result<Vec<f32>>;
+    /// The text `token` adds after other text, or `None` for special tokens
+    /// and tokens that are part of a character
+    fn text(&self, token: u32) -> Option<&str>;
+}
+
+/// The text [`encode_with`] wrote, and what it cost
+#[derive(Clone, Debug, PartialEq)]
+pub struct EncodeReport {
+    /// The cover text
+    pub text: String,
+    /// Tokens written, the tail included
+    pub tokens: usize,
+    /// Tokens written after the payload, to end the sentence
+    pub tail_tokens: usize,
+    /// Bits carried: the payload and its length prefix
+    pub payload_bits: usize,
+    /// Time spent in the model and picking tokens
+    pub elapsed: Duration,
+}
+
+impl EncodeReport {
+    /// Payload bits per carrier token (the tail carries none).
+    pub fn bits_per_token(&self) -> f64 {
+        self.payload_bits as f64 / (self.tokens - self.tail_tokens).max(1) as f64
+    }
+
+    /// Inference speed.
+    pub fn tokens_per_second(&self) -> f64 {
+        self.tokens as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+/// The tokens the next bits choose between, likeliest first: up to `top_k`
+/// whose texts are prefix-free, cut to a power of two. The first token of a
+/// text loses its leading whitespace.
+fn candidates<'m, M: LanguageModel>(model: &'m M, logits: &[f32], top_k: usize, first: bool) -> Vec<(u32, &'m str)> {
+    let mut ranked: Vec<u32> = (0..logits.len() as u32).collect();
+    let by_logit = |a: &u32, b: &u32| logits[*b as usize].total_cmp(&logits[*a as usize]).then(a.cmp(b));
+    let scan = (top_k * SCAN_FACTOR).min(ranked.len());
+    if scan < ranked.len() {
+        ranked.select_nth_unstable_by(scan, by_logit);
+        ranked.truncate(scan);
+    }
+    ranked.sort_unstable_by(by_logit);
+
+    let mut picked: Vec<(u32, &str)> = Vec::new();
+    for token in ranked {
+        let Some(text) = model.text(token).map(|text| if first { text.trim_start() } else { text }) else {
+            continue;
+        };
+        if text.is_empty() || picked.iter().any(|(_, other)| other.starts_with(text) || text.starts_with(other)) {
+            continue;
+        }
+        picked.push((token, text));
+        if picked.len() == top_k {
+            break;
+        }
+    }
+    if !picked.is_empty() {
+        picked.truncate(1 << picked.len().ilog2());
+    }
+    picked
+}
+
+fn ends_sentence(text: &str) -> bool {
+    text.trim_end().ends_with(['.', '!', '?'])
+}
+
+/// Cover text carrying `payload`, written by `model`.
+pub fn encode_with<M: LanguageModel>(model: &mut M, payload: &[u8], options: &NeuralOptions) -> Result<EncodeReport> {
+    if options.top_k < 2 || !options.top_k.is_power_of_two() {
+        bail!("top_k must be a power of two of at least 2, not {}", options.top_k);
+    }
+    let started = Instant::now();
+    let mut framed = Vec::new();
+    write_varint(&mut framed, payload.len() as u64);
+    framed.extend_from_slice(payload);
+    let payload_bits = 8 * framed.len();
+
+    let mut logits = model.start(&options.prompt)?;
+    let (mut text, mut tokens, mut tail, mut read) = (String::new(), 0, 0, 0);
+    loop {
+        if read >= payload_bits {
+            if ends_sentence(&text) || tail == options.tail_tokens {
+                break;
+            }
+            tail += 1;
+        }
+        let candidates = candidates(model, &logits, options.top_k, text.is_empty());
+        if candidates.is_empty() {
+            bail!("The model offers no usable next token after {} tokens", tokens);
+        }
+        // Past the payload the bits read as zeros: the likeliest token
+        let width = candidates.len().ilog2() as usize;
+        let index = (read..read + width).fold(0, |index, bit| index << 1 | usize::from(bit_at(&framed, bit)));
+        read += width;
+        let (token, piece) = candidates[index];
+        text.push_str(piece);
+        tokens += 1;
+        logits = model.push(token)?;
+    }
+    Ok(EncodeReport { text, tokens, tail_tokens: tail, payload_bits, elapsed: started.elapsed() })
+}
+
+fn bit_at(bytes: &[u8], bit: usize) -> bool {
+    bytes.get(bit / 8).is_some_and(|byte| byte >> (7 - bit % 8) & 1 == 1)
+}
+
+/// The payload [`encode_with`] hid in `text`, read back with the same model.
+pub fn decode_with<M: LanguageModel>(model: &mut M, text: &str, options: &NeuralOptions) -> Result<Vec<u8>> {
+    let text = text.trim_start();
+    let mut logits = model.start(&options.prompt)?;
+    let (mut framed, mut bits, mut at) = (Vec::new(), 0usize, 0);
+    loop {
+        if let Some(payload) = framed_payload(&framed[..bits / 8])? {
+            return Ok(payload);
+        }
+        if at == text.len() {
+            bail!("Text ends before the payload does");
+        }
+        let candidates = candidates(model, &logits, options.top_k, at == 0);
+        let (index, (token, piece)) = candidates
+            .iter()
+            .enumerate()
+            .find(|(_, (_, piece))| text[at..].starts_with(piece))
+            .ok_or_else(|| anyhow!("Text at byte {} is not one of the model's candidates", at))?;
+        for bit in (0..candidates.len().ilog2()).rev() {
+            if bits % 8 == 0 {
+                framed.push(0);
+            }
+            framed[bits / 8] |= ((index >> bit & 1) as u8) << (7 - bits % 8);
+            bits += 1;
+        }
+        at += piece.len();
+        logits = model.push(*token)?;
+    }
+}
+
+/// The payload behind a complete length prefix, once all of it is there.
+fn framed_payload(framed: &[u8]) -> Result<Option<Vec<u8>>> {
+    if !framed.iter().any(|byte| byte & 0x80 == 0) {
+        return Ok(None);
+    }
+    let (len, rest) = read_varint(framed)?;
+    Ok(usize::try_from(len).ok().and_then(|len| rest.get(..len)).map(<[u8]>::to_vec))
+}
+
+/// A quantized llama-architecture model (TinyLlama, SmolLM and the like) from
+/// a GGUF file, with its `tokenizer.json`, run on the CPU.
+pub struct Gguf {
+    weights: ModelWeights,
+    tokenizer: Tokenizer,
+    /// Each token's text after other text
+    texts: Vec<Option<String>>,
+    /// Tokens already in the model's cache
+    position: usize,
+}
+
+impl Gguf {
+    pub fn load(model: impl AsRef<Path>, tokenizer: impl AsRef<Path>) -> Result<Self> {
+        let (model, tokenizer) = (model.as_ref(), tokenizer.as_ref());
+        let mut file = std::fs::File::open(model).with_context(|| format!("Failed to open model {}", model.display()))?;
+        let content = gguf_file::Content::read(&mut file).with_context(|| format!("Invalid GGUF model {}", model.display()))?;
+        let weights = ModelWeights::from_gguf(content, &mut file, &Device::Cpu)
+            .with_context(|| format!("Failed to load model {}", model.display()))?;
+        let tokenizer = Tokenizer::from_file(tokenizer)
+            .map_err(|e| anyhow!("Failed to load tokenizer {}: {}", tokenizer.display(), e))?;
+
+        // A token's text is what it adds after another: decoded alone, a
+        // leading-space token ("▁the") would lose its space
+        let anchor = *tokenizer
+            .encode("a", false)
+            .map_err(|e| anyhow!("Tokenizer failed: {}", e))?
+            .get_ids()
+            .first()
+            .ok_or_else(|| anyhow!("The tokenizer has no token for 'a'"))?;
+        let lead = tokenizer.decode(&[anchor], true).map_err(|e| anyhow!("Tokenizer failed: {}", e))?;
+        let texts = (0..tokenizer.get_vocab_size(true) as u32)
+            .map(|token| {
+                let text = tokenizer.decode(&[anchor, token], true).ok()?;
+                let piece = text.strip_prefix(&lead)?;
+                let usable = !piece.is_empty() && !piece.contains('\u{FFFD}') && !piece.chars().any(char::is_control);
+                usable.then(|| piece.to_string())
+            })
+            .collect();
+        Ok(Self { weights, tokenizer, texts, position: 0 })
+    }
+
+    fn forward(&mut self, tokens: &[u32]) -> Result<Vec<f32>> {
+        let input = Tensor::new(tokens, &Device::Cpu)?.unsqueeze(0)?;
+        let logits = self.weights.forward(&input, self.position)?;
+        self.position += tokens.len();
+        Ok(logits.squeeze(0)?.to_dtype(DType::F32)?.to_vec1()?)
+    }
+}
+
+impl LanguageModel for Gguf {
+    fn start(&mut self, prompt: &str) -> Result<Vec<f32>> {
+        let encoding = self.tokenizer.encode(prompt, true).map_err(|e| anyhow!("Tokenizer failed: {}", e))?;
+        if encoding.get_ids().is_empty() {
+            bail!("The prompt is empty and the tokenizer adds no start token");
+        }
+        // Position 0 clears the model's cache
+        self.position = 0;
+        self.forward(encoding.get_ids())
+    }
+
+    fn push(&mut self, token: u32) -> Result<Vec<f32>> {
+        self.forward(&[token])
+    }
+
+    fn text(&self, token: u32) -> Option<&str> {
+        self.texts.get(token as usize)?.as_deref()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Forty pieces, one special token, and " ca" to clash with " cat"
+    struct Toy {
+        pieces: Vec<Option<String>>,
+        last: u32,
+    }
+
+    impl Toy {
+        fn new() -> Self {
+            let words = "the cat sat on a mat and dog ran to park it was very warm day we saw \
+                         two birds in tree by old red barn then went home for tea with milk ca";
+            let mut pieces = vec![None];
+            pieces.extend(words.split(' ').map(|word| Some(format!(" {}", word))));
+            pieces.extend([Some(".".to_string()), Some(",".to_string())]);
+            Self { pieces, last: 0 }
+        }
+
+        fn logits(&self) -> Vec<f32> {
+            (0..self.pieces.len() as u32).map(|token| ((self.last * 31 + token * 17) % 97) as f32).collect()
+        }
+    }
+
+    impl LanguageModel for Toy {
+        fn start(&mut self, prompt: &str) -> Result<Vec<f32>> {
+            self.last = prompt.len() as u32;
+            Ok(self.logits())
+        }
+
+        fn push(&mut self, token: u32) -> Result<Vec<f32>> {
+            self.last = token;
+            Ok(self.logits())
+        }
+
+        fn text(&self, token: u32) -> Option<&str> {
+            self.pieces.get(token as usize)?.as_deref()
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let options = NeuralOptions { prompt: "Dear Sam,".to_string(), ..Default::default() };
+        for payload in [b"meet at noon".as_slice(), b"", &[0xff; 40]] {
+            let report = encode_with(&mut Toy::new(), payload, &options).unwrap();
+            assert_eq!(report.payload_bits, 8 * (1 + payload.len()));
+            assert!(report.bits_per_token() <= 4.0, "{} bits a token", report.bits_per_token());
+            assert!(!report.text.starts_with(' '));
+            assert_eq!(decode_with(&mut Toy::new(), &report.text, &options).unwrap(), payload);
+            assert_eq!(decode_with(&mut Toy::new(), &format!("\n{}\n", report.text), &options).unwrap(), payload);
+        }
+
+        let report = encode_with(&mut Toy::new(), b"meet at noon", &options).unwrap();
+        let other = NeuralOptions { prompt: "Hi".to_string(), ..options.clone() };
+        assert_ne!(decode_with(&mut Toy::new(), &report.text, &other).ok().as_deref(), Some(b"meet at noon".as_slice()));
+        assert!(decode_with(&mut Toy::new(), &report.text[..report.text.len() / 2], &options).is_err());
+        assert!(encode_with(&mut Toy::new(), b"", &NeuralOptions { top_k: 12, ..options }).is_err());
+    }
+
+    #[test]
+    fn test_candidates_are_prefix_free() {
+        for last in 0..Toy::new().pieces.len() as u32 {
+            let toy = Toy { last, ..Toy::new() };
+            let first = last % 2 == 0;
+            let picked = candidates(&toy, &toy.logits(), 16, first);
+            assert_eq!(picked.len(), 16);
+            for (i, (_, a)) in picked.iter().enumerate() {
+                assert!(!a.is_empty());
+                assert!(!first || !a.starts_with(' '), "{:?}", a);
+                for (_, b) in &picked[i + 1..] {
+                    assert!(!a.starts_with(b) && !b.starts_with(a), "{:?} and {:?}", a, b);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,437 @@
+//! Decoding cover text that went through OCR or speech recognition.
+//!
+//! Scanned or dictated text garbles some carrier words ("rnoral" for "moral",
+//! "ate" for "eight"), which plain decoding silently drops. [`decode_noisy`]
+//! proposes wordlist candidates for every token from bundled confusion tables,
+//! beam-searches the most likely carrier sequence, and accepts the best
+//! hypothesis whose trailing checksum word (see [`crate::codec::append_checksum`])
+//! verifies. [`decode_noisy_with`] also takes a spelling dictionary
+//! ([`crate::spelling`]), so OCR tokens that are words in their own right are
+//! less readily taken for garbled carriers. [`decode_noisy_report`] says how
+//! each carrier was read, so a UI can point the user at the words to check
+//! against the source.
+
+use crate::codec;
+use crate::decode;
+use crate::error::GlossiaError;
+use crate::homophones::HomophoneTable;
+use crate::index::WordIndex;
+use crate::spelling::Dictionary;
+use anyhow::Result;
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// Which channel the text went through.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NoiseModel {
+    /// Scanned or photographed text: character-level misreads
+    Ocr,
+    /// Speech-to-text: whole-word homophone substitutions
+    Asr,
+}
+
+/// How a carrier was read from its token
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MatchKind {
+    /// The token is the wordlist word
+    Exact,
+    /// The token shares the word's unique 4-letter prefix
+    Prefix,
+    /// The token is one dropped, added or changed letter away from the word
+    Edit,
+    /// A known OCR misread or ASR homophone of the word
+    Confusion,
+}
+
+impl MatchKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MatchKind::Exact => "exact",
+            MatchKind::Prefix => "prefix",
+            MatchKind::Edit => "edit",
+            MatchKind::Confusion => "confusion",
+        }
+    }
+}
+
+/// A carrier [`decode_noisy_report`] read
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodedWord {
+    /// The wordlist word
+    pub word: String,
+    /// The token it was read from, as it appears in the text
+    pub token: String,
+    /// Byte range of the token in the text
+    pub span: Range<usize>,
+    pub kind: MatchKind,
+    /// How likely the token is to be this word, from the confusion tables
+    pub probability: f64,
+}
+
+/// The carriers of a noisy decode and how each one was read
+#[derive(Clone, Debug, PartialEq)]
+pub struct DecodeReport {
+    /// Payload words, in order
+    pub words: Vec<DecodedWord>,
+    /// The checksum word that verified them
+    pub checksum: DecodedWord,
+}
+
+impl DecodeReport {
+    /// Carriers not read exactly, which a user may want to check
+    pub fn uncertain(&self) -> impl Iterator<Item = &DecodedWord> {
+        self.words.iter().chain([&self.checksum]).filter(|word| word.kind != MatchKind::Exact)
+    }
+}
+
+/// OCR character confusions: (as read, intended, probability).
+const OCR_CONFUSIONS: &[(&str, &str, f64)] = &[
+    ("rn", "m", 0.30),
+    ("m", "rn", 0.10),
+    ("cl", "d", 0.20),
+    ("vv", "w", 0.30),
+    ("ii", "u", 0.10),
+    ("li", "h", 0.10),
+    ("0", "o", 0.40),
+    ("1", "l", 0.30),
+    ("1", "i", 0.20),
+    ("l", "i", 0.10),
+    ("i", "l", 0.10),
+    ("5", "s", 0.30),
+    ("8", "b", 0.20),
+    ("6", "b", 0.10),
+    ("3", "e", 0.10),
+    ("c", "e", 0.10),
+    ("e", "c", 0.10),
+    ("h", "b", 0.05),
+    ("b", "h", 0.05),
+    ("u", "v", 0.05),
+    ("v", "u", 0.05),
+    ("n", "h", 0.05),
+    ("f", "t", 0.05),
+    ("t", "f", 0.05),
+];
+
+/// ASR word confusions beyond the homophone table: (as heard, intended, probability).
+const ASR_CONFUSIONS: &[(&str, &str, f64)] = &[
+    ("then", "than", 0.10),
+    ("than", "then", 0.10),
+    ("accept", "except", 0.05),
+    ("except", "accept", 0.05),
+    ("affect", "effect", 0.10),
+    ("effect", "affect", 0.10),
+    ("lose", "loose", 0.05),
+    ("quite", "quiet", 0.05),
+    ("quiet", "quite", 0.05),
+];
+
+/// Probability that a homophone in the transcript stands for the carrier word
+const HOMOPHONE_PROBABILITY: f64 = 0.5;
+
+/// Probability that an exact wordlist token is really a misread cover word
+const FALSE_CARRIER_PROBABILITY: f64 = 0.02;
+
+/// Probability that a 4+ letter token sharing a unique 4-letter prefix with a
+/// wordlist word is a mangled form of it (BIP39 words are unique in 4 letters)
+const PREFIX_PROBABILITY: f64 = 0.05;
+
+/// Probability that a token one dropped, added or changed letter away from a
+/// wordlist word is that word (misreads the substitution table doesn't cover)
+const EDIT_PROBABILITY: f64 = 0.02;
+
+/// How much less likely an OCR carrier reading is when the token is itself a
+/// correctly spelled word: scanners mostly garble letters into non-words
+const DICTIONARY_WORD_FACTOR: f64 = 0.1;
+
+/// Substitution rules applied at most this many times per token
+const MAX_EDITS: usize = 2;
+
+const BEAM_WIDTH: usize = 256;
+
+/// Decode noisy cover text. `wordlist` must be in canonical sorted order, and
+/// the payload must have been framed with a checksum word. Returns the payload
+/// words without the checksum.
+pub fn decode_noisy(text: &str, wordlist: &[String], model: NoiseModel) -> Result<Vec<String>> {
+    decode_noisy_with(text, wordlist, model, None)
+}
+
+/// [`decode_noisy`], discounting OCR carrier readings of tokens `dictionary`
+/// knows as words.
+pub fn decode_noisy_with(text: &str, wordlist: &[String], model: NoiseModel, dictionary: Option<&dyn Dictionary>) -> Result<Vec<String>> {
+    let report = decode_noisy_report(text, wordlist, model, dictionary)?;
+    Ok(report.words.into_iter().map(|word| word.word).collect())
+}
+
+/// [`decode_noisy_with`], reporting the token each carrier was read from and
+/// how: exactly, by prefix, by edit distance or by a confusion table.
+pub fn decode_noisy_report(text: &str, wordlist: &[String], model: NoiseModel, dictionary: Option<&dyn Dictionary>) -> Result<DecodeReport> {
+    let word_set: HashSet<String> = wordlist.iter().cloned().collect();
+    let index = WordIndex::shared(wordlist)?;
+    let candidates = Candidates::new(&word_set, &index, model, dictionary);
+
+    // Each hypothesis: carrier indices so far, log-probability, and for each
+    // carrier its token's position, how it was read and its log-probability
+    type Readings = Vec<(usize, MatchKind, f64)>;
+    let mut beam: Vec<(Vec<usize>, f64, Readings)> = vec![(Vec::new(), 0.0, Vec::new())];
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    for (position, token) in tokens.iter().enumerate() {
+        let options = candidates.for_token(token);
+        if options.is_empty() {
+            continue;
+        }
+
+        let mut next: HashMap<Vec<usize>, (f64, Readings)> = HashMap::new();
+        for (seq, score, readings) in &beam {
+            for (reading, logp) in &options {
+                let mut extended = seq.clone();
+                let mut extended_readings = readings.clone();
+                if let Some((word, kind)) = reading {
+                    extended.extend(index.get(word));
+                    extended_readings.push((position, *kind, *logp));
+                }
+                let s = score + logp;
+                let entry = next.entry(extended).or_insert((f64::NEG_INFINITY, Vec::new()));
+                if s > entry.0 {
+                    *entry = (s, extended_readings);
+                }
+            }
+        }
+
+        beam = next.into_iter().map(|(seq, (score, readings))| (seq, score, readings)).collect();
+        beam.sort_by(|a, b| b.1.total_cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        beam.truncate(BEAM_WIDTH);
+    }
+
+    for (seq, _, readings) in &beam {
+        if let Some((&check, body)) = seq.split_last() {
+            if codec::checksum_index(body, wordlist.len()) == check {
+                let mut words: Vec<DecodedWord> = seq
+                    .iter()
+                    .zip(readings)
+                    .map(|(&i, &(position, kind, logp))| {
+                        let token = tokens[position];
+                        let start = token.as_ptr() as usize - text.as_ptr() as usize;
+                        DecodedWord { word: wordlist[i].clone(), token: token.to_string(), span: start..start + token.len(), kind, probability: logp.exp() }
+                    })
+                    .collect();
+                let checksum = words.pop().expect("a checksum word");
+                return Ok(DecodeReport { words, checksum });
+            }
+        }
+    }
+    Err(GlossiaError::NoValidCandidate.into())
+}
+
+/// Per-token candidate generation for one noise model.
+struct Candidates<'a> {
+    wordlist: &'a HashSet<String>,
+    index: &'a WordIndex,
+    model: NoiseModel,
+    word_rules: HashMap<String, Vec<(String, f64)>>,
+    dictionary: Option<&'a dyn Dictionary>,
+}
+
+impl<'a> Candidates<'a> {
+    fn new(wordlist: &'a HashSet<String>, index: &'a WordIndex, model: NoiseModel, dictionary: Option<&'a dyn Dictionary>) -> Self {
+        let mut word_rules: HashMap<String, Vec<(String, f64)>> = HashMap::new();
+        if model == NoiseModel::Asr {
+            let table = HomophoneTable::parse(include_str!("../languages/english/homophones.txt"));
+            for (heard, carrier) in table.corrections(wordlist) {
+                word_rules.entry(heard).or_default().push((carrier, HOMOPHONE_PROBABILITY));
+            }
+            for (heard, intended, p) in ASR_CONFUSIONS {
+                word_rules
+                    .entry(heard.to_string())
+                    .or_default()
+                    .push((intended.to_string(), *p));
+            }
+        }
+
+        Self {
+            wordlist,
+            index,
+            model,
+            word_rules,
+            dictionary,
+        }
+    }
+
+    /// Candidate readings of a token: `Some(word, how)` for a carrier, `None`
+    /// for a cover word, each with a log-probability. Empty if the token can
+    /// only be cover.
+    fn for_token(&self, token: &str) -> Vec<(Option<(String, MatchKind)>, f64)> {
+        let clean = decode::normalize_token(token);
+        if self.wordlist.contains(&clean) {
+            return vec![
+                (Some((clean, MatchKind::Exact)), (1.0 - FALSE_CARRIER_PROBABILITY).ln()),
+                (None, FALSE_CARRIER_PROBABILITY.ln()),
+            ];
+        }
+
+        // The likeliest way to read each word
+        let mut found: HashMap<String, (f64, MatchKind)> = HashMap::new();
+        let mut add = |word: String, p: f64, kind: MatchKind| {
+            let entry = found.entry(word).or_insert((0.0, kind));
+            if p > entry.0 {
+                *entry = (p, kind);
+            }
+        };
+
+        match self.model {
+            NoiseModel::Asr => {
+                for (word, p) in self.word_rules.get(&clean).into_iter().flatten() {
+                    if self.wordlist.contains(word) {
+                        add(word.clone(), *p, MatchKind::Confusion);
+                    }
+                }
+            }
+            NoiseModel::Ocr => {
+                // OCR noise includes digits, so keep them when normalizing
+                let raw: String = decode::strip_markup(token)
+                    .trim_matches(|c: char| !c.is_ascii_alphanumeric())
+                    .to_lowercase();
+                let factor = match self.dictionary {
+                    Some(dictionary) if dictionary.is_word(&raw) => DICTIONARY_WORD_FACTOR,
+                    _ => 1.0,
+                };
+                for (variant, p) in ocr_variants(&raw) {
+                    if self.wordlist.contains(&variant) {
+                        add(variant, p * factor, MatchKind::Confusion);
+                    }
+                }
+                if let Some(i) = self.index.unique_prefix(&raw) {
+                    add(self.index.word(i).to_string(), PREFIX_PROBABILITY * factor, MatchKind::Prefix);
+                }
+                if raw.len() >= 4 {
+                    for i in self.index.within_one_edit(&raw) {
+                        add(self.index.word(i).to_string(), EDIT_PROBABILITY * factor, MatchKind::Edit);
+                    }
+                }
+            }
+        }
+
+        if found.is_empty() {
+            return Vec::new();
+        }
+        let carrier_mass: f64 = found.values().map(|(p, _)| p).sum::<f64>().min(0.95);
+        let mut options: Vec<(Option<(String, MatchKind)>, f64)> =
+            found.into_iter().map(|(w, (p, kind))| (Some((w, kind)), p.ln())).collect();
+        options.push((None, (1.0 - carrier_mass).ln()));
+        options
+    }
+}
+
+/// All strings reachable from `token` by up to `MAX_EDITS` OCR substitutions,
+/// with the product of the rule probabilities.
+fn ocr_variants(token: &str) -> Vec<(String, f64)> {
+    let mut seen: HashMap<String, f64> = HashMap::new();
+    let mut frontier = vec![(token.to_string(), 1.0)];
+    for _ in 0..MAX_EDITS {
+        let mut next = Vec::new();
+        for (s, p) in &frontier {
+            for (from, to, rule_p) in OCR_CONFUSIONS {
+                for (pos, _) in s.match_indices(from) {
+                    let variant = format!("{}{}{}", &s[..pos], to, &s[pos + from.len()..]);
+                    let vp = p * rule_p;
+                    let entry = seen.entry(variant.clone()).or_insert(0.0);
+                    if vp > *entry {
+                        *entry = vp;
+                        next.push((variant, vp));
+                    }
+                }
+            }
+        }
+        frontier = next;
+    }
+    seen.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wordlist() -> Vec<String> {
+        let mut words: Vec<String> = [
+            "abandon", "ability", "moral", "eight", "sea", "list", "wood", "dolphin", "bean",
+        ]
+        .iter()
+        .map(|w| w.to_string())
+        .collect();
+        // Pad to BIP39 size so the checksum is as selective as in real use
+        let letters: Vec<char> = ('a'..='z').collect();
+        for i in 0..(2048 - words.len()) {
+            words.push(format!("zz{}{}{}", letters[i / 676], letters[i / 26 % 26], letters[i % 26]));
+        }
+        words.sort();
+        words
+    }
+
+    fn frame(words: &[&str], list: &[String]) -> Vec<String> {
+        let words: Vec<String> = words.iter().map(|w| w.to_string()).collect();
+        codec::append_checksum(&words, list).unwrap()
+    }
+
+    #[test]
+    fn test_ocr_recovers_misread_carriers() {
+        let list = wordlist();
+        let framed = frame(&["moral", "dolphin", "list"], &list);
+        assert_eq!(framed.len(), 4);
+        let text = format!(
+            "The rnoral user saw a clolphin on the 1ist near the {}.",
+            framed[3]
+        );
+        let decoded = decode_noisy(&text, &list, NoiseModel::Ocr).unwrap();
+        assert_eq!(decoded, vec!["moral", "dolphin", "list"]);
+
+        let report = decode_noisy_report(&text, &list, NoiseModel::Ocr, None).unwrap();
+        let read: Vec<(&str, MatchKind)> = report.words.iter().map(|word| (word.token.as_str(), word.kind)).collect();
+        assert_eq!(read, [("rnoral", MatchKind::Confusion), ("clolphin", MatchKind::Confusion), ("1ist", MatchKind::Confusion)]);
+        assert_eq!(&text[report.words[1].span.clone()], "clolphin");
+        assert_eq!((report.checksum.kind, report.checksum.token.trim_end_matches('.')), (MatchKind::Exact, framed[3].as_str()));
+        assert!(report.checksum.probability > 0.9 && report.words[0].probability < 0.5);
+        assert_eq!(report.uncertain().count(), 3);
+    }
+
+    #[test]
+    fn test_asr_recovers_homophones() {
+        let list = wordlist();
+        let framed = frame(&["eight", "sea"], &list);
+        let text = format!("They ate by the see with {}.", framed[2]);
+        let decoded = decode_noisy(&text, &list, NoiseModel::Asr).unwrap();
+        assert_eq!(decoded, vec!["eight", "sea"]);
+    }
+
+    #[test]
+    fn test_ocr_recovers_single_letter_misreads() {
+        let list = wordlist();
+        let framed = frame(&["abandon", "bean"], &list);
+        let text = format!("We abxndon the bean near the {}.", framed[2]);
+        let report = decode_noisy_report(&text, &list, NoiseModel::Ocr, None).unwrap();
+        let read: Vec<(&str, &str, MatchKind)> = report.words.iter().map(|word| (word.word.as_str(), word.token.as_str(), word.kind)).collect();
+        assert_eq!(read, [("abandon", "abxndon", MatchKind::Edit), ("bean", "bean", MatchKind::Exact)]);
+    }
+
+    #[test]
+    fn test_dictionary_words_are_less_likely_carriers() {
+        let list = wordlist();
+        let word_set: HashSet<String> = list.iter().cloned().collect();
+        let index = WordIndex::shared(&list).unwrap();
+        let dictionary = crate::spelling::word_set("coral\nthe\n");
+        let moral = |dictionary: Option<&dyn Dictionary>| {
+            let options = Candidates::new(&word_set, &index, NoiseModel::Ocr, dictionary).for_token("coral");
+            options.into_iter().find(|(reading, _)| reading.as_ref().is_some_and(|(word, _)| word == "moral")).unwrap().1
+        };
+        assert!(moral(Some(&dictionary)) < moral(None));
+
+        let framed = frame(&["moral", "list"], &list);
+        let text = format!("The rnoral coral on the 1ist near the {}.", framed[2]);
+        let decoded = decode_noisy_with(&text, &list, NoiseModel::Ocr, Some(&dictionary)).unwrap();
+        assert_eq!(decoded, vec!["moral", "list"]);
+    }
+
+    #[test]
+    fn test_fails_closed_without_valid_checksum() {
+        let list = wordlist();
+        assert!(decode_noisy("The moral of the story.", &list, NoiseModel::Ocr).is_err());
+    }
+}
@@ -0,0 +1,82 @@
+//! Cleaning up text before it is decoded.
+//!
+//! Cover text rarely comes back as it was printed: terminals keep the
+//! highlighting, word processors curl the quotes, PDFs hard-wrap lines and
+//! turn `fi` into a ligature, phones capitalize. [`clean`] undoes all of that
+//! in one pass, the same way for every front end, so a text that decodes in
+//! the CLI also decodes in the library and in the browser. Each step can be
+//! turned off in [`NormalizeOptions`]; [`decode`](crate::decode) has the
+//! steps on their own.
+
+use crate::decode;
+use unicode_normalization::UnicodeNormalization;
+
+/// Which cleanup steps [`clean`] runs; all of them by default
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NormalizeOptions {
+    /// Remove ANSI escape codes and highlighting bars
+    pub strip_tags: bool,
+    /// Apply Unicode NFKC: ligatures, full-width and compatibility forms
+    /// become plain letters (`ﬁ` to `fi`, `ｗｏｒｄ` to `word`)
+    pub nfkc: bool,
+    /// Fold curly quotes to `'` and `"`, and dashes to `-`
+    pub fold_punctuation: bool,
+    /// Undo hard wrapping ([`decode::unwrap_lines`])
+    pub unwrap: bool,
+    /// Lowercase everything
+    pub fold_case: bool,
+}
+
+impl Default for NormalizeOptions {
+    fn default() -> Self {
+        Self { strip_tags: true, nfkc: true, fold_punctuation: true, unwrap: true, fold_case: true }
+    }
+}
+
+/// `text` with the steps of `options` applied, in the order the fields list
+/// them.
+pub fn clean(text: &str, options: NormalizeOptions) -> String {
+    let mut text = if options.strip_tags { decode::strip_markup(text) } else { text.to_string() };
+    if options.nfkc {
+        text = text.nfkc().collect();
+    }
+    if options.fold_punctuation {
+        text = text.chars().map(fold_punctuation).collect();
+    }
+    if options.unwrap {
+        text = decode::unwrap_lines(&text);
+    }
+    if options.fold_case {
+        text = text.to_lowercase();
+    }
+    text
+}
+
+fn fold_punctuation(c: char) -> char {
+    match c {
+        '\u{2018}' | '\u{2019}' | '\u{201A}' | '\u{201B}' => '\'',
+        '\u{201C}' | '\u{201D}' | '\u{201E}' | '\u{201F}' | '\u{00AB}' | '\u{00BB}' => '"',
+        '\u{2010}'..='\u{2015}' | '\u{2212}' => '-',
+        c => c,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clean_runs_every_step() {
+        let text = "\x1b[1m|Ａbandon|\x1b[0m the “oﬃce” —\nthe aban-\ndon’s";
+        assert_eq!(clean(text, NormalizeOptions::default()), "abandon the \"office\" - the abandon's");
+    }
+
+    #[test]
+    fn test_steps_can_be_turned_off() {
+        let text = "|Oﬃce| “A”\nB";
+        let none = NormalizeOptions { strip_tags: false, nfkc: false, fold_punctuation: false, unwrap: false, fold_case: false };
+        assert_eq!(clean(text, none), text);
+        assert_eq!(clean(text, NormalizeOptions { nfkc: true, ..none }), "|Office| “A”\nB");
+        assert_eq!(clean(text, NormalizeOptions { fold_case: false, ..Default::default() }), "Office \"A\" B");
+    }
+}
@@ -0,0 +1,328 @@
+//! Nostr event JSON packed compactly, and written back byte for byte.
+//!
+//! An event spells its 32-byte id and pubkey and 64-byte signature in hex, and
+//! a NIP-59 gift wrap carries its NIP-44 ciphertext in base64, so the JSON is
+//! far larger than what it holds. [`compact_event`] stores the fields in a CBOR
+//! map keyed by their index in [`FIELDS`], in the order the JSON has them: hex
+//! as raw bytes, base64 content as the bytes it encodes, and content that is
+//! itself an event's JSON (an unwrapped seal or rumor) as a nested map.
+//! [`expand_event`] writes the JSON back.
+//!
+//! Only JSON that comes back exactly is packed: compact, as clients serialize
+//! events, with `serde_json`'s escapes. Anything else is an error rather than
+//! an event that decodes to different bytes (and a different id). A signed
+//! event can instead be packed by [`compact_signed_event`], which checks its
+//! id and signature and expands to the event's canonical JSON however it was
+//! formatted: different bytes, but the same event, still verifying.
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use ciborium::Value;
+use indexmap::IndexMap;
+use k256::schnorr::{Signature, SigningKey, VerifyingKey};
+use rand::{CryptoRng, Rng};
+use sha2::{Digest, Sha256};
+
+/// Event fields in NIP-01 order; a field's index is its CBOR key
+pub const FIELDS: [&str; 7] = ["id", "pubkey", "created_at", "kind", "tags", "content", "sig"];
+
+/// Kind of a NIP-59 gift wrap
+pub const GIFT_WRAP_KIND: u64 = 1059;
+
+/// The `kind` of an event's JSON, if it has one.
+pub fn event_kind(json: &str) -> Option<u64> {
+    serde_json::from_str::<serde_json::Value>(json).ok()?.get("kind")?.as_u64()
+}
+
+/// Pack the JSON of a Nostr event (signed or not) as CBOR.
+pub fn compact_event(json: &str) -> Result<Vec<u8>> {
+    let value = event_value(json)?;
+    let mut out = Vec::new();
+    ciborium::into_writer(&value, &mut out).map_err(|e| anyhow!("{}", e))?;
+    Ok(out)
+}
+
+/// Pack a signed event as CBOR with its fields in NIP-01 order, once its id
+/// and signature check out ([`verify_event`]). It expands to the canonical
+/// JSON of the event, which has the same id and signature.
+pub fn compact_signed_event(json: &str) -> Result<Vec<u8>> {
+    verify_event(json)?;
+    let mut out = Vec::new();
+    ciborium::into_writer(&canonical_value(json)?, &mut out).map_err(|e| anyhow!("{}", e))?;
+    Ok(out)
+}
+
+/// Check that an event's id is the SHA-256 of its NIP-01 serialization and
+/// that `sig` is the pubkey's BIP340 signature of the id. Returns the id.
+pub fn verify_event(json: &str) -> Result<[u8; 32]> {
+    let event: serde_json::Value = serde_json::from_str(json).context("Not a JSON object")?;
+    let field = |name: &str| event.get(name).ok_or_else(|| anyhow!("Event has no {}", name));
+    let hex_field = |name: &str, len: usize| {
+        let s = field(name)?.as_str().ok_or_else(|| anyhow!("Event {} is not a string", name))?;
+        hex_bytes(s, len).with_context(|| format!("Invalid {}", name))
+    };
+    let id = event_id(field("pubkey")?, field("created_at")?, field("kind")?, field("tags")?, field("content")?)?;
+    if hex_field("id", 32)? != id {
+        bail!("Event id does not match its contents");
+    }
+    let pubkey = VerifyingKey::from_bytes(&hex_field("pubkey", 32)?).map_err(|_| anyhow!("Invalid pubkey"))?;
+    let sig = Signature::try_from(hex_field("sig", 64)?.as_slice()).map_err(|_| anyhow!("Invalid sig"))?;
+    pubkey.verify_raw(&id, &sig).map_err(|_| anyhow!("Event signature does not verify"))?;
+    Ok(id)
+}
+
+/// The canonical JSON of an event signed with `key`.
+pub fn sign_event<R: Rng + CryptoRng>(
+    rng: &mut R,
+    key: &SigningKey,
+    created_at: u64,
+    kind: u64,
+    tags: &[Vec<String>],
+    content: &str,
+) -> Result<String> {
+    let pubkey = hex(&key.verifying_key().to_bytes());
+    let tags = serde_json::to_value(tags)?;
+    let id = event_id(&pubkey.clone().into(), &created_at.into(), &kind.into(), &tags, &content.into())?;
+    let aux: [u8; 32] = rng.gen();
+    let sig = key.sign_prehash_with_aux_rand(&id, &aux).map_err(|_| anyhow!("Signing failed"))?;
+    let event = serde_json::json!({
+        "id": hex(&id),
+        "pubkey": pubkey,
+        "created_at": created_at,
+        "kind": kind,
+        "tags": tags,
+        "content": content,
+        "sig": hex(&sig.to_bytes()),
+    });
+    let mut out = String::new();
+    write_event(&canonical_value(&event.to_string())?, &mut out)?;
+    Ok(out)
+}
+
+/// SHA-256 of `[0, pubkey, created_at, kind, tags, content]`, the NIP-01 id.
+fn event_id(
+    pubkey: &serde_json::Value,
+    created_at: &serde_json::Value,
+    kind: &serde_json::Value,
+    tags: &serde_json::Value,
+    content: &serde_json::Value,
+) -> Result<[u8; 32]> {
+    let serialized = serde_json::to_string(&serde_json::json!([0, pubkey, created_at, kind, tags, content]))?;
+    Ok(Sha256::digest(serialized).into())
+}
+
+/// The event JSON [`compact_event`] or [`compact_signed_event`] packed.
+pub fn expand_event(packed: &[u8]) -> Result<String> {
+    let value: Value = ciborium::from_reader(packed).map_err(|e| anyhow!("Invalid packed event: {}", e))?;
+    let mut out = String::new();
+    write_event(&value, &mut out)?;
+    Ok(out)
+}
+
+/// The CBOR form of an event's JSON, checked to write back exactly.
+fn event_value(json: &str) -> Result<Value> {
+    let value = field_map(event_fields(json)?);
+    let mut written = String::new();
+    write_event(&value, &mut written)?;
+    if written != json {
+        bail!("Event JSON is not in compact form (whitespace, escapes or repeated fields would not survive)");
+    }
+    Ok(value)
+}
+
+/// The CBOR form of an event's JSON with its fields in NIP-01 order.
+fn canonical_value(json: &str) -> Result<Value> {
+    let mut fields = event_fields(json)?;
+    fields.sort_by_key(|(index, _)| *index);
+    Ok(field_map(fields))
+}
+
+/// An event's fields packed for CBOR, keyed by their index in [`FIELDS`], in
+/// the order the JSON has them.
+fn event_fields(json: &str) -> Result<Vec<(usize, Value)>> {
+    let fields: IndexMap<String, serde_json::Value> = serde_json::from_str(json).context("Not a JSON object")?;
+    if !fields.contains_key("pubkey") || !fields.contains_key("kind") {
+        bail!("Not a Nostr event (no pubkey or kind)");
+    }
+    let mut packed_fields = Vec::with_capacity(fields.len());
+    for (name, value) in &fields {
+        let index = FIELDS.iter().position(|field| field == name).ok_or_else(|| anyhow!("Unexpected event field '{}'", name))?;
+        let packed = match (name.as_str(), value) {
+            ("id" | "pubkey", serde_json::Value::String(s)) => Value::Bytes(hex_bytes(s, 32).with_context(|| format!("Invalid {}", name))?),
+            ("sig", serde_json::Value::String(s)) => Value::Bytes(hex_bytes(s, 64).context("Invalid sig")?),
+            ("created_at" | "kind", serde_json::Value::Number(n)) => {
+                Value::Integer(n.as_u64().ok_or_else(|| anyhow!("Event {} is not a whole number", name))?.into())
+            }
+            ("tags", serde_json::Value::Array(tags)) => Value::Array(tags.iter().map(pack_tag).collect::<Result<_>>()?),
+            ("content", serde_json::Value::String(s)) => pack_content(s),
+            _ => bail!("Event field '{}' has the wrong type", name),
+        };
+        packed_fields.push((index, packed));
+    }
+    Ok(packed_fields)
+}
+
+fn field_map(fields: Vec<(usize, Value)>) -> Value {
+    Value::Map(fields.into_iter().map(|(index, value)| (Value::Integer((index as u64).into()), value)).collect())
+}
+
+/// A tag, with 64-digit hex elements (event ids, pubkeys) as bytes.
+fn pack_tag(tag: &serde_json::Value) -> Result<Value> {
+    let elements = tag.as_array().ok_or_else(|| anyhow!("Event tag is not an array"))?;
+    elements
+        .iter()
+        .map(|element| {
+            let s = element.as_str().ok_or_else(|| anyhow!("Event tag element is not a string"))?;
+            Ok(hex_bytes(s, 32).map_or_else(|_| Value::Text(s.to_string()), Value::Bytes))
+        })
+        .collect::<Result<_>>()
+        .map(Value::Array)
+}
+
+/// Content as a nested event, as the bytes it encodes in base64, or as text,
+/// whichever writes back exactly.
+fn pack_content(content: &str) -> Value {
+    if content.starts_with('{') {
+        if let Ok(event) = event_value(content) {
+            return event;
+        }
+    }
+    match BASE64.decode(content) {
+        Ok(bytes) if !bytes.is_empty() && BASE64.encode(&bytes) == content => Value::Bytes(bytes),
+        _ => Value::Text(content.to_string()),
+    }
+}
+
+fn write_event(value: &Value, out: &mut String) -> Result<()> {
+    let map = value.as_map().ok_or_else(|| anyhow!("Packed event is not a map"))?;
+    out.push('{');
+    for (i, (key, value)) in map.iter().enumerate() {
+        let index = key.as_integer().and_then(|n| usize::try_from(n).ok());
+        let name = index.and_then(|index| FIELDS.get(index)).ok_or_else(|| anyhow!("Packed event has an unknown field"))?;
+        if i > 0 {
+            out.push(',');
+        }
+        out.push_str(&json_string(name));
+        out.push(':');
+        match (*name, value) {
+            ("created_at" | "kind", Value::Integer(n)) => {
+                out.push_str(&u64::try_from(*n).map_err(|_| anyhow!("Packed event {} is out of range", name))?.to_string())
+            }
+            ("tags", Value::Array(tags)) => {
+                let tags = tags
+                    .iter()
+                    .map(|tag| {
+                        let elements = tag.as_array().ok_or_else(|| anyhow!("Packed event tag is not an array"))?;
+                        elements.iter().map(text_or_hex).collect::<Result<Vec<String>>>()
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                out.push_str(&serde_json::to_string(&tags)?);
+            }
+            ("content", Value::Map(_)) => {
+                let mut inner = String::new();
+                write_event(value, &mut inner)?;
+                out.push_str(&json_string(&inner));
+            }
+            ("content", Value::Bytes(bytes)) => out.push_str(&json_string(&BASE64.encode(bytes))),
+            (_, value) => out.push_str(&json_string(&text_or_hex(value)?)),
+        }
+    }
+    out.push('}');
+    Ok(())
+}
+
+/// A text value, or a bytes value as lowercase hex.
+fn text_or_hex(value: &Value) -> Result<String> {
+    match value {
+        Value::Text(s) => Ok(s.clone()),
+        Value::Bytes(bytes) => Ok(hex(bytes)),
+        _ => bail!("Packed event has a value of the wrong type"),
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn json_string(s: &str) -> String {
+    serde_json::to_string(s).expect("strings serialize")
+}
+
+/// `len` bytes from lowercase hex (the form hex round-trips in).
+fn hex_bytes(s: &str, len: usize) -> Result<Vec<u8>> {
+    if s.len() != 2 * len || !s.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+        bail!("expected {} lowercase hex digits", 2 * len);
+    }
+    Ok((0..len).map(|i| u8::from_str_radix(&s[2 * i..2 * i + 2], 16).expect("checked hex")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hex(seed: u8, len: usize) -> String {
+        (0..len).map(|i| format!("{:02x}", (i as u8).wrapping_mul(31).wrapping_add(seed))).collect()
+    }
+
+    /// A gift wrap as a relay would serve it: NIP-44 ciphertext in base64
+    fn gift_wrap() -> String {
+        let ciphertext: Vec<u8> = (0..400u32).map(|i| (i * 7 % 251) as u8).collect();
+        format!(
+            r#"{{"id":"{}","pubkey":"{}","created_at":1703021488,"kind":1059,"tags":[["p","{}"]],"content":"{}","sig":"{}"}}"#,
+            hex(1, 32),
+            hex(2, 32),
+            hex(3, 32),
+            BASE64.encode(ciphertext),
+            hex(4, 64)
+        )
+    }
+
+    #[test]
+    fn test_gift_wrap_round_trip() {
+        let json = gift_wrap();
+        let packed = compact_event(&json).unwrap();
+        assert_eq!(expand_event(&packed).unwrap(), json);
+        assert!(packed.len() * 10 < json.len() * 7, "{} of {} bytes", packed.len(), json.len());
+        assert_eq!(event_kind(&json), Some(GIFT_WRAP_KIND));
+
+        // An unwrapped seal in the content, holding a rumor (no id or sig), packs as nested events
+        let rumor = format!(r#"{{"pubkey":"{}","created_at":1,"kind":14,"tags":[],"content":"Meet at noon \"sharp\"\n"}}"#, hex(5, 32));
+        let seal = format!(r#"{{"kind":13,"pubkey":"{}","content":{},"tags":[],"created_at":2,"sig":"{}"}}"#, hex(6, 32), json_string(&rumor), hex(7, 64));
+        let wrap = json.replacen(&json[json.find("\"content\"").unwrap()..json.find(",\"sig\"").unwrap()], &format!("\"content\":{}", json_string(&seal)), 1);
+        let packed = compact_event(&wrap).unwrap();
+        assert_eq!(expand_event(&packed).unwrap(), wrap);
+        assert!(packed.len() < wrap.len() / 2);
+    }
+
+    #[test]
+    fn test_signed_event_expands_canonical() {
+        let key = SigningKey::from_bytes(&[7; 32]).unwrap();
+        let tags = vec![vec!["e".to_string(), hex(8, 32)], vec!["t".to_string(), "nostr".to_string()]];
+        let json = sign_event(&mut rand::thread_rng(), &key, 1703021488, 1, &tags, "Meet at noon").unwrap();
+        assert!(json.starts_with("{\"id\":") && json.ends_with("\"}"));
+        verify_event(&json).unwrap();
+
+        // Pretty-printed, it only packs as a signed event, and comes back canonical
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let pretty = serde_json::to_string_pretty(&value).unwrap();
+        assert!(compact_event(&pretty).is_err());
+        let packed = compact_signed_event(&pretty).unwrap();
+        assert_eq!(expand_event(&packed).unwrap(), json);
+        assert!(packed.len() * 2 < json.len(), "{} of {} bytes", packed.len(), json.len());
+
+        // Edited content no longer matches the id; a forged sig doesn't verify
+        assert!(compact_signed_event(&json.replace("noon", "nine")).is_err());
+        assert!(compact_signed_event(&json.replace(value["sig"].as_str().unwrap(), &hex(9, 64))).is_err());
+    }
+
+    #[test]
+    fn test_rejects_what_would_not_round_trip() {
+        let json = gift_wrap();
+        assert!(compact_event(&json.replace(",\"kind\"", ", \"kind\"")).is_err());
+        assert!(compact_event(&json.replace(&hex(2, 32), &hex(2, 32).to_uppercase())).is_err());
+        assert!(compact_event(&json.replace("\"sig\"", "\"extra\"")).is_err());
+        assert!(compact_event(r#"{"content":"hi"}"#).is_err());
+        assert!(compact_event("[1, 2]").is_err());
+    }
+}
@@ -0,0 +1,256 @@
+//! Numbers as a side channel.
+//!
+//! Quantities, clock times and years turn up in ordinary writing ("three
+//! eggs", "at 7 pm", "in 1994"), and their exact values are arbitrary.
+//! Each number form below ranges over `2^k` plausible values and carries `k`
+//! bits of a side payload, framed as in [`synonyms`](crate::synonyms).
+//!
+//! | Form | Values | Bits | Read as |
+//! |---|---|---|---|
+//! | count | 2 to 9 | 3 | `2`..`9` or `two`..`nine`, not after `Step` |
+//! | clock | 1 to 8 | 3 | an hour before `pm` or `o'clock` |
+//! | year | 1980 to 2011 | 5 | a four-digit number |
+//!
+//! [`NumberChannel::embed`] rewrites the numbers already in the text (such
+//! as list quantities) and can add clock or year phrases to sentences for
+//! more room. Every number of these forms is read back as bits, so spelled
+//! numbers that are wordlist words (and so carry payload) are left out.
+
+use crate::chaff::split_sentences;
+use crate::synonyms::{frame_bits, unframe_bits};
+use anyhow::{bail, Result};
+use rand::Rng;
+use std::collections::HashSet;
+
+/// Spelled numbers, indexed by value
+const SPELLED: [&str; 13] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten", "eleven", "twelve",
+];
+
+/// A kind of number the channel reads.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NumberForm {
+    Count,
+    Clock,
+    Year,
+}
+
+impl NumberForm {
+    /// Bits one number of this form carries
+    pub fn bits(&self) -> usize {
+        match self {
+            NumberForm::Count | NumberForm::Clock => 3,
+            NumberForm::Year => 5,
+        }
+    }
+
+    /// Smallest value; values run up to `min + 2^bits - 1`
+    fn min(&self) -> u32 {
+        match self {
+            NumberForm::Count => 2,
+            NumberForm::Clock => 1,
+            NumberForm::Year => 1980,
+        }
+    }
+
+    fn holds(&self, value: u32) -> bool {
+        value >= self.min() && value - self.min() < 1 << self.bits()
+    }
+}
+
+/// The value of a number word (`7` or `seven`), ignoring case
+pub fn number_value(word: &str) -> Option<u32> {
+    if !word.is_empty() && word.bytes().all(|b| b.is_ascii_digit()) {
+        return word.parse().ok();
+    }
+    SPELLED.iter().position(|s| s.eq_ignore_ascii_case(word)).map(|n| n as u32)
+}
+
+/// One carrier number: its token's position in the text, form and value
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Carrier {
+    token: usize,
+    form: NumberForm,
+    value: u32,
+}
+
+/// Reads and writes a side payload in the numbers of a text.
+#[derive(Clone, Debug)]
+pub struct NumberChannel {
+    /// Spelled numbers that are wordlist words, and so not carriers
+    reserved: HashSet<String>,
+}
+
+impl NumberChannel {
+    pub fn new(wordlist: &HashSet<String>) -> Self {
+        let reserved = SPELLED.iter().filter(|s| wordlist.contains(**s)).map(|s| s.to_string()).collect();
+        Self { reserved }
+    }
+
+    fn value_of(&self, core: &str) -> Option<u32> {
+        if self.reserved.contains(&core.to_lowercase()) {
+            return None;
+        }
+        number_value(core)
+    }
+
+    /// The carrier numbers in `text`, in order
+    fn scan(&self, text: &str) -> Vec<Carrier> {
+        let cores: Vec<String> = text.split_whitespace().map(|token| core(token).to_lowercase()).collect();
+        let mut carriers = Vec::new();
+        for (i, word) in cores.iter().enumerate() {
+            let Some(value) = self.value_of(word) else {
+                continue;
+            };
+            let next = cores.get(i + 1).map(String::as_str);
+            let form = if word.len() == 4 && word.bytes().all(|b| b.is_ascii_digit()) {
+                NumberForm::Year
+            } else if matches!(next, Some("pm" | "o'clock")) {
+                NumberForm::Clock
+            } else if i > 0 && cores[i - 1] == "step" {
+                continue;
+            } else {
+                NumberForm::Count
+            };
+            if form.holds(value) {
+                carriers.push(Carrier { token: i, form, value });
+            }
+        }
+        carriers
+    }
+
+    /// Side-channel bits the numbers in `text` carry
+    pub fn capacity(&self, text: &str) -> usize {
+        self.scan(text).iter().map(|carrier| carrier.form.bits()).sum()
+    }
+
+    /// Rewrite the numbers in `text` so their values spell out `payload`.
+    /// With `insert`, sentences that lack room get a clock time or year
+    /// before their end mark ("... at 7 pm."), one phrase per sentence.
+    pub fn embed<R: Rng + ?Sized>(&self, rng: &mut R, text: &str, payload: &[u8], insert: bool) -> Result<String> {
+        let bits = frame_bits(payload)?;
+        let mut text = text.to_string();
+        let mut capacity = self.capacity(&text);
+        if insert && capacity < bits.len() {
+            let mut sentences = split_sentences(&text);
+            for sentence in sentences.iter_mut() {
+                if capacity >= bits.len() {
+                    break;
+                }
+                let form = if rng.gen_bool(0.5) { NumberForm::Clock } else { NumberForm::Year };
+                let value = form.min() + rng.gen_range(0..1u32 << form.bits());
+                let phrase = match form {
+                    NumberForm::Clock => format!("at {} pm", value),
+                    _ => format!("in {}", value),
+                };
+                let body = sentence.trim_end_matches(['.', '!', '?']);
+                *sentence = format!("{} {}{}", body, phrase, &sentence[body.len()..]);
+                capacity += form.bits();
+            }
+            text = sentences.join(" ");
+        }
+        if capacity < bits.len() {
+            bail!("The side payload needs {} bits but the text's numbers carry only {}", bits.len(), capacity);
+        }
+
+        let carriers = self.scan(&text);
+        let mut next = bits.into_iter();
+        let mut tokens: Vec<String> = text.split_whitespace().map(str::to_string).collect();
+        for carrier in carriers {
+            let offset = (0..carrier.form.bits()).fold(0, |acc, _| {
+                let bit = next.next().unwrap_or_else(|| rng.gen());
+                (acc << 1) | bit as u32
+            });
+            let value = carrier.form.min() + offset;
+            let token = &tokens[carrier.token];
+            let word = core(token);
+            let spelled = !word.bytes().all(|b| b.is_ascii_digit());
+            let mut replacement = match SPELLED.get(value as usize) {
+                Some(s) if spelled && !self.reserved.contains(*s) => s.to_string(),
+                _ => value.to_string(),
+            };
+            if word.starts_with(|c: char| c.is_ascii_uppercase()) {
+                replacement[..1].make_ascii_uppercase();
+            }
+            tokens[carrier.token] = token.replacen(word, &replacement, 1);
+        }
+        Ok(rejoin(&text, &tokens))
+    }
+
+    /// Read back a payload written by [`embed`](Self::embed).
+    pub fn extract(&self, text: &str) -> Result<Vec<u8>> {
+        let mut bits = Vec::new();
+        for carrier in self.scan(text) {
+            let offset = carrier.value - carrier.form.min();
+            bits.extend((0..carrier.form.bits()).rev().map(|i| (offset >> i) & 1 == 1));
+        }
+        unframe_bits(&bits)
+    }
+}
+
+/// A token without its surrounding punctuation (`o'clock` keeps its apostrophe)
+fn core(token: &str) -> &str {
+    token.trim_matches(|c: char| !c.is_ascii_alphanumeric())
+}
+
+/// `tokens` (the whitespace-separated tokens of `text`, rewritten) with the
+/// whitespace of `text` between them
+fn rejoin(text: &str, tokens: &[String]) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut tokens = tokens.iter();
+    let mut in_token = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            out.push(c);
+            in_token = false;
+        } else if !in_token {
+            out.push_str(tokens.next().map(String::as_str).unwrap_or_default());
+            in_token = true;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_scan_reads_forms_in_order() {
+        let channel = NumberChannel::new(&HashSet::new());
+        let text = "Step 3: Add Three cups, then 10 more.\nMeet at 7 pm in 1994; eight o'clock is late.";
+        let forms: Vec<(NumberForm, u32)> = channel.scan(text).iter().map(|c| (c.form, c.value)).collect();
+        assert_eq!(
+            forms,
+            vec![(NumberForm::Count, 3), (NumberForm::Clock, 7), (NumberForm::Year, 1994), (NumberForm::Clock, 8)]
+        );
+        assert_eq!(channel.capacity(text), 14);
+
+        // A spelled number in the wordlist carries payload, not side bits
+        let wordlist: HashSet<String> = ["three".to_string()].into_iter().collect();
+        assert_eq!(NumberChannel::new(&wordlist).capacity(text), 11);
+    }
+
+    #[test]
+    fn test_embed_round_trips() {
+        let channel = NumberChannel::new(&HashSet::new());
+        let mut rng = StdRng::seed_from_u64(9);
+
+        let list = "Shopping list:\n- 2 cups of rice\n- Four eggs\n- 3 tins of beans\n- 5 apples\n- 6 pears\n- 2 jars of jam";
+        let out = channel.embed(&mut rng, list, b"k", false).unwrap();
+        assert_eq!(channel.extract(&out).unwrap(), b"k".to_vec());
+        assert_eq!(out.lines().count(), list.lines().count());
+        assert!(out.lines().nth(2).unwrap().starts_with("- ") && !out.contains("- 4 eggs"), "{}", out);
+        assert!(channel.embed(&mut rng, list, b"too long", false).is_err());
+
+        // Ten sentences add at least 30 bits, enough for "hi" (24)
+        let prose = "The cat sleeps. A dog barks! Who is there? We wait. It rains. \
+                     They sing. The sun sets. A bell rings. She reads. He leaves.";
+        let out = channel.embed(&mut rng, prose, b"hi", true).unwrap();
+        assert_eq!(channel.extract(&out).unwrap(), b"hi".to_vec());
+        assert_eq!(split_sentences(&out).len(), 10);
+        assert!(channel.extract(prose).is_err());
+    }
+}
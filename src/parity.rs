@@ -0,0 +1,177 @@
+//! Per-sentence integrity markers.
+//!
+//! A whole-message checksum says that something broke, not where. With
+//! markers, every sentence that carries payload words ends in one more
+//! carrier, a noun in a short tail phrase ("... by the apple."), whose
+//! wordlist index holds [`PARITY_BITS`] bits of a CRC over the sentence's
+//! other carriers. The decoder checks each sentence, drops the markers and
+//! names the sentences that fail ([`GlossiaError::ParityMismatch`]), so only
+//! those need to be sent again.
+
+use crate::codec::crc32;
+use crate::decode::{normalize_token, strip_markup};
+use crate::error::GlossiaError;
+use crate::synonyms::map_tokens;
+use anyhow::{bail, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::HashMap;
+
+/// Check bits per sentence
+pub const PARITY_BITS: usize = 4;
+
+/// Phrases that introduce the marker noun; none of their words is a wordlist word
+const TAILS: &[&str] = &["by the", "with the", "for the", "beside the", "past the", "at the", "from the", "on the"];
+
+/// The check value of a sentence's carrier indices: the low bits of their
+/// CRC-32, over the same little-endian u16s as the checksum word
+pub fn sentence_parity(indices: &[usize]) -> usize {
+    let bytes: Vec<u8> = indices.iter().flat_map(|&i| (i as u16).to_le_bytes()).collect();
+    crc32(&bytes) as usize & ((1 << PARITY_BITS) - 1)
+}
+
+/// Looks tokens up as the decoder does: `corrections` first, then the wordlist.
+struct Carriers<'a> {
+    positions: HashMap<&'a str, usize>,
+    corrections: &'a HashMap<String, String>,
+}
+
+impl<'a> Carriers<'a> {
+    fn new(wordlist: &'a [String], corrections: &'a HashMap<String, String>) -> Self {
+        let positions = wordlist.iter().enumerate().map(|(i, w)| (w.as_str(), i)).collect();
+        Self { positions, corrections }
+    }
+
+    fn index(&self, token: &str) -> Option<usize> {
+        let word = normalize_token(token);
+        let word = self.corrections.get(&word).unwrap_or(&word);
+        self.positions.get(word.as_str()).copied()
+    }
+}
+
+fn ends_sentence(token: &str) -> bool {
+    strip_markup(token).ends_with(['.', '!', '?'])
+}
+
+/// End every sentence that has carriers with a marker phrase. `markers` are
+/// the wordlist nouns the marker may be; each check value needs at least
+/// one. `corrections` must be the ones the decoder will use.
+pub fn mark<R: Rng + ?Sized>(
+    rng: &mut R,
+    text: &str,
+    wordlist: &[String],
+    markers: &[String],
+    corrections: &HashMap<String, String>,
+) -> Result<String> {
+    let carriers = Carriers::new(wordlist, corrections);
+    let mut by_value: Vec<Vec<&str>> = vec![Vec::new(); 1 << PARITY_BITS];
+    for marker in markers {
+        if let Some(&index) = carriers.positions.get(marker.as_str()) {
+            by_value[index & ((1 << PARITY_BITS) - 1)].push(marker);
+        }
+    }
+    if let Some(value) = by_value.iter().position(Vec::is_empty) {
+        bail!("No marker noun has check value {}", value);
+    }
+
+    // "by" reads as "buy" with homophone corrections
+    let tails: Vec<&str> = TAILS.iter().copied().filter(|t| t.split_whitespace().all(|w| carriers.index(w).is_none())).collect();
+    let phrase = |rng: &mut R, indices: &[usize]| {
+        let tail = tails.choose(rng).unwrap();
+        let marker = by_value[sentence_parity(indices)].choose(rng).unwrap();
+        format!("{} {}", tail, marker)
+    };
+    let mut sentence: Vec<usize> = Vec::new();
+    let mut marked = map_tokens(text, |token| {
+        sentence.extend(carriers.index(token));
+        if !ends_sentence(token) {
+            return None;
+        }
+        let indices = std::mem::take(&mut sentence);
+        if indices.is_empty() {
+            return None;
+        }
+        // The phrase goes after any highlighting, before the end mark and closing quotes
+        let body = token.trim_end_matches(['.', '!', '?', '"', '\'', ')']);
+        Some(format!("{} {}{}", body, phrase(rng, &indices), &token[body.len()..]))
+    });
+    if !sentence.is_empty() {
+        marked = format!("{} {}", marked.trim_end(), phrase(rng, &sentence));
+    }
+    Ok(marked)
+}
+
+/// The carrier words of a [`mark`]ed text without the markers, after
+/// checking every sentence's marker against the carriers before it.
+pub fn check(text: &str, wordlist: &[String], corrections: &HashMap<String, String>) -> Result<Vec<String>> {
+    let carriers = Carriers::new(wordlist, corrections);
+    let mut words = Vec::new();
+    let mut failed = Vec::new();
+    let mut sentence: Vec<usize> = Vec::new();
+    let mut number = 0;
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    for (i, token) in tokens.iter().enumerate() {
+        sentence.extend(carriers.index(token));
+        if !ends_sentence(token) && i + 1 < tokens.len() {
+            continue;
+        }
+        number += 1;
+        if let Some((marker, indices)) = sentence.split_last() {
+            if sentence_parity(indices) != marker & ((1 << PARITY_BITS) - 1) {
+                failed.push(number);
+            }
+            words.extend(indices.iter().map(|&i| wordlist[i].clone()));
+        }
+        sentence.clear();
+    }
+    if !failed.is_empty() {
+        return Err(GlossiaError::ParityMismatch { sentences: failed }.into());
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    fn wordlist() -> Vec<String> {
+        // 64 marker nouns "zaa" to "zhh", then the carriers
+        let mut words: Vec<String> = (0..64u8).map(|i| format!("z{}{}", (b'a' + i / 8) as char, (b'a' + i % 8) as char)).collect();
+        words.extend(["ability", "cat", "dog", "run"].iter().map(|w| w.to_string()));
+        words
+    }
+
+    #[test]
+    fn test_mark_and_check() {
+        let wordlist = wordlist();
+        let markers: Vec<String> = wordlist[..64].to_vec();
+        let text = "The |cat| will run home.\n\nNo carriers here! A dog and a cat? Ability";
+        let mut rng = StdRng::seed_from_u64(8);
+        let marked = mark(&mut rng, text, &wordlist, &markers, &HashMap::new()).unwrap();
+        assert!(marked.contains(".\n\nNo carriers here! A dog") && !marked.contains("here by"), "{}", marked);
+        let first = marked.split(".\n\n").next().unwrap();
+        assert!(first.starts_with("The |cat| will run home ") && first.rsplit(' ').next().unwrap().starts_with('z'), "{}", marked);
+        assert_eq!(marked.split_whitespace().filter(|t| t.starts_with('z')).count(), 3, "{}", marked);
+        assert_eq!(check(&marked, &wordlist, &HashMap::new()).unwrap(), vec!["cat", "run", "dog", "cat", "ability"]);
+
+        // Swapping a carrier in the third sentence fails only that sentence
+        let corrupted = marked.replacen("A dog", "A cat", 1);
+        let err = check(&corrupted, &wordlist, &HashMap::new()).unwrap_err();
+        assert_eq!(err.downcast_ref::<GlossiaError>(), Some(&GlossiaError::ParityMismatch { sentences: vec![3] }));
+        assert!(err.to_string().contains("sentence 3 failed parity"), "{}", err);
+
+        assert!(mark(&mut rng, text, &wordlist, &markers[..8], &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_tails_are_cover_words() {
+        let payload = include_str!("../languages/english/payload.yaml");
+        let wordlist: HashSet<&str> = payload.lines().filter_map(|line| line.strip_suffix(':')).collect();
+        for tail in TAILS {
+            assert!(tail.split_whitespace().all(|w| !wordlist.contains(w)), "{}", tail);
+        }
+    }
+}
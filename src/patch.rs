@@ -0,0 +1,187 @@
+//! Payload updates sent as a binary diff against an earlier payload.
+//!
+//! An edited note or a rotated token changes a few bytes of a payload the
+//! receiver already has. [`diff`] writes only what changed: copies of runs
+//! of the old payload (the base) and the new bytes between them, behind a
+//! header naming the base by the first bytes of its SHA-256. [`apply`]
+//! checks the base against that id before rebuilding the new payload;
+//! [`encode_patch`] and [`decode_patch`] go to and from carrier words like
+//! [`payload`](crate::payload) does for whole payloads.
+
+use crate::framing::{read_varint, write_varint};
+use crate::payload::{self, PayloadOptions};
+use crate::secret::Wipe;
+use anyhow::{anyhow, bail, Result};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Bytes of the base's SHA-256 a patch starts with
+pub const BASE_ID_LEN: usize = 8;
+
+/// Shortest run of the base worth a copy rather than literal bytes
+const MIN_COPY: usize = 4;
+
+/// Places in the base tried for each run, so repetitive bases stay linear
+const MAX_CANDIDATES: usize = 16;
+
+/// Low bit of an op: copy from the base, or insert the bytes that follow
+const COPY: u64 = 1;
+
+/// The id a patch names its base by.
+pub fn base_id(base: &[u8]) -> [u8; BASE_ID_LEN] {
+    Sha256::digest(base)[..BASE_ID_LEN].try_into().expect("digest is longer than the id")
+}
+
+/// A patch that turns `base` into `new`.
+pub fn diff(base: &[u8], new: &[u8]) -> Vec<u8> {
+    let mut starts: HashMap<&[u8], Vec<usize>> = HashMap::new();
+    for (start, run) in base.windows(MIN_COPY).enumerate() {
+        let places = starts.entry(run).or_default();
+        if places.len() < MAX_CANDIDATES {
+            places.push(start);
+        }
+    }
+
+    let mut out = base_id(base).to_vec();
+    let mut literal = Vec::new();
+    let mut at = 0;
+    while at < new.len() {
+        // The longest run of the base that continues here, earliest first
+        let best = new
+            .get(at..at + MIN_COPY)
+            .and_then(|run| starts.get(run))
+            .into_iter()
+            .flatten()
+            .map(|&start| (start, base[start..].iter().zip(&new[at..]).take_while(|(a, b)| a == b).count()))
+            .fold(None, |best: Option<(usize, usize)>, (start, len)| match best {
+                Some((_, best_len)) if best_len >= len => best,
+                _ => Some((start, len)),
+            });
+        match best {
+            Some((start, len)) => {
+                write_insert(&mut out, &literal);
+                literal.clear();
+                write_varint(&mut out, (len as u64) << 1 | COPY);
+                write_varint(&mut out, start as u64);
+                at += len;
+            }
+            None => {
+                literal.push(new[at]);
+                at += 1;
+            }
+        }
+    }
+    write_insert(&mut out, &literal);
+    literal.wipe();
+    out
+}
+
+fn write_insert(out: &mut Vec<u8>, bytes: &[u8]) {
+    if !bytes.is_empty() {
+        write_varint(out, (bytes.len() as u64) << 1);
+        out.extend_from_slice(bytes);
+    }
+}
+
+/// The payload a [`diff`] patch rebuilds from `base`. Fails if the patch
+/// was made against another base.
+pub fn apply(base: &[u8], patch: &[u8]) -> Result<Vec<u8>> {
+    let id = patch.get(..BASE_ID_LEN).ok_or_else(|| anyhow!("Patch is truncated"))?;
+    if id != base_id(base) {
+        bail!("Patch was made against base {}, not this one ({})", hex(id), hex(&base_id(base)));
+    }
+    let mut rest = &patch[BASE_ID_LEN..];
+    let mut out = Vec::new();
+    while !rest.is_empty() {
+        let (op, tail) = read_varint(rest)?;
+        let len = usize::try_from(op >> 1)?;
+        let (bytes, tail) = if op & COPY != 0 {
+            let (start, tail) = read_varint(tail)?;
+            let start = usize::try_from(start)?;
+            let run = start.checked_add(len).and_then(|end| base.get(start..end));
+            (run.ok_or_else(|| anyhow!("Patch copies past the end of the base"))?, tail)
+        } else {
+            let bytes = tail.get(..len).ok_or_else(|| anyhow!("Patch is truncated"))?;
+            (bytes, &tail[len..])
+        };
+        out.extend_from_slice(bytes);
+        rest = tail;
+    }
+    Ok(out)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Encode the patch from `base` to `new` as carrier words.
+pub fn encode_patch<R: Rng>(
+    rng: &mut R,
+    base: &[u8],
+    new: &[u8],
+    wordlist: &[String],
+    options: &PayloadOptions,
+) -> Result<Vec<String>> {
+    let mut patch = diff(base, new);
+    let words = payload::encode_payload(rng, &patch, wordlist, options);
+    patch.wipe();
+    words
+}
+
+/// Recover the payload a patch text encodes, applied to `base`.
+pub fn decode_patch(base: &[u8], text: &str, wordlist: &[String], options: &PayloadOptions) -> Result<Vec<u8>> {
+    let mut patch = payload::decode_payload(text, wordlist, options)?;
+    let new = apply(base, &patch);
+    patch.wipe();
+    new
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const NOTE: &[u8] = b"Wifi: hunter2-garden\nSafe: 31-14-15\nSpare key under the blue pot by the shed door.\n";
+
+    #[test]
+    fn test_patch_round_trip() {
+        let edited = b"Wifi: hunter2-garden\nSafe: 27-18-28\nSpare key under the blue pot by the shed door.\n";
+        let rotated = [b"token=".as_slice(), &[0xa5; 32], b";exp=2027"].concat();
+        let rerotated = [b"token=".as_slice(), &[0x5a; 32], b";exp=2028"].concat();
+        for (base, new) in [(NOTE, edited.as_slice()), (&rotated, &rerotated), (NOTE, b""), (b"", NOTE), (NOTE, NOTE)] {
+            let patch = diff(base, new);
+            assert_eq!(apply(base, &patch).unwrap(), new);
+        }
+        // Two copies and the changed digits
+        assert!(diff(NOTE, edited).len() < BASE_ID_LEN + 16, "{} bytes", diff(NOTE, edited).len());
+    }
+
+    #[test]
+    fn test_refuses_other_bases() {
+        let patch = diff(NOTE, b"Wifi: hunter3-garden\n");
+        let err = apply(b"Wifi: hunter2-garden\n", &patch).unwrap_err();
+        assert!(err.to_string().contains("against base"), "{}", err);
+        assert!(apply(NOTE, &patch[..patch.len() - 1]).is_err());
+        assert!(apply(NOTE, &patch[..4]).is_err());
+        // Copy 4 bytes from offset 200 of an 86-byte base
+        assert!(apply(NOTE, &[base_id(NOTE).as_slice(), &[9, 200, 1]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_patch_text_round_trip() {
+        let list = alpha_wordlist();
+        let options = PayloadOptions { checksum: true, ..Default::default() };
+        let edited = NOTE.to_ascii_uppercase();
+        let mut rng = StdRng::seed_from_u64(5);
+
+        let words = encode_patch(&mut rng, NOTE, b"Wifi: hunter3-garden\n", &list, &options).unwrap();
+        assert!(words.len() < 16, "{} words", words.len());
+        assert_eq!(decode_patch(NOTE, &words.join(" "), &list, &options).unwrap(), b"Wifi: hunter3-garden\n");
+
+        let words = encode_patch(&mut rng, NOTE, &edited, &list, &options).unwrap();
+        assert!(decode_patch(&edited, &words.join(" "), &list, &options).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Payload-level encode and decode with the common framing options.
+//!
+//! The CLI chains these steps itself; language bindings and services call
+//! [`encode_payload`] and [`decode_payload`] with a [`PayloadOptions`] instead,
+//! so every front end frames a payload the same way: optional whitening (a
+//! nonce of several words plus keystream, see [`crate::whiten`]), then bit
+//! packing, then an optional trailing checksum word.
+
+use crate::keys::TAG_LEN;
+use crate::normalize::{self, NormalizeOptions};
+use crate::secret::Wipe;
+use crate::{codec, decode, phonetic, weights, whiten};
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// Framing applied around the packed payload words.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PayloadOptions {
+    /// Append a checksum word (verified and stripped when decoding)
+    pub checksum: bool,
+    /// Whiten the bytes before packing them
+    pub whiten: bool,
+    /// Keys the whitening keystream
+    pub passphrase: Option<String>,
+    /// Add a key-committing tag to whitened payloads, so they decode under
+    /// `passphrase` only ([`whiten::encode_committed`])
+    pub commit: bool,
+}
+
+/// Encode `bytes` as carrier words from `wordlist` (canonical sorted order).
+pub fn encode_payload<R: Rng>(
+    rng: &mut R,
+    bytes: &[u8],
+    wordlist: &[String],
+    options: &PayloadOptions,
+) -> Result<Vec<String>> {
+    let words = if options.whiten && options.commit {
+        whiten::encode_committed(rng, bytes, wordlist, options.passphrase.as_deref())?
+    } else if options.whiten {
+        whiten::encode_whitened(rng, bytes, wordlist, options.passphrase.as_deref())?
+    } else if options.commit {
+        bail!("A key-committing tag needs whitening");
+    } else {
+        codec::encode_bytes(bytes, wordlist)?
+    };
+    if options.checksum {
+        codec::append_checksum(&words, wordlist)
+    } else {
+        Ok(words)
+    }
+}
+
+/// Recover the bytes [`encode_payload`] packed into the carrier words of
+/// `text`, which is cleaned up first ([`normalize::clean`]).
+pub fn decode_payload(text: &str, wordlist: &[String], options: &PayloadOptions) -> Result<Vec<u8>> {
+    let set: HashSet<String> = wordlist.iter().cloned().collect();
+    let mut words = decode::extract_payload_words(&normalize::clean(text, NormalizeOptions::default()), &set);
+    if options.checksum {
+        let body = codec::strip_checksum(&words, wordlist);
+        words.wipe();
+        words = body?;
+    }
+    let bytes = if options.whiten && options.commit {
+        whiten::decode_committed(&words, wordlist, options.passphrase.as_deref())
+    } else if options.whiten {
+        whiten::decode_whitened(&words, wordlist, options.passphrase.as_deref())
+    } else {
+        codec::decode_bytes(&words, wordlist)
+    };
+    words.wipe();
+    bytes
+}
+
+/// Number of carrier words [`encode_payload`] emits for a `byte_len`-byte payload.
+pub fn carrier_words(byte_len: usize, wordlist_len: usize, options: &PayloadOptions) -> usize {
+    let byte_len = if options.whiten && options.commit { byte_len + TAG_LEN } else { byte_len };
+    let packed = codec::packed_words(byte_len, wordlist_len);
+    let nonce = if options.whiten { whiten::nonce_words(wordlist_len) } else { 0 };
+    packed + nonce + usize::from(options.checksum)
+}
+
+/// Words that introduce a [`fingerprint_sentence`]; neither is a BIP39 word
+pub const FINGERPRINT_LEAD: &str = "Read back:";
+
+/// A four-word phrase naming `data`, for sender and receiver to read to each
+/// other: the first four bytes of its SHA-256 as [`phonetic`] words, which
+/// are chosen to be told apart aloud. It depends only on the bytes, not on
+/// how they were encoded.
+pub fn fingerprint(data: &[u8]) -> String {
+    phonetic::encode_bytes(&Sha256::digest(data)[..4]).join(" ")
+}
+
+/// The sentence a cover text may end with to carry [`fingerprint`]
+pub fn fingerprint_sentence(data: &[u8]) -> String {
+    format!("{} {}.", FINGERPRINT_LEAD, fingerprint(data))
+}
+
+/// Split a trailing [`fingerprint_sentence`] off `text`: the text before it,
+/// and the phrase in lowercase. `None` if `text` does not end with one.
+pub fn split_fingerprint(text: &str) -> Option<(&str, String)> {
+    let tokens: Vec<&str> = text.split_whitespace().collect();
+    let [.., read, back, _, _, _, _] = tokens[..] else {
+        return None;
+    };
+    let (lead_read, lead_back) = FINGERPRINT_LEAD.split_once(' ').expect("two lead words");
+    if !decode::strip_markup(read).eq_ignore_ascii_case(lead_read) || !decode::strip_markup(back).eq_ignore_ascii_case(lead_back) {
+        return None;
+    }
+    let phrase: Vec<String> = tokens[tokens.len() - 4..].iter().map(|word| decode::normalize_token(word)).collect();
+    let start = read.as_ptr() as usize - text.as_ptr() as usize;
+    Some((text[..start].trim_end(), phrase.join(" ")))
+}
+
+/// What [`detect`] found in a text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Detection {
+    /// Tokens that are wordlist words
+    pub carrier_words: usize,
+    /// All whitespace-separated tokens
+    pub total_words: usize,
+    /// The last carrier word is a valid checksum of the others (`checksum` framing)
+    pub checksum_valid: bool,
+}
+
+impl Detection {
+    /// Whether the text plausibly carries a payload: it has carrier words, and
+    /// either verifies as checksum-framed or has at least one carrier word per
+    /// eight tokens (ordinary English prose has far fewer BIP39 words).
+    pub fn is_likely(&self) -> bool {
+        self.carrier_words > 0 && (self.checksum_valid || self.carrier_words * 8 >= self.total_words)
+    }
+}
+
+/// Inspect `text` for carrier words without decoding it.
+pub fn detect(text: &str, wordlist: &[String]) -> Detection {
+    let set: HashSet<String> = wordlist.iter().cloned().collect();
+    let text = normalize::clean(text, NormalizeOptions::default());
+    let words = decode::extract_payload_words(&text, &set);
+    Detection {
+        carrier_words: words.len(),
+        total_words: text.split_whitespace().count(),
+        checksum_valid: words.len() > 1 && codec::strip_checksum(&words, wordlist).is_ok(),
+    }
+}
+
+/// The payload wordlist from a `payload.yaml` document (its keys, sorted).
+pub fn wordlist_from_yaml(yaml: &str) -> Result<Vec<String>> {
+    let entries = weights::parse(yaml).context("Invalid payload wordlist")?;
+    let mut words: Vec<String> = entries.words().map(str::to_string).collect();
+    words.sort();
+    Ok(words)
+}
+
+/// A payload wordlist from a list of words, checked and put in canonical
+/// (sorted) order: at least two words, each lowercase, without whitespace, and
+/// listed once. Front ends that take a wordlist from the user (rather than a
+/// `payload.yaml`) check it here, so a list that would decode ambiguously is
+/// refused up front.
+pub fn wordlist_from_words(words: Vec<String>) -> Result<Vec<String>> {
+    if words.len() < 2 {
+        bail!("A wordlist needs at least two words, got {}", words.len());
+    }
+    for word in &words {
+        if word.is_empty() || word.chars().any(char::is_whitespace) {
+            bail!("Wordlist entry {:?} is not a single word", word);
+        }
+        if *word != word.to_lowercase() {
+            bail!("Wordlist entry '{}' is not lowercase", word);
+        }
+    }
+    let mut sorted = words;
+    sorted.sort();
+    if let Some(pair) = sorted.windows(2).find(|pair| pair[0] == pair[1]) {
+        bail!("Wordlist lists '{}' twice", pair[0]);
+    }
+    Ok(sorted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_roundtrip_with_each_framing() {
+        let list = alpha_wordlist();
+        let mut rng = StdRng::seed_from_u64(3);
+        let framings = [
+            PayloadOptions::default(),
+            PayloadOptions { checksum: true, ..Default::default() },
+            PayloadOptions { whiten: true, passphrase: Some("pass".into()), ..Default::default() },
+            PayloadOptions { checksum: true, whiten: true, passphrase: None, commit: false },
+            PayloadOptions { whiten: true, passphrase: Some("pass".into()), commit: true, ..Default::default() },
+        ];
+        for options in &framings {
+            let words = encode_payload(&mut rng, b"Hello World", &list, options).unwrap();
+            assert_eq!(words.len(), carrier_words(11, list.len(), options));
+            let text = words.iter().map(|w| format!("The {} said.", w)).collect::<Vec<_>>().join(" ");
+            assert_eq!(decode_payload(&text, &list, options).unwrap(), b"Hello World", "{:?}", options);
+        }
+    }
+
+    #[test]
+    fn test_fingerprint_sentence() {
+        let phrase = fingerprint(b"Hello World");
+        assert_eq!(phrase.split(' ').count(), 4);
+        assert_eq!(phrase, fingerprint(b"Hello World"));
+        assert_ne!(phrase, fingerprint(b"Hello World!"));
+
+        let text = format!("The |wabc| said.\n{}", fingerprint_sentence(b"Hello World"));
+        assert_eq!(split_fingerprint(&text), Some(("The |wabc| said.", phrase.to_lowercase())));
+        assert_eq!(split_fingerprint("The wabc said. Read back: one two three."), None);
+        assert_eq!(split_fingerprint("The wabc said one two three four."), None);
+    }
+
+    #[test]
+    fn test_checksum_rejects_tampering() {
+        let list = alpha_wordlist();
+        let options = PayloadOptions { checksum: true, ..Default::default() };
+        let mut words = encode_payload(&mut StdRng::seed_from_u64(4), b"abc", &list, &options).unwrap();
+        words[0] = if words[0] == list[0] { list[1].clone() } else { list[0].clone() };
+        assert!(decode_payload(&words.join(" "), &list, &options).is_err());
+    }
+
+    #[test]
+    fn test_detect_checksum_framing() {
+        let list = alpha_wordlist();
+        let options = PayloadOptions { checksum: true, ..Default::default() };
+        let words = encode_payload(&mut StdRng::seed_from_u64(5), b"Hi there", &list, &options).unwrap();
+        let text = words.iter().map(|w| format!("Some people said {} was here today.", w)).collect::<Vec<_>>().join(" ");
+
+        let found = detect(&text, &list);
+        assert_eq!(found.carrier_words, words.len());
+        assert!(found.checksum_valid);
+        assert!(found.is_likely());
+
+        let plain = detect("Nothing to see in this ordinary sentence at all.", &list);
+        assert_eq!(plain, Detection { carrier_words: 0, total_words: 9, checksum_valid: false });
+        assert!(!plain.is_likely());
+    }
+
+    #[test]
+    fn test_wordlist_from_yaml_sorts_keys() {
+        let words = wordlist_from_yaml("zoo:\n  N: 1.0\nable:\n  Adj: 1.0\n").unwrap();
+        assert_eq!(words, ["able", "zoo"]);
+    }
+
+    #[test]
+    fn test_wordlist_from_words() {
+        let words = |list: &[&str]| list.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+        assert_eq!(wordlist_from_words(words(&["zoo", "able", "moss"])).unwrap(), ["able", "moss", "zoo"]);
+        for bad in [&["able"][..], &["able", "Zoo"], &["able", "ice cream"], &["able", ""], &["zoo", "able", "zoo"]] {
+            assert!(wordlist_from_words(words(bad)).is_err(), "{:?}", bad);
+        }
+    }
+}
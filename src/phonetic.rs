@@ -0,0 +1,116 @@
+//! Phonetically distinct wordlist for voice channels.
+//!
+//! Read over a phone or radio, BIP39 words get confused ("sea"/"see",
+//! "wood"/"would"). This list follows the PGP word list: 256 two-syllable
+//! words for bytes at even positions and 256 three-syllable words for bytes
+//! at odd positions, chosen to sound unlike each other. Each word carries one
+//! byte, and because the two lists alternate, a dropped, doubled or swapped
+//! word shows up as two words of the same kind in a row
+//! ([`GlossiaError::BrokenAlternation`]).
+
+use crate::codec::unknown_word;
+use crate::error::GlossiaError;
+use anyhow::Result;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+/// One line per byte value: its even word, then its odd word
+const WORDS_FILE: &str = include_str!("phonetic_words.txt");
+
+struct Lists {
+    /// `[even, odd]` words for each byte value
+    words: Vec<[String; 2]>,
+    /// Word -> (byte value, parity: 0 even, 1 odd)
+    values: HashMap<String, (u8, usize)>,
+}
+
+fn lists() -> &'static Lists {
+    static LISTS: OnceLock<Lists> = OnceLock::new();
+    LISTS.get_or_init(|| {
+        let words: Vec<[String; 2]> = WORDS_FILE
+            .lines()
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+            .filter_map(|line| {
+                let mut pair = line.split_whitespace().map(str::to_string);
+                Some([pair.next()?, pair.next()?])
+            })
+            .collect();
+        let values = words
+            .iter()
+            .enumerate()
+            .flat_map(|(value, pair)| pair.iter().enumerate().map(move |(parity, w)| (w.clone(), (value as u8, parity))))
+            .collect();
+        Lists { words, values }
+    })
+}
+
+/// All 512 words: the even list, then the odd list
+pub fn words() -> Vec<String> {
+    let lists = lists();
+    (0..2).flat_map(|parity| lists.words.iter().map(move |pair| pair[parity].clone())).collect()
+}
+
+/// One word per byte, alternating even and odd words from the first
+pub fn encode_bytes(bytes: &[u8]) -> Vec<String> {
+    let words = &lists().words;
+    bytes.iter().enumerate().map(|(position, &b)| words[b as usize][position % 2].clone()).collect()
+}
+
+/// The bytes of [`encode_bytes`] words, checking that even and odd words
+/// alternate. The first word that breaks the alternation is reported: a
+/// word before it was dropped, doubled or swapped.
+pub fn decode_words(words: &[String]) -> Result<Vec<u8>> {
+    let values = &lists().values;
+    words
+        .iter()
+        .enumerate()
+        .map(|(position, word)| match values.get(&word.to_lowercase()) {
+            Some(&(value, parity)) if parity == position % 2 => Ok(value),
+            Some(_) => Err(GlossiaError::BrokenAlternation { word: word.clone(), position: position + 1 }.into()),
+            None => Err(unknown_word(word, position).into()),
+        })
+        .collect()
+}
+
+/// Check the alternation of `words` without decoding them
+pub fn check_alternation(words: &[String]) -> Result<()> {
+    decode_words(words).map(drop)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_lists_are_complete_and_match_weights() {
+        let all = words();
+        assert_eq!(all.len(), 512);
+        assert_eq!(all.iter().collect::<HashSet<_>>().len(), 512);
+        assert_eq!(lists().words[0], ["aardvark".to_string(), "adroitness".to_string()]);
+
+        // The POS weight file lists the same words
+        let weights = include_str!("../languages/english/phonetic.yaml");
+        let listed: HashSet<&str> = weights.lines().filter_map(|line| line.strip_suffix(':')).collect();
+        assert_eq!(listed, all.iter().map(String::as_str).collect());
+    }
+
+    #[test]
+    fn test_round_trip_and_alternation() {
+        let bytes = b"\x00\xffHi!";
+        let words = encode_bytes(bytes);
+        assert_eq!(words[0], "aardvark");
+        assert_eq!(decode_words(&words).unwrap(), bytes.to_vec());
+        assert_eq!(decode_words(&[words[0].to_uppercase()]).unwrap(), vec![0]);
+
+        // Dropping the second word puts two even words side by side
+        let mut dropped = words.clone();
+        dropped.remove(1);
+        let err = decode_words(&dropped).unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<GlossiaError>(),
+            Some(&GlossiaError::BrokenAlternation { word: words[2].clone(), position: 2 })
+        );
+        assert!(check_alternation(&["abandon".to_string()]).unwrap_err().downcast_ref::<GlossiaError>().is_some());
+    }
+}
@@ -0,0 +1,191 @@
+//! Morphological prefixes on carrier words.
+//!
+//! Always writing a carrier in its bare form is a pattern of its own, so the
+//! encoder can put a prefix on some adjectives and verbs ("unsafe", "reload",
+//! "overcook"). The prefix is not a wordlist word and carries nothing: the
+//! decoder maps each prefixed form back to its base through the same
+//! corrections table that homophone decoding uses. Only the pairs a
+//! [`PrefixRules`] file lists are used, so the prefixed forms are real words.
+
+use crate::decode::normalize_token;
+use crate::synonyms::map_tokens;
+use crate::types::Pos;
+use anyhow::{anyhow, bail, Result};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// One prefix and the words it attaches to.
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    prefix: String,
+    pos: Pos,
+    bases: Vec<String>,
+}
+
+/// Prefix rules, in file order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PrefixRules {
+    rules: Vec<Rule>,
+}
+
+impl PrefixRules {
+    /// Parse one rule per line: `<prefix> <Adj|V>: <words>`. Blank lines and
+    /// lines starting with `#` are ignored; errors name the line.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut rules = Vec::new();
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let rule = Self::parse_rule(line).map_err(|e| anyhow!("line {}: {}", n + 1, e))?;
+            rules.push(rule);
+        }
+        Ok(Self { rules })
+    }
+
+    fn parse_rule(line: &str) -> Result<Rule> {
+        let (head, words) = line.split_once(':').ok_or_else(|| anyhow!("expected '<prefix> <tag>: <words>'"))?;
+        let mut head = head.split_whitespace();
+        let (Some(prefix), Some(tag), None) = (head.next(), head.next(), head.next()) else {
+            bail!("expected '<prefix> <tag>: <words>'");
+        };
+        let pos = match Pos::from_name(tag) {
+            Some(pos @ (Pos::Adj | Pos::V)) => pos,
+            _ => bail!("prefixes attach to Adj or V words, not '{}'", tag),
+        };
+        if !prefix.bytes().all(|b| b.is_ascii_lowercase()) {
+            bail!("prefix '{}' must be lowercase letters", prefix);
+        }
+        let bases: Vec<String> = words.split_whitespace().map(str::to_lowercase).collect();
+        if bases.is_empty() {
+            bail!("no words for prefix '{}'", prefix);
+        }
+        Ok(Rule { prefix: prefix.to_string(), pos, bases })
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Prefixed form -> base, for the bases in `wordlist` whose prefixed form
+    /// is not itself a wordlist word. Decoding looks tokens up here first;
+    /// encoding drops cover words that appear as keys.
+    pub fn corrections(&self, wordlist: &HashSet<String>) -> HashMap<String, String> {
+        let mut corrections = HashMap::new();
+        for rule in &self.rules {
+            for base in rule.bases.iter().filter(|base| wordlist.contains(*base)) {
+                let prefixed = format!("{}{}", rule.prefix, base);
+                if !wordlist.contains(&prefixed) {
+                    corrections.insert(prefixed, base.clone());
+                }
+            }
+        }
+        corrections
+    }
+
+    /// Put a prefix on each listed carrier in `text` with probability
+    /// `rate`, choosing among the prefixes that word takes. Capitalization,
+    /// punctuation and highlighting are kept.
+    pub fn attach<R: Rng + ?Sized>(&self, rng: &mut R, text: &str, wordlist: &HashSet<String>, rate: f64) -> String {
+        let corrections = self.corrections(wordlist);
+        let mut by_base: HashMap<&str, Vec<&str>> = HashMap::new();
+        for rule in &self.rules {
+            for base in &rule.bases {
+                if corrections.get(&format!("{}{}", rule.prefix, base)) == Some(base) {
+                    by_base.entry(base.as_str()).or_default().push(rule.prefix.as_str());
+                }
+            }
+        }
+        map_tokens(text, |token| {
+            let prefixes = by_base.get(normalize_token(token).as_str())?;
+            if !rng.gen_bool(rate) {
+                return None;
+            }
+            let prefix = prefixes[rng.gen_range(0..prefixes.len())];
+            Some(prefix_word(token, prefix))
+        })
+    }
+}
+
+/// `token` with `prefix` before its word, moving a leading capital onto the
+/// prefix (`|Safe|.` becomes `|Unsafe|.`)
+fn prefix_word(token: &str, prefix: &str) -> String {
+    // Skip highlighting: bars, and ANSI codes up to their closing `m`
+    let mut in_escape = false;
+    let start = token
+        .char_indices()
+        .find(|&(_, c)| {
+            if c == '\x1b' {
+                in_escape = true;
+            } else if in_escape {
+                in_escape = c != 'm';
+                return false;
+            }
+            !in_escape && c.is_ascii_alphabetic()
+        })
+        .map_or(0, |(i, _)| i);
+    let (before, word) = token.split_at(start);
+    let mut out = before.to_string();
+    if word.starts_with(|c: char| c.is_ascii_uppercase()) {
+        out.push_str(&prefix[..1].to_ascii_uppercase());
+        out.push_str(&prefix[1..]);
+        out.push_str(&word[..1].to_ascii_lowercase());
+        out.push_str(&word[1..]);
+    } else {
+        out.push_str(prefix);
+        out.push_str(word);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::extract_payload_words_with_corrections;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const RULES: &str = "# test\nun Adj: safe happy fair\nre V: load\nover V: load\n";
+
+    fn set(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_and_corrections() {
+        let rules = PrefixRules::parse(RULES).unwrap();
+        assert_eq!(rules.len(), 3);
+        assert!(PrefixRules::parse("un N: dog").unwrap_err().to_string().contains("line 1"));
+        assert!(PrefixRules::parse("un Adj safe").is_err());
+        assert!(PrefixRules::parse("un Adj:").is_err());
+
+        // "unfair" is itself a wordlist word, and "happy" is not one
+        let wordlist = set(&["safe", "fair", "unfair", "load"]);
+        let corrections = rules.corrections(&wordlist);
+        let mut pairs: Vec<(&str, &str)> = corrections.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![("overload", "load"), ("reload", "load"), ("unsafe", "safe")]);
+    }
+
+    #[test]
+    fn test_attach_round_trips() {
+        let rules = PrefixRules::parse(RULES).unwrap();
+        let wordlist = set(&["safe", "fair", "unfair", "load"]);
+        let text = "|Safe| roads load fast. The |load| was fair, \x1b[32msafe\x1b[0m.";
+        let mut rng = StdRng::seed_from_u64(1);
+        let out = rules.attach(&mut rng, text, &wordlist, 1.0);
+        assert!(out.starts_with("|Unsafe| roads "), "{}", out);
+        assert!(out.contains("was fair,") && out.contains("\x1b[32munsafe\x1b[0m."), "{}", out);
+        assert!(!out.contains(" load "), "{}", out);
+
+        let corrections = rules.corrections(&wordlist);
+        let expected = vec!["safe", "load", "load", "fair", "safe"];
+        assert_eq!(extract_payload_words_with_corrections(&out, &wordlist, &corrections), expected);
+        assert_eq!(extract_payload_words_with_corrections(text, &wordlist, &corrections), expected);
+    }
+}
@@ -0,0 +1,137 @@
+//! Readability scores for generated text.
+//!
+//! BIP39 contains a fair number of long or uncommon words ("abstract",
+//! "october", "vehicle"), and a sentence that packs several of them reads far
+//! above the surrounding prose. These scores let the encoder (or a caller)
+//! notice and reject such sentences.
+
+use std::collections::HashSet;
+
+/// Readability measures for a piece of text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReadabilityScores {
+    /// Flesch-Kincaid grade level (U.S. school grade; higher is harder)
+    pub flesch_kincaid: f64,
+    /// Mean words per sentence
+    pub avg_sentence_len: f64,
+    /// Fraction of words considered rare
+    pub rare_word_ratio: f64,
+}
+
+/// Score `text`, treating words of three or more syllables as rare.
+pub fn readability(text: &str) -> ReadabilityScores {
+    score(text, |word| count_syllables(word) >= 3)
+}
+
+/// Score `text`, treating any word outside `common` (lowercase) as rare.
+pub fn readability_with_vocabulary(text: &str, common: &HashSet<String>) -> ReadabilityScores {
+    score(text, |word| !common.contains(word))
+}
+
+fn score(text: &str, is_rare: impl Fn(&str) -> bool) -> ReadabilityScores {
+    let mut sentences = 0usize;
+    let mut words = 0usize;
+    let mut syllables = 0usize;
+    let mut rare = 0usize;
+
+    for sentence in text.split(['.', '!', '?']) {
+        let sentence_words: Vec<String> = sentence
+            .split(|c: char| !(c.is_alphabetic() || c == '\''))
+            .map(|w| w.trim_matches('\'').to_lowercase())
+            .filter(|w| !w.is_empty())
+            .collect();
+        if sentence_words.is_empty() {
+            continue;
+        }
+        sentences += 1;
+        words += sentence_words.len();
+        for word in &sentence_words {
+            syllables += count_syllables(word);
+            if is_rare(word) {
+                rare += 1;
+            }
+        }
+    }
+
+    if words == 0 {
+        return ReadabilityScores {
+            flesch_kincaid: 0.0,
+            avg_sentence_len: 0.0,
+            rare_word_ratio: 0.0,
+        };
+    }
+
+    let avg_sentence_len = words as f64 / sentences as f64;
+    let syllables_per_word = syllables as f64 / words as f64;
+    ReadabilityScores {
+        flesch_kincaid: 0.39 * avg_sentence_len + 11.8 * syllables_per_word - 15.59,
+        avg_sentence_len,
+        rare_word_ratio: rare as f64 / words as f64,
+    }
+}
+
+/// Estimate the syllable count of an English word by counting vowel groups.
+///
+/// A trailing silent "e" is not counted ("game", but not "able"), and every word
+/// has at least one syllable. Good enough for grade-level estimates.
+pub fn count_syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let chars: Vec<char> = word.chars().filter(|c| c.is_alphabetic()).collect();
+    if chars.is_empty() {
+        return 0;
+    }
+
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+    let mut count = 0;
+    let mut prev_vowel = false;
+    for &c in &chars {
+        let vowel = is_vowel(c);
+        if vowel && !prev_vowel {
+            count += 1;
+        }
+        prev_vowel = vowel;
+    }
+
+    let n = chars.len();
+    if n > 2 && chars[n - 1] == 'e' && !is_vowel(chars[n - 2]) && chars[n - 2] != 'l' && count > 1 {
+        count -= 1;
+    }
+
+    count.max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_count_syllables() {
+        assert_eq!(count_syllables("cat"), 1);
+        assert_eq!(count_syllables("game"), 1);
+        assert_eq!(count_syllables("able"), 2);
+        assert_eq!(count_syllables("october"), 3);
+        assert_eq!(count_syllables("Abandon"), 3);
+        assert_eq!(count_syllables("the"), 1);
+    }
+
+    #[test]
+    fn test_readability_scores() {
+        let easy = readability("The cat sat. The dog ran.");
+        assert_eq!(easy.avg_sentence_len, 3.0);
+        assert_eq!(easy.rare_word_ratio, 0.0);
+
+        let hard = readability("Abstract ability obviously illustrates vocabulary.");
+        assert!(hard.flesch_kincaid > easy.flesch_kincaid + 5.0);
+        assert!(hard.rare_word_ratio > 0.5);
+
+        let empty = readability("");
+        assert_eq!(empty.avg_sentence_len, 0.0);
+    }
+
+    #[test]
+    fn test_readability_with_vocabulary() {
+        let common: HashSet<String> = ["the", "cat", "sat"].iter().map(|w| w.to_string()).collect();
+        let scores = readability_with_vocabulary("The cat sat on zebras.", &common);
+        assert!((scores.rare_word_ratio - 0.4).abs() < 1e-9);
+    }
+}
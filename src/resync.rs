@@ -0,0 +1,286 @@
+//! Resync markers, for texts a platform may cut short.
+//!
+//! A message truncated past its limit loses its last carriers without a
+//! trace: the words that are left still decode, to a payload that is simply
+//! shorter. A [`Fragment`] interleaves markers with the carriers, `#k/T`
+//! after every N carrier words, where `k` counts the carriers so far and `T`
+//! is the payload's length in bytes, plus one at the start and one after the
+//! last carrier. None of them is a wordlist word. [`recover`] reads back the
+//! carriers up to the last marker whose count still matches, reports how
+//! many payload bytes those hold, and where to resume: a text sent again
+//! from byte `b` carries `#k/T@b` markers, and a retransmission pasted after
+//! the truncated text decodes with it.
+//!
+//! ```
+//! use glossia::resync::{recover, Fragment};
+//!
+//! let letter = |n: usize| (b'a' + (n % 26) as u8) as char;
+//! let wordlist: Vec<String> = (0..256).map(|i| format!("w{}{}", letter(i / 26), letter(i))).collect();
+//! let words = glossia::codec::encode_bytes(b"meet at noon", &wordlist)?;
+//! let text = Fragment::new(12, 0, 4)?.mark(&words.join(" "), &wordlist)?;
+//! assert!(text.starts_with("#0/12 "));
+//! assert_eq!(recover(&text, &wordlist, 4)?.into_bytes()?, b"meet at noon");
+//!
+//! // Cut off after the sixth word: the first four are vouched for
+//! let cut: Vec<&str> = text.split(' ').take(8).collect();
+//! let recovery = recover(&cut.join(" "), &wordlist, 4)?;
+//! assert_eq!((recovery.resume_at(), recovery.total), (4, 12));
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+use crate::codec;
+use crate::decode::{normalize_token, strip_markup};
+use crate::error::GlossiaError;
+use crate::index::WordIndex;
+use crate::payload::{carrier_words, PayloadOptions};
+use anyhow::{bail, Result};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Where a run of carriers sits in the payload, and how often it is marked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fragment {
+    /// Payload length in bytes
+    pub total: usize,
+    /// Byte the carriers start from; 0 unless this is a retransmission
+    pub offset: usize,
+    /// Carrier words between markers
+    pub every: usize,
+}
+
+impl Fragment {
+    pub fn new(total: usize, offset: usize, every: usize) -> Result<Self> {
+        if every == 0 {
+            bail!("Resync markers need at least one carrier word between them");
+        }
+        if offset > total {
+            bail!("Cannot resume from byte {} of a {}-byte payload", offset, total);
+        }
+        Ok(Self { total, offset, every })
+    }
+
+    /// The marker after the `k`th carrier word
+    pub fn marker(&self, k: usize) -> String {
+        if self.offset == 0 {
+            format!("#{}/{}", k, self.total)
+        } else {
+            format!("#{}/{}@{}", k, self.total, self.offset)
+        }
+    }
+
+    /// Carrier words encoding the payload from `offset` takes
+    pub fn carriers(&self, wordlist_len: usize) -> usize {
+        carrier_words(self.total - self.offset, wordlist_len, &PayloadOptions::default())
+    }
+
+    /// `text` with a marker at the start, after every `every`th carrier and
+    /// after the last one. Fails if `text` does not have the carriers the
+    /// fragment's bytes encode to.
+    pub fn mark(&self, text: &str, wordlist: &[String]) -> Result<String> {
+        let index = WordIndex::shared(wordlist)?;
+        if index.is_unspaced() {
+            bail!("Resync markers need a wordlist written with spaces");
+        }
+        let expected = self.carriers(wordlist.len());
+        let mut out = format!("{} ", self.marker(0));
+        let (mut k, mut copied) = (0, 0);
+        for token in token_re().find_iter(text) {
+            if index.get(&normalize_token(token.as_str())).is_none() {
+                continue;
+            }
+            k += 1;
+            if k % self.every == 0 || k == expected {
+                out.push_str(&text[copied..token.end()]);
+                out.push(' ');
+                out.push_str(&self.marker(k));
+                copied = token.end();
+            }
+        }
+        out.push_str(&text[copied..]);
+        if k != expected {
+            bail!("Text has {} carrier words where {} bytes take {}", k, self.total - self.offset, expected);
+        }
+        Ok(out)
+    }
+}
+
+fn token_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"\S+").expect("valid regex"))
+}
+
+/// What [`recover`] could vouch for.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Recovery {
+    /// Payload length the markers give
+    pub total: usize,
+    /// The payload from its first byte, as far as the markers vouch for it
+    pub bytes: Vec<u8>,
+}
+
+impl Recovery {
+    pub fn is_complete(&self) -> bool {
+        self.bytes.len() == self.total
+    }
+
+    /// Byte to send the rest of the payload from
+    pub fn resume_at(&self) -> usize {
+        self.bytes.len()
+    }
+
+    /// The payload, or [`GlossiaError::Truncated`] if part of it is missing.
+    pub fn into_bytes(mut self) -> Result<Vec<u8>> {
+        if !self.is_complete() {
+            let (recovered, total) = (self.bytes.len(), self.total);
+            crate::secret::Wipe::wipe(&mut self.bytes);
+            return Err(GlossiaError::Truncated { recovered, total }.into());
+        }
+        Ok(self.bytes)
+    }
+}
+
+/// `(k, total, offset)` of a marker token
+fn parse_marker(token: &str) -> Option<(usize, usize, usize)> {
+    let token = strip_markup(token);
+    let body = token.trim_start_matches('\\').strip_prefix('#')?.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+    let (counts, offset) = match body.split_once('@') {
+        Some((counts, offset)) => (counts, offset.parse().ok()?),
+        None => (body, 0),
+    };
+    let (k, total) = counts.split_once('/')?;
+    Some((k.parse().ok()?, total.parse().ok()?, offset))
+}
+
+/// Read the marked fragments of `text`, each up to its last marker whose
+/// count matches the carriers before it, and join them from byte 0. `every`
+/// is the spacing they were marked with.
+pub fn recover(text: &str, wordlist: &[String], every: usize) -> Result<Recovery> {
+    let index = WordIndex::shared(wordlist)?;
+    let mut total = None;
+    // (offset, carriers the markers vouch for)
+    let mut fragments: Vec<(usize, Vec<usize>)> = Vec::new();
+    let mut current: Option<(Fragment, Vec<usize>, usize)> = None;
+    for token in text.split_whitespace() {
+        if let Some((k, marker_total, offset)) = parse_marker(token) {
+            let total = *total.get_or_insert(marker_total);
+            if k == 0 && marker_total == total {
+                fragments.extend(current.take().map(|(fragment, carriers, verified)| (fragment.offset, carriers[..verified].to_vec())));
+                current = Some((Fragment::new(total, offset, every)?, Vec::new(), 0));
+                continue;
+            }
+            let Some((fragment, carriers, verified)) = &mut current else { continue };
+            let expected = fragment.carriers(wordlist.len());
+            let in_place = k == carriers.len() && marker_total == total && offset == fragment.offset;
+            if in_place && k != *verified + every && k != expected {
+                bail!("Resync marker #{} does not follow #{} by {} carrier words: decode with the --resync it was encoded with", k, verified, every);
+            }
+            if !in_place || k > expected {
+                // Words lost or added: nothing after the last good marker is vouched for
+                let (fragment, carriers, verified) = current.take().unwrap();
+                fragments.push((fragment.offset, carriers[..verified].to_vec()));
+                continue;
+            }
+            *verified = k;
+        } else if let Some((_, carriers, _)) = &mut current {
+            if let Some(i) = index.get(&normalize_token(token)) {
+                carriers.push(i);
+            }
+        }
+    }
+    fragments.extend(current.map(|(fragment, carriers, verified)| (fragment.offset, carriers[..verified].to_vec())));
+    let Some(total) = total else {
+        bail!("Text has no resync markers: it was not encoded with --resync");
+    };
+
+    let mut bytes = Vec::new();
+    loop {
+        let longest = fragments
+            .iter()
+            .filter(|(offset, _)| *offset <= bytes.len())
+            .map(|(offset, carriers)| {
+                let mut unpacked = unpack(carriers, wordlist.len());
+                unpacked.truncate(total - offset);
+                (*offset, unpacked)
+            })
+            .max_by_key(|(offset, unpacked)| offset + unpacked.len());
+        match longest {
+            Some((offset, unpacked)) if offset + unpacked.len() > bytes.len() => {
+                let start = bytes.len() - offset;
+                bytes.extend_from_slice(&unpacked[start..]);
+            }
+            _ => break,
+        }
+    }
+    Ok(Recovery { total, bytes })
+}
+
+/// The whole bytes a prefix of `encode_bytes`'s carriers holds
+fn unpack(indices: &[usize], wordlist_len: usize) -> Vec<u8> {
+    let Some(bits) = codec::bits_per_word(wordlist_len) else {
+        return indices.iter().map(|&i| i as u8).collect();
+    };
+    let mut out = Vec::with_capacity(indices.len() * bits / 8);
+    let (mut buffer, mut held) = (0u32, 0);
+    for &i in indices {
+        buffer = (buffer << bits) | i as u32;
+        held += bits;
+        while held >= 8 {
+            held -= 8;
+            out.push((buffer >> held) as u8);
+            buffer &= (1 << held) - 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+
+    fn marked(payload: &[u8], offset: usize) -> String {
+        let words = codec::encode_bytes(&payload[offset..], &alpha_wordlist()).unwrap();
+        let prose: Vec<String> = words.iter().map(|w| format!("the {} is", w)).collect();
+        Fragment::new(payload.len(), offset, 5).unwrap().mark(&prose.join(" "), &alpha_wordlist()).unwrap()
+    }
+
+    #[test]
+    fn test_marks_round_trip() {
+        let payload = b"The boat leaves at six from the north pier.";
+        let text = marked(payload, 0);
+        // 43 bytes take 32 words: markers at 0, after 5, 10, ..., 30 and after 32
+        assert_eq!(text.matches("#").count(), 8);
+        assert!(text.ends_with("#32/43 is"), "{}", text);
+        let recovery = recover(&text, &alpha_wordlist(), 5).unwrap();
+        assert!(recovery.is_complete());
+        assert_eq!(recovery.into_bytes().unwrap(), payload);
+
+        let resent = marked(payload, 20);
+        assert!(resent.starts_with("#0/43@20 the "));
+        assert_eq!(recover(&resent, &alpha_wordlist(), 5).unwrap().resume_at(), 0);
+        assert!(recover(&text, &alpha_wordlist(), 4).is_err());
+        assert!(recover("the boat is", &alpha_wordlist(), 5).is_err());
+        assert!(Fragment::new(43, 0, 5).unwrap().mark("the waaa is", &alpha_wordlist()).is_err());
+    }
+
+    #[test]
+    fn test_truncation_reports_and_resumes() {
+        let payload = b"The boat leaves at six from the north pier.";
+        let text = marked(payload, 0);
+        // Cut inside the fourth group of five carriers: 15 are vouched for
+        let cut = &text[..text.find("#20/43").unwrap() - 12];
+        let recovery = recover(cut, &alpha_wordlist(), 5).unwrap();
+        assert_eq!((recovery.total, recovery.resume_at()), (43, 15 * 11 / 8));
+        assert_eq!(recovery.bytes, payload[..20]);
+        let err = recovery.into_bytes().unwrap_err();
+        assert_eq!(err.downcast_ref::<GlossiaError>(), Some(&GlossiaError::Truncated { recovered: 20, total: 43 }));
+
+        // A dropped word breaks the count at the next marker
+        let dropped = text.replacen(&format!("the {} is", codec::encode_bytes(payload, &alpha_wordlist()).unwrap()[7]), "", 1);
+        assert_eq!(recover(&dropped, &alpha_wordlist(), 5).unwrap().resume_at(), 5 * 11 / 8);
+
+        // The retransmission from the reported byte completes it
+        let joined = format!("{}\n\n{}", cut, marked(payload, 20));
+        assert_eq!(recover(&joined, &alpha_wordlist(), 5).unwrap().into_bytes().unwrap(), payload);
+    }
+}
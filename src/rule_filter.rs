@@ -0,0 +1,168 @@
+//! Choosing which nlprule rules a [`GrammarChecker`](crate::GrammarChecker) applies.
+//!
+//! Code that regenerates a sentence until the checker accepts it can spin on
+//! rules that have nothing to do with grammar: style advice, typography, the
+//! Oxford comma. [`GrammarCheckerOptions`] keeps only some rule categories
+//! and turns off individual rules by id, using nlprule's selector syntax:
+//! `category`, `category/group` or `category/group/index`, matched without
+//! regard to case. [`GrammarChecker::rule_summary`](crate::GrammarChecker::rule_summary)
+//! reports what is left switched on.
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Which rules a grammar checker applies. The default applies all of them.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GrammarCheckerOptions {
+    /// Rule categories to apply (`grammar`, `typos`, ...); every category if unset
+    pub enabled_categories: Option<Vec<String>>,
+    /// Rules to turn off, as `category`, `category/group` or
+    /// `category/group/index` selectors
+    pub disabled_ids: Vec<String>,
+}
+
+/// The rules a grammar checker has switched on, for diagnostics.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RuleSummary {
+    /// Rules in the model
+    pub total: usize,
+    /// Rules switched on
+    pub enabled: usize,
+    /// Categories with at least one rule switched on, and how many, by name
+    pub categories: Vec<(String, usize)>,
+    /// The `disabled_ids` selectors last applied
+    pub disabled_ids: Vec<String>,
+}
+
+impl fmt::Display for RuleSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} grammar rules enabled", self.enabled, self.total)?;
+        if !self.categories.is_empty() {
+            let categories: Vec<String> = self.categories.iter().map(|(name, n)| format!("{} ({})", name, n)).collect();
+            write!(f, "; categories: {}", categories.join(", "))?;
+        }
+        if !self.disabled_ids.is_empty() {
+            write!(f, "; disabled: {}", self.disabled_ids.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "grammar")]
+mod checker {
+    use super::{GrammarCheckerOptions, RuleSummary};
+    use crate::GrammarChecker;
+    use anyhow::{bail, Result};
+    use nlprule::rule::id::{Category, Selector};
+    use std::collections::BTreeMap;
+
+    impl GrammarChecker {
+        /// Switch rules on and off as `options` say, starting from all rules
+        /// on. A category or id that matches no rule in the model is an
+        /// error, so a misspelling doesn't silently leave a rule in place.
+        pub fn set_options(&mut self, options: &GrammarCheckerOptions) -> Result<()> {
+            let rules = self.rules.rules_mut();
+            let matches_any = |selector: &Selector| rules.iter().any(|rule| selector.is_match(rule.id()));
+            let mut enabled = Vec::new();
+            for name in options.enabled_categories.iter().flatten() {
+                let selector = Selector::from(Category::new(name.as_str()));
+                if !matches_any(&selector) {
+                    bail!("No grammar rules in category '{}'", name);
+                }
+                enabled.push(selector);
+            }
+            let mut disabled = Vec::new();
+            for id in &options.disabled_ids {
+                let selector = Selector::try_from(id.as_str()).map_err(|e| anyhow::anyhow!("Invalid rule id '{}': {}", id, e))?;
+                if !matches_any(&selector) {
+                    bail!("No grammar rule matches '{}'", id);
+                }
+                disabled.push(selector);
+            }
+            for rule in rules.iter_mut() {
+                let id = rule.id();
+                let on = (options.enabled_categories.is_none() || enabled.iter().any(|s| s.is_match(id)))
+                    && !disabled.iter().any(|s| s.is_match(id));
+                if on {
+                    rule.enable();
+                } else {
+                    rule.disable();
+                }
+            }
+            self.options = options.clone();
+            Ok(())
+        }
+
+        /// [`GrammarChecker::set_options`], by value.
+        pub fn with_options(mut self, options: &GrammarCheckerOptions) -> Result<Self> {
+            self.set_options(options)?;
+            Ok(self)
+        }
+
+        /// The rule selection last applied; all rules if none was.
+        pub fn options(&self) -> &GrammarCheckerOptions {
+            &self.options
+        }
+
+        /// The rules switched on, per category.
+        pub fn rule_summary(&self) -> RuleSummary {
+            let rules = self.rules.rules();
+            let mut categories: BTreeMap<String, usize> = BTreeMap::new();
+            for rule in rules.iter().filter(|rule| rule.enabled()) {
+                *categories.entry(rule.id().parent().parent().to_string().to_lowercase()).or_default() += 1;
+            }
+            RuleSummary {
+                total: rules.len(),
+                enabled: categories.values().sum(),
+                categories: categories.into_iter().collect(),
+                disabled_ids: self.options.disabled_ids.clone(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_display() {
+        let summary = RuleSummary {
+            total: 10,
+            enabled: 7,
+            categories: vec![("grammar".into(), 5), ("typos".into(), 2)],
+            disabled_ids: vec!["typography/oxford_comma".into()],
+        };
+        assert_eq!(
+            summary.to_string(),
+            "7 of 10 grammar rules enabled; categories: grammar (5), typos (2); disabled: typography/oxford_comma"
+        );
+        assert_eq!(RuleSummary::default().to_string(), "0 of 0 grammar rules enabled");
+    }
+
+    #[cfg(feature = "grammar")]
+    #[test]
+    fn test_set_options() {
+        // Skip if the model files are not available
+        let Ok(mut checker) = crate::GrammarChecker::from_language(crate::Language::English) else {
+            return;
+        };
+        let all = checker.rule_summary();
+        assert_eq!(all.enabled, all.total);
+
+        let options = GrammarCheckerOptions { enabled_categories: Some(vec!["GRAMMAR".into()]), disabled_ids: vec![] };
+        checker.set_options(&options).unwrap();
+        let summary = checker.rule_summary();
+        assert_eq!(summary.categories.len(), 1, "{}", summary);
+        assert_eq!(summary.categories[0].0, "grammar");
+
+        let bad = GrammarCheckerOptions { enabled_categories: None, disabled_ids: vec!["no_such_category".into()] };
+        assert!(checker.set_options(&bad).is_err());
+        // A failed call changes nothing
+        assert_eq!(checker.rule_summary(), summary);
+
+        checker.set_options(&GrammarCheckerOptions::default()).unwrap();
+        assert_eq!(checker.rule_summary(), all);
+    }
+}
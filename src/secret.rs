@@ -0,0 +1,182 @@
+//! Wiping sensitive buffers.
+//!
+//! Payload bytes, keys and intermediate word indices pass through several
+//! temporary allocations while encoding and decoding. [`Wipe`] clears them
+//! before they are freed, using the `zeroize` crate when the (default-on)
+//! `zeroize` feature is enabled and volatile writes otherwise, and
+//! [`SecretPayload`] holds a plaintext that is wiped when dropped.
+
+use std::fmt;
+
+/// Overwrite a buffer with zeros in a way the compiler won't optimize out.
+/// Growable buffers are also emptied.
+pub trait Wipe {
+    fn wipe(&mut self);
+}
+
+#[cfg(feature = "zeroize")]
+mod imp {
+    use super::Wipe;
+    use zeroize::Zeroize;
+
+    impl Wipe for Vec<u8> {
+        fn wipe(&mut self) {
+            self.zeroize();
+        }
+    }
+
+    impl Wipe for Vec<usize> {
+        fn wipe(&mut self) {
+            self.zeroize();
+        }
+    }
+
+    impl Wipe for String {
+        fn wipe(&mut self) {
+            self.zeroize();
+        }
+    }
+
+    impl Wipe for [u8; 32] {
+        fn wipe(&mut self) {
+            self.zeroize();
+        }
+    }
+
+    impl Wipe for [u8] {
+        fn wipe(&mut self) {
+            self.zeroize();
+        }
+    }
+}
+
+#[cfg(not(feature = "zeroize"))]
+mod imp {
+    use super::Wipe;
+    use std::sync::atomic::{compiler_fence, Ordering};
+
+    fn volatile_zero<T: Copy + Default>(values: &mut [T]) {
+        for v in values.iter_mut() {
+            // SAFETY: `v` is a valid, aligned, exclusive reference.
+            unsafe { std::ptr::write_volatile(v, T::default()) };
+        }
+        compiler_fence(Ordering::SeqCst);
+    }
+
+    impl Wipe for Vec<u8> {
+        fn wipe(&mut self) {
+            volatile_zero(self.as_mut_slice());
+            self.clear();
+        }
+    }
+
+    impl Wipe for Vec<usize> {
+        fn wipe(&mut self) {
+            volatile_zero(self.as_mut_slice());
+            self.clear();
+        }
+    }
+
+    impl Wipe for String {
+        fn wipe(&mut self) {
+            // SAFETY: all-zero bytes are valid UTF-8, and the string is emptied after.
+            volatile_zero(unsafe { self.as_mut_vec() }.as_mut_slice());
+            self.clear();
+        }
+    }
+
+    impl Wipe for [u8; 32] {
+        fn wipe(&mut self) {
+            volatile_zero(self);
+        }
+    }
+
+    impl Wipe for [u8] {
+        fn wipe(&mut self) {
+            volatile_zero(self);
+        }
+    }
+}
+
+impl Wipe for Vec<String> {
+    fn wipe(&mut self) {
+        for s in self.iter_mut() {
+            s.wipe();
+        }
+        self.clear();
+    }
+}
+
+/// A plaintext payload that is wiped when dropped. `Debug` doesn't print it.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct SecretPayload(Vec<u8>);
+
+impl SecretPayload {
+    pub fn new(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// The payload bytes.
+    pub fn expose(&self) -> &[u8] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl From<Vec<u8>> for SecretPayload {
+    fn from(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+}
+
+impl From<String> for SecretPayload {
+    fn from(text: String) -> Self {
+        Self(text.into_bytes())
+    }
+}
+
+impl Drop for SecretPayload {
+    fn drop(&mut self) {
+        self.0.wipe();
+    }
+}
+
+impl fmt::Debug for SecretPayload {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SecretPayload({} bytes)", self.0.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wipe_clears_buffers() {
+        let mut bytes = b"secret".to_vec();
+        bytes.wipe();
+        assert!(bytes.is_empty());
+
+        let mut words = vec!["abandon".to_string(), "ability".to_string()];
+        words.wipe();
+        assert!(words.is_empty());
+
+        let mut key = [7u8; 32];
+        key.wipe();
+        assert_eq!(key, [0u8; 32]);
+    }
+
+    #[test]
+    fn test_secret_payload_is_redacted() {
+        let secret = SecretPayload::from("Meet at noon".to_string());
+        assert_eq!(secret.expose(), b"Meet at noon");
+        assert_eq!(format!("{:?}", secret), "SecretPayload(12 bytes)");
+    }
+}
@@ -0,0 +1,117 @@
+//! Word segmentation for wordlists written without spaces.
+//!
+//! Chinese and Japanese text has no spaces between words, so the BIP39
+//! Chinese lists (single characters) and Japanese list (kana words) cannot be
+//! found by splitting on whitespace. For a wordlist written entirely in those
+//! scripts the decoders [`segment`] the text instead: at each character they
+//! take the longest wordlist entry starting there, or else move on by one.
+//!
+//! As with spaced wordlists, cover text must not contain wordlist entries,
+//! here not even inside longer words.
+
+/// Whether `c` is a Han ideograph, hiragana or katakana
+pub fn is_unspaced_char(c: char) -> bool {
+    matches!(c,
+        '\u{3005}'                    // 々, the ideographic iteration mark
+        | '\u{3040}'..='\u{30FF}'     // hiragana, katakana
+        | '\u{31F0}'..='\u{31FF}'     // katakana phonetic extensions
+        | '\u{3400}'..='\u{4DBF}'     // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}'     // CJK unified ideographs
+        | '\u{F900}'..='\u{FAFF}'     // CJK compatibility ideographs
+        | '\u{20000}'..='\u{2FA1F}')  // CJK extensions B onward
+}
+
+/// Whether `c` is an unspaced character or CJK punctuation (`。`, `，`)
+fn is_unspaced_text(c: char) -> bool {
+    is_unspaced_char(c) || matches!(c, '\u{3000}'..='\u{303F}' | '\u{FF00}'..='\u{FFEF}')
+}
+
+/// Whether every word of a (non-empty) wordlist is written in unspaced characters
+pub fn is_unspaced_wordlist<S: AsRef<str>>(words: impl IntoIterator<Item = S>) -> bool {
+    let mut words = words.into_iter().peekable();
+    words.peek().is_some() && words.all(|w| !w.as_ref().is_empty() && w.as_ref().chars().all(is_unspaced_char))
+}
+
+/// The wordlist entries in `text`, in order, matched longest first. `contains`
+/// tells whether a string is an entry; no entry is longer than `max_chars`.
+pub fn segment(text: &str, max_chars: usize, contains: impl Fn(&str) -> bool) -> Vec<&str> {
+    let bounds: Vec<usize> = text.char_indices().map(|(i, _)| i).chain(std::iter::once(text.len())).collect();
+    let chars = bounds.len() - 1;
+    let mut found = Vec::new();
+    let mut at = 0;
+    while at < chars {
+        let longest = text[bounds[at]..]
+            .starts_with(is_unspaced_char)
+            .then(|| (1..=max_chars.min(chars - at)).rev().find(|&n| contains(&text[bounds[at]..bounds[at + n]])))
+            .flatten();
+        match longest {
+            Some(n) => {
+                found.push(&text[bounds[at]..bounds[at + n]]);
+                at += n;
+            }
+            None => at += 1,
+        }
+    }
+    found
+}
+
+/// Close up the spaces a word-by-word generator leaves between unspaced
+/// characters, and turn ASCII punctuation after them into its full-width form
+/// (`字 字.` becomes `字字。`). Line breaks are kept.
+pub fn join_unspaced(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, c) in text.char_indices() {
+        let after_unspaced = out.chars().next_back().is_some_and(is_unspaced_text);
+        if c.is_whitespace() && c != '\n' && after_unspaced {
+            let next = text[i..].trim_start_matches(|c: char| c.is_whitespace() && c != '\n');
+            if next.starts_with(is_unspaced_text) {
+                continue;
+            }
+        }
+        let c = match (after_unspaced, c) {
+            (true, '.') => '。',
+            (true, ',') => '，',
+            (true, '!') => '！',
+            (true, '?') => '？',
+            (true, ';') => '；',
+            (true, ':') => '：',
+            _ => c,
+        };
+        out.push(c);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    fn set(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_segment_takes_longest_match() {
+        // Single characters, as in the Chinese lists
+        let chinese = set(&["的", "一", "是", "在"]);
+        assert!(is_unspaced_wordlist(&chinese));
+        let text = "我们在公园里，一起看的是花。";
+        assert_eq!(segment(text, 1, |w| chinese.contains(w)), vec!["在", "一", "的", "是"]);
+
+        // Kana words: "あいこくしん" must not split into "あい" + "こくしん"
+        let japanese = set(&["あいこくしん", "あい", "こくしん", "あおぞら"]);
+        let text = "今日はあいこくしんとあおぞらの話 (and some English).";
+        assert_eq!(segment(text, 6, |w| japanese.contains(w)), vec!["あいこくしん", "あおぞら"]);
+
+        assert!(!is_unspaced_wordlist(set(&["abandon", "的"])));
+        assert!(!is_unspaced_wordlist(Vec::<String>::new()));
+    }
+
+    #[test]
+    fn test_join_unspaced() {
+        assert_eq!(join_unspaced("我们 在 公园 里, 一起 看.\n\n好 吗?"), "我们在公园里，一起看。\n\n好吗？");
+        // Spaces next to other scripts stay
+        assert_eq!(join_unspaced("glossia 是 a tool. 的 Zoo"), "glossia 是 a tool. 的 Zoo");
+    }
+}
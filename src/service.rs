@@ -0,0 +1,656 @@
+//! Shared core of the network services (feature `service`).
+//!
+//! Every server front end (currently the gRPC one, `glossia_grpc`) is a thin
+//! protocol adapter over a [`Service`]: it owns the loaded wordlist, applies
+//! the configured default framing, and enforces the limits, so the protocols
+//! cannot drift apart. [`ServiceConfig`] is read from a YAML file and/or
+//! `GLOSSIA_*` environment variables, the same way for every front end.
+//!
+//! A request over a limit fails with a [`ServiceError`], which front ends
+//! recover with `err.downcast_ref::<ServiceError>()` and turn into their own
+//! "too large" (413), "too many requests" (429) or "forbidden" (403) answer.
+//!
+//! The wordlist (a `payload.yaml`, which also holds the POS weights) can be
+//! edited while the service runs: [`Service::reload`] reads it again and, if
+//! it parses and changed, swaps it in whole as the next version. A request
+//! runs against the version current when it started, and a file that fails
+//! to load leaves the running one in place. [`Service::active`] reports the
+//! version, for front ends to serve. A reload asked for over the network goes
+//! through [`Service::reload_for`], which wants the configured
+//! [`ServiceConfig::reload_token`], or a loopback client when none is set. The services encode to carrier words
+//! and never load a grammar, so there is none to reload.
+//!
+//! Every request is also reported as a `tracing` event on [`AUDIT_TARGET`]:
+//! the operation, its framing mode, the payload size, how long it took and
+//! the outcome as a stable code. Payloads, carrier text, passphrases and error
+//! messages (which can quote carrier words) are never recorded. Front ends
+//! install a subscriber for these when [`ServiceConfig::audit_log`] is set.
+
+use crate::error::GlossiaError;
+use crate::payload::{self, Detection, PayloadOptions};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Payloads larger than this are refused unless configured otherwise
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 1 << 20;
+
+/// Longest carrier output produced unless configured otherwise
+pub const DEFAULT_MAX_OUTPUT_WORDS: usize = 1 << 16;
+
+/// Requests a client address may make per minute unless configured otherwise
+pub const DEFAULT_RATE_LIMIT_PER_MINUTE: u32 = 600;
+
+/// `tracing` target of the audit events
+pub const AUDIT_TARGET: &str = "glossia::audit";
+
+/// Client addresses the rate limiter tracks at most; past it, idle ones are
+/// forgotten first, then the longest unseen
+const MAX_TRACKED_CLIENTS: usize = 10_000;
+
+/// A request refused by the service's limits.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServiceError {
+    /// The request or its result is larger than `limit` (in `unit`s)
+    TooLarge { size: usize, limit: usize, unit: &'static str },
+    /// The client has used up its requests; it may try again after `retry_after`
+    RateLimited { retry_after: Duration },
+    /// The client may not reload the wordlist: wrong or missing reload token,
+    /// or a remote address when no token is configured
+    Forbidden,
+}
+
+impl ServiceError {
+    /// Short stable identifier for front ends to report.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ServiceError::TooLarge { .. } => "too_large",
+            ServiceError::RateLimited { .. } => "rate_limited",
+            ServiceError::Forbidden => "forbidden",
+        }
+    }
+
+    /// The HTTP status of the refusal: 413, 429 or 403.
+    pub fn http_status(&self) -> u16 {
+        match self {
+            ServiceError::TooLarge { .. } => 413,
+            ServiceError::RateLimited { .. } => 429,
+            ServiceError::Forbidden => 403,
+        }
+    }
+}
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ServiceError::TooLarge { size, limit, unit } => {
+                write!(f, "Request needs {} {}, over the limit of {}", size, unit, limit)
+            }
+            ServiceError::RateLimited { retry_after } => {
+                write!(f, "Too many requests; retry in {:.1} seconds", retry_after.as_secs_f64())
+            }
+            ServiceError::Forbidden => write!(f, "Not allowed to reload the wordlist"),
+        }
+    }
+}
+
+impl std::error::Error for ServiceError {}
+
+/// Settings shared by all service front ends.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServiceConfig {
+    /// Address to listen on
+    pub listen: SocketAddr,
+    /// `payload.yaml` to load the wordlist from
+    pub wordlist: PathBuf,
+    /// Framing used when a request doesn't specify its own
+    pub defaults: PayloadOptions,
+    /// Largest payload (encode) or decoded payload accepted, in bytes
+    pub max_payload_bytes: usize,
+    /// Most carrier words an encode may produce
+    pub max_output_words: usize,
+    /// Requests each client address may make per minute, in bursts of up to
+    /// as many; 0 turns the limit off
+    pub rate_limit_per_minute: u32,
+    /// Write the audit events to stdout as JSON lines
+    pub audit_log: bool,
+    /// Secret a network client must present to reload the wordlist; without
+    /// one, only loopback clients may
+    pub reload_token: Option<String>,
+}
+
+impl Default for ServiceConfig {
+    fn default() -> Self {
+        Self {
+            listen: SocketAddr::from(([127, 0, 0, 1], 50051)),
+            wordlist: PathBuf::from("languages/english/payload.yaml"),
+            defaults: PayloadOptions::default(),
+            max_payload_bytes: DEFAULT_MAX_PAYLOAD_BYTES,
+            max_output_words: DEFAULT_MAX_OUTPUT_WORDS,
+            rate_limit_per_minute: DEFAULT_RATE_LIMIT_PER_MINUTE,
+            audit_log: false,
+            reload_token: None,
+        }
+    }
+}
+
+impl ServiceConfig {
+    /// Read a YAML config file; missing keys keep their defaults.
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+        serde_yaml::from_str(&text).with_context(|| format!("Invalid service config {}", path.display()))
+    }
+
+    /// Override fields from `GLOSSIA_LISTEN`, `GLOSSIA_WORDLIST`,
+    /// `GLOSSIA_MAX_PAYLOAD_BYTES`, `GLOSSIA_MAX_OUTPUT_WORDS`,
+    /// `GLOSSIA_RATE_LIMIT_PER_MINUTE`, `GLOSSIA_AUDIT_LOG` and
+    /// `GLOSSIA_RELOAD_TOKEN` when they are set.
+    pub fn with_env(mut self) -> Result<Self> {
+        if let Ok(listen) = std::env::var("GLOSSIA_LISTEN") {
+            self.listen = listen.parse().with_context(|| format!("Invalid GLOSSIA_LISTEN: {}", listen))?;
+        }
+        if let Ok(wordlist) = std::env::var("GLOSSIA_WORDLIST") {
+            self.wordlist = PathBuf::from(wordlist);
+        }
+        if let Ok(max) = std::env::var("GLOSSIA_MAX_PAYLOAD_BYTES") {
+            self.max_payload_bytes = max
+                .parse()
+                .with_context(|| format!("Invalid GLOSSIA_MAX_PAYLOAD_BYTES: {}", max))?;
+        }
+        if let Ok(max) = std::env::var("GLOSSIA_MAX_OUTPUT_WORDS") {
+            self.max_output_words = max
+                .parse()
+                .with_context(|| format!("Invalid GLOSSIA_MAX_OUTPUT_WORDS: {}", max))?;
+        }
+        if let Ok(rate) = std::env::var("GLOSSIA_RATE_LIMIT_PER_MINUTE") {
+            self.rate_limit_per_minute = rate
+                .parse()
+                .with_context(|| format!("Invalid GLOSSIA_RATE_LIMIT_PER_MINUTE: {}", rate))?;
+        }
+        if let Ok(audit) = std::env::var("GLOSSIA_AUDIT_LOG") {
+            self.audit_log = audit.parse().with_context(|| format!("Invalid GLOSSIA_AUDIT_LOG: {}", audit))?;
+        }
+        if let Ok(token) = std::env::var("GLOSSIA_RELOAD_TOKEN") {
+            self.reload_token = Some(token);
+        }
+        Ok(self)
+    }
+}
+
+/// A token bucket per client address: each holds up to `per_minute`
+/// requests and refills at `per_minute` a minute.
+struct RateLimiter {
+    per_minute: u32,
+    buckets: HashMap<IpAddr, (f64, Instant)>,
+}
+
+impl RateLimiter {
+    fn new(per_minute: u32) -> Self {
+        Self { per_minute, buckets: HashMap::new() }
+    }
+
+    fn admit(&mut self, client: IpAddr, now: Instant) -> Result<(), ServiceError> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        let capacity = f64::from(self.per_minute);
+        let per_second = capacity / 60.0;
+        if self.buckets.len() >= MAX_TRACKED_CLIENTS && !self.buckets.contains_key(&client) {
+            // A bucket idle for a minute is full again, the same as a new one
+            self.buckets.retain(|_, (_, last)| now.saturating_duration_since(*last) < Duration::from_secs(60));
+            // Still full: every client was seen this minute, so make room by
+            // forgetting the one seen longest ago
+            if self.buckets.len() >= MAX_TRACKED_CLIENTS {
+                if let Some(stalest) = self.buckets.iter().min_by_key(|(_, (_, last))| *last).map(|(ip, _)| *ip) {
+                    self.buckets.remove(&stalest);
+                }
+            }
+        }
+        let (tokens, last) = self.buckets.entry(client).or_insert((capacity, now));
+        *tokens = (*tokens + now.saturating_duration_since(*last).as_secs_f64() * per_second).min(capacity);
+        *last = now;
+        if *tokens < 1.0 {
+            let retry_after = Duration::from_secs_f64((1.0 - *tokens) / per_second);
+            return Err(ServiceError::RateLimited { retry_after });
+        }
+        *tokens -= 1.0;
+        Ok(())
+    }
+}
+
+/// Report a finished request on [`AUDIT_TARGET`]. Only sizes, flags and
+/// stable codes go in the event, never contents or messages.
+fn audit<T>(operation: &str, options: Option<&PayloadOptions>, payload_bytes: Option<usize>, started: Instant, result: &Result<T>) {
+    let outcome = match result {
+        Ok(_) => "ok",
+        Err(err) => outcome(err),
+    };
+    tracing::info!(
+        target: AUDIT_TARGET,
+        operation,
+        mode = options.map(mode).as_deref(),
+        payload_bytes = payload_bytes.map(|n| n as u64),
+        duration_us = started.elapsed().as_micros() as u64,
+        outcome,
+    );
+}
+
+/// The stable code of a failure: a [`ServiceError`] or [`GlossiaError`]
+/// code, or `error` for anything else.
+fn outcome(err: &anyhow::Error) -> &'static str {
+    if let Some(refusal) = err.downcast_ref::<ServiceError>() {
+        refusal.code()
+    } else if let Some(err) = err.downcast_ref::<GlossiaError>() {
+        err.code()
+    } else {
+        "error"
+    }
+}
+
+/// The framing steps of `options` joined by `+`, or `plain`.
+fn mode(options: &PayloadOptions) -> String {
+    let steps = [("checksum", options.checksum), ("whiten", options.whiten), ("commit", options.whiten && options.commit)];
+    let mode: Vec<&str> = steps.iter().filter(|(_, on)| *on).map(|(step, _)| *step).collect();
+    if mode.is_empty() { "plain".to_string() } else { mode.join("+") }
+}
+
+/// The version of the loaded files a [`Service`] is answering with.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct ActiveConfig {
+    /// 1 at startup, and one more for each reload that changed the wordlist
+    pub version: u64,
+    /// The `payload.yaml` the wordlist and weights came from
+    pub wordlist: PathBuf,
+    /// SHA-256 of that file, in hex (of the words, one per line, for a
+    /// service built over an already loaded wordlist)
+    pub wordlist_sha256: String,
+    /// Words in the wordlist
+    pub words: usize,
+    /// When this version was loaded, in seconds since the Unix epoch
+    pub loaded_at: u64,
+}
+
+/// One version of the wordlist, never changed once shared.
+struct Loaded {
+    version: u64,
+    wordlist: Vec<String>,
+    sha256: String,
+    loaded_at: SystemTime,
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes).iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Compare two secrets in time independent of where they differ (or of
+/// their lengths, by comparing digests).
+fn same_secret(a: &str, b: &str) -> bool {
+    let (a, b) = (Sha256::digest(a.as_bytes()), Sha256::digest(b.as_bytes()));
+    a.iter().zip(b.iter()).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+/// The wordlist in `path` and the digest of the file.
+fn read_wordlist(path: &Path) -> Result<(Vec<String>, String)> {
+    let yaml = std::fs::read_to_string(path).with_context(|| format!("Failed to read wordlist {}", path.display()))?;
+    let wordlist = payload::wordlist_from_yaml(&yaml)?;
+    Ok((wordlist, sha256_hex(yaml.as_bytes())))
+}
+
+/// A loaded wordlist plus the configured policy, shared across requests.
+pub struct Service {
+    config: ServiceConfig,
+    loaded: RwLock<Arc<Loaded>>,
+    limiter: Mutex<RateLimiter>,
+}
+
+impl Service {
+    pub fn new(config: ServiceConfig) -> Result<Self> {
+        let (wordlist, sha256) = read_wordlist(&config.wordlist)?;
+        Ok(Self::with_loaded(config, wordlist, sha256))
+    }
+
+    /// A service over an already loaded wordlist (canonical sorted order).
+    pub fn with_wordlist(config: ServiceConfig, wordlist: Vec<String>) -> Self {
+        let sha256 = sha256_hex(wordlist.join("\n").as_bytes());
+        Self::with_loaded(config, wordlist, sha256)
+    }
+
+    fn with_loaded(config: ServiceConfig, wordlist: Vec<String>, sha256: String) -> Self {
+        let loaded = RwLock::new(Arc::new(Loaded { version: 1, wordlist, sha256, loaded_at: SystemTime::now() }));
+        let limiter = Mutex::new(RateLimiter::new(config.rate_limit_per_minute));
+        Self { config, loaded, limiter }
+    }
+
+    pub fn config(&self) -> &ServiceConfig {
+        &self.config
+    }
+
+    /// The current version; requests hold on to it until they finish.
+    fn loaded(&self) -> Arc<Loaded> {
+        Arc::clone(&self.loaded.read().unwrap_or_else(|poisoned| poisoned.into_inner()))
+    }
+
+    /// The version of the wordlist requests are answered with.
+    pub fn active(&self) -> ActiveConfig {
+        let loaded = self.loaded();
+        ActiveConfig {
+            version: loaded.version,
+            wordlist: self.config.wordlist.clone(),
+            wordlist_sha256: loaded.sha256.clone(),
+            words: loaded.wordlist.len(),
+            loaded_at: loaded.loaded_at.duration_since(UNIX_EPOCH).map_or(0, |age| age.as_secs()),
+        }
+    }
+
+    /// Read the configured wordlist again and, if it changed, answer later
+    /// requests with it as the next version. A file that fails to read or
+    /// parse is an error and leaves the current version in place. Texts
+    /// encoded with the old wordlist may not decode with the new one.
+    pub fn reload(&self) -> Result<ActiveConfig> {
+        let started = Instant::now();
+        let reloaded = self.reload_wordlist();
+        audit("reload", None, None, started, &reloaded);
+        reloaded
+    }
+
+    /// [`Service::reload`] asked for by `client` over the network, presenting
+    /// `token`. Fails with [`ServiceError::Forbidden`] unless `token` matches
+    /// [`ServiceConfig::reload_token`] or, when no token is configured,
+    /// `client` is a loopback address.
+    pub fn reload_for(&self, client: IpAddr, token: Option<&str>) -> Result<ActiveConfig> {
+        let started = Instant::now();
+        let reloaded = self.authorize_reload(client, token).and_then(|()| self.reload_wordlist());
+        audit("reload", None, None, started, &reloaded);
+        reloaded
+    }
+
+    fn authorize_reload(&self, client: IpAddr, token: Option<&str>) -> Result<()> {
+        let allowed = match self.config.reload_token.as_deref().filter(|expected| !expected.is_empty()) {
+            Some(expected) => token.is_some_and(|token| same_secret(token, expected)),
+            None => client.is_loopback(),
+        };
+        if !allowed {
+            bail!(ServiceError::Forbidden);
+        }
+        Ok(())
+    }
+
+    fn reload_wordlist(&self) -> Result<ActiveConfig> {
+        let (wordlist, sha256) = read_wordlist(&self.config.wordlist)?;
+        {
+            let mut loaded = self.loaded.write().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if loaded.sha256 != sha256 {
+                let version = loaded.version + 1;
+                *loaded = Arc::new(Loaded { version, wordlist, sha256, loaded_at: SystemTime::now() });
+            }
+        }
+        Ok(self.active())
+    }
+
+    fn options<'a>(&'a self, requested: Option<&'a PayloadOptions>) -> &'a PayloadOptions {
+        requested.unwrap_or(&self.config.defaults)
+    }
+
+    /// Count a request from `client` against its rate limit; fails with
+    /// [`ServiceError::RateLimited`] once the client has used it up.
+    pub fn admit(&self, client: IpAddr) -> Result<()> {
+        self.admit_at(client, Instant::now())
+    }
+
+    fn admit_at(&self, client: IpAddr, now: Instant) -> Result<()> {
+        let mut limiter = self.limiter.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let admitted = limiter.admit(client, now).map_err(anyhow::Error::from);
+        if admitted.is_err() {
+            audit("admit", None, None, now, &admitted);
+        }
+        admitted
+    }
+
+    /// Carrier words for `bytes`, framed by `options` or the configured defaults.
+    pub fn encode(&self, bytes: &[u8], options: Option<&PayloadOptions>) -> Result<Vec<String>> {
+        let started = Instant::now();
+        let options = self.options(options);
+        let words = self.encode_within_limits(bytes, options);
+        audit("encode", Some(options), Some(bytes.len()), started, &words);
+        words
+    }
+
+    fn encode_within_limits(&self, bytes: &[u8], options: &PayloadOptions) -> Result<Vec<String>> {
+        let limit = self.config.max_payload_bytes;
+        if bytes.len() > limit {
+            bail!(ServiceError::TooLarge { size: bytes.len(), limit, unit: "payload bytes" });
+        }
+        let loaded = self.loaded();
+        let words = payload::carrier_words(bytes.len(), loaded.wordlist.len(), options);
+        if words > self.config.max_output_words {
+            bail!(ServiceError::TooLarge { size: words, limit: self.config.max_output_words, unit: "output words" });
+        }
+        payload::encode_payload(&mut rand::thread_rng(), bytes, &loaded.wordlist, options)
+    }
+
+    /// The payload carried by `text`.
+    pub fn decode(&self, text: &str, options: Option<&PayloadOptions>) -> Result<Vec<u8>> {
+        let started = Instant::now();
+        let options = self.options(options);
+        let bytes = self.decode_within_limits(text, options);
+        audit("decode", Some(options), bytes.as_ref().ok().map(Vec::len), started, &bytes);
+        bytes
+    }
+
+    fn decode_within_limits(&self, text: &str, options: &PayloadOptions) -> Result<Vec<u8>> {
+        let loaded = self.loaded();
+        let carriers = payload::detect(text, &loaded.wordlist).carrier_words;
+        let limit = payload::carrier_words(self.config.max_payload_bytes, loaded.wordlist.len(), options);
+        if carriers > limit {
+            bail!(ServiceError::TooLarge { size: carriers, limit, unit: "carrier words" });
+        }
+        payload::decode_payload(text, &loaded.wordlist, options)
+    }
+
+    pub fn detect(&self, text: &str) -> Detection {
+        let started = Instant::now();
+        let found = payload::detect(text, &self.loaded().wordlist);
+        audit("detect", None, None, started, &Ok(()));
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+
+    fn refusal(err: anyhow::Error) -> ServiceError {
+        err.downcast::<ServiceError>().expect("a service refusal")
+    }
+
+    fn service(max_payload_bytes: usize) -> Service {
+        let config = ServiceConfig {
+            max_payload_bytes,
+            defaults: PayloadOptions { checksum: true, ..Default::default() },
+            ..Default::default()
+        };
+        Service::with_wordlist(config, alpha_wordlist())
+    }
+
+    #[test]
+    fn test_defaults_and_limits() {
+        let service = service(16);
+        let words = service.encode(b"hello", None).unwrap();
+        assert!(service.detect(&words.join(" ")).checksum_valid);
+        assert_eq!(service.decode(&words.join(" "), None).unwrap(), b"hello");
+
+        let plain = PayloadOptions::default();
+        let words = service.encode(b"hello", Some(&plain)).unwrap();
+        assert_eq!(service.decode(&words.join(" "), Some(&plain)).unwrap(), b"hello");
+
+        let err = refusal(service.encode(&[1u8; 17], None).unwrap_err());
+        assert_eq!((err.code(), err.http_status()), ("too_large", 413));
+        let big = service.encode(&[1u8; 16], Some(&plain)).unwrap();
+        let doubled = format!("{} {}", big.join(" "), big.join(" "));
+        assert_eq!(refusal(service.decode(&doubled, Some(&plain)).unwrap_err()).http_status(), 413);
+    }
+
+    #[test]
+    fn test_output_words_and_rate_limits() {
+        let mut service = service(1024);
+        service.config.max_output_words = 12;
+        // 11 bits a word: 16 bytes need 12 words, plus a checksum word
+        assert!(service.encode(&[1u8; 16], Some(&PayloadOptions::default())).is_ok());
+        let err = refusal(service.encode(&[1u8; 16], None).unwrap_err());
+        assert_eq!(err, ServiceError::TooLarge { size: 13, limit: 12, unit: "output words" });
+
+        service.limiter = Mutex::new(RateLimiter::new(60));
+        let (alice, bob) = (IpAddr::from([10, 0, 0, 1]), IpAddr::from([10, 0, 0, 2]));
+        let start = Instant::now();
+        for _ in 0..60 {
+            service.admit_at(alice, start).unwrap();
+        }
+        let err = refusal(service.admit_at(alice, start).unwrap_err());
+        assert_eq!(err, ServiceError::RateLimited { retry_after: Duration::from_secs(1) });
+        assert_eq!(err.http_status(), 429);
+        service.admit_at(bob, start).unwrap();
+        // One request a second comes back
+        service.admit_at(alice, start + Duration::from_secs(1)).unwrap();
+        assert!(service.admit_at(alice, start + Duration::from_secs(1)).is_err());
+
+        service.limiter = Mutex::new(RateLimiter::new(0));
+        for _ in 0..1000 {
+            service.admit_at(alice, start).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_rate_limiter_tracks_at_most_max_clients() {
+        let mut limiter = RateLimiter::new(60);
+        let start = Instant::now();
+        let client = |i: u32| IpAddr::from((0x0a00_0000 + i).to_be_bytes());
+        for i in 0..MAX_TRACKED_CLIENTS as u32 {
+            limiter.admit(client(i), start + Duration::from_micros(u64::from(i))).unwrap();
+        }
+        // Every client is recent, so new ones push out the longest unseen
+        for i in 0..5 {
+            limiter.admit(client(MAX_TRACKED_CLIENTS as u32 + i), start + Duration::from_secs(2)).unwrap();
+            assert_eq!(limiter.buckets.len(), MAX_TRACKED_CLIENTS);
+        }
+        assert!((0..5).all(|i| !limiter.buckets.contains_key(&client(i))));
+        assert!(limiter.buckets.contains_key(&client(5)));
+    }
+
+    #[test]
+    fn test_remote_reload_needs_token_or_loopback() {
+        let (local, remote) = (IpAddr::from([127, 0, 0, 1]), IpAddr::from([10, 0, 0, 1]));
+        let mut service = service(64);
+        service.config.wordlist = PathBuf::from("missing/payload.yaml");
+        assert_eq!(refusal(service.reload_for(remote, None).unwrap_err()), ServiceError::Forbidden);
+        assert_eq!(ServiceError::Forbidden.http_status(), 403);
+        // Past the check, the missing wordlist file fails the reload itself
+        let err = service.reload_for(local, None).unwrap_err();
+        assert!(err.downcast_ref::<ServiceError>().is_none(), "{}", err);
+
+        service.config.reload_token = Some("s3cret".to_string());
+        for (client, token) in [(local, None), (remote, None), (remote, Some("s3cre")), (local, Some("wrong"))] {
+            assert_eq!(refusal(service.reload_for(client, token).unwrap_err()), ServiceError::Forbidden);
+        }
+        assert!(service.reload_for(remote, Some("s3cret")).unwrap_err().downcast_ref::<ServiceError>().is_none());
+    }
+
+    /// Everything written to it, shared with the test
+    #[derive(Clone, Default)]
+    struct Captured(std::sync::Arc<Mutex<Vec<u8>>>);
+
+    impl std::io::Write for Captured {
+        fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_audit_log_never_records_contents() {
+        let service = service(64);
+        let secret = PayloadOptions {
+            checksum: true,
+            whiten: true,
+            passphrase: Some("correct horse battery staple".to_string()),
+            commit: true,
+        };
+        let log = Captured::default();
+        let writer = log.clone();
+        let subscriber = tracing_subscriber::fmt().json().with_writer(move || writer.clone()).finish();
+        let (words, message) = tracing::subscriber::with_default(subscriber, || {
+            let words = service.encode(b"nsec1-hunter2-garden", Some(&secret)).unwrap();
+            assert!(service.decode(&words.join(" "), Some(&secret)).is_ok());
+            // The checksum error quotes the carrier text's checksum word
+            let mut broken = words.clone();
+            broken.swap(0, 1);
+            let err = service.decode(&broken.join(" "), Some(&secret)).unwrap_err();
+            assert!(err.to_string().contains(words.last().unwrap()), "{}", err);
+            assert!(service.decode("wbbb wccc", Some(&PayloadOptions { passphrase: Some("wrong".into()), ..secret.clone() })).is_err());
+            assert!(service.encode(&[7u8; 65], None).is_err());
+            (words, err.to_string())
+        });
+
+        let log = String::from_utf8(log.0.lock().unwrap().clone()).unwrap();
+        let events: Vec<serde_json::Value> = log.lines().map(|line| serde_json::from_str(line).unwrap()).collect();
+        let outcomes: Vec<&str> = events.iter().map(|event| event["fields"]["outcome"].as_str().unwrap()).collect();
+        assert_eq!(outcomes, ["ok", "ok", "checksum", "checksum", "too_large"]);
+        assert_eq!(events[0]["fields"]["mode"], "checksum+whiten+commit");
+        assert_eq!(events[0]["fields"]["payload_bytes"], 20);
+        assert_eq!(events[0]["target"], AUDIT_TARGET);
+        for secret in ["hunter2", "correct horse", "wrong", &message, &words[0], words.last().unwrap()] {
+            assert!(!log.contains(secret), "audit log leaks {:?}: {}", secret, log);
+        }
+    }
+
+    #[test]
+    fn test_reload_swaps_versions() {
+        let uniq = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_nanos();
+        let path = std::env::temp_dir().join(format!("glossia_service_{}_{}.yaml", std::process::id(), uniq));
+        let yaml = |words: &[&str]| words.iter().map(|w| format!("{}:\n  N: 1.0\n", w)).collect::<String>();
+        std::fs::write(&path, yaml(&["wbbb", "wccc", "wddd", "weee"])).unwrap();
+        let service = Service::new(ServiceConfig { wordlist: path.clone(), ..Default::default() }).unwrap();
+        let first = service.active();
+        assert_eq!((first.version, first.words, first.wordlist_sha256.len()), (1, 4, 64));
+        let words = service.encode(b"hi", None).unwrap();
+
+        // An unchanged file is the same version
+        assert_eq!(service.reload().unwrap(), first);
+        // A broken edit keeps the running wordlist
+        std::fs::write(&path, "wbbb: [").unwrap();
+        assert!(service.reload().is_err());
+        assert_eq!(service.active(), first);
+        assert_eq!(service.decode(&words.join(" "), None).unwrap(), b"hi");
+
+        std::fs::write(&path, yaml(&["wbbb", "wccc", "wddd", "weee", "wfff", "wggg", "whhh", "wiii"])).unwrap();
+        let second = service.reload().unwrap();
+        assert_eq!((second.version, second.words), (2, 8));
+        assert_ne!(second.wordlist_sha256, first.wordlist_sha256);
+        // 3 bits a word now, where 2 bytes took 8 words of 2 bits
+        let words = service.encode(b"hi", None).unwrap();
+        assert_eq!(words.len(), 6);
+        assert_eq!(service.decode(&words.join(" "), None).unwrap(), b"hi");
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_config_from_yaml_keeps_defaults() {
+        let config: ServiceConfig = serde_yaml::from_str("listen: 0.0.0.0:9000\ndefaults:\n  whiten: true\n").unwrap();
+        assert_eq!(config.listen, SocketAddr::from(([0, 0, 0, 0], 9000)));
+        assert!(config.defaults.whiten);
+        assert_eq!(config.max_payload_bytes, DEFAULT_MAX_PAYLOAD_BYTES);
+        assert_eq!(config.max_output_words, DEFAULT_MAX_OUTPUT_WORDS);
+        assert_eq!(config.rate_limit_per_minute, DEFAULT_RATE_LIMIT_PER_MINUTE);
+    }
+}
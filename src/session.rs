@@ -0,0 +1,183 @@
+//! Resumable encoding of large payloads.
+//!
+//! An [`EncodeSession`] packs a payload into words a chunk at a time and can
+//! be checkpointed between chunks: the byte offset, the bits not yet emitted,
+//! the random stream position and a running sentence count all serialize to
+//! a small JSON document. A long encode can then survive a restart, or be
+//! split across worker invocations that each emit a few chunks and hand the
+//! checkpoint on. Concatenating every chunk's words gives exactly
+//! [`crate::codec::encode_bytes`] of the whole payload.
+
+use crate::codec;
+use anyhow::{bail, Context, Result};
+use rand::SeedableRng;
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Serialize};
+
+/// Checkpointable state of a chunked encode.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EncodeSession {
+    /// Base seed; chunk `n` draws its randomness from `(seed, n)`
+    pub seed: u64,
+    /// Payload length and CRC-32, to refuse resuming against different input
+    pub payload_len: usize,
+    pub payload_crc: u32,
+    /// Next unread payload byte
+    pub byte_pos: usize,
+    /// Bits read but not yet emitted as a word
+    pub bit_buffer: u32,
+    pub bits_in_buffer: usize,
+    /// Whether the last word, holding the codec's end marker, has been emitted
+    #[serde(default)]
+    pub ended: bool,
+    /// Chunks emitted so far
+    pub chunk: u64,
+    /// Words emitted so far
+    pub words_emitted: usize,
+    /// Sentences of cover text emitted so far
+    pub sentences_emitted: usize,
+}
+
+impl EncodeSession {
+    /// Start encoding `payload`.
+    pub fn new(payload: &[u8], seed: u64) -> Self {
+        Self {
+            seed,
+            payload_len: payload.len(),
+            payload_crc: codec::crc32(payload),
+            byte_pos: 0,
+            bit_buffer: 0,
+            bits_in_buffer: 0,
+            ended: false,
+            chunk: 0,
+            words_emitted: 0,
+            sentences_emitted: 0,
+        }
+    }
+
+    /// Restore a session from [`EncodeSession::checkpoint`] output and check
+    /// that it belongs to `payload`.
+    pub fn resume(checkpoint: &str, payload: &[u8]) -> Result<Self> {
+        let session: Self = serde_json::from_str(checkpoint).context("Invalid session checkpoint")?;
+        if session.payload_len != payload.len() || session.payload_crc != codec::crc32(payload) {
+            bail!("Session checkpoint was made for a different payload");
+        }
+        if session.byte_pos > payload.len() {
+            bail!("Session checkpoint is past the end of the payload");
+        }
+        Ok(session)
+    }
+
+    /// Serialize the session state.
+    pub fn checkpoint(&self) -> String {
+        serde_json::to_string_pretty(self).expect("session state always serializes")
+    }
+
+    /// Whether every payload bit has been emitted.
+    pub fn is_finished(&self) -> bool {
+        self.ended
+    }
+
+    /// The random stream for the current chunk.
+    pub fn chunk_rng(&self) -> ChaCha20Rng {
+        let mut seed = [0u8; 32];
+        seed[..8].copy_from_slice(&self.seed.to_le_bytes());
+        seed[8..16].copy_from_slice(&self.chunk.to_le_bytes());
+        ChaCha20Rng::from_seed(seed)
+    }
+
+    /// Pack up to `max_words` more words of `payload` and advance to the next chunk.
+    pub fn next_words(&mut self, payload: &[u8], max_words: usize, wordlist: &[String]) -> Result<Vec<String>> {
+        if payload.len() != self.payload_len {
+            bail!("Payload length changed during the session");
+        }
+        let mut words = Vec::new();
+        match codec::bits_per_word(wordlist.len()) {
+            None => {
+                while words.len() < max_words && self.byte_pos < payload.len() {
+                    words.push(wordlist[payload[self.byte_pos] as usize % wordlist.len()].clone());
+                    self.byte_pos += 1;
+                }
+                self.ended = self.byte_pos >= payload.len();
+            }
+            Some(bits) => {
+                let mask = (1u32 << bits) - 1;
+                while words.len() < max_words && !self.ended {
+                    if self.bits_in_buffer < bits && self.byte_pos < payload.len() {
+                        self.bit_buffer = (self.bit_buffer << 8) | payload[self.byte_pos] as u32;
+                        self.bits_in_buffer += 8;
+                        self.byte_pos += 1;
+                        continue;
+                    }
+                    if self.bits_in_buffer >= bits {
+                        let shift = self.bits_in_buffer - bits;
+                        words.push(wordlist[((self.bit_buffer >> shift) & mask) as usize].clone());
+                        self.bit_buffer &= (1u32 << shift) - 1;
+                        self.bits_in_buffer = shift;
+                    } else {
+                        // End of payload: the end marker and padding, as encode_bytes writes them
+                        let index = ((self.bit_buffer << 1 | 1) << (bits - self.bits_in_buffer - 1)) & mask;
+                        words.push(wordlist[index as usize].clone());
+                        self.bit_buffer = 0;
+                        self.bits_in_buffer = 0;
+                        self.ended = true;
+                    }
+                }
+            }
+        }
+        self.words_emitted += words.len();
+        self.chunk += 1;
+        Ok(words)
+    }
+
+    /// Record the number of sentences generated for the chunks just packed.
+    pub fn record_sentences(&mut self, sentences: usize) {
+        self.sentences_emitted += sentences;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+
+    /// The first `len` words of [`alpha_wordlist`]
+    fn wordlist(len: usize) -> Vec<String> {
+        alpha_wordlist()[..len].to_vec()
+    }
+
+    #[test]
+    fn test_chunks_concatenate_to_whole_encoding() {
+        let payload: Vec<u8> = (0..1000u32).map(|i| (i * 7 % 251) as u8).collect();
+        for len in [2048, 300] {
+            let list = wordlist(len);
+            let mut session = EncodeSession::new(&payload, 1);
+            let mut words = Vec::new();
+            while !session.is_finished() {
+                words.extend(session.next_words(&payload, 37, &list).unwrap());
+            }
+            assert_eq!(words, codec::encode_bytes(&payload, &list).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_checkpoint_resume_continues_exactly() {
+        let list = wordlist(2048);
+        let payload = b"A long message that will be split across several worker invocations.";
+        let mut session = EncodeSession::new(payload, 42);
+        let mut words = session.next_words(payload, 10, &list).unwrap();
+        session.record_sentences(1);
+        let rng_before = session.chunk_rng();
+
+        let saved = session.checkpoint();
+        let mut resumed = EncodeSession::resume(&saved, payload).unwrap();
+        assert_eq!(resumed, session);
+        assert_eq!(resumed.chunk_rng(), rng_before);
+        while !resumed.is_finished() {
+            words.extend(resumed.next_words(payload, 10, &list).unwrap());
+        }
+        assert_eq!(words, codec::encode_bytes(payload, &list).unwrap());
+
+        assert!(EncodeSession::resume(&saved, b"different").is_err());
+    }
+}
@@ -0,0 +1,144 @@
+//! Signed payloads: BIP340 Schnorr signatures, as used by Nostr.
+//!
+//! The signer appends a 64-byte signature over SHA-256 of the payload before
+//! the bytes are packed into words, so a recipient holding the sender's
+//! public key (hex or `npub`) can check that a note really came from that
+//! identity. Verification fails closed: any change to the carrier words, or
+//! the wrong key, is an error rather than a payload.
+
+use crate::codec;
+use crate::decode;
+use crate::error::GlossiaError;
+use crate::secret::Wipe;
+use anyhow::{anyhow, bail, Context, Result};
+use bech32::{Bech32, Hrp};
+use k256::schnorr::{Signature, SigningKey, VerifyingKey};
+use rand::{CryptoRng, Rng};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+/// Bytes appended by [`sign_bytes`]
+pub const SIGNATURE_LEN: usize = 64;
+
+/// Parse a secret key given as 64 hex digits or a NIP-19 `nsec`.
+pub fn parse_secret_key(s: &str) -> Result<SigningKey> {
+    let bytes = parse_key_bytes(s.trim(), "nsec")?;
+    SigningKey::from_bytes(&bytes).map_err(|_| anyhow!("Not a valid secp256k1 secret key"))
+}
+
+/// Parse an x-only public key given as 64 hex digits or a NIP-19 `npub`.
+pub fn parse_public_key(s: &str) -> Result<VerifyingKey> {
+    let bytes = parse_key_bytes(s.trim(), "npub")?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| anyhow!("Not a valid BIP340 public key"))
+}
+
+/// The NIP-19 `npub` form of a public key.
+pub fn npub(key: &VerifyingKey) -> String {
+    let hrp = Hrp::parse("npub").expect("valid hrp");
+    bech32::encode::<Bech32>(hrp, &key.to_bytes()).expect("32 bytes always encode")
+}
+
+fn parse_key_bytes(s: &str, hrp: &str) -> Result<Vec<u8>> {
+    if s.len() == 64 && s.chars().all(|c| c.is_ascii_hexdigit()) {
+        return (0..64)
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).context("Invalid hex key"))
+            .collect();
+    }
+    let (found, data) = bech32::decode(s).map_err(|e| anyhow!("Key is neither hex nor bech32: {}", e))?;
+    if found.as_str() != hrp {
+        bail!("Expected an '{}' key, found '{}'", hrp, found);
+    }
+    if data.len() != 32 {
+        bail!("Key must be 32 bytes, found {}", data.len());
+    }
+    Ok(data)
+}
+
+/// `data` followed by a BIP340 signature over SHA-256 of `data`.
+pub fn sign_bytes<R: Rng + CryptoRng>(rng: &mut R, data: &[u8], key: &SigningKey) -> Result<Vec<u8>> {
+    let digest: [u8; 32] = Sha256::digest(data).into();
+    let aux: [u8; 32] = rng.gen();
+    let signature = key
+        .sign_prehash_with_aux_rand(&digest, &aux)
+        .map_err(|_| anyhow!("Signing failed"))?;
+    let mut out = data.to_vec();
+    out.extend_from_slice(&signature.to_bytes());
+    Ok(out)
+}
+
+/// Split off and check the signature added by [`sign_bytes`], returning the payload.
+pub fn verify_bytes(bytes: &[u8], key: &VerifyingKey) -> Result<Vec<u8>> {
+    if bytes.len() < SIGNATURE_LEN {
+        return Err(GlossiaError::BadSignature("Payload is too short to carry a signature".to_string()).into());
+    }
+    let (data, sig) = bytes.split_at(bytes.len() - SIGNATURE_LEN);
+    let signature = Signature::try_from(sig).map_err(|_| GlossiaError::BadSignature("Malformed signature".to_string()))?;
+    let digest: [u8; 32] = Sha256::digest(data).into();
+    key.verify_raw(&digest, &signature)
+        .map_err(|_| GlossiaError::BadSignature(format!("Signature does not verify for {}", npub(key))))?;
+    Ok(data.to_vec())
+}
+
+/// Sign `data` and pack it into words from `wordlist` (canonical sorted order).
+pub fn encode_signed<R: Rng + CryptoRng>(
+    rng: &mut R,
+    data: &[u8],
+    secret_key: &SigningKey,
+    wordlist: &[String],
+) -> Result<Vec<String>> {
+    let mut signed = sign_bytes(rng, data, secret_key)?;
+    let words = codec::encode_bytes(&signed, wordlist);
+    signed.wipe();
+    words
+}
+
+/// Recover the payload words from cover `text` and verify their signature.
+pub fn decode_verified(text: &str, pubkey: &VerifyingKey, wordlist: &[String]) -> Result<Vec<u8>> {
+    let set: HashSet<String> = wordlist.iter().cloned().collect();
+    let words = decode::extract_payload_words(text, &set);
+    let mut bytes = codec::decode_bytes(&words, wordlist)?;
+    let payload = verify_bytes(&bytes, pubkey);
+    bytes.wipe();
+    payload
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_nip19_keys() {
+        // Examples from NIP-19
+        let public = parse_public_key("npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg").unwrap();
+        let hex = parse_public_key("7e7e9c42a91bfef19fa929e5fda1b72e0ebc1a4c1141673e2794234d86addf4e").unwrap();
+        assert_eq!(public, hex);
+        assert_eq!(npub(&public), "npub10elfcs4fr0l0r8af98jlmgdh9c8tcxjvz9qkw038js35mp4dma8qzvjptg");
+
+        let secret = parse_secret_key("nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5").unwrap();
+        let hex = parse_secret_key("67dea2ed018072d675f5415ecfaed7d2597555e202d85b3d65ea4e58d2d92ffa").unwrap();
+        assert_eq!(secret.to_bytes(), hex.to_bytes());
+        assert!(parse_public_key("nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5").is_err());
+    }
+
+    #[test]
+    fn test_signed_roundtrip_and_fail_closed() {
+        let list = alpha_wordlist();
+        let mut rng = StdRng::seed_from_u64(9);
+        let key = SigningKey::random(&mut rng);
+        let words = encode_signed(&mut rng, b"Meet at noon", &key, &list).unwrap();
+        let text = words.join(" ");
+        assert_eq!(decode_verified(&text, key.verifying_key(), &list).unwrap(), b"Meet at noon");
+
+        let other = SigningKey::random(&mut rng);
+        assert!(decode_verified(&text, other.verifying_key(), &list).is_err());
+
+        let mut tampered = words.clone();
+        tampered[0] = if tampered[0] == list[0] { list[1].clone() } else { list[0].clone() };
+        assert!(decode_verified(&tampered.join(" "), key.verifying_key(), &list).is_err());
+        assert!(decode_verified("", key.verifying_key(), &list).is_err());
+    }
+}
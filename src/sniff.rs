@@ -0,0 +1,114 @@
+//! Guessing what kind of text an input is before encoding it.
+//!
+//! A front end that knows a pasted input is hex or base64 can offer to pack
+//! its bytes rather than its characters, which takes half or three quarters
+//! of the carrier words. [`sniff`] makes that guess from the text alone; it
+//! is a hint for the user, not a parser, and anything it doesn't recognize is
+//! [`InputFormat::Text`].
+
+use serde::{Deserialize, Serialize};
+
+/// Shortest hex or base64 input recognized as such: shorter runs of hex digits
+/// ("cafe", "2024") or base64 letters are more often just words.
+const MIN_ENCODED_LEN: usize = 8;
+
+/// What an input looks like
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputFormat {
+    /// Hex digits, optionally `0x`-prefixed, in whole bytes
+    Hex,
+    /// Standard or URL-safe base64
+    Base64,
+    /// A JSON object or array
+    Json,
+    /// An HTML document or fragment
+    Html,
+    Text,
+}
+
+/// What [`sniff`] found in an input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FormatGuess {
+    pub format: InputFormat,
+    /// Bytes the input decodes to, for hex and base64
+    pub decoded_len: Option<usize>,
+}
+
+/// Guess the format of `input`. Surrounding whitespace is ignored, and so are
+/// line breaks inside hex and base64, which are often wrapped.
+pub fn sniff(input: &str) -> FormatGuess {
+    let trimmed = input.trim();
+    let guess = |format, decoded_len| FormatGuess { format, decoded_len };
+    if (trimmed.starts_with('{') || trimmed.starts_with('['))
+        && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+    {
+        return guess(InputFormat::Json, None);
+    }
+    if trimmed.starts_with('<') && trimmed.ends_with('>') && (trimmed.contains("</") || trimmed.contains("/>")) {
+        return guess(InputFormat::Html, None);
+    }
+    let compact: String = trimmed.split_whitespace().collect();
+    if let Some(len) = hex_len(&compact) {
+        return guess(InputFormat::Hex, Some(len));
+    }
+    if let Some(len) = base64_len(&compact) {
+        return guess(InputFormat::Base64, Some(len));
+    }
+    guess(InputFormat::Text, None)
+}
+
+/// Decoded length of `s` as hex, if it is hex.
+fn hex_len(s: &str) -> Option<usize> {
+    let digits = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")).unwrap_or(s);
+    (digits.len() >= MIN_ENCODED_LEN && digits.len().is_multiple_of(2) && digits.bytes().all(|b| b.is_ascii_hexdigit()))
+        .then_some(digits.len() / 2)
+}
+
+/// Decoded length of `s` as base64, if it is base64. Plain words are made of
+/// base64 letters too, so a digit, a symbol, padding or a capital after the
+/// first letter is required as well.
+fn base64_len(s: &str) -> Option<usize> {
+    let body = s.trim_end_matches('=');
+    let padding = s.len() - body.len();
+    let standard = body.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'+' || b == b'/');
+    let url_safe = body.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_');
+    if body.len() < MIN_ENCODED_LEN || !(standard || url_safe) || padding > 2 {
+        return None;
+    }
+    // Padded input comes in whole quads; unpadded, one spare character is never valid
+    let framed = if padding > 0 { s.len().is_multiple_of(4) } else { body.len() % 4 != 1 };
+    let encoded_looking = padding > 0
+        || body.bytes().any(|b| !b.is_ascii_alphabetic())
+        || body.bytes().skip(1).any(|b| b.is_ascii_uppercase());
+    (framed && encoded_looking).then_some(body.len() * 6 / 8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff_encodings() {
+        assert_eq!(sniff("0xdeadBEEF00").format, InputFormat::Hex);
+        assert_eq!(sniff("deadbeef\n0011\n").decoded_len, Some(6));
+        assert_eq!(sniff("aGVsbG8gd29ybGQ=").decoded_len, Some(11));
+        assert_eq!(sniff("aGVsbG8gd29ybGQ").format, InputFormat::Base64);
+        assert_eq!(sniff("PDw_Pz4-Pj4").format, InputFormat::Base64);
+        // Odd-length hex and single words are not
+        assert_eq!(sniff("deadbeef0").format, InputFormat::Text);
+        assert_eq!(sniff("Password").format, InputFormat::Text);
+        assert_eq!(sniff("cafe").format, InputFormat::Text);
+    }
+
+    #[test]
+    fn test_sniff_documents() {
+        assert_eq!(sniff(" {\"a\": [1, 2]}\n").format, InputFormat::Json);
+        assert_eq!(sniff("[not json").format, InputFormat::Text);
+        assert_eq!(sniff("<p>Meet at <b>noon</b></p>").format, InputFormat::Html);
+        assert_eq!(sniff("Meet at noon").format, InputFormat::Text);
+        assert_eq!(sniff("").format, InputFormat::Text);
+        let json = serde_json::to_string(&sniff("deadbeef")).unwrap();
+        assert_eq!(json, r#"{"format":"hex","decoded_len":4}"#);
+    }
+}
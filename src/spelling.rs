@@ -0,0 +1,104 @@
+//! Telling real words from misread carriers.
+//!
+//! [`crate::noisy`] proposes wordlist words for tokens a prefix or an edit
+//! away, which also catches cover words: "coral" is one letter from "moral"
+//! but is far more likely the grammar's own filler than a misread carrier.
+//! A [`Dictionary`] of correctly spelled words lets the decoder tell the two
+//! apart and discount carrier readings of tokens that are words in their own
+//! right. [`load`] reads a Hunspell dictionary (`.dic` with its `.aff`, with
+//! the `spellcheck` feature, via `spellbook`) or a plain list, one word per
+//! line.
+
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::path::Path;
+
+/// A set of correctly spelled words.
+pub trait Dictionary: Send + Sync {
+    /// Whether `word` (lowercase) is spelled correctly
+    fn is_word(&self, word: &str) -> bool;
+}
+
+impl Dictionary for HashSet<String> {
+    fn is_word(&self, word: &str) -> bool {
+        self.contains(word)
+    }
+}
+
+/// The dictionary at `path`: a Hunspell `.dic` or `.aff` (the other file
+/// alongside it), or else a word list with one word per line.
+pub fn load(path: &Path) -> Result<Box<dyn Dictionary>> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("dic" | "aff") => {
+            let read = |ext: &str| {
+                let file = path.with_extension(ext);
+                std::fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))
+            };
+            hunspell(&read("aff")?, &read("dic")?).with_context(|| format!("Invalid Hunspell dictionary {}", path.display()))
+        }
+        _ => {
+            let text = std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+            Ok(Box::new(word_set(&text)))
+        }
+    }
+}
+
+/// The words of a list, one per line, lowercased; blank lines and `#`
+/// comments are skipped.
+pub fn word_set(text: &str) -> HashSet<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+#[cfg(feature = "spellcheck")]
+struct Hunspell(spellbook::Dictionary);
+
+#[cfg(feature = "spellcheck")]
+impl Dictionary for Hunspell {
+    fn is_word(&self, word: &str) -> bool {
+        self.0.check(word)
+    }
+}
+
+/// A Hunspell dictionary from the text of its `.aff` and `.dic` files.
+#[cfg(feature = "spellcheck")]
+pub fn hunspell(aff: &str, dic: &str) -> Result<Box<dyn Dictionary>> {
+    let dictionary = spellbook::Dictionary::new(aff, dic).map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(Box::new(Hunspell(dictionary)))
+}
+
+/// A Hunspell dictionary (needs the `spellcheck` feature).
+#[cfg(not(feature = "spellcheck"))]
+pub fn hunspell(_aff: &str, _dic: &str) -> Result<Box<dyn Dictionary>> {
+    anyhow::bail!("Hunspell dictionaries need glossia built with the 'spellcheck' feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_set() {
+        let words = word_set("# filler words\nCoral\n\n  moral \n");
+        assert!(words.is_word("coral") && words.is_word("moral"));
+        assert!(!words.is_word("rnoral"));
+        assert_eq!(words.len(), 2);
+    }
+
+    #[test]
+    fn test_hunspell() {
+        let aff = "SET UTF-8\nSFX S Y 1\nSFX S 0 s .\n";
+        let dic = "2\ncoral/S\nreef\n";
+        let result = hunspell(aff, dic);
+        if cfg!(feature = "spellcheck") {
+            let dictionary = result.unwrap();
+            assert!(dictionary.is_word("coral") && dictionary.is_word("corals") && dictionary.is_word("reef"));
+            assert!(!dictionary.is_word("reefs") && !dictionary.is_word("rnoral"));
+        } else {
+            assert!(result.err().unwrap().to_string().contains("spellcheck"));
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! OpenSSH public key lines packed as key bytes, and written back exactly.
+//!
+//! A line of `id_ed25519.pub` or `authorized_keys` is an optional list of
+//! options, the key type, the key blob in base64 and a free-text comment.
+//! The blob starts with the key type again, so [`compact_key_lines`] stores
+//! the type once, as its index in [`KEY_TYPES`], then the rest of the blob as
+//! bytes; options and comment are kept as text, with the whitespace around
+//! them. [`expand_key_lines`] writes the same bytes back, so the key's
+//! fingerprint and any signature over the file still match.
+
+use crate::framing::{read_text, read_varint, write_text, write_varint};
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+
+/// Key types a line may have; a type's index is what is stored, so new types
+/// only ever go on the end
+pub const KEY_TYPES: [&str; 11] = [
+    "ssh-ed25519",
+    "ssh-rsa",
+    "ecdsa-sha2-nistp256",
+    "ecdsa-sha2-nistp384",
+    "ecdsa-sha2-nistp521",
+    "sk-ssh-ed25519@openssh.com",
+    "sk-ecdsa-sha2-nistp256@openssh.com",
+    "ssh-dss",
+    "ssh-ed25519-cert-v01@openssh.com",
+    "ssh-rsa-cert-v01@openssh.com",
+    "ecdsa-sha2-nistp256-cert-v01@openssh.com",
+];
+
+/// Pack one or more key lines (joined by `\n`).
+pub fn compact_key_lines(text: &str) -> Result<Vec<u8>> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut out = Vec::with_capacity(text.len());
+    write_varint(&mut out, lines.len() as u64);
+    for (i, line) in lines.iter().enumerate() {
+        compact_line(line, &mut out).with_context(|| format!("Line {} is not an SSH public key", i + 1))?;
+    }
+    Ok(out)
+}
+
+/// The lines [`compact_key_lines`] packed.
+pub fn expand_key_lines(packed: &[u8]) -> Result<String> {
+    let (count, mut rest) = read_varint(packed)?;
+    let mut lines = Vec::new();
+    for _ in 0..count {
+        let (options, tail) = read_text(rest)?;
+        let (&type_index, tail) = tail.split_first().ok_or_else(|| anyhow!("Packed key is truncated"))?;
+        let key_type = KEY_TYPES.get(type_index as usize).ok_or_else(|| anyhow!("Unknown key type {}", type_index))?;
+        let (len, tail) = read_varint(tail)?;
+        let key = tail.get(..len as usize).ok_or_else(|| anyhow!("Packed key is truncated"))?;
+        let (comment, tail) = read_text(&tail[key.len()..])?;
+        rest = tail;
+
+        let mut blob = (key_type.len() as u32).to_be_bytes().to_vec();
+        blob.extend_from_slice(key_type.as_bytes());
+        blob.extend_from_slice(key);
+        lines.push(format!("{}{} {}{}", options, key_type, BASE64.encode(&blob), comment));
+    }
+    if !rest.is_empty() {
+        bail!("Packed keys have {} bytes left over", rest.len());
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Pack a line as its options, key type index, key bytes after the type, and
+/// comment.
+fn compact_line(line: &str, out: &mut Vec<u8>) -> Result<()> {
+    // The type is the first known one, at the start or after a space, that
+    // is followed by a blob of that type; options before it may quote spaces
+    for (start, _) in line.match_indices(|c: char| c.is_ascii_alphabetic()) {
+        if start > 0 && !line[..start].ends_with(' ') {
+            continue;
+        }
+        let rest = &line[start..];
+        let Some((key_type, rest)) = rest.split_once(' ') else {
+            continue;
+        };
+        let Some(type_index) = KEY_TYPES.iter().position(|&t| t == key_type) else {
+            continue;
+        };
+        let blob_len = rest.find([' ', '\t']).unwrap_or(rest.len());
+        let Ok(blob) = BASE64.decode(&rest[..blob_len]) else {
+            continue;
+        };
+        let Some(key) = blob
+            .get(..4)
+            .map(|len| u32::from_be_bytes(len.try_into().expect("4 bytes")) as usize)
+            .filter(|&len| blob.get(4..4 + len) == Some(key_type.as_bytes()))
+            .map(|len| &blob[4 + len..])
+        else {
+            continue;
+        };
+        write_text(out, &line[..start]);
+        out.push(type_index as u8);
+        write_varint(out, key.len() as u64);
+        out.extend_from_slice(key);
+        write_text(out, &rest[blob_len..]);
+        return Ok(());
+    }
+    bail!("no known key type followed by its base64 key");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ED25519: &str = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIOMqqnkVzrm0SdG6UOoqKLsabgH5C9okWi0dh2l9GKJl alice@laptop";
+
+    #[test]
+    fn test_key_line_round_trip() {
+        let packed = compact_key_lines(ED25519).unwrap();
+        assert_eq!(expand_key_lines(&packed).unwrap(), ED25519);
+        assert!(packed.len() < ED25519.len() * 2 / 3, "{} of {} bytes", packed.len(), ED25519.len());
+
+        // authorized_keys: options (with a quoted key type), no comment, tabs
+        let lines = format!(
+            "command=\"echo ssh-rsa x\",no-pty {}\n{}\n{}",
+            ED25519.replace(" alice@laptop", ""),
+            ED25519.replace(" alice", "\t\talice"),
+            ED25519
+        );
+        assert_eq!(expand_key_lines(&compact_key_lines(&lines).unwrap()).unwrap(), lines);
+    }
+
+    #[test]
+    fn test_refuses_other_lines() {
+        assert!(compact_key_lines("Meet at noon").is_err());
+        // The blob says ssh-ed25519, the line says ssh-rsa
+        assert!(compact_key_lines(&ED25519.replace("ssh-ed25519 ", "ssh-rsa ")).is_err());
+        assert!(compact_key_lines(&ED25519.replace("ssh-ed25519 ", "ssh-ed25519  ")).is_err());
+        assert!(compact_key_lines(&format!("{}\n", ED25519)).is_err());
+        assert!(expand_key_lines(&[1, 0, 99, 0, 0]).is_err());
+    }
+}
@@ -0,0 +1,378 @@
+//! Detectability self-audit for generated text.
+//!
+//! These are the cheap first-order tests a curious reader (or a naive filter)
+//! would run against a cover text: how far its word frequencies are from
+//! ordinary English, how much of it is drawn from the payload wordlist, how
+//! uniform its sentences are, and how repetitive its vocabulary is. The result
+//! is a [`ScoreCard`] that can be compared before and after a grammar or
+//! lexicon change; it is not a guarantee against a trained classifier.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use serde::Serialize;
+
+/// Approximate per-token frequencies of the most common English words
+/// (rounded from large general-English corpora such as COCA/BNC).
+const REFERENCE_WORD_FREQUENCIES: &[(&str, f64)] = &[
+    ("the", 0.0500),
+    ("of", 0.0270),
+    ("and", 0.0250),
+    ("to", 0.0230),
+    ("a", 0.0210),
+    ("in", 0.0180),
+    ("that", 0.0100),
+    ("is", 0.0100),
+    ("it", 0.0090),
+    ("i", 0.0090),
+    ("you", 0.0090),
+    ("for", 0.0070),
+    ("was", 0.0070),
+    ("he", 0.0070),
+    ("on", 0.0060),
+    ("with", 0.0060),
+    ("as", 0.0060),
+    ("be", 0.0050),
+    ("at", 0.0050),
+    ("his", 0.0050),
+    ("this", 0.0050),
+    ("they", 0.0040),
+    ("are", 0.0040),
+    ("but", 0.0040),
+    ("not", 0.0040),
+];
+
+/// Window used for the moving-average type-token ratio, so the measure does not
+/// simply fall as texts get longer.
+const TTR_WINDOW: usize = 50;
+
+/// Reference statistics the audit compares against.
+///
+/// The defaults are rough figures for general English prose. Use
+/// [`ReferenceStats::from_corpus`] to calibrate against the kind of text the
+/// output is meant to blend into (e.g. a mail archive).
+#[derive(Clone, Debug)]
+pub struct ReferenceStats {
+    /// Fraction of word tokens that happen to be wordlist words
+    pub wordlist_coverage: f64,
+    /// Mean words per sentence
+    pub sentence_length_mean: f64,
+    /// Variance of words per sentence
+    pub sentence_length_variance: f64,
+    /// Moving-average type-token ratio
+    pub type_token_ratio: f64,
+}
+
+impl Default for ReferenceStats {
+    fn default() -> Self {
+        Self {
+            wordlist_coverage: 0.12,
+            sentence_length_mean: 18.0,
+            sentence_length_variance: 110.0,
+            type_token_ratio: 0.72,
+        }
+    }
+}
+
+impl ReferenceStats {
+    /// Measure reference statistics from a sample of natural text.
+    pub fn from_corpus(text: &str, wordlist: &HashSet<String>) -> Self {
+        let profile = TextProfile::new(text);
+        let defaults = Self::default();
+        Self {
+            wordlist_coverage: profile.wordlist_coverage(wordlist),
+            sentence_length_mean: profile
+                .sentence_length_mean()
+                .unwrap_or(defaults.sentence_length_mean),
+            sentence_length_variance: profile
+                .sentence_length_variance()
+                .unwrap_or(defaults.sentence_length_variance),
+            type_token_ratio: profile.type_token_ratio(),
+        }
+    }
+}
+
+/// Result of auditing a text.
+///
+/// Each `*_deviation` is a non-negative distance from the reference (0 means
+/// indistinguishable on that measure); `overall` is their mean.
+#[derive(Clone, Debug, Serialize)]
+pub struct ScoreCard {
+    pub tokens: usize,
+    pub sentences: usize,
+    /// Pearson chi-square statistic of common-word counts against English
+    pub chi_square: f64,
+    /// Degrees of freedom for `chi_square`
+    pub chi_square_df: usize,
+    /// Fraction of word tokens that are wordlist words
+    pub wordlist_coverage: f64,
+    pub sentence_length_mean: f64,
+    pub sentence_length_variance: f64,
+    pub type_token_ratio: f64,
+    pub frequency_deviation: f64,
+    pub coverage_deviation: f64,
+    pub sentence_length_deviation: f64,
+    pub variance_deviation: f64,
+    pub type_token_deviation: f64,
+    pub overall: f64,
+}
+
+impl fmt::Display for ScoreCard {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "=== Detectability Audit ===")?;
+        writeln!(f, "Tokens: {}  Sentences: {}", self.tokens, self.sentences)?;
+        writeln!(
+            f,
+            "  Word frequency chi-square: {:.1} (df {})  deviation {:.2}",
+            self.chi_square, self.chi_square_df, self.frequency_deviation
+        )?;
+        writeln!(
+            f,
+            "  Wordlist coverage: {:.1}%  deviation {:.2}",
+            self.wordlist_coverage * 100.0,
+            self.coverage_deviation
+        )?;
+        writeln!(
+            f,
+            "  Sentence length: mean {:.1}  deviation {:.2}",
+            self.sentence_length_mean, self.sentence_length_deviation
+        )?;
+        writeln!(
+            f,
+            "  Sentence length variance: {:.1}  deviation {:.2}",
+            self.sentence_length_variance, self.variance_deviation
+        )?;
+        writeln!(
+            f,
+            "  Type-token ratio: {:.3}  deviation {:.2}",
+            self.type_token_ratio, self.type_token_deviation
+        )?;
+        write!(f, "Overall deviation: {:.2} (lower is less conspicuous)", self.overall)
+    }
+}
+
+/// Audit `text` against the default English reference statistics.
+pub fn audit(text: &str, wordlist: &HashSet<String>) -> ScoreCard {
+    audit_with_reference(text, wordlist, &ReferenceStats::default())
+}
+
+/// Audit `text` against caller-supplied reference statistics.
+pub fn audit_with_reference(
+    text: &str,
+    wordlist: &HashSet<String>,
+    reference: &ReferenceStats,
+) -> ScoreCard {
+    let profile = TextProfile::new(text);
+    let (chi_square, chi_square_df) = profile.chi_square();
+    let wordlist_coverage = profile.wordlist_coverage(wordlist);
+    let sentence_length_mean = profile.sentence_length_mean().unwrap_or(0.0);
+    let sentence_length_variance = profile.sentence_length_variance().unwrap_or(0.0);
+    let type_token_ratio = profile.type_token_ratio();
+
+    // Phi coefficient: chi-square scaled so it doesn't grow with text length
+    let frequency_deviation = if profile.tokens.is_empty() {
+        0.0
+    } else {
+        (chi_square / profile.tokens.len() as f64).sqrt()
+    };
+    let coverage_deviation =
+        relative_deviation(wordlist_coverage, reference.wordlist_coverage);
+    let sentence_length_deviation = if reference.sentence_length_variance > 0.0 {
+        (sentence_length_mean - reference.sentence_length_mean).abs()
+            / reference.sentence_length_variance.sqrt()
+    } else {
+        0.0
+    };
+    // Variance is compared on a log scale: half or double the reference are equally
+    // odd. Floor at one word^2 so identical sentence lengths score as badly as nearly
+    // identical ones rather than blowing up.
+    let variance_deviation = if reference.sentence_length_variance > 0.0 {
+        (sentence_length_variance.max(1.0) / reference.sentence_length_variance.max(1.0))
+            .ln()
+            .abs()
+    } else {
+        0.0
+    };
+    let type_token_deviation = relative_deviation(type_token_ratio, reference.type_token_ratio);
+
+    let overall = (frequency_deviation
+        + coverage_deviation
+        + sentence_length_deviation
+        + variance_deviation
+        + type_token_deviation)
+        / 5.0;
+
+    ScoreCard {
+        tokens: profile.tokens.len(),
+        sentences: profile.sentence_lengths.len(),
+        chi_square,
+        chi_square_df,
+        wordlist_coverage,
+        sentence_length_mean,
+        sentence_length_variance,
+        type_token_ratio,
+        frequency_deviation,
+        coverage_deviation,
+        sentence_length_deviation,
+        variance_deviation,
+        type_token_deviation,
+        overall,
+    }
+}
+
+fn relative_deviation(value: f64, reference: f64) -> f64 {
+    if reference > 0.0 {
+        (value - reference).abs() / reference
+    } else {
+        value.abs()
+    }
+}
+
+/// Lowercased word tokens and per-sentence word counts.
+struct TextProfile {
+    tokens: Vec<String>,
+    sentence_lengths: Vec<usize>,
+}
+
+impl TextProfile {
+    fn new(text: &str) -> Self {
+        let mut tokens = Vec::new();
+        let mut sentence_lengths = Vec::new();
+
+        for sentence in text.split(['.', '!', '?']) {
+            let words: Vec<String> = sentence
+                .split(|c: char| !(c.is_alphabetic() || c == '\''))
+                .map(|w| w.trim_matches('\'').to_lowercase())
+                .filter(|w| !w.is_empty())
+                .collect();
+            if !words.is_empty() {
+                sentence_lengths.push(words.len());
+                tokens.extend(words);
+            }
+        }
+
+        Self {
+            tokens,
+            sentence_lengths,
+        }
+    }
+
+    /// Chi-square of the reference words plus an "other" bucket.
+    fn chi_square(&self) -> (f64, usize) {
+        let n = self.tokens.len() as f64;
+        if self.tokens.is_empty() {
+            return (0.0, 0);
+        }
+
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for token in &self.tokens {
+            *counts.entry(token.as_str()).or_insert(0) += 1;
+        }
+
+        let mut chi = 0.0;
+        let mut reference_total = 0.0;
+        let mut observed_total = 0usize;
+        for (word, p) in REFERENCE_WORD_FREQUENCIES {
+            let observed = counts.get(word).copied().unwrap_or(0);
+            let expected = n * p;
+            chi += (observed as f64 - expected).powi(2) / expected;
+            reference_total += p;
+            observed_total += observed;
+        }
+        let other_expected = n * (1.0 - reference_total);
+        let other_observed = (self.tokens.len() - observed_total) as f64;
+        chi += (other_observed - other_expected).powi(2) / other_expected;
+
+        (chi, REFERENCE_WORD_FREQUENCIES.len())
+    }
+
+    fn wordlist_coverage(&self, wordlist: &HashSet<String>) -> f64 {
+        if self.tokens.is_empty() {
+            return 0.0;
+        }
+        let hits = self.tokens.iter().filter(|t| wordlist.contains(*t)).count();
+        hits as f64 / self.tokens.len() as f64
+    }
+
+    fn sentence_length_mean(&self) -> Option<f64> {
+        if self.sentence_lengths.is_empty() {
+            return None;
+        }
+        let total: usize = self.sentence_lengths.iter().sum();
+        Some(total as f64 / self.sentence_lengths.len() as f64)
+    }
+
+    fn sentence_length_variance(&self) -> Option<f64> {
+        let mean = self.sentence_length_mean()?;
+        let sum_sq: f64 = self
+            .sentence_lengths
+            .iter()
+            .map(|l| (*l as f64 - mean).powi(2))
+            .sum();
+        Some(sum_sq / self.sentence_lengths.len() as f64)
+    }
+
+    /// Moving-average type-token ratio (plain TTR for texts shorter than the window).
+    fn type_token_ratio(&self) -> f64 {
+        if self.tokens.is_empty() {
+            return 0.0;
+        }
+        let window = TTR_WINDOW.min(self.tokens.len());
+        let windows = self.tokens.len() - window + 1;
+        let total: f64 = (0..windows)
+            .map(|start| {
+                let distinct: HashSet<&String> = self.tokens[start..start + window].iter().collect();
+                distinct.len() as f64 / window as f64
+            })
+            .sum();
+        total / windows as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wordlist(words: &[&str]) -> HashSet<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[test]
+    fn test_audit_basic_measures() {
+        let text = "The cat sat on the mat. A dog ran in the park!";
+        let card = audit(text, &wordlist(&["cat", "dog", "park"]));
+
+        assert_eq!(card.tokens, 12);
+        assert_eq!(card.sentences, 2);
+        assert!((card.wordlist_coverage - 0.25).abs() < 1e-9);
+        assert!((card.sentence_length_mean - 6.0).abs() < 1e-9);
+        assert_eq!(card.sentence_length_variance, 0.0);
+        assert_eq!(card.chi_square_df, REFERENCE_WORD_FREQUENCIES.len());
+    }
+
+    #[test]
+    fn test_wordlist_heavy_text_scores_worse() {
+        let list = wordlist(&["abandon", "ability", "able", "about", "above", "absent"]);
+        let natural = "The report was late, and we talked about it for a while. \
+                       It is not the first time this has happened to the team. \
+                       Next week they will try again with a better plan.";
+        let stuffed = "Abandon ability able. Absent above abandon. Able ability absent.";
+
+        let natural_card = audit(natural, &list);
+        let stuffed_card = audit(stuffed, &list);
+        assert!(stuffed_card.coverage_deviation > natural_card.coverage_deviation);
+        assert!(stuffed_card.overall > natural_card.overall);
+    }
+
+    #[test]
+    fn test_reference_from_corpus() {
+        let corpus = "One two three. Four five six seven.";
+        let reference = ReferenceStats::from_corpus(corpus, &wordlist(&["two"]));
+        assert!((reference.sentence_length_mean - 3.5).abs() < 1e-9);
+        assert!((reference.wordlist_coverage - 1.0 / 7.0).abs() < 1e-9);
+
+        // Auditing the corpus against itself has no coverage/length deviation
+        let card = audit_with_reference(corpus, &wordlist(&["two"]), &reference);
+        assert!(card.coverage_deviation < 1e-9);
+        assert!(card.sentence_length_deviation < 1e-9);
+    }
+}
@@ -0,0 +1,499 @@
+//! Writing-style profiles, so cover text can resemble a sender's own writing.
+//!
+//! [`StyleProfile::from_text`] measures a sample of the sender's writing:
+//! how long their sentences are, how often they end in `?` or `!`, how many
+//! commas they use, how often they contract ("don't" rather than "do not"),
+//! and how many sentences make up a paragraph. The encoder uses the length
+//! distribution to pick sentence shapes ([`StyleProfile::length_weight`]) and
+//! [`StyleProfile::apply`] to punctuate and lay out the generated text.
+//!
+//! None of the changes touch payload words: contractions only join two
+//! non-wordlist words, and punctuation is invisible to decoding.
+//!
+//! [`Style`] picks the genre of the output. Besides grammar-generated prose,
+//! carrier words can be laid out as a shopping list or a recipe, one item or
+//! step per line inside fixed scaffolding ("- 2 cups of ...", "Step 3: ...").
+//! Scaffolding may contain wordlist words ("step", "add"), so list styles are
+//! decoded line by line with [`Style::extract`] rather than by wordlist lookup.
+
+use crate::chaff::split_sentences;
+use crate::decode::{normalize_token, strip_markup};
+use crate::numbers::number_value;
+use crate::types::Pos;
+use anyhow::{bail, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashSet};
+
+/// Word pairs the generator can emit, with their contracted form.
+const CONTRACTIONS: &[(&str, &str, &str)] = &[
+    ("do", "not", "don't"),
+    ("does", "not", "doesn't"),
+    ("did", "not", "didn't"),
+    ("is", "not", "isn't"),
+    ("are", "not", "aren't"),
+    ("was", "not", "wasn't"),
+    ("can", "not", "can't"),
+    ("could", "not", "couldn't"),
+    ("should", "not", "shouldn't"),
+    ("would", "not", "wouldn't"),
+    ("will", "not", "won't"),
+    ("it", "is", "it's"),
+    ("that", "is", "that's"),
+    ("there", "is", "there's"),
+    ("they", "are", "they're"),
+    ("we", "are", "we're"),
+    ("you", "are", "you're"),
+    ("i", "am", "I'm"),
+    ("they", "will", "they'll"),
+    ("we", "will", "we'll"),
+    ("you", "will", "you'll"),
+    ("i", "will", "I'll"),
+    ("they", "have", "they've"),
+    ("we", "have", "we've"),
+    ("i", "have", "I've"),
+];
+
+/// Conjunctions a comma may precede
+const COMMA_BEFORE: &[&str] = &["and", "but", "or", "so", "yet", "while", "though"];
+
+/// Spread (in words) of each sample sentence over neighbouring lengths, so a
+/// short sample still admits lengths it happens not to contain
+const LENGTH_BANDWIDTH: f64 = 1.5;
+
+/// Measured habits of a writing sample.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct StyleProfile {
+    /// Words per sentence -> number of sentences
+    pub sentence_lengths: BTreeMap<usize, usize>,
+    /// Fraction of sentences ending in `?`
+    pub question_rate: f64,
+    /// Fraction of sentences ending in `!`
+    pub exclamation_rate: f64,
+    /// Mean commas per sentence
+    pub commas_per_sentence: f64,
+    /// Fraction of contractible word pairs written contracted
+    pub contraction_rate: f64,
+    /// Mean sentences per paragraph (paragraphs are separated by blank lines)
+    pub paragraph_sentences: f64,
+}
+
+impl StyleProfile {
+    /// Measure `sample`. An empty sample gives an empty profile, which
+    /// [`apply`](Self::apply) leaves text unchanged under.
+    pub fn from_text(sample: &str) -> Self {
+        let sample = sample.replace('\u{2019}', "'");
+        let paragraphs: Vec<String> = sample
+            .split("\n\n")
+            .map(|p| p.split_whitespace().collect::<Vec<_>>().join(" "))
+            .filter(|p| !p.is_empty())
+            .collect();
+
+        let mut profile = StyleProfile::default();
+        let (mut sentences, mut questions, mut exclamations, mut commas) = (0usize, 0usize, 0usize, 0usize);
+        let (mut contracted, mut expanded) = (0usize, 0usize);
+        for paragraph in &paragraphs {
+            for sentence in split_sentences(paragraph) {
+                let tokens: Vec<&str> = sentence.split_whitespace().collect();
+                sentences += 1;
+                *profile.sentence_lengths.entry(tokens.len()).or_insert(0) += 1;
+                match sentence.trim_end().chars().last() {
+                    Some('?') => questions += 1,
+                    Some('!') => exclamations += 1,
+                    _ => {}
+                }
+                commas += sentence.matches(',').count();
+                let words: Vec<String> = tokens.iter().map(|t| normalize_token(t)).collect();
+                contracted += tokens.iter().filter(|t| is_contraction(t)).count();
+                expanded += words.windows(2).filter(|pair| contract(&pair[0], &pair[1]).is_some()).count();
+            }
+        }
+        if sentences == 0 {
+            return profile;
+        }
+        let sentences_f = sentences as f64;
+        profile.question_rate = questions as f64 / sentences_f;
+        profile.exclamation_rate = exclamations as f64 / sentences_f;
+        profile.commas_per_sentence = commas as f64 / sentences_f;
+        if contracted + expanded > 0 {
+            profile.contraction_rate = contracted as f64 / (contracted + expanded) as f64;
+        }
+        profile.paragraph_sentences = sentences_f / paragraphs.len() as f64;
+        profile
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sentence_lengths.is_empty()
+    }
+
+    /// Relative likelihood of a sentence of `words` words in this style
+    /// (a smoothed histogram of the sample; never zero).
+    pub fn length_weight(&self, words: usize) -> f64 {
+        let density: f64 = self
+            .sentence_lengths
+            .iter()
+            .map(|(len, count)| {
+                let d = (*len as f64 - words as f64) / LENGTH_BANDWIDTH;
+                *count as f64 * (-0.5 * d * d).exp()
+            })
+            .sum();
+        density + 1e-3
+    }
+
+    /// Punctuate and lay out generated `text` in this style: end marks,
+    /// commas before conjunctions, contractions of non-wordlist word pairs,
+    /// and, with `paragraphs`, blank-line paragraph breaks. The sentence count
+    /// and order are unchanged, so keyed chaff still strips.
+    pub fn apply<R: Rng + ?Sized>(&self, rng: &mut R, text: &str, wordlist: &HashSet<String>, paragraphs: bool) -> String {
+        if self.is_empty() {
+            return text.to_string();
+        }
+        let sentences: Vec<String> = split_sentences(text)
+            .iter()
+            .map(|sentence| self.apply_sentence(rng, sentence, wordlist))
+            .collect();
+        if !paragraphs || self.paragraph_sentences <= 0.0 {
+            return sentences.join(" ");
+        }
+
+        let mut out: Vec<String> = Vec::new();
+        let mut rest = sentences.as_slice();
+        while !rest.is_empty() {
+            // Sizes vary around the sample's mean, by up to half of it
+            let mean = self.paragraph_sentences;
+            let size = rng.gen_range(mean * 0.5..=mean * 1.5).round().max(1.0) as usize;
+            let (paragraph, tail) = rest.split_at(size.min(rest.len()));
+            out.push(paragraph.join(" "));
+            rest = tail;
+        }
+        out.join("\n\n")
+    }
+
+    fn apply_sentence<R: Rng + ?Sized>(&self, rng: &mut R, sentence: &str, wordlist: &HashSet<String>) -> String {
+        let mut tokens: Vec<String> = sentence.split_whitespace().map(str::to_string).collect();
+
+        // Contractions: only between plain (unhighlighted) cover words
+        let mut i = 0;
+        while i + 1 < tokens.len() {
+            let (a, b) = (&tokens[i], &tokens[i + 1]);
+            let plain = |t: &str| strip_markup(t) == t && !wordlist.contains(&normalize_token(t));
+            let a_clean = a.chars().all(|c| c.is_ascii_alphabetic());
+            let joined = if a_clean && plain(a) && plain(b) {
+                contract(&a.to_lowercase(), &normalize_token(b))
+            } else {
+                None
+            };
+            match joined {
+                Some(joined) if !wordlist.contains(&normalize_token(joined)) && rng.gen_bool(self.contraction_rate.clamp(0.0, 1.0)) => {
+                    let suffix: String = b.chars().skip_while(|c| c.is_ascii_alphabetic()).collect();
+                    let mut word = joined.to_string();
+                    if a.starts_with(|c: char| c.is_uppercase()) {
+                        word = capitalize(&word);
+                    }
+                    tokens.splice(i..=i + 1, [word + &suffix]);
+                }
+                _ => {}
+            }
+            i += 1;
+        }
+
+        // Commas before conjunctions
+        let comma_rate = self.commas_per_sentence.clamp(0.0, 1.0);
+        for i in 1..tokens.len() {
+            let conj = COMMA_BEFORE.contains(&normalize_token(&tokens[i]).as_str());
+            let prev = &tokens[i - 1];
+            if conj && !prev.ends_with([',', '.', '!', '?']) && rng.gen_bool(comma_rate) {
+                tokens[i - 1].push(',');
+            }
+        }
+
+        // End mark
+        if let Some(last) = tokens.last_mut() {
+            if last.ends_with('.') {
+                let roll: f64 = rng.gen();
+                let mark = if roll < self.question_rate {
+                    Some('?')
+                } else if roll < self.question_rate + self.exclamation_rate {
+                    Some('!')
+                } else {
+                    None
+                };
+                if let Some(mark) = mark {
+                    last.pop();
+                    last.push(mark);
+                }
+            }
+        }
+        tokens.join(" ")
+    }
+}
+
+/// Shopping list items; `{}` is where the carrier words go and `{n}` is a
+/// quantity from 2 to 9 (which `--number-payload` may rewrite). No prefix is
+/// a prefix of another, so each line matches exactly one item.
+const SHOPPING_ITEMS: &[&str] = &[
+    "- {n} cups of {}",
+    "- {n} lb of {}",
+    "- a dozen {}",
+    "- {n} tins of {}",
+    "- a bag of {}",
+    "- some {}",
+    "- half a pound of {}",
+    "- {n} packs of {}",
+    "- a bunch of {}",
+    "- {n} jars of {}",
+    "- {n} bottles of {}",
+    "- {n} packets of {}",
+];
+
+/// Recipe steps, after the `Step N: ` number
+const RECIPE_STEPS: &[&str] = &[
+    "Add {n} cups of {} and stir.",
+    "Chop the {} finely.",
+    "Fold in the {}.",
+    "Whisk in {n} spoons of {} until smooth.",
+    "Season with {n} pinches of {}.",
+    "Simmer the {} for 10 minutes.",
+    "Slice {n} of the {} thinly.",
+    "Stir in the {} and set aside.",
+    "Sprinkle {n} pinches of {} over the top.",
+    "Bake the {} for 20 minutes.",
+];
+
+const SHOPPING_HEADER: &str = "Shopping list:";
+const RECIPE_HEADER: &str = "Method:";
+
+/// Output genre.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Style {
+    /// Sentences from the grammar
+    #[default]
+    Prose,
+    /// A bulleted shopping list, one item per line
+    ShoppingList,
+    /// Numbered recipe steps
+    Recipe,
+}
+
+impl Style {
+    /// Every style, in menu order
+    pub const ALL: &'static [Style] = &[Style::Prose, Style::ShoppingList, Style::Recipe];
+
+    /// The `--output-style` name, which [`Style::from_name`] parses
+    pub fn name(&self) -> &'static str {
+        match self {
+            Style::Prose => "prose",
+            Style::ShoppingList => "list",
+            Style::Recipe => "recipe",
+        }
+    }
+
+    /// Parse `prose`, `list` or `recipe`
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "prose" => Some(Style::Prose),
+            "list" => Some(Style::ShoppingList),
+            "recipe" => Some(Style::Recipe),
+            _ => None,
+        }
+    }
+
+    fn scaffolding(&self) -> Result<(&'static str, &'static [&'static str])> {
+        match self {
+            Style::Prose => bail!("Prose is generated from the grammar, not from scaffolding"),
+            Style::ShoppingList => Ok((SHOPPING_HEADER, SHOPPING_ITEMS)),
+            Style::Recipe => Ok((RECIPE_HEADER, RECIPE_STEPS)),
+        }
+    }
+
+    /// Lay out carrier `words` (with the tags each may take) as list lines.
+    /// Each line holds one word, or an adjective and the noun after it.
+    pub fn render<R: Rng + ?Sized>(&self, rng: &mut R, words: &[(String, Vec<Pos>)]) -> Result<String> {
+        let (header, items) = self.scaffolding()?;
+        let mut lines = vec![header.to_string()];
+        let mut i = 0;
+        while i < words.len() {
+            let pair = words[i].1.contains(&Pos::Adj) && words.get(i + 1).is_some_and(|(_, tags)| tags.contains(&Pos::N));
+            let take = if pair { 2 } else { 1 };
+            let item = words[i..i + take].iter().map(|(word, _)| word.as_str()).collect::<Vec<_>>().join(" ");
+            let quantity = rng.gen_range(2..=9).to_string();
+            let line = items
+                .choose(rng)
+                .expect("scaffolding is not empty")
+                .replace("{n}", &quantity)
+                .replace("{}", &item);
+            lines.push(match self {
+                Style::Recipe => format!("Step {}: {}", lines.len(), line),
+                _ => line,
+            });
+            i += take;
+        }
+        Ok(lines.join("\n"))
+    }
+
+    /// Read the carrier words back out of a [`render`](Self::render)ed list,
+    /// skipping the header, blank lines and all scaffolding.
+    pub fn extract(&self, text: &str) -> Result<Vec<String>> {
+        let (header, items) = self.scaffolding()?;
+        let mut words = Vec::new();
+        for (n, line) in text.lines().enumerate() {
+            let line = strip_markup(line);
+            let line = line.trim();
+            if line.is_empty() || line == header {
+                continue;
+            }
+            let body = match self {
+                Style::Recipe => strip_step_number(line),
+                _ => Some(line),
+            };
+            let slot = body.and_then(|body| {
+                items.iter().find_map(|item| {
+                    let (prefix, suffix) = item.split_once("{}")?;
+                    let slot = strip_quantity_prefix(body, prefix)?.strip_suffix(suffix)?;
+                    (!slot.trim().is_empty()).then_some(slot)
+                })
+            });
+            let Some(slot) = slot else {
+                bail!("Line {} does not match the scaffolding: '{}'", n + 1, line);
+            };
+            words.extend(slot.split_whitespace().map(normalize_token));
+        }
+        Ok(words)
+    }
+}
+
+/// `body` after `prefix`, where each `{n}` in the prefix stands for any
+/// number word (`7` or `seven`)
+fn strip_quantity_prefix<'a>(body: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut rest = body;
+    for (i, part) in prefix.split("{n}").enumerate() {
+        if i > 0 {
+            let end = rest.find(' ').unwrap_or(rest.len());
+            number_value(&rest[..end])?;
+            rest = &rest[end..];
+        }
+        rest = rest.strip_prefix(part)?;
+    }
+    Some(rest)
+}
+
+/// `line` without its leading `Step N: `
+fn strip_step_number(line: &str) -> Option<&str> {
+    let rest = line.strip_prefix("Step ")?;
+    let digits = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    rest[digits..].strip_prefix(": ")
+}
+
+/// The contraction of `a b` (both lowercase), if the generator's pair has one
+fn contract(a: &str, b: &str) -> Option<&'static str> {
+    CONTRACTIONS.iter().find(|(x, y, _)| *x == a && *y == b).map(|(_, _, joined)| *joined)
+}
+
+/// Whether a sample token is a contraction (possessive `'s` excluded)
+fn is_contraction(token: &str) -> bool {
+    let word = token
+        .trim_matches(|c: char| !c.is_ascii_alphabetic() && c != '\'')
+        .to_lowercase();
+    ["n't", "'re", "'ll", "'ve", "'m"].iter().any(|suffix| word.ends_with(suffix))
+        || CONTRACTIONS.iter().any(|(_, _, joined)| joined.to_lowercase() == word)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SAMPLE: &str = "Hey, I don't think so. Can you check it?\n\n\
+        It's fine! We're done, and the rest can wait. It is late.";
+
+    #[test]
+    fn test_from_text_measures_habits() {
+        let profile = StyleProfile::from_text(SAMPLE);
+        assert_eq!(profile.sentence_lengths.values().sum::<usize>(), 5);
+        assert_eq!(profile.sentence_lengths.get(&5), Some(&1));
+        assert!((profile.question_rate - 0.2).abs() < 1e-9);
+        assert!((profile.exclamation_rate - 0.2).abs() < 1e-9);
+        assert!((profile.commas_per_sentence - 0.4).abs() < 1e-9);
+        // don't, it's, we're contracted; "it is" not
+        assert!((profile.contraction_rate - 0.75).abs() < 1e-9);
+        assert!((profile.paragraph_sentences - 2.5).abs() < 1e-9);
+        assert!(profile.length_weight(5) > profile.length_weight(15));
+    }
+
+    #[test]
+    fn test_apply_keeps_payload_words_and_sentences() {
+        let profile = StyleProfile {
+            sentence_lengths: [(4, 1)].into_iter().collect(),
+            question_rate: 1.0,
+            contraction_rate: 1.0,
+            commas_per_sentence: 1.0,
+            paragraph_sentences: 1.0,
+            ..Default::default()
+        };
+        let wordlist: HashSet<String> = ["not", "ivory"].iter().map(|w| w.to_string()).collect();
+        let text = "It is |ivory| and calm. They do not wait. It is late.";
+        let mut rng = StdRng::seed_from_u64(1);
+        let out = profile.apply(&mut rng, text, &wordlist, true);
+
+        assert_eq!(out, "It's |ivory|, and calm?\n\nThey do not wait?\n\nIt's late?");
+        let payload = crate::decode::extract_payload_words(&out, &wordlist);
+        assert_eq!(payload, vec!["ivory".to_string(), "not".to_string()]);
+        assert_eq!(StyleProfile::default().apply(&mut rng, text, &wordlist, true), text);
+    }
+
+    #[test]
+    fn test_list_styles_round_trip_past_scaffolding() {
+        // "step", "add" and "fresh" are wordlist words in the scaffolding's way
+        let words: Vec<(String, Vec<Pos>)> = [
+            ("fresh", vec![Pos::Adj]),
+            ("apple", vec![Pos::N]),
+            ("step", vec![Pos::N, Pos::V]),
+            ("add", vec![Pos::V]),
+            ("ivory", vec![Pos::Adj]),
+        ]
+        .into_iter()
+        .map(|(w, tags)| (w.to_string(), tags))
+        .collect();
+        let expected: Vec<String> = words.iter().map(|(w, _)| w.clone()).collect();
+        let mut rng = StdRng::seed_from_u64(4);
+
+        for style in [Style::ShoppingList, Style::Recipe] {
+            let text = style.render(&mut rng, &words).unwrap();
+            // The adjective pairs with the noun after it
+            assert_eq!(text.lines().count(), 5, "{}", text);
+            assert!(text.lines().nth(1).unwrap().contains("fresh apple"));
+            assert_eq!(style.extract(&text).unwrap(), expected, "{}", text);
+        }
+        let recipe = Style::Recipe.render(&mut rng, &words).unwrap();
+        assert!(recipe.lines().nth(4).unwrap().starts_with("Step 4: "));
+        assert!(Style::Recipe.extract("Method:\nStep 1: Eat the apple.").is_err());
+        let spelled = "Shopping list:\n- Seven tins of ivory\n- 4 cups of fresh apple";
+        assert_eq!(Style::ShoppingList.extract(spelled).unwrap(), ["ivory", "fresh", "apple"]);
+        assert!(Style::Prose.render(&mut rng, &words).is_err());
+    }
+
+    #[test]
+    fn test_scaffolding_prefixes_are_unambiguous() {
+        for items in [SHOPPING_ITEMS, RECIPE_STEPS] {
+            let prefixes: Vec<&str> = items.iter().map(|item| item.split_once("{}").unwrap().0).collect();
+            for (i, a) in prefixes.iter().enumerate() {
+                for (j, b) in prefixes.iter().enumerate() {
+                    assert!(i == j || !b.starts_with(a), "'{}' is a prefix of '{}'", a, b);
+                }
+            }
+        }
+    }
+}
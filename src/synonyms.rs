@@ -0,0 +1,260 @@
+//! Synonym choice as a side channel.
+//!
+//! Cover words carry no payload, so the generator could as well have written
+//! "perhaps" where it wrote "maybe". A [`SynonymTable`] ranks the members of
+//! each synonym set in sorted order; choosing the member of a `2^k`-word set
+//! whose rank is a `k`-bit value hides those bits without adding a word.
+//!
+//! Framing: a length byte, then the payload bytes, most significant bit
+//! first. Table words after the last payload bit are chosen at random. Every
+//! table word in the text is read back as bits, so the encoder and decoder
+//! must use the same table (and the same wordlist, which removes sets that
+//! contain payload words).
+
+use crate::decode::{normalize_token, strip_markup};
+use anyhow::{bail, Result};
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// Largest side-channel payload, set by the one-byte length prefix
+pub const MAX_PAYLOAD_BYTES: usize = u8::MAX as usize;
+
+/// Bits of the length prefix
+const LENGTH_BITS: usize = 8;
+
+/// Ranked synonym sets.
+#[derive(Clone, Debug, Default)]
+pub struct SynonymTable {
+    /// Each set's members, sorted
+    sets: Vec<Vec<String>>,
+    /// Word -> (set, rank)
+    ranks: HashMap<String, (usize, usize)>,
+}
+
+impl SynonymTable {
+    /// Parse a table with one set of synonyms per line, separated by
+    /// whitespace. Blank lines and lines starting with `#` are ignored. Every
+    /// set must have a power-of-two number of members (at least 2), and a
+    /// word may belong to only one set.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut sets: Vec<Vec<String>> = Vec::new();
+        let mut seen: HashMap<String, usize> = HashMap::new();
+        for (n, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let set: Vec<String> = line.split_whitespace().map(str::to_lowercase).collect();
+            for word in &set {
+                if let Some(first) = seen.insert(word.clone(), n + 1) {
+                    bail!("line {}: '{}' is already listed on line {}", n + 1, word, first);
+                }
+            }
+            if set.len() < 2 || !set.len().is_power_of_two() {
+                bail!("line {}: a synonym set needs 2, 4, 8, ... words, found {}", n + 1, set.len());
+            }
+            sets.push(set);
+        }
+        Ok(Self::from_sets(sets))
+    }
+
+    fn from_sets(mut sets: Vec<Vec<String>>) -> Self {
+        let mut ranks = HashMap::new();
+        for (index, set) in sets.iter_mut().enumerate() {
+            set.sort();
+            for (rank, word) in set.iter().enumerate() {
+                ranks.insert(word.clone(), (index, rank));
+            }
+        }
+        Self { sets, ranks }
+    }
+
+    /// The sets that contain no `wordlist` word
+    pub fn without(&self, wordlist: &HashSet<String>) -> Self {
+        let sets = self
+            .sets
+            .iter()
+            .filter(|set| !set.iter().any(|word| wordlist.contains(word)))
+            .cloned()
+            .collect();
+        Self::from_sets(sets)
+    }
+
+    pub fn len(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sets.is_empty()
+    }
+
+    /// The set holding `token` and the token's rank in it
+    fn lookup(&self, token: &str) -> Option<(&[String], usize)> {
+        let (set, rank) = self.ranks.get(&normalize_token(token))?;
+        Some((&self.sets[*set], *rank))
+    }
+
+    /// Side-channel bits the table words in `text` can carry
+    pub fn capacity(&self, text: &str) -> usize {
+        text.split_whitespace()
+            .filter_map(|token| self.lookup(token))
+            .map(|(set, _)| set_bits(set))
+            .sum()
+    }
+
+    /// Rewrite the table words in `text` so their ranks spell out `payload`.
+    /// Whitespace, capitalization and punctuation are kept.
+    pub fn embed<R: Rng + ?Sized>(&self, rng: &mut R, text: &str, payload: &[u8]) -> Result<String> {
+        let bits = frame_bits(payload)?;
+        let capacity = self.capacity(text);
+        if bits.len() > capacity {
+            bail!(
+                "The synonym payload needs {} bits but the cover text's synonyms carry only {}",
+                bits.len(),
+                capacity
+            );
+        }
+
+        let mut next = bits.into_iter();
+        Ok(map_tokens(text, |token| {
+            let (set, _) = self.lookup(token)?;
+            let rank = (0..set_bits(set)).fold(0, |rank, _| {
+                let bit = next.next().unwrap_or_else(|| rng.gen());
+                (rank << 1) | bit as usize
+            });
+            Some(replace_word(token, &set[rank]))
+        }))
+    }
+
+    /// Read back a payload written by [`embed`](Self::embed).
+    pub fn extract(&self, text: &str) -> Result<Vec<u8>> {
+        let mut bits: Vec<bool> = Vec::new();
+        for (set, rank) in text.split_whitespace().filter_map(|token| self.lookup(token)) {
+            bits.extend((0..set_bits(set)).rev().map(|i| (rank >> i) & 1 == 1));
+        }
+        unframe_bits(&bits)
+    }
+}
+
+/// A side payload as bits: its length byte, then its bytes, most significant
+/// bit first. Shared with the number channel.
+pub(crate) fn frame_bits(payload: &[u8]) -> Result<Vec<bool>> {
+    if payload.len() > MAX_PAYLOAD_BYTES {
+        bail!("A side payload holds at most {} bytes, got {}", MAX_PAYLOAD_BYTES, payload.len());
+    }
+    Ok(std::iter::once(payload.len() as u8)
+        .chain(payload.iter().copied())
+        .flat_map(|byte| (0..8).rev().map(move |i| (byte >> i) & 1 == 1))
+        .collect())
+}
+
+/// The payload of [`frame_bits`]; bits after it are ignored
+pub(crate) fn unframe_bits(bits: &[bool]) -> Result<Vec<u8>> {
+    let byte = |start: usize| bits[start..start + 8].iter().fold(0u8, |acc, &bit| (acc << 1) | bit as u8);
+    if bits.len() < LENGTH_BITS {
+        bail!("The text carries {} side-channel bits, too few for a payload", bits.len());
+    }
+    let len = byte(0) as usize;
+    if bits.len() < LENGTH_BITS + 8 * len {
+        bail!("The side payload claims {} bytes but the text carries only {} bits", len, bits.len());
+    }
+    Ok((0..len).map(|i| byte(LENGTH_BITS + 8 * i)).collect())
+}
+
+/// Bits carried by choosing a member of `set` (its size is a power of two)
+fn set_bits(set: &[String]) -> usize {
+    set.len().trailing_zeros() as usize
+}
+
+/// Apply `f` to each whitespace-separated token, keeping the whitespace;
+/// `None` leaves the token as it is.
+pub(crate) fn map_tokens(text: &str, mut f: impl FnMut(&str) -> Option<String>) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while !rest.is_empty() {
+        let token_start = rest.len() - rest.trim_start().len();
+        out.push_str(&rest[..token_start]);
+        rest = &rest[token_start..];
+        let token_end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+        let token = &rest[..token_end];
+        match f(token) {
+            Some(replaced) => out.push_str(&replaced),
+            None => out.push_str(token),
+        }
+        rest = &rest[token_end..];
+    }
+    out
+}
+
+/// `token` with its word swapped for `word`, keeping surrounding punctuation
+/// and a leading capital
+fn replace_word(token: &str, word: &str) -> String {
+    let token = strip_markup(token);
+    let start = token.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(0);
+    let end = token.rfind(|c: char| c.is_ascii_alphabetic()).map_or(token.len(), |i| i + 1);
+    let capital = token[start..].starts_with(|c: char| c.is_ascii_uppercase());
+    let mut replaced = token[..start].to_string();
+    let mut chars = word.chars();
+    if let (true, Some(first)) = (capital, chars.next()) {
+        replaced.extend(first.to_uppercase());
+        replaced.push_str(chars.as_str());
+    } else {
+        replaced.push_str(word);
+    }
+    replaced.push_str(&token[end..]);
+    replaced
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const TABLE: &str = "# test\nmaybe perhaps\nbig huge large vast\n\nmainly mostly\n";
+
+    #[test]
+    fn test_embed_round_trips_and_keeps_layout() {
+        let table = SynonymTable::parse(TABLE).unwrap();
+        let text = "Maybe the big dog, mainly brown, sleeps.\n\nPerhaps a large cat waits. \
+                    The vast sea is mostly calm; maybe not. A huge bird, perhaps.";
+        assert_eq!(table.capacity(text), 14);
+
+        // One length byte and one data byte need 16 bits
+        let mut rng = StdRng::seed_from_u64(3);
+        let err = table.embed(&mut rng, text, b"a").unwrap_err();
+        assert!(err.to_string().contains("needs 16 bits"), "{}", err);
+
+        let short = "Maybe the big dog. Perhaps mainly a vast sea. Mostly huge, maybe large. \
+                     The big hill is mainly grass; perhaps huge.";
+        let out = table.embed(&mut rng, short, b"a").unwrap();
+        assert_eq!(table.extract(&out).unwrap(), b"a".to_vec());
+        // Only words change: punctuation and spacing stay put
+        let shape = |s: &str| s.replace(|c: char| c.is_ascii_alphabetic(), "");
+        assert_eq!(shape(&out), shape(short));
+        assert!(out.starts_with(|c: char| c.is_uppercase()));
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_sets_and_skips_wordlist_sets() {
+        assert!(SynonymTable::parse("big large huge").is_err());
+        assert!(SynonymTable::parse("big large\nlarge vast").is_err());
+
+        // Ranks follow sorted order, not line order
+        let a = SynonymTable::parse("perhaps maybe").unwrap();
+        let b = SynonymTable::parse("maybe perhaps").unwrap();
+        assert_eq!(a.lookup("Perhaps,").map(|(_, r)| r), Some(1));
+        assert_eq!(a.lookup("perhaps"), b.lookup("perhaps"));
+
+        let wordlist: HashSet<String> = ["large".to_string()].into_iter().collect();
+        let table = SynonymTable::parse(TABLE).unwrap().without(&wordlist);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.capacity("big maybe"), 1);
+    }
+
+    #[test]
+    fn test_shipped_table_is_valid() {
+        let table = SynonymTable::parse(include_str!("../languages/english/synonyms.txt")).unwrap();
+        assert!(!table.is_empty());
+    }
+}
@@ -0,0 +1,306 @@
+//! User-supplied sentence templates with typed blanks.
+//!
+//! A template is a line of text with blanks such as `{N}` or `{Adj}`:
+//!
+//! ```text
+//! I saw a {N} near the {N} and felt {Adj}.
+//! ```
+//!
+//! [`TemplateSet::fill`] cycles through the templates, putting each carrier
+//! word into the next blank of a tag it can take and a cover word into every
+//! other blank. [`TemplateSet::extract`] walks the same templates over the
+//! text and reads only the blanks, so the fixed text may contain anything,
+//! wordlist words included.
+
+use crate::decode::normalize_token;
+use crate::types::Pos;
+use anyhow::{anyhow, bail, Result};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use std::collections::{HashMap, HashSet};
+
+/// One whitespace-separated token of a template.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Piece {
+    /// Fixed text, written as is
+    Text(String),
+    /// A blank for a word of this tag, with the punctuation around it
+    Blank { pos: Pos, before: String, after: String },
+}
+
+/// A single template line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Template {
+    pieces: Vec<Piece>,
+}
+
+impl Template {
+    /// Parse one template. Each blank is a tag name in braces, alone in its
+    /// token apart from punctuation (`{N},` or `"{Adj}"`), and a template
+    /// needs at least one blank.
+    pub fn parse(line: &str) -> Result<Self> {
+        let mut pieces = Vec::new();
+        for token in line.split_whitespace() {
+            let Some(open) = token.find('{') else {
+                if token.contains('}') {
+                    bail!("Unmatched '}}' in '{}'", token);
+                }
+                pieces.push(Piece::Text(token.to_string()));
+                continue;
+            };
+            let close = token[open..].find('}').map(|i| open + i).ok_or_else(|| anyhow!("Unclosed '{{' in '{}'", token))?;
+            let (before, tag, after) = (&token[..open], &token[open + 1..close], &token[close + 1..]);
+            if before.chars().chain(after.chars()).any(|c| c.is_alphanumeric() || c == '{' || c == '}') {
+                bail!("A blank must be a separate word, apart from punctuation: '{}'", token);
+            }
+            let pos = Pos::from_name(tag).ok_or_else(|| anyhow!("Unknown tag '{{{}}}' in '{}'", tag, token))?;
+            pieces.push(Piece::Blank { pos, before: before.to_string(), after: after.to_string() });
+        }
+        if !pieces.iter().any(|piece| matches!(piece, Piece::Blank { .. })) {
+            bail!("Template has no blanks: '{}'", line.trim());
+        }
+        Ok(Self { pieces })
+    }
+
+    pub fn pieces(&self) -> &[Piece] {
+        &self.pieces
+    }
+
+    /// Tags of the blanks, in order
+    pub fn blanks(&self) -> impl Iterator<Item = Pos> + '_ {
+        self.pieces.iter().filter_map(|piece| match piece {
+            Piece::Blank { pos, .. } => Some(*pos),
+            Piece::Text(_) => None,
+        })
+    }
+}
+
+/// Templates used in turn, first to last and around again.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateSet {
+    templates: Vec<Template>,
+}
+
+/// Which payload word (if any) fills each blank of one template
+type Fill = (usize, Vec<Option<usize>>);
+
+impl TemplateSet {
+    /// Parse one template per line. Blank lines and lines starting with `#`
+    /// are ignored; errors name the line.
+    pub fn parse(content: &str) -> Result<Self> {
+        let mut templates = Vec::new();
+        for (n, line) in content.lines().enumerate() {
+            if line.trim().is_empty() || line.trim_start().starts_with('#') {
+                continue;
+            }
+            templates.push(Template::parse(line).map_err(|e| anyhow!("line {}: {}", n + 1, e))?);
+        }
+        if templates.is_empty() {
+            bail!("No templates found");
+        }
+        Ok(Self { templates })
+    }
+
+    pub fn templates(&self) -> &[Template] {
+        &self.templates
+    }
+
+    /// Blanks in one pass through every template
+    pub fn blanks_per_cycle(&self) -> usize {
+        self.templates.iter().map(|template| template.blanks().count()).sum()
+    }
+
+    /// Most payload bits one pass through the templates can carry, when
+    /// every blank takes a carrier word from a `wordlist_len`-word list
+    pub fn capacity_bits(&self, wordlist_len: usize) -> f64 {
+        self.blanks_per_cycle() as f64 * (wordlist_len as f64).log2()
+    }
+
+    /// Number of template lines needed to carry `payload` (words with the
+    /// tags each may fill)
+    pub fn templates_needed(&self, payload: &[(String, Vec<Pos>)]) -> Result<usize> {
+        Ok(self.plan(payload)?.len())
+    }
+
+    /// Assign payload words to blanks, greedily and in order.
+    fn plan(&self, payload: &[(String, Vec<Pos>)]) -> Result<Vec<Fill>> {
+        let tags: HashSet<Pos> = self.templates.iter().flat_map(Template::blanks).collect();
+        if let Some((word, _)) = payload.iter().find(|(_, allowed)| !allowed.iter().any(|pos| tags.contains(pos))) {
+            bail!("'{}' fits no blank in the templates", word);
+        }
+
+        let mut fills = Vec::new();
+        let mut next = 0;
+        for (index, template) in self.templates.iter().enumerate().cycle() {
+            if next == payload.len() {
+                break;
+            }
+            let blanks = template
+                .blanks()
+                .map(|pos| {
+                    let fits = payload.get(next).is_some_and(|(_, allowed)| allowed.contains(&pos));
+                    fits.then(|| {
+                        next += 1;
+                        next - 1
+                    })
+                })
+                .collect();
+            fills.push((index, blanks));
+        }
+        Ok(fills)
+    }
+
+    /// Write `payload` into the templates, with words from `cover` in the
+    /// blanks the payload doesn't use. Cover words must not be wordlist words.
+    pub fn fill<R: Rng + ?Sized>(
+        &self,
+        rng: &mut R,
+        payload: &[(String, Vec<Pos>)],
+        cover: &HashMap<Pos, Vec<String>>,
+    ) -> Result<String> {
+        let mut lines = Vec::new();
+        for (index, blanks) in self.plan(payload)? {
+            let mut blanks = blanks.into_iter();
+            let mut tokens = Vec::new();
+            for piece in &self.templates[index].pieces {
+                let token = match piece {
+                    Piece::Text(text) => text.clone(),
+                    Piece::Blank { pos, before, after } => {
+                        let word = match blanks.next().flatten() {
+                            Some(i) => payload[i].0.clone(),
+                            None => cover
+                                .get(pos)
+                                .and_then(|words| words.choose(rng))
+                                .cloned()
+                                .ok_or_else(|| anyhow!("No cover word for a {{{}}} blank", pos.name()))?,
+                        };
+                        let word = if tokens.is_empty() { capitalize(&word) } else { word };
+                        format!("{}{}{}", before, word, after)
+                    }
+                };
+                tokens.push(token);
+            }
+            lines.push(tokens.join(" "));
+        }
+        Ok(lines.join(" "))
+    }
+
+    /// Read the carrier words out of the blanks of a [`fill`](Self::fill)ed
+    /// text, mapping tokens through `corrections` first. The fixed text must
+    /// match the templates (ignoring case and punctuation).
+    pub fn extract(
+        &self,
+        text: &str,
+        wordlist: &HashSet<String>,
+        corrections: &HashMap<String, String>,
+    ) -> Result<Vec<String>> {
+        let tokens: Vec<&str> = text.split_whitespace().collect();
+        let mut words = Vec::new();
+        let mut at = 0;
+        for (index, template) in self.templates.iter().enumerate().cycle() {
+            if at == tokens.len() {
+                break;
+            }
+            for piece in &template.pieces {
+                let token = tokens
+                    .get(at)
+                    .ok_or_else(|| anyhow!("The text ends partway through template {}", index + 1))?;
+                match piece {
+                    Piece::Text(text) if normalize_token(text) != normalize_token(token) => {
+                        bail!("Word {} ('{}') does not match template {} ('{}')", at + 1, token, index + 1, text);
+                    }
+                    Piece::Text(_) => {}
+                    Piece::Blank { .. } => {
+                        let word = normalize_token(token);
+                        let word = corrections.get(&word).cloned().unwrap_or(word);
+                        if wordlist.contains(&word) {
+                            words.push(word);
+                        }
+                    }
+                }
+                at += 1;
+            }
+        }
+        Ok(words)
+    }
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const TEMPLATES: &str = "# Field notes\n\
+        I saw a {N} near the {N} and felt {Adj}.\n\
+        \n\
+        {Adv}, the abandon was \"{Adj}\".\n";
+
+    fn tagged(words: &[(&str, &[Pos])]) -> Vec<(String, Vec<Pos>)> {
+        words.iter().map(|(w, tags)| (w.to_string(), tags.to_vec())).collect()
+    }
+
+    fn cover() -> HashMap<Pos, Vec<String>> {
+        [(Pos::N, "lamp"), (Pos::Adj, "calm"), (Pos::Adv, "slowly")]
+            .into_iter()
+            .map(|(pos, word)| (pos, vec![word.to_string()]))
+            .collect()
+    }
+
+    #[test]
+    fn test_parse_and_capacity() {
+        let set = TemplateSet::parse(TEMPLATES).unwrap();
+        assert_eq!(set.templates().len(), 2);
+        assert_eq!(set.blanks_per_cycle(), 5);
+        assert!((set.capacity_bits(2048) - 55.0).abs() < 1e-9);
+        assert_eq!(
+            set.templates()[1].pieces()[4],
+            Piece::Blank { pos: Pos::Adj, before: "\"".to_string(), after: "\".".to_string() }
+        );
+
+        assert!(TemplateSet::parse("no blanks here").is_err());
+        assert!(TemplateSet::parse("a {Noun} here").unwrap_err().to_string().contains("line 1"));
+        assert!(TemplateSet::parse("a {N}s here").is_err());
+        assert!(TemplateSet::parse("# only comments\n").is_err());
+    }
+
+    #[test]
+    fn test_fill_round_trips_positionally() {
+        let set = TemplateSet::parse(TEMPLATES).unwrap();
+        let payload = tagged(&[
+            ("zoo", &[Pos::N]),
+            ("brave", &[Pos::Adj]),
+            ("quickly", &[Pos::Adv]),
+            ("ivory", &[Pos::N, Pos::Adj]),
+            ("zebra", &[Pos::N]),
+        ]);
+        assert_eq!(set.templates_needed(&payload).unwrap(), 3);
+
+        let mut rng = StdRng::seed_from_u64(5);
+        let text = set.fill(&mut rng, &payload, &cover()).unwrap();
+        assert_eq!(
+            text,
+            "I saw a zoo near the lamp and felt brave. Quickly, the abandon was \"ivory\". \
+             I saw a zebra near the lamp and felt calm."
+        );
+
+        // "abandon" is a wordlist word in the fixed text; only blanks are read
+        let wordlist: HashSet<String> =
+            ["zoo", "brave", "quickly", "ivory", "zebra", "abandon"].iter().map(|w| w.to_string()).collect();
+        let words = set.extract(&text, &wordlist, &HashMap::new()).unwrap();
+        assert_eq!(words, payload.iter().map(|(w, _)| w.clone()).collect::<Vec<_>>());
+
+        assert!(set.extract("I saw a zoo near", &wordlist, &HashMap::new()).is_err());
+        assert!(set.extract("You saw a zoo", &wordlist, &HashMap::new()).is_err());
+        let misfit = tagged(&[("run", &[Pos::V])]);
+        assert!(set.fill(&mut rng, &misfit, &cover()).is_err());
+    }
+}
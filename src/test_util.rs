@@ -0,0 +1,11 @@
+//! Fixtures shared by the unit tests.
+
+/// 2048 distinct words (`waaa`, `waab`, ...) in canonical sorted order.
+/// Full-size, so checksum collisions are rare, and alphabetic, since
+/// cover-text decoding strips digits from tokens.
+pub fn alpha_wordlist() -> Vec<String> {
+    let letter = |n: usize| (b'a' + (n % 26) as u8) as char;
+    (0..2048)
+        .map(|i| format!("w{}{}{}", letter(i / 676), letter(i / 26), letter(i)))
+        .collect()
+}
@@ -0,0 +1,155 @@
+//! Sentences and tokens as glossia hands them out.
+//!
+//! [`GrammarChecker::tokenize`](crate::GrammarChecker::tokenize) used to
+//! return nlprule's own types, so every caller was written against one
+//! nlprule version. It now copies them into the owned [`Sentence`],
+//! [`Token`] and [`Tag`] below, which keep what the tools use (text, byte
+//! spans into the input, lemmas and tags) and map the Penn Treebank tags
+//! nlprule assigns onto the grammar's [`Pos`].
+
+use crate::types::Pos;
+use std::ops::Range;
+
+/// A sentence of the tokenized text
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sentence {
+    /// The sentence as it appears in the input
+    pub text: String,
+    /// Byte range of the sentence in the input
+    pub span: Range<usize>,
+    pub tokens: Vec<Token>,
+}
+
+/// A word or punctuation mark
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    /// Byte range of the token in the input
+    pub span: Range<usize>,
+    /// Every reading the tagger allows, most likely first
+    pub tags: Vec<Tag>,
+}
+
+/// One reading of a token
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Tag {
+    /// Penn Treebank tag (`NN`, `VBZ`, `JJ`, ...)
+    pub tag: String,
+    /// Dictionary form (`run` for `running`); empty if the tagger has none
+    pub lemma: String,
+}
+
+/// The grammar tag a Penn Treebank tag corresponds to. `Aux`, `Cop`, `Dot`
+/// and `Prefix` are the grammar's own and no tag maps to them; pronouns and
+/// interjections have no grammar slot.
+pub fn penn_to_pos(tag: &str) -> Option<Pos> {
+    Some(match tag {
+        "NN" | "NNS" | "NNP" | "NNPS" => Pos::N,
+        "VB" | "VBD" | "VBG" | "VBN" | "VBP" | "VBZ" => Pos::V,
+        "JJ" | "JJR" | "JJS" => Pos::Adj,
+        "RB" | "RBR" | "RBS" => Pos::Adv,
+        "IN" => Pos::Prep,
+        "DT" => Pos::Det,
+        "CC" => Pos::Conj,
+        "MD" => Pos::Modal,
+        "TO" => Pos::To,
+        _ => return None,
+    })
+}
+
+impl Tag {
+    /// The grammar tag of this reading ([`penn_to_pos`])
+    pub fn pos(&self) -> Option<Pos> {
+        penn_to_pos(&self.tag)
+    }
+}
+
+impl Token {
+    /// The grammar tags of the token's readings, without repeats
+    pub fn pos(&self) -> Vec<Pos> {
+        let mut out = Vec::new();
+        for pos in self.tags.iter().filter_map(Tag::pos) {
+            if !out.contains(&pos) {
+                out.push(pos);
+            }
+        }
+        out
+    }
+
+    /// The lemma of the most likely reading that has one, else the text
+    pub fn lemma(&self) -> &str {
+        self.tags.iter().map(|tag| tag.lemma.as_str()).find(|lemma| !lemma.is_empty()).unwrap_or(&self.text)
+    }
+}
+
+#[cfg(feature = "grammar")]
+impl From<&nlprule::types::Sentence<'_>> for Sentence {
+    fn from(sentence: &nlprule::types::Sentence<'_>) -> Self {
+        Sentence {
+            text: sentence.text().to_string(),
+            span: sentence.span().byte().clone(),
+            tokens: sentence.tokens().iter().map(Token::from).collect(),
+        }
+    }
+}
+
+#[cfg(feature = "grammar")]
+impl From<&nlprule::types::Token<'_>> for Token {
+    fn from(token: &nlprule::types::Token<'_>) -> Self {
+        let word = token.word();
+        Token {
+            text: word.text().as_str().to_string(),
+            span: token.span().byte().clone(),
+            tags: word
+                .tags()
+                .iter()
+                .map(|data| Tag { tag: data.pos().as_str().to_string(), lemma: data.lemma().as_str().to_string() })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(text: &str, tags: &[(&str, &str)]) -> Token {
+        Token {
+            text: text.to_string(),
+            span: 0..text.len(),
+            tags: tags.iter().map(|(tag, lemma)| Tag { tag: tag.to_string(), lemma: lemma.to_string() }).collect(),
+        }
+    }
+
+    #[test]
+    fn test_token_pos_and_lemma() {
+        let running = token("running", &[("VBG", "run"), ("NN", "running"), ("VBG", "run")]);
+        assert_eq!(running.pos(), vec![Pos::V, Pos::N]);
+        assert_eq!(running.lemma(), "run");
+        let she = token("she", &[("PRP", "")]);
+        assert!(she.pos().is_empty());
+        assert_eq!(she.lemma(), "she");
+        assert_eq!(penn_to_pos("MD"), Some(Pos::Modal));
+    }
+
+    #[cfg(feature = "grammar")]
+    #[test]
+    fn test_tokenize_spans() {
+        // Skip if the model files are not available
+        let Ok(checker) = crate::GrammarChecker::global(crate::Language::English) else {
+            return;
+        };
+        let text = "The cat sat. Dogs run fast.";
+        let sentences: Vec<Sentence> = checker.tokenize(text).collect();
+        assert_eq!(sentences.len(), 2);
+        for sentence in &sentences {
+            assert_eq!(&text[sentence.span.clone()], sentence.text);
+            for token in &sentence.tokens {
+                assert_eq!(&text[token.span.clone()], token.text);
+            }
+        }
+        let dogs = sentences[1].tokens.iter().find(|token| token.text == "Dogs").unwrap();
+        assert_eq!(dogs.lemma().to_lowercase(), "dog");
+        assert!(dogs.pos().contains(&Pos::N));
+    }
+}
@@ -0,0 +1,141 @@
+//! The vocabulary of a conversation, for replies that stay on its subject.
+//!
+//! A reply about drawer handles in a thread about fishing stands out more
+//! than any word choice. A [`Topic`] counts the content words of the
+//! messages before the reply (words outside the most common few hundred of
+//! English), with later messages counting more, and credits each word's
+//! base forms too, so "fishing" in the thread also makes "fish" topical.
+//! A content word right after a determiner and not followed by another
+//! ("the ramp is", not "the boat ramp") is taken as one of the thread's
+//! [`nouns`](Topic::nouns), which the generator may add to its cover words. The generator then prefers topical cover words; payload
+//! words are not touched, so decoding never needs the thread.
+
+use crate::decode::normalize_token;
+use crate::frequency;
+use crate::lemma::{Lemmatizer, RuleLemmatizer};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use std::collections::{BTreeSet, HashMap};
+
+/// Words ranked this high or higher in the frequency list are too common to
+/// say anything about the subject
+pub const COMMON_RANK: usize = 200;
+
+/// Weight of each message relative to the one after it
+pub const RECENCY: f64 = 0.7;
+
+/// Weight of a base form relative to the word it was found in
+const LEMMA_WEIGHT: f64 = 0.5;
+
+/// Words that start a noun phrase
+const DETERMINERS: &[&str] = &["a", "an", "the", "this", "that", "these", "those", "my", "your", "our", "their", "his", "her"];
+
+/// Content-word weights of the messages in a thread.
+#[derive(Clone, Debug, Default)]
+pub struct Topic {
+    weights: HashMap<String, f64>,
+    nouns: BTreeSet<String>,
+}
+
+impl Topic {
+    /// The topic of `messages`, oldest first.
+    pub fn from_messages(messages: &[&str]) -> Self {
+        let common = frequency::english();
+        let is_content = |word: &str| word.len() >= 3 && common.rank(word) > COMMON_RANK;
+        let mut weights: HashMap<String, f64> = HashMap::new();
+        let mut nouns = BTreeSet::new();
+        for (age, message) in messages.iter().rev().enumerate() {
+            let weight = RECENCY.powi(age as i32);
+            let words: Vec<String> = message.split_whitespace().map(normalize_token).collect();
+            for (i, word) in words.iter().enumerate() {
+                if !is_content(word) {
+                    continue;
+                }
+                // "the <word>", not "the <word> <content word>"
+                let after_determiner = i > 0 && DETERMINERS.contains(&words[i - 1].as_str());
+                if after_determiner && !words.get(i + 1).is_some_and(|next| is_content(next)) {
+                    nouns.insert(word.clone());
+                }
+                for lemma in RuleLemmatizer.lemmas(word) {
+                    *weights.entry(lemma).or_insert(0.0) += LEMMA_WEIGHT * weight;
+                }
+                *weights.entry(word.clone()).or_insert(0.0) += weight;
+            }
+        }
+        Self { weights, nouns }
+    }
+
+    /// Whether the messages had no content words
+    pub fn is_empty(&self) -> bool {
+        self.weights.is_empty()
+    }
+
+    /// How topical `word` is; 0 for words the thread doesn't use
+    pub fn weight(&self, word: &str) -> f64 {
+        self.weights.get(&normalize_token(word)).copied().unwrap_or(0.0)
+    }
+
+    /// Topical words the thread uses as nouns, alphabetically
+    pub fn nouns(&self) -> impl Iterator<Item = &str> {
+        self.nouns.iter().map(String::as_str)
+    }
+
+    /// The `n` most topical words, most topical first
+    pub fn top(&self, n: usize) -> Vec<&str> {
+        let mut words: Vec<(&String, &f64)> = self.weights.iter().collect();
+        words.sort_by(|a, b| b.1.total_cmp(a.1).then_with(|| a.0.cmp(b.0)));
+        words.into_iter().take(n).map(|(word, _)| word.as_str()).collect()
+    }
+
+    /// Pick one of `candidates` weighted by [`weight`](Self::weight); `None`
+    /// if none of them is topical.
+    pub fn choose<'a, R: Rng + ?Sized>(&self, rng: &mut R, candidates: &[&'a str]) -> Option<&'a str> {
+        let weights = WeightedIndex::new(candidates.iter().map(|word| self.weight(word))).ok()?;
+        Some(candidates[weights.sample(rng)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const THREAD: [&str; 3] = [
+        "Heading to the lake on Saturday, anyone know if the pike are biting?",
+        "Pike were slow last week. Try the river by the bridge, the trout are everywhere.",
+        "Trout it is. Is the bridge parking still free?",
+    ];
+
+    #[test]
+    fn test_topic_weighs_recent_content_words() {
+        let topic = Topic::from_messages(&THREAD);
+        // Common words are not topical
+        for word in ["the", "are", "know", "is"] {
+            assert_eq!(topic.weight(word), 0.0, "{}", word);
+        }
+        assert!(topic.weight("Trout") > topic.weight("pike"));
+        assert!(topic.weight("pike") > topic.weight("lake"));
+        // "biting" credits "bite"
+        assert!(topic.weight("bite") > 0.0);
+        // Both in the last two messages; ties go alphabetically
+        assert_eq!(topic.top(2), ["bridge", "trout"]);
+        // "the bridge," counts; "Saturday" doesn't, nor either word of "the bridge parking"
+        assert_eq!(topic.nouns().collect::<Vec<_>>(), ["bridge", "lake", "pike", "river", "trout"]);
+        assert_eq!(Topic::from_messages(&["the bridge parking"]).nouns().count(), 0);
+        assert!(Topic::from_messages(&["It is what it is."]).is_empty());
+    }
+
+    #[test]
+    fn test_choose_prefers_topical_words() {
+        let topic = Topic::from_messages(&THREAD);
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for _ in 0..1000 {
+            *counts.entry(topic.choose(&mut rng, &["lake", "trout", "drawer"]).unwrap()).or_insert(0) += 1;
+        }
+        assert!(counts["trout"] > counts["lake"], "{:?}", counts);
+        assert!(!counts.contains_key("drawer"));
+        assert!(topic.choose(&mut rng, &["drawer", "handle"]).is_none());
+    }
+}
@@ -0,0 +1,443 @@
+//! Fitting cover text to what messengers do to a message.
+//!
+//! A cover text pasted into a chat client doesn't always arrive as written:
+//! Matrix clients render Markdown, so `*word*` turns bold and a line starting
+//! `- ` becomes a bullet with its dash gone; most clients turn anything that
+//! looks like a domain into a link and fetch a preview of it, which is a
+//! request to a server nobody meant to contact; and long messages are cut off
+//! or sent as attachments. LoRa meshes and serial links, often bridged over
+//! MQTT, carry a couple of hundred bytes a message ([`Platform::Mesh`]). DNS
+//! carries text in TXT records of quoted strings of at most 255 bytes each,
+//! which resolvers concatenate, as DKIM keys are published
+//! ([`Platform::DnsTxt`]). Nostr notes are JSON strings on relays that
+//! refuse large events ([`Platform::Nostr`]). [`encode_for_platform`] escapes what would be
+//! rendered, breaks what would be linked, and splits the text into messages
+//! that fit, tagged `(1/3)`, `(2/3)`, ... when there is more than one.
+//! [`reassemble`] puts the messages back in order and undoes the rest.
+//!
+//! Tags, escapes and link breaks are all outside the wordlist, so a text
+//! pasted straight from the chat decodes as well.
+//!
+//! ```
+//! use glossia::transport::{encode_for_platform, reassemble, Platform};
+//!
+//! let cover = "The *bold* plan is set.\n- two eggs";
+//! let messages = encode_for_platform(cover, Platform::Matrix);
+//! assert_eq!(messages, ["The \\*bold\\* plan is set.\n\\- two eggs"]);
+//! assert_eq!(reassemble(&messages).unwrap(), cover);
+//! ```
+
+use anyhow::{anyhow, bail, Result};
+use regex::Regex;
+use std::sync::OnceLock;
+
+/// Zero-width space, invisible in a message but enough to stop auto-linking
+pub(crate) const LINK_BREAK: char = '\u{200B}';
+
+/// Characters (and bytes) reserved for a chunk tag, `(9999/9999) `
+const TAG_RESERVE: usize = 12;
+
+/// Longest character-string in a DNS TXT record, in bytes
+pub const TXT_STRING_MAX: usize = 255;
+
+/// A messenger [`encode_for_platform`] adapts cover text to
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Platform {
+    Matrix,
+    Signal,
+    /// LoRa mesh and serial links, and the MQTT bridges in front of them
+    Mesh,
+    /// DNS TXT records, one message per record
+    DnsTxt,
+    /// Nostr text notes (kind 1)
+    Nostr,
+}
+
+impl Platform {
+    /// Every platform, in menu order
+    pub const ALL: &'static [Platform] = &[Platform::Matrix, Platform::Signal, Platform::Mesh, Platform::DnsTxt, Platform::Nostr];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Platform::Matrix => "matrix",
+            Platform::Signal => "signal",
+            Platform::Mesh => "mesh",
+            Platform::DnsTxt => "dns-txt",
+            Platform::Nostr => "nostr",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|platform| platform.name() == name)
+    }
+
+    /// What the platform does to a message.
+    pub fn profile(&self) -> Profile {
+        match self {
+            // Events are capped at 64 KiB of JSON, and clients send a
+            // formatted HTML body next to the plain one
+            Platform::Matrix => Profile { max_chars: usize::MAX, max_bytes_per_message: 30_000, markdown: true, unfurls_links: true, txt_record: false },
+            // Longer messages go out as a text attachment
+            Platform::Signal => Profile { max_chars: 2_000, max_bytes_per_message: usize::MAX, markdown: false, unfurls_links: true, txt_record: false },
+            // A LoRa frame holds about 250 bytes, less the mesh's own headers
+            Platform::Mesh => Profile { max_chars: usize::MAX, max_bytes_per_message: 200, markdown: false, unfurls_links: false, txt_record: false },
+            // Records of a few strings, which hosting panels accept and a
+            // 1232-byte EDNS response holds with room for the rest
+            Platform::DnsTxt => Profile { max_chars: usize::MAX, max_bytes_per_message: 1_000, markdown: false, unfurls_links: false, txt_record: true },
+            // Relays commonly refuse events over 64 KiB, and the note is a
+            // JSON string inside one, next to its tags and signature
+            Platform::Nostr => Profile { max_chars: usize::MAX, max_bytes_per_message: 32_000, markdown: false, unfurls_links: true, txt_record: false },
+        }
+    }
+}
+
+/// The message constraints of a platform
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// Longest message, in characters, tag included
+    pub max_chars: usize,
+    /// Longest message, in UTF-8 bytes, tag included
+    pub max_bytes_per_message: usize,
+    /// Whether typed Markdown is rendered
+    pub markdown: bool,
+    /// Whether domain-like text is linked and previewed
+    pub unfurls_links: bool,
+    /// Whether each message is written as a TXT record's quoted strings
+    /// ([`to_txt_record`]); the byte limit counts the unquoted text
+    pub txt_record: bool,
+}
+
+/// `text` adapted to `platform` as one or more messages, see [`adapt`].
+pub fn encode_for_platform(text: &str, platform: Platform) -> Vec<String> {
+    adapt(text, &platform.profile())
+}
+
+/// `text` with Markdown escaped (if `profile.markdown`), links broken (if
+/// `profile.unfurls_links`), and split at sentence or word boundaries into
+/// messages within `profile.max_chars` and `profile.max_bytes_per_message`,
+/// tagged when there are several, and written as TXT records if
+/// `profile.txt_record`.
+/// A single word longer than a message is split too.
+pub fn adapt(text: &str, profile: &Profile) -> Vec<String> {
+    let mut text = text.to_string();
+    if profile.markdown {
+        text = escape_markdown(&text);
+    }
+    if profile.unfurls_links {
+        text = break_links(&text);
+    }
+    let chunks = split(
+        &text,
+        profile.max_chars.saturating_sub(TAG_RESERVE).max(1),
+        profile.max_bytes_per_message.saturating_sub(TAG_RESERVE).max(1),
+    );
+    let total = chunks.len();
+    let messages = chunks
+        .into_iter()
+        .enumerate()
+        .map(|(i, chunk)| if total == 1 { chunk } else { format!("({}/{}) {}", i + 1, total, chunk) });
+    if profile.txt_record {
+        messages.map(|message| to_txt_record(&message)).collect()
+    } else {
+        messages.collect()
+    }
+}
+
+/// The text [`adapt`] split into `messages`, which may come in any order.
+/// Messages are joined with line breaks, which decoding reads as spaces.
+/// Messages in TXT record form (`"..." "..."`, with or without the owner
+/// name and type before them, as `dig` prints records) are read with
+/// [`from_txt_record`] first.
+pub fn reassemble<S: AsRef<str>>(messages: &[S]) -> Result<String> {
+    let messages = messages
+        .iter()
+        .map(|message| {
+            let message = message.as_ref();
+            if is_txt_record(message) { from_txt_record(message) } else { Ok(message.to_string()) }
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let mut chunks: Vec<(usize, &str)> = Vec::with_capacity(messages.len());
+    let mut total = None;
+    for message in &messages {
+        let message = message.as_str();
+        match tag_re().captures(message) {
+            Some(caps) => {
+                let (index, of): (usize, usize) = (caps[1].parse()?, caps[2].parse()?);
+                if *total.get_or_insert(of) != of || index == 0 || index > of {
+                    bail!("Message tag ({}/{}) does not fit the others", index, of);
+                }
+                chunks.push((index, &message[caps[0].len()..]));
+            }
+            None if messages.len() == 1 => chunks.push((1, message)),
+            None => bail!("Message {} of {} has no (i/n) tag", chunks.len() + 1, messages.len()),
+        }
+    }
+    chunks.sort_by_key(|(index, _)| *index);
+    if let Some(total) = total {
+        if chunks.len() != total || chunks.iter().enumerate().any(|(i, (index, _))| *index != i + 1) {
+            bail!("Expected messages 1 to {}, found {}", total, chunks.iter().map(|(index, _)| index.to_string()).collect::<Vec<_>>().join(", "));
+        }
+    }
+    let joined = chunks.iter().map(|(_, chunk)| *chunk).collect::<Vec<_>>().join("\n");
+    Ok(unescape_markdown(&joined).replace(LINK_BREAK, ""))
+}
+
+/// `text` as the presentation form of a TXT record: quoted strings of at
+/// most [`TXT_STRING_MAX`] bytes, each ending after a space where one fits,
+/// with `"` and `\` escaped and bytes outside printable ASCII as `\DDD`.
+pub fn to_txt_record(text: &str) -> String {
+    let mut strings = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let mut limit = rest.len().min(TXT_STRING_MAX);
+        while !rest.is_char_boundary(limit) {
+            limit -= 1;
+        }
+        if limit < rest.len() {
+            limit = rest[..limit].rfind(' ').map_or(limit, |i| i + 1);
+        }
+        let mut quoted = String::from('"');
+        for byte in rest[..limit].bytes() {
+            match byte {
+                b'"' | b'\\' => quoted.extend(['\\', byte as char]),
+                0x20..=0x7e => quoted.push(byte as char),
+                _ => quoted.push_str(&format!("\\{:03}", byte)),
+            }
+        }
+        quoted.push('"');
+        strings.push(quoted);
+        rest = &rest[limit..];
+    }
+    if strings.is_empty() {
+        strings.push("\"\"".to_string());
+    }
+    strings.join(" ")
+}
+
+/// The text of a TXT record: its quoted strings unescaped and concatenated.
+/// Anything before the first quote (`example.com. 300 IN TXT`) is skipped.
+pub fn from_txt_record(record: &str) -> Result<String> {
+    let start = record.find('"').ok_or_else(|| anyhow!("TXT record has no quoted string"))?;
+    let mut bytes = Vec::with_capacity(record.len());
+    let mut chars = record[start..].chars();
+    loop {
+        match chars.by_ref().find(|c| !c.is_whitespace()) {
+            None => break,
+            Some('"') => {}
+            Some(c) => bail!("Unexpected {:?} between TXT strings", c),
+        }
+        loop {
+            match chars.next() {
+                None => bail!("Unterminated TXT string"),
+                Some('"') => break,
+                Some('\\') => match chars.next() {
+                    Some(d) if d.is_ascii_digit() => {
+                        let digits: String = [Some(d), chars.next(), chars.next()].into_iter().flatten().collect();
+                        let byte = digits.parse::<u8>().ok().filter(|_| digits.len() == 3);
+                        bytes.push(byte.ok_or_else(|| anyhow!("Invalid TXT escape \\{}", digits))?);
+                    }
+                    Some(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+                    None => bail!("Unterminated TXT string"),
+                },
+                Some(c) => bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes()),
+            }
+        }
+    }
+    String::from_utf8(bytes).map_err(|_| anyhow!("TXT record is not UTF-8 text"))
+}
+
+/// Whether `message` is all quoted strings, or a record `dig` printed.
+pub(crate) fn is_txt_record(message: &str) -> bool {
+    let message = message.trim();
+    let before = &message[..message.find('"').unwrap_or(0)];
+    message.ends_with('"') && (before.is_empty() || before.split_whitespace().last() == Some("TXT"))
+}
+
+fn tag_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^\((\d{1,4})/(\d{1,4})\) ").expect("valid regex"))
+}
+
+/// Characters that are Markdown wherever they appear
+pub(crate) const INLINE_MARKDOWN: &[char] = &['\\', '*', '_', '`', '~', '[', ']', '<', '>', '|'];
+
+/// Backslash-escape inline Markdown, and line starts that would make a
+/// heading, quote or list.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let body = line.trim_start();
+        out.push_str(&line[..line.len() - body.len()]);
+        // Headings, list items and numbered list items ("1. ")
+        let digits = body.bytes().take_while(u8::is_ascii_digit).count();
+        if body.starts_with('#') || body.starts_with("- ") || body.starts_with("+ ") {
+            out.push('\\');
+        } else if digits > 0 && (body[digits..].starts_with(". ") || body[digits..].starts_with(") ")) {
+            out.push_str(&body[..digits]);
+            out.push('\\');
+            out.push_str(&body[digits..digits + 1]);
+            out.push_str(&escape_inline(&body[digits + 1..]));
+            continue;
+        }
+        out.push_str(&escape_inline(body));
+    }
+    out
+}
+
+fn escape_inline(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        if INLINE_MARKDOWN.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// Drop the backslashes [`escape_markdown`] added.
+fn unescape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c == '\\' && chars.peek().is_some_and(|next| INLINE_MARKDOWN.contains(next) || matches!(next, '#' | '-' | '+' | '.' | ')')) {
+            out.push(chars.next().expect("peeked"));
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Put a zero-width space after the `.` of anything a client would link
+/// (`example.com`, `www.`) and after the `:` of a URL scheme.
+fn break_links(text: &str) -> String {
+    link_re().replace_all(text, |caps: &regex::Captures| {
+        let link = &caps[0];
+        let at = link.rfind(['.', ':']).expect("matched a separator") + 1;
+        format!("{}{}{}", &link[..at], LINK_BREAK, &link[at..])
+    })
+    .into_owned()
+}
+
+/// Anything a client would link: a URL scheme or a domain-like name.
+pub(crate) fn link_re() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)\b(?:https?:|[a-z0-9-]+\.[a-z]{2,}\b)").expect("valid regex"))
+}
+
+/// `text` in pieces of at most `max_chars` and `max_bytes`, ending at the
+/// last sentence end that fits, else the last space, else mid-word (but never
+/// mid-character). The whitespace a piece ends at is dropped.
+fn split(text: &str, max_chars: usize, max_bytes: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut rest = text.trim();
+    loop {
+        let mut limit = rest.char_indices().nth(max_chars).map_or(rest.len(), |(i, _)| i).min(max_bytes);
+        if limit >= rest.len() {
+            break;
+        }
+        while !rest.is_char_boundary(limit) {
+            limit -= 1;
+        }
+        if limit == 0 {
+            limit = rest.chars().next().map_or(0, char::len_utf8);
+        }
+        let window = &rest[..limit];
+        let space = |i: usize| rest[i..].starts_with(char::is_whitespace);
+        let sentence_end = window
+            .char_indices()
+            .rev()
+            .find(|&(i, c)| matches!(c, '.' | '!' | '?') && space(i + 1))
+            .map(|(i, _)| i + 1);
+        let at = sentence_end
+            .or_else(|| window.rfind(char::is_whitespace).filter(|&i| i > 0))
+            .unwrap_or(limit);
+        chunks.push(rest[..at].trim_end().to_string());
+        rest = rest[at..].trim_start();
+    }
+    chunks.push(rest.to_string());
+    chunks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escapes_and_links() {
+        let text = "# Re: lunch\n1. the *cheap* one_two\n  - see example.com or https://x.org/a\nThe plan is set.";
+        let matrix = encode_for_platform(text, Platform::Matrix);
+        assert_eq!(matrix.len(), 1);
+        assert!(matrix[0].starts_with("\\# Re: lunch\n1\\. the \\*cheap\\* one\\_two\n  \\- see example."));
+        assert!(!matrix[0].contains("example.com") && !matrix[0].contains("https://"));
+        assert_eq!(reassemble(&matrix).unwrap(), text);
+
+        // Signal doesn't render Markdown, but does preview links
+        let signal = encode_for_platform(text, Platform::Signal);
+        assert!(signal[0].contains("*cheap*") && !signal[0].contains("x.org"));
+        assert_eq!(reassemble(&signal).unwrap(), text);
+    }
+
+    #[test]
+    fn test_splits_and_tags() {
+        let sentence = "The plan is set and the lake is calm. ";
+        let text = sentence.repeat(200);
+        let profile = Profile { max_chars: 500, max_bytes_per_message: usize::MAX, markdown: true, unfurls_links: true, txt_record: false };
+        let messages = adapt(&text, &profile);
+        assert!(messages.len() > 1);
+        for (i, message) in messages.iter().enumerate() {
+            assert!(message.chars().count() <= 500);
+            assert!(message.starts_with(&format!("({}/{}) The plan", i + 1, messages.len())), "{}", message);
+            assert!(message.ends_with("calm."));
+        }
+        let mut shuffled = messages.clone();
+        shuffled.reverse();
+        let joined = reassemble(&shuffled).unwrap();
+        assert_eq!(joined.split_whitespace().collect::<Vec<_>>(), text.split_whitespace().collect::<Vec<_>>());
+
+        assert!(reassemble(&messages[1..]).is_err());
+        assert!(reassemble(&[messages[0].as_str(), "untagged"]).is_err());
+        let long_word = "a".repeat(30);
+        assert_eq!(adapt(&long_word, &Profile { max_chars: 22, ..profile }), ["(1/3) aaaaaaaaaa", "(2/3) aaaaaaaaaa", "(3/3) aaaaaaaaaa"]);
+
+        // Mesh messages are counted in bytes, and never cut inside a character
+        let accented = "Le café est calme. ".repeat(40);
+        let messages = encode_for_platform(&accented, Platform::Mesh);
+        assert!(messages.len() > 3);
+        assert!(messages.iter().all(|message| message.len() <= 200 && message.ends_with("calme.")));
+        let word = "é".repeat(10);
+        let messages = adapt(&word, &Profile { max_bytes_per_message: 17, ..Platform::Mesh.profile() });
+        assert_eq!(messages, ["(1/5) éé", "(2/5) éé", "(3/5) éé", "(4/5) éé", "(5/5) éé"]);
+    }
+
+    #[test]
+    fn test_txt_records() {
+        let text = "The \"plan\" is set and the lake is calm.\nLe café est calme. ".repeat(40);
+        let records = encode_for_platform(&text, Platform::DnsTxt);
+        assert!(records.len() > 1);
+        for record in &records {
+            let strings = from_txt_record(record).unwrap();
+            assert!(strings.len() <= 1_000);
+            assert!(record.starts_with("\"(") && record.contains("\\\"plan\\\"") && record.contains("caf\\195\\169"));
+            // Every string fits, and ends after a space
+            for string in record.split("\" \"") {
+                let unquoted = from_txt_record(&format!("\"{}\"", string.trim_matches('"'))).unwrap();
+                assert!(unquoted.len() <= TXT_STRING_MAX);
+            }
+        }
+        let mut shuffled: Vec<String> = records.iter().rev().map(|record| format!("example.com. 300 IN TXT {}", record)).collect();
+        shuffled.swap(0, 1);
+        let joined = reassemble(&shuffled).unwrap();
+        assert_eq!(joined.split_whitespace().collect::<Vec<_>>(), text.split_whitespace().collect::<Vec<_>>());
+
+        assert_eq!(from_txt_record(r#""v=DKIM1; k=rsa; " "p=MIIB""#).unwrap(), "v=DKIM1; k=rsa; p=MIIB");
+        assert_eq!(to_txt_record(""), r#""""#);
+        assert!(from_txt_record(r#""open"#).is_err());
+        assert!(from_txt_record(r#""a" b "c""#).is_err());
+        assert_eq!(reassemble(&[r#"He said "hi""#]).unwrap(), r#"He said "hi""#);
+    }
+}
@@ -1,4 +1,4 @@
-#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum Pos {
     Det,
     Adj,
@@ -15,6 +15,38 @@ pub enum Pos {
     Prefix,
 }
 
+impl Pos {
+    /// Every tag, in declaration order
+    pub const ALL: [Pos; 13] = [
+        Pos::Det, Pos::Adj, Pos::N, Pos::V, Pos::Modal, Pos::Aux, Pos::Cop,
+        Pos::To, Pos::Prep, Pos::Adv, Pos::Conj, Pos::Dot, Pos::Prefix,
+    ];
+
+    /// The tag as written in `cover.yaml`/`payload.yaml` and the grammar files
+    pub fn name(&self) -> &'static str {
+        match self {
+            Pos::Det => "Det",
+            Pos::Adj => "Adj",
+            Pos::N => "N",
+            Pos::V => "V",
+            Pos::Modal => "Modal",
+            Pos::Aux => "Aux",
+            Pos::Cop => "Cop",
+            Pos::To => "To",
+            Pos::Prep => "Prep",
+            Pos::Adv => "Adv",
+            Pos::Conj => "Conj",
+            Pos::Dot => "Dot",
+            Pos::Prefix => "Prefix",
+        }
+    }
+
+    /// Parse a tag name (the inverse of [`Pos::name`])
+    pub fn from_name(name: &str) -> Option<Pos> {
+        Pos::ALL.into_iter().find(|pos| pos.name() == name)
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum Sym {
     NT(String),
@@ -0,0 +1,102 @@
+//! When a payload was made and when it stops being good.
+//!
+//! Invitation codes and one-time secrets have a validity window, and the
+//! cover text is often the only thing that travels. [`stamp`] puts the
+//! window in front of the payload (a flag byte, then the creation time and
+//! the expiry as unix-second varints); [`read_stamp`] takes it back off, and
+//! [`Validity::check`] refuses a payload past its expiry. Times are passed
+//! in rather than read from the clock, so the module works where there is
+//! none (WebAssembly).
+
+use crate::error::GlossiaError;
+use crate::framing::{read_varint, write_varint};
+use anyhow::{bail, Result};
+
+/// Flag bit of a stamp that carries an expiry; the other bits are zero
+const HAS_EXPIRY: u8 = 1;
+
+/// The validity window of a stamped payload, in unix seconds
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Validity {
+    /// When the payload was stamped
+    pub created: u64,
+    /// When it stops being good, if ever
+    pub expires: Option<u64>,
+}
+
+impl Validity {
+    /// Whether the payload has expired at `now`.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires.is_some_and(|expires| now >= expires)
+    }
+
+    /// Fail with [`GlossiaError::Expired`] if the payload has expired at `now`.
+    pub fn check(&self, now: u64) -> Result<()> {
+        match self.expires {
+            Some(expires) if self.is_expired(now) => Err(GlossiaError::Expired { expires, now }.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// `payload` behind a header holding `validity`.
+pub fn stamp(validity: &Validity, payload: &[u8]) -> Result<Vec<u8>> {
+    if validity.expires.is_some_and(|expires| expires <= validity.created) {
+        bail!("A payload cannot expire before it is made");
+    }
+    let mut out = vec![if validity.expires.is_some() { HAS_EXPIRY } else { 0 }];
+    write_varint(&mut out, validity.created);
+    if let Some(expires) = validity.expires {
+        write_varint(&mut out, expires);
+    }
+    out.extend_from_slice(payload);
+    Ok(out)
+}
+
+/// The validity window of a [`stamp`]ed payload, and the payload after it.
+pub fn read_stamp(stamped: &[u8]) -> Result<(Validity, &[u8])> {
+    let Some((&flags, rest)) = stamped.split_first() else {
+        bail!("Payload has no validity stamp");
+    };
+    if flags & !HAS_EXPIRY != 0 {
+        bail!("Unknown validity stamp flags {:#04x}", flags);
+    }
+    let (created, mut rest) = read_varint(rest)?;
+    let mut expires = None;
+    if flags & HAS_EXPIRY != 0 {
+        let (time, tail) = read_varint(rest)?;
+        expires = Some(time);
+        rest = tail;
+    }
+    Ok((Validity { created, expires }, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CREATED: u64 = 1_760_000_000;
+
+    #[test]
+    fn test_stamp_round_trip() {
+        for expires in [None, Some(CREATED + 7 * 86_400)] {
+            let validity = Validity { created: CREATED, expires };
+            let stamped = stamp(&validity, b"invite: 7F3K-92QD").unwrap();
+            assert_eq!(read_stamp(&stamped).unwrap(), (validity, b"invite: 7F3K-92QD".as_slice()));
+            // Flags, then five varint bytes per time
+            assert_eq!(stamped.len(), 1 + 5 * (1 + expires.is_some() as usize) + 17);
+        }
+        assert!(stamp(&Validity { created: CREATED, expires: Some(CREATED) }, b"").is_err());
+        assert!(read_stamp(&[2, 1]).is_err());
+        assert!(read_stamp(&[1, 1]).is_err());
+    }
+
+    #[test]
+    fn test_check_refuses_expired_payloads() {
+        let validity = Validity { created: CREATED, expires: Some(CREATED + 60) };
+        assert!(validity.check(CREATED + 59).is_ok());
+        let err = validity.check(CREATED + 60).unwrap_err();
+        assert_eq!(err.downcast_ref::<GlossiaError>().map(GlossiaError::code), Some("expired"));
+        assert!(Validity { created: CREATED, expires: None }.check(u64::MAX).is_ok());
+    }
+}
@@ -0,0 +1,217 @@
+//! JavaScript exports for wasm32 builds (feature `wasm`).
+//!
+//! Values cross the boundary as JSON strings, so the web UI can `JSON.parse`
+//! them without generated TypeScript types; payload bytes cross as
+//! `Uint8Array`s. Anything with state (a custom wordlist, loaded grammar
+//! models) lives in a [`GlossiaHandle`] rather than in the module, so a page
+//! and its Web Workers each build their own and nothing is shared by accident.
+//! A returned `Uint8Array` owns a fresh `ArrayBuffer`, which a worker can hand
+//! back with `postMessage(bytes, [bytes.buffer])` instead of copying it.
+
+use crate::config::GlossiaConfig;
+use crate::payload::{self, PayloadOptions};
+use crate::weights::{self, SlotMap};
+use crate::{capabilities, sniff};
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+
+fn js_error(e: anyhow::Error) -> JsError {
+    JsError::new(&format!("{:#}", e))
+}
+
+/// [`capabilities::capabilities`] as JSON, for building option menus.
+#[wasm_bindgen]
+pub fn capabilities() -> String {
+    capabilities::capabilities().to_json()
+}
+
+/// Check a TOML config ([`GlossiaConfig::from_toml`]) and return it as JSON,
+/// so the web UI applies the same defaults as the CLI.
+#[wasm_bindgen(js_name = parseConfig)]
+pub fn parse_config(toml: &str) -> Result<String, JsError> {
+    let config = GlossiaConfig::from_toml(toml).map_err(js_error)?;
+    serde_json::to_string(&config).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// What kind of text `input` looks like ([`sniff::sniff`]), as JSON
+/// `{"format": "hex", "decoded_len": 32}`, so the web UI can offer to encode
+/// hex or base64 as bytes.
+#[wasm_bindgen(js_name = detectFormat)]
+pub fn detect_format(input: &str) -> String {
+    serde_json::to_string(&sniff::sniff(input)).expect("format guess serializes")
+}
+
+#[derive(Default)]
+struct State {
+    /// The wordlist `setWordlist` or `setGrammar` gave, if either was called
+    wordlist: Option<Vec<String>>,
+    /// The checker `loadGrammarChecker` fetched, if it was called
+    #[cfg(feature = "grammar")]
+    checker: Option<std::sync::Arc<crate::GrammarChecker>>,
+}
+
+/// One nlprule suggestion, as [`GlossiaHandle::check_grammar`] returns it
+#[cfg(feature = "grammar")]
+#[derive(serde::Serialize)]
+struct Suggestion<'a> {
+    /// Id of the rule that fired (`grammar/agreement/1`, ...)
+    rule: &'a str,
+    message: &'a str,
+    /// Character (not byte) offsets of the flagged text
+    start: usize,
+    end: usize,
+    replacements: &'a [String],
+}
+
+/// A wordlist and grammar models, and the operations that use them:
+/// `new GlossiaHandle()` in each page or worker that needs one.
+#[wasm_bindgen]
+pub struct GlossiaHandle {
+    // Shared with the future `loadGrammarChecker` returns
+    state: Rc<RefCell<State>>,
+}
+
+impl GlossiaHandle {
+    /// Run `f` on the wordlist set from JavaScript, else the embedded one.
+    fn with_wordlist<T>(&self, f: impl FnOnce(&[String]) -> T) -> Result<T, JsError> {
+        match self.state.borrow().wordlist.as_deref() {
+            Some(words) => Ok(f(words)),
+            #[cfg(feature = "embedded-wordlist")]
+            None => Ok(f(crate::embedded::wordlist())),
+            #[cfg(not(feature = "embedded-wordlist"))]
+            None => Err(JsError::new("No wordlist: call setWordlist or setGrammar, or build with 'embedded-wordlist'")),
+        }
+    }
+}
+
+impl Default for GlossiaHandle {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[wasm_bindgen]
+impl GlossiaHandle {
+    /// A handle with the embedded wordlist (if compiled in) and no grammar
+    /// models loaded.
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> GlossiaHandle {
+        GlossiaHandle { state: Rc::default() }
+    }
+
+    /// Carrier words for `bytes` ([`payload::encode_payload`]) as a JSON
+    /// array. `options` is JSON [`PayloadOptions`] (`{"checksum": true}`);
+    /// omit it for none.
+    #[wasm_bindgen(js_name = encodePayload)]
+    pub fn encode_payload(&self, bytes: &[u8], options: Option<String>) -> Result<String, JsError> {
+        let options = parse_options(options.as_deref())?;
+        let words = self
+            .with_wordlist(|words| payload::encode_payload(&mut rand::thread_rng(), bytes, words, &options))?
+            .map_err(js_error)?;
+        serde_json::to_string(&words).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// The payload `text` carries ([`payload::decode_payload`]), with the
+    /// same `options` it was encoded with.
+    #[wasm_bindgen(js_name = decodePayload)]
+    pub fn decode_payload(&self, text: &str, options: Option<String>) -> Result<Vec<u8>, JsError> {
+        let options = parse_options(options.as_deref())?;
+        self.with_wordlist(|words| payload::decode_payload(text, words, &options))?.map_err(js_error)
+    }
+
+    /// Whether `text` appears to carry a payload ([`payload::detect`]), as
+    /// JSON with the [`payload::Detection`] fields and `likely`, so the web
+    /// UI can suggest decoding before the user asks.
+    #[wasm_bindgen(js_name = detectPayload)]
+    pub fn detect_payload(&self, text: &str) -> Result<String, JsError> {
+        let detection = self.with_wordlist(|words| payload::detect(text, words))?;
+        Ok(serde_json::json!({
+            "carrier_words": detection.carrier_words,
+            "total_words": detection.total_words,
+            "checksum_valid": detection.checksum_valid,
+            "likely": detection.is_likely(),
+        })
+        .to_string())
+    }
+
+    /// Use `words` as the payload wordlist, checked like the native loaders
+    /// ([`payload::wordlist_from_words`]): at least two distinct lowercase
+    /// words. Returns how many words the list has.
+    #[wasm_bindgen(js_name = setWordlist)]
+    pub fn set_wordlist(&self, words: Vec<String>) -> Result<usize, JsError> {
+        let words = payload::wordlist_from_words(words).map_err(js_error)?;
+        let len = words.len();
+        self.state.borrow_mut().wordlist = Some(words);
+        Ok(len)
+    }
+
+    /// Use the words of a `payload.yaml` weight document as the payload
+    /// wordlist, validated like `--wordlist` files ([`weights::parse`]: known
+    /// tags, no negative weights, distributions summing to 1). Returns JSON
+    /// `{"words": 2048, "slots": {"Adj": 412, ...}}`, the number of words
+    /// each grammar slot admits, so the page can show what the grammar makes
+    /// of it.
+    #[wasm_bindgen(js_name = setGrammar)]
+    pub fn set_grammar(&self, yaml: &str) -> Result<String, JsError> {
+        let pos_weights = weights::parse(yaml).map_err(|e| JsError::new(&format!("Invalid weight file: {}", e)))?;
+        let words = payload::wordlist_from_words(pos_weights.words().map(str::to_string).collect()).map_err(js_error)?;
+        let slots: BTreeMap<&str, usize> = SlotMap::new(&pos_weights, 0.0)
+            .by_slot()
+            .iter()
+            .map(|(pos, words)| (pos.name(), words.len()))
+            .collect();
+        let summary = serde_json::json!({ "words": words.len(), "slots": slots }).to_string();
+        self.state.borrow_mut().wordlist = Some(words);
+        Ok(summary)
+    }
+
+    /// Download the nlprule models from the given URLs (cached in IndexedDB,
+    /// see [`crate::GrammarChecker::from_fetch`]) for `checkGrammar` to use.
+    /// Resolves once they are loaded.
+    #[cfg(all(feature = "grammar", feature = "fetch"))]
+    #[wasm_bindgen(js_name = loadGrammarChecker)]
+    pub fn load_grammar_checker(&self, tokenizer_url: String, rules_url: String) -> js_sys::Promise {
+        let state = Rc::clone(&self.state);
+        wasm_bindgen_futures::future_to_promise(async move {
+            let checker = crate::GrammarChecker::from_fetch(&tokenizer_url, &rules_url).await.map_err(js_error)?;
+            state.borrow_mut().checker = Some(std::sync::Arc::new(checker));
+            Ok(JsValue::UNDEFINED)
+        })
+    }
+
+    /// The grammar suggestions for `text`, as a JSON array of `{rule,
+    /// message, start, end, replacements}` objects (empty if the text is
+    /// correct). Uses the models `loadGrammarChecker` fetched, else the
+    /// English models compiled in with `embedded-models`.
+    #[cfg(feature = "grammar")]
+    #[wasm_bindgen(js_name = checkGrammar)]
+    pub fn check_grammar(&self, text: &str) -> Result<String, JsError> {
+        let loaded = self.state.borrow().checker.clone();
+        let checker = match loaded {
+            Some(checker) => checker,
+            None => crate::GrammarChecker::global(crate::Language::English).map_err(js_error)?,
+        };
+        let suggestions = checker.check(text);
+        let out: Vec<Suggestion> = suggestions
+            .iter()
+            .map(|suggestion| Suggestion {
+                rule: suggestion.source(),
+                message: suggestion.message(),
+                start: suggestion.span().char().start,
+                end: suggestion.span().char().end,
+                replacements: suggestion.replacements(),
+            })
+            .collect();
+        serde_json::to_string(&out).map_err(|e| JsError::new(&e.to_string()))
+    }
+}
+
+/// JSON [`PayloadOptions`], or the defaults if none were passed.
+fn parse_options(json: Option<&str>) -> Result<PayloadOptions, JsError> {
+    match json {
+        Some(json) => serde_json::from_str(json).map_err(|e| JsError::new(&format!("Invalid payload options: {}", e))),
+        None => Ok(PayloadOptions::default()),
+    }
+}
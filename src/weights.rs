@@ -0,0 +1,360 @@
+//! Loading and validating POS weight files (`cover.yaml`, `payload.yaml`).
+//!
+//! A weight file maps each word to a distribution over POS tags:
+//!
+//! ```yaml
+//! light:
+//!   Adj: 0.4
+//!   N: 0.6
+//! ```
+//!
+//! [`load`] checks that every tag is a [`Pos`] name (or one of the
+//! [`UNSLOTTED_TAGS`] the tagger emits but the grammar has no slot for), that
+//! no weight is negative, and that each distribution sums to 1.0 within
+//! [`SUM_TOLERANCE`]. A word with an empty distribution (`word: {}`) is
+//! allowed: it is listed but fills no slot. Errors carry the 1-based line of
+//! the offending word or tag.
+//!
+//! [`SlotMap`] turns a validated wordlist into the words each grammar slot
+//! admits at encode time.
+
+use crate::types::Pos;
+use indexmap::IndexMap;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+/// Tags nlprule assigns that no grammar slot takes (pronouns, interjections).
+/// They count toward a word's distribution but never map to a [`Pos`].
+pub const UNSLOTTED_TAGS: &[&str] = &["Pron", "Intj"];
+
+/// How far a distribution may sum from 1.0 (weights are rounded to 3 places)
+pub const SUM_TOLERANCE: f64 = 0.01;
+
+/// A validated weight file, in file order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct PosWeights {
+    words: IndexMap<String, IndexMap<String, f64>>,
+}
+
+impl PosWeights {
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Words in file order
+    pub fn words(&self) -> impl Iterator<Item = &str> {
+        self.words.keys().map(String::as_str)
+    }
+
+    /// A word's distribution, tag name -> weight, in file order
+    pub fn get(&self, word: &str) -> Option<&IndexMap<String, f64>> {
+        self.words.get(word)
+    }
+
+    /// `(word, distribution)` pairs in file order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &IndexMap<String, f64>)> {
+        self.words.iter().map(|(word, tags)| (word.as_str(), tags))
+    }
+
+    /// The grammar slots a word may fill: its tags with a positive weight,
+    /// skipping [`UNSLOTTED_TAGS`].
+    pub fn pos_tags(&self, word: &str) -> Vec<Pos> {
+        self.pos_tags_above(word, 0.0)
+    }
+
+    /// Like [`PosWeights::pos_tags`], keeping only tags weighted above `threshold`.
+    pub fn pos_tags_above(&self, word: &str, threshold: f64) -> Vec<Pos> {
+        self.get(word)
+            .into_iter()
+            .flatten()
+            .filter(|(_, weight)| **weight > threshold)
+            .filter_map(|(tag, _)| Pos::from_name(tag))
+            .collect()
+    }
+
+    pub fn into_inner(self) -> IndexMap<String, IndexMap<String, f64>> {
+        self.words
+    }
+}
+
+/// Why a weight file was rejected. `line` counts from 1.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WeightError {
+    /// The file could not be read
+    Io { path: String, message: String },
+    /// The file is not a `word: {tag: weight}` mapping
+    Syntax { line: Option<usize>, message: String },
+    /// A tag is neither a [`Pos`] name nor in [`UNSLOTTED_TAGS`]
+    UnknownTag { line: usize, word: String, tag: String },
+    /// A weight is below zero
+    NegativeWeight { line: usize, word: String, tag: String, weight: f64 },
+    /// A non-empty distribution does not sum to 1.0
+    BadSum { line: usize, word: String, sum: f64 },
+}
+
+impl fmt::Display for WeightError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WeightError::Io { path, message } => write!(f, "Failed to read '{}': {}", path, message),
+            WeightError::Syntax { line: Some(line), message } => write!(f, "line {}: {}", line, message),
+            WeightError::Syntax { line: None, message } => write!(f, "{}", message),
+            WeightError::UnknownTag { line, word, tag } => {
+                write!(f, "line {}: '{}' has unknown POS tag '{}'", line, word, tag)
+            }
+            WeightError::NegativeWeight { line, word, tag, weight } => {
+                write!(f, "line {}: '{}' has negative weight {} for {}", line, word, weight, tag)
+            }
+            WeightError::BadSum { line, word, sum } => {
+                write!(f, "line {}: weights of '{}' sum to {:.3}, not 1.0", line, word, sum)
+            }
+        }
+    }
+}
+
+impl std::error::Error for WeightError {}
+
+/// Which wordlist words each grammar slot admits: a word fills a slot when
+/// its weight for that tag exceeds the threshold. Words are lowercased.
+#[derive(Clone, Debug, Default)]
+pub struct SlotMap {
+    threshold: f64,
+    by_slot: HashMap<Pos, Vec<String>>,
+    by_word: HashMap<String, Vec<Pos>>,
+}
+
+impl SlotMap {
+    /// Partition `weights` by slot. A threshold of 0 admits every tag with a
+    /// positive weight (the behaviour without a threshold).
+    pub fn new(weights: &PosWeights, threshold: f64) -> Self {
+        let mut map = SlotMap { threshold, ..Default::default() };
+        for word in weights.words() {
+            let slots = weights.pos_tags_above(word, threshold);
+            let lower = word.to_lowercase();
+            if lower.is_empty() || slots.is_empty() {
+                continue;
+            }
+            for slot in &slots {
+                map.by_slot.entry(*slot).or_default().push(lower.clone());
+            }
+            map.by_word.insert(lower, slots);
+        }
+        for words in map.by_slot.values_mut() {
+            words.sort();
+            words.dedup();
+        }
+        map
+    }
+
+    pub fn threshold(&self) -> f64 {
+        self.threshold
+    }
+
+    /// The slots `word` may fill (empty if the threshold excludes all of them)
+    pub fn slots(&self, word: &str) -> &[Pos] {
+        self.by_word.get(&word.to_lowercase()).map_or(&[], Vec::as_slice)
+    }
+
+    /// The words slot `pos` admits, sorted
+    pub fn words(&self, pos: Pos) -> &[String] {
+        self.by_slot.get(&pos).map_or(&[], Vec::as_slice)
+    }
+
+    /// Per-slot partition, for reporting
+    pub fn by_slot(&self) -> &HashMap<Pos, Vec<String>> {
+        &self.by_slot
+    }
+
+    /// Check the threshold keeps full density against `weights` (the file this
+    /// map was built from): every word still fills some slot, so any payload
+    /// encodes at one word per symbol, and no slot drops below `min_words`
+    /// (or below its unthresholded size, if that was already smaller).
+    pub fn validate(&self, weights: &PosWeights, min_words: usize) -> Result<(), SlotError> {
+        let unfiltered = SlotMap::new(weights, 0.0);
+        let mut stranded: Vec<String> = unfiltered
+            .by_word
+            .keys()
+            .filter(|word| !self.by_word.contains_key(*word))
+            .cloned()
+            .collect();
+        stranded.sort();
+        let mut starved: Vec<(Pos, usize)> = unfiltered
+            .by_slot
+            .keys()
+            .map(|slot| (*slot, self.words(*slot).len()))
+            .filter(|(slot, count)| *count < min_words.min(unfiltered.words(*slot).len()))
+            .collect();
+        starved.sort_by_key(|(slot, count)| (*count, slot.name()));
+        if stranded.is_empty() && starved.is_empty() {
+            Ok(())
+        } else {
+            Err(SlotError { threshold: self.threshold, stranded, starved, min_words })
+        }
+    }
+}
+
+/// A slot threshold that would cost density (see [`SlotMap::validate`]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct SlotError {
+    pub threshold: f64,
+    /// Words no slot admits any more, sorted
+    pub stranded: Vec<String>,
+    /// Slots left with fewer than `min_words` words, with their counts
+    pub starved: Vec<(Pos, usize)>,
+    pub min_words: usize,
+}
+
+impl fmt::Display for SlotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "slot threshold {} loses density:", self.threshold)?;
+        if !self.stranded.is_empty() {
+            let shown: Vec<&str> = self.stranded.iter().take(10).map(String::as_str).collect();
+            let more = self.stranded.len() - shown.len();
+            write!(f, " {} words fit no slot ({}", self.stranded.len(), shown.join(", "))?;
+            if more > 0 {
+                write!(f, ", ...")?;
+            }
+            write!(f, ")")?;
+        }
+        if !self.starved.is_empty() {
+            let slots: Vec<String> = self.starved.iter().map(|(pos, n)| format!("{} has {}", pos.name(), n)).collect();
+            write!(f, " slots below {} words ({})", self.min_words, slots.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SlotError {}
+
+/// Read and validate a weight file.
+pub fn load(path: impl AsRef<Path>) -> Result<PosWeights, WeightError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path).map_err(|e| WeightError::Io {
+        path: path.display().to_string(),
+        message: e.to_string(),
+    })?;
+    parse(&source).map_err(|err| match err {
+        WeightError::Syntax { line, message } => WeightError::Syntax {
+            line,
+            message: format!("{}: {}", path.display(), message),
+        },
+        other => other,
+    })
+}
+
+/// Validate a weight file already in memory.
+pub fn parse(source: &str) -> Result<PosWeights, WeightError> {
+    let words: IndexMap<String, IndexMap<String, f64>> = serde_yaml::from_str(source)
+        .map_err(|e| WeightError::Syntax {
+            line: e.location().map(|l| l.line()),
+            message: e.to_string(),
+        })?;
+
+    for (word, tags) in &words {
+        for (tag, weight) in tags {
+            if Pos::from_name(tag).is_none() && !UNSLOTTED_TAGS.contains(&tag.as_str()) {
+                return Err(WeightError::UnknownTag {
+                    line: line_of(source, word, Some(tag)),
+                    word: word.clone(),
+                    tag: tag.clone(),
+                });
+            }
+            if *weight < 0.0 {
+                return Err(WeightError::NegativeWeight {
+                    line: line_of(source, word, Some(tag)),
+                    word: word.clone(),
+                    tag: tag.clone(),
+                    weight: *weight,
+                });
+            }
+        }
+        let sum: f64 = tags.values().sum();
+        if !tags.is_empty() && ((sum - 1.0).abs() > SUM_TOLERANCE || sum.is_nan()) {
+            return Err(WeightError::BadSum { line: line_of(source, word, None), word: word.clone(), sum });
+        }
+    }
+    Ok(PosWeights { words })
+}
+
+/// Line of `word`'s key, or of `tag` within its block when given. Falls back
+/// to the word's line for flow-style blocks (`word: {N: 1.0}`).
+fn line_of(source: &str, word: &str, tag: Option<&str>) -> usize {
+    let key = |line: &str| {
+        line.split_once(':')
+            .map(|(key, _)| key.trim().trim_matches(|c| c == '"' || c == '\'').to_string())
+    };
+    let lines: Vec<&str> = source.lines().collect();
+    let Some(start) = lines.iter().position(|line| {
+        !line.starts_with([' ', '\t', '#']) && key(line).as_deref() == Some(word)
+    }) else {
+        return 1;
+    };
+    let Some(tag) = tag else {
+        return start + 1;
+    };
+    lines[start + 1..]
+        .iter()
+        .take_while(|line| line.trim().is_empty() || line.starts_with([' ', '\t', '#']))
+        .position(|line| key(line).as_deref() == Some(tag))
+        .map_or(start + 1, |offset| start + offset + 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_keeps_order_and_slots() {
+        let weights = parse("zebra:\n  N: 1.0\n# comment\napple:\n  N: 0.5\n  Pron: 0.5\nidle: {}\n").unwrap();
+        assert_eq!(weights.words().collect::<Vec<_>>(), vec!["zebra", "apple", "idle"]);
+        assert_eq!(weights.pos_tags("apple"), vec![Pos::N]);
+        assert!(weights.pos_tags("idle").is_empty());
+    }
+
+    #[test]
+    fn test_errors_report_lines() {
+        let err = parse("apple:\n  N: 0.5\n  Noun: 0.5\n").unwrap_err();
+        assert_eq!(err, WeightError::UnknownTag { line: 3, word: "apple".into(), tag: "Noun".into() });
+
+        let err = parse("apple:\n  N: 1.0\nbanana:\n  N: 1.5\n  V: -0.5\n").unwrap_err();
+        assert!(matches!(err, WeightError::NegativeWeight { line: 5, .. }), "{}", err);
+
+        let err = parse("apple: {N: 0.5, V: 0.2}\n").unwrap_err();
+        assert!(matches!(err, WeightError::BadSum { line: 1, .. }), "{}", err);
+        assert!(err.to_string().starts_with("line 1: weights of 'apple' sum to 0.700"));
+
+        assert!(matches!(parse("apple: [N]\n"), Err(WeightError::Syntax { line: Some(1), .. })));
+    }
+
+    #[test]
+    fn test_slot_map_threshold() {
+        let weights = parse("light:\n  N: 0.7\n  Adj: 0.3\nrun:\n  V: 0.9\n  N: 0.1\nsoft:\n  Adj: 1.0\n").unwrap();
+
+        let all = SlotMap::new(&weights, 0.0);
+        assert_eq!(all.words(Pos::N), ["light", "run"]);
+        assert!(all.validate(&weights, 1).is_ok());
+
+        let strict = SlotMap::new(&weights, 0.2);
+        assert_eq!(strict.words(Pos::N), ["light"]);
+        assert_eq!(strict.slots("RUN"), [Pos::V]);
+        assert!(strict.validate(&weights, 1).is_ok());
+        let err = strict.validate(&weights, 2).unwrap_err();
+        assert_eq!(err.starved, vec![(Pos::N, 1)]);
+
+        let err = SlotMap::new(&weights, 0.8).validate(&weights, 1).unwrap_err();
+        assert_eq!(err.stranded, vec!["light".to_string()]);
+        assert_eq!(err.starved, vec![(Pos::N, 0)]);
+    }
+
+    #[test]
+    fn test_shipped_weight_files_validate() {
+        for path in ["languages/english/cover.yaml", "languages/english/payload.yaml"] {
+            let weights = load(path).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            assert!(!weights.is_empty());
+        }
+    }
+}
@@ -0,0 +1,213 @@
+//! Payload whitening.
+//!
+//! Structured payloads (runs of zero bytes, repeated JSON keys) map to
+//! repeated carrier words, and repeated rare words stand out. Whitening XORs
+//! the bytes with a keystream before they are packed into words, so carrier
+//! words come out uniformly distributed whatever the payload looks like.
+//!
+//! The keystream is seeded by a per-message nonce of at least [`NONCE_BITS`]
+//! random bits, stored as the first [`nonce_words`] carrier words, and by a
+//! passphrase if one is given, stretched with the nonce as its salt. Without a passphrase anyone can undo the
+//! whitening; it only removes patterns.
+//!
+//! XOR alone decodes under any passphrase, to garbage under the wrong one.
+//! [`encode_committed`] adds a key-committing tag after the whitened bytes,
+//! so [`decode_committed`] refuses every passphrase but the one used, and no
+//! text can be crafted to decode under two.
+
+use crate::codec;
+use crate::error::GlossiaError;
+use crate::index::WordIndex;
+use crate::keys::{self, PassphraseKey, TAG_LEN};
+use crate::secret::Wipe;
+use anyhow::{bail, Result};
+use rand::Rng;
+
+const LABEL: &str = "whiten";
+const COMMIT_LABEL: &str = "whiten/commit";
+
+/// Random bits in a nonce. Two messages under one passphrase share a keystream
+/// only if their nonces collide, which takes about 2^32 messages.
+pub const NONCE_BITS: usize = 64;
+
+/// Number of words a nonce takes from a `wordlist_len`-word list: each word
+/// carries the whole bits of its index.
+pub fn nonce_words(wordlist_len: usize) -> usize {
+    let bits = (usize::BITS - 1 - wordlist_len.max(2).leading_zeros()) as usize;
+    NONCE_BITS.div_ceil(bits)
+}
+
+/// XOR `bytes` with the keystream for `nonce`. Applying it twice is the identity.
+pub fn apply_keystream(bytes: &[u8], nonce: &[usize], key: &PassphraseKey) -> Vec<u8> {
+    let mut stream = key.rng(LABEL, &keys::index_salt(nonce));
+    bytes.iter().map(|b| b ^ stream.gen::<u8>()).collect()
+}
+
+/// The key for a message with `nonce`: `passphrase` salted by the nonce, or
+/// [`PassphraseKey::unkeyed`] without one.
+pub fn nonce_key(passphrase: Option<&str>, nonce: &[usize]) -> PassphraseKey {
+    match passphrase {
+        Some(passphrase) => PassphraseKey::derive(passphrase, &keys::index_salt(nonce)),
+        None => PassphraseKey::unkeyed(),
+    }
+}
+
+/// Whiten `bytes` and encode them as nonce words followed by the packed words.
+/// `wordlist` must be in canonical sorted order.
+pub fn encode_whitened<R: Rng>(
+    rng: &mut R,
+    bytes: &[u8],
+    wordlist: &[String],
+    passphrase: Option<&str>,
+) -> Result<Vec<String>> {
+    encode_with(rng, bytes, wordlist, passphrase, false)
+}
+
+/// Like [`encode_whitened`], with a [`PassphraseKey::commit_tag`] over the
+/// nonce and the whitened bytes packed after them ([`TAG_LEN`] more bytes).
+pub fn encode_committed<R: Rng>(
+    rng: &mut R,
+    bytes: &[u8],
+    wordlist: &[String],
+    passphrase: Option<&str>,
+) -> Result<Vec<String>> {
+    encode_with(rng, bytes, wordlist, passphrase, true)
+}
+
+fn encode_with<R: Rng>(
+    rng: &mut R,
+    bytes: &[u8],
+    wordlist: &[String],
+    passphrase: Option<&str>,
+    commit: bool,
+) -> Result<Vec<String>> {
+    if wordlist.len() < 2 {
+        bail!("Wordlist needs at least 2 words to hold a nonce");
+    }
+    let usable = 1 << (usize::BITS - 1 - wordlist.len().leading_zeros());
+    let nonce: Vec<usize> = (0..nonce_words(wordlist.len())).map(|_| rng.gen_range(0..usable)).collect();
+    let key = nonce_key(passphrase, &nonce);
+    let mut whitened = apply_keystream(bytes, &nonce, &key);
+    if commit {
+        let tag = key.commit_tag(COMMIT_LABEL, &tagged(&nonce, &whitened));
+        whitened.extend_from_slice(&tag);
+    }
+    let words = codec::encode_bytes(&whitened, wordlist);
+    whitened.wipe();
+    let mut out: Vec<String> = nonce.iter().map(|&i| wordlist[i].clone()).collect();
+    out.extend(words?);
+    Ok(out)
+}
+
+/// Invert [`encode_whitened`].
+pub fn decode_whitened(words: &[String], wordlist: &[String], passphrase: Option<&str>) -> Result<Vec<u8>> {
+    let (nonce, mut whitened) = split_nonce(words, wordlist)?;
+    let bytes = apply_keystream(&whitened, &nonce, &nonce_key(passphrase, &nonce));
+    whitened.wipe();
+    Ok(bytes)
+}
+
+/// Invert [`encode_committed`]. A wrong passphrase, or a tampered text, fails
+/// with [`GlossiaError::WrongPassphrase`].
+pub fn decode_committed(words: &[String], wordlist: &[String], passphrase: Option<&str>) -> Result<Vec<u8>> {
+    let (nonce, mut whitened) = split_nonce(words, wordlist)?;
+    let Some(body_len) = whitened.len().checked_sub(TAG_LEN) else {
+        whitened.wipe();
+        bail!("Payload is too short to hold a key-committing tag");
+    };
+    let tag = whitened.split_off(body_len);
+    let key = nonce_key(passphrase, &nonce);
+    if !key.verify_tag(COMMIT_LABEL, &tagged(&nonce, &whitened), &tag) {
+        whitened.wipe();
+        return Err(GlossiaError::WrongPassphrase.into());
+    }
+    let bytes = apply_keystream(&whitened, &nonce, &key);
+    whitened.wipe();
+    Ok(bytes)
+}
+
+/// The nonce a whitened carrier starts with, and the bytes after it.
+fn split_nonce(words: &[String], wordlist: &[String]) -> Result<(Vec<usize>, Vec<u8>)> {
+    let count = nonce_words(wordlist.len());
+    if words.len() < count {
+        bail!("Too few words to hold a whitening nonce ({} needed)", count);
+    }
+    let (head, body) = words.split_at(count);
+    let index = WordIndex::shared(wordlist)?;
+    let nonce = head
+        .iter()
+        .enumerate()
+        .map(|(position, word)| index.get(&word.to_lowercase()).ok_or_else(|| codec::unknown_word(word, position).into()))
+        .collect::<Result<Vec<_>>>()?;
+    Ok((nonce, codec::decode_bytes(body, wordlist)?))
+}
+
+/// What a commit tag covers: the nonce, then the whitened bytes.
+fn tagged(nonce: &[usize], whitened: &[u8]) -> Vec<u8> {
+    [keys::index_salt(nonce).as_slice(), whitened].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::alpha_wordlist;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_roundtrip_keyed_and_unkeyed() {
+        let list = alpha_wordlist();
+        let mut rng = StdRng::seed_from_u64(1);
+        for payload in [&b""[..], b"a", b"{\"k\":0,\"k\":0}", &[0u8; 40]] {
+            for passphrase in [Some("pass"), None] {
+                let words = encode_whitened(&mut rng, payload, &list, passphrase).unwrap();
+                assert_eq!(decode_whitened(&words, &list, passphrase).unwrap(), payload);
+            }
+        }
+        let words = encode_whitened(&mut rng, b"secret", &list, Some("pass")).unwrap();
+        assert_ne!(decode_whitened(&words, &list, None).unwrap(), b"secret");
+    }
+
+    #[test]
+    fn test_committed_payload_refuses_other_passphrases() {
+        let list = alpha_wordlist();
+        let mut rng = StdRng::seed_from_u64(3);
+        let key = Some("pass");
+        let words = encode_committed(&mut rng, b"meet at the north gate", &list, key).unwrap();
+        assert_eq!(decode_committed(&words, &list, key).unwrap(), b"meet at the north gate");
+        // The plain decoder reads it under any key, tag and all
+        assert!(decode_whitened(&words, &list, Some("guess")).is_ok());
+        let err = decode_committed(&words, &list, Some("guess")).unwrap_err();
+        assert_eq!(err.downcast_ref::<GlossiaError>(), Some(&GlossiaError::WrongPassphrase));
+
+        let mut tampered = words.clone();
+        tampered[3] = if tampered[3] == list[0] { list[1].clone() } else { list[0].clone() };
+        assert!(decode_committed(&tampered, &list, key).is_err());
+        assert!(decode_committed(&words[..5], &list, key).is_err());
+    }
+
+    #[test]
+    fn test_nonce_carries_at_least_64_bits() {
+        assert_eq!(nonce_words(2048), 6);
+        assert_eq!(nonce_words(256), 8);
+        assert_eq!(nonce_words(3000), 6);
+        let list = alpha_wordlist();
+        let mut rng = StdRng::seed_from_u64(4);
+        let words = encode_whitened(&mut rng, b"abc", &list, None).unwrap();
+        assert_eq!(words.len(), nonce_words(list.len()) + codec::encode_bytes(b"abc", &list).unwrap().len());
+        assert!(decode_whitened(&words[..5], &list, None).is_err());
+    }
+
+    #[test]
+    fn test_structured_payload_spreads_over_wordlist() {
+        let list = alpha_wordlist();
+        let zeros = [0u8; 110];
+        let plain: HashSet<String> = codec::encode_bytes(&zeros, &list).unwrap().into_iter().collect();
+        assert!(plain.len() <= 2);
+
+        let words = encode_whitened(&mut StdRng::seed_from_u64(2), &zeros, &list, None).unwrap();
+        let distinct: HashSet<&String> = words.iter().collect();
+        assert!(distinct.len() > words.len() * 9 / 10);
+    }
+}
@@ -0,0 +1,48 @@
+//! The shell round trip the help text advertises:
+//! `glossia --from-ascii - --mode binary < key.bin | glossia --decode-ascii - --mode binary > key.out`
+//! gives back the same bytes, whichever grammar carries them.
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Run glossia with `args`, feeding `input` on stdin, and return its stdout.
+fn glossia(args: &[&str], input: &[u8]) -> Vec<u8> {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_glossia"))
+        .args(args)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("glossia runs");
+    child.stdin.take().unwrap().write_all(input).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success(), "glossia {:?} failed: {}", args, String::from_utf8_lossy(&output.stderr));
+    output.stdout
+}
+
+/// Encode `bytes` through a pipe and decode them back.
+fn round_trip(bytes: &[u8], grammar: &str) -> Vec<u8> {
+    let text = glossia(&["--from-ascii", "-", "--mode", "binary", "--grammar", grammar], bytes);
+    glossia(&["--decode-ascii", "-", "--mode", "binary"], &text)
+}
+
+#[test]
+fn test_random_bytes_round_trip_through_a_pipe() {
+    let mut rng = StdRng::seed_from_u64(886);
+    for grammar in ["body", "subject"] {
+        let mut bytes = vec![0u8; 300];
+        rng.fill_bytes(&mut bytes);
+        assert_eq!(round_trip(&bytes, grammar), bytes, "{} grammar", grammar);
+    }
+}
+
+#[test]
+fn test_trailing_zero_bytes_round_trip_through_a_pipe() {
+    let payloads: [&[u8]; 5] = [b"ABC\0", b"ABC\0\0", b"ABC\0\0\0", &[0; 4], &[0; 11]];
+    for bytes in payloads {
+        assert_eq!(round_trip(bytes, "body"), bytes, "{:02x?}", bytes);
+    }
+}
@@ -0,0 +1,15 @@
+//! Golden test: every fixed-seed encoding in `tests/vectors.json` is still
+//! produced byte for byte. After an intended output change, regenerate the
+//! corpus with `cargo run --bin gen_vectors -- -o tests/vectors.json`.
+
+use std::process::Command;
+
+#[test]
+fn test_vectors_are_current() {
+    let status = Command::new(env!("CARGO_BIN_EXE_gen_vectors"))
+        .args(["--check", "tests/vectors.json", "--glossia", env!("CARGO_BIN_EXE_glossia")])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .status()
+        .expect("gen_vectors runs");
+    assert!(status.success(), "tests/vectors.json is out of date (see the vectors listed above)");
+}
@@ -0,0 +1,38 @@
+//! The WASM exports inside a dedicated Web Worker, the way a page offloads
+//! encoding: `wasm-pack test --headless --firefox -- --no-default-features --features wasm-slim`.
+#![cfg(all(target_arch = "wasm32", feature = "wasm"))]
+
+#[path = "../src/test_util.rs"]
+mod test_util;
+
+use glossia::wasm::GlossiaHandle;
+use test_util::alpha_wordlist;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test_configure!(run_in_dedicated_worker);
+
+#[wasm_bindgen_test]
+fn test_worker_round_trip() {
+    let handle = GlossiaHandle::new();
+    assert_eq!(handle.set_wordlist(alpha_wordlist()).unwrap(), 2048);
+    let options = Some(r#"{"checksum": true}"#.to_string());
+    let words: Vec<String> = serde_json::from_str(&handle.encode_payload(b"Meet at noon", options.clone()).unwrap()).unwrap();
+    let text = words.iter().map(|w| format!("The {} said.", w)).collect::<Vec<_>>().join(" ");
+
+    let detection: serde_json::Value = serde_json::from_str(&handle.detect_payload(&text).unwrap()).unwrap();
+    assert_eq!(detection["checksum_valid"], true);
+    assert_eq!(handle.decode_payload(&text, options).unwrap(), b"Meet at noon");
+}
+
+#[wasm_bindgen_test]
+fn test_handles_are_independent() {
+    let custom = GlossiaHandle::new();
+    custom.set_wordlist(alpha_wordlist()).unwrap();
+    let other = GlossiaHandle::new();
+    let text = "The wbaa said.";
+    let carriers = |handle: &GlossiaHandle| {
+        serde_json::from_str::<serde_json::Value>(&handle.detect_payload(text).unwrap()).unwrap()["carrier_words"].clone()
+    };
+    assert_eq!(carriers(&custom), 1);
+    assert_eq!(carriers(&other), 0);
+}